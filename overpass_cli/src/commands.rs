@@ -0,0 +1,319 @@
+// src/commands.rs
+
+use anyhow::{anyhow, bail, Context};
+use bitcoin::Network;
+use clap::Subcommand;
+use overpass_core::bitcoin::root_anchor::AnchorPayload;
+use overpass_core::services::rpc_server::RpcServer;
+use overpass_core::zkp::helpers::Bytes32;
+use overpass_core::zkp::pedersen_parameters::PedersenParameters;
+use overpass_core::zkp::proof_of_funds::FundsProof;
+use overpass_core::zkp::wallet_lock::KdfParams;
+use serde_json::{json, Value};
+use std::path::PathBuf;
+
+use crate::local::LocalStore;
+use crate::remote::RemoteClient;
+
+/// RPC methods that change the wallet, after which local storage is saved.
+const MUTATING: &[&str] = &["open_channel", "close_channel", "pay"];
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Create or inspect the wallet.
+    #[command(subcommand)]
+    Wallet(WalletCommand),
+    /// Open, close and inspect channels.
+    #[command(subcommand)]
+    Channel(ChannelCommand),
+    /// Pay a counterparty over one of its channels.
+    Pay {
+        #[arg(long)]
+        to: String,
+        #[arg(long)]
+        amount: u64,
+        #[arg(long)]
+        memo: Option<String>,
+        #[arg(long)]
+        routing_fee: Option<u64>,
+    },
+    /// Verify a channel's stored history and summarize it.
+    History { channel_id: String },
+    /// Prove the wallet holds at least `minimum` to a verifier.
+    Prove {
+        #[arg(long)]
+        minimum: u64,
+        /// Hex-encoded challenge from the verifier.
+        #[arg(long, default_value = "")]
+        context: String,
+        /// Write the proof here instead of printing it.
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Check a proof of funds against anchored global roots. Needs no wallet.
+    Verify {
+        proof: PathBuf,
+        /// Hex-encoded global root seen anchored on Bitcoin; may be repeated.
+        #[arg(long = "root", required = true)]
+        roots: Vec<String>,
+        #[arg(long, default_value = "")]
+        context: String,
+    },
+    /// Publish the wallet root and print the OP_RETURN output that anchors it.
+    Anchor,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum WalletCommand {
+    /// Create a wallet in the data directory and print its backup phrase.
+    Create {
+        #[arg(long, default_value_t = 12)]
+        words: usize,
+    },
+    /// Show the balance and open channels.
+    Show,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ChannelCommand {
+    Open {
+        #[arg(long)]
+        counterparty: String,
+        #[arg(long)]
+        balance: u64,
+    },
+    Close {
+        channel_id: String,
+    },
+    Show {
+        channel_id: String,
+    },
+    List,
+}
+
+/// Where wallet commands are carried out.
+pub enum Backend {
+    Local { store: LocalStore, network: Network },
+    Remote(RemoteClient),
+}
+
+impl Backend {
+    fn local(&self, command: &str) -> anyhow::Result<(&LocalStore, Network)> {
+        match self {
+            Backend::Local { store, network } => Ok((store, *network)),
+            Backend::Remote(_) => bail!("`{}` needs local storage, not a remote daemon", command),
+        }
+    }
+
+    /// Runs a wallet RPC method, in-process against local storage or on the daemon, so
+    /// both answer in the same JSON.
+    pub fn call(&self, method: &str, params: Value) -> anyhow::Result<Value> {
+        let store = match self {
+            Backend::Local { store, .. } => store,
+            Backend::Remote(client) => return client.call(method, params),
+        };
+        let (wallet, meta) = store.load()?;
+        let server = RpcServer::new(wallet);
+        let result = server
+            .call(method, params)
+            .map_err(|error| anyhow!("{} (code {})", error.message, error.code))?;
+        if MUTATING.contains(&method) {
+            store.save(&mut server.into_wallet(), meta)?;
+        }
+        Ok(result)
+    }
+}
+
+fn bytes32(name: &str, value: &str) -> anyhow::Result<Bytes32> {
+    hex::decode(value)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .with_context(|| format!("{} must be 32 hex-encoded bytes", name))
+}
+
+pub fn run(command: Command, backend: &Backend, kdf: KdfParams) -> anyhow::Result<Value> {
+    match command {
+        Command::Wallet(WalletCommand::Create { words }) => {
+            let (store, network) = backend.local("wallet create")?;
+            let (meta, mnemonic) = store.create(network, words, kdf)?;
+            Ok(json!({
+                "wallet_id": hex::encode(meta.wallet_id),
+                "network": meta.network,
+                "mnemonic": mnemonic,
+            }))
+        }
+        Command::Wallet(WalletCommand::Show) => Ok(json!({
+            "balance": backend.call("get_balance", Value::Null)?,
+            "channels": backend.call("list_channels", Value::Null)?,
+        })),
+        Command::Channel(ChannelCommand::Open {
+            counterparty,
+            balance,
+        }) => backend.call(
+            "open_channel",
+            json!({ "counterparty": counterparty, "balance": balance }),
+        ),
+        Command::Channel(ChannelCommand::Close { channel_id }) => {
+            backend.call("close_channel", json!({ "channel_id": channel_id }))
+        }
+        Command::Channel(ChannelCommand::Show { channel_id }) => {
+            backend.call("get_channel", json!({ "channel_id": channel_id }))
+        }
+        Command::Channel(ChannelCommand::List) => backend.call("list_channels", Value::Null),
+        Command::Pay {
+            to,
+            amount,
+            memo,
+            routing_fee,
+        } => backend.call(
+            "pay",
+            json!({ "counterparty": to, "amount": amount, "memo": memo, "routing_fee": routing_fee }),
+        ),
+        Command::History { channel_id } => {
+            backend.call("channel_history", json!({ "channel_id": channel_id }))
+        }
+        Command::Prove {
+            minimum,
+            context,
+            out,
+        } => {
+            let proof = backend.call(
+                "proof_of_funds",
+                json!({ "minimum": minimum, "context": context }),
+            )?;
+            match out {
+                Some(path) => {
+                    std::fs::write(&path, serde_json::to_vec_pretty(&proof)?)
+                        .with_context(|| format!("Failed to write {}", path.display()))?;
+                    Ok(json!({ "written": path }))
+                }
+                None => Ok(proof),
+            }
+        }
+        Command::Verify {
+            proof,
+            roots,
+            context,
+        } => {
+            let bytes = std::fs::read(&proof)
+                .with_context(|| format!("Failed to read {}", proof.display()))?;
+            let proof: FundsProof = serde_json::from_slice(&bytes).context("Malformed proof")?;
+            let roots = roots
+                .iter()
+                .map(|root| bytes32("root", root))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            let context = hex::decode(&context).context("context must be hex-encoded")?;
+            proof.verify(&PedersenParameters::default(), &roots, &context)?;
+            Ok(json!({
+                "wallet_id": hex::encode(proof.wallet_id()),
+                "minimum": proof.minimum,
+                "global_root": hex::encode(proof.global_root),
+            }))
+        }
+        Command::Anchor => {
+            let (store, _) = backend.local("anchor")?;
+            let (mut wallet, meta) = store.load()?;
+            let meta = store.save(&mut wallet, meta)?;
+            // The publishing height doubles as the anchor epoch, so the two never diverge.
+            let payload = AnchorPayload {
+                epoch: meta.height as u64,
+                root: wallet.global_contract.get_global_merkle_root(),
+            };
+            Ok(json!({
+                "epoch": payload.epoch,
+                "global_root": hex::encode(payload.root),
+                "script_pubkey": hex::encode(payload.script_pubkey().as_bytes()),
+            }))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cheap_kdf() -> KdfParams {
+        KdfParams {
+            memory_kib: 64,
+            iterations: 1,
+            parallelism: 1,
+        }
+    }
+
+    #[test]
+    fn test_local_wallet_lifecycle() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join(format!("overpass_cli_{}", std::process::id()));
+        let backend = Backend::Local {
+            store: LocalStore::open(&dir, "hunter2".into())?,
+            network: Network::Regtest,
+        };
+        let run = |command| run(command, &backend, cheap_kdf());
+
+        let created = run(Command::Wallet(WalletCommand::Create { words: 12 }))?;
+        assert_eq!(created["mnemonic"].as_str().unwrap().split(' ').count(), 12);
+        assert!(run(Command::Wallet(WalletCommand::Create { words: 12 })).is_err());
+
+        let opened = run(Command::Channel(ChannelCommand::Open {
+            counterparty: hex::encode([7u8; 32]),
+            balance: 100,
+        }))?;
+        let channel_id = opened["channel_id"].as_str().unwrap().to_string();
+        // Each command reloads the wallet from storage.
+        let shown = run(Command::Wallet(WalletCommand::Show))?;
+        assert_eq!(shown["balance"]["open"], 100);
+        assert_eq!(shown["channels"], json!([channel_id]));
+        let anchor = run(Command::Anchor)?;
+        assert!(anchor["script_pubkey"].as_str().unwrap().starts_with("6a"));
+
+        let proof_path = dir.join("proof.json");
+        run(Command::Prove {
+            minimum: 60,
+            context: "0102".into(),
+            out: Some(proof_path.clone()),
+        })?;
+        let verified = run(Command::Verify {
+            proof: proof_path.clone(),
+            roots: vec![anchor["global_root"].as_str().unwrap().into()],
+            context: "0102".into(),
+        })?;
+        assert_eq!(verified["minimum"], 60);
+        assert_eq!(verified["wallet_id"], created["wallet_id"]);
+        assert!(run(Command::Verify {
+            proof: proof_path,
+            roots: vec![hex::encode([0u8; 32])],
+            context: "0102".into(),
+        })
+        .is_err());
+
+        run(Command::Channel(ChannelCommand::Close {
+            channel_id: channel_id.clone(),
+        }))?;
+        assert_eq!(run(Command::Channel(ChannelCommand::List))?, json!([]));
+        drop(backend);
+
+        let wrong = Backend::Local {
+            store: LocalStore::open(&dir, "hunter3".into())?,
+            network: Network::Regtest,
+        };
+        let error = wrong.call("list_channels", Value::Null).unwrap_err();
+        assert!(error.to_string().contains("passphrase"));
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_remote_backend_refuses_local_commands() {
+        let backend = Backend::Remote(RemoteClient::new(
+            "http://127.0.0.1:1",
+            overpass_core::services::server::AuthToken::generate(),
+        ));
+        let error = run(Command::Anchor, &backend, cheap_kdf()).unwrap_err();
+        assert!(error.to_string().contains("needs local storage"));
+        assert!(run(
+            Command::Channel(ChannelCommand::List),
+            &backend,
+            cheap_kdf()
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,70 @@
+// src/config.rs
+
+use anyhow::Context;
+use bitcoin::Network;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// Config file read from the working directory when none is named.
+pub const DEFAULT_CONFIG: &str = "overpass.toml";
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Directory holding the local wallet's database.
+    pub data_dir: PathBuf,
+    pub network: Network,
+    /// JSON-RPC endpoint of a daemon to use instead of local storage.
+    pub remote: Option<String>,
+    /// File holding the daemon's auth token; `.cookie` in the data directory if unset.
+    pub cookie: Option<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            data_dir: PathBuf::from("overpass-data"),
+            network: Network::Regtest,
+            remote: None,
+            cookie: None,
+        }
+    }
+}
+
+impl Config {
+    /// Reads the config at `path`, or at `DEFAULT_CONFIG` if it exists, or the defaults.
+    pub fn load(path: Option<&Path>) -> anyhow::Result<Self> {
+        let path = match path {
+            Some(path) => path,
+            None if Path::new(DEFAULT_CONFIG).exists() => Path::new(DEFAULT_CONFIG),
+            None => return Ok(Self::default()),
+        };
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("Invalid config {}", path.display()))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let config = Config::parse(
+            "data_dir = \"/var/lib/overpass\"\nnetwork = \"testnet\"\nremote = \"http://127.0.0.1:7070\"\ncookie = \"/run/overpass/.cookie\"\n",
+        )
+        .unwrap();
+        assert_eq!(config.data_dir, PathBuf::from("/var/lib/overpass"));
+        assert_eq!(config.network, Network::Testnet);
+        assert_eq!(config.remote.as_deref(), Some("http://127.0.0.1:7070"));
+        assert_eq!(config.cookie, Some(PathBuf::from("/run/overpass/.cookie")));
+
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+        assert!(Config::parse("netwrok = \"signet\"").is_err());
+    }
+}
@@ -0,0 +1,147 @@
+// src/local.rs
+
+use anyhow::{bail, Context};
+use bitcoin::Network;
+use overpass_core::bitcoin::keys::KeyManager;
+use overpass_core::services::overpass_db::OverpassDB;
+use overpass_core::zkp::channel_backup::ChannelBackup;
+use overpass_core::zkp::global_root_contract::GlobalRootContract;
+use overpass_core::zkp::helpers::Bytes32;
+use overpass_core::zkp::pedersen_parameters::PedersenParameters;
+use overpass_core::zkp::wallet_audit::AuditLog;
+use overpass_core::zkp::wallet_contract::WalletContract;
+use overpass_core::zkp::wallet_lock::{KdfParams, SealedKeys};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+
+const META_KEY: &[u8] = b"cli_wallet";
+const BACKUPS_KEY: &[u8] = b"cli_channel_backups";
+
+/// What the CLI keeps about its wallet next to the core's own records.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WalletMeta {
+    pub wallet_id: Bytes32,
+    pub network: Network,
+    /// Height the wallet root was last published to the local global contract at.
+    pub height: u32,
+}
+
+/// A wallet kept in a local database, its seed sealed under a passphrase.
+///
+/// Between runs the wallet lives as its audit log and a backup of every open channel,
+/// proven under the saved global contract, and is rebuilt with `WalletContract::recover`.
+pub struct LocalStore {
+    db: OverpassDB,
+    passphrase: String,
+}
+
+impl LocalStore {
+    pub fn open(data_dir: &Path, passphrase: String) -> anyhow::Result<Self> {
+        let path = data_dir
+            .to_str()
+            .context("Data directory must be valid UTF-8")?;
+        Ok(Self {
+            db: OverpassDB::new(path)?,
+            passphrase,
+        })
+    }
+
+    fn meta(&self) -> anyhow::Result<Option<WalletMeta>> {
+        self.db
+            .get(META_KEY)?
+            .map(|bytes| serde_json::from_slice(&bytes).context("Corrupt wallet record"))
+            .transpose()
+    }
+
+    /// Creates a wallet from a fresh mnemonic, which is returned for the user to write down.
+    pub fn create(
+        &self,
+        network: Network,
+        words: usize,
+        kdf: KdfParams,
+    ) -> anyhow::Result<(WalletMeta, String)> {
+        if self.meta()?.is_some() {
+            bail!("A wallet already exists in this data directory");
+        }
+        let keys = KeyManager::generate(network, words, "")?;
+        let mnemonic = keys.mnemonic()?.to_string();
+        let mut wallet_id = [0u8; 32];
+        rand::rngs::OsRng.fill_bytes(&mut wallet_id);
+        SealedKeys::seal(&keys, &self.passphrase, kdf)?.save(&self.db, &wallet_id)?;
+
+        let params = PedersenParameters::default();
+        // Only this wallet submits to the local contract, so there is no one to wait for
+        // a challenge from.
+        let global = GlobalRootContract::new(params.clone()).with_challenge_window(0);
+        let mut wallet =
+            WalletContract::new(wallet_id, params, global).with_key_manager(Arc::new(keys));
+        let meta = WalletMeta {
+            wallet_id,
+            network,
+            height: 0,
+        };
+        Ok((self.save(&mut wallet, meta)?, mnemonic))
+    }
+
+    /// Rebuilds the saved wallet, unlocked with the store's passphrase.
+    pub fn load(&self) -> anyhow::Result<(WalletContract, WalletMeta)> {
+        let meta = self
+            .meta()?
+            .context("No wallet in this data directory; run `overpass wallet create`")?;
+        let keys = SealedKeys::restore(&self.db, &meta.wallet_id)?
+            .context("The wallet's sealed keys are missing")?
+            .open(&self.passphrase)?;
+        let global = GlobalRootContract::restore(&self.db, PedersenParameters::default())?
+            .context("The global contract is missing")?;
+        let backups: Vec<ChannelBackup> = match self.db.get(BACKUPS_KEY)? {
+            Some(bytes) => serde_json::from_slice(&bytes).context("Corrupt channel backups")?,
+            None => Vec::new(),
+        };
+
+        let anchored = [global.get_global_merkle_root()];
+        let (wallet, report) = WalletContract::recover(
+            &keys.mnemonic()?,
+            "",
+            meta.network,
+            meta.wallet_id,
+            &backups,
+            &anchored,
+            global,
+        )?;
+        if let Some((channel_id, error)) = report.rejected.first() {
+            bail!(
+                "Backup of channel {} is invalid: {}",
+                hex::encode(channel_id),
+                error
+            );
+        }
+        let audit = AuditLog::restore(&self.db, &meta.wallet_id)?;
+        Ok((wallet.with_audit_log(audit), meta))
+    }
+
+    /// Publishes the wallet's root at the next height and saves everything `load` reads.
+    pub fn save(
+        &self,
+        wallet: &mut WalletContract,
+        mut meta: WalletMeta,
+    ) -> anyhow::Result<WalletMeta> {
+        meta.height += 1;
+        wallet.global_contract.finalize_submissions(meta.height);
+        wallet.repair_against_global(meta.height)?;
+        let mut channel_ids = wallet.list_channels();
+        channel_ids.sort_unstable();
+        let backups = channel_ids
+            .iter()
+            .map(|channel_id| wallet.channel_backup(channel_id))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        wallet.global_contract.save(&self.db)?;
+        wallet.audit_log().save(&self.db, &meta.wallet_id)?;
+        self.db.put(BACKUPS_KEY, &serde_json::to_vec(&backups)?)?;
+        self.db.put(META_KEY, &serde_json::to_vec(&meta)?)?;
+        self.db.flush()?;
+        Ok(meta)
+    }
+}
@@ -0,0 +1,79 @@
+// src/main.rs
+
+//! `overpass`: operate a wallet kept in local storage or served by a daemon.
+
+mod commands;
+mod config;
+mod local;
+mod remote;
+
+use anyhow::Context;
+use clap::Parser;
+use overpass_core::services::server::{AuthToken, COOKIE_FILE};
+use overpass_core::zkp::wallet_lock::KdfParams;
+use std::path::PathBuf;
+
+use commands::{Backend, Command};
+use config::Config;
+use local::LocalStore;
+use remote::RemoteClient;
+
+#[derive(Debug, Parser)]
+#[command(name = "overpass", version, about)]
+struct Cli {
+    /// Config file; `overpass.toml` in the working directory is read if present.
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+    /// JSON-RPC endpoint of a wallet daemon, overriding the config.
+    #[arg(long, global = true)]
+    remote: Option<String>,
+    /// File holding the daemon's auth token, overriding the config.
+    #[arg(long, global = true)]
+    cookie: Option<PathBuf>,
+    /// Directory of the local wallet, overriding the config.
+    #[arg(long, global = true)]
+    data_dir: Option<PathBuf>,
+    /// Passphrase the local wallet's seed is sealed under.
+    #[arg(
+        long,
+        env = "OVERPASS_PASSPHRASE",
+        hide_env_values = true,
+        global = true
+    )]
+    passphrase: Option<String>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+fn backend(cli: &Cli, config: Config) -> anyhow::Result<Backend> {
+    let data_dir = cli.data_dir.clone().unwrap_or(config.data_dir);
+    if let Some(url) = cli.remote.clone().or(config.remote) {
+        let cookie = cli
+            .cookie
+            .clone()
+            .or(config.cookie)
+            .unwrap_or_else(|| data_dir.join(COOKIE_FILE));
+        let token = AuthToken::read_cookie(&cookie)
+            .with_context(|| format!("Failed to read the daemon's token from {}", cookie.display()))?;
+        return Ok(Backend::Remote(RemoteClient::new(url, token)));
+    }
+    // Verifying a proof touches no wallet, so it runs without a passphrase.
+    let passphrase = match (&cli.passphrase, &cli.command) {
+        (Some(passphrase), _) => passphrase.clone(),
+        (None, Command::Verify { .. }) => String::new(),
+        (None, _) => anyhow::bail!("Set OVERPASS_PASSPHRASE or pass --passphrase"),
+    };
+    Ok(Backend::Local {
+        store: LocalStore::open(&data_dir, passphrase)?,
+        network: config.network,
+    })
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    let config = Config::load(cli.config.as_deref())?;
+    let backend = backend(&cli, config)?;
+    let output = commands::run(cli.command, &backend, KdfParams::default())?;
+    println!("{}", serde_json::to_string_pretty(&output)?);
+    Ok(())
+}
@@ -0,0 +1,47 @@
+// src/remote.rs
+
+use anyhow::{anyhow, Context};
+use overpass_core::services::rpc_server::Response;
+use overpass_core::services::server::AuthToken;
+use serde_json::{json, Value};
+use std::cell::Cell;
+use std::time::Duration;
+
+/// Client for the JSON-RPC API of a wallet daemon.
+pub struct RemoteClient {
+    url: String,
+    token: AuthToken,
+    agent: ureq::Agent,
+    next_id: Cell<u64>,
+}
+
+impl RemoteClient {
+    pub fn new(url: impl Into<String>, token: AuthToken) -> Self {
+        Self {
+            url: url.into(),
+            token,
+            agent: ureq::AgentBuilder::new()
+                .timeout(Duration::from_secs(30))
+                .build(),
+            next_id: Cell::new(1),
+        }
+    }
+
+    pub fn call(&self, method: &str, params: Value) -> anyhow::Result<Value> {
+        let id = self.next_id.replace(self.next_id.get() + 1);
+        let request = json!({ "jsonrpc": "2.0", "method": method, "params": params, "id": id });
+        let response: Response = self
+            .agent
+            .post(&self.url)
+            .set("Authorization", &self.token.bearer())
+            .send_json(request)
+            .with_context(|| format!("Request to {} failed", self.url))?
+            .into_json()
+            .context("Malformed JSON-RPC response")?;
+        match response.error {
+            Some(error) => Err(anyhow!("{} (code {})", error.message, error.code)),
+            // A null result reads back as none.
+            None => Ok(response.result.unwrap_or(Value::Null)),
+        }
+    }
+}
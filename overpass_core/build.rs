@@ -0,0 +1,15 @@
+// build.rs
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    // The gRPC service is the only generated code.
+    #[cfg(feature = "grpc")]
+    {
+        let protoc =
+            protoc_bin_vendored::protoc_bin_path().expect("No vendored protoc for this platform");
+        std::env::set_var("PROTOC", protoc);
+        println!("cargo:rerun-if-changed=proto/overpass.proto");
+        tonic_build::compile_protos("proto/overpass.proto")
+            .expect("Failed to compile proto/overpass.proto");
+    }
+}
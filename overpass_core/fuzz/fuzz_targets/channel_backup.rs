@@ -0,0 +1,15 @@
+// fuzz/fuzz_targets/channel_backup.rs
+
+//! Channel backups, as a counterparty sends them or a user restores them from a file.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use overpass_core::zkp::channel_backup::ChannelBackup;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(backup) = serde_json::from_slice::<ChannelBackup>(data) else {
+        return;
+    };
+    let _ = backup.verify(&backup.wallet_proof.wallet_id, &[backup.global_root]);
+});
@@ -0,0 +1,24 @@
+// fuzz/fuzz_targets/compressed_transaction.rs
+
+//! Stored transactions: canonical encodings, encodings with proofs, and delta-encoded
+//! histories.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use overpass_core::zkp::compressed_transaction::{CompressedTransaction, DeltaHistory};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(txs) = CompressedTransaction::decode_all(data) {
+        assert_eq!(CompressedTransaction::encode_all(&txs), data);
+    }
+    if let Ok(txs) = CompressedTransaction::decode_proven(data) {
+        let encoded = CompressedTransaction::encode_proven(&txs);
+        assert_eq!(CompressedTransaction::decode_proven(&encoded).unwrap(), txs);
+    }
+    if let Ok(history) = DeltaHistory::from_bytes(data) {
+        let encoded = history.to_bytes();
+        assert_eq!(DeltaHistory::from_bytes(&encoded).unwrap(), history);
+        let _ = history.decode();
+    }
+});
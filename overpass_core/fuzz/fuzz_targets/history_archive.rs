@@ -0,0 +1,15 @@
+// fuzz/fuzz_targets/history_archive.rs
+
+//! Archive blocks read back from cold storage.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use overpass_core::zkp::history_archive::ArchiveBlock;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(block) = ArchiveBlock::from_bytes(data) else {
+        return;
+    };
+    assert_eq!(ArchiveBlock::from_bytes(&block.to_bytes()).unwrap(), block);
+});
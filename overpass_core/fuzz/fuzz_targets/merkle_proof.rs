@@ -0,0 +1,32 @@
+// fuzz/fuzz_targets/merkle_proof.rs
+
+//! Inclusion proofs checked against a leaf and root taken from the input.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use overpass_core::zkp::global_root_contract::{EpochRootProof, WalletInclusionProof};
+use overpass_core::zkp::mmr::MmrProof;
+use overpass_core::zkp::wallet_contract::ChannelInclusionProof;
+
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 64 {
+        return;
+    }
+    let (head, json) = data.split_at(64);
+    let leaf: [u8; 32] = head[..32].try_into().unwrap();
+    let root: [u8; 32] = head[32..].try_into().unwrap();
+
+    if let Ok(proof) = serde_json::from_slice::<MmrProof>(json) {
+        let _ = proof.verify(&leaf, &root);
+    }
+    if let Ok(proof) = serde_json::from_slice::<EpochRootProof>(json) {
+        let _ = proof.verify(&root);
+    }
+    if let Ok(proof) = serde_json::from_slice::<WalletInclusionProof>(json) {
+        let _ = proof.verify(&root);
+    }
+    if let Ok(proof) = serde_json::from_slice::<ChannelInclusionProof>(json) {
+        let _ = proof.verify(&root);
+    }
+});
@@ -0,0 +1,18 @@
+// fuzz/fuzz_targets/proof_envelope.rs
+
+//! Relay events and the NIP-44 payloads that carry channel messages and their proofs.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use overpass_core::network::nostr::{nip44, Event};
+
+const CONVERSATION_KEY: [u8; 32] = [7u8; 32];
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(event) = serde_json::from_slice::<Event>(data) {
+        let _ = event.verify();
+        let _ = event.author();
+    }
+    let _ = nip44::decrypt(&CONVERSATION_KEY, data);
+});
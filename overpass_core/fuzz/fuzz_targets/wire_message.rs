@@ -0,0 +1,20 @@
+// fuzz/fuzz_targets/wire_message.rs
+
+//! Peer messages as they arrive off a transport.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use overpass_core::network::wire::{Decoded, Message};
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(Decoded::Message(message)) = Message::decode(data) else {
+        return;
+    };
+    // Whatever decodes re-encodes canonically.
+    let bytes = message.encode();
+    let Ok(Decoded::Message(again)) = Message::decode(&bytes) else {
+        panic!("re-encoded message type {} fails to decode", message.message_type());
+    };
+    assert_eq!(again.encode(), bytes);
+});
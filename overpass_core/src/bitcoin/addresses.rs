@@ -0,0 +1,156 @@
+// src/bitcoin/addresses.rs
+
+use crate::bitcoin::keys::KeyManager;
+use bitcoin::address::{NetworkUnchecked, Payload, WitnessVersion};
+use bitcoin::secp256k1::{Secp256k1, Verification, XOnlyPublicKey};
+use bitcoin::{Address, Network, PublicKey};
+use std::str::FromStr;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum AddressError {
+    #[error("Invalid address: {0}")]
+    Invalid(String),
+    #[error("Address is for {found}, expected {expected}")]
+    WrongNetwork { expected: Network, found: String },
+    #[error("Address is not native segwit")]
+    NotSegwit,
+    #[error("Key error: {0}")]
+    Key(String),
+}
+
+/// Segwit output types used for settlement and sweep destinations.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AddressKind {
+    /// Segwit v0 key hash, encoded as bech32.
+    P2wpkh,
+    /// Segwit v1 key-path Taproot, encoded as bech32m.
+    P2tr,
+}
+
+/// Builds a P2WPKH (bech32) address for a compressed key.
+pub fn p2wpkh_address(key: &PublicKey, network: Network) -> Result<Address, AddressError> {
+    Address::p2wpkh(key, network).map_err(|e| AddressError::Key(e.to_string()))
+}
+
+/// Builds a key-path-only P2TR (bech32m) address, tweaking `internal_key` per BIP86.
+pub fn p2tr_address<C: Verification>(
+    secp: &Secp256k1<C>,
+    internal_key: XOnlyPublicKey,
+    network: Network,
+) -> Address {
+    Address::p2tr(secp, internal_key, None, network)
+}
+
+/// Builds an address of the given kind for `key`.
+pub fn address_for_key<C: Verification>(
+    secp: &Secp256k1<C>,
+    key: &PublicKey,
+    kind: AddressKind,
+    network: Network,
+) -> Result<Address, AddressError> {
+    match kind {
+        AddressKind::P2wpkh => p2wpkh_address(key, network),
+        AddressKind::P2tr => Ok(p2tr_address(secp, key.inner.x_only_public_key().0, network)),
+    }
+}
+
+/// Derives a fresh wallet address to sweep or settle funds into.
+///
+/// Uses the key manager's BIP86 receive or change chain, so the address is recoverable
+/// from the backup phrase.
+pub fn wallet_address(
+    keys: &KeyManager,
+    change: bool,
+    index: u32,
+    network: Network,
+) -> Result<Address, AddressError> {
+    let secp = Secp256k1::verification_only();
+    let key_pair = keys
+        .wallet_key(change, index)
+        .map_err(|e| AddressError::Key(e.to_string()))?;
+    Ok(p2tr_address(&secp, key_pair.x_only_public_key().0, network))
+}
+
+/// Parses a user-supplied destination, requiring a native segwit address on `network`.
+pub fn parse_destination(address: &str, network: Network) -> Result<Address, AddressError> {
+    let unchecked = Address::<NetworkUnchecked>::from_str(address)
+        .map_err(|e| AddressError::Invalid(e.to_string()))?;
+    if !unchecked.is_valid_for_network(network) {
+        return Err(AddressError::WrongNetwork {
+            expected: network,
+            found: address.chars().take(4).collect(),
+        });
+    }
+    let address = unchecked.assume_checked();
+    match address.payload {
+        Payload::WitnessProgram(_) => Ok(address),
+        _ => Err(AddressError::NotSegwit),
+    }
+}
+
+/// Returns true if the address uses the bech32m encoding (witness version 1 and above).
+pub fn is_bech32m(address: &Address) -> bool {
+    matches!(
+        &address.payload,
+        Payload::WitnessProgram(program) if program.version() != WitnessVersion::V0
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::PHRASE;
+    use bitcoin::secp256k1::SecretKey;
+
+    fn key() -> PublicKey {
+        let secp = Secp256k1::new();
+        PublicKey::new(SecretKey::from_slice(&[1u8; 32]).unwrap().public_key(&secp))
+    }
+
+    #[test]
+    fn test_prefixes_per_network() {
+        let secp = Secp256k1::new();
+        let cases = [
+            (Network::Bitcoin, "bc1q", "bc1p"),
+            (Network::Testnet, "tb1q", "tb1p"),
+            (Network::Signet, "tb1q", "tb1p"),
+            (Network::Regtest, "bcrt1q", "bcrt1p"),
+        ];
+        for (network, segwit_v0, taproot) in cases {
+            let v0 = address_for_key(&secp, &key(), AddressKind::P2wpkh, network).unwrap();
+            let v1 = address_for_key(&secp, &key(), AddressKind::P2tr, network).unwrap();
+            assert!(v0.to_string().starts_with(segwit_v0));
+            assert!(v1.to_string().starts_with(taproot));
+            assert!(!is_bech32m(&v0));
+            assert!(is_bech32m(&v1));
+        }
+    }
+
+    #[test]
+    fn test_parse_destination() {
+        let secp = Secp256k1::new();
+        let address = address_for_key(&secp, &key(), AddressKind::P2tr, Network::Regtest).unwrap();
+        let parsed = parse_destination(&address.to_string(), Network::Regtest).unwrap();
+        assert_eq!(parsed.script_pubkey(), address.script_pubkey());
+
+        assert!(matches!(
+            parse_destination(&address.to_string(), Network::Bitcoin),
+            Err(AddressError::WrongNetwork { .. })
+        ));
+        assert!(matches!(
+            parse_destination("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2", Network::Bitcoin),
+            Err(AddressError::NotSegwit)
+        ));
+        assert!(parse_destination("not-an-address", Network::Bitcoin).is_err());
+    }
+
+    #[test]
+    fn test_wallet_address_is_recoverable() {
+        let keys = KeyManager::from_mnemonic(PHRASE, "", Network::Bitcoin).unwrap();
+        assert_eq!(
+            wallet_address(&keys, false, 0, Network::Bitcoin).unwrap().to_string(),
+            "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr"
+        );
+    }
+}
@@ -0,0 +1,548 @@
+// src/bitcoin/anchor_scheduler.rs
+
+use crate::bitcoin::broadcast::RetryPolicy;
+use crate::bitcoin::chain::ChainBackend;
+use crate::bitcoin::fees::FeeEstimator;
+use crate::bitcoin::rbf::{RbfError, RbfPolicy, SettlementTracker};
+use crate::bitcoin::root_anchor::{AnchorPayload, RootAnchorError, RootAnchorer};
+use crate::bitcoin::signer::{sign_transaction, Signer};
+use crate::bitcoin::triggers::ChainTime;
+use crate::bitcoin::utxo::Utxo;
+use crate::zkp::global_root_contract::GlobalRootContract;
+use crate::zkp::helpers::Bytes32;
+use bitcoin::{ScriptBuf, Transaction, TxOut, Txid};
+use thiserror::Error;
+
+/// Index of the change output in anchor transactions, which pays for fee bumps.
+const ANCHOR_CHANGE_INDEX: usize = 1;
+
+#[derive(Error, Debug)]
+pub enum AnchorSchedulerError {
+    #[error("Anchor construction failed: {0}")]
+    Anchor(#[from] RootAnchorError),
+    #[error("Fee bump failed: {0}")]
+    Rbf(#[from] RbfError),
+    #[error("Signing failed: {0}")]
+    Signing(String),
+    #[error("Chain backend error: {0}")]
+    Chain(String),
+}
+
+/// When the latest sealed epoch root is anchored; it is due as soon as any set limit is
+/// reached since the previous anchor.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AnchorPolicy {
+    /// Epochs sealed since the last anchored one.
+    pub every_epochs: Option<u64>,
+    /// Blocks since the last anchor confirmed.
+    pub every_blocks: Option<u32>,
+    /// Value in satoshis moved by channel updates since the last anchor.
+    pub pending_value: Option<u64>,
+}
+
+impl AnchorPolicy {
+    pub fn every_epochs(epochs: u64) -> Self {
+        Self {
+            every_epochs: Some(epochs),
+            ..Self::default()
+        }
+    }
+
+    pub fn every_blocks(blocks: u32) -> Self {
+        Self {
+            every_blocks: Some(blocks),
+            ..Self::default()
+        }
+    }
+
+    pub fn or_every_blocks(mut self, blocks: u32) -> Self {
+        self.every_blocks = Some(blocks);
+        self
+    }
+
+    pub fn or_pending_value(mut self, sats: u64) -> Self {
+        self.pending_value = Some(sats);
+        self
+    }
+
+    /// Checks whether an anchor is due `epochs` epochs and `blocks` blocks after the last
+    /// one, with `pending_value` satoshis moved since.
+    pub fn is_due(&self, epochs: u64, blocks: u32, pending_value: u64) -> bool {
+        self.every_epochs.is_some_and(|limit| epochs >= limit)
+            || self.every_blocks.is_some_and(|limit| blocks >= limit)
+            || self
+                .pending_value
+                .is_some_and(|limit| pending_value >= limit)
+    }
+}
+
+/// What a scheduler tick did.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SchedulerEvent {
+    Broadcast {
+        txid: Txid,
+        payload: AnchorPayload,
+    },
+    /// Broadcasting failed; it is retried from `retry_at`.
+    BroadcastFailed {
+        txid: Txid,
+        error: String,
+        retry_at: u64,
+    },
+    /// The anchor sat unconfirmed too long and was replaced at a higher fee rate.
+    Bumped {
+        replaced: Txid,
+        txid: Txid,
+    },
+    Confirmed {
+        txid: Txid,
+        payload: AnchorPayload,
+    },
+}
+
+/// The anchor awaiting confirmation.
+#[derive(Clone, Debug)]
+struct InFlight {
+    payload: AnchorPayload,
+    sealed_epoch: u64,
+    /// Txid of the current version, which the RBF tracker knows it by.
+    txid: Txid,
+    accepted: bool,
+    failures: u32,
+    next_attempt_at: u64,
+}
+
+/// Commits sealed epoch roots to Bitcoin according to an `AnchorPolicy`, retrying failed
+/// broadcasts and bumping the fee of anchors that do not confirm.
+///
+/// Only one anchor is in flight at a time; a root sealed meanwhile is picked up by the
+/// first tick after the previous anchor confirms.
+pub struct AnchorScheduler {
+    policy: AnchorPolicy,
+    anchorer: RootAnchorer,
+    tracker: SettlementTracker,
+    retry: RetryPolicy,
+    change_script: ScriptBuf,
+    /// Sealed epoch covered by the last confirmed anchor.
+    last_epoch: Option<u64>,
+    pending_value: u64,
+    in_flight: Option<InFlight>,
+}
+
+impl AnchorScheduler {
+    pub fn new(policy: AnchorPolicy, anchorer: RootAnchorer, change_script: ScriptBuf) -> Self {
+        Self {
+            policy,
+            anchorer,
+            tracker: SettlementTracker::new(RbfPolicy::default()),
+            retry: RetryPolicy::default(),
+            change_script,
+            last_epoch: None,
+            pending_value: 0,
+            in_flight: None,
+        }
+    }
+
+    pub fn with_rbf_policy(mut self, policy: RbfPolicy) -> Self {
+        self.tracker = SettlementTracker::new(policy);
+        self
+    }
+
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    pub fn anchorer(&self) -> &RootAnchorer {
+        &self.anchorer
+    }
+
+    /// Counts value moved by a channel update towards the pending-value limit.
+    pub fn add_pending_value(&mut self, sats: u64) {
+        self.pending_value = self.pending_value.saturating_add(sats);
+    }
+
+    /// Gets the txid of the anchor awaiting confirmation.
+    pub fn in_flight(&self) -> Option<Txid> {
+        self.in_flight.as_ref().map(|in_flight| in_flight.txid)
+    }
+
+    /// Advances the anchor in flight, or starts a new one if the policy says it is due.
+    #[tracing::instrument(
+        name = "anchor",
+        skip_all,
+        fields(epoch = tracing::field::Empty, txid = tracing::field::Empty),
+        err
+    )]
+    pub fn tick(
+        &mut self,
+        contract: &GlobalRootContract,
+        utxos: &[Utxo],
+        backend: &dyn ChainBackend,
+        fee_estimator: &dyn FeeEstimator,
+        signer: &dyn Signer,
+        now: ChainTime,
+    ) -> Result<Vec<SchedulerEvent>, AnchorSchedulerError> {
+        if let Some(in_flight) = &self.in_flight {
+            let span = tracing::Span::current();
+            span.record("epoch", in_flight.sealed_epoch);
+            span.record("txid", tracing::field::display(in_flight.txid));
+            return self.advance(contract, backend, fee_estimator, signer, now);
+        }
+        let Some(sealed) = contract.latest_sealed_epoch() else {
+            return Ok(Vec::new());
+        };
+        if !self.is_due(sealed.epoch, sealed.root, now) {
+            return Ok(Vec::new());
+        }
+
+        tracing::Span::current().record("epoch", sealed.epoch);
+        let payload = self.anchorer.next_payload(sealed.root);
+        let tx = self.anchorer.build_anchor_tx(
+            &payload,
+            utxos,
+            self.change_script.clone(),
+            fee_estimator,
+        )?;
+        let prevouts = prevouts_for(&tx, utxos)?;
+        let tx = sign_transaction(signer, tx, &prevouts)
+            .map_err(|e| AnchorSchedulerError::Signing(e.to_string()))?;
+        let txid = self
+            .tracker
+            .track(tx, prevouts, ANCHOR_CHANGE_INDEX, now.unix)?;
+        tracing::Span::current().record("txid", tracing::field::display(txid));
+        self.in_flight = Some(InFlight {
+            payload,
+            sealed_epoch: sealed.epoch,
+            txid,
+            accepted: false,
+            failures: 0,
+            next_attempt_at: now.unix,
+        });
+        Ok(self.try_broadcast(backend, now).into_iter().collect())
+    }
+
+    fn is_due(&self, epoch: u64, root: Bytes32, now: ChainTime) -> bool {
+        match (self.anchorer.last_anchor(), self.last_epoch) {
+            (Some(last), Some(last_epoch)) => {
+                last.payload.root != root
+                    && self.policy.is_due(
+                        epoch.saturating_sub(last_epoch),
+                        now.height.saturating_sub(last.anchored_at.height),
+                        self.pending_value,
+                    )
+            }
+            _ => true,
+        }
+    }
+
+    fn advance(
+        &mut self,
+        contract: &GlobalRootContract,
+        backend: &dyn ChainBackend,
+        fee_estimator: &dyn FeeEstimator,
+        signer: &dyn Signer,
+        now: ChainTime,
+    ) -> Result<Vec<SchedulerEvent>, AnchorSchedulerError> {
+        let in_flight = self
+            .in_flight
+            .clone()
+            .expect("called with an anchor in flight");
+        if !in_flight.accepted {
+            if now.unix < in_flight.next_attempt_at {
+                return Ok(Vec::new());
+            }
+            return Ok(self.try_broadcast(backend, now).into_iter().collect());
+        }
+
+        if let Some(txid) = self.confirmed_version(backend, &in_flight.txid)? {
+            self.tracker.mark_confirmed(&txid);
+            self.anchorer.record(in_flight.payload, txid, now);
+            contract.announce_anchor(in_flight.sealed_epoch, in_flight.payload.root, txid);
+            self.last_epoch = Some(in_flight.sealed_epoch);
+            self.pending_value = 0;
+            self.in_flight = None;
+            return Ok(vec![SchedulerEvent::Confirmed {
+                txid,
+                payload: in_flight.payload,
+            }]);
+        }
+
+        if !self
+            .tracker
+            .due_for_bump(now.unix)
+            .contains(&in_flight.txid)
+        {
+            return Ok(Vec::new());
+        }
+        let bumped = self
+            .tracker
+            .bump(&in_flight.txid, fee_estimator, now.unix, signer)?;
+        let txid = bumped.txid();
+        if let Some(current) = self.in_flight.as_mut() {
+            current.txid = txid;
+            current.accepted = false;
+            current.failures = 0;
+        }
+        let mut events = vec![SchedulerEvent::Bumped {
+            replaced: in_flight.txid,
+            txid,
+        }];
+        events.extend(self.try_broadcast(backend, now));
+        Ok(events)
+    }
+
+    /// Broadcasts the current version of the anchor in flight, scheduling a retry on failure.
+    fn try_broadcast(
+        &mut self,
+        backend: &dyn ChainBackend,
+        now: ChainTime,
+    ) -> Option<SchedulerEvent> {
+        let in_flight = self.in_flight.as_mut()?;
+        let tx = &self.tracker.get(&in_flight.txid)?.tx;
+        match backend.broadcast(tx) {
+            Ok(_) => {
+                in_flight.accepted = true;
+                in_flight.failures = 0;
+                Some(SchedulerEvent::Broadcast {
+                    txid: in_flight.txid,
+                    payload: in_flight.payload,
+                })
+            }
+            Err(e) => {
+                in_flight.failures += 1;
+                in_flight.next_attempt_at = now.unix + self.retry.backoff_secs(in_flight.failures);
+                tracing::warn!(
+                    txid = %in_flight.txid,
+                    retry_at = in_flight.next_attempt_at,
+                    "Anchor broadcast failed: {}",
+                    e
+                );
+                Some(SchedulerEvent::BroadcastFailed {
+                    txid: in_flight.txid,
+                    error: e.to_string(),
+                    retry_at: in_flight.next_attempt_at,
+                })
+            }
+        }
+    }
+
+    /// Finds a confirmed version of the anchor, current or replaced.
+    fn confirmed_version(
+        &self,
+        backend: &dyn ChainBackend,
+        txid: &Txid,
+    ) -> Result<Option<Txid>, AnchorSchedulerError> {
+        let replaced = self
+            .tracker
+            .get(txid)
+            .map(|pending| pending.replaced.clone())
+            .unwrap_or_default();
+        for candidate in std::iter::once(*txid).chain(replaced) {
+            let confirmations = backend
+                .confirmations(&candidate)
+                .map_err(|e| AnchorSchedulerError::Chain(e.to_string()))?;
+            if confirmations.is_some_and(|confirmations| confirmations > 0) {
+                return Ok(Some(candidate));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Gets the outputs spent by `tx`, in input order.
+fn prevouts_for(tx: &Transaction, utxos: &[Utxo]) -> Result<Vec<TxOut>, AnchorSchedulerError> {
+    tx.input
+        .iter()
+        .map(|input| {
+            utxos
+                .iter()
+                .find(|utxo| utxo.outpoint == input.previous_output)
+                .map(|utxo| utxo.txout.clone())
+                .ok_or_else(|| {
+                    AnchorSchedulerError::Signing(format!(
+                        "No UTXO for input {}",
+                        input.previous_output
+                    ))
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::chain::ChainError;
+    use crate::bitcoin::fees::StaticFeeEstimator;
+    use crate::bitcoin::signer::SignerError;
+    use crate::zkp::global_root_contract::GlobalRootEvent;
+    use crate::zkp::pedersen_parameters::PedersenParameters;
+    use bitcoin::hashes::Hash;
+    use bitcoin::psbt::PartiallySignedTransaction as Psbt;
+    use bitcoin::{FeeRate, OutPoint, WPubkeyHash, Witness};
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    struct MockChain {
+        online: Mutex<bool>,
+        confirmations: Mutex<HashMap<Txid, u32>>,
+    }
+
+    impl ChainBackend for MockChain {
+        fn broadcast(&self, tx: &Transaction) -> Result<Txid, ChainError> {
+            if !*self.online.lock().unwrap() {
+                return Err(ChainError::BackendError("offline".to_string()));
+            }
+            self.confirmations
+                .lock()
+                .unwrap()
+                .entry(tx.txid())
+                .or_insert(0);
+            Ok(tx.txid())
+        }
+
+        fn confirmations(&self, txid: &Txid) -> Result<Option<u32>, ChainError> {
+            Ok(self.confirmations.lock().unwrap().get(txid).copied())
+        }
+
+        fn tip_height(&self) -> Result<u32, ChainError> {
+            Ok(0)
+        }
+
+        fn spending_transaction(
+            &self,
+            _outpoint: &OutPoint,
+        ) -> Result<Option<Transaction>, ChainError> {
+            Ok(None)
+        }
+    }
+
+    struct FakeSigner;
+
+    impl Signer for FakeSigner {
+        fn sign_psbt(&self, psbt: &mut Psbt) -> Result<(), SignerError> {
+            for input in &mut psbt.inputs {
+                input.final_script_witness =
+                    Some(Witness::from_slice(&[vec![1u8; 72], vec![1u8; 33]]));
+            }
+            Ok(())
+        }
+    }
+
+    fn change_script() -> ScriptBuf {
+        ScriptBuf::new_v0_p2wpkh(&WPubkeyHash::all_zeros())
+    }
+
+    fn utxos() -> Vec<Utxo> {
+        vec![Utxo {
+            outpoint: OutPoint::new(Txid::all_zeros(), 0),
+            txout: TxOut {
+                value: 100_000,
+                script_pubkey: change_script(),
+            },
+            confirmations: 6,
+            input_vsize: 68,
+        }]
+    }
+
+    #[test]
+    fn test_policy_triggers() {
+        let policy = AnchorPolicy::every_epochs(4)
+            .or_every_blocks(144)
+            .or_pending_value(1_000_000);
+        assert!(!policy.is_due(3, 143, 999_999));
+        assert!(policy.is_due(4, 0, 0));
+        assert!(policy.is_due(0, 144, 0));
+        assert!(policy.is_due(0, 0, 1_000_000));
+        assert!(!AnchorPolicy::default().is_due(u64::MAX, u32::MAX, u64::MAX));
+    }
+
+    #[test]
+    fn test_retry_bump_and_confirm() {
+        let chain = MockChain {
+            online: Mutex::new(false),
+            confirmations: Mutex::new(HashMap::new()),
+        };
+        let estimator = StaticFeeEstimator::new(FeeRate::from_sat_per_vb_unchecked(2));
+        let mut contract = GlobalRootContract::new(PedersenParameters::default());
+        contract.register_wallet([1u8; 32], [2u8; 32]).unwrap();
+        let first = contract.seal_epoch();
+        let mut announcements = contract.subscribe();
+
+        let mut scheduler = AnchorScheduler::new(
+            AnchorPolicy::every_epochs(2).or_pending_value(50_000),
+            RootAnchorer::new(3_600, 6),
+            change_script(),
+        );
+        let tick = |scheduler: &mut AnchorScheduler, contract: &GlobalRootContract, unix| {
+            scheduler
+                .tick(
+                    contract,
+                    &utxos(),
+                    &chain,
+                    &estimator,
+                    &FakeSigner,
+                    ChainTime::new(unix, 800_000),
+                )
+                .unwrap()
+        };
+
+        assert!(matches!(
+            tick(&mut scheduler, &contract, 1_000)[..],
+            [SchedulerEvent::BroadcastFailed {
+                retry_at: 1_030,
+                ..
+            }]
+        ));
+        assert!(tick(&mut scheduler, &contract, 1_010).is_empty());
+        *chain.online.lock().unwrap() = true;
+        let original = scheduler.in_flight().unwrap();
+        assert!(matches!(
+            tick(&mut scheduler, &contract, 1_030)[..],
+            [SchedulerEvent::Broadcast { txid, payload }] if txid == original && payload.root == first.root
+        ));
+
+        // Still unconfirmed an hour later, so the fee is bumped and the replacement sent.
+        let events = tick(&mut scheduler, &contract, 1_000 + 3_600);
+        let bumped = scheduler.in_flight().unwrap();
+        assert_ne!(bumped, original);
+        assert_eq!(
+            events[0],
+            SchedulerEvent::Bumped {
+                replaced: original,
+                txid: bumped
+            }
+        );
+        assert!(matches!(events[1], SchedulerEvent::Broadcast { txid, .. } if txid == bumped));
+
+        // The original version confirming settles the anchor.
+        chain.confirmations.lock().unwrap().insert(original, 1);
+        assert!(matches!(
+            tick(&mut scheduler, &contract, 5_000)[..],
+            [SchedulerEvent::Confirmed { txid, .. }] if txid == original
+        ));
+        assert!(scheduler.in_flight().is_none());
+        assert_eq!(
+            scheduler.anchorer().last_anchor().unwrap().payload.root,
+            first.root
+        );
+        assert_eq!(
+            announcements.try_recv().ok(),
+            Some(GlobalRootEvent::Anchored {
+                epoch: first.epoch,
+                root: first.root,
+                anchor_txid: original,
+            })
+        );
+
+        // One more epoch is not enough on its own, but enough pending value is.
+        contract.register_wallet([3u8; 32], [4u8; 32]).unwrap();
+        contract.seal_epoch();
+        assert!(tick(&mut scheduler, &contract, 6_000).is_empty());
+        scheduler.add_pending_value(50_000);
+        assert!(matches!(
+            tick(&mut scheduler, &contract, 6_000)[..],
+            [SchedulerEvent::Broadcast { payload, .. }] if payload.epoch == 1
+        ));
+    }
+}
@@ -0,0 +1,279 @@
+// src/bitcoin/anchors.rs
+
+use crate::bitcoin::fees::{fee_for_vsize, FeeEstimator};
+use crate::bitcoin::utxo::Utxo;
+use bitcoin::blockdata::opcodes::all::{OP_CHECKSIG, OP_CSV, OP_ENDIF, OP_IFDUP, OP_NOTIF, OP_PUSHNUM_16};
+use bitcoin::blockdata::script::Builder;
+use bitcoin::{FeeRate, OutPoint, PublicKey, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Witness};
+use thiserror::Error;
+
+/// Value of each anchor output, matching the Lightning anchor convention.
+pub const ANCHOR_VALUE: u64 = 330;
+
+/// Virtual size of spending an anchor output with a signature.
+pub const ANCHOR_INPUT_VSIZE: u64 = 70;
+
+/// Virtual size of a child transaction's header plus one P2WPKH change output.
+const CHILD_BASE_VSIZE: u64 = 42;
+
+#[derive(Error, Debug)]
+pub enum AnchorError {
+    #[error("Anchor output not found in parent transaction")]
+    AnchorNotFound,
+    #[error("Insufficient funds for CPFP: need {0} sat more")]
+    InsufficientFunds(u64),
+    #[error("Invalid package: {0}")]
+    InvalidPackage(String),
+    #[error("Fee estimation failed: {0}")]
+    Fee(String),
+}
+
+/// Builds an anchor script: spendable by `key` at once, or by anyone after 16 blocks.
+///
+/// `<key> OP_CHECKSIG OP_IFDUP OP_NOTIF OP_16 OP_CHECKSEQUENCEVERIFY OP_ENDIF`. The
+/// anyone-can-spend branch lets third parties sweep unused anchors so they don't bloat
+/// the UTXO set.
+pub fn anchor_script(key: &PublicKey) -> ScriptBuf {
+    Builder::new()
+        .push_key(key)
+        .push_opcode(OP_CHECKSIG)
+        .push_opcode(OP_IFDUP)
+        .push_opcode(OP_NOTIF)
+        .push_opcode(OP_PUSHNUM_16)
+        .push_opcode(OP_CSV)
+        .push_opcode(OP_ENDIF)
+        .into_script()
+}
+
+/// Creates the P2WSH anchor output for `key`.
+pub fn anchor_output(key: &PublicKey) -> TxOut {
+    TxOut {
+        value: ANCHOR_VALUE,
+        script_pubkey: ScriptBuf::new_v0_p2wsh(&anchor_script(key).wscript_hash()),
+    }
+}
+
+/// Appends one anchor output per party to a commitment transaction.
+///
+/// The anchors are funded from the output at `fee_source`, so the commitment's total value
+/// is unchanged.
+pub fn add_anchor_outputs(
+    tx: &mut Transaction,
+    fee_source: usize,
+    local_key: &PublicKey,
+    remote_key: &PublicKey,
+) -> Result<(), AnchorError> {
+    let source = tx
+        .output
+        .get_mut(fee_source)
+        .ok_or_else(|| AnchorError::InvalidPackage("Fee source output out of range".to_string()))?;
+    source.value = source
+        .value
+        .checked_sub(2 * ANCHOR_VALUE)
+        .ok_or_else(|| AnchorError::InvalidPackage("Fee source cannot fund anchors".to_string()))?;
+    tx.output.push(anchor_output(local_key));
+    tx.output.push(anchor_output(remote_key));
+    Ok(())
+}
+
+/// Finds the anchor output belonging to `key` in a commitment transaction.
+pub fn find_anchor(tx: &Transaction, key: &PublicKey) -> Option<u32> {
+    let script_pubkey = anchor_output(key).script_pubkey;
+    tx.output
+        .iter()
+        .position(|output| output.script_pubkey == script_pubkey)
+        .map(|vout| vout as u32)
+}
+
+/// An unsigned child transaction bumping its parent's fee through an anchor.
+#[derive(Clone, Debug)]
+pub struct CpfpPackage {
+    pub child: Transaction,
+    /// Outputs spent by the child, in input order (the anchor first).
+    pub prevouts: Vec<TxOut>,
+    pub child_fee: u64,
+    pub package_fee_rate: FeeRate,
+}
+
+/// Builds a child spending our anchor plus wallet inputs so that the parent and child
+/// together pay the rate `fee_estimator` gives for confirmation within `target_blocks`.
+///
+/// The anchor input comes first and is satisfied by `[signature, anchor_script]`; the
+/// wallet inputs are left for the caller to sign.
+pub fn build_cpfp_child(
+    parent: &Transaction,
+    parent_fee: u64,
+    anchor_key: &PublicKey,
+    wallet_utxos: &[Utxo],
+    change_script: ScriptBuf,
+    fee_estimator: &dyn FeeEstimator,
+    target_blocks: u16,
+) -> Result<CpfpPackage, AnchorError> {
+    let target_rate = fee_estimator
+        .estimate_fee_rate(target_blocks)
+        .map_err(|e| AnchorError::Fee(e.to_string()))?;
+    let anchor_vout = find_anchor(parent, anchor_key).ok_or(AnchorError::AnchorNotFound)?;
+    let anchor_prevout = parent.output[anchor_vout as usize].clone();
+    let parent_vsize = parent.vsize() as u64;
+
+    let mut utxos: Vec<&Utxo> = wallet_utxos.iter().collect();
+    utxos.sort_by_key(|utxo| std::cmp::Reverse(utxo.txout.value));
+
+    let mut selected = Vec::new();
+    let mut input_value = anchor_prevout.value;
+    let mut child_vsize = CHILD_BASE_VSIZE + ANCHOR_INPUT_VSIZE;
+    let mut remaining = utxos.into_iter();
+    let child_fee = loop {
+        let package_fee = fee_for_vsize(target_rate, parent_vsize + child_vsize)
+            .map_err(|e| AnchorError::InvalidPackage(e.to_string()))?;
+        let child_fee = package_fee.saturating_sub(parent_fee);
+        let dust_limit = change_script.dust_value().to_sat();
+        if input_value >= child_fee + dust_limit {
+            break child_fee;
+        }
+        match remaining.next() {
+            Some(utxo) => {
+                input_value += utxo.txout.value;
+                child_vsize += utxo.input_vsize;
+                selected.push(utxo.clone());
+            }
+            None => {
+                return Err(AnchorError::InsufficientFunds(
+                    child_fee + dust_limit - input_value,
+                ))
+            }
+        }
+    };
+
+    let mut input = vec![TxIn {
+        previous_output: OutPoint::new(parent.txid(), anchor_vout),
+        script_sig: ScriptBuf::new(),
+        sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+        witness: Witness::new(),
+    }];
+    let mut prevouts = vec![anchor_prevout];
+    for utxo in &selected {
+        input.push(TxIn {
+            previous_output: utxo.outpoint,
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            witness: Witness::new(),
+        });
+        prevouts.push(utxo.txout.clone());
+    }
+
+    let child = Transaction {
+        version: 2,
+        lock_time: bitcoin::absolute::LockTime::ZERO,
+        input,
+        output: vec![TxOut {
+            value: input_value - child_fee,
+            script_pubkey: change_script,
+        }],
+    };
+    let package_fee_rate =
+        FeeRate::from_sat_per_kwu((parent_fee + child_fee) * 250 / (parent_vsize + child_vsize));
+
+    Ok(CpfpPackage {
+        child,
+        prevouts,
+        child_fee,
+        package_fee_rate,
+    })
+}
+
+/// Builds the witness spending an anchor with our signature.
+pub fn anchor_witness(key: &PublicKey, signature: &bitcoin::ecdsa::Signature) -> Witness {
+    Witness::from_slice(&[signature.to_vec(), anchor_script(key).to_bytes()])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::fees::StaticFeeEstimator;
+    use bitcoin::hashes::Hash;
+    use bitcoin::secp256k1::{Secp256k1, SecretKey};
+    use bitcoin::{Txid, WPubkeyHash};
+
+    fn key(byte: u8) -> PublicKey {
+        let secp = Secp256k1::new();
+        PublicKey::new(SecretKey::from_slice(&[byte; 32]).unwrap().public_key(&secp))
+    }
+
+    fn commitment() -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: bitcoin::absolute::LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(Txid::all_zeros(), 0),
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::MAX,
+                witness: Witness::from_slice(&[vec![0u8; 64]]),
+            }],
+            output: vec![
+                TxOut { value: 60_000, script_pubkey: ScriptBuf::new_v0_p2wpkh(&WPubkeyHash::all_zeros()) },
+                TxOut { value: 39_660, script_pubkey: ScriptBuf::new_v0_p2wpkh(&WPubkeyHash::all_zeros()) },
+            ],
+        }
+    }
+
+    fn wallet_utxo(value: u64) -> Utxo {
+        Utxo {
+            outpoint: OutPoint::new(Txid::from_byte_array([9u8; 32]), 1),
+            txout: TxOut {
+                value,
+                script_pubkey: ScriptBuf::new_v0_p2wpkh(&WPubkeyHash::all_zeros()),
+            },
+            confirmations: 6,
+            input_vsize: 68,
+        }
+    }
+
+    #[test]
+    fn test_anchor_outputs() {
+        let mut tx = commitment();
+        add_anchor_outputs(&mut tx, 0, &key(1), &key(2)).unwrap();
+
+        assert_eq!(tx.output.len(), 4);
+        assert_eq!(tx.output[0].value, 60_000 - 2 * ANCHOR_VALUE);
+        assert_eq!(find_anchor(&tx, &key(1)), Some(2));
+        assert_eq!(find_anchor(&tx, &key(2)), Some(3));
+        assert_eq!(find_anchor(&tx, &key(3)), None);
+        assert!(tx.output[2].script_pubkey.is_v0_p2wsh());
+    }
+
+    #[test]
+    fn test_cpfp_reaches_package_rate() {
+        let mut parent = commitment();
+        add_anchor_outputs(&mut parent, 0, &key(1), &key(2)).unwrap();
+        let change = ScriptBuf::new_v0_p2wpkh(&WPubkeyHash::all_zeros());
+        let estimator = StaticFeeEstimator::new(FeeRate::from_sat_per_vb_unchecked(20));
+
+        let package = build_cpfp_child(
+            &parent,
+            200,
+            &key(1),
+            &[wallet_utxo(50_000)],
+            change.clone(),
+            &estimator,
+            6,
+        )
+        .unwrap();
+        assert_eq!(package.child.input.len(), 2);
+        assert_eq!(package.prevouts[0].value, ANCHOR_VALUE);
+        assert_eq!(package.package_fee_rate, FeeRate::from_sat_per_vb_unchecked(20));
+        assert_eq!(
+            package.child.output[0].value,
+            ANCHOR_VALUE + 50_000 - package.child_fee
+        );
+
+        assert!(matches!(
+            build_cpfp_child(&parent, 200, &key(1), &[], change.clone(), &estimator, 6),
+            Err(AnchorError::InsufficientFunds(_))
+        ));
+        assert!(matches!(
+            build_cpfp_child(&parent, 200, &key(3), &[], change, &estimator, 6),
+            Err(AnchorError::AnchorNotFound)
+        ));
+    }
+}
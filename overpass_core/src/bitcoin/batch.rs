@@ -0,0 +1,350 @@
+// src/bitcoin/batch.rs
+
+use crate::bitcoin::fees::{fee_for_vsize, FeeEstimator};
+use crate::bitcoin::musig2::cooperative_spend_message;
+use crate::services::overpass_db::OverpassDB;
+use bitcoin::{OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid, Witness};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Key prefix under which per-channel settlement records are persisted.
+const SETTLEMENT_PREFIX: &[u8] = b"batch_settlement:";
+
+/// Weight of a transaction's version, lock time, segwit marker and counts.
+const BASE_WEIGHT: u64 = 4 * 10 + 2;
+/// Weight of a Taproot key-path input: outpoint, empty script, sequence and a 64-byte signature.
+const KEY_SPEND_INPUT_WEIGHT: u64 = 4 * 41 + 66;
+
+#[derive(Error, Debug)]
+pub enum BatchError {
+    #[error("Batch contains no channels")]
+    Empty,
+    #[error("Channel {0} appears more than once")]
+    Duplicate(String),
+    #[error("Channel {0}: {1}")]
+    InvalidChannel(String, String),
+    #[error("Fee error: {0}")]
+    Fee(String),
+    #[error("Signing error: {0}")]
+    Signing(String),
+    #[error("Storage error: {0}")]
+    StorageError(String),
+    #[error("Serialization error: {0}")]
+    SerializationError(String),
+}
+
+/// A channel to settle cooperatively, with the agreed payouts of its final state.
+#[derive(Clone, Debug)]
+pub struct ChannelPayout {
+    pub channel_id: [u8; 32],
+    pub funding_outpoint: OutPoint,
+    pub funding_output: TxOut,
+    /// Payouts of the final state. Funding value they leave unassigned is the channel's
+    /// fee reserve.
+    pub payouts: Vec<TxOut>,
+}
+
+/// Where one channel's funds went in a batch settlement.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SettlementRecord {
+    pub channel_id: [u8; 32],
+    pub txid: Txid,
+    pub input_index: u32,
+    pub output_indices: Vec<u32>,
+    /// Output values after the channel's fee share was deducted.
+    pub output_values: Vec<u64>,
+    pub fee_share: u64,
+}
+
+/// An unsigned transaction settling several channels at once.
+#[derive(Clone, Debug)]
+pub struct BatchSettlement {
+    pub tx: Transaction,
+    /// Funding outputs spent by `tx`, in input order.
+    pub prevouts: Vec<TxOut>,
+    pub records: Vec<SettlementRecord>,
+}
+
+impl BatchSettlement {
+    /// Gets the total fee paid by the batch.
+    pub fn fee(&self) -> u64 {
+        self.records.iter().map(|record| record.fee_share).sum()
+    }
+
+    /// Gets the key-spend sighash the channel's parties co-sign for its funding input.
+    pub fn signing_message(&self, channel_id: &[u8; 32]) -> Result<[u8; 32], BatchError> {
+        let record = self
+            .records
+            .iter()
+            .find(|record| &record.channel_id == channel_id)
+            .ok_or_else(|| BatchError::InvalidChannel(hex::encode(channel_id), "Not in batch".to_string()))?;
+        cooperative_spend_message(&self.tx, record.input_index as usize, &self.prevouts)
+            .map_err(|e| BatchError::Signing(e.to_string()))
+    }
+
+    /// Sets the aggregate signature of a channel's funding input.
+    pub fn set_signature(&mut self, channel_id: &[u8; 32], signature: &[u8; 64]) -> Result<(), BatchError> {
+        let index = self
+            .records
+            .iter()
+            .find(|record| &record.channel_id == channel_id)
+            .map(|record| record.input_index as usize)
+            .ok_or_else(|| BatchError::InvalidChannel(hex::encode(channel_id), "Not in batch".to_string()))?;
+        self.tx.input[index].witness = Witness::from_slice(&[signature.to_vec()]);
+        Ok(())
+    }
+}
+
+fn output_weight(output: &TxOut) -> u64 {
+    let script_len = output.script_pubkey.len() as u64;
+    4 * (8 + bitcoin::VarInt(script_len).len() as u64 + script_len)
+}
+
+/// Builds one transaction spending every channel's funding output to its payouts.
+///
+/// Each channel pays for its own input and outputs (the first also pays the transaction
+/// overhead), first from its fee reserve and then from its largest payout, so settling in
+/// a batch never shifts fees between channels. The fee rate comes from `fee_estimator`
+/// for confirmation within `target_blocks`.
+pub fn build_batch_settlement(
+    channels: &[ChannelPayout],
+    fee_estimator: &dyn FeeEstimator,
+    target_blocks: u16,
+) -> Result<BatchSettlement, BatchError> {
+    if channels.is_empty() {
+        return Err(BatchError::Empty);
+    }
+    let fee_rate = fee_estimator
+        .estimate_fee_rate(target_blocks)
+        .map_err(|e| BatchError::Fee(e.to_string()))?;
+    let mut ids = HashSet::new();
+    let mut outpoints = HashSet::new();
+    for channel in channels {
+        if !ids.insert(channel.channel_id) || !outpoints.insert(channel.funding_outpoint) {
+            return Err(BatchError::Duplicate(hex::encode(channel.channel_id)));
+        }
+    }
+
+    let mut input = Vec::new();
+    let mut output: Vec<TxOut> = Vec::new();
+    let mut prevouts = Vec::new();
+    let mut records = Vec::new();
+
+    for (index, channel) in channels.iter().enumerate() {
+        let id = hex::encode(channel.channel_id);
+        let invalid = |reason: &str| BatchError::InvalidChannel(id.clone(), reason.to_string());
+        if channel.payouts.is_empty() {
+            return Err(invalid("No payouts"));
+        }
+        let assigned = channel
+            .payouts
+            .iter()
+            .try_fold(0u64, |acc, payout| acc.checked_add(payout.value))
+            .filter(|assigned| *assigned <= channel.funding_output.value)
+            .ok_or_else(|| invalid("Payouts exceed the funding value"))?;
+
+        let mut weight = KEY_SPEND_INPUT_WEIGHT + channel.payouts.iter().map(output_weight).sum::<u64>();
+        if index == 0 {
+            weight += BASE_WEIGHT;
+        }
+        let fee = fee_for_vsize(fee_rate, weight.div_ceil(4)).map_err(|e| BatchError::Fee(e.to_string()))?;
+
+        let mut payouts = channel.payouts.clone();
+        let reserve = channel.funding_output.value - assigned;
+        if fee > reserve {
+            let largest = (0..payouts.len())
+                .max_by_key(|i| (payouts[*i].value, std::cmp::Reverse(*i)))
+                .expect("non-empty payouts");
+            let payout = &mut payouts[largest];
+            let dust_limit = payout.script_pubkey.dust_value().to_sat();
+            payout.value = payout
+                .value
+                .checked_sub(fee - reserve)
+                .filter(|value| *value >= dust_limit)
+                .ok_or_else(|| invalid("Balance cannot cover its share of the fee"))?;
+        }
+
+        let first_output = output.len() as u32;
+        records.push(SettlementRecord {
+            channel_id: channel.channel_id,
+            txid: Txid::from_raw_hash(bitcoin::hashes::Hash::all_zeros()),
+            input_index: index as u32,
+            output_indices: (first_output..first_output + payouts.len() as u32).collect(),
+            output_values: payouts.iter().map(|payout| payout.value).collect(),
+            fee_share: channel.funding_output.value - payouts.iter().map(|payout| payout.value).sum::<u64>(),
+        });
+        input.push(TxIn {
+            previous_output: channel.funding_outpoint,
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            witness: Witness::new(),
+        });
+        prevouts.push(channel.funding_output.clone());
+        output.extend(payouts);
+    }
+
+    let tx = Transaction {
+        version: 2,
+        lock_time: bitcoin::absolute::LockTime::ZERO,
+        input,
+        output,
+    };
+    let txid = tx.txid();
+    for record in &mut records {
+        record.txid = txid;
+    }
+    Ok(BatchSettlement { tx, prevouts, records })
+}
+
+/// Persistent per-channel audit trail of batch settlements.
+pub struct SettlementLog {
+    db: Arc<OverpassDB>,
+}
+
+impl SettlementLog {
+    pub fn new(db: Arc<OverpassDB>) -> Self {
+        Self { db }
+    }
+
+    /// Stores the record of every channel in the batch.
+    pub fn record(&self, batch: &BatchSettlement) -> Result<(), BatchError> {
+        for record in &batch.records {
+            let bytes = serde_json::to_vec(record)
+                .map_err(|e| BatchError::SerializationError(e.to_string()))?;
+            self.db
+                .put(&settlement_key(&record.channel_id), &bytes)
+                .map_err(|e| BatchError::StorageError(e.to_string()))?;
+        }
+        self.db
+            .flush()
+            .map_err(|e| BatchError::StorageError(e.to_string()))
+    }
+
+    /// Gets how a channel was settled.
+    pub fn for_channel(&self, channel_id: &[u8; 32]) -> Result<Option<SettlementRecord>, BatchError> {
+        self.db
+            .get(&settlement_key(channel_id))
+            .map_err(|e| BatchError::StorageError(e.to_string()))?
+            .map(|bytes| {
+                serde_json::from_slice(&bytes).map_err(|e| BatchError::SerializationError(e.to_string()))
+            })
+            .transpose()
+    }
+}
+
+fn settlement_key(channel_id: &[u8; 32]) -> Vec<u8> {
+    let mut key = SETTLEMENT_PREFIX.to_vec();
+    key.extend_from_slice(hex::encode(channel_id).as_bytes());
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::fees::StaticFeeEstimator;
+    use bitcoin::hashes::Hash;
+    use bitcoin::{FeeRate, WPubkeyHash};
+
+    fn p2wpkh(byte: u8) -> ScriptBuf {
+        ScriptBuf::new_v0_p2wpkh(&WPubkeyHash::from_byte_array([byte; 20]))
+    }
+
+    fn channel(byte: u8, funding: u64, payouts: &[u64]) -> ChannelPayout {
+        ChannelPayout {
+            channel_id: [byte; 32],
+            funding_outpoint: OutPoint::new(Txid::from_byte_array([byte; 32]), 0),
+            funding_output: TxOut {
+                value: funding,
+                script_pubkey: ScriptBuf::new_v1_p2tr_tweaked(bitcoin::key::TweakedPublicKey::dangerous_assume_tweaked(
+                    bitcoin::secp256k1::XOnlyPublicKey::from_slice(&[
+                        0x79, 0xbe, 0x66, 0x7e, 0xf9, 0xdc, 0xbb, 0xac, 0x55, 0xa0, 0x62, 0x95, 0xce, 0x87, 0x0b, 0x07,
+                        0x02, 0x9b, 0xfc, 0xdb, 0x2d, 0xce, 0x28, 0xd9, 0x59, 0xf2, 0x81, 0x5b, 0x16, 0xf8, 0x17, 0x98,
+                    ])
+                    .unwrap(),
+                )),
+            },
+            payouts: payouts
+                .iter()
+                .enumerate()
+                .map(|(i, value)| TxOut {
+                    value: *value,
+                    script_pubkey: p2wpkh(byte + i as u8),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_batch_maps_channels_to_outputs() {
+        let estimator = StaticFeeEstimator::new(FeeRate::from_sat_per_vb_unchecked(10));
+        let batch = build_batch_settlement(
+            &[channel(1, 101_000, &[60_000, 39_000]), channel(2, 50_000, &[50_000])],
+            &estimator,
+            6,
+        )
+        .unwrap();
+
+        assert_eq!(batch.tx.input.len(), 2);
+        assert_eq!(batch.tx.output.len(), 3);
+        assert_eq!(batch.records[0].output_indices, vec![0, 1]);
+        assert_eq!(batch.records[1].output_indices, vec![2]);
+        assert!(batch.records.iter().all(|record| record.txid == batch.tx.txid()));
+
+        // Channel 1's 2,000 sat reserve covers its share; channel 2 pays from its payout.
+        assert_eq!(batch.records[0].output_values, vec![60_000, 39_000]);
+        assert_eq!(batch.records[0].fee_share, 2_000);
+        assert!(batch.tx.output[2].value < 50_000);
+
+        let funding: u64 = batch.prevouts.iter().map(|prevout| prevout.value).sum();
+        let paid: u64 = batch.tx.output.iter().map(|output| output.value).sum();
+        assert_eq!(funding - paid, batch.fee());
+
+        let mut signed = batch.clone();
+        for record in &batch.records {
+            signed.set_signature(&record.channel_id, &[7u8; 64]).unwrap();
+        }
+        assert!(batch.fee() >= signed.tx.vsize() as u64 * 10);
+        assert!(batch.signing_message(&[1u8; 32]).is_ok());
+        assert!(batch.signing_message(&[9u8; 32]).is_err());
+    }
+
+    #[test]
+    fn test_rejects_invalid_batches() {
+        let estimator = StaticFeeEstimator::new(FeeRate::from_sat_per_vb_unchecked(10));
+        assert!(matches!(build_batch_settlement(&[], &estimator, 6), Err(BatchError::Empty)));
+        assert!(matches!(
+            build_batch_settlement(&[channel(1, 1_000, &[500]), channel(1, 1_000, &[500])], &estimator, 6),
+            Err(BatchError::Duplicate(_))
+        ));
+        assert!(matches!(
+            build_batch_settlement(&[channel(1, 1_000, &[2_000])], &estimator, 6),
+            Err(BatchError::InvalidChannel(..))
+        ));
+        assert!(matches!(
+            build_batch_settlement(&[channel(1, 1_000, &[1_000])], &estimator, 6),
+            Err(BatchError::InvalidChannel(..))
+        ));
+    }
+
+    #[test]
+    fn test_settlement_log_roundtrip() -> anyhow::Result<()> {
+        let path = std::env::temp_dir().join(format!("batch_settlement_{}", std::process::id()));
+        let db = Arc::new(OverpassDB::new(path.to_str().unwrap())?);
+        let log = SettlementLog::new(db);
+        let batch = build_batch_settlement(
+            &[channel(1, 100_000, &[60_000, 39_000]), channel(2, 50_000, &[50_000])],
+            &StaticFeeEstimator::new(FeeRate::from_sat_per_vb_unchecked(2)),
+            6,
+        )
+        .unwrap();
+
+        log.record(&batch)?;
+        assert_eq!(log.for_channel(&[2u8; 32])?, Some(batch.records[1].clone()));
+        assert_eq!(log.for_channel(&[3u8; 32])?, None);
+
+        std::fs::remove_dir_all(&path).ok();
+        Ok(())
+    }
+}
@@ -3,9 +3,11 @@
 use bitcoin::hashes::{sha256d, Hash, HashEngine};
 use bitcoin::secp256k1::PublicKey;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
 use thiserror::Error;
-use tokio::sync::RwLock;
 
 /// Represents a stealth address.
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -214,7 +216,10 @@ impl BitcoinLockState {
             return Ok(false);
         }
 
-        let mut cache = self.state_cache.write().await;
+        let mut cache = self
+            .state_cache
+            .write()
+            .map_err(|_| BitcoinStateError::StateError("State cache lock poisoned".to_string()))?;
         cache.insert(
             next_hash,
             serde_json::to_vec(next_state)
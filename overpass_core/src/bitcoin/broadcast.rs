@@ -0,0 +1,341 @@
+// src/bitcoin/broadcast.rs
+
+use crate::bitcoin::chain::ChainBackend;
+#[cfg(feature = "tokio")]
+use crate::bitcoin::chain::AsyncChain;
+use crate::services::overpass_db::OverpassDB;
+#[cfg(feature = "tokio")]
+use crate::utils::blocking::run_blocking;
+use bitcoin::{Transaction, Txid};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Key prefix under which queued transactions are persisted.
+const QUEUE_PREFIX: &[u8] = b"broadcast_queue:";
+
+#[derive(Error, Debug)]
+pub enum BroadcastError {
+    #[error("Storage error: {0}")]
+    StorageError(String),
+    #[error("Serialization error: {0}")]
+    SerializationError(String),
+}
+
+/// Retry and rebroadcast timing for queued transactions.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// Delay before the first retry after a failed broadcast.
+    pub initial_backoff_secs: u64,
+    /// Upper bound on the exponential backoff.
+    pub max_backoff_secs: u64,
+    /// Interval between rebroadcasts of an accepted but unconfirmed transaction.
+    pub rebroadcast_interval_secs: u64,
+    /// Confirmations after which a transaction is dropped from the queue.
+    pub required_confirmations: u32,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            initial_backoff_secs: 30,
+            max_backoff_secs: 3_600,
+            rebroadcast_interval_secs: 600,
+            required_confirmations: 1,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Gets the backoff after `failures` consecutive failed attempts.
+    pub fn backoff_secs(&self, failures: u32) -> u64 {
+        let factor = 1u64.checked_shl(failures.saturating_sub(1)).unwrap_or(u64::MAX);
+        self.initial_backoff_secs
+            .saturating_mul(factor)
+            .min(self.max_backoff_secs)
+    }
+}
+
+/// A transaction waiting to be broadcast or confirmed.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct QueuedTransaction {
+    pub tx: Transaction,
+    pub enqueued_at: u64,
+    pub next_attempt_at: u64,
+    /// Consecutive failed broadcast attempts.
+    pub failures: u32,
+    /// Whether the network has accepted the transaction at least once.
+    pub accepted: bool,
+    pub last_error: Option<String>,
+}
+
+/// Result of processing one queued transaction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BroadcastOutcome {
+    Broadcast(Txid),
+    Failed(Txid, String),
+    Confirmed(Txid),
+}
+
+/// Persistent queue that retries failed broadcasts and rebroadcasts until confirmation.
+///
+/// Entries live in `OverpassDB`, so a transaction queued before the app is killed is picked
+/// up again on the next start.
+pub struct BroadcastQueue {
+    db: Arc<OverpassDB>,
+    policy: RetryPolicy,
+}
+
+impl BroadcastQueue {
+    pub fn new(db: Arc<OverpassDB>, policy: RetryPolicy) -> Self {
+        Self { db, policy }
+    }
+
+    /// Adds a transaction to the queue; it is attempted on the next `process` call.
+    pub fn enqueue(&self, tx: Transaction, now: u64) -> Result<Txid, BroadcastError> {
+        let txid = tx.txid();
+        if self.get(&txid)?.is_some() {
+            return Ok(txid);
+        }
+        self.store(&QueuedTransaction {
+            tx,
+            enqueued_at: now,
+            next_attempt_at: now,
+            failures: 0,
+            accepted: false,
+            last_error: None,
+        })?;
+        Ok(txid)
+    }
+
+    /// Gets a queued transaction by txid.
+    pub fn get(&self, txid: &Txid) -> Result<Option<QueuedTransaction>, BroadcastError> {
+        self.db
+            .get(&queue_key(txid))
+            .map_err(|e| BroadcastError::StorageError(e.to_string()))?
+            .map(|bytes| {
+                serde_json::from_slice(&bytes)
+                    .map_err(|e| BroadcastError::SerializationError(e.to_string()))
+            })
+            .transpose()
+    }
+
+    /// Lists every queued transaction.
+    pub fn pending(&self) -> Result<Vec<QueuedTransaction>, BroadcastError> {
+        let mut end = QUEUE_PREFIX.to_vec();
+        *end.last_mut().expect("non-empty prefix") += 1;
+        self.db
+            .scan(QUEUE_PREFIX, &end)
+            .map_err(|e| BroadcastError::StorageError(e.to_string()))?
+            .into_iter()
+            .map(|(_, bytes)| {
+                serde_json::from_slice(&bytes)
+                    .map_err(|e| BroadcastError::SerializationError(e.to_string()))
+            })
+            .collect()
+    }
+
+    /// Drops a transaction from the queue, e.g. after it was replaced.
+    pub fn remove(&self, txid: &Txid) -> Result<(), BroadcastError> {
+        self.db
+            .delete(&queue_key(txid))
+            .map_err(|e| BroadcastError::StorageError(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Attempts every due transaction against the backend.
+    pub fn process(
+        &self,
+        backend: &dyn ChainBackend,
+        now: u64,
+    ) -> Result<Vec<BroadcastOutcome>, BroadcastError> {
+        let mut outcomes = Vec::new();
+        for mut entry in self.pending()? {
+            if entry.next_attempt_at > now {
+                continue;
+            }
+            let txid = entry.tx.txid();
+
+            if entry.accepted {
+                if let Ok(Some(confirmations)) = backend.confirmations(&txid) {
+                    if confirmations >= self.policy.required_confirmations {
+                        self.remove(&txid)?;
+                        outcomes.push(BroadcastOutcome::Confirmed(txid));
+                        continue;
+                    }
+                }
+            }
+
+            match backend.broadcast(&entry.tx) {
+                Ok(_) => {
+                    entry.accepted = true;
+                    entry.failures = 0;
+                    entry.last_error = None;
+                    entry.next_attempt_at = now + self.policy.rebroadcast_interval_secs;
+                    outcomes.push(BroadcastOutcome::Broadcast(txid));
+                }
+                Err(e) => {
+                    entry.failures += 1;
+                    entry.last_error = Some(e.to_string());
+                    entry.next_attempt_at = now + self.policy.backoff_secs(entry.failures);
+                    outcomes.push(BroadcastOutcome::Failed(txid, e.to_string()));
+                }
+            }
+            self.store(&entry)?;
+        }
+        self.db
+            .flush()
+            .map_err(|e| BroadcastError::StorageError(e.to_string()))?;
+        Ok(outcomes)
+    }
+
+    /// Attempts every due transaction like `process`, on tokio's blocking pool.
+    #[cfg(feature = "tokio")]
+    pub async fn process_async(
+        &self,
+        backend: &AsyncChain,
+        now: u64,
+    ) -> Result<Vec<BroadcastOutcome>, BroadcastError> {
+        let queue = Self::new(self.db.clone(), self.policy.clone());
+        let backend = backend.backend().clone();
+        run_blocking(move || queue.process(backend.as_ref(), now)).await
+    }
+
+    fn store(&self, entry: &QueuedTransaction) -> Result<(), BroadcastError> {
+        let bytes = serde_json::to_vec(entry)
+            .map_err(|e| BroadcastError::SerializationError(e.to_string()))?;
+        self.db
+            .put(&queue_key(&entry.tx.txid()), &bytes)
+            .map_err(|e| BroadcastError::StorageError(e.to_string()))?;
+        Ok(())
+    }
+}
+
+fn queue_key(txid: &Txid) -> Vec<u8> {
+    let mut key = QUEUE_PREFIX.to_vec();
+    key.extend_from_slice(txid.to_string().as_bytes());
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::chain::ChainError;
+    use bitcoin::absolute::LockTime;
+    use std::sync::Mutex;
+
+    /// Backend that fails until `online` is set and reports scripted confirmations.
+    struct MockBackend {
+        online: Mutex<bool>,
+        confirmations: Mutex<Option<u32>>,
+    }
+
+    impl ChainBackend for MockBackend {
+        fn broadcast(&self, tx: &Transaction) -> Result<Txid, ChainError> {
+            if *self.online.lock().unwrap() {
+                Ok(tx.txid())
+            } else {
+                Err(ChainError::BackendError("offline".to_string()))
+            }
+        }
+
+        fn confirmations(&self, _txid: &Txid) -> Result<Option<u32>, ChainError> {
+            Ok(*self.confirmations.lock().unwrap())
+        }
+
+        fn tip_height(&self) -> Result<u32, ChainError> {
+            Ok(0)
+        }
+
+        fn spending_transaction(
+            &self,
+            _outpoint: &bitcoin::OutPoint,
+        ) -> Result<Option<Transaction>, ChainError> {
+            Ok(None)
+        }
+    }
+
+    fn test_tx() -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: LockTime::from_height(42).unwrap(),
+            input: vec![],
+            output: vec![],
+        }
+    }
+
+    #[test]
+    fn test_retry_with_backoff_and_restart() -> anyhow::Result<()> {
+        let path = std::env::temp_dir().join(format!("broadcast_queue_{}", std::process::id()));
+        let backend = MockBackend {
+            online: Mutex::new(false),
+            confirmations: Mutex::new(None),
+        };
+        let policy = RetryPolicy::default();
+
+        let db = Arc::new(OverpassDB::new(path.to_str().unwrap())?);
+        let queue = BroadcastQueue::new(db.clone(), policy.clone());
+        let txid = queue.enqueue(test_tx(), 1_000)?;
+        assert!(matches!(queue.process(&backend, 1_000)?[..], [BroadcastOutcome::Failed(..)]));
+        // Not due again until the backoff elapses.
+        assert!(queue.process(&backend, 1_010)?.is_empty());
+        assert_eq!(queue.get(&txid)?.unwrap().next_attempt_at, 1_030);
+        db.flush()?;
+        drop((queue, db));
+
+        // A queue over the reopened database picks up the persisted entry.
+        let db = Arc::new(OverpassDB::new(path.to_str().unwrap())?);
+        let queue = BroadcastQueue::new(db.clone(), policy);
+        assert_eq!(queue.pending()?.len(), 1);
+        *backend.online.lock().unwrap() = true;
+        assert_eq!(queue.process(&backend, 1_030)?, vec![BroadcastOutcome::Broadcast(txid)]);
+
+        *backend.confirmations.lock().unwrap() = Some(1);
+        assert_eq!(queue.process(&backend, 1_630)?, vec![BroadcastOutcome::Confirmed(txid)]);
+        assert!(queue.pending()?.is_empty());
+
+        drop((queue, db));
+        std::fs::remove_dir_all(&path)?;
+        Ok(())
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_queue_broadcasts_off_the_runtime() -> anyhow::Result<()> {
+        let path = std::env::temp_dir().join(format!("broadcast_async_{}", std::process::id()));
+        let backend = Arc::new(MockBackend {
+            online: Mutex::new(true),
+            confirmations: Mutex::new(None),
+        });
+        let chain = AsyncChain::new(backend.clone());
+        let db = Arc::new(OverpassDB::new(path.to_str().unwrap())?);
+        let queue = BroadcastQueue::new(db.clone(), RetryPolicy::default());
+        let txid = queue.enqueue(test_tx(), 1_000)?;
+
+        assert_eq!(
+            queue.process_async(&chain, 1_000).await?,
+            vec![BroadcastOutcome::Broadcast(txid)]
+        );
+        assert!(queue.get(&txid)?.unwrap().accepted);
+        assert!(db.get_async(&queue_key(&txid)).await?.is_some());
+        assert_eq!(chain.confirmations(txid).await?, None);
+        *backend.confirmations.lock().unwrap() = Some(1);
+        assert_eq!(
+            queue.process_async(&chain, 1_600).await?,
+            vec![BroadcastOutcome::Confirmed(txid)]
+        );
+        assert_eq!(db.scan_async(QUEUE_PREFIX, b"broadcast_queue;").await?, Vec::new());
+
+        drop((queue, db));
+        std::fs::remove_dir_all(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_backoff_is_capped() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.backoff_secs(1), 30);
+        assert_eq!(policy.backoff_secs(3), 120);
+        assert_eq!(policy.backoff_secs(64), 3_600);
+    }
+}
@@ -0,0 +1,228 @@
+// src/bitcoin/chain.rs
+
+#[cfg(feature = "bitcoin-backend")]
+use crate::bitcoin::esplora::{EsploraClient, EsploraError};
+#[cfg(feature = "tokio")]
+use crate::utils::blocking::run_blocking;
+use bitcoin::{OutPoint, Transaction, Txid};
+#[cfg(feature = "bitcoin-backend")]
+use bitcoincore_rpc::RpcApi;
+#[cfg(feature = "bitcoin-backend")]
+use serde::Deserialize;
+#[cfg(feature = "tokio")]
+use std::sync::Arc;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ChainError {
+    #[error("Backend error: {0}")]
+    BackendError(String),
+    #[error("Broadcast rejected: {0}")]
+    Rejected(String),
+}
+
+#[cfg(feature = "bitcoin-backend")]
+impl From<EsploraError> for ChainError {
+    fn from(e: EsploraError) -> Self {
+        ChainError::BackendError(e.to_string())
+    }
+}
+
+/// Read/broadcast access to the Bitcoin network, shared by Core RPC and Esplora backends.
+pub trait ChainBackend: Send + Sync {
+    /// Submits a transaction to the network.
+    fn broadcast(&self, tx: &Transaction) -> Result<Txid, ChainError>;
+
+    /// Gets a transaction's confirmation count: `None` if unknown, `Some(0)` if in the mempool.
+    fn confirmations(&self, txid: &Txid) -> Result<Option<u32>, ChainError>;
+
+    /// Gets the height of the backend's best block.
+    fn tip_height(&self) -> Result<u32, ChainError>;
+
+    /// Gets the transaction spending `outpoint`, from the mempool or the chain.
+    fn spending_transaction(&self, outpoint: &OutPoint) -> Result<Option<Transaction>, ChainError>;
+}
+
+/// A chain backend behind async calls. Each call runs on tokio's blocking pool, so a
+/// slow node or Esplora server holds up a blocking thread rather than the runtime.
+#[cfg(feature = "tokio")]
+#[derive(Clone)]
+pub struct AsyncChain {
+    backend: Arc<dyn ChainBackend>,
+}
+
+#[cfg(feature = "tokio")]
+impl AsyncChain {
+    pub fn new(backend: Arc<dyn ChainBackend>) -> Self {
+        Self { backend }
+    }
+
+    pub fn backend(&self) -> &Arc<dyn ChainBackend> {
+        &self.backend
+    }
+
+    pub async fn broadcast(&self, tx: Transaction) -> Result<Txid, ChainError> {
+        let backend = self.backend.clone();
+        run_blocking(move || backend.broadcast(&tx)).await
+    }
+
+    pub async fn confirmations(&self, txid: Txid) -> Result<Option<u32>, ChainError> {
+        let backend = self.backend.clone();
+        run_blocking(move || backend.confirmations(&txid)).await
+    }
+
+    pub async fn tip_height(&self) -> Result<u32, ChainError> {
+        let backend = self.backend.clone();
+        run_blocking(move || backend.tip_height()).await
+    }
+
+    pub async fn spending_transaction(
+        &self,
+        outpoint: OutPoint,
+    ) -> Result<Option<Transaction>, ChainError> {
+        let backend = self.backend.clone();
+        run_blocking(move || backend.spending_transaction(&outpoint)).await
+    }
+}
+
+#[cfg(feature = "bitcoin-backend")]
+#[derive(Deserialize)]
+struct EsploraTxStatus {
+    confirmed: bool,
+    block_height: Option<u32>,
+}
+
+#[cfg(feature = "bitcoin-backend")]
+#[derive(Deserialize)]
+struct EsploraOutspend {
+    spent: bool,
+    txid: Option<Txid>,
+}
+
+#[cfg(feature = "bitcoin-backend")]
+impl ChainBackend for EsploraClient {
+    #[tracing::instrument(
+        name = "broadcast",
+        skip_all,
+        fields(backend = "esplora", txid = %tx.txid()),
+        err
+    )]
+    fn broadcast(&self, tx: &Transaction) -> Result<Txid, ChainError> {
+        EsploraClient::broadcast(self, tx).map_err(|e| ChainError::Rejected(e.to_string()))
+    }
+
+    fn confirmations(&self, txid: &Txid) -> Result<Option<u32>, ChainError> {
+        let status: EsploraTxStatus = match self.get_json(&format!("/tx/{}/status", txid)) {
+            Ok(status) => status,
+            Err(EsploraError::HttpError(e)) if e.contains("404") => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        match (status.confirmed, status.block_height) {
+            (true, Some(height)) => Ok(Some(self.get_tip_height()?.saturating_sub(height) + 1)),
+            _ => Ok(Some(0)),
+        }
+    }
+
+    fn tip_height(&self) -> Result<u32, ChainError> {
+        Ok(self.get_tip_height()?)
+    }
+
+    fn spending_transaction(&self, outpoint: &OutPoint) -> Result<Option<Transaction>, ChainError> {
+        let outspend: EsploraOutspend =
+            self.get_json(&format!("/tx/{}/outspend/{}", outpoint.txid, outpoint.vout))?;
+        match (outspend.spent, outspend.txid) {
+            (true, Some(txid)) => Ok(Some(self.get_consensus(&format!("/tx/{}/hex", txid))?)),
+            _ => Ok(None),
+        }
+    }
+}
+
+#[cfg(feature = "bitcoin-backend")]
+#[derive(Deserialize)]
+struct CoreOutspend {
+    spendingtxid: Option<Txid>,
+}
+
+#[cfg(feature = "bitcoin-backend")]
+impl ChainBackend for bitcoincore_rpc::Client {
+    #[tracing::instrument(
+        name = "broadcast",
+        skip_all,
+        fields(backend = "core", txid = %tx.txid()),
+        err
+    )]
+    fn broadcast(&self, tx: &Transaction) -> Result<Txid, ChainError> {
+        self.send_raw_transaction(tx)
+            .map_err(|e| ChainError::Rejected(e.to_string()))
+    }
+
+    fn confirmations(&self, txid: &Txid) -> Result<Option<u32>, ChainError> {
+        match self.get_raw_transaction_info(txid, None) {
+            Ok(info) => Ok(Some(info.confirmations.unwrap_or(0))),
+            Err(bitcoincore_rpc::Error::JsonRpc(_)) => Ok(None),
+            Err(e) => Err(ChainError::BackendError(e.to_string())),
+        }
+    }
+
+    fn tip_height(&self) -> Result<u32, ChainError> {
+        self.get_block_count()
+            .map(|height| height as u32)
+            .map_err(|e| ChainError::BackendError(e.to_string()))
+    }
+
+    fn spending_transaction(&self, outpoint: &OutPoint) -> Result<Option<Transaction>, ChainError> {
+        let unspent = self
+            .get_tx_out(&outpoint.txid, outpoint.vout, Some(true))
+            .map_err(|e| ChainError::BackendError(e.to_string()))?;
+        if unspent.is_some() {
+            return Ok(None);
+        }
+
+        // Mempool spends are indexed by Core itself (v24+).
+        let spending: Vec<CoreOutspend> = self
+            .call(
+                "gettxspendingprevout",
+                &[serde_json::json!([{ "txid": outpoint.txid, "vout": outpoint.vout }])],
+            )
+            .map_err(|e| ChainError::BackendError(e.to_string()))?;
+        if let Some(txid) = spending.into_iter().find_map(|outspend| outspend.spendingtxid) {
+            return self
+                .get_raw_transaction(&txid, None)
+                .map(Some)
+                .map_err(|e| ChainError::BackendError(e.to_string()));
+        }
+
+        // A confirmed spend can only be in a block after the one confirming the outpoint.
+        // Locating that block needs `-txindex`.
+        let funding = self
+            .get_raw_transaction_info(&outpoint.txid, None)
+            .map_err(|e| ChainError::BackendError(e.to_string()))?;
+        let Some(funding_block) = funding.blockhash else {
+            return Ok(None);
+        };
+        let start = self
+            .get_block_header_info(&funding_block)
+            .map_err(|e| ChainError::BackendError(e.to_string()))?
+            .height as u64;
+        let tip = self
+            .get_block_count()
+            .map_err(|e| ChainError::BackendError(e.to_string()))?;
+        for height in start..=tip {
+            let block = self
+                .get_block_hash(height)
+                .and_then(|hash| self.get_block(&hash))
+                .map_err(|e| ChainError::BackendError(e.to_string()))?;
+            if let Some(tx) = block
+                .txdata
+                .into_iter()
+                .find(|tx| tx.input.iter().any(|input| input.previous_output == *outpoint))
+            {
+                return Ok(Some(tx));
+            }
+        }
+        Err(ChainError::BackendError(format!(
+            "{} is spent but no spending transaction was found",
+            outpoint
+        )))
+    }
+}
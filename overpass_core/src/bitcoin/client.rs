@@ -10,8 +10,10 @@ use bitcoin::{
     script::Builder,
     OutPoint, Sequence, Transaction, TxIn, TxOut, Witness,
 };
-use std::{collections::HashMap, sync::Arc};
-use tokio::sync::RwLock;
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
 
 /// Represents a Bitcoin client managing state and operations.
 #[derive(Debug, Clone)]
@@ -144,14 +146,20 @@ impl BitcoinClient {
         state_hash: [u8; 32],
         state_data: Vec<u8>,
     ) -> Result<(), String> {
-        let mut cache = self.state_cache.write().await;
+        let mut cache = self
+            .state_cache
+            .write()
+            .map_err(|_| "State cache lock poisoned".to_string())?;
         cache.insert(state_hash, state_data);
         Ok(())
     }
 
     /// Retrieves a state from the cache.
-    pub async fn get_cached_state(&self, state_hash: [u8; 32]) -> Option<Vec<u8>> {
-        let cache = self.state_cache.read().await;
-        cache.get(&state_hash).cloned()
+    pub async fn get_cached_state(&self, state_hash: [u8; 32]) -> Result<Option<Vec<u8>>, String> {
+        let cache = self
+            .state_cache
+            .read()
+            .map_err(|_| "State cache lock poisoned".to_string())?;
+        Ok(cache.get(&state_hash).cloned())
     }
 }
\ No newline at end of file
@@ -0,0 +1,306 @@
+// src/bitcoin/commitment.rs
+
+use crate::bitcoin::anchors::{add_anchor_outputs, AnchorError};
+use crate::bitcoin::dust::{apply_dust_policy, DustError, DustPolicy};
+use crate::bitcoin::monitor::{apply_state_number, StateNumberError};
+use crate::bitcoin::taproot::dispute_script;
+use crate::bitcoin::timelocks::ChannelTimelocks;
+use crate::zkp::channel::ChannelState;
+use bitcoin::secp256k1::{Secp256k1, Verification, XOnlyPublicKey};
+use bitcoin::taproot::{ControlBlock, LeafVersion, TaprootBuilder, TaprootSpendInfo};
+use bitcoin::{OutPoint, PublicKey, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Witness};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum CommitmentError {
+    #[error("Channel state has no proof")]
+    Unproven,
+    #[error("Invalid channel state: {0}")]
+    InvalidState(String),
+    #[error("State number: {0}")]
+    StateNumber(#[from] StateNumberError),
+    #[error("Taproot construction failed: {0}")]
+    Taproot(String),
+    #[error("Anchor error: {0}")]
+    Anchor(#[from] AnchorError),
+    #[error("Dust error: {0}")]
+    Dust(#[from] DustError),
+}
+
+/// One party's keys in a commitment transaction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CommitmentParty {
+    /// Key that claims the party's output once the dispute window has passed.
+    pub payout_key: XOnlyPublicKey,
+    /// Key path of the party's output, used by the counterparty to punish revoked states.
+    pub revocation_key: XOnlyPublicKey,
+    /// Key of the party's anchor output, when anchors are enabled.
+    pub anchor_key: PublicKey,
+}
+
+/// Static parameters shared by every commitment of a channel.
+#[derive(Clone, Debug)]
+pub struct CommitmentParams {
+    pub funding_outpoint: OutPoint,
+    pub funding_value: u64,
+    pub timelocks: ChannelTimelocks,
+    /// Parties in the order of `ChannelState::balances`; the first is the funder and pays
+    /// for the anchors.
+    pub parties: [CommitmentParty; 2],
+    pub anchors: bool,
+    /// How balances below the dust limit are settled.
+    pub dust_policy: DustPolicy,
+}
+
+/// Builds a party's commitment output: a key path for the counterparty's revocation key
+/// and a single leaf paying the owner after the dispute delay.
+pub fn commitment_output_spend_info<C: Verification>(
+    secp: &Secp256k1<C>,
+    party: &CommitmentParty,
+    dispute_delay: u16,
+) -> Result<TaprootSpendInfo, CommitmentError> {
+    TaprootBuilder::new()
+        .add_leaf(0, dispute_script(&party.payout_key, dispute_delay))
+        .map_err(|e| CommitmentError::Taproot(e.to_string()))?
+        .finalize(secp, party.revocation_key)
+        .map_err(|_| CommitmentError::Taproot("Taproot tree is incomplete".to_string()))
+}
+
+/// A commitment transaction mapped from a channel state.
+#[derive(Clone, Debug)]
+pub struct Commitment {
+    pub tx: Transaction,
+    pub state_number: u64,
+    /// Output index of each party's balance; `None` if it was dust and folded away.
+    pub party_outputs: [Option<u32>; 2],
+    spend_info: [TaprootSpendInfo; 2],
+    payout_keys: [XOnlyPublicKey; 2],
+    dispute_delay: u16,
+}
+
+impl Commitment {
+    /// Deterministically builds the commitment for `state`.
+    ///
+    /// The funding input and party outputs are placed in a fixed order and the state nonce
+    /// is encoded into the lock time and sequence, so both parties derive byte-identical
+    /// transactions and the monitor can recognise revoked ones. Any funding value not
+    /// assigned to a balance is the commitment fee.
+    pub fn from_state<C: Verification>(
+        secp: &Secp256k1<C>,
+        params: &CommitmentParams,
+        state: &ChannelState,
+    ) -> Result<Self, CommitmentError> {
+        if state.proof.is_none() {
+            return Err(CommitmentError::Unproven);
+        }
+        if state.balances.len() != 2 {
+            return Err(CommitmentError::InvalidState(format!(
+                "Expected 2 balances, found {}",
+                state.balances.len()
+            )));
+        }
+        let total = state
+            .balances
+            .iter()
+            .try_fold(0u64, |acc, balance| acc.checked_add(*balance))
+            .filter(|total| *total <= params.funding_value)
+            .ok_or_else(|| CommitmentError::InvalidState("Balances exceed the funding value".to_string()))?;
+        if total == 0 {
+            return Err(CommitmentError::InvalidState("Channel has no balance".to_string()));
+        }
+
+        let delay = params.timelocks.dispute_delay;
+        let spend_info = [
+            commitment_output_spend_info(secp, &params.parties[0], delay)?,
+            commitment_output_spend_info(secp, &params.parties[1], delay)?,
+        ];
+
+        let mut tx = Transaction {
+            version: 2,
+            lock_time: bitcoin::absolute::LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: params.funding_outpoint,
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::MAX,
+                witness: Witness::new(),
+            }],
+            output: Vec::new(),
+        };
+        apply_state_number(&mut tx, state.nonce)?;
+
+        let scripts = spend_info
+            .each_ref()
+            .map(|info| ScriptBuf::new_v1_p2tr_tweaked(info.output_key()));
+        let dust_limits: Vec<u64> = scripts.iter().map(|script| script.dust_value().to_sat()).collect();
+        let values = apply_dust_policy(&state.balances, &dust_limits, params.dust_policy)?;
+
+        let mut party_outputs = [None, None];
+        for (index, (value, script_pubkey)) in values.into_iter().zip(scripts).enumerate() {
+            let Some(value) = value else {
+                continue;
+            };
+            party_outputs[index] = Some(tx.output.len() as u32);
+            tx.output.push(TxOut { value, script_pubkey });
+        }
+
+        if params.anchors {
+            let fee_source = party_outputs[0].ok_or_else(|| {
+                CommitmentError::InvalidState("Funder balance cannot pay for anchors".to_string())
+            })?;
+            add_anchor_outputs(
+                &mut tx,
+                fee_source as usize,
+                &params.parties[0].anchor_key,
+                &params.parties[1].anchor_key,
+            )?;
+        }
+
+        Ok(Self {
+            tx,
+            state_number: state.nonce,
+            party_outputs,
+            spend_info,
+            payout_keys: [params.parties[0].payout_key, params.parties[1].payout_key],
+            dispute_delay: delay,
+        })
+    }
+
+    /// Gets the Taproot spend information of a party's output.
+    pub fn spend_info(&self, party: usize) -> Option<&TaprootSpendInfo> {
+        self.spend_info.get(party)
+    }
+
+    /// Gets the delayed leaf script and control block through which `party` claims its output.
+    pub fn delayed_leaf(&self, party: usize) -> Option<(ScriptBuf, ControlBlock)> {
+        let script = dispute_script(self.payout_keys.get(party)?, self.dispute_delay);
+        let control_block = self
+            .spend_info
+            .get(party)?
+            .control_block(&(script.clone(), LeafVersion::TapScript))?;
+        Some((script, control_block))
+    }
+
+    /// Gets the key that claims a party's output through the delayed leaf.
+    pub fn payout_key(&self, party: usize) -> Option<XOnlyPublicKey> {
+        self.payout_keys.get(party).copied()
+    }
+
+    /// Gets the outpoint of a party's output.
+    pub fn party_outpoint(&self, party: usize) -> Option<OutPoint> {
+        let vout = (*self.party_outputs.get(party)?)?;
+        Some(OutPoint::new(self.tx.txid(), vout))
+    }
+
+    /// Gets the dispute delay a party must wait before claiming its output.
+    pub fn dispute_delay(&self) -> u16 {
+        self.dispute_delay
+    }
+
+    /// Gets the fee paid by the commitment, given the funding value.
+    pub fn fee(&self, funding_value: u64) -> u64 {
+        let outputs: u64 = self.tx.output.iter().map(|output| output.value).sum();
+        funding_value.saturating_sub(outputs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::monitor::state_number_from_tx;
+    use bitcoin::hashes::Hash;
+    use bitcoin::secp256k1::SecretKey;
+    use bitcoin::Txid;
+    use std::collections::BTreeMap;
+
+    fn x_only(byte: u8) -> XOnlyPublicKey {
+        let secp = Secp256k1::new();
+        SecretKey::from_slice(&[byte; 32]).unwrap().x_only_public_key(&secp).0
+    }
+
+    fn party(byte: u8) -> CommitmentParty {
+        let secp = Secp256k1::new();
+        CommitmentParty {
+            payout_key: x_only(byte),
+            revocation_key: x_only(byte + 10),
+            anchor_key: PublicKey::new(SecretKey::from_slice(&[byte + 20; 32]).unwrap().public_key(&secp)),
+        }
+    }
+
+    fn params(anchors: bool) -> CommitmentParams {
+        CommitmentParams {
+            funding_outpoint: OutPoint::new(Txid::all_zeros(), 0),
+            funding_value: 100_000,
+            timelocks: ChannelTimelocks {
+                dispute_delay: 144,
+                expiry_height: 900_000,
+            },
+            parties: [party(1), party(2)],
+            anchors,
+            dust_policy: DustPolicy::FoldIntoFees,
+        }
+    }
+
+    fn state(balances: Vec<u64>, nonce: u64) -> ChannelState {
+        ChannelState {
+            balances,
+            nonce,
+            metadata: vec![],
+            merkle_root: [0u8; 32],
+            proof: Some(vec![1]),
+            assets: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_commitment_is_deterministic() {
+        let secp = Secp256k1::new();
+        let a = Commitment::from_state(&secp, &params(true), &state(vec![60_000, 39_000], 7)).unwrap();
+        let b = Commitment::from_state(&secp, &params(true), &state(vec![60_000, 39_000], 7)).unwrap();
+        assert_eq!(a.tx, b.tx);
+        assert_eq!(state_number_from_tx(&a.tx), Some(7));
+        assert_eq!(a.tx.output.len(), 4);
+        assert_eq!(a.tx.output[0].value, 60_000 - 660);
+        assert_eq!(a.fee(100_000), 1_000);
+
+        let (script, control_block) = a.delayed_leaf(1).unwrap();
+        assert!(control_block.verify_taproot_commitment(
+            &secp,
+            a.spend_info(1).unwrap().output_key().to_inner(),
+            &script
+        ));
+    }
+
+    #[test]
+    fn test_dust_balance_goes_to_fees() {
+        let secp = Secp256k1::new();
+        let commitment = Commitment::from_state(&secp, &params(false), &state(vec![99_000, 100], 2)).unwrap();
+        assert_eq!(commitment.party_outputs, [Some(0), None]);
+        assert_eq!(commitment.party_outpoint(1), None);
+        assert_eq!(commitment.fee(100_000), 1_000);
+
+        let mut folding = params(false);
+        folding.dust_policy = DustPolicy::FoldIntoLargest;
+        let commitment = Commitment::from_state(&secp, &folding, &state(vec![99_000, 100], 2)).unwrap();
+        assert_eq!(commitment.tx.output[0].value, 99_100);
+        assert_eq!(commitment.fee(100_000), 900);
+    }
+
+    #[test]
+    fn test_rejects_invalid_states() {
+        let secp = Secp256k1::new();
+        let mut unproven = state(vec![50_000, 50_000], 1);
+        unproven.proof = None;
+        assert!(matches!(
+            Commitment::from_state(&secp, &params(false), &unproven),
+            Err(CommitmentError::Unproven)
+        ));
+        assert!(matches!(
+            Commitment::from_state(&secp, &params(false), &state(vec![60_000, 50_000], 1)),
+            Err(CommitmentError::InvalidState(_))
+        ));
+        assert!(matches!(
+            Commitment::from_state(&secp, &params(true), &state(vec![0, 99_000], 1)),
+            Err(CommitmentError::InvalidState(_))
+        ));
+    }
+}
@@ -0,0 +1,312 @@
+// src/bitcoin/confirmations.rs
+
+use crate::bitcoin::chain::{ChainBackend, ChainError};
+use bitcoin::Txid;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// On-chain lifecycle of a channel, driven by funding and settlement confirmations.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChannelPhase {
+    /// Funding transaction broadcast but not yet buried deep enough.
+    PendingFunding,
+    Open,
+    /// Settlement transaction broadcast but not yet buried deep enough.
+    Closing,
+    Closed,
+}
+
+/// Which of a channel's transactions a tracked txid is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TrackedKind {
+    Funding,
+    Settlement,
+}
+
+/// Events raised while tracking confirmations.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConfirmationEvent {
+    /// A transaction reached its required depth.
+    Confirmed {
+        channel_id: [u8; 32],
+        txid: Txid,
+        kind: TrackedKind,
+        confirmations: u32,
+    },
+    /// A transaction lost confirmations it had before, so its block was reorged out.
+    Reorged {
+        channel_id: [u8; 32],
+        txid: Txid,
+        kind: TrackedKind,
+        previous_confirmations: u32,
+        confirmations: u32,
+    },
+    PhaseChanged {
+        channel_id: [u8; 32],
+        from: ChannelPhase,
+        to: ChannelPhase,
+    },
+}
+
+#[derive(Clone, Debug)]
+struct TrackedTx {
+    txid: Txid,
+    kind: TrackedKind,
+    confirmations: u32,
+    /// Tip height at which `confirmations` was observed.
+    observed_tip: u32,
+}
+
+#[derive(Clone, Debug)]
+struct TrackedChannel {
+    phase: ChannelPhase,
+    funding: TrackedTx,
+    settlement: Option<TrackedTx>,
+}
+
+/// Tracks funding and settlement depths per channel and rolls phases back on reorgs.
+pub struct ConfirmationTracker {
+    required_confirmations: u32,
+    channels: HashMap<[u8; 32], TrackedChannel>,
+}
+
+impl ConfirmationTracker {
+    /// Creates a tracker treating transactions as final after `required_confirmations`.
+    pub fn new(required_confirmations: u32) -> Self {
+        Self {
+            required_confirmations: required_confirmations.max(1),
+            channels: HashMap::new(),
+        }
+    }
+
+    /// Starts tracking a channel whose funding transaction has been broadcast.
+    pub fn track_funding(&mut self, channel_id: [u8; 32], funding_txid: Txid) {
+        self.channels.insert(
+            channel_id,
+            TrackedChannel {
+                phase: ChannelPhase::PendingFunding,
+                funding: TrackedTx {
+                    txid: funding_txid,
+                    kind: TrackedKind::Funding,
+                    confirmations: 0,
+                    observed_tip: 0,
+                },
+                settlement: None,
+            },
+        );
+    }
+
+    /// Records a broadcast settlement transaction, moving the channel to `Closing`.
+    pub fn track_settlement(&mut self, channel_id: &[u8; 32], settlement_txid: Txid) -> Option<ConfirmationEvent> {
+        let channel = self.channels.get_mut(channel_id)?;
+        channel.settlement = Some(TrackedTx {
+            txid: settlement_txid,
+            kind: TrackedKind::Settlement,
+            confirmations: 0,
+            observed_tip: 0,
+        });
+        let from = channel.phase;
+        channel.phase = ChannelPhase::Closing;
+        (from != ChannelPhase::Closing).then_some(ConfirmationEvent::PhaseChanged {
+            channel_id: *channel_id,
+            from,
+            to: ChannelPhase::Closing,
+        })
+    }
+
+    /// Stops tracking a channel.
+    pub fn untrack(&mut self, channel_id: &[u8; 32]) {
+        self.channels.remove(channel_id);
+    }
+
+    /// Gets a channel's current phase.
+    pub fn phase(&self, channel_id: &[u8; 32]) -> Option<ChannelPhase> {
+        self.channels.get(channel_id).map(|channel| channel.phase)
+    }
+
+    /// Gets the last observed confirmation count of a channel's funding transaction.
+    pub fn funding_confirmations(&self, channel_id: &[u8; 32]) -> Option<u32> {
+        self.channels.get(channel_id).map(|channel| channel.funding.confirmations)
+    }
+
+    /// Refreshes every tracked transaction and returns the resulting events.
+    ///
+    /// A reorg is detected when a transaction has fewer confirmations than the tip
+    /// movement since the last poll allows, which covers both being dropped back to the
+    /// mempool and being re-mined in a later block.
+    pub fn poll(&mut self, backend: &dyn ChainBackend) -> Result<Vec<ConfirmationEvent>, ChainError> {
+        let tip = backend.tip_height()?;
+        let required = self.required_confirmations;
+        let mut events = Vec::new();
+
+        for (channel_id, channel) in self.channels.iter_mut() {
+            let funding_confirmed = refresh(backend, channel_id, &mut channel.funding, tip, required, &mut events)?;
+            let settlement_confirmed = match channel.settlement.as_mut() {
+                Some(settlement) => Some(refresh(backend, channel_id, settlement, tip, required, &mut events)?),
+                None => None,
+            };
+
+            let next = match (funding_confirmed, settlement_confirmed) {
+                (_, Some(true)) => ChannelPhase::Closed,
+                (_, Some(false)) => ChannelPhase::Closing,
+                (true, None) => ChannelPhase::Open,
+                (false, None) => ChannelPhase::PendingFunding,
+            };
+            if next != channel.phase {
+                events.push(ConfirmationEvent::PhaseChanged {
+                    channel_id: *channel_id,
+                    from: channel.phase,
+                    to: next,
+                });
+                channel.phase = next;
+            }
+        }
+        Ok(events)
+    }
+}
+
+/// Updates one transaction's depth, returning whether it meets `required`.
+fn refresh(
+    backend: &dyn ChainBackend,
+    channel_id: &[u8; 32],
+    tracked: &mut TrackedTx,
+    tip: u32,
+    required: u32,
+    events: &mut Vec<ConfirmationEvent>,
+) -> Result<bool, ChainError> {
+    let confirmations = backend.confirmations(&tracked.txid)?.unwrap_or(0);
+    let previous = tracked.confirmations;
+    let expected = if previous == 0 {
+        0
+    } else {
+        previous + tip.saturating_sub(tracked.observed_tip)
+    };
+
+    if previous > 0 && confirmations < expected {
+        events.push(ConfirmationEvent::Reorged {
+            channel_id: *channel_id,
+            txid: tracked.txid,
+            kind: tracked.kind,
+            previous_confirmations: previous,
+            confirmations,
+        });
+    }
+    if confirmations >= required && (previous < required || confirmations < expected) {
+        events.push(ConfirmationEvent::Confirmed {
+            channel_id: *channel_id,
+            txid: tracked.txid,
+            kind: tracked.kind,
+            confirmations,
+        });
+    }
+
+    tracked.confirmations = confirmations;
+    tracked.observed_tip = tip;
+    Ok(confirmations >= required)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::hashes::Hash;
+    use bitcoin::{OutPoint, Transaction};
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct MockChain {
+        tip: Mutex<u32>,
+        confirmations: Mutex<HashMap<Txid, u32>>,
+    }
+
+    impl MockChain {
+        fn set(&self, tip: u32, txid: Txid, confirmations: u32) {
+            *self.tip.lock().unwrap() = tip;
+            self.confirmations.lock().unwrap().insert(txid, confirmations);
+        }
+    }
+
+    impl ChainBackend for MockChain {
+        fn broadcast(&self, tx: &Transaction) -> Result<Txid, ChainError> {
+            Ok(tx.txid())
+        }
+
+        fn confirmations(&self, txid: &Txid) -> Result<Option<u32>, ChainError> {
+            Ok(self.confirmations.lock().unwrap().get(txid).copied())
+        }
+
+        fn tip_height(&self) -> Result<u32, ChainError> {
+            Ok(*self.tip.lock().unwrap())
+        }
+
+        fn spending_transaction(&self, _outpoint: &OutPoint) -> Result<Option<Transaction>, ChainError> {
+            Ok(None)
+        }
+    }
+
+    const CHANNEL: [u8; 32] = [1u8; 32];
+
+    fn phases(events: &[ConfirmationEvent]) -> Vec<(ChannelPhase, ChannelPhase)> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                ConfirmationEvent::PhaseChanged { from, to, .. } => Some((*from, *to)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_funding_reorg_returns_to_pending() {
+        let funding = Txid::from_byte_array([2u8; 32]);
+        let chain = MockChain::default();
+        let mut tracker = ConfirmationTracker::new(3);
+        tracker.track_funding(CHANNEL, funding);
+
+        chain.set(100, funding, 1);
+        assert!(phases(&tracker.poll(&chain).unwrap()).is_empty());
+
+        chain.set(102, funding, 3);
+        let events = tracker.poll(&chain).unwrap();
+        assert!(events.iter().any(|e| matches!(e, ConfirmationEvent::Confirmed { kind: TrackedKind::Funding, .. })));
+        assert_eq!(phases(&events), vec![(ChannelPhase::PendingFunding, ChannelPhase::Open)]);
+
+        // Two blocks replaced: the funding tx is back in the mempool.
+        chain.set(102, funding, 0);
+        let events = tracker.poll(&chain).unwrap();
+        assert!(events.iter().any(|e| matches!(
+            e,
+            ConfirmationEvent::Reorged { previous_confirmations: 3, confirmations: 0, .. }
+        )));
+        assert_eq!(phases(&events), vec![(ChannelPhase::Open, ChannelPhase::PendingFunding)]);
+        assert_eq!(tracker.phase(&CHANNEL), Some(ChannelPhase::PendingFunding));
+    }
+
+    #[test]
+    fn test_settlement_lifecycle_and_remine() {
+        let funding = Txid::from_byte_array([2u8; 32]);
+        let settlement = Txid::from_byte_array([3u8; 32]);
+        let chain = MockChain::default();
+        let mut tracker = ConfirmationTracker::new(2);
+        tracker.track_funding(CHANNEL, funding);
+        chain.set(10, funding, 2);
+        tracker.poll(&chain).unwrap();
+
+        assert!(tracker.track_settlement(&CHANNEL, settlement).is_some());
+        chain.set(12, settlement, 2);
+        let events = tracker.poll(&chain).unwrap();
+        assert_eq!(phases(&events), vec![(ChannelPhase::Closing, ChannelPhase::Closed)]);
+
+        // Re-mined one block later: still buried enough, but the reorg is reported.
+        chain.set(13, settlement, 2);
+        let events = tracker.poll(&chain).unwrap();
+        assert!(events.iter().any(|e| matches!(
+            e,
+            ConfirmationEvent::Reorged { kind: TrackedKind::Settlement, .. }
+        )));
+        assert!(phases(&events).is_empty());
+
+        chain.set(13, settlement, 1);
+        let events = tracker.poll(&chain).unwrap();
+        assert_eq!(phases(&events), vec![(ChannelPhase::Closed, ChannelPhase::Closing)]);
+    }
+}
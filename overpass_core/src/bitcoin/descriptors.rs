@@ -0,0 +1,480 @@
+// src/bitcoin/descriptors.rs
+
+use crate::bitcoin::anchors::anchor_script;
+use crate::bitcoin::keys::KeyManager;
+use crate::bitcoin::taproot::{TaprootFunding, TaprootFundingParams};
+use bitcoin::bip32::{ChildNumber, DerivationPath, ExtendedPubKey as Xpub, Fingerprint};
+use bitcoin::secp256k1::{Secp256k1, Verification, XOnlyPublicKey};
+use bitcoin::{Address, Network, PublicKey, ScriptBuf};
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+const INPUT_CHARSET: &str =
+    "0123456789()[],'/*abcdefgh@:$%{}IJKLMNOPQRSTUVWXYZ&+-.;<=>?!^_|~ijklmnopqrstuvwxyzABCDEFGH`#\"\\ ";
+const CHECKSUM_CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const GENERATOR: [u64; 5] = [0xf5dee51989, 0xa9fdca3312, 0x1bab10e32d, 0x3706b1677a, 0x644d626ffd];
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum DescriptorError {
+    #[error("Invalid descriptor: {0}")]
+    Parse(String),
+    #[error("Invalid checksum: {0}")]
+    Checksum(String),
+    #[error("Invalid key: {0}")]
+    Key(String),
+    #[error("Derivation failed: {0}")]
+    Derivation(String),
+}
+
+/// Computes the BIP380 descriptor checksum.
+pub fn descriptor_checksum(descriptor: &str) -> Result<String, DescriptorError> {
+    let mut chk: u64 = 1;
+    let mut polymod = |value: u64| {
+        let top = chk >> 35;
+        chk = ((chk & 0x7_ffff_ffff) << 5) ^ value;
+        for (i, generator) in GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                chk ^= generator;
+            }
+        }
+    };
+
+    let mut groups = Vec::with_capacity(3);
+    for c in descriptor.chars() {
+        let value = INPUT_CHARSET
+            .find(c)
+            .ok_or_else(|| DescriptorError::Checksum(format!("Invalid character {:?}", c)))?
+            as u64;
+        polymod(value & 31);
+        groups.push(value >> 5);
+        if groups.len() == 3 {
+            polymod(groups[0] * 9 + groups[1] * 3 + groups[2]);
+            groups.clear();
+        }
+    }
+    match groups[..] {
+        [a] => polymod(a),
+        [a, b] => polymod(a * 3 + b),
+        _ => {}
+    }
+    for _ in 0..8 {
+        polymod(0);
+    }
+
+    let checksum = chk ^ 1;
+    Ok((0..8)
+        .map(|i| CHECKSUM_CHARSET[((checksum >> (5 * (7 - i))) & 31) as usize] as char)
+        .collect())
+}
+
+/// A key expression inside a descriptor.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DescriptorKey {
+    Single(PublicKey),
+    XOnly(XOnlyPublicKey),
+    /// An extended key, optionally ranged over its final child index (`/*`).
+    Extended {
+        origin: Option<(Fingerprint, DerivationPath)>,
+        xpub: Xpub,
+        path: DerivationPath,
+        wildcard: bool,
+    },
+}
+
+impl DescriptorKey {
+    /// Derives the public key at `index` (ignored for non-ranged keys).
+    pub fn derive<C: Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        index: u32,
+    ) -> Result<PublicKey, DescriptorError> {
+        match self {
+            DescriptorKey::Single(key) => Ok(*key),
+            DescriptorKey::XOnly(_) => Err(DescriptorError::Key(
+                "X-only keys are only valid inside tr()".to_string(),
+            )),
+            DescriptorKey::Extended { xpub, path, wildcard, .. } => {
+                let mut path = path.clone();
+                if *wildcard {
+                    let child = ChildNumber::from_normal_idx(index)
+                        .map_err(|e| DescriptorError::Derivation(e.to_string()))?;
+                    path = path.child(child);
+                }
+                xpub.derive_pub(secp, &path)
+                    .map(|derived| PublicKey::new(derived.public_key))
+                    .map_err(|e| DescriptorError::Derivation(e.to_string()))
+            }
+        }
+    }
+
+    /// Derives the x-only key at `index`, as used by Taproot outputs.
+    pub fn derive_x_only<C: Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        index: u32,
+    ) -> Result<XOnlyPublicKey, DescriptorError> {
+        match self {
+            DescriptorKey::XOnly(key) => Ok(*key),
+            _ => Ok(self.derive(secp, index)?.inner.x_only_public_key().0),
+        }
+    }
+
+    pub fn is_ranged(&self) -> bool {
+        matches!(self, DescriptorKey::Extended { wildcard: true, .. })
+    }
+}
+
+fn write_path(f: &mut fmt::Formatter<'_>, path: &DerivationPath) -> fmt::Result {
+    for child in path {
+        write!(f, "/{}", child)?;
+    }
+    Ok(())
+}
+
+fn parse_path<'a>(
+    components: impl Iterator<Item = &'a str>,
+) -> Result<DerivationPath, DescriptorError> {
+    components
+        .map(|c| ChildNumber::from_str(c).map_err(|e| DescriptorError::Key(e.to_string())))
+        .collect::<Result<Vec<_>, _>>()
+        .map(DerivationPath::from)
+}
+
+impl fmt::Display for DescriptorKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DescriptorKey::Single(key) => write!(f, "{}", key),
+            DescriptorKey::XOnly(key) => write!(f, "{}", key),
+            DescriptorKey::Extended { origin, xpub, path, wildcard } => {
+                if let Some((fingerprint, origin_path)) = origin {
+                    write!(f, "[{}", fingerprint)?;
+                    write_path(f, origin_path)?;
+                    write!(f, "]")?;
+                }
+                write!(f, "{}", xpub)?;
+                write_path(f, path)?;
+                if *wildcard {
+                    write!(f, "/*")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl FromStr for DescriptorKey {
+    type Err = DescriptorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (origin, key) = match s.strip_prefix('[') {
+            Some(rest) => {
+                let (origin, key) = rest
+                    .split_once(']')
+                    .ok_or_else(|| DescriptorError::Key("Unterminated key origin".to_string()))?;
+                let mut components = origin.split('/');
+                let fingerprint = Fingerprint::from_str(components.next().unwrap_or_default())
+                    .map_err(|e| DescriptorError::Key(e.to_string()))?;
+                (Some((fingerprint, parse_path(components)?)), key)
+            }
+            None => (None, s),
+        };
+
+        let is_hex = key.chars().all(|c| c.is_ascii_hexdigit());
+        if is_hex && key.len() == 66 && origin.is_none() {
+            return PublicKey::from_str(key)
+                .map(DescriptorKey::Single)
+                .map_err(|e| DescriptorError::Key(e.to_string()));
+        }
+        if is_hex && key.len() == 64 && origin.is_none() {
+            return XOnlyPublicKey::from_str(key)
+                .map(DescriptorKey::XOnly)
+                .map_err(|e| DescriptorError::Key(e.to_string()));
+        }
+
+        let mut components: Vec<&str> = key.split('/').collect();
+        let xpub = Xpub::from_str(components.remove(0))
+            .map_err(|e| DescriptorError::Key(e.to_string()))?;
+        let wildcard = match components.last() {
+            Some(&"*") => {
+                components.pop();
+                true
+            }
+            Some(last) if last.starts_with('*') => {
+                return Err(DescriptorError::Key("Hardened wildcards are not supported".to_string()))
+            }
+            _ => false,
+        };
+        Ok(DescriptorKey::Extended {
+            origin,
+            xpub,
+            path: parse_path(components.into_iter())?,
+            wildcard,
+        })
+    }
+}
+
+/// An output descriptor for the scripts this wallet creates or watches.
+///
+/// Besides the plain wallet forms, the channel funding output is expressed as
+/// `tr(INTERNAL,{and_v(v:pk(DISPUTE),older(DELAY)),and_v(v:pk(TIMEOUT),after(HEIGHT))})`
+/// and the commitment anchors as `wsh(or_d(pk(KEY),older(16)))`, so Bitcoin Core or
+/// Sparrow derive exactly the scripts built in [`crate::bitcoin::taproot`] and
+/// [`crate::bitcoin::anchors`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Descriptor {
+    Wpkh(DescriptorKey),
+    Tr(DescriptorKey),
+    ChannelFunding(TaprootFundingParams),
+    Anchor(PublicKey),
+    Raw(ScriptBuf),
+}
+
+impl Descriptor {
+    /// Gets the BIP86 receive (`change == false`) or change descriptor of a wallet.
+    pub fn wallet(keys: &KeyManager, change: bool) -> Result<Self, DescriptorError> {
+        let xpub = keys
+            .wallet_account_xpub()
+            .map_err(|e| DescriptorError::Derivation(e.to_string()))?;
+        let origin_path = keys
+            .wallet_account_path()
+            .map_err(|e| DescriptorError::Derivation(e.to_string()))?;
+        Ok(Descriptor::Tr(DescriptorKey::Extended {
+            origin: Some((keys.master_fingerprint(), origin_path)),
+            xpub,
+            path: DerivationPath::from(vec![ChildNumber::Normal { index: change as u32 }]),
+            wildcard: true,
+        }))
+    }
+
+    /// Returns true if the descriptor derives a different script per index.
+    pub fn is_ranged(&self) -> bool {
+        match self {
+            Descriptor::Wpkh(key) | Descriptor::Tr(key) => key.is_ranged(),
+            _ => false,
+        }
+    }
+
+    /// Derives the script pubkey at `index` (ignored for non-ranged descriptors).
+    pub fn script_pubkey<C: Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        index: u32,
+    ) -> Result<ScriptBuf, DescriptorError> {
+        match self {
+            Descriptor::Wpkh(key) => {
+                let key = key.derive(secp, index)?;
+                let hash = key
+                    .wpubkey_hash()
+                    .ok_or_else(|| DescriptorError::Key("wpkh() requires a compressed key".to_string()))?;
+                Ok(ScriptBuf::new_v0_p2wpkh(&hash))
+            }
+            Descriptor::Tr(key) => Ok(ScriptBuf::new_v1_p2tr(secp, key.derive_x_only(secp, index)?, None)),
+            Descriptor::ChannelFunding(params) => TaprootFunding::new(secp, params.clone())
+                .map(|funding| funding.script_pubkey())
+                .map_err(|e| DescriptorError::Parse(e.to_string())),
+            Descriptor::Anchor(key) => Ok(ScriptBuf::new_v0_p2wsh(&anchor_script(key).wscript_hash())),
+            Descriptor::Raw(script) => Ok(script.clone()),
+        }
+    }
+
+    /// Derives the address at `index` on the given network.
+    pub fn address<C: Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        index: u32,
+        network: Network,
+    ) -> Result<Address, DescriptorError> {
+        let script_pubkey = self.script_pubkey(secp, index)?;
+        Address::from_script(&script_pubkey, network)
+            .map_err(|e| DescriptorError::Derivation(e.to_string()))
+    }
+
+    /// Formats the descriptor with its checksum appended.
+    pub fn to_string_with_checksum(&self) -> Result<String, DescriptorError> {
+        let body = self.to_string();
+        let checksum = descriptor_checksum(&body)?;
+        Ok(format!("{}#{}", body, checksum))
+    }
+}
+
+impl fmt::Display for Descriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Descriptor::Wpkh(key) => write!(f, "wpkh({})", key),
+            Descriptor::Tr(key) => write!(f, "tr({})", key),
+            Descriptor::ChannelFunding(params) => write!(
+                f,
+                "tr({},{{and_v(v:pk({}),older({})),and_v(v:pk({}),after({}))}})",
+                params.internal_key,
+                params.dispute_key,
+                params.dispute_delay,
+                params.timeout_key,
+                params.timeout_height
+            ),
+            Descriptor::Anchor(key) => write!(f, "wsh(or_d(pk({}),older(16)))", key),
+            Descriptor::Raw(script) => write!(f, "raw({})", script.to_hex_string()),
+        }
+    }
+}
+
+fn unwrap_call<'a>(s: &'a str, name: &str) -> Option<&'a str> {
+    s.strip_prefix(name)?.strip_prefix('(')?.strip_suffix(')')
+}
+
+fn parse_x_only(s: &str) -> Result<XOnlyPublicKey, DescriptorError> {
+    XOnlyPublicKey::from_str(s).map_err(|e| DescriptorError::Key(e.to_string()))
+}
+
+/// Parses a `and_v(v:pk(KEY),FRAGMENT(N))` leaf, returning the key and the number.
+fn parse_leaf<'a>(s: &'a str, fragment: &str) -> Option<(&'a str, &'a str)> {
+    let inner = unwrap_call(s, "and_v")?.strip_prefix("v:pk(")?;
+    let (key, rest) = inner.split_once(')')?;
+    Some((key, unwrap_call(rest.strip_prefix(',')?, fragment)?))
+}
+
+fn parse_number<T: FromStr>(s: &str) -> Result<T, DescriptorError> {
+    s.parse()
+        .map_err(|_| DescriptorError::Parse(format!("Invalid number {:?}", s)))
+}
+
+fn parse_channel_funding(internal: &str, tree: &str) -> Result<Descriptor, DescriptorError> {
+    let branches = tree
+        .strip_prefix('{')
+        .and_then(|t| t.strip_suffix('}'))
+        .ok_or_else(|| DescriptorError::Parse("Expected a two-leaf script tree".to_string()))?;
+    let (first, second) = branches
+        .split_once("),and_v(")
+        .map(|(a, b)| (format!("{})", a), format!("and_v({}", b)))
+        .ok_or_else(|| DescriptorError::Parse("Expected a two-leaf script tree".to_string()))?;
+
+    let (dispute, timeout) = match (parse_leaf(&first, "older"), parse_leaf(&second, "after")) {
+        (Some(dispute), Some(timeout)) => (dispute, timeout),
+        _ => match (parse_leaf(&second, "older"), parse_leaf(&first, "after")) {
+            (Some(dispute), Some(timeout)) => (dispute, timeout),
+            _ => {
+                return Err(DescriptorError::Parse(
+                    "Script tree is not a channel funding template".to_string(),
+                ))
+            }
+        },
+    };
+    Ok(Descriptor::ChannelFunding(TaprootFundingParams {
+        internal_key: parse_x_only(internal)?,
+        dispute_key: parse_x_only(dispute.0)?,
+        dispute_delay: parse_number(dispute.1)?,
+        timeout_key: parse_x_only(timeout.0)?,
+        timeout_height: parse_number(timeout.1)?,
+    }))
+}
+
+impl FromStr for Descriptor {
+    type Err = DescriptorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let body = match s.split_once('#') {
+            Some((body, checksum)) => {
+                let expected = descriptor_checksum(body)?;
+                if checksum != expected {
+                    return Err(DescriptorError::Checksum(format!(
+                        "Expected {}, found {}",
+                        expected, checksum
+                    )));
+                }
+                body
+            }
+            None => s,
+        };
+
+        if let Some(key) = unwrap_call(body, "wpkh") {
+            return Ok(Descriptor::Wpkh(key.parse()?));
+        }
+        if let Some(inner) = unwrap_call(body, "tr") {
+            return match inner.split_once(',') {
+                Some((internal, tree)) => parse_channel_funding(internal, tree),
+                None => Ok(Descriptor::Tr(inner.parse()?)),
+            };
+        }
+        if let Some(key) = unwrap_call(body, "wsh")
+            .and_then(|inner| unwrap_call(inner, "or_d"))
+            .and_then(|inner| inner.strip_suffix(",older(16)"))
+            .and_then(|pk| unwrap_call(pk, "pk"))
+        {
+            return PublicKey::from_str(key)
+                .map(Descriptor::Anchor)
+                .map_err(|e| DescriptorError::Key(e.to_string()));
+        }
+        if let Some(hex) = unwrap_call(body, "raw") {
+            return ScriptBuf::from_hex(hex)
+                .map(Descriptor::Raw)
+                .map_err(|e| DescriptorError::Parse(e.to_string()));
+        }
+        Err(DescriptorError::Parse(format!("Unsupported descriptor {:?}", body)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::PHRASE;
+    use bitcoin::secp256k1::SecretKey;
+
+    fn x_only(byte: u8) -> XOnlyPublicKey {
+        let secp = Secp256k1::new();
+        SecretKey::from_slice(&[byte; 32]).unwrap().x_only_public_key(&secp).0
+    }
+
+    #[test]
+    fn test_checksum() {
+        assert_eq!(descriptor_checksum("raw(deadbeef)").unwrap(), "89f8spxm");
+        let parsed = Descriptor::from_str("raw(deadbeef)#89f8spxm").unwrap();
+        assert_eq!(parsed, Descriptor::Raw(ScriptBuf::from_hex("deadbeef").unwrap()));
+        assert!(matches!(
+            Descriptor::from_str("raw(deadbeef)#89f8spxn"),
+            Err(DescriptorError::Checksum(_))
+        ));
+    }
+
+    #[test]
+    fn test_wallet_descriptor_matches_bip86() {
+        let secp = Secp256k1::new();
+        let keys = KeyManager::from_mnemonic(PHRASE, "", Network::Bitcoin).unwrap();
+        let descriptor = Descriptor::wallet(&keys, false).unwrap();
+        assert!(descriptor.is_ranged());
+        assert_eq!(
+            descriptor.address(&secp, 0, Network::Bitcoin).unwrap().to_string(),
+            "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr"
+        );
+
+        let text = descriptor.to_string_with_checksum().unwrap();
+        assert!(text.starts_with("tr([73c5da0a/86'/0'/0']xpub"));
+        assert_eq!(Descriptor::from_str(&text).unwrap(), descriptor);
+    }
+
+    #[test]
+    fn test_channel_templates_roundtrip() {
+        let secp = Secp256k1::new();
+        let params = TaprootFundingParams {
+            internal_key: x_only(1),
+            dispute_key: x_only(2),
+            dispute_delay: 144,
+            timeout_key: x_only(3),
+            timeout_height: 800_000,
+        };
+        let funding = Descriptor::ChannelFunding(params.clone());
+        let parsed = Descriptor::from_str(&funding.to_string_with_checksum().unwrap()).unwrap();
+        assert_eq!(parsed, funding);
+        assert_eq!(
+            parsed.script_pubkey(&secp, 0).unwrap(),
+            TaprootFunding::new(&secp, params).unwrap().script_pubkey()
+        );
+
+        let key = PublicKey::new(SecretKey::from_slice(&[4u8; 32]).unwrap().public_key(&secp));
+        let anchor = Descriptor::from_str(&format!("wsh(or_d(pk({}),older(16)))", key)).unwrap();
+        assert_eq!(
+            anchor.script_pubkey(&secp, 0).unwrap(),
+            crate::bitcoin::anchors::anchor_output(&key).script_pubkey
+        );
+        assert!(Descriptor::from_str("sh(multi(1,00))").is_err());
+    }
+}
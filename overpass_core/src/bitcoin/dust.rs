@@ -0,0 +1,98 @@
+// src/bitcoin/dust.rs
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum DustError {
+    #[error("Every settlement output is below its dust limit")]
+    AllDust,
+    #[error("Got {0} balances but {1} dust limits")]
+    LengthMismatch(usize, usize),
+}
+
+/// What to do with a balance too small to be relayed as its own output.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DustPolicy {
+    /// Drop the output and let its value go to the miners.
+    #[default]
+    FoldIntoFees,
+    /// Drop the output and add its value to the largest remaining output.
+    FoldIntoLargest,
+}
+
+/// Applies `policy` to settlement balances, returning the value of each output or `None`
+/// where the balance was folded away.
+///
+/// `dust_limits[i]` is the dust threshold of output `i`'s script. Ties for the largest
+/// output go to the lowest index, so both parties agree on the result.
+pub fn apply_dust_policy(
+    balances: &[u64],
+    dust_limits: &[u64],
+    policy: DustPolicy,
+) -> Result<Vec<Option<u64>>, DustError> {
+    if balances.len() != dust_limits.len() {
+        return Err(DustError::LengthMismatch(balances.len(), dust_limits.len()));
+    }
+
+    let mut outputs: Vec<Option<u64>> = balances
+        .iter()
+        .zip(dust_limits)
+        .map(|(balance, limit)| (*balance >= *limit).then_some(*balance))
+        .collect();
+
+    let largest = outputs
+        .iter()
+        .enumerate()
+        .filter_map(|(index, value)| value.map(|value| (index, value)))
+        .max_by(|(a_index, a), (b_index, b)| a.cmp(b).then(b_index.cmp(a_index)))
+        .map(|(index, _)| index)
+        .ok_or(DustError::AllDust)?;
+
+    if policy == DustPolicy::FoldIntoLargest {
+        let folded: u64 = balances
+            .iter()
+            .zip(&outputs)
+            .filter(|(_, output)| output.is_none())
+            .map(|(balance, _)| *balance)
+            .sum();
+        if let Some(value) = outputs[largest].as_mut() {
+            *value += folded;
+        }
+    }
+    Ok(outputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fold_policies() {
+        let limits = [330, 330];
+        assert_eq!(
+            apply_dust_policy(&[99_000, 200], &limits, DustPolicy::FoldIntoFees).unwrap(),
+            vec![Some(99_000), None]
+        );
+        assert_eq!(
+            apply_dust_policy(&[200, 99_000], &limits, DustPolicy::FoldIntoLargest).unwrap(),
+            vec![None, Some(99_200)]
+        );
+        assert_eq!(
+            apply_dust_policy(&[5_000, 5_000], &limits, DustPolicy::FoldIntoLargest).unwrap(),
+            vec![Some(5_000), Some(5_000)]
+        );
+    }
+
+    #[test]
+    fn test_all_dust_is_rejected() {
+        assert_eq!(
+            apply_dust_policy(&[100, 100], &[330, 330], DustPolicy::FoldIntoLargest),
+            Err(DustError::AllDust)
+        );
+        assert_eq!(
+            apply_dust_policy(&[100], &[330, 330], DustPolicy::FoldIntoFees),
+            Err(DustError::LengthMismatch(1, 2))
+        );
+    }
+}
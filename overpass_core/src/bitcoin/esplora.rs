@@ -0,0 +1,125 @@
+// src/bitcoin/esplora.rs
+
+use bitcoin::block::Header;
+use bitcoin::consensus::encode;
+use bitcoin::{BlockHash, Transaction, Txid};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum EsploraError {
+    #[error("HTTP request failed: {0}")]
+    HttpError(String),
+    #[error("Invalid response: {0}")]
+    InvalidResponse(String),
+}
+
+/// Mempool summary returned by Esplora's `/mempool` endpoint.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MempoolInfo {
+    pub count: u64,
+    pub vsize: u64,
+    pub total_fee: u64,
+    /// `(fee rate in sat/vB, vsize)` buckets ordered from highest to lowest fee rate.
+    pub fee_histogram: Vec<(f64, u64)>,
+}
+
+/// Minimal blocking client for an Esplora HTTP API.
+#[derive(Clone, Debug)]
+pub struct EsploraClient {
+    base_url: String,
+    agent: ureq::Agent,
+}
+
+impl EsploraClient {
+    /// Creates a client for the given base URL (e.g. `https://blockstream.info/api`).
+    pub fn new(base_url: &str, timeout_seconds: u64) -> Self {
+        let agent = ureq::AgentBuilder::new()
+            .timeout(Duration::from_secs(timeout_seconds))
+            .build();
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            agent,
+        }
+    }
+
+    /// Gets the base URL requests are sent to.
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Fetches the current mempool summary including the fee histogram.
+    pub fn get_mempool(&self) -> Result<MempoolInfo, EsploraError> {
+        self.get_json("/mempool")
+    }
+
+    /// Gets the height of the server's best block.
+    pub fn get_tip_height(&self) -> Result<u32, EsploraError> {
+        self.get_text("/blocks/tip/height")?
+            .trim()
+            .parse()
+            .map_err(|e: std::num::ParseIntError| EsploraError::InvalidResponse(e.to_string()))
+    }
+
+    /// Gets the hash of the best-chain block at `height`.
+    pub fn get_block_hash(&self, height: u32) -> Result<BlockHash, EsploraError> {
+        let text = self.get_text(&format!("/block-height/{}", height))?;
+        text.trim()
+            .parse()
+            .map_err(|e: bitcoin::hashes::hex::Error| EsploraError::InvalidResponse(e.to_string()))
+    }
+
+    /// Gets the header of the given block.
+    pub fn get_header(&self, block_hash: &BlockHash) -> Result<Header, EsploraError> {
+        self.get_consensus(&format!("/block/{}/header", block_hash))
+    }
+
+    /// Gets a BIP37 merkle block proving a transaction's inclusion.
+    pub fn get_merkle_block(&self, txid: &Txid) -> Result<bitcoin::MerkleBlock, EsploraError> {
+        self.get_consensus(&format!("/tx/{}/merkleblock-proof", txid))
+    }
+
+    /// Broadcasts a raw transaction via `POST /tx`.
+    pub fn broadcast(&self, tx: &Transaction) -> Result<Txid, EsploraError> {
+        let response = self.post_text("/tx", &encode::serialize_hex(tx))?;
+        response
+            .trim()
+            .parse()
+            .map_err(|e: bitcoin::hashes::hex::Error| EsploraError::InvalidResponse(e.to_string()))
+    }
+
+    pub(crate) fn get_consensus<T: encode::Decodable>(&self, path: &str) -> Result<T, EsploraError> {
+        let bytes = hex::decode(self.get_text(path)?.trim())
+            .map_err(|e| EsploraError::InvalidResponse(e.to_string()))?;
+        encode::deserialize(&bytes).map_err(|e| EsploraError::InvalidResponse(e.to_string()))
+    }
+
+    pub(crate) fn get_text(&self, path: &str) -> Result<String, EsploraError> {
+        self.agent
+            .get(&format!("{}{}", self.base_url, path))
+            .call()
+            .map_err(|e| EsploraError::HttpError(e.to_string()))?
+            .into_string()
+            .map_err(|e| EsploraError::InvalidResponse(e.to_string()))
+    }
+
+    pub(crate) fn post_text(&self, path: &str, body: &str) -> Result<String, EsploraError> {
+        self.agent
+            .post(&format!("{}{}", self.base_url, path))
+            .send_string(body)
+            .map_err(|e| EsploraError::HttpError(e.to_string()))?
+            .into_string()
+            .map_err(|e| EsploraError::InvalidResponse(e.to_string()))
+    }
+
+    pub(crate) fn get_json<T: DeserializeOwned>(&self, path: &str) -> Result<T, EsploraError> {
+        self.agent
+            .get(&format!("{}{}", self.base_url, path))
+            .call()
+            .map_err(|e| EsploraError::HttpError(e.to_string()))?
+            .into_json()
+            .map_err(|e| EsploraError::InvalidResponse(e.to_string()))
+    }
+}
@@ -0,0 +1,228 @@
+// src/bitcoin/fees.rs
+
+#[cfg(feature = "bitcoin-backend")]
+use crate::bitcoin::esplora::EsploraClient;
+use bitcoin::FeeRate;
+#[cfg(feature = "bitcoin-backend")]
+use bitcoincore_rpc::json::EstimateMode;
+#[cfg(feature = "bitcoin-backend")]
+use bitcoincore_rpc::RpcApi;
+#[cfg(feature = "bitcoin-backend")]
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Approximate virtual size of one block, used to walk the mempool fee histogram.
+const BLOCK_VSIZE: u64 = 1_000_000;
+
+#[derive(Error, Debug)]
+pub enum FeeEstimationError {
+    #[error("RPC error: {0}")]
+    RpcError(String),
+    #[error("Esplora error: {0}")]
+    EsploraError(String),
+    #[error("No fee estimate available for a {0}-block target")]
+    NoEstimate(u16),
+    #[error("Fee overflow for a {0} vbyte transaction")]
+    FeeOverflow(u64),
+}
+
+/// Source of fee rates for on-chain transactions.
+pub trait FeeEstimator: Send + Sync {
+    /// Estimates the fee rate needed to confirm within `target_blocks` blocks.
+    fn estimate_fee_rate(&self, target_blocks: u16) -> Result<FeeRate, FeeEstimationError>;
+
+    /// Estimates the absolute fee for a transaction of the given virtual size.
+    fn estimate_fee(&self, vsize: u64, target_blocks: u16) -> Result<u64, FeeEstimationError> {
+        let fee_rate = self.estimate_fee_rate(target_blocks)?;
+        fee_for_vsize(fee_rate, vsize)
+    }
+}
+
+/// Computes the fee in satoshis for a transaction of the given virtual size.
+pub fn fee_for_vsize(fee_rate: FeeRate, vsize: u64) -> Result<u64, FeeEstimationError> {
+    fee_rate
+        .to_sat_per_kwu()
+        .checked_mul(vsize.checked_mul(4).ok_or(FeeEstimationError::FeeOverflow(vsize))?)
+        .map(|fee| fee.div_ceil(1000))
+        .ok_or(FeeEstimationError::FeeOverflow(vsize))
+}
+
+/// Fee estimator backed by Bitcoin Core's `estimatesmartfee`.
+#[cfg(feature = "bitcoin-backend")]
+pub struct CoreRpcFeeEstimator {
+    client: Arc<bitcoincore_rpc::Client>,
+    mode: EstimateMode,
+}
+
+#[cfg(feature = "bitcoin-backend")]
+impl CoreRpcFeeEstimator {
+    pub fn new(client: Arc<bitcoincore_rpc::Client>) -> Self {
+        Self {
+            client,
+            mode: EstimateMode::Conservative,
+        }
+    }
+
+    /// Sets the estimate mode passed to `estimatesmartfee`.
+    pub fn with_mode(mut self, mode: EstimateMode) -> Self {
+        self.mode = mode;
+        self
+    }
+}
+
+#[cfg(feature = "bitcoin-backend")]
+impl FeeEstimator for CoreRpcFeeEstimator {
+    fn estimate_fee_rate(&self, target_blocks: u16) -> Result<FeeRate, FeeEstimationError> {
+        let result = self
+            .client
+            .estimate_smart_fee(target_blocks, Some(self.mode))
+            .map_err(|e| FeeEstimationError::RpcError(e.to_string()))?;
+        let sat_per_kvb = result
+            .fee_rate
+            .ok_or(FeeEstimationError::NoEstimate(target_blocks))?
+            .to_sat();
+        Ok(FeeRate::from_sat_per_kwu(sat_per_kvb / 4))
+    }
+}
+
+/// Fee estimator walking an Esplora server's mempool fee histogram.
+#[cfg(feature = "bitcoin-backend")]
+pub struct EsploraFeeEstimator {
+    client: EsploraClient,
+    floor: FeeRate,
+}
+
+#[cfg(feature = "bitcoin-backend")]
+impl EsploraFeeEstimator {
+    pub fn new(client: EsploraClient) -> Self {
+        Self {
+            client,
+            floor: FeeRate::BROADCAST_MIN,
+        }
+    }
+
+    /// Sets the minimum fee rate returned when the mempool is nearly empty.
+    pub fn with_floor(mut self, floor: FeeRate) -> Self {
+        self.floor = floor;
+        self
+    }
+}
+
+#[cfg(feature = "bitcoin-backend")]
+impl FeeEstimator for EsploraFeeEstimator {
+    fn estimate_fee_rate(&self, target_blocks: u16) -> Result<FeeRate, FeeEstimationError> {
+        let mempool = self
+            .client
+            .get_mempool()
+            .map_err(|e| FeeEstimationError::EsploraError(e.to_string()))?;
+        Ok(estimate_from_histogram(&mempool.fee_histogram, target_blocks, self.floor))
+    }
+}
+
+/// Estimates a fee rate from a `(sat/vB, vsize)` histogram ordered by descending fee rate.
+///
+/// Transactions above the returned rate fill the next `target_blocks` blocks; if the mempool
+/// would clear sooner than that, the floor is returned.
+pub fn estimate_from_histogram(histogram: &[(f64, u64)], target_blocks: u16, floor: FeeRate) -> FeeRate {
+    let capacity = BLOCK_VSIZE * target_blocks.max(1) as u64;
+    let mut cumulative = 0u64;
+    for &(sat_per_vb, vsize) in histogram {
+        cumulative += vsize;
+        if cumulative >= capacity {
+            let rate = FeeRate::from_sat_per_kwu((sat_per_vb * 250.0).ceil() as u64);
+            return rate.max(floor);
+        }
+    }
+    floor
+}
+
+/// Fee estimator returning a fixed rate, used as a last-resort fallback.
+#[derive(Clone, Copy, Debug)]
+pub struct StaticFeeEstimator {
+    pub fee_rate: FeeRate,
+}
+
+impl StaticFeeEstimator {
+    pub fn new(fee_rate: FeeRate) -> Self {
+        Self { fee_rate }
+    }
+}
+
+impl Default for StaticFeeEstimator {
+    fn default() -> Self {
+        Self::new(FeeRate::from_sat_per_vb_unchecked(10))
+    }
+}
+
+impl FeeEstimator for StaticFeeEstimator {
+    fn estimate_fee_rate(&self, _target_blocks: u16) -> Result<FeeRate, FeeEstimationError> {
+        Ok(self.fee_rate)
+    }
+}
+
+/// Tries each backend in order, falling back to a static rate if all of them fail.
+pub struct FallbackFeeEstimator {
+    backends: Vec<Box<dyn FeeEstimator>>,
+    fallback: StaticFeeEstimator,
+}
+
+impl FallbackFeeEstimator {
+    pub fn new(backends: Vec<Box<dyn FeeEstimator>>, fallback: StaticFeeEstimator) -> Self {
+        Self { backends, fallback }
+    }
+}
+
+impl FeeEstimator for FallbackFeeEstimator {
+    fn estimate_fee_rate(&self, target_blocks: u16) -> Result<FeeRate, FeeEstimationError> {
+        for backend in &self.backends {
+            if let Ok(fee_rate) = backend.estimate_fee_rate(target_blocks) {
+                return Ok(fee_rate);
+            }
+        }
+        self.fallback.estimate_fee_rate(target_blocks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FailingEstimator;
+
+    impl FeeEstimator for FailingEstimator {
+        fn estimate_fee_rate(&self, target_blocks: u16) -> Result<FeeRate, FeeEstimationError> {
+            Err(FeeEstimationError::NoEstimate(target_blocks))
+        }
+    }
+
+    #[test]
+    fn test_fee_for_vsize() {
+        let fee_rate = FeeRate::from_sat_per_vb_unchecked(5);
+        assert_eq!(fee_for_vsize(fee_rate, 200).unwrap(), 1_000);
+        assert!(fee_for_vsize(FeeRate::MAX, 200).is_err());
+        assert!(fee_for_vsize(FeeRate::BROADCAST_MIN, u64::MAX / 2).is_err());
+    }
+
+    #[test]
+    fn test_estimate_from_histogram() {
+        let floor = FeeRate::BROADCAST_MIN;
+        let histogram = vec![(50.0, 400_000), (20.0, 700_000), (5.0, 2_000_000)];
+
+        // The first block fills within the 20 sat/vB bucket.
+        assert_eq!(estimate_from_histogram(&histogram, 1, floor).to_sat_per_vb_ceil(), 20);
+        // Two blocks reach into the 5 sat/vB bucket.
+        assert_eq!(estimate_from_histogram(&histogram, 2, floor).to_sat_per_vb_ceil(), 5);
+        // The whole mempool clears within ten blocks.
+        assert_eq!(estimate_from_histogram(&histogram, 10, floor), floor);
+    }
+
+    #[test]
+    fn test_fallback_estimator() {
+        let estimator = FallbackFeeEstimator::new(
+            vec![Box::new(FailingEstimator)],
+            StaticFeeEstimator::new(FeeRate::from_sat_per_vb_unchecked(3)),
+        );
+        assert_eq!(estimator.estimate_fee_rate(6).unwrap().to_sat_per_vb_floor(), 3);
+        assert_eq!(estimator.estimate_fee(100, 6).unwrap(), 300);
+    }
+}
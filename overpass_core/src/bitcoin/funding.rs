@@ -0,0 +1,464 @@
+// src/bitcoin/funding.rs
+
+use crate::bitcoin::chain::{ChainBackend, ChainError};
+use crate::bitcoin::fees::FeeEstimator;
+use crate::bitcoin::utxo::{select_coins, CoinSelection, SelectionParams, Utxo};
+use bitcoin::{OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid, Witness};
+use std::collections::HashMap;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum FundingError {
+    #[error("Funding output {0} does not exist")]
+    MissingOutput(OutPoint),
+    #[error("Funding transaction has {confirmations} confirmations, {required} required")]
+    Unconfirmed { confirmations: u32, required: u32 },
+    #[error("Funding output already spent by {0}")]
+    OutputSpent(Txid),
+    #[error("Funding input {input} double-spent by {conflicting_txid}")]
+    DoubleSpent { input: OutPoint, conflicting_txid: Txid },
+    #[error("Chain error: {0}")]
+    Chain(#[from] ChainError),
+    #[error("Fee estimation failed: {0}")]
+    FeeEstimation(String),
+    #[error("Coin selection failed: {0}")]
+    CoinSelection(String),
+}
+
+/// Virtual size of a transaction header, with the segwit marker and flag rounded up.
+const HEADER_VSIZE: u64 = 11;
+/// Virtual size of later spending a P2WPKH change output.
+const CHANGE_SPEND_VSIZE: u64 = 68;
+
+fn output_vsize(script_pubkey: &ScriptBuf) -> u64 {
+    let script_len = script_pubkey.len() as u64;
+    8 + bitcoin::VarInt(script_len).len() as u64 + script_len
+}
+
+/// An unsigned funding transaction with the channel output at `vout`.
+#[derive(Clone, Debug)]
+pub struct FundingTx {
+    pub tx: Transaction,
+    pub vout: u32,
+    pub selection: CoinSelection,
+}
+
+/// Builds an unsigned transaction paying `funding_output` from the wallet's `utxos`, with
+/// change to `change_script` when it is worth keeping.
+///
+/// The fee rate comes from `fee_estimator` for confirmation within `target_blocks`.
+pub fn build_funding_tx(
+    funding_output: TxOut,
+    utxos: &[Utxo],
+    change_script: ScriptBuf,
+    fee_estimator: &dyn FeeEstimator,
+    target_blocks: u16,
+) -> Result<FundingTx, FundingError> {
+    let fee_rate = fee_estimator
+        .estimate_fee_rate(target_blocks)
+        .map_err(|e| FundingError::FeeEstimation(e.to_string()))?;
+    let selection = select_coins(
+        utxos,
+        &SelectionParams {
+            target: funding_output.value,
+            fee_rate,
+            base_vsize: HEADER_VSIZE + output_vsize(&funding_output.script_pubkey),
+            change_vsize: output_vsize(&change_script),
+            change_spend_vsize: CHANGE_SPEND_VSIZE,
+            dust_limit: change_script.dust_value().to_sat(),
+        },
+    )
+    .map_err(|e| FundingError::CoinSelection(e.to_string()))?;
+
+    let mut output = vec![funding_output];
+    if let Some(change) = selection.change {
+        output.push(TxOut {
+            value: change,
+            script_pubkey: change_script,
+        });
+    }
+    let tx = Transaction {
+        version: 2,
+        lock_time: bitcoin::absolute::LockTime::ZERO,
+        input: selection
+            .selected
+            .iter()
+            .map(|utxo| TxIn {
+                previous_output: utxo.outpoint,
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness: Witness::new(),
+            })
+            .collect(),
+        output,
+    };
+    Ok(FundingTx {
+        tx,
+        vout: 0,
+        selection,
+    })
+}
+
+/// Checks that a funding transaction has not been replaced by a conflicting spend of
+/// any of its inputs.
+fn check_inputs(backend: &dyn ChainBackend, funding_tx: &Transaction) -> Result<(), FundingError> {
+    let funding_txid = funding_tx.txid();
+    for input in &funding_tx.input {
+        if let Some(spend) = backend.spending_transaction(&input.previous_output)? {
+            let conflicting_txid = spend.txid();
+            if conflicting_txid != funding_txid {
+                return Err(FundingError::DoubleSpent {
+                    input: input.previous_output,
+                    conflicting_txid,
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Verifies that a channel's funding output is confirmed, unspent and not double-spent.
+///
+/// Inputs are only checked for conflicts while the funding is unconfirmed: once it is in
+/// a block its inputs are spent by the funding itself, which backends without a spent
+/// output index cannot look up. Must pass before a channel is treated as open.
+pub fn verify_funding(
+    backend: &dyn ChainBackend,
+    funding_tx: &Transaction,
+    vout: u32,
+    required_confirmations: u32,
+) -> Result<(), FundingError> {
+    let outpoint = OutPoint::new(funding_tx.txid(), vout);
+    if funding_tx.output.get(vout as usize).is_none() {
+        return Err(FundingError::MissingOutput(outpoint));
+    }
+
+    let confirmations = backend.confirmations(&outpoint.txid)?.unwrap_or(0);
+    if confirmations == 0 {
+        check_inputs(backend, funding_tx)?;
+    }
+    if confirmations < required_confirmations {
+        return Err(FundingError::Unconfirmed {
+            confirmations,
+            required: required_confirmations,
+        });
+    }
+    if let Some(spend) = backend.spending_transaction(&outpoint)? {
+        return Err(FundingError::OutputSpent(spend.txid()));
+    }
+    Ok(())
+}
+
+/// Events raised while watching pending channel fundings.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FundingEvent {
+    /// The funding output is confirmed and unspent; the channel may open.
+    Ready {
+        channel_id: [u8; 32],
+        funding_outpoint: OutPoint,
+    },
+    /// The funder spent a funding input elsewhere; the channel open is aborted.
+    Aborted {
+        channel_id: [u8; 32],
+        funding_txid: Txid,
+        input: OutPoint,
+        conflicting_txid: Txid,
+    },
+}
+
+struct PendingFunding {
+    funding_tx: Transaction,
+    vout: u32,
+    ready: bool,
+}
+
+/// Watches funding transactions until they confirm, aborting on double-spends.
+///
+/// Channels stay watched after becoming ready, since a reorg can still unconfirm the
+/// funding and expose it to a conflicting spend.
+pub struct FundingWatcher {
+    required_confirmations: u32,
+    pending: HashMap<[u8; 32], PendingFunding>,
+}
+
+impl FundingWatcher {
+    pub fn new(required_confirmations: u32) -> Self {
+        Self {
+            required_confirmations,
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Starts watching a channel's funding transaction.
+    pub fn watch(&mut self, channel_id: [u8; 32], funding_tx: Transaction, vout: u32) {
+        self.pending.insert(
+            channel_id,
+            PendingFunding {
+                funding_tx,
+                vout,
+                ready: false,
+            },
+        );
+    }
+
+    pub fn unwatch(&mut self, channel_id: &[u8; 32]) {
+        self.pending.remove(channel_id);
+    }
+
+    pub fn is_watching(&self, channel_id: &[u8; 32]) -> bool {
+        self.pending.contains_key(channel_id)
+    }
+
+    /// Checks every watched funding and returns new events.
+    ///
+    /// Aborted channels are dropped from the watch list.
+    pub fn poll(&mut self, backend: &dyn ChainBackend) -> Result<Vec<FundingEvent>, ChainError> {
+        let mut events = Vec::new();
+        let mut aborted = Vec::new();
+
+        for (channel_id, pending) in self.pending.iter_mut() {
+            let funding_txid = pending.funding_tx.txid();
+            match verify_funding(backend, &pending.funding_tx, pending.vout, self.required_confirmations) {
+                Ok(()) if !pending.ready => {
+                    pending.ready = true;
+                    events.push(FundingEvent::Ready {
+                        channel_id: *channel_id,
+                        funding_outpoint: OutPoint::new(funding_txid, pending.vout),
+                    });
+                }
+                Ok(()) => {}
+                Err(FundingError::DoubleSpent { input, conflicting_txid }) => {
+                    aborted.push(*channel_id);
+                    events.push(FundingEvent::Aborted {
+                        channel_id: *channel_id,
+                        funding_txid,
+                        input,
+                        conflicting_txid,
+                    });
+                }
+                Err(FundingError::Chain(e)) => return Err(e),
+                // Unconfirmed or reorged out: wait for the next poll.
+                Err(FundingError::Unconfirmed { .. }) => pending.ready = false,
+                // A spent funding output is a close, handled by the channel monitor.
+                Err(_) => {}
+            }
+        }
+
+        for channel_id in aborted {
+            self.pending.remove(&channel_id);
+        }
+        Ok(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::fees::{fee_for_vsize, StaticFeeEstimator};
+    use bitcoin::absolute::LockTime;
+    use bitcoin::hashes::Hash;
+    use bitcoin::FeeRate;
+    use std::collections::HashSet;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct MockChain {
+        confirmations: Mutex<HashMap<Txid, u32>>,
+        spends: Mutex<HashMap<OutPoint, Transaction>>,
+        /// Outpoints whose spender the backend cannot find, like Core without an index.
+        unindexed: Mutex<HashSet<OutPoint>>,
+    }
+
+    impl ChainBackend for MockChain {
+        fn broadcast(&self, tx: &Transaction) -> Result<Txid, ChainError> {
+            Ok(tx.txid())
+        }
+
+        fn confirmations(&self, txid: &Txid) -> Result<Option<u32>, ChainError> {
+            Ok(self.confirmations.lock().unwrap().get(txid).copied())
+        }
+
+        fn tip_height(&self) -> Result<u32, ChainError> {
+            Ok(100)
+        }
+
+        fn spending_transaction(&self, outpoint: &OutPoint) -> Result<Option<Transaction>, ChainError> {
+            if self.unindexed.lock().unwrap().contains(outpoint) {
+                return Err(ChainError::BackendError(format!("{} spent by unknown tx", outpoint)));
+            }
+            Ok(self.spends.lock().unwrap().get(outpoint).cloned())
+        }
+    }
+
+    fn spend(prevout: OutPoint, value: u64) -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: prevout,
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness: Witness::new(),
+            }],
+            output: vec![TxOut {
+                value,
+                script_pubkey: ScriptBuf::new(),
+            }],
+        }
+    }
+
+    const CHANNEL: [u8; 32] = [5u8; 32];
+
+    fn wallet_utxo(vout: u32, value: u64) -> Utxo {
+        Utxo {
+            outpoint: OutPoint::new(Txid::from_byte_array([1u8; 32]), vout),
+            txout: TxOut {
+                value,
+                script_pubkey: ScriptBuf::new_v0_p2wpkh(&bitcoin::WPubkeyHash::all_zeros()),
+            },
+            confirmations: 6,
+            input_vsize: 68,
+        }
+    }
+
+    #[test]
+    fn test_funding_tx_pays_the_estimated_fee() {
+        let funding_output = TxOut {
+            value: 60_000,
+            script_pubkey: ScriptBuf::new_v0_p2wsh(&bitcoin::WScriptHash::all_zeros()),
+        };
+        let change_script = ScriptBuf::new_v0_p2wpkh(&bitcoin::WPubkeyHash::all_zeros());
+        let rate = FeeRate::from_sat_per_vb_unchecked(10);
+        let utxos = [wallet_utxo(0, 40_000), wallet_utxo(1, 100_000)];
+
+        let funding = build_funding_tx(
+            funding_output.clone(),
+            &utxos,
+            change_script.clone(),
+            &StaticFeeEstimator::new(rate),
+            6,
+        )
+        .unwrap();
+        assert_eq!(funding.tx.output[funding.vout as usize], funding_output);
+        let change = funding.selection.change.unwrap();
+        assert_eq!(funding.tx.output[1].value, change);
+        let inputs = funding.selection.input_value();
+        assert_eq!(inputs, 60_000 + change + funding.selection.fee);
+        // Header, both outputs and each input at 10 sat/vB.
+        let vsize = HEADER_VSIZE
+            + output_vsize(&funding_output.script_pubkey)
+            + output_vsize(&change_script)
+            + 68 * funding.tx.input.len() as u64;
+        assert_eq!(funding.selection.fee, fee_for_vsize(rate, vsize).unwrap());
+
+        // A higher estimate takes a higher fee from the same coins.
+        let urgent = build_funding_tx(
+            funding_output.clone(),
+            &utxos,
+            change_script.clone(),
+            &StaticFeeEstimator::new(FeeRate::from_sat_per_vb_unchecked(50)),
+            1,
+        )
+        .unwrap();
+        assert!(urgent.selection.fee > funding.selection.fee);
+
+        assert!(matches!(
+            build_funding_tx(
+                TxOut {
+                    value: 200_000,
+                    ..funding_output
+                },
+                &utxos,
+                change_script,
+                &StaticFeeEstimator::new(rate),
+                6,
+            ),
+            Err(FundingError::CoinSelection(_))
+        ));
+    }
+
+    #[test]
+    fn test_verify_funding() {
+        let wallet_utxo = OutPoint::new(Txid::from_byte_array([1u8; 32]), 0);
+        let funding = spend(wallet_utxo, 100_000);
+        let chain = MockChain::default();
+        chain.spends.lock().unwrap().insert(wallet_utxo, funding.clone());
+
+        assert!(matches!(
+            verify_funding(&chain, &funding, 0, 1),
+            Err(FundingError::Unconfirmed { confirmations: 0, required: 1 })
+        ));
+        assert!(matches!(
+            verify_funding(&chain, &funding, 1, 1),
+            Err(FundingError::MissingOutput(_))
+        ));
+
+        chain.confirmations.lock().unwrap().insert(funding.txid(), 3);
+        verify_funding(&chain, &funding, 0, 3).unwrap();
+
+        let close = spend(OutPoint::new(funding.txid(), 0), 99_000);
+        chain.spends.lock().unwrap().insert(OutPoint::new(funding.txid(), 0), close.clone());
+        assert!(matches!(
+            verify_funding(&chain, &funding, 0, 3),
+            Err(FundingError::OutputSpent(txid)) if txid == close.txid()
+        ));
+    }
+
+    #[test]
+    fn test_watcher_aborts_on_double_spend() {
+        let wallet_utxo = OutPoint::new(Txid::from_byte_array([1u8; 32]), 0);
+        let funding = spend(wallet_utxo, 100_000);
+        let chain = MockChain::default();
+        let mut watcher = FundingWatcher::new(1);
+        watcher.watch(CHANNEL, funding.clone(), 0);
+
+        assert!(watcher.poll(&chain).unwrap().is_empty());
+
+        // The funder replaces the funding transaction with a payment to themselves.
+        let conflict = spend(wallet_utxo, 99_500);
+        chain.spends.lock().unwrap().insert(wallet_utxo, conflict.clone());
+        let events = watcher.poll(&chain).unwrap();
+        assert_eq!(
+            events,
+            vec![FundingEvent::Aborted {
+                channel_id: CHANNEL,
+                funding_txid: funding.txid(),
+                input: wallet_utxo,
+                conflicting_txid: conflict.txid(),
+            }]
+        );
+        assert!(!watcher.is_watching(&CHANNEL));
+    }
+
+    #[test]
+    fn test_watcher_reports_ready_once() {
+        let wallet_utxo = OutPoint::new(Txid::from_byte_array([1u8; 32]), 0);
+        let funding = spend(wallet_utxo, 100_000);
+        let chain = MockChain::default();
+        chain.spends.lock().unwrap().insert(wallet_utxo, funding.clone());
+        chain.confirmations.lock().unwrap().insert(funding.txid(), 1);
+
+        let mut watcher = FundingWatcher::new(1);
+        watcher.watch(CHANNEL, funding.clone(), 0);
+        assert!(matches!(watcher.poll(&chain).unwrap()[..], [FundingEvent::Ready { .. }]));
+        assert!(watcher.poll(&chain).unwrap().is_empty());
+        assert!(watcher.is_watching(&CHANNEL));
+    }
+
+    #[test]
+    fn test_confirmed_funding_skips_input_lookup() {
+        let wallet_utxo = OutPoint::new(Txid::from_byte_array([1u8; 32]), 0);
+        let funding = spend(wallet_utxo, 100_000);
+        let chain = MockChain::default();
+        chain.unindexed.lock().unwrap().insert(wallet_utxo);
+
+        // Unconfirmed, the inputs must be checked and the lookup failure surfaces.
+        assert!(matches!(verify_funding(&chain, &funding, 0, 1), Err(FundingError::Chain(_))));
+
+        chain.confirmations.lock().unwrap().insert(funding.txid(), 2);
+        verify_funding(&chain, &funding, 0, 1).unwrap();
+        let mut watcher = FundingWatcher::new(1);
+        watcher.watch(CHANNEL, funding, 0);
+        assert!(matches!(watcher.poll(&chain).unwrap()[..], [FundingEvent::Ready { .. }]));
+    }
+}
@@ -0,0 +1,445 @@
+// src/bitcoin/keys.rs
+
+use crate::bitcoin::wallet::WalletError;
+use bip39::{Language, Mnemonic};
+use bitcoin::bip32::{ChildNumber, DerivationPath, ExtendedPrivKey as Xpriv, ExtendedPubKey as Xpub, Fingerprint};
+use bitcoin::secp256k1::{All, KeyPair, Secp256k1, SecretKey};
+use bitcoin::Network;
+use crate::zkp::blinding::BlindingFactor;
+use crate::zkp::helpers::Bytes32;
+use crate::zkp::pedersen_commitment::PedersenCommitment;
+use crate::zkp::pedersen_parameters::PedersenParameters;
+use rand::RngCore;
+use sha2::{Digest, Sha256, Sha512};
+use std::fmt;
+use std::sync::RwLock;
+use zeroize::{Zeroize, Zeroizing};
+
+/// BIP44-style purpose reserved for Overpass channel key families.
+pub const CHANNEL_PURPOSE: u32 = 9000;
+/// BIP86 purpose used for single-key Taproot wallet outputs.
+pub const WALLET_PURPOSE: u32 = 86;
+/// Branch under the channel purpose holding the key invoices are signed with, clear of
+/// the channel key families.
+const INVOICE_BRANCH: u32 = 1_000;
+/// Branch under the channel purpose holding the key the wallet's p2p identity is made from.
+const TRANSPORT_BRANCH: u32 = 1_001;
+
+/// Independent families of channel key material.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum KeyFamily {
+    Funding = 0,
+    Dispute = 1,
+    Timeout = 2,
+    Revocation = 3,
+    Anchor = 4,
+    Blinding = 5,
+}
+
+/// Position of a Pedersen blinding factor under a channel's `Blinding` key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BlindingPath {
+    pub channel_index: u32,
+    /// Nonce of the state update the commitment belongs to.
+    pub update: u64,
+    /// Which of the update's commitments is blinded, e.g. one per party balance.
+    pub slot: u32,
+}
+
+impl BlindingPath {
+    pub fn new(channel_index: u32, update: u64) -> Self {
+        Self {
+            channel_index,
+            update,
+            slot: 0,
+        }
+    }
+
+    pub fn with_slot(mut self, slot: u32) -> Self {
+        self.slot = slot;
+        self
+    }
+}
+
+impl fmt::Display for BlindingPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}'/{}/{}", self.channel_index, self.update, self.slot)
+    }
+}
+
+/// Maps a channel id to the hardened index of its channel keys, so a restored wallet finds
+/// them from the id alone.
+pub fn channel_index_for_id(channel_id: &Bytes32) -> u32 {
+    let digest = Sha256::new()
+        .chain_update(b"overpass/channel-index")
+        .chain_update(channel_id)
+        .finalize();
+    u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]]) & 0x7fff_ffff
+}
+
+/// Derives every key the wallet uses from a single BIP39 backup phrase.
+///
+/// Layout:
+/// - wallet keys: `m/86'/coin'/0'/change/index` (BIP86)
+/// - channel keys: `m/9000'/coin'/family'/channel'`
+/// - Pedersen blinding factors: hashed from the channel's `Blinding` key and a `BlindingPath`
+///
+/// [`Self::wipe`] zeroizes the seed for every holder of the manager at once; derivations
+/// fail with [`WalletError::KeysWiped`] from then on.
+pub struct KeyManager {
+    secrets: RwLock<Option<Secrets>>,
+    fingerprint: Fingerprint,
+    network: Network,
+    secp: Secp256k1<All>,
+}
+
+/// The backup phrase and encoded master key, zeroized when dropped.
+struct Secrets {
+    phrase: Zeroizing<String>,
+    master: Zeroizing<[u8; 78]>,
+}
+
+impl KeyManager {
+    /// Generates a fresh mnemonic with the given word count (12, 15, 18, 21 or 24).
+    pub fn generate(network: Network, word_count: usize, passphrase: &str) -> Result<Self, WalletError> {
+        if !(12..=24).contains(&word_count) || word_count % 3 != 0 {
+            return Err(WalletError::KeyFormatError(format!(
+                "Unsupported mnemonic length: {}",
+                word_count
+            )));
+        }
+        let mut entropy = Zeroizing::new(vec![0u8; word_count / 3 * 4]);
+        rand::rngs::OsRng.fill_bytes(&mut entropy);
+        let mnemonic = Mnemonic::from_entropy_in(Language::English, &entropy)?;
+        Self::from_mnemonic(&Zeroizing::new(mnemonic.to_string()), passphrase, network)
+    }
+
+    /// Recovers all key material from an existing backup phrase.
+    pub fn from_mnemonic(phrase: &str, passphrase: &str, network: Network) -> Result<Self, WalletError> {
+        let mnemonic = Mnemonic::parse_in(Language::English, phrase)?;
+        let seed = Zeroizing::new(mnemonic.to_seed(passphrase));
+        let master = Xpriv::new_master(network, seed.as_slice())?;
+        Self::from_parts(Zeroizing::new(mnemonic.to_string()), &master, network)
+    }
+
+    fn from_parts(phrase: Zeroizing<String>, master: &Xpriv, network: Network) -> Result<Self, WalletError> {
+        let secp = Secp256k1::new();
+        Ok(Self {
+            fingerprint: master.fingerprint(&secp),
+            secrets: RwLock::new(Some(Secrets {
+                phrase,
+                master: Zeroizing::new(master.encode()),
+            })),
+            network,
+            secp,
+        })
+    }
+
+    /// Runs `f` over the seed, failing once it has been wiped. A lock poisoned by a panic
+    /// mid-wipe counts as wiped.
+    fn with_secrets<T>(&self, f: impl FnOnce(&Secrets) -> Result<T, WalletError>) -> Result<T, WalletError> {
+        let secrets = self.secrets.read().map_err(|_| WalletError::KeysWiped)?;
+        f(secrets.as_ref().ok_or(WalletError::KeysWiped)?)
+    }
+
+    /// Zeroizes the seed, for this and every other holder of the manager.
+    pub fn wipe(&self) {
+        let mut secrets = self
+            .secrets
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        *secrets = None;
+    }
+
+    pub fn is_wiped(&self) -> bool {
+        self.secrets.read().map_or(true, |secrets| secrets.is_none())
+    }
+
+    /// Gets the backup phrase.
+    pub fn mnemonic(&self) -> Result<Zeroizing<String>, WalletError> {
+        self.with_secrets(|secrets| Ok(secrets.phrase.clone()))
+    }
+
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    /// Serializes the master key and backup phrase, for encryption at rest.
+    pub(crate) fn to_secret_bytes(&self) -> Result<Zeroizing<Vec<u8>>, WalletError> {
+        self.with_secrets(|secrets| {
+            // Sized up front so no reallocation leaves a stray copy behind.
+            let mut bytes = Zeroizing::new(Vec::with_capacity(78 + secrets.phrase.len()));
+            bytes.extend_from_slice(secrets.master.as_slice());
+            bytes.extend_from_slice(secrets.phrase.as_bytes());
+            Ok(bytes)
+        })
+    }
+
+    /// Rebuilds a key manager from `to_secret_bytes` output.
+    pub(crate) fn from_secret_bytes(bytes: &[u8], network: Network) -> Result<Self, WalletError> {
+        if bytes.len() < 78 {
+            return Err(WalletError::KeyFormatError("Truncated key material".into()));
+        }
+        let master = Xpriv::decode(&bytes[..78])?;
+        let phrase = std::str::from_utf8(&bytes[78..])
+            .map_err(|e| WalletError::KeyFormatError(e.to_string()))?;
+        let mnemonic = Mnemonic::parse_in(Language::English, phrase)?;
+        Self::from_parts(Zeroizing::new(mnemonic.to_string()), &master, network)
+    }
+
+    /// Gets the master key fingerprint used in descriptor key origins.
+    pub fn master_fingerprint(&self) -> Fingerprint {
+        self.fingerprint
+    }
+
+    fn coin_type(&self) -> u32 {
+        match self.network {
+            Network::Bitcoin => 0,
+            _ => 1,
+        }
+    }
+
+    fn derive(&self, path: &DerivationPath) -> Result<Xpriv, WalletError> {
+        self.with_secrets(|secrets| {
+            Ok(Xpriv::decode(secrets.master.as_slice())?.derive_priv(&self.secp, path)?)
+        })
+    }
+
+    /// Gets the BIP86 account xpub, for watch-only export.
+    pub fn wallet_account_xpub(&self) -> Result<Xpub, WalletError> {
+        Ok(Xpub::from_priv(&self.secp, &self.derive(&self.wallet_account_path()?)?))
+    }
+
+    /// Gets the derivation path of the BIP86 account.
+    pub fn wallet_account_path(&self) -> Result<DerivationPath, WalletError> {
+        Ok(DerivationPath::from(vec![
+            ChildNumber::from_hardened_idx(WALLET_PURPOSE)?,
+            ChildNumber::from_hardened_idx(self.coin_type())?,
+            ChildNumber::from_hardened_idx(0)?,
+        ]))
+    }
+
+    /// Gets the derivation path of a wallet key.
+    pub fn wallet_key_path(&self, change: bool, index: u32) -> Result<DerivationPath, WalletError> {
+        Ok(self.wallet_account_path()?.extend([
+            ChildNumber::from_normal_idx(change as u32)?,
+            ChildNumber::from_normal_idx(index)?,
+        ]))
+    }
+
+    /// Derives a receive (`change == false`) or change wallet key.
+    pub fn wallet_key(&self, change: bool, index: u32) -> Result<KeyPair, WalletError> {
+        let xpriv = self.derive(&self.wallet_key_path(change, index)?)?;
+        Ok(KeyPair::from_secret_key(&self.secp, &xpriv.private_key))
+    }
+
+    /// Gets the derivation path of a channel key.
+    pub fn channel_key_path(&self, family: KeyFamily, channel_index: u32) -> Result<DerivationPath, WalletError> {
+        Ok(DerivationPath::from(vec![
+            ChildNumber::from_hardened_idx(CHANNEL_PURPOSE)?,
+            ChildNumber::from_hardened_idx(self.coin_type())?,
+            ChildNumber::from_hardened_idx(family as u32)?,
+            ChildNumber::from_hardened_idx(channel_index)?,
+        ]))
+    }
+
+    /// Derives the key of the given family for a channel.
+    pub fn channel_key(&self, family: KeyFamily, channel_index: u32) -> Result<KeyPair, WalletError> {
+        let xpriv = self.derive(&self.channel_key_path(family, channel_index)?)?;
+        Ok(KeyPair::from_secret_key(&self.secp, &xpriv.private_key))
+    }
+
+    /// Gets the derivation path of a channel key after `generation` rotations. Generation 0
+    /// is the original path; later ones add a hardened generation index.
+    pub fn rotated_key_path(&self, family: KeyFamily, channel_index: u32, generation: u32) -> Result<DerivationPath, WalletError> {
+        let path = self.channel_key_path(family, channel_index)?;
+        if generation == 0 {
+            return Ok(path);
+        }
+        Ok(path.extend([ChildNumber::from_hardened_idx(generation)?]))
+    }
+
+    /// Derives the key of the given family for a channel after `generation` rotations.
+    pub fn rotated_channel_key(&self, family: KeyFamily, channel_index: u32, generation: u32) -> Result<KeyPair, WalletError> {
+        let xpriv = self.derive(&self.rotated_key_path(family, channel_index, generation)?)?;
+        Ok(KeyPair::from_secret_key(&self.secp, &xpriv.private_key))
+    }
+
+    /// Gets the derivation path of the key the wallet signs its invoices with.
+    pub fn invoice_key_path(&self) -> Result<DerivationPath, WalletError> {
+        Ok(DerivationPath::from(vec![
+            ChildNumber::from_hardened_idx(CHANNEL_PURPOSE)?,
+            ChildNumber::from_hardened_idx(self.coin_type())?,
+            ChildNumber::from_hardened_idx(INVOICE_BRANCH)?,
+        ]))
+    }
+
+    /// Derives the key the wallet signs its invoices with.
+    pub fn invoice_key(&self) -> Result<KeyPair, WalletError> {
+        let xpriv = self.derive(&self.invoice_key_path()?)?;
+        Ok(KeyPair::from_secret_key(&self.secp, &xpriv.private_key))
+    }
+
+    /// Gets the derivation path of the key counterparties know the wallet's transport by.
+    pub fn transport_key_path(&self) -> Result<DerivationPath, WalletError> {
+        Ok(DerivationPath::from(vec![
+            ChildNumber::from_hardened_idx(CHANNEL_PURPOSE)?,
+            ChildNumber::from_hardened_idx(self.coin_type())?,
+            ChildNumber::from_hardened_idx(TRANSPORT_BRANCH)?,
+        ]))
+    }
+
+    /// Derives the key counterparties know the wallet's transport by.
+    pub fn transport_key(&self) -> Result<KeyPair, WalletError> {
+        let xpriv = self.derive(&self.transport_key_path()?)?;
+        Ok(KeyPair::from_secret_key(&self.secp, &xpriv.private_key))
+    }
+
+    /// Derives the raw secret of a channel key.
+    pub fn channel_secret(&self, family: KeyFamily, channel_index: u32) -> Result<SecretKey, WalletError> {
+        Ok(self.channel_key(family, channel_index)?.secret_key())
+    }
+
+    /// Derives the Pedersen blinding factor for a channel state.
+    pub fn blinding_factor(&self, channel_index: u32, state_nonce: u64) -> Result<BlindingFactor, WalletError> {
+        self.blinding_at(&BlindingPath::new(channel_index, state_nonce))
+    }
+
+    /// Derives the Pedersen blinding factor at `path`.
+    pub fn blinding_at(&self, path: &BlindingPath) -> Result<BlindingFactor, WalletError> {
+        let base = Zeroizing::new(self.channel_secret(KeyFamily::Blinding, path.channel_index)?.secret_bytes());
+        let mut digest = Sha512::new()
+            .chain_update(b"overpass/blinding")
+            .chain_update(base.as_slice())
+            .chain_update(path.update.to_be_bytes())
+            .chain_update(path.slot.to_be_bytes())
+            .finalize();
+        let mut wide = Zeroizing::new([0u8; 64]);
+        wide.copy_from_slice(&digest);
+        digest.as_mut_slice().zeroize();
+        Ok(BlindingFactor::from_wide_bytes(&wide))
+    }
+
+    /// Derives the blinding factor at `path` in the byte form taken by `pedersen_commit`.
+    pub fn blinding_bytes(&self, path: &BlindingPath) -> Result<Zeroizing<Bytes32>, WalletError> {
+        Ok(self.blinding_at(path)?.to_bytes())
+    }
+
+    /// Re-derives the blinding of a commitment to `value` at `path`, failing if it does not
+    /// open the commitment.
+    pub fn open_commitment(
+        &self,
+        params: &PedersenParameters,
+        commitment: &PedersenCommitment,
+        path: &BlindingPath,
+        value: u64,
+    ) -> Result<BlindingFactor, WalletError> {
+        let blinding = self.blinding_at(path)?;
+        if !commitment.verify_opening(params, value, blinding.scalar()) {
+            return Err(WalletError::KeyFormatError(format!(
+                "Commitment does not open to {} at blinding path {}",
+                value, path
+            )));
+        }
+        Ok(blinding)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::PHRASE;
+
+    #[test]
+    fn test_bip86_vector() {
+        let keys = KeyManager::from_mnemonic(PHRASE, "", Network::Bitcoin).unwrap();
+        let (x_only, _) = keys.wallet_key(false, 0).unwrap().x_only_public_key();
+        assert_eq!(
+            x_only.to_string(),
+            "cc8a4bc64d897bddc5fbc2f670f7a8ba0b386779106cf1223c6fc5d7cd6fc115"
+        );
+        assert_eq!(
+            keys.wallet_account_xpub().unwrap().to_string(),
+            "xpub6BgBgsespWvERF3LHQu6CnqdvfEvtMcQjYrcRzx53QJjSxarj2afYWcLteoGVky7D3UKDP9QyrLprQ3VCECoY49yfdDEHGCtMMj92pReUsQ"
+        );
+    }
+
+    #[test]
+    fn test_recovery_reproduces_all_keys() {
+        let original = KeyManager::generate(Network::Regtest, 24, "pass").unwrap();
+        let restored = KeyManager::from_mnemonic(&original.mnemonic().unwrap(), "pass", Network::Regtest).unwrap();
+
+        for family in [KeyFamily::Funding, KeyFamily::Dispute, KeyFamily::Revocation] {
+            assert_eq!(
+                original.channel_secret(family, 3).unwrap(),
+                restored.channel_secret(family, 3).unwrap()
+            );
+        }
+        assert_eq!(
+            original.blinding_factor(3, 42).unwrap(),
+            restored.blinding_factor(3, 42).unwrap()
+        );
+        assert_eq!(
+            original.wallet_key(true, 5).unwrap().secret_key(),
+            restored.wallet_key(true, 5).unwrap().secret_key()
+        );
+
+        // A different passphrase yields unrelated keys.
+        let other = KeyManager::from_mnemonic(&original.mnemonic().unwrap(), "", Network::Regtest).unwrap();
+        assert_ne!(
+            original.channel_secret(KeyFamily::Funding, 3).unwrap(),
+            other.channel_secret(KeyFamily::Funding, 3).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_key_separation() {
+        let keys = KeyManager::from_mnemonic(PHRASE, "", Network::Testnet).unwrap();
+        assert_ne!(
+            keys.channel_secret(KeyFamily::Funding, 0).unwrap(),
+            keys.channel_secret(KeyFamily::Dispute, 0).unwrap()
+        );
+        assert_ne!(
+            keys.channel_secret(KeyFamily::Funding, 0).unwrap(),
+            keys.channel_secret(KeyFamily::Funding, 1).unwrap()
+        );
+        assert_ne!(keys.blinding_factor(0, 1).unwrap(), keys.blinding_factor(0, 2).unwrap());
+        assert_eq!(
+            keys.channel_key_path(KeyFamily::Anchor, 7).unwrap().to_string(),
+            "m/9000'/1'/4'/7'"
+        );
+        assert!(KeyManager::generate(Network::Testnet, 13, "").is_err());
+    }
+
+    #[test]
+    fn test_restored_wallet_reopens_commitments() {
+        let params = PedersenParameters::default();
+        let keys = KeyManager::from_mnemonic(PHRASE, "", Network::Regtest).unwrap();
+        let channel_index = channel_index_for_id(&[7u8; 32]);
+        let path = BlindingPath::new(channel_index, 3).with_slot(1);
+        let commitment = PedersenCommitment::commit(&params, 250, keys.blinding_at(&path).unwrap().scalar());
+
+        let restored = KeyManager::from_mnemonic(PHRASE, "", Network::Regtest).unwrap();
+        assert!(restored.open_commitment(&params, &commitment, &path, 250).is_ok());
+        assert!(restored.open_commitment(&params, &commitment, &path, 251).is_err());
+        assert!(restored
+            .open_commitment(&params, &commitment, &BlindingPath::new(channel_index, 3), 250)
+            .is_err());
+        assert!(channel_index_for_id(&[7u8; 32]) < 1 << 31);
+    }
+
+    #[test]
+    fn test_wipe_reaches_every_holder() {
+        let keys = std::sync::Arc::new(KeyManager::from_mnemonic(PHRASE, "", Network::Regtest).unwrap());
+        let signer = keys.clone();
+        let fingerprint = keys.master_fingerprint();
+        assert!(signer.channel_secret(KeyFamily::Funding, 0).is_ok());
+
+        keys.wipe();
+        assert!(signer.is_wiped());
+        assert!(matches!(signer.channel_secret(KeyFamily::Funding, 0), Err(WalletError::KeysWiped)));
+        assert!(matches!(signer.mnemonic(), Err(WalletError::KeysWiped)));
+        assert!(signer.to_secret_bytes().is_err());
+        assert_eq!(signer.master_fingerprint(), fingerprint);
+    }
+}
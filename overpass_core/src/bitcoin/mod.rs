@@ -1,19 +1,94 @@
 // ./src/bitcoin/mod.rs
 
+#[cfg(feature = "bitcoin-backend")]
 pub mod client;
 pub mod scripts;
+#[cfg(feature = "bitcoin-backend")]
 pub mod bitcoin_transaction;
 pub mod wallet;
+#[cfg(feature = "bitcoin-backend")]
 pub mod rpc_client;
 pub mod bitcoin_types;
+#[cfg(feature = "bitcoin-backend")]
 pub mod zkp_handler;
 pub mod stealth_addresses;
+pub mod taproot;
+pub mod musig2;
+#[cfg(feature = "bitcoin-backend")]
+pub mod esplora;
+pub mod fees;
+pub mod rbf;
+pub mod timelocks;
+pub mod utxo;
+pub mod spv;
+pub mod chain;
+pub mod broadcast;
+pub mod monitor;
+pub mod anchors;
+#[cfg(feature = "prover")]
+pub mod root_anchor;
+#[cfg(feature = "prover")]
+pub mod keys;
+#[cfg(feature = "prover")]
+pub mod descriptors;
+#[cfg(feature = "prover")]
+pub mod addresses;
+pub mod confirmations;
+pub mod networks;
+pub mod funding;
+pub mod dust;
+#[cfg(feature = "prover")]
+pub mod commitment;
+#[cfg(feature = "prover")]
+pub mod sweep;
+pub mod signer;
+pub mod batch;
+pub mod triggers;
+#[cfg(feature = "prover")]
+pub mod anchor_scheduler;
 
+#[cfg(feature = "bitcoin-backend")]
 pub use client::BitcoinClient;
 pub use wallet::{StealthKeyPair, Wallet};
 pub use bitcoin_types::{HTLCParameters, StealthAddress};
+#[cfg(feature = "bitcoin-backend")]
 pub use zkp_handler::BitcoinHtlcProof;
 pub use stealth_addresses::{StealthAddressGenerator, StealthAddressManager};
+#[cfg(feature = "bitcoin-backend")]
 pub use rpc_client::{BitcoinRpcClient, BitcoinRpcConfig};
+#[cfg(feature = "bitcoin-backend")]
 pub use bitcoin_transaction::BitcoinTransaction;
-pub use bitcoin_types::BitcoinLockState;
\ No newline at end of file
+pub use bitcoin_types::BitcoinLockState;
+pub use taproot::{FundingLeaf, TaprootFunding, TaprootFundingParams};
+pub use musig2::{KeyAggContext, PartialSignature, PubNonce, SigningSession};
+pub use fees::{FeeEstimator, FeeEstimationError, StaticFeeEstimator};
+pub use rbf::{RbfPolicy, SettlementTracker};
+pub use timelocks::ChannelTimelocks;
+pub use utxo::{select_coins, CoinSelection, Utxo, UtxoSet};
+pub use spv::HeaderChain;
+pub use chain::{ChainBackend, ChainError};
+pub use broadcast::{BroadcastQueue, RetryPolicy};
+pub use monitor::{ChannelEvent, ChannelMonitor, WatchedChannel};
+#[cfg(feature = "prover")]
+pub use root_anchor::{AnchorPayload, RootAnchorer};
+#[cfg(feature = "prover")]
+pub use keys::{KeyFamily, KeyManager};
+#[cfg(feature = "prover")]
+pub use descriptors::{Descriptor, DescriptorKey};
+#[cfg(feature = "prover")]
+pub use addresses::{AddressError, AddressKind};
+pub use confirmations::{ChannelPhase, ConfirmationEvent, ConfirmationTracker};
+pub use networks::ChainNetwork;
+pub use funding::{build_funding_tx, verify_funding, FundingError, FundingEvent, FundingTx, FundingWatcher};
+pub use dust::DustPolicy;
+#[cfg(feature = "prover")]
+pub use commitment::{Commitment, CommitmentParams, CommitmentParty};
+#[cfg(feature = "prover")]
+pub use sweep::{SweepEvent, Sweeper};
+#[cfg(feature = "tokio")]
+pub use signer::AsyncSigner;
+pub use signer::{HwiSigner, KeySigner, Signer, SignerError};
+pub use batch::{build_batch_settlement, BatchError, BatchSettlement, ChannelPayout, SettlementLog, SettlementRecord};
+pub use triggers::{ChainTime, Interval};
+#[cfg(feature = "prover")]
+pub use anchor_scheduler::{AnchorPolicy, AnchorScheduler};
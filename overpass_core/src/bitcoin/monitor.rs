@@ -0,0 +1,452 @@
+// src/bitcoin/monitor.rs
+
+use crate::bitcoin::chain::{ChainBackend, ChainError};
+use crate::bitcoin::timelocks::ChannelTimelocks;
+use bitcoin::absolute::LockTime;
+use bitcoin::{OutPoint, Sequence, Transaction, Txid};
+use std::collections::{HashMap, HashSet};
+use thiserror::Error;
+
+/// Marker in the top byte of a commitment's lock time identifying an encoded state number.
+const LOCK_TIME_MARKER: u32 = 0x20;
+/// Marker in the top byte of a commitment input's sequence identifying an encoded state number.
+const SEQUENCE_MARKER: u32 = 0x80;
+const LOWER_24_BITS: u64 = 0x00ff_ffff;
+
+/// Largest state number that fits the lock time / sequence encoding.
+pub const MAX_STATE_NUMBER: u64 = (1 << 48) - 1;
+
+/// Reasons a state number cannot be encoded into a commitment.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum StateNumberError {
+    #[error("State number {0} too large to encode")]
+    TooLarge(u64),
+    #[error("Commitment has no inputs")]
+    NoInputs,
+}
+
+/// Encodes a channel state number into a commitment's lock time and first input sequence.
+///
+/// The lower 24 bits go into the lock time and the upper 24 bits into the sequence, each
+/// tagged with a marker byte. The lock time lands in the timestamp range far in the past,
+/// so it never delays confirmation, and the sequence keeps relative locks disabled.
+pub fn apply_state_number(tx: &mut Transaction, state_number: u64) -> Result<(), StateNumberError> {
+    if state_number > MAX_STATE_NUMBER {
+        return Err(StateNumberError::TooLarge(state_number));
+    }
+    let input = tx.input.first_mut().ok_or(StateNumberError::NoInputs)?;
+    input.sequence = Sequence((SEQUENCE_MARKER << 24) | (state_number >> 24) as u32);
+    tx.lock_time =
+        LockTime::from_consensus((LOCK_TIME_MARKER << 24) | (state_number & LOWER_24_BITS) as u32);
+    Ok(())
+}
+
+/// Decodes the state number of a commitment built with [`apply_state_number`].
+pub fn state_number_from_tx(tx: &Transaction) -> Option<u64> {
+    let lock_time = tx.lock_time.to_consensus_u32();
+    let sequence = tx.input.first()?.sequence.0;
+    if lock_time >> 24 != LOCK_TIME_MARKER || sequence >> 24 != SEQUENCE_MARKER {
+        return None;
+    }
+    Some((((sequence as u64) & LOWER_24_BITS) << 24) | ((lock_time as u64) & LOWER_24_BITS))
+}
+
+/// A channel whose funding output is being watched for unilateral closes.
+#[derive(Clone, Debug)]
+pub struct WatchedChannel {
+    pub channel_id: [u8; 32],
+    pub funding_outpoint: OutPoint,
+    /// Latest state number both parties have signed.
+    pub latest_state: u64,
+    pub timelocks: ChannelTimelocks,
+}
+
+/// Builds the transaction answering a close that published a revoked state.
+pub trait JusticeBuilder {
+    fn build_justice(
+        &self,
+        channel: &WatchedChannel,
+        breach_tx: &Transaction,
+    ) -> Result<Transaction, String>;
+}
+
+/// Events raised while monitoring funding outputs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ChannelEvent {
+    /// The funding output was spent by the latest state or a cooperative close.
+    Closed {
+        channel_id: [u8; 32],
+        txid: Txid,
+    },
+    /// A revoked state was published; `justice_tx` must confirm before `respond_by_height`.
+    BreachDetected {
+        channel_id: [u8; 32],
+        breach_txid: Txid,
+        published_state: u64,
+        latest_state: u64,
+        /// `None` while the breach is unconfirmed and the dispute window has not started.
+        respond_by_height: Option<u32>,
+        justice_tx: Transaction,
+    },
+    /// A breach was detected but no response could be built.
+    JusticeFailed {
+        channel_id: [u8; 32],
+        breach_txid: Txid,
+        reason: String,
+    },
+    /// The confirmed tip is within the expiry margin of the channel's timeout height and
+    /// the channel should be settled before the funder can reclaim the funding output.
+    Expiring {
+        channel_id: [u8; 32],
+        expiry_height: u32,
+        tip_height: u32,
+    },
+}
+
+/// Watches funding outpoints for spends and reacts to revoked-state closes.
+#[derive(Default)]
+pub struct ChannelMonitor {
+    channels: HashMap<OutPoint, WatchedChannel>,
+    handled: HashSet<Txid>,
+    expiry_margin: u32,
+    expiring: HashSet<OutPoint>,
+}
+
+impl ChannelMonitor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Raises `Expiring` this many blocks before a channel's expiry height.
+    pub fn with_expiry_margin(mut self, blocks: u32) -> Self {
+        self.expiry_margin = blocks;
+        self
+    }
+
+    /// Starts watching a channel's funding output.
+    pub fn watch(&mut self, channel: WatchedChannel) {
+        self.channels.insert(channel.funding_outpoint, channel);
+    }
+
+    /// Stops watching a channel.
+    pub fn unwatch(&mut self, funding_outpoint: &OutPoint) -> Option<WatchedChannel> {
+        self.expiring.remove(funding_outpoint);
+        self.channels.remove(funding_outpoint)
+    }
+
+    /// Records a newly signed state so older states are treated as revoked.
+    pub fn update_state(&mut self, funding_outpoint: &OutPoint, state_number: u64) -> bool {
+        match self.channels.get_mut(funding_outpoint) {
+            Some(channel) if state_number > channel.latest_state => {
+                channel.latest_state = state_number;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Gets a watched channel.
+    pub fn channel(&self, funding_outpoint: &OutPoint) -> Option<&WatchedChannel> {
+        self.channels.get(funding_outpoint)
+    }
+
+    /// Checks every watched funding output and raises events for new spends.
+    ///
+    /// Each spending transaction is reported once; finished channels stay watched until
+    /// the caller unwatches them. Expiry is judged by the backend's tip height, so it does
+    /// not depend on either party's clock.
+    pub fn poll(
+        &mut self,
+        backend: &dyn ChainBackend,
+        justice: &dyn JusticeBuilder,
+    ) -> Result<Vec<ChannelEvent>, ChainError> {
+        let mut events = Vec::new();
+        let tip_height = backend.tip_height()?;
+        for channel in self.channels.values() {
+            // One channel's lookup failing must not stop the others being watched.
+            let spend = match backend.spending_transaction(&channel.funding_outpoint) {
+                Ok(spend) => spend,
+                Err(e) => {
+                    tracing::warn!(
+                        funding_outpoint = %channel.funding_outpoint,
+                        "Failed to look up funding spend: {}",
+                        e
+                    );
+                    continue;
+                }
+            };
+            let Some(spend) = spend else {
+                if tip_height.saturating_add(self.expiry_margin) >= channel.timelocks.expiry_height
+                    && self.expiring.insert(channel.funding_outpoint)
+                {
+                    events.push(ChannelEvent::Expiring {
+                        channel_id: channel.channel_id,
+                        expiry_height: channel.timelocks.expiry_height,
+                        tip_height,
+                    });
+                }
+                continue;
+            };
+            let txid = spend.txid();
+            if self.handled.contains(&txid) {
+                continue;
+            }
+
+            let published_state = state_number_from_tx(&spend);
+            match published_state {
+                Some(state) if state < channel.latest_state => {
+                    let respond_by_height = match backend.confirmations(&txid) {
+                        Ok(Some(confirmations)) if confirmations > 0 => {
+                            let confirm_height = (tip_height + 1).saturating_sub(confirmations);
+                            Some(channel.timelocks.dispute_mature_height(confirm_height))
+                        }
+                        _ => None,
+                    };
+                    events.push(match justice.build_justice(channel, &spend) {
+                        Ok(justice_tx) => ChannelEvent::BreachDetected {
+                            channel_id: channel.channel_id,
+                            breach_txid: txid,
+                            published_state: state,
+                            latest_state: channel.latest_state,
+                            respond_by_height,
+                            justice_tx,
+                        },
+                        Err(reason) => ChannelEvent::JusticeFailed {
+                            channel_id: channel.channel_id,
+                            breach_txid: txid,
+                            reason,
+                        },
+                    });
+                }
+                _ => events.push(ChannelEvent::Closed {
+                    channel_id: channel.channel_id,
+                    txid,
+                }),
+            }
+        }
+
+        for event in &events {
+            let txid = match event {
+                ChannelEvent::Closed { txid, .. } => txid,
+                ChannelEvent::BreachDetected { breach_txid, .. } => breach_txid,
+                ChannelEvent::JusticeFailed { breach_txid, .. } => breach_txid,
+                ChannelEvent::Expiring { .. } => continue,
+            };
+            self.handled.insert(*txid);
+        }
+        Ok(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::hashes::Hash;
+    use bitcoin::{ScriptBuf, TxIn, TxOut, Witness};
+
+    struct MockChain {
+        spend: Option<Transaction>,
+        confirmations: Option<u32>,
+        failing: Option<OutPoint>,
+    }
+
+    impl ChainBackend for MockChain {
+        fn broadcast(&self, tx: &Transaction) -> Result<Txid, ChainError> {
+            Ok(tx.txid())
+        }
+
+        fn confirmations(&self, _txid: &Txid) -> Result<Option<u32>, ChainError> {
+            Ok(self.confirmations)
+        }
+
+        fn tip_height(&self) -> Result<u32, ChainError> {
+            Ok(1_000)
+        }
+
+        fn spending_transaction(&self, outpoint: &OutPoint) -> Result<Option<Transaction>, ChainError> {
+            if self.failing == Some(*outpoint) {
+                return Err(ChainError::BackendError("lookup failed".to_string()));
+            }
+            Ok(self.spend.clone())
+        }
+    }
+
+    struct SweepToSelf;
+
+    impl JusticeBuilder for SweepToSelf {
+        fn build_justice(
+            &self,
+            _channel: &WatchedChannel,
+            breach_tx: &Transaction,
+        ) -> Result<Transaction, String> {
+            Ok(Transaction {
+                version: 2,
+                lock_time: LockTime::ZERO,
+                input: vec![TxIn {
+                    previous_output: OutPoint::new(breach_tx.txid(), 0),
+                    script_sig: ScriptBuf::new(),
+                    sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                    witness: Witness::new(),
+                }],
+                output: vec![TxOut {
+                    value: 9_000,
+                    script_pubkey: ScriptBuf::new(),
+                }],
+            })
+        }
+    }
+
+    fn funding_outpoint() -> OutPoint {
+        OutPoint::new(Txid::all_zeros(), 0)
+    }
+
+    fn commitment(state_number: u64) -> Transaction {
+        let mut tx = Transaction {
+            version: 2,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: funding_outpoint(),
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::MAX,
+                witness: Witness::new(),
+            }],
+            output: vec![TxOut {
+                value: 10_000,
+                script_pubkey: ScriptBuf::new(),
+            }],
+        };
+        apply_state_number(&mut tx, state_number).unwrap();
+        tx
+    }
+
+    fn monitor(latest_state: u64) -> ChannelMonitor {
+        let mut monitor = ChannelMonitor::new();
+        monitor.watch(WatchedChannel {
+            channel_id: [7u8; 32],
+            funding_outpoint: funding_outpoint(),
+            latest_state,
+            timelocks: ChannelTimelocks {
+                dispute_delay: 144,
+                expiry_height: 5_000,
+            },
+        });
+        monitor
+    }
+
+    #[test]
+    fn test_state_number_roundtrip() {
+        for state in [0, 1, 0x00ff_ffff, 0x0100_0000, MAX_STATE_NUMBER] {
+            assert_eq!(state_number_from_tx(&commitment(state)), Some(state));
+        }
+        let mut tx = commitment(0);
+        assert_eq!(
+            apply_state_number(&mut tx, MAX_STATE_NUMBER + 1),
+            Err(StateNumberError::TooLarge(MAX_STATE_NUMBER + 1))
+        );
+        tx.lock_time = LockTime::ZERO;
+        assert_eq!(state_number_from_tx(&tx), None);
+    }
+
+    #[test]
+    fn test_detects_revoked_state() {
+        let mut monitor = monitor(5);
+        let backend = MockChain {
+            spend: Some(commitment(3)),
+            confirmations: Some(2),
+            failing: None,
+        };
+
+        let events = monitor.poll(&backend, &SweepToSelf).unwrap();
+        match &events[..] {
+            [ChannelEvent::BreachDetected {
+                published_state,
+                latest_state,
+                respond_by_height,
+                justice_tx,
+                ..
+            }] => {
+                assert_eq!((*published_state, *latest_state), (3, 5));
+                // Confirmed at height 999, so the window closes 144 blocks later.
+                assert_eq!(*respond_by_height, Some(999 + 144));
+                assert_eq!(justice_tx.input[0].previous_output.txid, commitment(3).txid());
+            }
+            other => panic!("unexpected events: {:?}", other),
+        }
+
+        // The same spend is not reported twice.
+        assert!(monitor.poll(&backend, &SweepToSelf).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_latest_state_close_is_not_a_breach() {
+        let mut monitor = monitor(5);
+        assert!(monitor.update_state(&funding_outpoint(), 6));
+        assert!(!monitor.update_state(&funding_outpoint(), 4));
+
+        let backend = MockChain {
+            spend: Some(commitment(6)),
+            confirmations: Some(0),
+            failing: None,
+        };
+        let events = monitor.poll(&backend, &SweepToSelf).unwrap();
+        assert!(matches!(events[..], [ChannelEvent::Closed { .. }]));
+    }
+
+    #[test]
+    fn test_expiry_follows_tip_height() {
+        let backend = MockChain {
+            spend: None,
+            confirmations: None,
+            failing: None,
+        };
+        let mut monitor = monitor(5);
+        assert!(monitor.poll(&backend, &SweepToSelf).unwrap().is_empty());
+
+        // The tip at 1,000 is within 4,000 blocks of the 5,000 expiry.
+        let mut monitor = monitor.with_expiry_margin(4_000);
+        let events = monitor.poll(&backend, &SweepToSelf).unwrap();
+        assert!(matches!(
+            events[..],
+            [ChannelEvent::Expiring {
+                expiry_height: 5_000,
+                tip_height: 1_000,
+                ..
+            }]
+        ));
+        assert!(monitor.poll(&backend, &SweepToSelf).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_failed_lookup_does_not_stop_other_channels() {
+        let mut monitor = monitor(5);
+        let failing = OutPoint::new(Txid::all_zeros(), 1);
+        monitor.watch(WatchedChannel {
+            channel_id: [8u8; 32],
+            funding_outpoint: failing,
+            latest_state: 5,
+            timelocks: ChannelTimelocks {
+                dispute_delay: 144,
+                expiry_height: 5_000,
+            },
+        });
+        // More confirmations than the tip height must not underflow.
+        let backend = MockChain {
+            spend: Some(commitment(3)),
+            confirmations: Some(2_000),
+            failing: Some(failing),
+        };
+
+        let events = monitor.poll(&backend, &SweepToSelf).unwrap();
+        match &events[..] {
+            [ChannelEvent::BreachDetected {
+                channel_id,
+                respond_by_height,
+                ..
+            }] => {
+                assert_eq!(*channel_id, [7u8; 32]);
+                assert_eq!(*respond_by_height, Some(144));
+            }
+            other => panic!("unexpected events: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,557 @@
+// src/bitcoin/musig2.rs
+
+//! MuSig2 (BIP327) two-round signing between channel counterparties.
+//!
+//! The aggregate key is used as the Taproot internal key of the funding output, so a
+//! cooperative close is a single key-path spend indistinguishable from a single-sig spend.
+
+use bitcoin::secp256k1::{self, schnorr, PublicKey, SecretKey, XOnlyPublicKey};
+use bitcoin::sighash::{Prevouts, SighashCache, TapSighashType};
+use bitcoin::taproot::{TapNodeHash, TapTweakHash};
+use bitcoin::hashes::Hash;
+use bitcoin::{Transaction, TxOut};
+use k256::elliptic_curve::ops::Reduce;
+use k256::elliptic_curve::point::AffineCoordinates;
+use k256::elliptic_curve::sec1::ToEncodedPoint;
+use k256::elliptic_curve::PrimeField;
+use k256::{FieldBytes, ProjectivePoint, Scalar, U256};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+/// Errors raised during MuSig2 key aggregation and signing.
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum MusigError {
+    #[error("No public keys supplied")]
+    NoKeys,
+    #[error("Invalid public key: {0}")]
+    InvalidPublicKey(String),
+    #[error("Invalid nonce: {0}")]
+    InvalidNonce(String),
+    #[error("Invalid partial signature: {0}")]
+    InvalidPartialSignature(String),
+    #[error("Signer is not part of the aggregate key")]
+    UnknownSigner,
+    #[error("Aggregation produced the point at infinity")]
+    PointAtInfinity,
+    #[error("Sighash error: {0}")]
+    SighashError(String),
+}
+
+/// Key aggregation context over the ordered set of signer keys, including any tweaks.
+#[derive(Clone, Debug)]
+pub struct KeyAggContext {
+    pubkeys: Vec<PublicKey>,
+    keys_hash: [u8; 32],
+    second_key: Option<PublicKey>,
+    q: ProjectivePoint,
+    gacc: Scalar,
+    tacc: Scalar,
+}
+
+impl KeyAggContext {
+    /// Aggregates the given public keys. Key order matters and must match across signers.
+    pub fn new(pubkeys: Vec<PublicKey>) -> Result<Self, MusigError> {
+        if pubkeys.is_empty() {
+            return Err(MusigError::NoKeys);
+        }
+
+        let mut list = Vec::with_capacity(pubkeys.len() * 33);
+        for pk in &pubkeys {
+            list.extend_from_slice(&pk.serialize());
+        }
+        let keys_hash = tagged_hash("KeyAgg list", &[&list]);
+        let second_key = pubkeys.iter().find(|pk| **pk != pubkeys[0]).copied();
+
+        let mut q = ProjectivePoint::IDENTITY;
+        for pk in &pubkeys {
+            let coefficient = key_agg_coefficient(&keys_hash, second_key.as_ref(), pk);
+            q += to_point(pk)? * coefficient;
+        }
+        if q == ProjectivePoint::IDENTITY {
+            return Err(MusigError::PointAtInfinity);
+        }
+
+        Ok(Self {
+            pubkeys,
+            keys_hash,
+            second_key,
+            q,
+            gacc: Scalar::ONE,
+            tacc: Scalar::ZERO,
+        })
+    }
+
+    /// Applies the BIP341 Taproot tweak for the given script tree merkle root.
+    ///
+    /// The resulting context signs for the funding output key rather than the internal key.
+    pub fn with_taproot_tweak(&self, merkle_root: Option<TapNodeHash>) -> Result<Self, MusigError> {
+        let tweak = TapTweakHash::from_key_and_tweak(self.x_only_public_key(), merkle_root);
+        self.with_xonly_tweak(tweak.to_byte_array())
+    }
+
+    /// Applies an x-only tweak to the aggregate key.
+    pub fn with_xonly_tweak(&self, tweak: [u8; 32]) -> Result<Self, MusigError> {
+        let t = Option::<Scalar>::from(Scalar::from_repr(tweak.into()))
+            .ok_or_else(|| MusigError::InvalidPublicKey("Tweak exceeds curve order".to_string()))?;
+        let g = if has_even_y(&self.q) { Scalar::ONE } else { -Scalar::ONE };
+
+        let q = self.q * g + ProjectivePoint::GENERATOR * t;
+        if q == ProjectivePoint::IDENTITY {
+            return Err(MusigError::PointAtInfinity);
+        }
+
+        Ok(Self {
+            q,
+            gacc: g * self.gacc,
+            tacc: t + g * self.tacc,
+            ..self.clone()
+        })
+    }
+
+    /// Gets the signer keys in aggregation order.
+    pub fn pubkeys(&self) -> &[PublicKey] {
+        &self.pubkeys
+    }
+
+    /// Gets the (possibly tweaked) aggregate key.
+    pub fn aggregated_pubkey(&self) -> PublicKey {
+        from_point(&self.q).expect("aggregate key is never the point at infinity")
+    }
+
+    /// Gets the x-only aggregate key used for BIP340 verification.
+    pub fn x_only_public_key(&self) -> XOnlyPublicKey {
+        self.aggregated_pubkey().x_only_public_key().0
+    }
+
+    fn coefficient(&self, pk: &PublicKey) -> Scalar {
+        key_agg_coefficient(&self.keys_hash, self.second_key.as_ref(), pk)
+    }
+}
+
+/// Secret nonce for a single signing session. Consumed on use to prevent nonce reuse.
+pub struct SecNonce {
+    k1: Scalar,
+    k2: Scalar,
+    pubkey: PublicKey,
+}
+
+/// Public nonce shared with the counterparty in the first signing round.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PubNonce {
+    r1: PublicKey,
+    r2: PublicKey,
+}
+
+impl PubNonce {
+    /// Serializes the nonce as two compressed points.
+    pub fn to_bytes(&self) -> [u8; 66] {
+        let mut bytes = [0u8; 66];
+        bytes[..33].copy_from_slice(&self.r1.serialize());
+        bytes[33..].copy_from_slice(&self.r2.serialize());
+        bytes
+    }
+
+    /// Parses a nonce from two compressed points.
+    pub fn from_bytes(bytes: &[u8; 66]) -> Result<Self, MusigError> {
+        let r1 = PublicKey::from_slice(&bytes[..33])
+            .map_err(|e| MusigError::InvalidNonce(e.to_string()))?;
+        let r2 = PublicKey::from_slice(&bytes[33..])
+            .map_err(|e| MusigError::InvalidNonce(e.to_string()))?;
+        Ok(Self { r1, r2 })
+    }
+}
+
+/// Sum of all signers' public nonces.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AggNonce {
+    r1: ProjectivePoint,
+    r2: ProjectivePoint,
+}
+
+impl AggNonce {
+    /// Aggregates the public nonces of all signers.
+    pub fn new(nonces: &[PubNonce]) -> Result<Self, MusigError> {
+        if nonces.is_empty() {
+            return Err(MusigError::InvalidNonce("No nonces supplied".to_string()));
+        }
+        let mut r1 = ProjectivePoint::IDENTITY;
+        let mut r2 = ProjectivePoint::IDENTITY;
+        for nonce in nonces {
+            r1 += to_point(&nonce.r1)?;
+            r2 += to_point(&nonce.r2)?;
+        }
+        Ok(Self { r1, r2 })
+    }
+
+    /// Serializes the aggregate nonce, encoding the point at infinity as 33 zero bytes.
+    pub fn to_bytes(&self) -> [u8; 66] {
+        let mut bytes = [0u8; 66];
+        bytes[..33].copy_from_slice(&cbytes_ext(&self.r1));
+        bytes[33..].copy_from_slice(&cbytes_ext(&self.r2));
+        bytes
+    }
+}
+
+/// A signer's partial signature over the session message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PartialSignature(Scalar);
+
+impl PartialSignature {
+    /// Serializes the partial signature as a 32-byte big-endian scalar.
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.0.to_bytes().into()
+    }
+
+    /// Parses a partial signature, rejecting values not below the curve order.
+    pub fn from_bytes(bytes: &[u8; 32]) -> Result<Self, MusigError> {
+        Option::<Scalar>::from(Scalar::from_repr((*bytes).into()))
+            .map(Self)
+            .ok_or_else(|| MusigError::InvalidPartialSignature("Scalar exceeds curve order".to_string()))
+    }
+}
+
+/// Generates a fresh nonce pair for signing `message` under `key_agg`.
+pub fn nonce_gen(
+    secret_key: Option<&SecretKey>,
+    pubkey: &PublicKey,
+    key_agg: &KeyAggContext,
+    message: Option<&[u8]>,
+    extra_input: &[u8],
+) -> (SecNonce, PubNonce) {
+    let mut rand_prime = [0u8; 32];
+    OsRng.fill_bytes(&mut rand_prime);
+    nonce_gen_internal(rand_prime, secret_key, pubkey, key_agg, message, extra_input)
+}
+
+fn nonce_gen_internal(
+    rand_prime: [u8; 32],
+    secret_key: Option<&SecretKey>,
+    pubkey: &PublicKey,
+    key_agg: &KeyAggContext,
+    message: Option<&[u8]>,
+    extra_input: &[u8],
+) -> (SecNonce, PubNonce) {
+    let mut rand = rand_prime;
+    if let Some(sk) = secret_key {
+        let aux = tagged_hash("MuSig/aux", &[&rand_prime]);
+        for (byte, mask) in rand.iter_mut().zip(sk.secret_bytes().iter().zip(aux.iter())) {
+            *byte = mask.0 ^ mask.1;
+        }
+    }
+
+    let pk_bytes = pubkey.serialize();
+    let agg_bytes = key_agg.x_only_public_key().serialize();
+    let msg_prefixed = match message {
+        Some(m) => {
+            let mut prefixed = vec![1u8];
+            prefixed.extend_from_slice(&(m.len() as u64).to_be_bytes());
+            prefixed.extend_from_slice(m);
+            prefixed
+        }
+        None => vec![0u8],
+    };
+    let extra_len = (extra_input.len() as u32).to_be_bytes();
+
+    let derive = |index: u8| -> Scalar {
+        let hash = tagged_hash(
+            "MuSig/nonce",
+            &[
+                &rand,
+                &[pk_bytes.len() as u8],
+                &pk_bytes,
+                &[agg_bytes.len() as u8],
+                &agg_bytes,
+                &msg_prefixed,
+                &extra_len,
+                extra_input,
+                &[index],
+            ],
+        );
+        scalar_from_hash(hash)
+    };
+
+    let k1 = derive(0);
+    let k2 = derive(1);
+    let pub_nonce = PubNonce {
+        r1: from_point(&(ProjectivePoint::GENERATOR * k1)).expect("nonce is never zero"),
+        r2: from_point(&(ProjectivePoint::GENERATOR * k2)).expect("nonce is never zero"),
+    };
+
+    (SecNonce { k1, k2, pubkey: *pubkey }, pub_nonce)
+}
+
+/// Signing session binding the key aggregation context, aggregate nonce, and message.
+#[derive(Clone, Debug)]
+pub struct SigningSession {
+    key_agg: KeyAggContext,
+    message: Vec<u8>,
+    b: Scalar,
+    r: ProjectivePoint,
+    e: Scalar,
+}
+
+impl SigningSession {
+    /// Creates the session for signing `message` with the aggregated nonces.
+    pub fn new(key_agg: KeyAggContext, agg_nonce: &AggNonce, message: &[u8]) -> Self {
+        let agg_bytes = agg_nonce.to_bytes();
+        let q_bytes = key_agg.x_only_public_key().serialize();
+        let b = scalar_from_hash(tagged_hash("MuSig/noncecoef", &[&agg_bytes, &q_bytes, message]));
+
+        let mut r = agg_nonce.r1 + agg_nonce.r2 * b;
+        if r == ProjectivePoint::IDENTITY {
+            r = ProjectivePoint::GENERATOR;
+        }
+        let r_x: [u8; 32] = r.to_affine().x().into();
+        let e = scalar_from_hash(tagged_hash("BIP0340/challenge", &[&r_x, &q_bytes, message]));
+
+        Self {
+            key_agg,
+            message: message.to_vec(),
+            b,
+            r,
+            e,
+        }
+    }
+
+    /// Gets the message being signed.
+    pub fn message(&self) -> &[u8] {
+        &self.message
+    }
+
+    /// Produces this signer's partial signature, consuming the secret nonce.
+    pub fn partial_sign(
+        &self,
+        sec_nonce: SecNonce,
+        secret_key: &SecretKey,
+    ) -> Result<PartialSignature, MusigError> {
+        let secp = secp256k1::Secp256k1::signing_only();
+        let pubkey = PublicKey::from_secret_key(&secp, secret_key);
+        if pubkey != sec_nonce.pubkey {
+            return Err(MusigError::InvalidNonce("Nonce was generated for a different key".to_string()));
+        }
+        if !self.key_agg.pubkeys.contains(&pubkey) {
+            return Err(MusigError::UnknownSigner);
+        }
+
+        let (k1, k2) = if has_even_y(&self.r) {
+            (sec_nonce.k1, sec_nonce.k2)
+        } else {
+            (-sec_nonce.k1, -sec_nonce.k2)
+        };
+        let d = self.signer_factor() * scalar_from_secret(secret_key);
+        let a = self.key_agg.coefficient(&pubkey);
+
+        let partial = PartialSignature(k1 + self.b * k2 + self.e * a * d);
+        Ok(partial)
+    }
+
+    /// Verifies a counterparty's partial signature against their public nonce and key.
+    pub fn verify_partial(
+        &self,
+        partial: &PartialSignature,
+        pub_nonce: &PubNonce,
+        pubkey: &PublicKey,
+    ) -> Result<bool, MusigError> {
+        if !self.key_agg.pubkeys.contains(pubkey) {
+            return Err(MusigError::UnknownSigner);
+        }
+
+        let mut r_effective = to_point(&pub_nonce.r1)? + to_point(&pub_nonce.r2)? * self.b;
+        if !has_even_y(&self.r) {
+            r_effective = -r_effective;
+        }
+        let a = self.key_agg.coefficient(pubkey);
+        let expected = r_effective + to_point(pubkey)? * (self.e * a * self.signer_factor());
+
+        Ok(ProjectivePoint::GENERATOR * partial.0 == expected)
+    }
+
+    /// Combines all partial signatures into a BIP340 signature for the aggregate key.
+    pub fn aggregate(&self, partials: &[PartialSignature]) -> Result<schnorr::Signature, MusigError> {
+        let g = if has_even_y(&self.key_agg.q) { Scalar::ONE } else { -Scalar::ONE };
+        let s = partials
+            .iter()
+            .fold(self.e * g * self.key_agg.tacc, |acc, partial| acc + partial.0);
+
+        let mut bytes = [0u8; 64];
+        let r_x: [u8; 32] = self.r.to_affine().x().into();
+        bytes[..32].copy_from_slice(&r_x);
+        bytes[32..].copy_from_slice(&s.to_bytes());
+        schnorr::Signature::from_slice(&bytes)
+            .map_err(|e| MusigError::InvalidPartialSignature(e.to_string()))
+    }
+
+    /// Combined sign factor `g * gacc` applied to every signer's secret key.
+    fn signer_factor(&self) -> Scalar {
+        let g = if has_even_y(&self.key_agg.q) { Scalar::ONE } else { -Scalar::ONE };
+        g * self.key_agg.gacc
+    }
+}
+
+/// Computes the BIP341 key-spend sighash for a cooperative spend of the given input.
+pub fn cooperative_spend_message(
+    tx: &Transaction,
+    input_index: usize,
+    prevouts: &[TxOut],
+) -> Result<[u8; 32], MusigError> {
+    let sighash = SighashCache::new(tx)
+        .taproot_key_spend_signature_hash(input_index, &Prevouts::All(prevouts), TapSighashType::Default)
+        .map_err(|e| MusigError::SighashError(e.to_string()))?;
+    Ok(sighash.to_byte_array())
+}
+
+/// BIP340 tagged hash: `SHA256(SHA256(tag) || SHA256(tag) || data...)`.
+fn tagged_hash(tag: &str, data: &[&[u8]]) -> [u8; 32] {
+    let tag_hash = Sha256::digest(tag.as_bytes());
+    let mut hasher = Sha256::new();
+    hasher.update(tag_hash);
+    hasher.update(tag_hash);
+    for chunk in data {
+        hasher.update(chunk);
+    }
+    hasher.finalize().into()
+}
+
+fn key_agg_coefficient(keys_hash: &[u8; 32], second_key: Option<&PublicKey>, pk: &PublicKey) -> Scalar {
+    if second_key == Some(pk) {
+        return Scalar::ONE;
+    }
+    scalar_from_hash(tagged_hash("KeyAgg coefficient", &[keys_hash, &pk.serialize()]))
+}
+
+fn scalar_from_hash(hash: [u8; 32]) -> Scalar {
+    <Scalar as Reduce<U256>>::reduce_bytes(&FieldBytes::from(hash))
+}
+
+fn scalar_from_secret(secret_key: &SecretKey) -> Scalar {
+    Option::<Scalar>::from(Scalar::from_repr(secret_key.secret_bytes().into()))
+        .expect("secret keys are always below the curve order")
+}
+
+fn has_even_y(point: &ProjectivePoint) -> bool {
+    !bool::from(point.to_affine().y_is_odd())
+}
+
+fn to_point(pk: &PublicKey) -> Result<ProjectivePoint, MusigError> {
+    k256::PublicKey::from_sec1_bytes(&pk.serialize())
+        .map(|key| key.to_projective())
+        .map_err(|e| MusigError::InvalidPublicKey(e.to_string()))
+}
+
+fn from_point(point: &ProjectivePoint) -> Result<PublicKey, MusigError> {
+    if *point == ProjectivePoint::IDENTITY {
+        return Err(MusigError::PointAtInfinity);
+    }
+    let encoded = point.to_affine().to_encoded_point(true);
+    PublicKey::from_slice(encoded.as_bytes()).map_err(|e| MusigError::InvalidPublicKey(e.to_string()))
+}
+
+fn cbytes_ext(point: &ProjectivePoint) -> [u8; 33] {
+    let mut bytes = [0u8; 33];
+    if *point != ProjectivePoint::IDENTITY {
+        bytes.copy_from_slice(point.to_affine().to_encoded_point(true).as_bytes());
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::taproot::{TaprootFunding, TaprootFundingParams};
+    use bitcoin::secp256k1::{Message, Secp256k1};
+
+    fn keypair(byte: u8) -> (SecretKey, PublicKey) {
+        let secp = Secp256k1::new();
+        let sk = SecretKey::from_slice(&[byte; 32]).unwrap();
+        (sk, PublicKey::from_secret_key(&secp, &sk))
+    }
+
+    fn sign_2_of_2(key_agg: &KeyAggContext, message: &[u8]) -> schnorr::Signature {
+        let (sk_a, pk_a) = keypair(7);
+        let (sk_b, pk_b) = keypair(9);
+
+        let (sec_a, pub_a) = nonce_gen(Some(&sk_a), &pk_a, key_agg, Some(message), &[]);
+        let (sec_b, pub_b) = nonce_gen(Some(&sk_b), &pk_b, key_agg, Some(message), &[]);
+        let agg_nonce = AggNonce::new(&[pub_a, pub_b]).unwrap();
+        let session = SigningSession::new(key_agg.clone(), &agg_nonce, message);
+
+        let partial_a = session.partial_sign(sec_a, &sk_a).unwrap();
+        let partial_b = session.partial_sign(sec_b, &sk_b).unwrap();
+        assert!(session.verify_partial(&partial_a, &pub_a, &pk_a).unwrap());
+        assert!(session.verify_partial(&partial_b, &pub_b, &pk_b).unwrap());
+
+        session.aggregate(&[partial_a, partial_b]).unwrap()
+    }
+
+    #[test]
+    fn test_two_party_signature_verifies() {
+        let secp = Secp256k1::new();
+        let key_agg = KeyAggContext::new(vec![keypair(7).1, keypair(9).1]).unwrap();
+        let message = [42u8; 32];
+
+        let signature = sign_2_of_2(&key_agg, &message);
+        let msg = Message::from_slice(&message).unwrap();
+        assert!(secp
+            .verify_schnorr(&signature, &msg, &key_agg.x_only_public_key())
+            .is_ok());
+    }
+
+    #[test]
+    fn test_taproot_tweaked_signature_matches_funding_output() {
+        let secp = Secp256k1::new();
+        let key_agg = KeyAggContext::new(vec![keypair(7).1, keypair(9).1]).unwrap();
+        let funding = TaprootFunding::new(
+            &secp,
+            TaprootFundingParams {
+                internal_key: key_agg.x_only_public_key(),
+                dispute_key: keypair(7).1.x_only_public_key().0,
+                dispute_delay: 144,
+                timeout_key: keypair(9).1.x_only_public_key().0,
+                timeout_height: 800_000,
+            },
+        )
+        .unwrap();
+
+        let tweaked = key_agg
+            .with_taproot_tweak(funding.spend_info().merkle_root())
+            .unwrap();
+        assert_eq!(tweaked.x_only_public_key(), funding.output_key());
+
+        let message = [5u8; 32];
+        let signature = sign_2_of_2(&tweaked, &message);
+        let msg = Message::from_slice(&message).unwrap();
+        assert!(secp
+            .verify_schnorr(&signature, &msg, &funding.output_key())
+            .is_ok());
+    }
+
+    #[test]
+    fn test_invalid_partial_signature_rejected() {
+        let key_agg = KeyAggContext::new(vec![keypair(7).1, keypair(9).1]).unwrap();
+        let (sk_a, pk_a) = keypair(7);
+        let (_, pk_b) = keypair(9);
+        let message = [1u8; 32];
+
+        let (sec_a, pub_a) = nonce_gen(Some(&sk_a), &pk_a, &key_agg, Some(&message), &[]);
+        let (_, pub_b) = nonce_gen(None, &pk_b, &key_agg, Some(&message), &[]);
+        let session = SigningSession::new(key_agg, &AggNonce::new(&[pub_a, pub_b]).unwrap(), &message);
+
+        let partial_a = session.partial_sign(sec_a, &sk_a).unwrap();
+        assert!(!session.verify_partial(&partial_a, &pub_b, &pk_b).unwrap());
+
+        let round_trip = PartialSignature::from_bytes(&partial_a.to_bytes()).unwrap();
+        assert_eq!(round_trip, partial_a);
+    }
+
+    #[test]
+    fn test_unknown_signer_rejected() {
+        let key_agg = KeyAggContext::new(vec![keypair(7).1, keypair(9).1]).unwrap();
+        let (sk_c, pk_c) = keypair(11);
+        let (sec_c, pub_c) = nonce_gen(Some(&sk_c), &pk_c, &key_agg, None, &[]);
+        let session = SigningSession::new(key_agg, &AggNonce::new(&[pub_c]).unwrap(), &[0u8; 32]);
+
+        assert_eq!(session.partial_sign(sec_c, &sk_c), Err(MusigError::UnknownSigner));
+    }
+}
@@ -0,0 +1,187 @@
+// src/bitcoin/networks.rs
+
+use crate::bitcoin::spv::HeaderChain;
+use bitcoin::block::{Header, Version};
+use bitcoin::blockdata::constants::genesis_block;
+use bitcoin::hash_types::TxMerkleNode;
+use bitcoin::hashes::Hash;
+use bitcoin::{BlockHash, CompactTarget, FeeRate, Network};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Merkle root of the testnet4 genesis block (BIP94).
+const TESTNET4_GENESIS_MERKLE_ROOT: &str = "7aa0a7ae1e223414cb807e40cd57e667b718e42aaf9306db9102fe28912b7b4e";
+
+/// Mainnet block 840,000 (the fourth halving), used as a sync checkpoint.
+const MAINNET_CHECKPOINTS: &[(u32, &str)] =
+    &[(840_000, "0000000000000000000320283a032748cef8227873ff4872689bf23f1cda83a5")];
+
+/// Bitcoin networks the channel flow can run on.
+///
+/// Wraps [`bitcoin::Network`] to add testnet4, which shares testnet3's address versions
+/// and consensus rules but has its own genesis block and ports.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ChainNetwork {
+    Mainnet,
+    Testnet3,
+    Testnet4,
+    Signet,
+    Regtest,
+}
+
+impl ChainNetwork {
+    /// Gets the `bitcoin` network used for address versions and consensus parameters.
+    pub fn network(&self) -> Network {
+        match self {
+            ChainNetwork::Mainnet => Network::Bitcoin,
+            ChainNetwork::Testnet3 | ChainNetwork::Testnet4 => Network::Testnet,
+            ChainNetwork::Signet => Network::Signet,
+            ChainNetwork::Regtest => Network::Regtest,
+        }
+    }
+
+    /// Gets Bitcoin Core's default RPC port.
+    pub fn default_rpc_port(&self) -> u16 {
+        match self {
+            ChainNetwork::Mainnet => 8332,
+            ChainNetwork::Testnet3 => 18332,
+            ChainNetwork::Testnet4 => 48332,
+            ChainNetwork::Signet => 38332,
+            ChainNetwork::Regtest => 18443,
+        }
+    }
+
+    /// Gets the default RPC URL of a local Bitcoin Core node.
+    pub fn default_rpc_url(&self) -> String {
+        format!("http://127.0.0.1:{}", self.default_rpc_port())
+    }
+
+    /// Gets the lowest fee rate used when estimators return nothing usable.
+    ///
+    /// Mainnet keeps a margin above the relay minimum so anchoring transactions still
+    /// propagate when mempools tighten; test networks use the relay minimum.
+    pub fn fee_floor(&self) -> FeeRate {
+        match self {
+            ChainNetwork::Mainnet => FeeRate::from_sat_per_vb_unchecked(2),
+            _ => FeeRate::BROADCAST_MIN,
+        }
+    }
+
+    /// Gets the genesis block header.
+    pub fn genesis_header(&self) -> Header {
+        match self {
+            ChainNetwork::Testnet4 => Header {
+                version: Version::ONE,
+                prev_blockhash: BlockHash::all_zeros(),
+                merkle_root: TxMerkleNode::from_str(TESTNET4_GENESIS_MERKLE_ROOT)
+                    .expect("valid merkle root"),
+                time: 1_714_777_860,
+                bits: CompactTarget::from_consensus(0x1d00ffff),
+                nonce: 393_743_547,
+            },
+            _ => genesis_block(self.network()).header,
+        }
+    }
+
+    pub fn genesis_hash(&self) -> BlockHash {
+        self.genesis_header().block_hash()
+    }
+
+    /// Gets known `(height, block hash)` checkpoints, including the genesis block.
+    pub fn checkpoints(&self) -> Vec<(u32, BlockHash)> {
+        let extra: &[(u32, &str)] = match self {
+            ChainNetwork::Mainnet => MAINNET_CHECKPOINTS,
+            _ => &[],
+        };
+        std::iter::once((0, self.genesis_hash()))
+            .chain(
+                extra
+                    .iter()
+                    .map(|(height, hash)| (*height, BlockHash::from_str(hash).expect("valid checkpoint hash"))),
+            )
+            .collect()
+    }
+
+    /// Creates a header chain anchored at the genesis block.
+    pub fn header_chain(&self) -> HeaderChain {
+        HeaderChain::new(self.network(), 0, self.genesis_header())
+    }
+}
+
+impl From<Network> for ChainNetwork {
+    fn from(network: Network) -> Self {
+        match network {
+            Network::Bitcoin => ChainNetwork::Mainnet,
+            Network::Testnet => ChainNetwork::Testnet3,
+            Network::Signet => ChainNetwork::Signet,
+            _ => ChainNetwork::Regtest,
+        }
+    }
+}
+
+impl fmt::Display for ChainNetwork {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ChainNetwork::Mainnet => "mainnet",
+            ChainNetwork::Testnet3 => "testnet3",
+            ChainNetwork::Testnet4 => "testnet4",
+            ChainNetwork::Signet => "signet",
+            ChainNetwork::Regtest => "regtest",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for ChainNetwork {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "mainnet" | "bitcoin" | "main" => Ok(ChainNetwork::Mainnet),
+            "testnet" | "testnet3" | "test" => Ok(ChainNetwork::Testnet3),
+            "testnet4" => Ok(ChainNetwork::Testnet4),
+            "signet" => Ok(ChainNetwork::Signet),
+            "regtest" => Ok(ChainNetwork::Regtest),
+            other => Err(format!("Unknown network: {}", other)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_genesis_hashes() {
+        assert_eq!(
+            ChainNetwork::Mainnet.genesis_hash().to_string(),
+            "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f"
+        );
+        assert_eq!(
+            ChainNetwork::Testnet4.genesis_hash().to_string(),
+            "00000000da84f2bafbbc53dee25a72ae507ff4914b867c565be350b0da8bf043"
+        );
+        assert_ne!(ChainNetwork::Testnet3.genesis_hash(), ChainNetwork::Testnet4.genesis_hash());
+        assert_eq!(ChainNetwork::Mainnet.checkpoints().len(), 2);
+        assert_eq!(ChainNetwork::Signet.header_chain().tip_hash(), ChainNetwork::Signet.genesis_hash());
+    }
+
+    #[test]
+    fn test_parse_and_map() {
+        for network in [
+            ChainNetwork::Mainnet,
+            ChainNetwork::Testnet3,
+            ChainNetwork::Testnet4,
+            ChainNetwork::Signet,
+            ChainNetwork::Regtest,
+        ] {
+            assert_eq!(network.to_string().parse::<ChainNetwork>(), Ok(network));
+        }
+        assert_eq!(ChainNetwork::Testnet4.network(), Network::Testnet);
+        assert_eq!(ChainNetwork::from(Network::Signet), ChainNetwork::Signet);
+        assert_eq!(ChainNetwork::Testnet4.default_rpc_url(), "http://127.0.0.1:48332");
+        assert!(ChainNetwork::Mainnet.fee_floor() > ChainNetwork::Regtest.fee_floor());
+        assert!("liquid".parse::<ChainNetwork>().is_err());
+    }
+}
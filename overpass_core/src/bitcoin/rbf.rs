@@ -0,0 +1,346 @@
+// src/bitcoin/rbf.rs
+
+use crate::bitcoin::fees::{fee_for_vsize, FeeEstimator};
+use crate::bitcoin::signer::{sign_transaction, Signer};
+use bitcoin::{FeeRate, Sequence, Transaction, TxOut, Txid};
+use std::collections::HashMap;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum RbfError {
+    #[error("Transaction not tracked: {0}")]
+    UnknownTransaction(Txid),
+    #[error("Transaction does not signal replaceability: {0}")]
+    NotReplaceable(Txid),
+    #[error("Invalid settlement: {0}")]
+    InvalidSettlement(String),
+    #[error("Fee estimation failed: {0}")]
+    FeeEstimation(String),
+    #[error("Fee bump exceeds policy: {0}")]
+    FeeLimitExceeded(String),
+    #[error("Re-signing failed: {0}")]
+    SigningError(String),
+}
+
+/// Policy controlling when and how far stuck settlements are bumped.
+#[derive(Clone, Debug)]
+pub struct RbfPolicy {
+    /// Seconds a settlement may sit unconfirmed before it becomes eligible for a bump.
+    pub bump_after_secs: u64,
+    /// Confirmation target passed to the fee estimator when bumping.
+    pub target_blocks: u16,
+    /// Minimum increase over the previous fee rate required by relay policy (BIP125 rule 4).
+    pub incremental_relay_fee: FeeRate,
+    /// Upper bound on the fee rate a bump may pay.
+    pub max_fee_rate: FeeRate,
+    /// Maximum number of replacements before the settlement is left alone.
+    pub max_bumps: u32,
+}
+
+impl Default for RbfPolicy {
+    fn default() -> Self {
+        Self {
+            bump_after_secs: 3_600,
+            target_blocks: 2,
+            incremental_relay_fee: FeeRate::BROADCAST_MIN,
+            max_fee_rate: FeeRate::from_sat_per_vb_unchecked(500),
+            max_bumps: 10,
+        }
+    }
+}
+
+/// An unconfirmed settlement transaction awaiting confirmation.
+#[derive(Clone, Debug)]
+pub struct PendingSettlement {
+    pub tx: Transaction,
+    /// Outputs spent by `tx`, in input order.
+    pub prevouts: Vec<TxOut>,
+    /// Index of the output the fee is taken from when bumping.
+    pub change_index: usize,
+    pub fee: u64,
+    pub broadcast_at: u64,
+    pub bumps: u32,
+    /// Txids of earlier versions replaced by this one.
+    pub replaced: Vec<Txid>,
+}
+
+impl PendingSettlement {
+    /// Gets the effective fee rate of the current version.
+    pub fn fee_rate(&self) -> FeeRate {
+        FeeRate::from_sat_per_kwu(self.fee * 1000 / self.tx.weight().to_wu().max(1))
+    }
+}
+
+/// Tracks unconfirmed settlements and rebuilds them with higher fees when they linger.
+pub struct SettlementTracker {
+    policy: RbfPolicy,
+    pending: HashMap<Txid, PendingSettlement>,
+}
+
+impl SettlementTracker {
+    pub fn new(policy: RbfPolicy) -> Self {
+        Self {
+            policy,
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Gets the active policy.
+    pub fn policy(&self) -> &RbfPolicy {
+        &self.policy
+    }
+
+    /// Starts tracking a broadcast settlement transaction.
+    pub fn track(
+        &mut self,
+        tx: Transaction,
+        prevouts: Vec<TxOut>,
+        change_index: usize,
+        broadcast_at: u64,
+    ) -> Result<Txid, RbfError> {
+        let txid = tx.txid();
+        if !tx.is_explicitly_rbf() {
+            return Err(RbfError::NotReplaceable(txid));
+        }
+        if prevouts.len() != tx.input.len() {
+            return Err(RbfError::InvalidSettlement(
+                "Prevout count does not match input count".to_string(),
+            ));
+        }
+        if change_index >= tx.output.len() {
+            return Err(RbfError::InvalidSettlement(format!(
+                "Change index {} out of range",
+                change_index
+            )));
+        }
+        let fee = fee_paid(&tx, &prevouts)?;
+
+        self.pending.insert(
+            txid,
+            PendingSettlement {
+                tx,
+                prevouts,
+                change_index,
+                fee,
+                broadcast_at,
+                bumps: 0,
+                replaced: Vec::new(),
+            },
+        );
+        Ok(txid)
+    }
+
+    /// Gets a tracked settlement by the txid of its current version.
+    pub fn get(&self, txid: &Txid) -> Option<&PendingSettlement> {
+        self.pending.get(txid)
+    }
+
+    /// Gets the number of settlements awaiting confirmation.
+    pub fn pending_count(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Stops tracking a settlement once any of its versions confirms.
+    pub fn mark_confirmed(&mut self, txid: &Txid) -> Option<PendingSettlement> {
+        let current = self
+            .pending
+            .iter()
+            .find(|(id, pending)| *id == txid || pending.replaced.contains(txid))
+            .map(|(id, _)| *id)?;
+        self.pending.remove(&current)
+    }
+
+    /// Lists settlements that have been unconfirmed past the policy deadline.
+    pub fn due_for_bump(&self, now: u64) -> Vec<Txid> {
+        self.pending
+            .iter()
+            .filter(|(_, pending)| {
+                pending.bumps < self.policy.max_bumps
+                    && now.saturating_sub(pending.broadcast_at) >= self.policy.bump_after_secs
+            })
+            .map(|(txid, _)| *txid)
+            .collect()
+    }
+
+    /// Rebuilds a settlement at a higher fee rate and re-signs it.
+    ///
+    /// The extra fee is taken from the change output, and every input is signed again
+    /// through `signer`.
+    pub fn bump(
+        &mut self,
+        txid: &Txid,
+        fee_estimator: &dyn FeeEstimator,
+        now: u64,
+        signer: &dyn Signer,
+    ) -> Result<Transaction, RbfError> {
+        let pending = self
+            .pending
+            .get(txid)
+            .ok_or(RbfError::UnknownTransaction(*txid))?;
+        if pending.bumps >= self.policy.max_bumps {
+            return Err(RbfError::FeeLimitExceeded(format!(
+                "Settlement already bumped {} times",
+                pending.bumps
+            )));
+        }
+
+        let vsize = pending.tx.vsize() as u64;
+        let estimated = fee_estimator
+            .estimate_fee_rate(self.policy.target_blocks)
+            .map_err(|e| RbfError::FeeEstimation(e.to_string()))?;
+        let minimum = FeeRate::from_sat_per_kwu(
+            pending.fee_rate().to_sat_per_kwu() + self.policy.incremental_relay_fee.to_sat_per_kwu(),
+        );
+        let new_rate = estimated.max(minimum);
+        if new_rate > self.policy.max_fee_rate {
+            return Err(RbfError::FeeLimitExceeded(format!(
+                "{} sat/vB exceeds the maximum of {} sat/vB",
+                new_rate.to_sat_per_vb_ceil(),
+                self.policy.max_fee_rate.to_sat_per_vb_ceil()
+            )));
+        }
+
+        // BIP125 rule 4: the replacement must also pay for its own relay bandwidth.
+        let incremental = fee_for_vsize(self.policy.incremental_relay_fee, vsize)
+            .map_err(|e| RbfError::FeeEstimation(e.to_string()))?;
+        let new_fee = fee_for_vsize(new_rate, vsize)
+            .map_err(|e| RbfError::FeeEstimation(e.to_string()))?
+            .max(pending.fee + incremental);
+        let extra = new_fee - pending.fee;
+
+        let mut tx = pending.tx.clone();
+        let change = &mut tx.output[pending.change_index];
+        let dust_limit = change.script_pubkey.dust_value().to_sat();
+        if change.value < extra + dust_limit {
+            return Err(RbfError::FeeLimitExceeded(
+                "Change output cannot cover the bumped fee".to_string(),
+            ));
+        }
+        change.value -= extra;
+        for input in &mut tx.input {
+            if !input.sequence.is_rbf() {
+                input.sequence = Sequence::ENABLE_RBF_NO_LOCKTIME;
+            }
+        }
+
+        let tx = sign_transaction(signer, tx, &pending.prevouts)
+            .map_err(|e| RbfError::SigningError(e.to_string()))?;
+
+        let mut replacement = self.pending.remove(txid).expect("checked above");
+        replacement.replaced.push(*txid);
+        replacement.tx = tx.clone();
+        replacement.fee = new_fee;
+        replacement.broadcast_at = now;
+        replacement.bumps += 1;
+        self.pending.insert(tx.txid(), replacement);
+
+        Ok(tx)
+    }
+}
+
+/// Computes the absolute fee paid by a transaction given the outputs it spends.
+pub fn fee_paid(tx: &Transaction, prevouts: &[TxOut]) -> Result<u64, RbfError> {
+    let input_value: u64 = prevouts.iter().map(|txout| txout.value).sum();
+    let output_value: u64 = tx.output.iter().map(|txout| txout.value).sum();
+    input_value
+        .checked_sub(output_value)
+        .ok_or_else(|| RbfError::InvalidSettlement("Outputs exceed inputs".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::fees::StaticFeeEstimator;
+    use crate::bitcoin::signer::SignerError;
+    use bitcoin::psbt::PartiallySignedTransaction as Psbt;
+    use bitcoin::absolute::LockTime;
+    use bitcoin::hashes::Hash;
+    use bitcoin::{OutPoint, ScriptBuf, TxIn, WPubkeyHash, Witness};
+
+    fn settlement(sequence: Sequence) -> (Transaction, Vec<TxOut>) {
+        let script_pubkey = ScriptBuf::new_v0_p2wpkh(&WPubkeyHash::all_zeros());
+        let tx = Transaction {
+            version: 2,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(Txid::all_zeros(), 0),
+                script_sig: ScriptBuf::new(),
+                sequence,
+                witness: Witness::from_slice(&[vec![0u8; 72], vec![0u8; 33]]),
+            }],
+            output: vec![
+                TxOut { value: 60_000, script_pubkey: script_pubkey.clone() },
+                TxOut { value: 39_000, script_pubkey: script_pubkey.clone() },
+            ],
+        };
+        (tx, vec![TxOut { value: 100_000, script_pubkey }])
+    }
+
+    struct FakeSigner;
+
+    impl Signer for FakeSigner {
+        fn sign_psbt(&self, psbt: &mut Psbt) -> Result<(), SignerError> {
+            for input in &mut psbt.inputs {
+                input.final_script_witness = Some(Witness::from_slice(&[vec![1u8; 72], vec![1u8; 33]]));
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_bump_after_deadline() {
+        let mut tracker = SettlementTracker::new(RbfPolicy::default());
+        let (tx, prevouts) = settlement(Sequence::ENABLE_RBF_NO_LOCKTIME);
+        let txid = tracker.track(tx, prevouts, 1, 1_000).unwrap();
+
+        assert!(tracker.due_for_bump(1_000 + 60).is_empty());
+        assert_eq!(tracker.due_for_bump(1_000 + 3_600), vec![txid]);
+
+        let estimator = StaticFeeEstimator::new(FeeRate::from_sat_per_vb_unchecked(50));
+        let bumped = tracker.bump(&txid, &estimator, 5_000, &FakeSigner).unwrap();
+        let pending = tracker.get(&bumped.txid()).unwrap();
+
+        assert_eq!(bumped.output[0].value, 60_000);
+        assert!(bumped.output[1].value < 39_000);
+        assert!(pending.fee > 1_000);
+        assert!(pending.fee_rate() >= FeeRate::from_sat_per_vb_unchecked(50));
+        assert!(tracker.get(&txid).is_none());
+        assert!(tracker.due_for_bump(5_000).is_empty());
+
+        // Confirmation of the original version still clears the replacement.
+        assert!(tracker.mark_confirmed(&txid).is_some());
+        assert_eq!(tracker.pending_count(), 0);
+    }
+
+    #[test]
+    fn test_bump_respects_minimum_increment() {
+        let mut tracker = SettlementTracker::new(RbfPolicy::default());
+        let (tx, prevouts) = settlement(Sequence::ENABLE_RBF_NO_LOCKTIME);
+        let txid = tracker.track(tx, prevouts, 1, 0).unwrap();
+        let old_fee = tracker.get(&txid).unwrap().fee;
+
+        // An estimate below the current rate still has to outbid the original.
+        let estimator = StaticFeeEstimator::new(FeeRate::BROADCAST_MIN);
+        let bumped = tracker.bump(&txid, &estimator, 0, &FakeSigner).unwrap();
+        let new_fee = tracker.get(&bumped.txid()).unwrap().fee;
+        assert!(new_fee >= old_fee + bumped.vsize() as u64);
+    }
+
+    #[test]
+    fn test_rejects_non_replaceable_and_over_limit() {
+        let mut tracker = SettlementTracker::new(RbfPolicy::default());
+        let (tx, prevouts) = settlement(Sequence::MAX);
+        assert!(matches!(
+            tracker.track(tx, prevouts, 1, 0),
+            Err(RbfError::NotReplaceable(_))
+        ));
+
+        let (tx, prevouts) = settlement(Sequence::ENABLE_RBF_NO_LOCKTIME);
+        let txid = tracker.track(tx, prevouts, 1, 0).unwrap();
+        let estimator = StaticFeeEstimator::new(FeeRate::from_sat_per_vb_unchecked(1_000));
+        assert!(matches!(
+            tracker.bump(&txid, &estimator, 0, &FakeSigner),
+            Err(RbfError::FeeLimitExceeded(_))
+        ));
+    }
+}
@@ -0,0 +1,380 @@
+// src/bitcoin/root_anchor.rs
+
+use crate::bitcoin::fees::FeeEstimator;
+use crate::bitcoin::spv::HeaderChain;
+use crate::bitcoin::triggers::{ChainTime, Interval};
+use crate::bitcoin::utxo::{select_coins, SelectionParams, Utxo};
+use crate::zkp::global_root_contract::GlobalRootContract;
+use crate::zkp::helpers::Bytes32;
+use bitcoin::blockdata::opcodes::all::OP_RETURN;
+use bitcoin::blockdata::script::{Builder, Instruction, PushBytesBuf};
+use bitcoin::merkle_tree::MerkleBlock;
+use bitcoin::{Script, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid, Witness};
+use thiserror::Error;
+
+/// Magic prefix identifying Overpass root anchors.
+pub const ANCHOR_MAGIC: &[u8; 3] = b"OVP";
+const ANCHOR_VERSION: u8 = 1;
+/// Magic, version, 8-byte epoch and 32-byte root.
+const ANCHOR_PAYLOAD_LEN: usize = 3 + 1 + 8 + 32;
+
+/// Virtual size of an anchor transaction without inputs: header, OP_RETURN and P2WPKH change.
+const ANCHOR_BASE_VSIZE: u64 = 11 + 9 + 1 + 1 + ANCHOR_PAYLOAD_LEN as u64 + 31;
+
+#[derive(Error, Debug)]
+pub enum RootAnchorError {
+    #[error("Fee estimation failed: {0}")]
+    FeeEstimation(String),
+    #[error("Coin selection failed: {0}")]
+    CoinSelection(String),
+    #[error("Anchor verification failed: {0}")]
+    VerificationFailed(String),
+}
+
+/// A global root committed to in an OP_RETURN output.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AnchorPayload {
+    /// Monotonic anchor counter, so verifiers can detect gaps.
+    pub epoch: u64,
+    pub root: Bytes32,
+}
+
+impl AnchorPayload {
+    /// Serializes to `magic || version || epoch (BE) || root`.
+    pub fn to_bytes(&self) -> [u8; ANCHOR_PAYLOAD_LEN] {
+        let mut bytes = [0u8; ANCHOR_PAYLOAD_LEN];
+        bytes[..3].copy_from_slice(ANCHOR_MAGIC);
+        bytes[3] = ANCHOR_VERSION;
+        bytes[4..12].copy_from_slice(&self.epoch.to_be_bytes());
+        bytes[12..].copy_from_slice(&self.root);
+        bytes
+    }
+
+    /// Parses a payload, rejecting foreign OP_RETURN data.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != ANCHOR_PAYLOAD_LEN
+            || &bytes[..3] != ANCHOR_MAGIC
+            || bytes[3] != ANCHOR_VERSION
+        {
+            return None;
+        }
+        let mut epoch = [0u8; 8];
+        epoch.copy_from_slice(&bytes[4..12]);
+        let mut root = [0u8; 32];
+        root.copy_from_slice(&bytes[12..]);
+        Some(Self {
+            epoch: u64::from_be_bytes(epoch),
+            root,
+        })
+    }
+
+    /// Builds the OP_RETURN script carrying this payload.
+    pub fn script_pubkey(&self) -> ScriptBuf {
+        let data = PushBytesBuf::try_from(self.to_bytes().to_vec()).expect("payload under push limit");
+        Builder::new()
+            .push_opcode(OP_RETURN)
+            .push_slice(data)
+            .into_script()
+    }
+
+    /// Extracts a payload from an OP_RETURN script.
+    pub fn from_script(script: &Script) -> Option<Self> {
+        let mut instructions = script.instructions();
+        match instructions.next()? {
+            Ok(Instruction::Op(op)) if op == OP_RETURN => {}
+            _ => return None,
+        }
+        match instructions.next()? {
+            Ok(Instruction::PushBytes(data)) => Self::from_bytes(data.as_bytes()),
+            _ => None,
+        }
+    }
+
+    /// Finds the first anchor payload in a transaction's outputs.
+    pub fn from_tx(tx: &Transaction) -> Option<Self> {
+        tx.output
+            .iter()
+            .find_map(|output| Self::from_script(&output.script_pubkey))
+    }
+}
+
+/// A confirmed or broadcast anchor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AnchorRecord {
+    pub payload: AnchorPayload,
+    pub txid: Txid,
+    pub anchored_at: ChainTime,
+}
+
+/// Periodically commits the global root to Bitcoin.
+pub struct RootAnchorer {
+    interval: Interval,
+    target_blocks: u16,
+    last: Option<AnchorRecord>,
+}
+
+impl RootAnchorer {
+    pub fn new(interval_secs: u64, target_blocks: u16) -> Self {
+        Self {
+            interval: Interval::secs(interval_secs),
+            target_blocks,
+            last: None,
+        }
+    }
+
+    /// Replaces the anchoring interval, e.g. to anchor every N blocks.
+    pub fn with_interval(mut self, interval: Interval) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Gets the most recent anchor.
+    pub fn last_anchor(&self) -> Option<&AnchorRecord> {
+        self.last.as_ref()
+    }
+
+    /// Checks whether `root` should be anchored now: it changed and the interval elapsed.
+    pub fn is_due(&self, root: &Bytes32, now: ChainTime) -> bool {
+        match &self.last {
+            None => true,
+            Some(last) => last.payload.root != *root && self.interval.has_elapsed(last.anchored_at, now),
+        }
+    }
+
+    /// Builds an unsigned anchor transaction for the contract's current global root, if due.
+    pub fn maybe_build(
+        &self,
+        contract: &GlobalRootContract,
+        utxos: &[Utxo],
+        change_script: ScriptBuf,
+        fee_estimator: &dyn FeeEstimator,
+        now: ChainTime,
+    ) -> Result<Option<(Transaction, AnchorPayload)>, RootAnchorError> {
+        let root = contract.get_global_merkle_root();
+        if !self.is_due(&root, now) {
+            return Ok(None);
+        }
+        let payload = self.next_payload(root);
+        let tx = self.build_anchor_tx(&payload, utxos, change_script, fee_estimator)?;
+        Ok(Some((tx, payload)))
+    }
+
+    /// Gets the payload the next anchor of `root` carries.
+    pub fn next_payload(&self, root: Bytes32) -> AnchorPayload {
+        AnchorPayload {
+            epoch: self.last.map_or(0, |last| last.payload.epoch + 1),
+            root,
+        }
+    }
+
+    /// Builds an unsigned transaction with the anchor OP_RETURN and a change output.
+    pub fn build_anchor_tx(
+        &self,
+        payload: &AnchorPayload,
+        utxos: &[Utxo],
+        change_script: ScriptBuf,
+        fee_estimator: &dyn FeeEstimator,
+    ) -> Result<Transaction, RootAnchorError> {
+        let fee_rate = fee_estimator
+            .estimate_fee_rate(self.target_blocks)
+            .map_err(|e| RootAnchorError::FeeEstimation(e.to_string()))?;
+        let dust_limit = change_script.dust_value().to_sat();
+        // The change output is the only value-carrying output, so select for a dust-sized
+        // target and fold it back into change.
+        let selection = select_coins(
+            utxos,
+            &SelectionParams {
+                target: dust_limit,
+                fee_rate,
+                base_vsize: ANCHOR_BASE_VSIZE,
+                change_vsize: 0,
+                change_spend_vsize: 68,
+                dust_limit,
+            },
+        )
+        .map_err(|e| RootAnchorError::CoinSelection(e.to_string()))?;
+
+        Ok(Transaction {
+            version: 2,
+            lock_time: bitcoin::absolute::LockTime::ZERO,
+            input: selection
+                .selected
+                .iter()
+                .map(|utxo| TxIn {
+                    previous_output: utxo.outpoint,
+                    script_sig: ScriptBuf::new(),
+                    sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                    witness: Witness::new(),
+                })
+                .collect(),
+            output: vec![
+                TxOut {
+                    value: 0,
+                    script_pubkey: payload.script_pubkey(),
+                },
+                TxOut {
+                    value: selection.input_value() - selection.fee,
+                    script_pubkey: change_script,
+                },
+            ],
+        })
+    }
+
+    /// Records a broadcast anchor so the next one waits a full interval.
+    pub fn record(&mut self, payload: AnchorPayload, txid: Txid, now: ChainTime) {
+        self.last = Some(AnchorRecord {
+            payload,
+            txid,
+            anchored_at: now,
+        });
+    }
+}
+
+/// Verifies that `root` was anchored by `anchor_tx` in the block at `height`.
+///
+/// The block must be on the SPV chain and the merkle block must prove that the
+/// transaction was included in it.
+pub fn verify_root_anchored(
+    chain: &HeaderChain,
+    merkle_block: &MerkleBlock,
+    anchor_tx: &Transaction,
+    root: &Bytes32,
+    height: u32,
+) -> Result<AnchorPayload, RootAnchorError> {
+    let payload = AnchorPayload::from_tx(anchor_tx)
+        .ok_or_else(|| RootAnchorError::VerificationFailed("No anchor output".to_string()))?;
+    if payload.root != *root {
+        return Err(RootAnchorError::VerificationFailed("Anchored root differs".to_string()));
+    }
+    let block_hash = merkle_block.header.block_hash();
+    if chain.height_of(&block_hash) != Some(height) {
+        return Err(RootAnchorError::VerificationFailed(format!(
+            "Block {} is not on the best chain at height {}",
+            block_hash, height
+        )));
+    }
+    chain
+        .verify_inclusion(merkle_block, &anchor_tx.txid())
+        .map_err(|e| RootAnchorError::VerificationFailed(e.to_string()))?;
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::fees::StaticFeeEstimator;
+    use crate::zkp::pedersen_parameters::PedersenParameters;
+    use bitcoin::block::{Header, Version};
+    use bitcoin::hash_types::TxMerkleNode;
+    use bitcoin::hashes::Hash;
+    use bitcoin::{BlockHash, CompactTarget, FeeRate, Network, OutPoint, WPubkeyHash};
+
+    fn change_script() -> ScriptBuf {
+        ScriptBuf::new_v0_p2wpkh(&WPubkeyHash::all_zeros())
+    }
+
+    fn utxo(value: u64) -> Utxo {
+        Utxo {
+            outpoint: OutPoint::new(Txid::all_zeros(), 0),
+            txout: TxOut {
+                value,
+                script_pubkey: change_script(),
+            },
+            confirmations: 6,
+            input_vsize: 68,
+        }
+    }
+
+    #[test]
+    fn test_payload_roundtrip() {
+        let payload = AnchorPayload {
+            epoch: 7,
+            root: [3u8; 32],
+        };
+        let script = payload.script_pubkey();
+        assert!(script.is_op_return());
+        assert_eq!(AnchorPayload::from_script(&script), Some(payload));
+
+        let foreign = Builder::new()
+            .push_opcode(OP_RETURN)
+            .push_slice([0u8; 44])
+            .into_script();
+        assert_eq!(AnchorPayload::from_script(&foreign), None);
+    }
+
+    #[test]
+    fn test_anchoring_schedule() {
+        let contract = GlobalRootContract::new(PedersenParameters::default());
+        let estimator = StaticFeeEstimator::new(FeeRate::from_sat_per_vb_unchecked(2));
+        let mut anchorer = RootAnchorer::new(3_600, 6).with_interval(Interval::secs(3_600).or_blocks(6));
+        let start = ChainTime::new(0, 800_000);
+
+        let (tx, payload) = anchorer
+            .maybe_build(&contract, &[utxo(10_000)], change_script(), &estimator, start)
+            .unwrap()
+            .unwrap();
+        assert_eq!(payload.epoch, 0);
+        assert_eq!(payload.root, contract.get_global_merkle_root());
+        assert_eq!(AnchorPayload::from_tx(&tx), Some(payload));
+        assert!(tx.output[1].value < 10_000);
+        anchorer.record(payload, tx.txid(), start);
+
+        // Nothing new to anchor while the root is unchanged.
+        assert!(anchorer
+            .maybe_build(&contract, &[utxo(10_000)], change_script(), &estimator, ChainTime::new(10_000, 800_010))
+            .unwrap()
+            .is_none());
+        assert!(!anchorer.is_due(&[1u8; 32], ChainTime::new(60, 800_005)));
+        assert!(anchorer.is_due(&[1u8; 32], ChainTime::new(3_600, 800_000)));
+        assert!(anchorer.is_due(&[1u8; 32], ChainTime::new(60, 800_006)));
+    }
+
+    #[test]
+    fn test_verify_root_anchored() {
+        let root = [5u8; 32];
+        let anchor_tx = Transaction {
+            version: 2,
+            lock_time: bitcoin::absolute::LockTime::ZERO,
+            input: vec![],
+            output: vec![TxOut {
+                value: 0,
+                script_pubkey: AnchorPayload { epoch: 0, root }.script_pubkey(),
+            }],
+        };
+        let txids = vec![Txid::from_byte_array([1u8; 32]), anchor_tx.txid()];
+        let merkle_root = bitcoin::merkle_tree::calculate_root(txids.iter().map(|t| t.to_raw_hash()))
+            .map(TxMerkleNode::from_raw_hash)
+            .unwrap();
+
+        let genesis = Header {
+            version: Version::ONE,
+            prev_blockhash: BlockHash::all_zeros(),
+            merkle_root: TxMerkleNode::all_zeros(),
+            time: 1_296_688_602,
+            bits: CompactTarget::from_consensus(0x207fffff),
+            nonce: 2,
+        };
+        let mut block = Header {
+            version: Version::TWO,
+            prev_blockhash: genesis.block_hash(),
+            merkle_root,
+            time: genesis.time + 600,
+            bits: genesis.bits,
+            nonce: 0,
+        };
+        while block.validate_pow(block.target()).is_err() {
+            block.nonce += 1;
+        }
+        let mut chain = HeaderChain::new(Network::Regtest, 0, genesis);
+        chain.connect_headers(&[block]).unwrap();
+
+        let anchor_txid = anchor_tx.txid();
+        let proof = MerkleBlock::from_header_txids_with_predicate(&block, &txids, |t| *t == anchor_txid);
+        assert_eq!(
+            verify_root_anchored(&chain, &proof, &anchor_tx, &root, 1).unwrap().root,
+            root
+        );
+        assert!(verify_root_anchored(&chain, &proof, &anchor_tx, &root, 2).is_err());
+        assert!(verify_root_anchored(&chain, &proof, &anchor_tx, &[6u8; 32], 1).is_err());
+    }
+}
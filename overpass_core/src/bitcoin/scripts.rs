@@ -14,8 +14,10 @@ use crate::bitcoin::bitcoin_types::{
     BitcoinLockState, HTLCParameters, StealthAddress, OpReturnMetadata,
 };
 
-use std::{collections::HashMap, sync::Arc};
-use tokio::sync::RwLock;
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
 
 
 /// Represents a Bitcoin client managing state and operations.
@@ -140,15 +142,21 @@ pub fn create_op_return_script(
         state_hash: [u8; 32],
         state_data: Vec<u8>,
     ) -> Result<(), String> {
-        let mut cache = self.state_cache.write().await;
+        let mut cache = self
+            .state_cache
+            .write()
+            .map_err(|_| "State cache lock poisoned".to_string())?;
         cache.insert(state_hash, state_data);
         Ok(())
     }
 
     /// Retrieves a state from the cache.
-    pub async fn get_cached_state(&self, state_hash: [u8; 32]) -> Option<Vec<u8>> {
-        let cache = self.state_cache.read().await;
-        cache.get(&state_hash).cloned()
+    pub async fn get_cached_state(&self, state_hash: [u8; 32]) -> Result<Option<Vec<u8>>, String> {
+        let cache = self
+            .state_cache
+            .read()
+            .map_err(|_| "State cache lock poisoned".to_string())?;
+        Ok(cache.get(&state_hash).cloned())
     }
 }
 #[cfg(test)]
@@ -227,7 +235,7 @@ mod tests {
             .cache_state(test_hash, test_data.clone())
             .await
             .unwrap();
-        let cached = client.get_cached_state(test_hash).await;
+        let cached = client.get_cached_state(test_hash).await.unwrap();
 
         assert_eq!(cached, Some(test_data));
     }
@@ -0,0 +1,447 @@
+// src/bitcoin/signer.rs
+
+use crate::bitcoin::networks::ChainNetwork;
+#[cfg(feature = "tokio")]
+use crate::utils::blocking::run_blocking;
+#[cfg(feature = "tokio")]
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use bitcoin::bip32::Fingerprint;
+use bitcoin::hashes::Hash;
+use bitcoin::key::TapTweak;
+use bitcoin::psbt::PartiallySignedTransaction as Psbt;
+use bitcoin::secp256k1::{All, KeyPair, Message, Secp256k1};
+use bitcoin::sighash::{EcdsaSighashType, Prevouts, SighashCache, TapSighashType};
+use bitcoin::taproot::TapLeafHash;
+use bitcoin::{PublicKey, ScriptBuf, Transaction, TxOut, Witness};
+use serde::Deserialize;
+use std::process::{Command, Output};
+#[cfg(feature = "tokio")]
+use std::sync::Arc;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum SignerError {
+    #[error("Input {0} has no witness UTXO")]
+    MissingUtxo(usize),
+    #[error("Sighash computation failed: {0}")]
+    Sighash(String),
+    #[error("Invalid PSBT: {0}")]
+    Psbt(String),
+    #[error("Signing device error: {0}")]
+    Device(String),
+    #[error("Input {0} is missing signatures")]
+    Incomplete(usize),
+}
+
+/// Holds the keys for on-chain spends and signs PSBTs handed to it.
+///
+/// Implementations add signatures (`partial_sigs`, `tap_key_sig`, `tap_script_sigs`) for
+/// the inputs they can sign and leave the rest untouched; witnesses are assembled by
+/// [`finalize_psbt`], so keys can live on a hardware wallet or in an enclave.
+pub trait Signer: Send + Sync {
+    fn sign_psbt(&self, psbt: &mut Psbt) -> Result<(), SignerError>;
+}
+
+/// Asynchronous flavour of [`Signer`] for devices that need user confirmation.
+///
+/// Implementations must not block the runtime while a device waits for the user.
+#[cfg(feature = "tokio")]
+#[async_trait]
+pub trait AsyncSigner: Send + Sync {
+    async fn sign_psbt_async(&self, psbt: Psbt) -> Result<Psbt, SignerError>;
+}
+
+/// Runs any synchronous signer on the blocking pool.
+#[cfg(feature = "tokio")]
+#[async_trait]
+impl<T: Signer + 'static> AsyncSigner for Arc<T> {
+    async fn sign_psbt_async(&self, mut psbt: Psbt) -> Result<Psbt, SignerError> {
+        let signer = self.clone();
+        run_blocking(move || {
+            signer.sign_psbt(&mut psbt)?;
+            Ok(psbt)
+        })
+        .await
+    }
+}
+
+fn witness_utxos(psbt: &Psbt) -> Result<Vec<TxOut>, SignerError> {
+    psbt.inputs
+        .iter()
+        .enumerate()
+        .map(|(index, input)| input.witness_utxo.clone().ok_or(SignerError::MissingUtxo(index)))
+        .collect()
+}
+
+/// Signs with in-memory keys, for software wallets and tests.
+pub struct KeySigner {
+    secp: Secp256k1<All>,
+    keys: Vec<KeyPair>,
+}
+
+impl KeySigner {
+    pub fn new(keys: Vec<KeyPair>) -> Self {
+        Self {
+            secp: Secp256k1::new(),
+            keys,
+        }
+    }
+}
+
+impl Signer for KeySigner {
+    fn sign_psbt(&self, psbt: &mut Psbt) -> Result<(), SignerError> {
+        let prevouts = witness_utxos(psbt)?;
+        let mut cache = SighashCache::new(&psbt.unsigned_tx);
+
+        for (index, input) in psbt.inputs.iter_mut().enumerate() {
+            let prevout = &prevouts[index];
+            for key_pair in &self.keys {
+                let public_key = PublicKey::new(key_pair.public_key());
+                let (x_only, _) = key_pair.x_only_public_key();
+
+                if prevout.script_pubkey.is_v0_p2wpkh()
+                    && public_key.wpubkey_hash().map(|hash| ScriptBuf::new_v0_p2wpkh(&hash))
+                        == Some(prevout.script_pubkey.clone())
+                {
+                    let script_code = prevout
+                        .script_pubkey
+                        .p2wpkh_script_code()
+                        .ok_or_else(|| SignerError::Sighash("Invalid P2WPKH script".to_string()))?;
+                    let sighash = cache
+                        .segwit_signature_hash(index, &script_code, prevout.value, EcdsaSighashType::All)
+                        .map_err(|e| SignerError::Sighash(e.to_string()))?;
+                    let message = Message::from_slice(sighash.as_byte_array())
+                        .map_err(|e| SignerError::Sighash(e.to_string()))?;
+                    let sig = self.secp.sign_ecdsa(&message, &key_pair.secret_key());
+                    input.partial_sigs.insert(
+                        public_key,
+                        bitcoin::ecdsa::Signature {
+                            sig,
+                            hash_ty: EcdsaSighashType::All,
+                        },
+                    );
+                    continue;
+                }
+
+                if input.tap_internal_key == Some(x_only) {
+                    let sighash = cache
+                        .taproot_key_spend_signature_hash(index, &Prevouts::All(&prevouts), TapSighashType::Default)
+                        .map_err(|e| SignerError::Sighash(e.to_string()))?;
+                    let message = Message::from_slice(sighash.as_byte_array())
+                        .map_err(|e| SignerError::Sighash(e.to_string()))?;
+                    let tweaked = key_pair.tap_tweak(&self.secp, input.tap_merkle_root).to_inner();
+                    input.tap_key_sig = Some(bitcoin::taproot::Signature {
+                        sig: self.secp.sign_schnorr(&message, &tweaked),
+                        hash_ty: TapSighashType::Default,
+                    });
+                }
+
+                let leaves: Vec<TapLeafHash> = input
+                    .tap_key_origins
+                    .get(&x_only)
+                    .map(|(leaves, _)| leaves.clone())
+                    .unwrap_or_default();
+                for leaf_hash in leaves {
+                    let sighash = cache
+                        .taproot_script_spend_signature_hash(
+                            index,
+                            &Prevouts::All(&prevouts),
+                            leaf_hash,
+                            TapSighashType::Default,
+                        )
+                        .map_err(|e| SignerError::Sighash(e.to_string()))?;
+                    let message = Message::from_slice(sighash.as_byte_array())
+                        .map_err(|e| SignerError::Sighash(e.to_string()))?;
+                    input.tap_script_sigs.insert(
+                        (x_only, leaf_hash),
+                        bitcoin::taproot::Signature {
+                            sig: self.secp.sign_schnorr(&message, key_pair),
+                            hash_ty: TapSighashType::Default,
+                        },
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// In-memory keys sign without I/O, so this runs inline.
+#[cfg(feature = "tokio")]
+#[async_trait]
+impl AsyncSigner for KeySigner {
+    async fn sign_psbt_async(&self, mut psbt: Psbt) -> Result<Psbt, SignerError> {
+        self.sign_psbt(&mut psbt)?;
+        Ok(psbt)
+    }
+}
+
+#[derive(Deserialize)]
+struct HwiResponse {
+    psbt: Option<String>,
+    error: Option<String>,
+}
+
+/// Signs through a hardware wallet using the `hwi` command-line tool.
+pub struct HwiSigner {
+    binary: String,
+    fingerprint: Fingerprint,
+    chain: ChainNetwork,
+}
+
+impl HwiSigner {
+    /// Creates a signer for the device with the given master fingerprint.
+    pub fn new(fingerprint: Fingerprint, chain: ChainNetwork) -> Self {
+        Self {
+            binary: "hwi".to_string(),
+            fingerprint,
+            chain,
+        }
+    }
+
+    /// Uses an `hwi` binary at a non-default path.
+    pub fn with_binary(mut self, binary: &str) -> Self {
+        self.binary = binary.to_string();
+        self
+    }
+
+    fn chain_arg(&self) -> &'static str {
+        match self.chain {
+            ChainNetwork::Mainnet => "main",
+            ChainNetwork::Testnet3 | ChainNetwork::Testnet4 => "test",
+            ChainNetwork::Signet => "signet",
+            ChainNetwork::Regtest => "regtest",
+        }
+    }
+}
+
+impl HwiSigner {
+    fn args(&self, psbt: &Psbt) -> Vec<String> {
+        vec![
+            "--fingerprint".to_string(),
+            self.fingerprint.to_string(),
+            "--chain".to_string(),
+            self.chain_arg().to_string(),
+            "signtx".to_string(),
+            BASE64.encode(psbt.serialize()),
+        ]
+    }
+
+    fn combine_output(psbt: &mut Psbt, output: Output) -> Result<(), SignerError> {
+        let response: HwiResponse =
+            serde_json::from_slice(&output.stdout).map_err(|e| SignerError::Device(e.to_string()))?;
+        if let Some(error) = response.error {
+            return Err(SignerError::Device(error));
+        }
+        let encoded = response
+            .psbt
+            .ok_or_else(|| SignerError::Device("Device returned no PSBT".to_string()))?;
+        let bytes = BASE64
+            .decode(encoded)
+            .map_err(|e| SignerError::Psbt(e.to_string()))?;
+        let signed = Psbt::deserialize(&bytes).map_err(|e| SignerError::Psbt(e.to_string()))?;
+        psbt.combine(signed).map_err(|e| SignerError::Psbt(e.to_string()))
+    }
+}
+
+impl Signer for HwiSigner {
+    fn sign_psbt(&self, psbt: &mut Psbt) -> Result<(), SignerError> {
+        let output = Command::new(&self.binary)
+            .args(self.args(psbt))
+            .output()
+            .map_err(|e| SignerError::Device(format!("Failed to run {}: {}", self.binary, e)))?;
+        Self::combine_output(psbt, output)
+    }
+}
+
+/// Waits on the `hwi` process without blocking the runtime while the user confirms.
+#[cfg(feature = "tokio")]
+#[async_trait]
+impl AsyncSigner for HwiSigner {
+    async fn sign_psbt_async(&self, mut psbt: Psbt) -> Result<Psbt, SignerError> {
+        let output = tokio::process::Command::new(&self.binary)
+            .args(self.args(&psbt))
+            .output()
+            .await
+            .map_err(|e| SignerError::Device(format!("Failed to run {}: {}", self.binary, e)))?;
+        Self::combine_output(&mut psbt, output)?;
+        Ok(psbt)
+    }
+}
+
+/// Assembles final witnesses from the signatures collected in `psbt`.
+///
+/// Supports P2WPKH, Taproot key-path and single-signature Taproot leaves, which covers
+/// every output type this crate creates. Inputs that already carry a final witness are
+/// left alone.
+pub fn finalize_psbt(psbt: &mut Psbt) -> Result<(), SignerError> {
+    for (index, input) in psbt.inputs.iter_mut().enumerate() {
+        if input.final_script_witness.is_some() {
+            continue;
+        }
+
+        let witness = if let Some(signature) = input.tap_key_sig {
+            Witness::from_slice(&[signature.to_vec()])
+        } else if let Some((control_block, (script, _), signature)) =
+            input.tap_scripts.iter().find_map(|(control_block, (script, version))| {
+                let leaf_hash = TapLeafHash::from_script(script, *version);
+                input
+                    .tap_script_sigs
+                    .iter()
+                    .find(|((_, hash), _)| *hash == leaf_hash)
+                    .map(|(_, signature)| (control_block, (script, version), signature))
+            })
+        {
+            Witness::from_slice(&[signature.to_vec(), script.to_bytes(), control_block.serialize()])
+        } else if let Some((public_key, signature)) = input.partial_sigs.iter().next() {
+            Witness::from_slice(&[signature.to_vec(), public_key.to_bytes()])
+        } else {
+            return Err(SignerError::Incomplete(index));
+        };
+
+        input.final_script_witness = Some(witness);
+        input.partial_sigs.clear();
+        input.tap_key_sig = None;
+        input.tap_script_sigs.clear();
+    }
+    Ok(())
+}
+
+/// Signs a transaction spending `prevouts` through `signer` and returns it with witnesses.
+pub fn sign_transaction(
+    signer: &dyn Signer,
+    mut tx: Transaction,
+    prevouts: &[TxOut],
+) -> Result<Transaction, SignerError> {
+    for input in &mut tx.input {
+        input.witness.clear();
+        input.script_sig = ScriptBuf::new();
+    }
+    let mut psbt = Psbt::from_unsigned_tx(tx).map_err(|e| SignerError::Psbt(e.to_string()))?;
+    for (input, prevout) in psbt.inputs.iter_mut().zip(prevouts) {
+        input.witness_utxo = Some(prevout.clone());
+    }
+    signer.sign_psbt(&mut psbt)?;
+    finalize_psbt(&mut psbt)?;
+    Ok(psbt.extract_tx())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::absolute::LockTime;
+    use bitcoin::secp256k1::SecretKey;
+    use bitcoin::{OutPoint, Sequence, TxIn, Txid};
+
+    fn key_pair(byte: u8) -> KeyPair {
+        KeyPair::from_secret_key(&Secp256k1::new(), &SecretKey::from_slice(&[byte; 32]).unwrap())
+    }
+
+    fn spend(prevouts: usize) -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: LockTime::ZERO,
+            input: (0..prevouts)
+                .map(|vout| TxIn {
+                    previous_output: OutPoint::new(Txid::all_zeros(), vout as u32),
+                    script_sig: ScriptBuf::new(),
+                    sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                    witness: Witness::new(),
+                })
+                .collect(),
+            output: vec![TxOut {
+                value: 90_000,
+                script_pubkey: ScriptBuf::new(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_signs_p2wpkh_and_key_path() {
+        let secp = Secp256k1::new();
+        let wpkh_key = key_pair(1);
+        let tr_key = key_pair(2);
+        let prevouts = vec![
+            TxOut {
+                value: 50_000,
+                script_pubkey: ScriptBuf::new_v0_p2wpkh(
+                    &PublicKey::new(wpkh_key.public_key()).wpubkey_hash().unwrap(),
+                ),
+            },
+            TxOut {
+                value: 50_000,
+                script_pubkey: ScriptBuf::new_v1_p2tr(&secp, tr_key.x_only_public_key().0, None),
+            },
+        ];
+
+        let mut psbt = Psbt::from_unsigned_tx(spend(2)).unwrap();
+        for (input, prevout) in psbt.inputs.iter_mut().zip(&prevouts) {
+            input.witness_utxo = Some(prevout.clone());
+        }
+        psbt.inputs[1].tap_internal_key = Some(tr_key.x_only_public_key().0);
+
+        let signer = KeySigner::new(vec![wpkh_key, tr_key]);
+        signer.sign_psbt(&mut psbt).unwrap();
+        assert_eq!(psbt.inputs[0].partial_sigs.len(), 1);
+        assert!(psbt.inputs[1].tap_key_sig.is_some());
+
+        finalize_psbt(&mut psbt).unwrap();
+        let tx = psbt.extract_tx();
+        assert_eq!(tx.input[0].witness.len(), 2);
+        assert_eq!(tx.input[1].witness.len(), 1);
+
+        let tweaked = tr_key.tap_tweak(&secp, None).to_inner();
+        let sighash = SighashCache::new(&tx)
+            .taproot_key_spend_signature_hash(1, &Prevouts::All(&prevouts), TapSighashType::Default)
+            .unwrap();
+        let signature = bitcoin::taproot::Signature::from_slice(&tx.input[1].witness.to_vec()[0]).unwrap();
+        secp.verify_schnorr(
+            &signature.sig,
+            &Message::from_slice(sighash.as_byte_array()).unwrap(),
+            &tweaked.x_only_public_key().0,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_unsigned_input_is_incomplete() {
+        let prevouts = vec![TxOut {
+            value: 50_000,
+            script_pubkey: ScriptBuf::new_v0_p2wpkh(
+                &PublicKey::new(key_pair(1).public_key()).wpubkey_hash().unwrap(),
+            ),
+        }];
+        let signer = KeySigner::new(vec![key_pair(3)]);
+        assert!(matches!(
+            sign_transaction(&signer, spend(1), &prevouts),
+            Err(SignerError::Incomplete(0))
+        ));
+        assert!(matches!(
+            sign_transaction(&signer, spend(1), &[]),
+            Err(SignerError::MissingUtxo(0))
+        ));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_signer_wraps_sync() {
+        let prevout = TxOut {
+            value: 50_000,
+            script_pubkey: ScriptBuf::new_v0_p2wpkh(
+                &PublicKey::new(key_pair(1).public_key()).wpubkey_hash().unwrap(),
+            ),
+        };
+        let mut psbt = Psbt::from_unsigned_tx(spend(1)).unwrap();
+        psbt.inputs[0].witness_utxo = Some(prevout);
+
+        let signer: Box<dyn AsyncSigner> = Box::new(KeySigner::new(vec![key_pair(1)]));
+        let signed = signer.sign_psbt_async(psbt.clone()).await.unwrap();
+        assert_eq!(signed.inputs[0].partial_sigs.len(), 1);
+
+        // Other signers run on the blocking pool.
+        let signer: Box<dyn AsyncSigner> = Box::new(Arc::new(KeySigner::new(vec![key_pair(1)])));
+        let signed = signer.sign_psbt_async(psbt).await.unwrap();
+        assert_eq!(signed.inputs[0].partial_sigs.len(), 1);
+    }
+}
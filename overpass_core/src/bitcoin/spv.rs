@@ -0,0 +1,451 @@
+// src/bitcoin/spv.rs
+
+#[cfg(feature = "bitcoin-backend")]
+use crate::bitcoin::esplora::EsploraClient;
+use bitcoin::block::Header;
+use bitcoin::consensus::Params;
+use bitcoin::merkle_tree::MerkleBlock;
+use bitcoin::pow::{Target, Work};
+use bitcoin::{BlockHash, CompactTarget, Network, Txid};
+use std::collections::HashMap;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum SpvError {
+    #[error("Invalid proof of work: {0}")]
+    InvalidProofOfWork(String),
+    #[error("Invalid difficulty at height {0}")]
+    InvalidDifficulty(u32),
+    #[error("Header does not connect to the known chain: {0}")]
+    DisconnectedHeader(BlockHash),
+    #[error("Block not in the header chain: {0}")]
+    UnknownBlock(BlockHash),
+    #[error("Invalid merkle proof: {0}")]
+    InvalidMerkleProof(String),
+    #[error("Sync failed: {0}")]
+    SyncError(String),
+}
+
+/// A proof-of-work validated chain of block headers rooted at a trusted checkpoint.
+///
+/// Mobile clients cannot validate from genesis, so the chain starts from a hard-coded or
+/// user-supplied checkpoint and every later header is checked against consensus rules.
+pub struct HeaderChain {
+    params: Params,
+    checkpoint_height: u32,
+    headers: Vec<Header>,
+    chain_work: Vec<Work>,
+    index: HashMap<BlockHash, u32>,
+}
+
+impl HeaderChain {
+    /// Creates a chain whose first header is the trusted checkpoint at `checkpoint_height`.
+    pub fn new(network: Network, checkpoint_height: u32, checkpoint: Header) -> Self {
+        let mut index = HashMap::new();
+        index.insert(checkpoint.block_hash(), checkpoint_height);
+        Self {
+            params: Params::new(network),
+            checkpoint_height,
+            chain_work: vec![checkpoint.work()],
+            headers: vec![checkpoint],
+            index,
+        }
+    }
+
+    /// Gets the height of the best known header.
+    pub fn tip_height(&self) -> u32 {
+        self.checkpoint_height + self.headers.len() as u32 - 1
+    }
+
+    /// Gets the hash of the best known header.
+    pub fn tip_hash(&self) -> BlockHash {
+        self.tip().block_hash()
+    }
+
+    /// Gets the best known header.
+    pub fn tip(&self) -> &Header {
+        self.headers.last().expect("chain always contains the checkpoint")
+    }
+
+    /// Gets the header at the given height, if known.
+    pub fn header_at(&self, height: u32) -> Option<&Header> {
+        let offset = height.checked_sub(self.checkpoint_height)?;
+        self.headers.get(offset as usize)
+    }
+
+    /// Gets the height of a block on the best chain.
+    pub fn height_of(&self, block_hash: &BlockHash) -> Option<u32> {
+        self.index.get(block_hash).copied()
+    }
+
+    /// Gets the number of confirmations of a block on the best chain (1 at the tip).
+    pub fn confirmations(&self, block_hash: &BlockHash) -> Option<u32> {
+        self.height_of(block_hash)
+            .map(|height| self.tip_height() - height + 1)
+    }
+
+    /// Gets the accumulated work of headers built on top of, and including, `height`.
+    pub fn work_since(&self, height: u32) -> Option<Work> {
+        let offset = height.checked_sub(self.checkpoint_height)? as usize;
+        let tip_work = *self.chain_work.last()?;
+        if offset == 0 {
+            return Some(tip_work);
+        }
+        Some(tip_work - *self.chain_work.get(offset - 1)?)
+    }
+
+    /// Validates and connects a batch of consecutive headers.
+    ///
+    /// The batch may fork from any known header; it replaces the current tip only if it
+    /// carries more accumulated work. Returns the resulting tip height.
+    pub fn connect_headers(&mut self, headers: &[Header]) -> Result<u32, SpvError> {
+        let Some(first) = headers.first() else {
+            return Ok(self.tip_height());
+        };
+        let fork_height = self
+            .height_of(&first.prev_blockhash)
+            .ok_or(SpvError::DisconnectedHeader(first.prev_blockhash))?;
+        let fork_offset = (fork_height - self.checkpoint_height) as usize;
+
+        // Ancestors of a batch header come from the batch itself above the fork point.
+        let ancestor = |height: u32| {
+            if height <= fork_height {
+                self.header_at(height).copied()
+            } else {
+                headers.get((height - fork_height - 1) as usize).copied()
+            }
+        };
+        let mut prev = self.headers[fork_offset];
+        let mut work = self.chain_work[fork_offset];
+        let mut new_work = Vec::with_capacity(headers.len());
+        for (i, header) in headers.iter().enumerate() {
+            let height = fork_height + i as u32 + 1;
+            if header.prev_blockhash != prev.block_hash() {
+                return Err(SpvError::DisconnectedHeader(header.block_hash()));
+            }
+            self.check_header(header, &prev, height, &ancestor)?;
+            work = work + header.work();
+            new_work.push(work);
+            prev = *header;
+        }
+
+        if work <= *self.chain_work.last().expect("non-empty") {
+            return Ok(self.tip_height());
+        }
+
+        for stale in self.headers.drain(fork_offset + 1..) {
+            self.index.remove(&stale.block_hash());
+        }
+        self.chain_work.truncate(fork_offset + 1);
+        for (i, header) in headers.iter().enumerate() {
+            self.index.insert(header.block_hash(), fork_height + i as u32 + 1);
+            self.headers.push(*header);
+        }
+        self.chain_work.extend(new_work);
+        Ok(self.tip_height())
+    }
+
+    /// Gets the network's easiest allowed target. `Params::pow_limit` holds the target
+    /// value itself despite being typed as `Work`.
+    fn pow_limit(&self) -> Target {
+        Target::from_le_bytes(self.params.pow_limit.to_le_bytes())
+    }
+
+    fn check_header(
+        &self,
+        header: &Header,
+        prev: &Header,
+        height: u32,
+        ancestor: &dyn Fn(u32) -> Option<Header>,
+    ) -> Result<(), SpvError> {
+        let target = header.target();
+        if target > self.pow_limit() {
+            return Err(SpvError::InvalidProofOfWork(format!(
+                "Target above the network limit at height {}",
+                height
+            )));
+        }
+        header
+            .validate_pow(target)
+            .map_err(|e| SpvError::InvalidProofOfWork(e.to_string()))?;
+
+        if self.params.no_pow_retargeting {
+            return Ok(());
+        }
+        let interval = self.params.difficulty_adjustment_interval() as u32;
+        if height % interval == 0 {
+            match ancestor(height - interval) {
+                Some(period_start) => {
+                    if header.bits != self.expected_retarget(&period_start, prev) {
+                        return Err(SpvError::InvalidDifficulty(height));
+                    }
+                }
+                None => {
+                    // The period started before the checkpoint, so only the factor-of-four
+                    // bound can be enforced.
+                    let prev_target = prev.target();
+                    if target < prev_target.min_difficulty_transition_threshold()
+                        || target > prev_target.max_difficulty_transition_threshold()
+                    {
+                        return Err(SpvError::InvalidDifficulty(height));
+                    }
+                }
+            }
+        } else if header.bits != prev.bits {
+            // Testnet allows minimum-difficulty blocks after a 20 minute gap.
+            let min_difficulty = self.params.allow_min_difficulty_blocks
+                && header.time > prev.time + 2 * self.params.pow_target_spacing as u32;
+            if !min_difficulty && !self.returns_from_min_difficulty(header, height, interval, ancestor) {
+                return Err(SpvError::InvalidDifficulty(height));
+            }
+        }
+        Ok(())
+    }
+
+    /// Computes the target required at a retarget from the first and last headers of the
+    /// previous period, as Bitcoin Core's `CalculateNextWorkRequired` does.
+    fn expected_retarget(&self, period_start: &Header, prev: &Header) -> CompactTarget {
+        let target_timespan = self.params.pow_target_timespan;
+        let timespan = (prev.time as i64 - period_start.time as i64)
+            .clamp(target_timespan as i64 / 4, target_timespan as i64 * 4) as u64;
+
+        // prev_target * timespan / target_timespan over 320 bits so the product cannot overflow.
+        let bytes = prev.target().to_le_bytes();
+        let mut limbs = [0u64; 5];
+        for (i, limb) in limbs.iter_mut().take(4).enumerate() {
+            *limb = u64::from_le_bytes(bytes[i * 8..i * 8 + 8].try_into().expect("8 bytes"));
+        }
+        let mut carry = 0u128;
+        for limb in limbs.iter_mut() {
+            let product = *limb as u128 * timespan as u128 + carry;
+            *limb = product as u64;
+            carry = product >> 64;
+        }
+        let mut remainder = 0u128;
+        for limb in limbs.iter_mut().rev() {
+            let dividend = (remainder << 64) | *limb as u128;
+            *limb = (dividend / target_timespan as u128) as u64;
+            remainder = dividend % target_timespan as u128;
+        }
+        if limbs[4] != 0 {
+            return self.pow_limit().to_compact_lossy();
+        }
+        let mut bytes = [0u8; 32];
+        for (i, limb) in limbs.iter().take(4).enumerate() {
+            bytes[i * 8..i * 8 + 8].copy_from_slice(&limb.to_le_bytes());
+        }
+        Target::from_le_bytes(bytes).min(self.pow_limit()).to_compact_lossy()
+    }
+
+    /// On testnet, a normal block after minimum-difficulty blocks returns to the last real target.
+    fn returns_from_min_difficulty(
+        &self,
+        header: &Header,
+        height: u32,
+        interval: u32,
+        ancestor: &dyn Fn(u32) -> Option<Header>,
+    ) -> bool {
+        if !self.params.allow_min_difficulty_blocks {
+            return false;
+        }
+        let pow_limit = self.pow_limit().to_compact_lossy();
+        let mut height = height - 1;
+        while height % interval != 0 {
+            match ancestor(height) {
+                Some(h) if h.bits == pow_limit => height -= 1,
+                Some(h) => return h.bits == header.bits,
+                None => return true,
+            }
+        }
+        ancestor(height).is_none_or(|h| h.bits == header.bits)
+    }
+
+    /// Verifies that `txid` is committed to by a block on the best chain and returns its
+    /// confirmation count.
+    pub fn verify_inclusion(&self, merkle_block: &MerkleBlock, txid: &Txid) -> Result<u32, SpvError> {
+        let block_hash = merkle_block.header.block_hash();
+        let confirmations = self
+            .confirmations(&block_hash)
+            .ok_or(SpvError::UnknownBlock(block_hash))?;
+
+        let mut matches = Vec::new();
+        let mut indexes = Vec::new();
+        merkle_block
+            .extract_matches(&mut matches, &mut indexes)
+            .map_err(|e| SpvError::InvalidMerkleProof(format!("{:?}", e)))?;
+        if !matches.contains(txid) {
+            return Err(SpvError::InvalidMerkleProof(format!(
+                "Transaction {} not in proof",
+                txid
+            )));
+        }
+        Ok(confirmations)
+    }
+
+    /// Checks that `txid` is buried under at least `min_confirmations` blocks.
+    pub fn is_buried(
+        &self,
+        merkle_block: &MerkleBlock,
+        txid: &Txid,
+        min_confirmations: u32,
+    ) -> Result<bool, SpvError> {
+        Ok(self.verify_inclusion(merkle_block, txid)? >= min_confirmations)
+    }
+}
+
+/// Fetches and connects headers from an Esplora server until the local tip catches up.
+///
+/// Every header is validated locally, so a dishonest server can only withhold blocks,
+/// not forge confirmations.
+#[cfg(feature = "bitcoin-backend")]
+pub fn sync_headers(
+    chain: &mut HeaderChain,
+    client: &EsploraClient,
+    max_headers: u32,
+) -> Result<u32, SpvError> {
+    let remote_tip = client
+        .get_tip_height()
+        .map_err(|e| SpvError::SyncError(e.to_string()))?;
+    let end = remote_tip.min(chain.tip_height().saturating_add(max_headers));
+
+    let mut headers = Vec::new();
+    for height in chain.tip_height() + 1..=end {
+        let hash = client
+            .get_block_hash(height)
+            .map_err(|e| SpvError::SyncError(e.to_string()))?;
+        headers.push(
+            client
+                .get_header(&hash)
+                .map_err(|e| SpvError::SyncError(e.to_string()))?,
+        );
+    }
+    chain.connect_headers(&headers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::block::Version;
+    use bitcoin::hashes::Hash;
+    use bitcoin::hash_types::TxMerkleNode;
+
+    fn mine(prev: &Header, merkle_root: TxMerkleNode) -> Header {
+        let mut header = Header {
+            version: Version::TWO,
+            prev_blockhash: prev.block_hash(),
+            merkle_root,
+            time: prev.time + 600,
+            bits: prev.bits,
+            nonce: 0,
+        };
+        while header.validate_pow(header.target()).is_err() {
+            header.nonce += 1;
+        }
+        header
+    }
+
+    fn genesis() -> Header {
+        Header {
+            version: Version::ONE,
+            prev_blockhash: BlockHash::all_zeros(),
+            merkle_root: TxMerkleNode::all_zeros(),
+            time: 1_296_688_602,
+            bits: CompactTarget::from_consensus(0x207fffff),
+            nonce: 2,
+        }
+    }
+
+    fn extend(prev: &Header, count: usize, salt: u8) -> Vec<Header> {
+        let mut headers = Vec::new();
+        let mut last = *prev;
+        for _ in 0..count {
+            last = mine(&last, TxMerkleNode::from_byte_array([salt; 32]));
+            headers.push(last);
+        }
+        headers
+    }
+
+    #[test]
+    fn test_connect_and_reorg() {
+        let base = genesis();
+        let mut chain = HeaderChain::new(Network::Regtest, 0, base);
+
+        let main = extend(&base, 3, 1);
+        assert_eq!(chain.connect_headers(&main).unwrap(), 3);
+        assert_eq!(chain.confirmations(&main[0].block_hash()), Some(3));
+
+        // A shorter fork is ignored, a longer one replaces the tip.
+        let fork = extend(&main[0], 1, 2);
+        assert_eq!(chain.connect_headers(&fork).unwrap(), 3);
+        assert_eq!(chain.tip_hash(), main[2].block_hash());
+        let fork = extend(&main[0], 3, 3);
+        assert_eq!(chain.connect_headers(&fork).unwrap(), 4);
+        assert_eq!(chain.tip_hash(), fork[2].block_hash());
+        assert_eq!(chain.height_of(&main[2].block_hash()), None);
+
+        let orphan = extend(&Header { nonce: 99, ..base }, 1, 4);
+        assert!(matches!(
+            chain.connect_headers(&orphan),
+            Err(SpvError::DisconnectedHeader(_))
+        ));
+    }
+
+    #[test]
+    fn test_rejects_invalid_work() {
+        let base = genesis();
+        let mut chain = HeaderChain::new(Network::Regtest, 0, base);
+
+        let mut header = mine(&base, TxMerkleNode::all_zeros());
+        while header.validate_pow(header.target()).is_ok() {
+            header.nonce += 1;
+        }
+        assert!(matches!(
+            chain.connect_headers(&[header]),
+            Err(SpvError::InvalidProofOfWork(_))
+        ));
+    }
+
+    #[test]
+    fn test_verify_inclusion() {
+        let base = genesis();
+        let mut chain = HeaderChain::new(Network::Regtest, 0, base);
+
+        let txids: Vec<Txid> = (0u8..4).map(|i| Txid::from_byte_array([i; 32])).collect();
+        let merkle_root = bitcoin::merkle_tree::calculate_root(txids.iter().map(|t| t.to_raw_hash()))
+            .map(TxMerkleNode::from_raw_hash)
+            .unwrap();
+        let block = mine(&base, merkle_root);
+        chain.connect_headers(&[block]).unwrap();
+        chain.connect_headers(&extend(&block, 5, 9)).unwrap();
+
+        let target = txids[2];
+        let proof = MerkleBlock::from_header_txids_with_predicate(&block, &txids, |t| *t == target);
+        assert_eq!(chain.verify_inclusion(&proof, &target).unwrap(), 6);
+        assert!(chain.is_buried(&proof, &target, 6).unwrap());
+        assert!(!chain.is_buried(&proof, &target, 7).unwrap());
+        assert!(chain.verify_inclusion(&proof, &txids[0]).is_err());
+    }
+
+    #[test]
+    fn test_retarget_matches_core_vectors() {
+        let chain = HeaderChain::new(Network::Bitcoin, 0, genesis());
+        let at = |time: u32, bits: u32| Header {
+            time,
+            bits: CompactTarget::from_consensus(bits),
+            ..genesis()
+        };
+
+        // Vectors from Bitcoin Core's pow_tests: a normal retarget, the pow limit, and
+        // both clamps on the measured timespan.
+        let cases = [
+            (1_261_130_161, 1_262_152_739, 0x1d00ffff, 0x1d00d86a),
+            (1_231_006_505, 1_233_061_996, 0x1d00ffff, 0x1d00ffff),
+            (1_279_008_237, 1_279_297_671, 0x1c05a3f4, 0x1c0168fd),
+            (1_263_163_443, 1_269_211_443, 0x1c387f6f, 0x1d00e1fd),
+        ];
+        for (first_time, last_time, bits, expected) in cases {
+            let retarget = chain.expected_retarget(&at(first_time, bits), &at(last_time, bits));
+            assert_eq!(retarget, CompactTarget::from_consensus(expected));
+        }
+    }
+}
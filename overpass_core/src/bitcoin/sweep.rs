@@ -0,0 +1,388 @@
+// src/bitcoin/sweep.rs
+
+use crate::bitcoin::chain::{ChainBackend, ChainError};
+use crate::bitcoin::commitment::Commitment;
+use crate::bitcoin::fees::{fee_for_vsize, FeeEstimator};
+use crate::bitcoin::signer::{finalize_psbt, Signer};
+use crate::bitcoin::timelocks::csv_sequence;
+use bitcoin::bip32::{DerivationPath, Fingerprint};
+use bitcoin::psbt::PartiallySignedTransaction as Psbt;
+use bitcoin::taproot::{LeafVersion, TapLeafHash};
+use bitcoin::{ScriptBuf, Transaction, TxIn, TxOut, Txid, Witness};
+use std::collections::HashMap;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Virtual size of a sweep spending one delayed commitment output to one P2TR output.
+pub const DELAYED_SWEEP_VSIZE: u64 = 140;
+
+#[derive(Error, Debug)]
+pub enum SweepError {
+    #[error("Party {0} has no output in the commitment")]
+    NoOutput(usize),
+    #[error("Output of {value} sat cannot pay a {fee} sat sweep fee")]
+    Uneconomical { value: u64, fee: u64 },
+    #[error("Signing failed: {0}")]
+    Signing(String),
+    #[error("Fee estimation failed: {0}")]
+    Fee(String),
+    #[error("Chain error: {0}")]
+    Chain(#[from] ChainError),
+}
+
+/// Builds an unsigned sweep of `party`'s delayed output to `destination`, paying the rate
+/// `fee_estimator` gives for confirmation within `target_blocks`.
+pub fn build_sweep(
+    commitment: &Commitment,
+    party: usize,
+    destination: ScriptBuf,
+    fee_estimator: &dyn FeeEstimator,
+    target_blocks: u16,
+) -> Result<(Transaction, TxOut), SweepError> {
+    let fee_rate = fee_estimator
+        .estimate_fee_rate(target_blocks)
+        .map_err(|e| SweepError::Fee(e.to_string()))?;
+    let outpoint = commitment.party_outpoint(party).ok_or(SweepError::NoOutput(party))?;
+    let prevout = commitment.tx.output[outpoint.vout as usize].clone();
+    let fee = fee_for_vsize(fee_rate, DELAYED_SWEEP_VSIZE).map_err(|e| SweepError::Fee(e.to_string()))?;
+    let value = prevout
+        .value
+        .checked_sub(fee)
+        .filter(|value| *value >= destination.dust_value().to_sat())
+        .ok_or(SweepError::Uneconomical {
+            value: prevout.value,
+            fee,
+        })?;
+
+    let tx = Transaction {
+        version: 2,
+        lock_time: bitcoin::absolute::LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: outpoint,
+            script_sig: ScriptBuf::new(),
+            sequence: csv_sequence(commitment.dispute_delay()),
+            witness: Witness::new(),
+        }],
+        output: vec![TxOut {
+            value,
+            script_pubkey: destination,
+        }],
+    };
+    Ok((tx, prevout))
+}
+
+/// Builds the PSBT for a sweep, describing `party`'s delayed leaf so a signer holding
+/// the payout key can sign it.
+pub fn sweep_psbt(
+    commitment: &Commitment,
+    party: usize,
+    tx: Transaction,
+    prevout: &TxOut,
+) -> Result<Psbt, SweepError> {
+    let (script, control_block) = commitment.delayed_leaf(party).ok_or(SweepError::NoOutput(party))?;
+    let payout_key = commitment.payout_key(party).ok_or(SweepError::NoOutput(party))?;
+    let leaf_hash = TapLeafHash::from_script(&script, LeafVersion::TapScript);
+
+    let mut psbt = Psbt::from_unsigned_tx(tx).map_err(|e| SweepError::Signing(e.to_string()))?;
+    let input = &mut psbt.inputs[0];
+    input.witness_utxo = Some(prevout.clone());
+    input.tap_scripts.insert(control_block, (script, LeafVersion::TapScript));
+    input.tap_key_origins.insert(
+        payout_key,
+        (vec![leaf_hash], (Fingerprint::default(), DerivationPath::default())),
+    );
+    Ok(psbt)
+}
+
+/// Signs a sweep through `party`'s delayed leaf.
+pub fn sign_sweep(
+    signer: &dyn Signer,
+    commitment: &Commitment,
+    party: usize,
+    tx: Transaction,
+    prevout: &TxOut,
+) -> Result<Transaction, SweepError> {
+    let mut psbt = sweep_psbt(commitment, party, tx, prevout)?;
+    signer.sign_psbt(&mut psbt).map_err(|e| SweepError::Signing(e.to_string()))?;
+    finalize_psbt(&mut psbt).map_err(|e| SweepError::Signing(e.to_string()))?;
+    Ok(psbt.extract_tx())
+}
+
+/// Events raised while sweeping closed channels.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SweepEvent {
+    Broadcast {
+        commitment_txid: Txid,
+        sweep_txid: Txid,
+    },
+    Confirmed {
+        commitment_txid: Txid,
+        sweep_txid: Txid,
+    },
+    /// The output cannot be swept profitably at the current fee rate.
+    Skipped {
+        commitment_txid: Txid,
+        reason: String,
+    },
+}
+
+struct PendingSweep {
+    commitment: Commitment,
+    party: usize,
+    destination: ScriptBuf,
+    sweep_txid: Option<Txid>,
+}
+
+/// Sweeps delayed commitment outputs to the wallet once their dispute delay has passed.
+pub struct Sweeper {
+    signer: Arc<dyn Signer>,
+    target_blocks: u16,
+    required_confirmations: u32,
+    pending: HashMap<Txid, PendingSweep>,
+}
+
+impl Sweeper {
+    pub fn new(signer: Arc<dyn Signer>, target_blocks: u16, required_confirmations: u32) -> Self {
+        Self {
+            signer,
+            target_blocks,
+            required_confirmations: required_confirmations.max(1),
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Schedules `party`'s output of a published commitment for sweeping to `destination`.
+    pub fn schedule(&mut self, commitment: Commitment, party: usize, destination: ScriptBuf) {
+        self.pending.insert(
+            commitment.tx.txid(),
+            PendingSweep {
+                commitment,
+                party,
+                destination,
+                sweep_txid: None,
+            },
+        );
+    }
+
+    pub fn pending_count(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Broadcasts sweeps whose timelock has matured and reports confirmed ones.
+    ///
+    /// A sweep that disappears from the chain and mempool is rebuilt at the current fee
+    /// rate on the next poll.
+    pub fn poll(
+        &mut self,
+        backend: &dyn ChainBackend,
+        fee_estimator: &dyn FeeEstimator,
+    ) -> Result<Vec<SweepEvent>, SweepError> {
+        let mut events = Vec::new();
+        let mut finished = Vec::new();
+
+        for (commitment_txid, sweep) in self.pending.iter_mut() {
+            if let Some(sweep_txid) = sweep.sweep_txid {
+                match backend.confirmations(&sweep_txid)? {
+                    Some(confirmations) if confirmations >= self.required_confirmations => {
+                        finished.push(*commitment_txid);
+                        events.push(SweepEvent::Confirmed {
+                            commitment_txid: *commitment_txid,
+                            sweep_txid,
+                        });
+                    }
+                    Some(_) => {}
+                    None => sweep.sweep_txid = None,
+                }
+                continue;
+            }
+
+            let confirmations = backend.confirmations(commitment_txid)?.unwrap_or(0);
+            if confirmations < sweep.commitment.dispute_delay() as u32 {
+                continue;
+            }
+
+            let (tx, prevout) = match build_sweep(
+                &sweep.commitment,
+                sweep.party,
+                sweep.destination.clone(),
+                fee_estimator,
+                self.target_blocks,
+            ) {
+                Ok(built) => built,
+                Err(e @ SweepError::Uneconomical { .. }) => {
+                    events.push(SweepEvent::Skipped {
+                        commitment_txid: *commitment_txid,
+                        reason: e.to_string(),
+                    });
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            let tx = sign_sweep(self.signer.as_ref(), &sweep.commitment, sweep.party, tx, &prevout)?;
+            let sweep_txid = backend.broadcast(&tx)?;
+            sweep.sweep_txid = Some(sweep_txid);
+            events.push(SweepEvent::Broadcast {
+                commitment_txid: *commitment_txid,
+                sweep_txid,
+            });
+        }
+
+        for commitment_txid in finished {
+            self.pending.remove(&commitment_txid);
+        }
+        Ok(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::commitment::{CommitmentParams, CommitmentParty};
+    use crate::bitcoin::dust::DustPolicy;
+    use crate::bitcoin::fees::StaticFeeEstimator;
+    use crate::bitcoin::signer::KeySigner;
+    use bitcoin::hashes::Hash;
+    use bitcoin::secp256k1::{KeyPair, Message, Secp256k1};
+    use bitcoin::sighash::{Prevouts, SighashCache, TapSighashType};
+    use crate::bitcoin::timelocks::ChannelTimelocks;
+    use crate::zkp::channel::ChannelState;
+    use bitcoin::secp256k1::SecretKey;
+    use bitcoin::{FeeRate, OutPoint, PublicKey};
+    use std::sync::Mutex;
+    use std::collections::BTreeMap;
+
+    #[derive(Default)]
+    struct MockChain {
+        confirmations: Mutex<HashMap<Txid, u32>>,
+        broadcasts: Mutex<Vec<Transaction>>,
+    }
+
+    impl ChainBackend for MockChain {
+        fn broadcast(&self, tx: &Transaction) -> Result<Txid, ChainError> {
+            self.broadcasts.lock().unwrap().push(tx.clone());
+            Ok(tx.txid())
+        }
+
+        fn confirmations(&self, txid: &Txid) -> Result<Option<u32>, ChainError> {
+            Ok(self.confirmations.lock().unwrap().get(txid).copied())
+        }
+
+        fn tip_height(&self) -> Result<u32, ChainError> {
+            Ok(100)
+        }
+
+        fn spending_transaction(&self, _outpoint: &OutPoint) -> Result<Option<Transaction>, ChainError> {
+            Ok(None)
+        }
+    }
+
+    fn keypair(byte: u8) -> KeyPair {
+        KeyPair::from_secret_key(&Secp256k1::new(), &SecretKey::from_slice(&[byte; 32]).unwrap())
+    }
+
+    fn party(byte: u8) -> CommitmentParty {
+        CommitmentParty {
+            payout_key: keypair(byte).x_only_public_key().0,
+            revocation_key: keypair(byte + 10).x_only_public_key().0,
+            anchor_key: PublicKey::new(keypair(byte + 20).public_key()),
+        }
+    }
+
+    fn commitment() -> Commitment {
+        let params = CommitmentParams {
+            funding_outpoint: OutPoint::new(Txid::all_zeros(), 0),
+            funding_value: 100_000,
+            timelocks: ChannelTimelocks {
+                dispute_delay: 10,
+                expiry_height: 900_000,
+            },
+            parties: [party(1), party(2)],
+            anchors: false,
+            dust_policy: DustPolicy::FoldIntoFees,
+        };
+        let state = ChannelState {
+            balances: vec![70_000, 29_000],
+            nonce: 3,
+            metadata: vec![],
+            merkle_root: [0u8; 32],
+            proof: Some(vec![1]),
+            assets: BTreeMap::new(),
+        };
+        Commitment::from_state(&Secp256k1::new(), &params, &state).unwrap()
+    }
+
+    #[test]
+    fn test_signed_sweep_verifies() {
+        let secp = Secp256k1::new();
+        let commitment = commitment();
+        let destination = ScriptBuf::new_v1_p2tr(&secp, keypair(9).x_only_public_key().0, None);
+        let estimator = StaticFeeEstimator::new(FeeRate::from_sat_per_vb_unchecked(2));
+        let (tx, prevout) = build_sweep(&commitment, 1, destination, &estimator, 6).unwrap();
+        assert_eq!(tx.output[0].value, 29_000 - 2 * DELAYED_SWEEP_VSIZE);
+        assert_eq!(tx.input[0].sequence, csv_sequence(10));
+
+        let signer = KeySigner::new(vec![keypair(2)]);
+        let tx = sign_sweep(&signer, &commitment, 1, tx, &prevout).unwrap();
+        assert_eq!(tx.input[0].witness.len(), 3);
+        let (script, _) = commitment.delayed_leaf(1).unwrap();
+        let sighash = SighashCache::new(&tx)
+            .taproot_script_spend_signature_hash(
+                0,
+                &Prevouts::All(&[&prevout]),
+                TapLeafHash::from_script(&script, LeafVersion::TapScript),
+                TapSighashType::Default,
+            )
+            .unwrap();
+        let witness: Vec<&[u8]> = tx.input[0].witness.iter().collect();
+        let signature = bitcoin::taproot::Signature::from_slice(witness[0]).unwrap();
+        secp.verify_schnorr(
+            &signature.sig,
+            &Message::from_slice(sighash.as_byte_array()).unwrap(),
+            &keypair(2).x_only_public_key().0,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            build_sweep(
+                &commitment,
+                1,
+                ScriptBuf::new(),
+                &StaticFeeEstimator::new(FeeRate::from_sat_per_vb_unchecked(500)),
+                6,
+            ),
+            Err(SweepError::Uneconomical { .. })
+        ));
+    }
+
+    #[test]
+    fn test_sweeper_waits_for_timelock_and_confirmation() {
+        let secp = Secp256k1::new();
+        let commitment = commitment();
+        let commitment_txid = commitment.tx.txid();
+        let destination = ScriptBuf::new_v1_p2tr(&secp, keypair(9).x_only_public_key().0, None);
+        let chain = MockChain::default();
+        let fees = StaticFeeEstimator::new(FeeRate::from_sat_per_vb_unchecked(2));
+        let mut sweeper = Sweeper::new(Arc::new(KeySigner::new(vec![keypair(1)])), 6, 1);
+        sweeper.schedule(commitment, 0, destination);
+
+        chain.confirmations.lock().unwrap().insert(commitment_txid, 9);
+        assert!(sweeper.poll(&chain, &fees).unwrap().is_empty());
+
+        chain.confirmations.lock().unwrap().insert(commitment_txid, 10);
+        let events = sweeper.poll(&chain, &fees).unwrap();
+        let sweep_txid = match events[..] {
+            [SweepEvent::Broadcast { sweep_txid, .. }] => sweep_txid,
+            _ => panic!("unexpected events: {:?}", events),
+        };
+        assert_eq!(chain.broadcasts.lock().unwrap().len(), 1);
+
+        chain.confirmations.lock().unwrap().insert(sweep_txid, 0);
+        assert!(sweeper.poll(&chain, &fees).unwrap().is_empty());
+        chain.confirmations.lock().unwrap().insert(sweep_txid, 1);
+        assert_eq!(
+            sweeper.poll(&chain, &fees).unwrap(),
+            vec![SweepEvent::Confirmed { commitment_txid, sweep_txid }]
+        );
+        assert_eq!(sweeper.pending_count(), 0);
+    }
+}
@@ -0,0 +1,250 @@
+// src/bitcoin/taproot.rs
+
+use bitcoin::blockdata::opcodes::all::{OP_CHECKSIGVERIFY, OP_CLTV, OP_CSV};
+use bitcoin::blockdata::script::Builder;
+use bitcoin::secp256k1::{schnorr, Secp256k1, Verification, XOnlyPublicKey};
+use bitcoin::sighash::TapSighashType;
+use bitcoin::taproot::{ControlBlock, LeafVersion, TaprootBuilder, TaprootSpendInfo};
+use bitcoin::{Address, Network, ScriptBuf, TxOut, Witness};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Errors raised while constructing or spending Taproot funding outputs.
+#[derive(Error, Debug)]
+pub enum TaprootError {
+    #[error("Invalid funding parameters: {0}")]
+    InvalidParameters(String),
+    #[error("Taproot tree construction failed: {0}")]
+    BuilderError(String),
+    #[error("Control block not found for leaf {0:?}")]
+    MissingControlBlock(FundingLeaf),
+}
+
+/// Script-path leaves committed to by a channel funding output.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FundingLeaf {
+    /// Unilateral close path, spendable once the dispute window (CSV) has elapsed.
+    Dispute,
+    /// Funder refund path, spendable once the channel expiry height (CLTV) is reached.
+    Timeout,
+}
+
+/// Parameters describing a channel's Taproot funding output.
+///
+/// The internal key carries the cooperative key-spend path and is expected to be the
+/// aggregate of both counterparties' keys; the two script leaves are fallbacks used when
+/// the counterparties stop cooperating.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaprootFundingParams {
+    pub internal_key: XOnlyPublicKey,
+    pub dispute_key: XOnlyPublicKey,
+    pub dispute_delay: u16,
+    pub timeout_key: XOnlyPublicKey,
+    pub timeout_height: u32,
+}
+
+/// A Taproot funding output with its key-spend and script-path spend information.
+#[derive(Clone, Debug)]
+pub struct TaprootFunding {
+    params: TaprootFundingParams,
+    dispute_script: ScriptBuf,
+    timeout_script: ScriptBuf,
+    spend_info: TaprootSpendInfo,
+}
+
+impl TaprootFunding {
+    /// Builds the funding output's Taproot tree from the given parameters.
+    pub fn new<C: Verification>(
+        secp: &Secp256k1<C>,
+        params: TaprootFundingParams,
+    ) -> Result<Self, TaprootError> {
+        if params.dispute_delay == 0 {
+            return Err(TaprootError::InvalidParameters(
+                "Dispute delay must be at least one block".to_string(),
+            ));
+        }
+        if params.timeout_height == 0 || params.timeout_height >= 500_000_000 {
+            return Err(TaprootError::InvalidParameters(
+                "Timeout must be a block height".to_string(),
+            ));
+        }
+
+        let dispute_script = dispute_script(&params.dispute_key, params.dispute_delay);
+        let timeout_script = timeout_script(&params.timeout_key, params.timeout_height);
+
+        let spend_info = TaprootBuilder::new()
+            .add_leaf(1, dispute_script.clone())
+            .and_then(|builder| builder.add_leaf(1, timeout_script.clone()))
+            .map_err(|e| TaprootError::BuilderError(e.to_string()))?
+            .finalize(secp, params.internal_key)
+            .map_err(|_| TaprootError::BuilderError("Taproot tree is incomplete".to_string()))?;
+
+        Ok(Self {
+            params,
+            dispute_script,
+            timeout_script,
+            spend_info,
+        })
+    }
+
+    /// Gets the parameters this output was built from.
+    pub fn params(&self) -> &TaprootFundingParams {
+        &self.params
+    }
+
+    /// Gets the Taproot spend information (output key, merkle root, control blocks).
+    pub fn spend_info(&self) -> &TaprootSpendInfo {
+        &self.spend_info
+    }
+
+    /// Gets the tweaked output key the funding output pays to.
+    pub fn output_key(&self) -> XOnlyPublicKey {
+        self.spend_info.output_key().to_inner()
+    }
+
+    /// Gets the P2TR script pubkey of the funding output.
+    pub fn script_pubkey(&self) -> ScriptBuf {
+        ScriptBuf::new_v1_p2tr_tweaked(self.spend_info.output_key())
+    }
+
+    /// Gets the bech32m address of the funding output on the given network.
+    pub fn address(&self, network: Network) -> Address {
+        Address::p2tr_tweaked(self.spend_info.output_key(), network)
+    }
+
+    /// Creates the funding `TxOut` locking the given amount.
+    pub fn funding_output(&self, value: u64) -> TxOut {
+        TxOut {
+            value,
+            script_pubkey: self.script_pubkey(),
+        }
+    }
+
+    /// Gets the tapscript for a script-path leaf.
+    pub fn leaf_script(&self, leaf: FundingLeaf) -> &ScriptBuf {
+        match leaf {
+            FundingLeaf::Dispute => &self.dispute_script,
+            FundingLeaf::Timeout => &self.timeout_script,
+        }
+    }
+
+    /// Gets the control block proving a leaf's inclusion in the output key.
+    pub fn control_block(&self, leaf: FundingLeaf) -> Result<ControlBlock, TaprootError> {
+        let script = self.leaf_script(leaf).clone();
+        self.spend_info
+            .control_block(&(script, LeafVersion::TapScript))
+            .ok_or(TaprootError::MissingControlBlock(leaf))
+    }
+
+    /// Builds the witness for a cooperative key-path spend.
+    pub fn key_spend_witness(&self, signature: &schnorr::Signature) -> Witness {
+        let sig = bitcoin::taproot::Signature {
+            sig: *signature,
+            hash_ty: TapSighashType::Default,
+        };
+        Witness::from_slice(&[sig.to_vec()])
+    }
+
+    /// Builds the witness for a script-path spend through the given leaf.
+    pub fn script_spend_witness(
+        &self,
+        leaf: FundingLeaf,
+        signature: &schnorr::Signature,
+    ) -> Result<Witness, TaprootError> {
+        let control_block = self.control_block(leaf)?;
+        let sig = bitcoin::taproot::Signature {
+            sig: *signature,
+            hash_ty: TapSighashType::Default,
+        };
+        Ok(Witness::from_slice(&[
+            sig.to_vec(),
+            self.leaf_script(leaf).to_bytes(),
+            control_block.serialize(),
+        ]))
+    }
+}
+
+/// Builds the dispute leaf: `<key> OP_CHECKSIGVERIFY <delay> OP_CHECKSEQUENCEVERIFY`.
+pub fn dispute_script(key: &XOnlyPublicKey, delay: u16) -> ScriptBuf {
+    Builder::new()
+        .push_x_only_key(key)
+        .push_opcode(OP_CHECKSIGVERIFY)
+        .push_int(delay as i64)
+        .push_opcode(OP_CSV)
+        .into_script()
+}
+
+/// Builds the timeout leaf: `<key> OP_CHECKSIGVERIFY <height> OP_CHECKLOCKTIMEVERIFY`.
+pub fn timeout_script(key: &XOnlyPublicKey, height: u32) -> ScriptBuf {
+    Builder::new()
+        .push_x_only_key(key)
+        .push_opcode(OP_CHECKSIGVERIFY)
+        .push_int(height as i64)
+        .push_opcode(OP_CLTV)
+        .into_script()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::secp256k1::{KeyPair, SecretKey};
+
+    fn x_only(secp: &Secp256k1<bitcoin::secp256k1::All>, byte: u8) -> XOnlyPublicKey {
+        let secret = SecretKey::from_slice(&[byte; 32]).unwrap();
+        KeyPair::from_secret_key(secp, &secret).x_only_public_key().0
+    }
+
+    fn test_params(secp: &Secp256k1<bitcoin::secp256k1::All>) -> TaprootFundingParams {
+        TaprootFundingParams {
+            internal_key: x_only(secp, 1),
+            dispute_key: x_only(secp, 2),
+            dispute_delay: 144,
+            timeout_key: x_only(secp, 3),
+            timeout_height: 800_000,
+        }
+    }
+
+    #[test]
+    fn test_funding_output_is_p2tr() {
+        let secp = Secp256k1::new();
+        let funding = TaprootFunding::new(&secp, test_params(&secp)).unwrap();
+
+        assert!(funding.script_pubkey().is_v1_p2tr());
+        assert_eq!(funding.funding_output(50_000).value, 50_000);
+        assert!(funding
+            .address(Network::Regtest)
+            .to_string()
+            .starts_with("bcrt1p"));
+    }
+
+    #[test]
+    fn test_control_blocks_commit_to_leaves() {
+        let secp = Secp256k1::new();
+        let funding = TaprootFunding::new(&secp, test_params(&secp)).unwrap();
+
+        for leaf in [FundingLeaf::Dispute, FundingLeaf::Timeout] {
+            let control_block = funding.control_block(leaf).unwrap();
+            assert!(control_block.verify_taproot_commitment(
+                &secp,
+                funding.output_key(),
+                funding.leaf_script(leaf),
+            ));
+        }
+    }
+
+    #[test]
+    fn test_invalid_parameters() {
+        let secp = Secp256k1::new();
+
+        let mut params = test_params(&secp);
+        params.dispute_delay = 0;
+        assert!(matches!(
+            TaprootFunding::new(&secp, params),
+            Err(TaprootError::InvalidParameters(_))
+        ));
+
+        let mut params = test_params(&secp);
+        params.timeout_height = 600_000_000;
+        assert!(TaprootFunding::new(&secp, params).is_err());
+    }
+}
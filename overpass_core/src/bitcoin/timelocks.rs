@@ -0,0 +1,209 @@
+// src/bitcoin/timelocks.rs
+
+use crate::bitcoin::taproot::TaprootFundingParams;
+use bitcoin::absolute::LockTime;
+use bitcoin::secp256k1::XOnlyPublicKey;
+use bitcoin::{Sequence, Transaction};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Target spacing between blocks, used to convert durations into block counts.
+pub const TARGET_BLOCK_SECS: u64 = 600;
+
+/// Largest absolute lock time interpreted as a block height rather than a timestamp.
+const LOCK_TIME_THRESHOLD: u32 = 500_000_000;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum TimelockError {
+    #[error("Relative lock of {0} blocks exceeds the CSV maximum of 65535")]
+    RelativeLockTooLong(u64),
+    #[error("Invalid lock height: {0}")]
+    InvalidHeight(u32),
+    #[error("Invalid timelock parameters: {0}")]
+    InvalidParameters(String),
+    #[error("Input index {0} out of range")]
+    InputOutOfRange(usize),
+}
+
+/// Converts a duration in seconds to the number of blocks expected to cover it.
+pub fn blocks_for_duration(secs: u64) -> Result<u16, TimelockError> {
+    let blocks = secs.div_ceil(TARGET_BLOCK_SECS).max(1);
+    u16::try_from(blocks).map_err(|_| TimelockError::RelativeLockTooLong(blocks))
+}
+
+/// Builds the input sequence satisfying an `OP_CHECKSEQUENCEVERIFY` of `delay` blocks.
+pub fn csv_sequence(delay: u16) -> Sequence {
+    Sequence::from_height(delay)
+}
+
+/// Builds the transaction lock time satisfying an `OP_CHECKLOCKTIMEVERIFY` at `height`.
+pub fn cltv_lock_time(height: u32) -> Result<LockTime, TimelockError> {
+    LockTime::from_height(height).map_err(|_| TimelockError::InvalidHeight(height))
+}
+
+/// Timelocks guarding a channel's unilateral spending paths.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChannelTimelocks {
+    /// Blocks the dispute path must wait after the close confirms (CSV).
+    pub dispute_delay: u16,
+    /// Height at which the funder may reclaim the funding output (CLTV).
+    pub expiry_height: u32,
+}
+
+impl ChannelTimelocks {
+    /// Derives timelocks from a channel's opening height, dispute window and lifetime.
+    ///
+    /// The lifetime must leave room for a full dispute window before expiry, otherwise the
+    /// funder could reclaim the output while a dispute is still pending.
+    pub fn from_channel_params(
+        opening_height: u32,
+        dispute_window_secs: u64,
+        lifetime_blocks: u32,
+    ) -> Result<Self, TimelockError> {
+        let dispute_delay = blocks_for_duration(dispute_window_secs)?;
+        if lifetime_blocks <= dispute_delay as u32 {
+            return Err(TimelockError::InvalidParameters(format!(
+                "Lifetime of {} blocks does not exceed the {}-block dispute window",
+                lifetime_blocks, dispute_delay
+            )));
+        }
+        let expiry_height = opening_height
+            .checked_add(lifetime_blocks)
+            .filter(|height| *height < LOCK_TIME_THRESHOLD)
+            .ok_or(TimelockError::InvalidHeight(opening_height))?;
+
+        Ok(Self {
+            dispute_delay,
+            expiry_height,
+        })
+    }
+
+    /// Builds Taproot funding parameters enforcing these timelocks.
+    pub fn funding_params(
+        &self,
+        internal_key: XOnlyPublicKey,
+        dispute_key: XOnlyPublicKey,
+        timeout_key: XOnlyPublicKey,
+    ) -> TaprootFundingParams {
+        TaprootFundingParams {
+            internal_key,
+            dispute_key,
+            dispute_delay: self.dispute_delay,
+            timeout_key,
+            timeout_height: self.expiry_height,
+        }
+    }
+
+    /// Gets the first height at which a close confirmed at `close_height` can be disputed.
+    pub fn dispute_mature_height(&self, close_height: u32) -> u32 {
+        close_height.saturating_add(self.dispute_delay as u32)
+    }
+
+    /// Checks whether the dispute path is spendable at `current_height`.
+    pub fn is_dispute_mature(&self, close_height: u32, current_height: u32) -> bool {
+        current_height >= self.dispute_mature_height(close_height)
+    }
+
+    /// Checks whether the timeout path is spendable in a block at `current_height`.
+    pub fn is_expired(&self, current_height: u32) -> bool {
+        current_height >= self.expiry_height
+    }
+
+    /// Sets the sequence of a dispute-path input so its CSV check passes.
+    pub fn apply_dispute_lock(
+        &self,
+        tx: &mut Transaction,
+        input_index: usize,
+    ) -> Result<(), TimelockError> {
+        let input = tx
+            .input
+            .get_mut(input_index)
+            .ok_or(TimelockError::InputOutOfRange(input_index))?;
+        input.sequence = csv_sequence(self.dispute_delay);
+        // BIP68 relative locks are only enforced for version 2 transactions.
+        tx.version = tx.version.max(2);
+        Ok(())
+    }
+
+    /// Sets the lock time and a non-final sequence so a timeout-path CLTV check passes.
+    pub fn apply_timeout_lock(
+        &self,
+        tx: &mut Transaction,
+        input_index: usize,
+    ) -> Result<(), TimelockError> {
+        let lock_time = cltv_lock_time(self.expiry_height)?;
+        let input = tx
+            .input
+            .get_mut(input_index)
+            .ok_or(TimelockError::InputOutOfRange(input_index))?;
+        if input.sequence == Sequence::MAX {
+            input.sequence = Sequence::ENABLE_RBF_NO_LOCKTIME;
+        }
+        tx.lock_time = lock_time;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::hashes::Hash;
+    use bitcoin::{OutPoint, ScriptBuf, TxIn, Txid, Witness};
+
+    fn spend_tx() -> Transaction {
+        Transaction {
+            version: 1,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(Txid::all_zeros(), 0),
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::MAX,
+                witness: Witness::new(),
+            }],
+            output: vec![],
+        }
+    }
+
+    #[test]
+    fn test_lock_values_from_channel_params() {
+        // One day of dispute window is 144 blocks.
+        let locks = ChannelTimelocks::from_channel_params(800_000, 86_400, 4_320).unwrap();
+        assert_eq!(locks.dispute_delay, 144);
+        assert_eq!(locks.expiry_height, 804_320);
+        assert_eq!(locks.dispute_mature_height(800_010), 800_154);
+        assert!(!locks.is_dispute_mature(800_010, 800_153));
+        assert!(locks.is_dispute_mature(800_010, 800_154));
+        assert!(locks.is_expired(804_320));
+
+        assert_eq!(blocks_for_duration(1), Ok(1));
+        assert!(matches!(
+            blocks_for_duration(600 * 70_000),
+            Err(TimelockError::RelativeLockTooLong(_))
+        ));
+        assert!(ChannelTimelocks::from_channel_params(800_000, 86_400, 100).is_err());
+    }
+
+    #[test]
+    fn test_apply_locks_to_spends() {
+        let locks = ChannelTimelocks {
+            dispute_delay: 144,
+            expiry_height: 804_320,
+        };
+
+        let mut tx = spend_tx();
+        locks.apply_dispute_lock(&mut tx, 0).unwrap();
+        assert_eq!(tx.version, 2);
+        assert!(tx.input[0].sequence.is_height_locked());
+        assert_eq!(tx.input[0].sequence, Sequence(144));
+
+        let mut tx = spend_tx();
+        locks.apply_timeout_lock(&mut tx, 0).unwrap();
+        assert_eq!(tx.lock_time, LockTime::from_height(804_320).unwrap());
+        assert!(tx.input[0].sequence.enables_absolute_lock_time());
+
+        assert_eq!(
+            locks.apply_dispute_lock(&mut tx, 3),
+            Err(TimelockError::InputOutOfRange(3))
+        );
+    }
+}
@@ -0,0 +1,87 @@
+// src/bitcoin/triggers.rs
+
+use crate::bitcoin::chain::{ChainBackend, ChainError};
+use serde::{Deserialize, Serialize};
+
+/// A moment observed both on the local clock and on the chain.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainTime {
+    /// Unix time in seconds.
+    pub unix: u64,
+    /// Height of the best block at that time.
+    pub height: u32,
+}
+
+impl ChainTime {
+    pub fn new(unix: u64, height: u32) -> Self {
+        Self { unix, height }
+    }
+
+    /// Pairs `unix` with the backend's current tip height.
+    pub fn observe(backend: &dyn ChainBackend, unix: u64) -> Result<Self, ChainError> {
+        Ok(Self {
+            unix,
+            height: backend.tip_height()?,
+        })
+    }
+}
+
+/// How long to wait between two occurrences of a recurring action.
+///
+/// A block interval is a clock both parties agree on, so when both limits are set the
+/// interval elapses as soon as either one does.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Interval {
+    pub secs: Option<u64>,
+    pub blocks: Option<u32>,
+}
+
+impl Interval {
+    pub fn secs(secs: u64) -> Self {
+        Self {
+            secs: Some(secs),
+            blocks: None,
+        }
+    }
+
+    pub fn blocks(blocks: u32) -> Self {
+        Self {
+            secs: None,
+            blocks: Some(blocks),
+        }
+    }
+
+    /// Also lets the interval elapse after `blocks` blocks.
+    pub fn or_blocks(mut self, blocks: u32) -> Self {
+        self.blocks = Some(blocks);
+        self
+    }
+
+    /// Checks whether the interval has passed between `since` and `now`.
+    pub fn has_elapsed(&self, since: ChainTime, now: ChainTime) -> bool {
+        let by_time = self.secs.is_some_and(|secs| now.unix >= since.unix.saturating_add(secs));
+        let by_height = self
+            .blocks
+            .is_some_and(|blocks| now.height >= since.height.saturating_add(blocks));
+        by_time || by_height
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interval_elapses_on_either_clock() {
+        let start = ChainTime::new(1_000, 100);
+        let interval = Interval::secs(3_600).or_blocks(6);
+        assert!(!interval.has_elapsed(start, ChainTime::new(2_000, 105)));
+        assert!(interval.has_elapsed(start, ChainTime::new(2_000, 106)));
+        assert!(interval.has_elapsed(start, ChainTime::new(4_600, 100)));
+
+        // A height-only interval ignores a skewed local clock.
+        let blocks = Interval::blocks(6);
+        assert!(!blocks.has_elapsed(start, ChainTime::new(u64::MAX, 105)));
+        assert!(blocks.has_elapsed(start, ChainTime::new(0, 106)));
+    }
+}
@@ -0,0 +1,366 @@
+// src/bitcoin/utxo.rs
+
+use crate::bitcoin::fees::fee_for_vsize;
+use bitcoin::{FeeRate, OutPoint, Transaction, TxOut};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use thiserror::Error;
+
+/// Maximum number of branches explored by branch-and-bound before falling back.
+const BNB_MAX_TRIES: usize = 100_000;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum CoinSelectionError {
+    #[error("Insufficient funds: need {needed} sat, have {available} sat")]
+    InsufficientFunds { needed: u64, available: u64 },
+    #[error("Invalid selection parameters: {0}")]
+    InvalidParameters(String),
+}
+
+/// A spendable output owned by the wallet.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Utxo {
+    pub outpoint: OutPoint,
+    pub txout: TxOut,
+    pub confirmations: u32,
+    /// Virtual size this output adds to a transaction when spent, including its witness.
+    pub input_vsize: u64,
+}
+
+impl Utxo {
+    /// Gets the output's value minus the fee needed to spend it at `fee_rate`.
+    pub fn effective_value(&self, fee_rate: FeeRate) -> i64 {
+        let spend_fee = fee_for_vsize(fee_rate, self.input_vsize).unwrap_or(u64::MAX);
+        self.txout.value as i64 - spend_fee.min(i64::MAX as u64) as i64
+    }
+}
+
+/// Tracks the wallet's unspent outputs and which of them are reserved by pending spends.
+#[derive(Clone, Debug, Default)]
+pub struct UtxoSet {
+    utxos: HashMap<OutPoint, Utxo>,
+    reserved: HashSet<OutPoint>,
+}
+
+impl UtxoSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds or replaces an unspent output.
+    pub fn insert(&mut self, utxo: Utxo) {
+        self.utxos.insert(utxo.outpoint, utxo);
+    }
+
+    /// Removes an output, e.g. once it has been spent.
+    pub fn remove(&mut self, outpoint: &OutPoint) -> Option<Utxo> {
+        self.reserved.remove(outpoint);
+        self.utxos.remove(outpoint)
+    }
+
+    /// Gets an output by outpoint.
+    pub fn get(&self, outpoint: &OutPoint) -> Option<&Utxo> {
+        self.utxos.get(outpoint)
+    }
+
+    /// Removes every output spent by `tx`.
+    pub fn apply_transaction(&mut self, tx: &Transaction) {
+        for input in &tx.input {
+            self.remove(&input.previous_output);
+        }
+    }
+
+    /// Marks outputs as reserved so concurrent selections do not double-spend them.
+    pub fn reserve(&mut self, outpoints: &[OutPoint]) {
+        self.reserved.extend(outpoints.iter().filter(|o| self.utxos.contains_key(o)));
+    }
+
+    /// Releases previously reserved outputs.
+    pub fn release(&mut self, outpoints: &[OutPoint]) {
+        for outpoint in outpoints {
+            self.reserved.remove(outpoint);
+        }
+    }
+
+    /// Lists unreserved outputs with at least `min_confirmations` confirmations.
+    pub fn available(&self, min_confirmations: u32) -> Vec<Utxo> {
+        self.utxos
+            .values()
+            .filter(|u| u.confirmations >= min_confirmations && !self.reserved.contains(&u.outpoint))
+            .cloned()
+            .collect()
+    }
+
+    /// Gets the total value of all tracked outputs.
+    pub fn balance(&self) -> u64 {
+        self.utxos.values().map(|u| u.txout.value).sum()
+    }
+
+    pub fn len(&self) -> usize {
+        self.utxos.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.utxos.is_empty()
+    }
+}
+
+/// Parameters for selecting funding inputs.
+#[derive(Clone, Copy, Debug)]
+pub struct SelectionParams {
+    /// Amount the non-change outputs must receive.
+    pub target: u64,
+    pub fee_rate: FeeRate,
+    /// Virtual size of the transaction without inputs or change (header and outputs).
+    pub base_vsize: u64,
+    /// Virtual size added by a change output.
+    pub change_vsize: u64,
+    /// Virtual size of later spending the change output.
+    pub change_spend_vsize: u64,
+    /// Change below this value is dropped to fees instead of creating a dust output.
+    pub dust_limit: u64,
+}
+
+/// The result of coin selection.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CoinSelection {
+    pub selected: Vec<Utxo>,
+    pub fee: u64,
+    /// Change amount, or `None` if the selection needs no change output.
+    pub change: Option<u64>,
+}
+
+impl CoinSelection {
+    /// Gets the total value of the selected inputs.
+    pub fn input_value(&self) -> u64 {
+        self.selected.iter().map(|u| u.txout.value).sum()
+    }
+
+    /// Gets the outpoints of the selected inputs.
+    pub fn outpoints(&self) -> Vec<OutPoint> {
+        self.selected.iter().map(|u| u.outpoint).collect()
+    }
+}
+
+/// Selects inputs covering the target, preferring a changeless branch-and-bound match.
+pub fn select_coins(
+    utxos: &[Utxo],
+    params: &SelectionParams,
+) -> Result<CoinSelection, CoinSelectionError> {
+    if params.target == 0 {
+        return Err(CoinSelectionError::InvalidParameters(
+            "Target must be positive".to_string(),
+        ));
+    }
+    branch_and_bound(utxos, params).map_or_else(|| largest_first(utxos, params), Ok)
+}
+
+fn fee(params: &SelectionParams, vsize: u64) -> u64 {
+    fee_for_vsize(params.fee_rate, vsize).unwrap_or(u64::MAX)
+}
+
+/// Searches for an input set whose effective value lands within the cost of a change output
+/// above the target, so the transaction can skip change entirely.
+pub fn branch_and_bound(utxos: &[Utxo], params: &SelectionParams) -> Option<CoinSelection> {
+    let target = params.target.checked_add(fee(params, params.base_vsize))? as i64;
+    let cost_of_change =
+        fee(params, params.change_vsize).saturating_add(fee(params, params.change_spend_vsize)) as i64;
+
+    let mut pool: Vec<(i64, &Utxo)> = utxos
+        .iter()
+        .map(|u| (u.effective_value(params.fee_rate), u))
+        .filter(|(value, _)| *value > 0)
+        .collect();
+    pool.sort_by_key(|(value, _)| Reverse(*value));
+
+    let mut remaining: i64 = pool.iter().map(|(value, _)| value).sum();
+    if remaining < target {
+        return None;
+    }
+
+    let mut selected = vec![false; pool.len()];
+    let mut best: Option<(i64, Vec<bool>)> = None;
+    let mut value = 0i64;
+    let mut depth = 0usize;
+    let mut tries = 0usize;
+
+    loop {
+        tries += 1;
+        let backtrack = if value > target + cost_of_change || value + remaining < target {
+            true
+        } else if value >= target {
+            let excess = value - target;
+            if best.as_ref().is_none_or(|(b, _)| excess < *b) {
+                best = Some((excess, selected.clone()));
+            }
+            true
+        } else {
+            depth >= pool.len()
+        };
+
+        if tries >= BNB_MAX_TRIES {
+            break;
+        }
+
+        if backtrack {
+            // Walk back to the last included input and try excluding it instead.
+            while depth > 0 && !selected[depth - 1] {
+                depth -= 1;
+                remaining += pool[depth].0;
+            }
+            if depth == 0 {
+                break;
+            }
+            depth -= 1;
+            selected[depth] = false;
+            value -= pool[depth].0;
+            depth += 1;
+        } else {
+            remaining -= pool[depth].0;
+            selected[depth] = true;
+            value += pool[depth].0;
+            depth += 1;
+        }
+    }
+
+    let (_, chosen) = best?;
+    let selected: Vec<Utxo> = pool
+        .iter()
+        .zip(chosen)
+        .filter(|(_, keep)| *keep)
+        .map(|((_, utxo), _)| (*utxo).clone())
+        .collect();
+    let input_value: u64 = selected.iter().map(|u| u.txout.value).sum();
+    Some(CoinSelection {
+        fee: input_value - params.target,
+        selected,
+        change: None,
+    })
+}
+
+/// Accumulates the largest inputs until the target plus fees and change are covered.
+pub fn largest_first(
+    utxos: &[Utxo],
+    params: &SelectionParams,
+) -> Result<CoinSelection, CoinSelectionError> {
+    let mut pool: Vec<&Utxo> = utxos.iter().collect();
+    pool.sort_by_key(|u| Reverse(u.txout.value));
+
+    let mut selected = Vec::new();
+    let mut input_value = 0u64;
+    let mut vsize = params.base_vsize;
+    for utxo in pool {
+        selected.push(utxo.clone());
+        input_value += utxo.txout.value;
+        vsize += utxo.input_vsize;
+
+        let fee_without_change = fee(params, vsize);
+        let Some(excess) = input_value.checked_sub(params.target.saturating_add(fee_without_change))
+        else {
+            continue;
+        };
+
+        let fee_with_change = fee(params, vsize + params.change_vsize);
+        let change = input_value.saturating_sub(params.target.saturating_add(fee_with_change));
+        return Ok(if change >= params.dust_limit {
+            CoinSelection {
+                selected,
+                fee: fee_with_change,
+                change: Some(change),
+            }
+        } else {
+            CoinSelection {
+                selected,
+                fee: fee_without_change + excess,
+                change: None,
+            }
+        });
+    }
+
+    Err(CoinSelectionError::InsufficientFunds {
+        needed: params.target.saturating_add(fee(params, vsize)),
+        available: input_value,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::hashes::Hash;
+    use bitcoin::{ScriptBuf, Txid};
+
+    fn utxo(vout: u32, value: u64) -> Utxo {
+        Utxo {
+            outpoint: OutPoint::new(Txid::all_zeros(), vout),
+            txout: TxOut {
+                value,
+                script_pubkey: ScriptBuf::new(),
+            },
+            confirmations: 6,
+            input_vsize: 68,
+        }
+    }
+
+    fn params(target: u64) -> SelectionParams {
+        SelectionParams {
+            target,
+            fee_rate: FeeRate::from_sat_per_vb_unchecked(1),
+            base_vsize: 42,
+            change_vsize: 31,
+            change_spend_vsize: 68,
+            dust_limit: 546,
+        }
+    }
+
+    #[test]
+    fn test_branch_and_bound_finds_changeless_match() {
+        let utxos = vec![utxo(0, 100_000), utxo(1, 30_068), utxo(2, 20_068), utxo(3, 7_000)];
+        // 30_068 + 20_068 - 2 * 68 = 50_000, plus 42 vbytes of base fee.
+        let selection = select_coins(&utxos, &params(49_958)).unwrap();
+
+        assert_eq!(selection.change, None);
+        assert_eq!(selection.selected.len(), 2);
+        assert_eq!(selection.fee, 50_136 - 49_958);
+    }
+
+    #[test]
+    fn test_fallback_creates_change_and_avoids_dust() {
+        let utxos = vec![utxo(0, 100_000), utxo(1, 5_000)];
+        let selection = select_coins(&utxos, &params(60_000)).unwrap();
+        assert_eq!(selection.selected.len(), 1);
+        assert_eq!(selection.change, Some(100_000 - 60_000 - (42 + 68 + 31)));
+
+        // Leftover below the dust limit goes to fees.
+        let selection = largest_first(&utxos, &params(99_700)).unwrap();
+        assert_eq!(selection.change, None);
+        assert_eq!(selection.fee, 300);
+
+        assert!(matches!(
+            select_coins(&utxos, &params(200_000)),
+            Err(CoinSelectionError::InsufficientFunds { .. })
+        ));
+    }
+
+    #[test]
+    fn test_utxo_set_tracking() {
+        let mut set = UtxoSet::new();
+        set.insert(utxo(0, 10_000));
+        set.insert(utxo(1, 20_000));
+        let mut unconfirmed = utxo(2, 30_000);
+        unconfirmed.confirmations = 0;
+        set.insert(unconfirmed);
+
+        assert_eq!(set.balance(), 60_000);
+        assert_eq!(set.available(1).len(), 2);
+
+        let first = set.get(&OutPoint::new(Txid::all_zeros(), 0)).unwrap().outpoint;
+        set.reserve(&[first]);
+        assert_eq!(set.available(1).len(), 1);
+        set.release(&[first]);
+        assert_eq!(set.available(0).len(), 3);
+
+        set.remove(&first);
+        assert_eq!(set.len(), 2);
+    }
+}
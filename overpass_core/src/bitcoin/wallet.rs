@@ -41,6 +41,9 @@ pub enum WalletError {
     #[error("Key format error: {0}")]
     KeyFormatError(String),
 
+    #[error("Key material was wiped")]
+    KeysWiped,
+
     #[error("Address error: {0}")]
     AddressError(#[from] bitcoin::address::Error),
 
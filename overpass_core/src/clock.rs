@@ -0,0 +1,118 @@
+// ./src/clock.rs
+
+//! The source of wall-clock time for proofs, storage and channels, and the tolerance
+//! for timestamps that disagree with it.
+
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A source of Unix time in seconds.
+pub trait Clock: Send + Sync + fmt::Debug {
+    fn now(&self) -> u64;
+}
+
+/// A clock shared between the components that stamp and check times.
+pub type SharedClock = Arc<dyn Clock>;
+
+/// The system clock.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Time went backwards")
+            .as_secs()
+    }
+}
+
+/// Gets the system clock as a shared clock.
+pub fn system() -> SharedClock {
+    Arc::new(SystemClock)
+}
+
+/// A clock that only moves when told to, for deterministic tests and simulations.
+#[derive(Debug, Default)]
+pub struct ManualClock(AtomicU64);
+
+impl ManualClock {
+    pub fn new(now: u64) -> Self {
+        Self(AtomicU64::new(now))
+    }
+
+    pub fn set(&self, now: u64) {
+        self.0.store(now, Ordering::SeqCst);
+    }
+
+    pub fn advance(&self, secs: u64) {
+        self.0.fetch_add(secs, Ordering::SeqCst);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> u64 {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// How far a timestamp from elsewhere, e.g. on a counterparty's proof, may be from the
+/// local clock.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimePolicy {
+    /// Oldest a timestamp may be, in seconds.
+    pub max_age: u64,
+    /// How far ahead of the local clock a timestamp may be, in seconds.
+    pub max_skew: u64,
+}
+
+impl Default for TimePolicy {
+    fn default() -> Self {
+        Self {
+            max_age: 3600,
+            max_skew: 300,
+        }
+    }
+}
+
+impl TimePolicy {
+    /// Checks `timestamp` is neither older than `max_age` nor further than `max_skew`
+    /// ahead of `now`.
+    pub fn accepts(&self, timestamp: u64, now: u64) -> bool {
+        match timestamp.checked_sub(now) {
+            Some(ahead) => ahead <= self.max_skew,
+            None => now - timestamp <= self.max_age,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manual_clock_moves_only_when_told() {
+        let clock = ManualClock::new(1_000);
+        assert_eq!(clock.now(), 1_000);
+        clock.advance(60);
+        assert_eq!(clock.now(), 1_060);
+        clock.set(5);
+        assert_eq!(clock.now(), 5);
+    }
+
+    #[test]
+    fn test_policy_bounds_age_and_skew() {
+        let policy = TimePolicy {
+            max_age: 100,
+            max_skew: 10,
+        };
+        assert!(policy.accepts(1_000, 1_000));
+        assert!(policy.accepts(900, 1_000));
+        assert!(!policy.accepts(899, 1_000));
+        assert!(policy.accepts(1_010, 1_000));
+        assert!(!policy.accepts(1_011, 1_000));
+        assert!(policy.accepts(u64::MAX, u64::MAX - 10));
+    }
+}
@@ -0,0 +1,391 @@
+// ./src/config.rs
+
+use crate::bitcoin::anchor_scheduler::AnchorPolicy;
+use crate::bitcoin::fees::StaticFeeEstimator;
+use crate::bitcoin::rbf::RbfPolicy;
+use crate::bitcoin::root_anchor::RootAnchorer;
+use crate::zkp::mobile_optimized_storage::{MobileOptimizedStorage, ProofRetention};
+use crate::zkp::pedersen_parameters::{PedersenParameters, DEFAULT_LABEL};
+use bitcoin::{FeeRate, Network};
+use serde::{Deserialize, Serialize};
+use std::num::NonZero;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Prefix of the environment variables overriding config values. Sections are separated
+/// by a double underscore, as in `OVERPASS_STORAGE__DATA_DIR`.
+pub const ENV_PREFIX: &str = "OVERPASS_";
+
+const SECTIONS: &[&str] = &["network", "storage", "cache", "proofs", "fees", "epochs"];
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("Failed to read {path}: {source}")]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("Invalid config: {0}")]
+    Parse(#[from] toml::de::Error),
+    #[error("Invalid value for {name}: {reason}")]
+    Env { name: String, reason: String },
+    #[error("Invalid config: {0}")]
+    Invalid(String),
+}
+
+/// Settings of a wallet or daemon, with defaults for everything left out.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OverpassConfig {
+    pub network: Network,
+    pub storage: StorageConfig,
+    pub cache: CacheConfig,
+    pub proofs: ProofConfig,
+    pub fees: FeeConfig,
+    pub epochs: EpochConfig,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    /// Directory holding the wallet's databases.
+    pub data_dir: PathBuf,
+    /// Transactions a channel accumulates before its recent ones are compressed.
+    pub compression_threshold: usize,
+    /// Seconds of inactivity after which a channel's history is pruned.
+    pub retention_secs: u64,
+    /// Transactions kept across all histories before new ones are refused.
+    pub max_transactions: Option<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    pub active_channels: usize,
+    pub recent_transactions: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProofConfig {
+    /// Label the Pedersen generators are derived from; counterparties must agree on it.
+    pub label: String,
+    pub retention: ProofRetention,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeeConfig {
+    /// Confirmation target of anchors and fee bumps.
+    pub target_blocks: u16,
+    /// Rate paid when no estimator answers, in sat/vB.
+    pub fallback_sat_per_vb: u64,
+    /// Highest rate a fee bump may pay, in sat/vB.
+    pub max_sat_per_vb: u64,
+    pub bump_after_secs: u64,
+    pub max_bumps: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EpochConfig {
+    /// Seconds between anchors of a changed global root.
+    pub anchor_interval_secs: u64,
+    /// Sealed epochs after which the latest root is anchored.
+    pub anchor_every_epochs: Option<u64>,
+    /// Blocks after which the latest root is anchored.
+    pub anchor_every_blocks: Option<u32>,
+}
+
+impl Default for OverpassConfig {
+    fn default() -> Self {
+        Self {
+            network: Network::Regtest,
+            storage: StorageConfig::default(),
+            cache: CacheConfig::default(),
+            proofs: ProofConfig::default(),
+            fees: FeeConfig::default(),
+            epochs: EpochConfig::default(),
+        }
+    }
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            data_dir: PathBuf::from("overpass-data"),
+            compression_threshold: 100,
+            retention_secs: 30 * 24 * 3600,
+            max_transactions: None,
+        }
+    }
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            active_channels: 5,
+            recent_transactions: 100,
+        }
+    }
+}
+
+impl Default for ProofConfig {
+    fn default() -> Self {
+        Self {
+            label: String::from_utf8_lossy(DEFAULT_LABEL).into_owned(),
+            retention: ProofRetention::None,
+        }
+    }
+}
+
+impl Default for FeeConfig {
+    fn default() -> Self {
+        let rbf = RbfPolicy::default();
+        Self {
+            target_blocks: 6,
+            fallback_sat_per_vb: 10,
+            max_sat_per_vb: rbf.max_fee_rate.to_sat_per_vb_floor(),
+            bump_after_secs: rbf.bump_after_secs,
+            max_bumps: rbf.max_bumps,
+        }
+    }
+}
+
+impl Default for EpochConfig {
+    fn default() -> Self {
+        Self {
+            anchor_interval_secs: 3_600,
+            anchor_every_epochs: Some(1),
+            anchor_every_blocks: None,
+        }
+    }
+}
+
+impl OverpassConfig {
+    /// Reads the config at `path`, or the defaults if there is none, then applies the
+    /// `OVERPASS_` environment variables.
+    pub fn load(path: Option<&Path>) -> Result<Self, ConfigError> {
+        let text = match path {
+            Some(path) => std::fs::read_to_string(path).map_err(|source| ConfigError::Read {
+                path: path.to_path_buf(),
+                source,
+            })?,
+            None => String::new(),
+        };
+        Self::from_sources(&text, std::env::vars())
+    }
+
+    /// Parses and validates a TOML config.
+    pub fn parse(text: &str) -> Result<Self, ConfigError> {
+        Self::from_sources(text, std::iter::empty())
+    }
+
+    /// Parses a TOML config with `vars` overriding its values. Variables outside the
+    /// config's sections are ignored, so other `OVERPASS_` settings can share the prefix.
+    pub fn from_sources(
+        text: &str,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self, ConfigError> {
+        let mut table: toml::Table = toml::from_str(text)?;
+        for (name, value) in vars {
+            let Some(key) = name.strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+            let path: Vec<String> = key.split("__").map(str::to_lowercase).collect();
+            if !SECTIONS.contains(&path[0].as_str()) {
+                continue;
+            }
+            set(&mut table, &path, env_value(&value)).map_err(|reason| ConfigError::Env {
+                name: name.clone(),
+                reason,
+            })?;
+        }
+        let config: Self = toml::Value::Table(table).try_into()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Checks the values serde cannot, such as sizes that must not be zero.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |reason: &str| Err(ConfigError::Invalid(reason.into()));
+        if self.storage.compression_threshold == 0 {
+            return invalid("storage.compression_threshold must be at least 1");
+        }
+        if self.storage.max_transactions == Some(0) {
+            return invalid("storage.max_transactions must be at least 1");
+        }
+        if self.cache.active_channels == 0 || self.cache.recent_transactions == 0 {
+            return invalid("cache sizes must be at least 1");
+        }
+        if self.proofs.label.is_empty() {
+            return invalid("proofs.label must not be empty");
+        }
+        if self.fees.target_blocks == 0 {
+            return invalid("fees.target_blocks must be at least 1");
+        }
+        if self.fees.fallback_sat_per_vb == 0
+            || self.fees.max_sat_per_vb < self.fees.fallback_sat_per_vb
+        {
+            return invalid("fees.max_sat_per_vb must be at least fees.fallback_sat_per_vb");
+        }
+        if self.epochs.anchor_every_epochs.is_none() && self.epochs.anchor_every_blocks.is_none() {
+            return invalid("epochs must set anchor_every_epochs or anchor_every_blocks");
+        }
+        Ok(())
+    }
+
+    /// Builds the wallet's storage with the configured caches, compression and quota.
+    pub fn storage(&self) -> MobileOptimizedStorage {
+        let storage = MobileOptimizedStorage::new(
+            self.storage.compression_threshold,
+            self.storage.retention_secs,
+        )
+        .with_cache_sizes(
+            NonZero::new(self.cache.active_channels).unwrap_or(NonZero::<usize>::MIN),
+            NonZero::new(self.cache.recent_transactions).unwrap_or(NonZero::<usize>::MIN),
+        )
+        .with_proof_retention(self.proofs.retention);
+        match self.storage.max_transactions {
+            Some(max_transactions) => storage.with_transaction_quota(max_transactions),
+            None => storage,
+        }
+    }
+
+    pub fn pedersen_parameters(&self) -> PedersenParameters {
+        PedersenParameters::from_label(self.proofs.label.as_bytes())
+    }
+
+    pub fn fallback_fee_estimator(&self) -> StaticFeeEstimator {
+        StaticFeeEstimator::new(FeeRate::from_sat_per_vb_unchecked(
+            self.fees.fallback_sat_per_vb,
+        ))
+    }
+
+    pub fn rbf_policy(&self) -> RbfPolicy {
+        RbfPolicy {
+            bump_after_secs: self.fees.bump_after_secs,
+            target_blocks: self.fees.target_blocks,
+            max_fee_rate: FeeRate::from_sat_per_vb_unchecked(self.fees.max_sat_per_vb),
+            max_bumps: self.fees.max_bumps,
+            ..RbfPolicy::default()
+        }
+    }
+
+    pub fn anchor_policy(&self) -> AnchorPolicy {
+        AnchorPolicy {
+            every_epochs: self.epochs.anchor_every_epochs,
+            every_blocks: self.epochs.anchor_every_blocks,
+            pending_value: None,
+        }
+    }
+
+    pub fn root_anchorer(&self) -> RootAnchorer {
+        RootAnchorer::new(self.epochs.anchor_interval_secs, self.fees.target_blocks)
+    }
+}
+
+/// Reads an environment value as TOML, so numbers and booleans keep their type, falling
+/// back to a plain string.
+fn env_value(value: &str) -> toml::Value {
+    toml::from_str::<toml::Table>(&format!("value = {}", value))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(value.to_string()))
+}
+
+fn set(table: &mut toml::Table, path: &[String], value: toml::Value) -> Result<(), String> {
+    let (key, parents) = path.split_last().expect("paths have a key");
+    let mut table = table;
+    for parent in parents {
+        table = table
+            .entry(parent.clone())
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+            .as_table_mut()
+            .ok_or_else(|| format!("{} is not a section", parent))?;
+    }
+    table.insert(key.clone(), value);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zkp::mobile_optimized_storage::StorageError;
+    use crate::zkp::state_proof::StateProof;
+    use crate::zkp::tx_metadata::TxMetadata;
+
+    fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_toml_and_env_override_defaults() {
+        assert_eq!(
+            OverpassConfig::parse("").unwrap(),
+            OverpassConfig::default()
+        );
+
+        let text = "network = \"testnet\"\n\
+                    [storage]\ndata_dir = \"/var/lib/overpass\"\nmax_transactions = 500\n\
+                    [proofs]\nretention = \"digest\"\n";
+        let config = OverpassConfig::from_sources(
+            text,
+            vars(&[
+                ("OVERPASS_STORAGE__DATA_DIR", "/srv/overpass"),
+                ("OVERPASS_FEES__TARGET_BLOCKS", "3"),
+                ("OVERPASS_PASSPHRASE", "not a config value"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(config.network, Network::Testnet);
+        assert_eq!(config.storage.data_dir, PathBuf::from("/srv/overpass"));
+        assert_eq!(config.storage.max_transactions, Some(500));
+        assert_eq!(config.proofs.retention, ProofRetention::Digest);
+        assert_eq!(config.fees.target_blocks, 3);
+        assert_eq!(config.rbf_policy().target_blocks, 3);
+        assert_eq!(config.cache, CacheConfig::default());
+
+        assert!(matches!(
+            OverpassConfig::parse("[storage]\ndata_dri = \"x\"\n"),
+            Err(ConfigError::Parse(_))
+        ));
+        assert!(matches!(
+            OverpassConfig::parse("[cache]\nactive_channels = 0\n"),
+            Err(ConfigError::Invalid(_))
+        ));
+        assert!(matches!(
+            OverpassConfig::from_sources("", vars(&[("OVERPASS_FEES__MAX_BUMPS", "many")])),
+            Err(ConfigError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn test_storage_enforces_quota() {
+        let config = OverpassConfig::parse("[storage]\nmax_transactions = 2\n").unwrap();
+        let mut storage = config.storage();
+        let proof = StateProof {
+            pi: [0u8; 32],
+            public_inputs: Vec::new(),
+            timestamp: 1,
+        };
+        for nonce in 0..2u8 {
+            storage
+                .store_transaction(
+                    [1u8; 32],
+                    [nonce; 32],
+                    [nonce + 1; 32],
+                    proof.clone(),
+                    TxMetadata::None,
+                )
+                .unwrap();
+        }
+        assert!(matches!(
+            storage.store_transaction([2u8; 32], [0u8; 32], [1u8; 32], proof, TxMetadata::None),
+            Err(StorageError::StorageLimitExceeded)
+        ));
+    }
+}
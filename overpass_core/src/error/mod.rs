@@ -1,4 +1,6 @@
 // ./src/common/error/mod.rs
 
 pub mod client_errors;
+#[cfg(feature = "prover")]
+pub mod overpass_error;
 
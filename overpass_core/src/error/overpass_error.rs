@@ -0,0 +1,300 @@
+// src/error/overpass_error.rs
+
+use thiserror::Error;
+
+use crate::bitcoin::addresses::AddressError;
+use crate::bitcoin::anchor_scheduler::AnchorSchedulerError;
+use crate::bitcoin::anchors::AnchorError;
+use crate::bitcoin::batch::BatchError;
+#[cfg(feature = "bitcoin-backend")]
+use crate::bitcoin::bitcoin_transaction::BitcoinClientError;
+use crate::bitcoin::bitcoin_types::BitcoinStateError;
+use crate::bitcoin::broadcast::BroadcastError;
+use crate::bitcoin::chain::ChainError;
+use crate::bitcoin::commitment::CommitmentError;
+use crate::bitcoin::descriptors::DescriptorError;
+use crate::bitcoin::dust::DustError;
+#[cfg(feature = "bitcoin-backend")]
+use crate::bitcoin::esplora::EsploraError;
+use crate::bitcoin::fees::FeeEstimationError;
+use crate::bitcoin::funding::FundingError;
+use crate::bitcoin::monitor::StateNumberError;
+use crate::bitcoin::musig2::MusigError;
+use crate::bitcoin::rbf::RbfError;
+use crate::bitcoin::root_anchor::RootAnchorError;
+#[cfg(feature = "bitcoin-backend")]
+use crate::bitcoin::rpc_client::RpcError;
+use crate::bitcoin::signer::SignerError;
+use crate::bitcoin::spv::SpvError;
+use crate::bitcoin::sweep::SweepError;
+use crate::bitcoin::taproot::TaprootError;
+use crate::bitcoin::timelocks::TimelockError;
+use crate::bitcoin::utxo::CoinSelectionError;
+use crate::bitcoin::wallet::WalletError;
+use crate::config::ConfigError;
+use crate::error::client_errors;
+#[cfg(feature = "networking")]
+use crate::network::discovery::DiscoveryError;
+#[cfg(feature = "networking")]
+use crate::network::noise::NoiseError;
+#[cfg(feature = "nostr")]
+use crate::network::nostr::NostrError;
+#[cfg(feature = "p2p")]
+use crate::network::p2p::P2pError;
+use crate::network::wire::WireError;
+use crate::services::overpass_db::DbError;
+#[cfg(feature = "networking")]
+use crate::services::server::ServeError;
+use crate::services::storage_migration::MigrationError;
+#[cfg(feature = "networking")]
+use crate::services::watch_service::WatchServiceError;
+use crate::simulation::SimulationError;
+#[cfg(feature = "bitcoin-backend")]
+use crate::zkp::bitcoin_ephemeral_state::EphemeralStateError;
+use crate::zkp::channel::ChannelError;
+use crate::zkp::channel_backup::BackupError;
+use crate::zkp::compressed_transaction::DeltaError;
+use crate::zkp::counterparty_registry::RegistryError;
+use crate::zkp::device_sync::SyncError;
+use crate::zkp::fee_ledger::FeeLedgerError;
+use crate::zkp::global_root_contract::GlobalRootContractError;
+use crate::zkp::governance::GovernanceError;
+use crate::zkp::history_archive::ArchiveError;
+use crate::zkp::history_replay::{self, ReplayError};
+use crate::zkp::invoice::InvoiceError;
+use crate::zkp::mobile_optimized_storage::StorageError;
+use crate::zkp::multisig_wallet::MultisigError;
+use crate::zkp::offer::OfferError;
+use crate::zkp::operator_keys::OperatorKeyError;
+use crate::zkp::pedersen_parameters::PedersenError;
+use crate::zkp::payment_schedule::ScheduleError;
+use crate::zkp::proof_of_funds::FundsProofError;
+use crate::zkp::reconciliation::ReconciliationError;
+use crate::zkp::spending_policy::PolicyError;
+#[cfg(feature = "prover")]
+use crate::zkp::state_transition::TransitionError;
+use crate::zkp::sub_account::AccountError;
+use crate::zkp::tree::MerkleTreeError;
+use crate::zkp::tx_metadata::MetadataError;
+use crate::zkp::wallet_audit::AuditError;
+use crate::zkp::wallet_contract::WalletContractError;
+use crate::zkp::wallet_lock::LockError;
+use crate::zkp::wallet_signer::WalletSignerError;
+
+pub type OverpassResult<T> = Result<T, OverpassError>;
+
+/// Any error the crate returns, grouped by the part of the crate it comes from.
+///
+/// Every module error converts into it with `?`, and the module error stays reachable
+/// through `source`.
+#[derive(Error, Debug)]
+pub enum OverpassError {
+    #[error(transparent)]
+    Bitcoin(#[from] BitcoinError),
+    #[error(transparent)]
+    Zkp(#[from] ZkpError),
+    #[error(transparent)]
+    Service(#[from] ServiceError),
+    #[error(transparent)]
+    Network(#[from] NetworkError),
+    #[error("Client error: {0}")]
+    Client(#[from] client_errors::Error),
+}
+
+/// Declares a family of module errors, with a conversion from each straight into
+/// [`OverpassError`].
+macro_rules! error_family {
+    ($(#[$meta:meta])* $family:ident {
+        $($(#[$cfg:meta])* $variant:ident($error:ty) => $label:literal,)+
+    }) => {
+        $(#[$meta])*
+        #[derive(Error, Debug)]
+        pub enum $family {
+            $(
+                $(#[$cfg])*
+                #[error("{}: {0}", $label)]
+                $variant(#[from] $error),
+            )+
+        }
+
+        $(
+            $(#[$cfg])*
+            impl From<$error> for OverpassError {
+                fn from(error: $error) -> Self {
+                    $family::from(error).into()
+                }
+            }
+        )+
+    };
+}
+
+error_family!(
+    /// Errors from building, signing, broadcasting and watching Bitcoin transactions.
+    BitcoinError {
+        Address(AddressError) => "Address",
+        AnchorScheduler(AnchorSchedulerError) => "Anchor scheduler",
+        Anchor(AnchorError) => "Anchor",
+        Batch(BatchError) => "Batch settlement",
+        #[cfg(feature = "bitcoin-backend")]
+        Client(BitcoinClientError) => "Bitcoin client",
+        State(BitcoinStateError) => "Bitcoin state",
+        Broadcast(BroadcastError) => "Broadcast",
+        Chain(ChainError) => "Chain backend",
+        Commitment(CommitmentError) => "Commitment transaction",
+        Descriptor(DescriptorError) => "Descriptor",
+        Dust(DustError) => "Dust",
+        #[cfg(feature = "bitcoin-backend")]
+        Esplora(EsploraError) => "Esplora",
+        FeeEstimation(FeeEstimationError) => "Fee estimation",
+        Funding(FundingError) => "Funding",
+        Musig(MusigError) => "MuSig2",
+        Rbf(RbfError) => "Fee bump",
+        RootAnchor(RootAnchorError) => "Root anchor",
+        #[cfg(feature = "bitcoin-backend")]
+        Rpc(RpcError) => "RPC",
+        Signer(SignerError) => "Signer",
+        Spv(SpvError) => "SPV",
+        StateNumber(StateNumberError) => "State number",
+        Sweep(SweepError) => "Sweep",
+        Taproot(TaprootError) => "Taproot",
+        Timelock(TimelockError) => "Timelock",
+        CoinSelection(CoinSelectionError) => "Coin selection",
+        Wallet(WalletError) => "Bitcoin wallet",
+        #[cfg(feature = "bitcoin-backend")]
+        EphemeralState(EphemeralStateError) => "Test chain client",
+    }
+);
+
+error_family!(
+    /// Errors from the wallet, its channels and the proofs over them.
+    ZkpError {
+        Wallet(WalletContractError) => "Wallet",
+        GlobalRoot(GlobalRootContractError) => "Global root",
+        Channel(ChannelError) => "Channel state",
+        Pedersen(PedersenError) => "Pedersen commitment",
+        #[cfg(feature = "prover")]
+        Transition(TransitionError) => "State transition",
+        Storage(StorageError) => "Channel storage",
+        Delta(DeltaError) => "Compressed transaction",
+        Archive(ArchiveError) => "History archive",
+        Replay(ReplayError) => "History replay",
+        Metadata(MetadataError) => "Transaction metadata",
+        MerkleTree(MerkleTreeError) => "Merkle tree",
+        Backup(BackupError) => "Channel backup",
+        Registry(RegistryError) => "Counterparty registry",
+        Sync(SyncError) => "Device sync",
+        FeeLedger(FeeLedgerError) => "Fee ledger",
+        Governance(GovernanceError) => "Governance",
+        Invoice(InvoiceError) => "Invoice",
+        Offer(OfferError) => "Offer",
+        Multisig(MultisigError) => "Multisig",
+        OperatorKey(OperatorKeyError) => "Operator keys",
+        Schedule(ScheduleError) => "Payment schedule",
+        FundsProof(FundsProofError) => "Proof of funds",
+        Reconciliation(ReconciliationError) => "Reconciliation",
+        Policy(PolicyError) => "Spending policy",
+        Account(AccountError) => "Sub-account",
+        Audit(AuditError) => "Wallet audit",
+        Lock(LockError) => "Wallet lock",
+        WalletSigner(WalletSignerError) => "Wallet signer",
+        Simulation(SimulationError) => "Simulation",
+    }
+);
+
+error_family!(
+    /// Errors from the database and the services running over it.
+    ServiceError {
+        Database(DbError) => "Database",
+        Migration(MigrationError) => "Storage migration",
+        #[cfg(feature = "networking")]
+        Watch(WatchServiceError) => "Watch service",
+        Config(ConfigError) => "Configuration",
+        #[cfg(feature = "networking")]
+        Serve(ServeError) => "Server",
+    }
+);
+
+error_family!(
+    /// Errors from the wire format and the transports that carry it between peers.
+    NetworkError {
+        Wire(WireError) => "Wire format",
+        #[cfg(feature = "networking")]
+        Noise(NoiseError) => "Noise transport",
+        #[cfg(feature = "networking")]
+        Discovery(DiscoveryError) => "Peer discovery",
+        #[cfg(feature = "nostr")]
+        Nostr(NostrError) => "Nostr transport",
+        #[cfg(feature = "p2p")]
+        P2p(P2pError) => "libp2p transport",
+    }
+);
+
+impl From<history_replay::ChainError> for OverpassError {
+    fn from(error: history_replay::ChainError) -> Self {
+        ReplayError::from(error).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zkp::compressed_transaction::CompressedTransaction;
+    use std::error::Error as _;
+
+    fn decode(
+        bytes: &[u8; CompressedTransaction::ENCODED_LEN],
+    ) -> OverpassResult<CompressedTransaction> {
+        Ok(CompressedTransaction::from_bytes(bytes)?)
+    }
+
+    #[test]
+    fn test_module_errors_convert_with_question_mark() {
+        assert!(matches!(
+            decode(&[9u8; CompressedTransaction::ENCODED_LEN]),
+            Err(OverpassError::Zkp(ZkpError::Delta(
+                DeltaError::UnsupportedVersion(9)
+            )))
+        ));
+        let error = OverpassError::from(DustError::AllDust);
+        assert!(matches!(
+            error,
+            OverpassError::Bitcoin(BitcoinError::Dust(DustError::AllDust))
+        ));
+        assert_eq!(
+            error.to_string(),
+            "Dust: Every settlement output is below its dust limit"
+        );
+        assert!(matches!(
+            OverpassError::from(StateNumberError::NoInputs),
+            OverpassError::Bitcoin(BitcoinError::StateNumber(StateNumberError::NoInputs))
+        ));
+        assert!(matches!(
+            OverpassError::from(WireError::Truncated),
+            OverpassError::Network(NetworkError::Wire(WireError::Truncated))
+        ));
+        let error = OverpassError::from(history_replay::ChainError::InvalidCheckpoint(2));
+        assert!(matches!(
+            error,
+            OverpassError::Zkp(ZkpError::Replay(ReplayError::Chain(_)))
+        ));
+    }
+
+    #[test]
+    fn test_sources_chain_to_the_root_cause() {
+        let error = OverpassError::from(StorageError::Encoding(DeltaError::Truncated));
+        let mut chain = Vec::new();
+        let mut source = error.source();
+        while let Some(cause) = source {
+            chain.push(cause.to_string());
+            source = cause.source();
+        }
+        assert_eq!(
+            chain,
+            vec![
+                StorageError::Encoding(DeltaError::Truncated).to_string(),
+                DeltaError::Truncated.to_string(),
+            ]
+        );
+        assert!(error.to_string().starts_with("Channel storage: "));
+    }
+}
@@ -1,19 +1,35 @@
 // ./core/src/lib.rs
 
+#[cfg(all(
+    feature = "verifier-only",
+    any(feature = "prover", feature = "bitcoin-backend", feature = "networking")
+))]
+compile_error!(
+    "`verifier-only` builds without the prover, chain clients and servers; \
+     disable default features"
+);
 
 pub mod bitcoin;
 
 pub mod rng;
 
+pub mod clock;
+#[cfg(feature = "prover")]
+pub mod config;
 pub mod logging;
 pub mod error;
 pub mod types;
 pub mod network;
 pub mod contracts;
+#[cfg(feature = "networking")]
 pub mod api_ovp;
 pub mod db;
 pub mod models;
 pub mod services;
+#[cfg(feature = "prover")]
+pub mod simulation;
+#[cfg(all(test, feature = "prover"))]
+pub(crate) mod test_util;
 pub mod utils;
 pub mod zkp; // Add this line to expose the ZKP module
 
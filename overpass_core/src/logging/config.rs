@@ -130,9 +130,12 @@ mod tests {
 
     #[test]
     fn test_logger_log() {
-        let config = Config::new();
+        let path = std::env::temp_dir().join(format!("logger_{}", std::process::id()));
+        let mut config = Config::new();
+        config.set_log_file(path.join("log.txt").to_str().unwrap());
         let logger = Logger::new(config);
         logger.log("info", "Test log message");
-        assert!(Path::new("./log/log.txt").exists());
+        assert!(path.join("log.txt").exists());
+        let _ = fs::remove_dir_all(path);
     }
 }
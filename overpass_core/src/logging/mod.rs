@@ -2,3 +2,4 @@
 
 pub mod config;
 pub mod logger;
+pub mod spans;
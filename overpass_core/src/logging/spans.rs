@@ -0,0 +1,112 @@
+// ./src/logging/spans.rs
+
+use tracing::Subscriber;
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::{SubscriberInitExt, TryInitError};
+use tracing_subscriber::{EnvFilter, Layer};
+
+/// Formats events as JSON lines. Every span also logs a line when it closes, its
+/// `time.busy` and `time.idle` fields giving how long the step took.
+pub fn json_layer<S, W>(writer: W) -> impl Layer<S>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    tracing_subscriber::fmt::layer()
+        .json()
+        .with_span_events(FmtSpan::CLOSE)
+        .with_current_span(true)
+        .with_span_list(true)
+        .with_writer(writer)
+}
+
+/// Installs JSON logging to stderr of the store_transaction, prove, verify, broadcast
+/// and anchor spans and of `log` records, filtered by `RUST_LOG` or else `default_filter`.
+pub fn init_tracing(default_filter: &str) -> Result<(), TryInitError> {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(default_filter));
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(json_layer(std::io::stderr))
+        .try_init()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+    use serde_json::Value;
+    use std::io;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Buffer {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(bytes);
+            Ok(bytes.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Runs `f` with JSON logging into a buffer and returns the logged lines.
+    fn capture(f: impl FnOnce()) -> Vec<Value> {
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let subscriber = tracing_subscriber::registry().with(json_layer(move || writer.clone()));
+        tracing::subscriber::with_default(subscriber, f);
+        let text = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        text.lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    /// Finds the line logged when the span called `name` closed.
+    fn closed<'a>(lines: &'a [Value], name: &str) -> Option<&'a Value> {
+        lines
+            .iter()
+            .find(|line| line["fields"]["message"] == "close" && line["span"]["name"] == name)
+    }
+
+    #[test]
+    fn test_channel_update_logs_timed_spans() {
+        let mut wallet = test_util::wallet([1u8; 32]);
+        let channel_id = wallet.open_channel(100, [2u8; 32], Vec::new()).unwrap();
+
+        let lines = capture(|| {
+            wallet.pay([2u8; 32], 40).send().unwrap();
+        });
+
+        for name in ["prove", "store_transaction"] {
+            let line = closed(&lines, name).unwrap_or_else(|| panic!("no {} span", name));
+            assert_eq!(line["span"]["channel_id"], hex::encode(channel_id));
+            assert!(line["fields"]["time.busy"].is_string());
+        }
+    }
+
+    #[test]
+    fn test_failed_step_logs_its_error() {
+        let mut wallet = test_util::wallet([1u8; 32]);
+        let channel_id = wallet.open_channel(100, [2u8; 32], Vec::new()).unwrap();
+        let backup = wallet.channel_backup(&channel_id).unwrap();
+
+        let lines = capture(|| {
+            assert!(backup.verify(&[9u8; 32], &[]).is_err());
+        });
+
+        let error = lines
+            .iter()
+            .find(|line| line["level"] == "ERROR")
+            .expect("an error event");
+        assert_eq!(error["span"]["name"], "verify");
+        assert_eq!(error["span"]["proof"], "backup");
+        assert_eq!(error["span"]["channel_id"], hex::encode(channel_id));
+    }
+}
@@ -77,7 +77,7 @@ impl BitcoinRegtest {
         self.client.cache_state(state_hash, state_data).await
     }
 
-    pub async fn get_cached_state(&self, state_hash: [u8; 32]) -> Option<Vec<u8>> {
+    pub async fn get_cached_state(&self, state_hash: [u8; 32]) -> Result<Option<Vec<u8>>, String> {
         self.client.get_cached_state(state_hash).await
     }
 }
\ No newline at end of file
@@ -0,0 +1,409 @@
+// ./src/network/discovery.rs
+
+use crate::services::server::{bind, ServeError};
+use crate::zkp::operator_keys::message_from;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::{get, post},
+    Json, Router,
+};
+use bitcoin::secp256k1::{schnorr, KeyPair, Message, Secp256k1, XOnlyPublicKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use thiserror::Error;
+
+/// Most addresses a peer record may list.
+const MAX_ADDRESSES: usize = 16;
+/// Longest address a peer record may list.
+const MAX_ADDRESS_LEN: usize = 255;
+
+#[derive(Error, Debug)]
+pub enum DiscoveryError {
+    #[error("Peer record signature is invalid")]
+    BadSignature,
+    #[error("Peer record is not newer than the one already held")]
+    Stale,
+    #[error("Peer record is malformed: {0}")]
+    Malformed(&'static str),
+    #[error("Lookup failed: {0}")]
+    Lookup(String),
+    #[error("No endpoint is known for {0}")]
+    NotFound(XOnlyPublicKey),
+}
+
+/// Where a peer can currently be reached, signed by its transport key so whoever passes
+/// the record along cannot redirect its connections.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerRecord {
+    pub key: XOnlyPublicKey,
+    /// Raised with every new record, e.g. to the time of signing, so an old record
+    /// cannot replace a newer one.
+    pub sequence: u64,
+    pub addresses: Vec<String>,
+    pub signature: schnorr::Signature,
+}
+
+fn record_message(
+    key: &XOnlyPublicKey,
+    sequence: u64,
+    addresses: &[String],
+) -> Result<Message, DiscoveryError> {
+    if addresses.len() > MAX_ADDRESSES {
+        return Err(DiscoveryError::Malformed("too many addresses"));
+    }
+    let mut hasher = Sha256::new();
+    hasher.update(b"overpass/peer-record/v1");
+    hasher.update(key.serialize());
+    hasher.update(sequence.to_be_bytes());
+    hasher.update([addresses.len() as u8]);
+    for address in addresses {
+        if address.len() > MAX_ADDRESS_LEN {
+            return Err(DiscoveryError::Malformed("address too long"));
+        }
+        hasher.update([address.len() as u8]);
+        hasher.update(address.as_bytes());
+    }
+    Ok(message_from(hasher))
+}
+
+impl PeerRecord {
+    pub fn sign(
+        key: &KeyPair,
+        sequence: u64,
+        addresses: Vec<String>,
+    ) -> Result<Self, DiscoveryError> {
+        let public = key.x_only_public_key().0;
+        let message = record_message(&public, sequence, &addresses)?;
+        Ok(Self {
+            key: public,
+            sequence,
+            addresses,
+            signature: Secp256k1::new().sign_schnorr(&message, key),
+        })
+    }
+
+    pub fn verify(&self) -> bool {
+        let Ok(message) = record_message(&self.key, self.sequence, &self.addresses) else {
+            return false;
+        };
+        Secp256k1::verification_only()
+            .verify_schnorr(&self.signature, &message, &self.key)
+            .is_ok()
+    }
+}
+
+/// A source of peer endpoints.
+pub trait Resolver {
+    /// Gets the addresses `key` can be reached at; empty if this source does not know it.
+    fn resolve(&self, key: &XOnlyPublicKey) -> Result<Vec<String>, DiscoveryError>;
+}
+
+/// Endpoints fixed in configuration.
+#[derive(Clone, Debug, Default)]
+pub struct StaticPeers {
+    peers: HashMap<XOnlyPublicKey, Vec<String>>,
+}
+
+impl StaticPeers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_peer(mut self, key: XOnlyPublicKey, address: impl Into<String>) -> Self {
+        self.peers.entry(key).or_default().push(address.into());
+        self
+    }
+}
+
+impl Resolver for StaticPeers {
+    fn resolve(&self, key: &XOnlyPublicKey) -> Result<Vec<String>, DiscoveryError> {
+        Ok(self.peers.get(key).cloned().unwrap_or_default())
+    }
+}
+
+/// A DNS seed answering for `<key>.<domain>` with the A and AAAA records of the peer.
+/// The hex key is split over two labels, as one label holds at most 63 characters.
+#[derive(Clone, Debug)]
+pub struct DnsSeed {
+    domain: String,
+    port: u16,
+}
+
+impl DnsSeed {
+    /// Seeds return bare addresses, so peers found through one listen on `port`.
+    pub fn new(domain: impl Into<String>, port: u16) -> Self {
+        Self {
+            domain: domain.into(),
+            port,
+        }
+    }
+
+    pub fn host_for(&self, key: &XOnlyPublicKey) -> String {
+        let key = hex::encode(key.serialize());
+        format!("{}.{}.{}", &key[..32], &key[32..], self.domain)
+    }
+}
+
+impl Resolver for DnsSeed {
+    fn resolve(&self, key: &XOnlyPublicKey) -> Result<Vec<String>, DiscoveryError> {
+        match (self.host_for(key).as_str(), self.port).to_socket_addrs() {
+            Ok(addresses) => Ok(addresses.map(|address| address.to_string()).collect()),
+            // NXDOMAIN looks the same as any other failure here, so treat both as unknown.
+            Err(_) => Ok(Vec::new()),
+        }
+    }
+}
+
+/// Client of a rendezvous server, where peers publish their signed records.
+pub struct RendezvousClient {
+    url: String,
+    agent: ureq::Agent,
+}
+
+impl RendezvousClient {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into().trim_end_matches('/').to_string(),
+            agent: ureq::AgentBuilder::new()
+                .timeout(Duration::from_secs(10))
+                .build(),
+        }
+    }
+
+    pub fn publish(&self, record: &PeerRecord) -> Result<(), DiscoveryError> {
+        self.agent
+            .post(&format!("{}/peers", self.url))
+            .send_json(record)
+            .map_err(|e| DiscoveryError::Lookup(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Fetches the record published for `key`, checking it was signed by that key.
+    pub fn lookup(&self, key: &XOnlyPublicKey) -> Result<Option<PeerRecord>, DiscoveryError> {
+        let url = format!("{}/peers/{}", self.url, hex::encode(key.serialize()));
+        let record: PeerRecord = match self.agent.get(&url).call() {
+            Ok(response) => response
+                .into_json()
+                .map_err(|e| DiscoveryError::Lookup(e.to_string()))?,
+            Err(ureq::Error::Status(404, _)) => return Ok(None),
+            Err(e) => return Err(DiscoveryError::Lookup(e.to_string())),
+        };
+        if record.key != *key || !record.verify() {
+            return Err(DiscoveryError::BadSignature);
+        }
+        Ok(Some(record))
+    }
+}
+
+impl Resolver for RendezvousClient {
+    fn resolve(&self, key: &XOnlyPublicKey) -> Result<Vec<String>, DiscoveryError> {
+        Ok(self
+            .lookup(key)?
+            .map(|record| record.addresses)
+            .unwrap_or_default())
+    }
+}
+
+/// Asks each resolver in turn for a peer's endpoints.
+#[derive(Default)]
+pub struct Discovery {
+    resolvers: Vec<Box<dyn Resolver + Send + Sync>>,
+}
+
+impl Discovery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, resolver: impl Resolver + Send + Sync + 'static) -> Self {
+        self.resolvers.push(Box::new(resolver));
+        self
+    }
+
+    /// Gets the endpoints of the first resolver that knows `key`. A failing resolver is
+    /// passed over, and its error returned only if no other knows the key either.
+    pub fn locate(&self, key: &XOnlyPublicKey) -> Result<Vec<String>, DiscoveryError> {
+        let mut failure = None;
+        for resolver in &self.resolvers {
+            match resolver.resolve(key) {
+                Ok(addresses) if !addresses.is_empty() => return Ok(addresses),
+                Ok(_) => {}
+                Err(e) => failure = Some(e),
+            }
+        }
+        Err(failure.unwrap_or(DiscoveryError::NotFound(*key)))
+    }
+}
+
+/// The latest record of every peer that registered with a rendezvous server.
+#[derive(Debug, Default)]
+pub struct RendezvousStore {
+    records: HashMap<XOnlyPublicKey, PeerRecord>,
+}
+
+impl RendezvousStore {
+    pub fn register(&mut self, record: PeerRecord) -> Result<(), DiscoveryError> {
+        if !record.verify() {
+            return Err(DiscoveryError::BadSignature);
+        }
+        if let Some(held) = self.records.get(&record.key) {
+            if held.sequence >= record.sequence {
+                return Err(DiscoveryError::Stale);
+            }
+        }
+        self.records.insert(record.key, record);
+        Ok(())
+    }
+
+    pub fn lookup(&self, key: &XOnlyPublicKey) -> Option<&PeerRecord> {
+        self.records.get(key)
+    }
+}
+
+type SharedStore = Arc<Mutex<RendezvousStore>>;
+
+async fn register_handler(
+    State(store): State<SharedStore>,
+    Json(record): Json<PeerRecord>,
+) -> axum::response::Response {
+    let registered = store
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .register(record);
+    match registered {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(DiscoveryError::Stale) => (StatusCode::CONFLICT, "stale record").into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    }
+}
+
+async fn lookup_handler(
+    State(store): State<SharedStore>,
+    Path(key): Path<String>,
+) -> axum::response::Response {
+    let Ok(key) = XOnlyPublicKey::from_str(&key) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            "key must be a hex x-only public key",
+        )
+            .into_response();
+    };
+    let store = store
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    match store.lookup(&key) {
+        Some(record) => Json(record).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// Builds the rendezvous API: records are published by POST to `/peers` and fetched
+/// from `/peers/<hex key>`.
+pub fn router(store: SharedStore) -> Router {
+    Router::new()
+        .route("/peers", post(register_handler))
+        .route("/peers/:key", get(lookup_handler))
+        .with_state(store)
+}
+
+/// Serves a rendezvous server on `addr` until the listener fails.
+pub async fn serve(store: SharedStore, addr: SocketAddr) -> Result<(), ServeError> {
+    let listener = bind(addr).await?;
+    axum::serve(listener, router(store)).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+
+    fn transport_key() -> KeyPair {
+        test_util::key_manager().transport_key().unwrap()
+    }
+
+    struct Failing;
+
+    impl Resolver for Failing {
+        fn resolve(&self, _: &XOnlyPublicKey) -> Result<Vec<String>, DiscoveryError> {
+            Err(DiscoveryError::Lookup("unreachable".into()))
+        }
+    }
+
+    #[test]
+    fn test_records_and_resolver_order() {
+        let key = transport_key();
+        let public = key.x_only_public_key().0;
+        let record = PeerRecord::sign(&key, 1, vec!["/ip4/10.0.0.1/tcp/9735".into()]).unwrap();
+        assert!(record.verify());
+        let mut forged = record.clone();
+        forged.addresses = vec!["/ip4/6.6.6.6/tcp/9735".into()];
+        assert!(!forged.verify());
+
+        let mut store = RendezvousStore::default();
+        assert!(matches!(
+            store.register(forged),
+            Err(DiscoveryError::BadSignature)
+        ));
+        store.register(record.clone()).unwrap();
+        assert!(matches!(store.register(record), Err(DiscoveryError::Stale)));
+        let moved = PeerRecord::sign(&key, 2, vec!["/ip4/10.0.0.2/tcp/9735".into()]).unwrap();
+        store.register(moved.clone()).unwrap();
+        assert_eq!(store.lookup(&public), Some(&moved));
+
+        let discovery = Discovery::new()
+            .with(Failing)
+            .with(StaticPeers::new())
+            .with(StaticPeers::new().with_peer(public, "127.0.0.1:9735"));
+        assert_eq!(discovery.locate(&public).unwrap(), vec!["127.0.0.1:9735"]);
+        let unknown = Discovery::new().with(Failing).with(StaticPeers::new());
+        assert!(matches!(
+            unknown.locate(&public),
+            Err(DiscoveryError::Lookup(_))
+        ));
+        assert!(matches!(
+            Discovery::new().locate(&public),
+            Err(DiscoveryError::NotFound(_))
+        ));
+
+        let host = DnsSeed::new("seed.example", 9735).host_for(&public);
+        assert!(host.split('.').all(|label| label.len() <= 63));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_rendezvous_over_http() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            axum::serve(listener, router(SharedStore::default()))
+                .await
+                .unwrap()
+        });
+
+        tokio::task::spawn_blocking(move || {
+            let key = transport_key();
+            let public = key.x_only_public_key().0;
+            let client = RendezvousClient::new(url);
+            assert_eq!(client.lookup(&public).unwrap(), None);
+
+            let record =
+                PeerRecord::sign(&key, 7, vec!["/ip4/10.0.0.3/udp/9735/quic-v1".into()]).unwrap();
+            client.publish(&record).unwrap();
+            assert!(client.publish(&record).is_err());
+            assert_eq!(client.lookup(&public).unwrap(), Some(record.clone()));
+            assert_eq!(
+                Discovery::new().with(client).locate(&public).unwrap(),
+                record.addresses
+            );
+        })
+        .await
+        .unwrap();
+    }
+}
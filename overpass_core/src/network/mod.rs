@@ -1,3 +1,14 @@
 // mod.rs
 
-pub mod bitcoin_regtest;
\ No newline at end of file
+#[cfg(all(feature = "networking", feature = "bitcoin-backend"))]
+pub mod bitcoin_regtest;
+#[cfg(all(feature = "networking", feature = "prover"))]
+pub mod discovery;
+#[cfg(all(feature = "networking", feature = "prover"))]
+pub mod noise;
+#[cfg(feature = "nostr")]
+pub mod nostr;
+#[cfg(feature = "p2p")]
+pub mod p2p;
+#[cfg(feature = "prover")]
+pub mod wire;
@@ -0,0 +1,416 @@
+// ./src/network/noise.rs
+
+use crate::bitcoin::keys::{channel_index_for_id, KeyFamily, KeyManager};
+use crate::bitcoin::wallet::WalletError;
+use crate::zkp::helpers::Bytes32;
+use curve25519_dalek::montgomery::MontgomeryPoint;
+use sha2::{Digest, Sha256};
+use snow::{HandshakeState, TransportState};
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use thiserror::Error;
+use zeroize::Zeroizing;
+
+const PATTERN: &str = "Noise_XK_25519_ChaChaPoly_SHA256";
+/// Mixed into every handshake together with the channel id, so both sides must agree on
+/// which channel they are talking about.
+const PROLOGUE: &[u8] = b"overpass/noise/1";
+/// Largest Noise message, and so the largest frame.
+const MAX_MESSAGE: usize = 65_535;
+const TAG_LEN: usize = 16;
+/// Payload bytes per frame: a message less its tag and the continuation flag.
+const MAX_CHUNK: usize = MAX_MESSAGE - TAG_LEN - 1;
+/// Largest payload a session reassembles from its frames.
+pub const MAX_PAYLOAD: usize = 16 * 1024 * 1024;
+
+#[derive(Error, Debug)]
+pub enum NoiseError {
+    #[error("Noise protocol error: {0}")]
+    Protocol(#[from] snow::Error),
+    #[error("Peer authenticated as {0}, not the channel's counterparty")]
+    UnexpectedPeer(String),
+    #[error("The handshake is not finished")]
+    Unfinished,
+    #[error("Malformed frame: {0}")]
+    Malformed(&'static str),
+    #[error("Payload exceeds {} bytes", MAX_PAYLOAD)]
+    TooLarge,
+    #[error("The stream closed mid-conversation")]
+    Closed,
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Wallet(#[from] WalletError),
+}
+
+/// The static Noise key one side of a channel authenticates with.
+pub struct ChannelIdentity {
+    secret: Zeroizing<[u8; 32]>,
+    public: [u8; 32],
+}
+
+impl ChannelIdentity {
+    /// Derives the identity from the channel's funding key, so it is recoverable from the
+    /// seed and distinct per channel.
+    pub fn derive(keys: &KeyManager, channel_id: &Bytes32) -> Result<Self, NoiseError> {
+        let funding = keys.channel_secret(KeyFamily::Funding, channel_index_for_id(channel_id))?;
+        let secret = Zeroizing::new(
+            Sha256::new()
+                .chain_update(b"overpass/noise-static")
+                .chain_update(funding.secret_bytes())
+                .finalize()
+                .into(),
+        );
+        Ok(Self::from_secret(secret))
+    }
+
+    pub fn from_secret(secret: Zeroizing<[u8; 32]>) -> Self {
+        let public = MontgomeryPoint::mul_base_clamped(*secret).to_bytes();
+        Self { secret, public }
+    }
+
+    /// Gets the key the counterparty must know to start or accept a handshake.
+    pub fn public_key(&self) -> [u8; 32] {
+        self.public
+    }
+}
+
+fn prologue(channel_id: &Bytes32) -> Vec<u8> {
+    [PROLOGUE, channel_id].concat()
+}
+
+fn framed(message: &[u8]) -> Vec<u8> {
+    let mut frame = (message.len() as u16).to_be_bytes().to_vec();
+    frame.extend_from_slice(message);
+    frame
+}
+
+/// Cuts a byte stream into the length-prefixed frames sessions exchange.
+#[derive(Default)]
+struct FrameReader {
+    buffer: Vec<u8>,
+}
+
+impl FrameReader {
+    fn next_frame(&mut self) -> Option<Vec<u8>> {
+        let header = self.buffer.get(..2)?;
+        let len = u16::from_be_bytes([header[0], header[1]]) as usize;
+        if self.buffer.len() < 2 + len {
+            return None;
+        }
+        let frame = self.buffer[2..2 + len].to_vec();
+        self.buffer.drain(..2 + len);
+        Some(frame)
+    }
+}
+
+/// A Noise_XK handshake in progress, independent of how its bytes travel.
+///
+/// The initiator knows the responder's identity up front and reveals its own only under
+/// encryption; the responder accepts it only if it is the channel's counterparty.
+pub struct Handshake {
+    state: HandshakeState,
+    reader: FrameReader,
+    /// Identity the responder expects the initiator to prove.
+    expected: Option<[u8; 32]>,
+}
+
+impl Handshake {
+    pub fn initiator(
+        local: &ChannelIdentity,
+        remote: &[u8; 32],
+        channel_id: &Bytes32,
+    ) -> Result<Self, NoiseError> {
+        let state = snow::Builder::new(PATTERN.parse()?)
+            .local_private_key(local.secret.as_slice())
+            .remote_public_key(remote)
+            .prologue(&prologue(channel_id))
+            .build_initiator()?;
+        Ok(Self {
+            state,
+            reader: FrameReader::default(),
+            expected: None,
+        })
+    }
+
+    pub fn responder(
+        local: &ChannelIdentity,
+        counterparty: &[u8; 32],
+        channel_id: &Bytes32,
+    ) -> Result<Self, NoiseError> {
+        let state = snow::Builder::new(PATTERN.parse()?)
+            .local_private_key(local.secret.as_slice())
+            .prologue(&prologue(channel_id))
+            .build_responder()?;
+        Ok(Self {
+            state,
+            reader: FrameReader::default(),
+            expected: Some(*counterparty),
+        })
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.state.is_handshake_finished()
+    }
+
+    /// Gets the next handshake message to send, if it is this side's turn.
+    pub fn write(&mut self) -> Result<Option<Vec<u8>>, NoiseError> {
+        if self.is_finished() || !self.state.is_my_turn() {
+            return Ok(None);
+        }
+        let mut message = vec![0u8; MAX_MESSAGE];
+        let len = self.state.write_message(&[], &mut message)?;
+        Ok(Some(framed(&message[..len])))
+    }
+
+    /// Takes bytes received from the counterparty. Anything past the handshake is kept
+    /// for the session.
+    pub fn read(&mut self, bytes: &[u8]) -> Result<(), NoiseError> {
+        self.reader.buffer.extend_from_slice(bytes);
+        while !self.is_finished() && !self.state.is_my_turn() {
+            let Some(frame) = self.reader.next_frame() else {
+                break;
+            };
+            let mut payload = vec![0u8; MAX_MESSAGE];
+            self.state.read_message(&frame, &mut payload)?;
+        }
+        Ok(())
+    }
+
+    /// Finishes the handshake, checking who the initiator proved to be.
+    pub fn into_session(self) -> Result<Session, NoiseError> {
+        if !self.is_finished() {
+            return Err(NoiseError::Unfinished);
+        }
+        let remote: [u8; 32] = self
+            .state
+            .get_remote_static()
+            .and_then(|key| key.try_into().ok())
+            .ok_or(NoiseError::Unfinished)?;
+        if let Some(expected) = self.expected {
+            if remote != expected {
+                return Err(NoiseError::UnexpectedPeer(hex::encode(remote)));
+            }
+        }
+        Ok(Session {
+            transport: self.state.into_transport_mode()?,
+            reader: self.reader,
+            partial: Vec::new(),
+            ready: VecDeque::new(),
+            remote,
+        })
+    }
+
+    /// Runs the handshake to completion over a blocking stream.
+    pub fn complete<S: Read + Write>(mut self, stream: &mut S) -> Result<Session, NoiseError> {
+        let mut buffer = [0u8; 4096];
+        while !self.is_finished() {
+            if let Some(message) = self.write()? {
+                stream.write_all(&message)?;
+                stream.flush()?;
+                continue;
+            }
+            let len = stream.read(&mut buffer)?;
+            if len == 0 {
+                return Err(NoiseError::Closed);
+            }
+            self.read(&buffer[..len])?;
+        }
+        self.into_session()
+    }
+}
+
+/// An authenticated, encrypted session over which payloads of any size are sent as
+/// frames. Frames are bound to their order, so a dropped, replayed or altered one fails
+/// the session.
+pub struct Session {
+    transport: TransportState,
+    reader: FrameReader,
+    /// Decrypted chunks of a payload still waiting for its last frame.
+    partial: Vec<u8>,
+    ready: VecDeque<Vec<u8>>,
+    remote: [u8; 32],
+}
+
+impl Session {
+    /// Gets the identity the counterparty authenticated with.
+    pub fn remote_key(&self) -> [u8; 32] {
+        self.remote
+    }
+
+    /// Encrypts a payload into the bytes to send.
+    pub fn write(&mut self, payload: &[u8]) -> Result<Vec<u8>, NoiseError> {
+        if payload.len() > MAX_PAYLOAD {
+            return Err(NoiseError::TooLarge);
+        }
+        let mut chunks = payload.chunks(MAX_CHUNK).peekable();
+        let mut bytes = Vec::with_capacity(payload.len() + 32);
+        let mut message = vec![0u8; MAX_MESSAGE];
+        loop {
+            let chunk = chunks.next().unwrap_or_default();
+            let more = chunks.peek().is_some();
+            let plaintext = [&[more as u8][..], chunk].concat();
+            let len = self.transport.write_message(&plaintext, &mut message)?;
+            bytes.extend_from_slice(&framed(&message[..len]));
+            if !more {
+                return Ok(bytes);
+            }
+        }
+    }
+
+    /// Takes bytes received from the counterparty, decrypting every complete frame.
+    pub fn read(&mut self, bytes: &[u8]) -> Result<(), NoiseError> {
+        self.reader.buffer.extend_from_slice(bytes);
+        let mut plaintext = vec![0u8; MAX_MESSAGE];
+        while let Some(frame) = self.reader.next_frame() {
+            let len = self.transport.read_message(&frame, &mut plaintext)?;
+            let (flag, chunk) = plaintext[..len]
+                .split_first()
+                .ok_or(NoiseError::Malformed("missing continuation flag"))?;
+            if self.partial.len() + chunk.len() > MAX_PAYLOAD {
+                return Err(NoiseError::TooLarge);
+            }
+            self.partial.extend_from_slice(chunk);
+            match flag {
+                0 => self.ready.push_back(std::mem::take(&mut self.partial)),
+                1 => {}
+                _ => return Err(NoiseError::Malformed("invalid continuation flag")),
+            }
+        }
+        Ok(())
+    }
+
+    /// Gets the next payload received in full.
+    pub fn next_payload(&mut self) -> Option<Vec<u8>> {
+        self.ready.pop_front()
+    }
+
+    pub fn send<S: Write>(&mut self, stream: &mut S, payload: &[u8]) -> Result<(), NoiseError> {
+        let bytes = self.write(payload)?;
+        stream.write_all(&bytes)?;
+        Ok(stream.flush()?)
+    }
+
+    /// Blocks until a whole payload has arrived on `stream`.
+    pub fn receive<S: Read>(&mut self, stream: &mut S) -> Result<Vec<u8>, NoiseError> {
+        let mut buffer = [0u8; 16 * 1024];
+        loop {
+            if let Some(payload) = self.next_payload() {
+                return Ok(payload);
+            }
+            let len = stream.read(&mut buffer)?;
+            if len == 0 {
+                return Err(NoiseError::Closed);
+            }
+            self.read(&buffer[..len])?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::PHRASE;
+    use bitcoin::Network;
+    use std::os::unix::net::UnixStream;
+
+    const CHANNEL: Bytes32 = [7u8; 32];
+
+    fn identity(phrase: &str) -> ChannelIdentity {
+        let keys = KeyManager::from_mnemonic(phrase, "", Network::Regtest).unwrap();
+        ChannelIdentity::derive(&keys, &CHANNEL).unwrap()
+    }
+
+    fn alice() -> ChannelIdentity {
+        identity(PHRASE)
+    }
+
+    fn bob() -> ChannelIdentity {
+        identity("zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo wrong")
+    }
+
+    /// Passes handshake messages between the two sides until both are done.
+    fn shake(
+        mut initiator: Handshake,
+        mut responder: Handshake,
+    ) -> Result<(Session, Session), NoiseError> {
+        while !initiator.is_finished() || !responder.is_finished() {
+            if let Some(message) = initiator.write()? {
+                responder.read(&message)?;
+            }
+            if let Some(message) = responder.write()? {
+                initiator.read(&message)?;
+            }
+        }
+        Ok((initiator.into_session()?, responder.into_session()?))
+    }
+
+    #[test]
+    fn test_sessions_carry_payloads_over_a_stream() {
+        let (alice, bob) = (alice(), bob());
+        let (alice_key, bob_key) = (alice.public_key(), bob.public_key());
+        let (mut alice_stream, mut bob_stream) = UnixStream::pair().unwrap();
+
+        let payload: Vec<u8> = (0..200_000u32).map(|i| i as u8).collect();
+        let expected = payload.clone();
+        let responder = std::thread::spawn(move || {
+            let mut session = Handshake::responder(&bob, &alice_key, &CHANNEL)
+                .unwrap()
+                .complete(&mut bob_stream)
+                .unwrap();
+            assert_eq!(session.remote_key(), alice_key);
+            let received = session.receive(&mut bob_stream).unwrap();
+            session.send(&mut bob_stream, b"ack").unwrap();
+            received
+        });
+
+        let mut session = Handshake::initiator(&alice, &bob_key, &CHANNEL)
+            .unwrap()
+            .complete(&mut alice_stream)
+            .unwrap();
+        session.send(&mut alice_stream, &payload).unwrap();
+        assert_eq!(session.receive(&mut alice_stream).unwrap(), b"ack");
+        assert_eq!(responder.join().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_only_the_counterparty_gets_through() {
+        let (alice, bob) = (alice(), bob());
+        let mallory = ChannelIdentity::from_secret(Zeroizing::new([9u8; 32]));
+
+        // Bob expects Alice, so Mallory is turned away once she proves who she is.
+        let error = shake(
+            Handshake::initiator(&mallory, &bob.public_key(), &CHANNEL).unwrap(),
+            Handshake::responder(&bob, &alice.public_key(), &CHANNEL).unwrap(),
+        )
+        .err()
+        .unwrap();
+        assert!(matches!(error, NoiseError::UnexpectedPeer(_)));
+        // A handshake for another channel fails outright.
+        assert!(shake(
+            Handshake::initiator(&alice, &bob.public_key(), &CHANNEL).unwrap(),
+            Handshake::responder(&bob, &alice.public_key(), &[8u8; 32]).unwrap(),
+        )
+        .is_err());
+
+        let (mut sender, mut receiver) = shake(
+            Handshake::initiator(&alice, &bob.public_key(), &CHANNEL).unwrap(),
+            Handshake::responder(&bob, &alice.public_key(), &CHANNEL).unwrap(),
+        )
+        .unwrap();
+        let mut frame = sender.write(b"balance 90").unwrap();
+        // Bytes may arrive in any split.
+        receiver.read(&frame[..3]).unwrap();
+        assert!(receiver.next_payload().is_none());
+        receiver.read(&frame[3..]).unwrap();
+        assert_eq!(receiver.next_payload().unwrap(), b"balance 90");
+
+        frame = sender.write(b"balance 80").unwrap();
+        let last = frame.len() - 1;
+        frame[last] ^= 1;
+        assert!(matches!(
+            receiver.read(&frame),
+            Err(NoiseError::Protocol(_))
+        ));
+    }
+}
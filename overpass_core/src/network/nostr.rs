@@ -0,0 +1,675 @@
+// ./src/network/nostr.rs
+
+use crate::bitcoin::keys::KeyManager;
+use crate::bitcoin::wallet::WalletError;
+use crate::network::wire::{Decoded, Message, WireError};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use bitcoin::secp256k1::{schnorr, KeyPair, Secp256k1, XOnlyPublicKey};
+use futures_util::{SinkExt, StreamExt};
+use lru::LruCache;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, SystemTimeError, UNIX_EPOCH};
+use thiserror::Error;
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::Message as Frame;
+
+/// Kind of the events channel messages travel in; a regular kind, so relays keep them
+/// for a recipient that is offline.
+pub const CHANNEL_MESSAGE_KIND: u16 = 7_700;
+const SUBSCRIPTION: &str = "overpass";
+/// How long a send waits for some relay to accept the event.
+const ACK_TIMEOUT: Duration = Duration::from_secs(10);
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+/// Event ids remembered to drop the copies that arrive from other relays.
+const SEEN_CAPACITY: usize = 4_096;
+const INBOUND_CAPACITY: usize = 256;
+const OUTGOING_CAPACITY: usize = 64;
+
+#[derive(Error, Debug)]
+pub enum NostrError {
+    #[error("Encryption failed: {0}")]
+    Crypto(&'static str),
+    #[error("Malformed event: {0}")]
+    Malformed(&'static str),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Wire(#[from] WireError),
+    #[error("Every relay rejected the event: {0:?}")]
+    Rejected(Vec<String>),
+    #[error("No relay acknowledged the event in time")]
+    Timeout,
+    #[error("The transport has shut down")]
+    Closed,
+    #[error(transparent)]
+    Wallet(#[from] WalletError),
+    #[error("System clock is before the Unix epoch")]
+    Clock(#[from] SystemTimeError),
+}
+
+/// NIP-44 v2 encryption between two Nostr keys.
+pub mod nip44 {
+    use super::NostrError;
+    use bitcoin::secp256k1::{ecdh, Parity, SecretKey, XOnlyPublicKey};
+    use chacha20::cipher::{KeyIvInit, StreamCipher};
+    use chacha20::ChaCha20;
+    use hkdf::Hkdf;
+    use hmac::{Hmac, Mac};
+    use sha2::Sha256;
+
+    const VERSION: u8 = 2;
+    const MAX_PLAINTEXT: usize = 65_535;
+
+    pub fn conversation_key(secret: &SecretKey, public: &XOnlyPublicKey) -> [u8; 32] {
+        let point = ecdh::shared_secret_point(&public.public_key(Parity::Even), secret);
+        let (key, _) = Hkdf::<Sha256>::extract(Some(b"nip44-v2"), &point[..32]);
+        key.into()
+    }
+
+    fn message_keys(
+        conversation_key: &[u8; 32],
+        nonce: &[u8; 32],
+    ) -> ([u8; 32], [u8; 12], [u8; 32]) {
+        let mut keys = [0u8; 76];
+        Hkdf::<Sha256>::from_prk(conversation_key)
+            .expect("a conversation key is a full-length PRK")
+            .expand(nonce, &mut keys)
+            .expect("76 bytes is a valid HKDF length");
+        let mut cipher_key = [0u8; 32];
+        let mut cipher_nonce = [0u8; 12];
+        let mut hmac_key = [0u8; 32];
+        cipher_key.copy_from_slice(&keys[..32]);
+        cipher_nonce.copy_from_slice(&keys[32..44]);
+        hmac_key.copy_from_slice(&keys[44..]);
+        (cipher_key, cipher_nonce, hmac_key)
+    }
+
+    fn padded_len(len: usize) -> usize {
+        if len <= 32 {
+            return 32;
+        }
+        let next_power = 1usize << (usize::BITS - (len - 1).leading_zeros());
+        let chunk = if next_power <= 256 {
+            32
+        } else {
+            next_power / 8
+        };
+        chunk * ((len - 1) / chunk + 1)
+    }
+
+    fn mac(hmac_key: &[u8; 32], nonce: &[u8; 32], ciphertext: &[u8]) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(hmac_key).expect("HMAC takes any key");
+        mac.update(nonce);
+        mac.update(ciphertext);
+        mac
+    }
+
+    pub fn encrypt(
+        conversation_key: &[u8; 32],
+        plaintext: &str,
+        nonce: &[u8; 32],
+    ) -> Result<Vec<u8>, NostrError> {
+        let len = plaintext.len();
+        if len == 0 || len > MAX_PLAINTEXT {
+            return Err(NostrError::Crypto("plaintext must be 1 to 65535 bytes"));
+        }
+        let (cipher_key, cipher_nonce, hmac_key) = message_keys(conversation_key, nonce);
+        let mut padded = (len as u16).to_be_bytes().to_vec();
+        padded.extend_from_slice(plaintext.as_bytes());
+        padded.resize(2 + padded_len(len), 0);
+        ChaCha20::new(&cipher_key.into(), &cipher_nonce.into()).apply_keystream(&mut padded);
+
+        let tag = mac(&hmac_key, nonce, &padded).finalize().into_bytes();
+        Ok([&[VERSION][..], nonce, &padded, &tag].concat())
+    }
+
+    pub fn decrypt(conversation_key: &[u8; 32], payload: &[u8]) -> Result<String, NostrError> {
+        if payload.len() < 1 + 32 + 2 + 32 + 32 {
+            return Err(NostrError::Crypto("payload too short"));
+        }
+        if payload[0] != VERSION {
+            return Err(NostrError::Crypto("unsupported version"));
+        }
+        let nonce: [u8; 32] = payload[1..33].try_into().expect("32 bytes");
+        let (ciphertext, tag) = payload[33..].split_at(payload.len() - 33 - 32);
+        let (cipher_key, cipher_nonce, hmac_key) = message_keys(conversation_key, &nonce);
+        mac(&hmac_key, &nonce, ciphertext)
+            .verify_slice(tag)
+            .map_err(|_| NostrError::Crypto("invalid MAC"))?;
+
+        let mut padded = ciphertext.to_vec();
+        ChaCha20::new(&cipher_key.into(), &cipher_nonce.into()).apply_keystream(&mut padded);
+        let len = u16::from_be_bytes([padded[0], padded[1]]) as usize;
+        if len == 0 || padded.len() != 2 + padded_len(len) {
+            return Err(NostrError::Crypto("invalid padding"));
+        }
+        String::from_utf8(padded[2..2 + len].to_vec())
+            .map_err(|_| NostrError::Crypto("plaintext is not UTF-8"))
+    }
+}
+
+/// A signed Nostr event, as relays carry it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Event {
+    pub id: String,
+    pub pubkey: String,
+    pub created_at: u64,
+    pub kind: u16,
+    pub tags: Vec<Vec<String>>,
+    pub content: String,
+    pub sig: String,
+}
+
+fn event_id(
+    pubkey: &str,
+    created_at: u64,
+    kind: u16,
+    tags: &[Vec<String>],
+    content: &str,
+) -> [u8; 32] {
+    let serialized = json!([0, pubkey, created_at, kind, tags, content]).to_string();
+    Sha256::digest(serialized.as_bytes()).into()
+}
+
+impl Event {
+    pub fn sign(
+        keys: &KeyPair,
+        created_at: u64,
+        kind: u16,
+        tags: Vec<Vec<String>>,
+        content: String,
+    ) -> Self {
+        let pubkey = hex::encode(keys.x_only_public_key().0.serialize());
+        let id = event_id(&pubkey, created_at, kind, &tags, &content);
+        let message = bitcoin::secp256k1::Message::from_slice(&id).expect("32-byte id");
+        Self {
+            id: hex::encode(id),
+            pubkey,
+            created_at,
+            kind,
+            tags,
+            content,
+            sig: Secp256k1::new().sign_schnorr(&message, keys).to_string(),
+        }
+    }
+
+    /// Checks the id commits to the event and the author's key signed it.
+    pub fn verify(&self) -> bool {
+        let id = event_id(
+            &self.pubkey,
+            self.created_at,
+            self.kind,
+            &self.tags,
+            &self.content,
+        );
+        let (Ok(author), Ok(signature)) = (self.author(), schnorr::Signature::from_str(&self.sig))
+        else {
+            return false;
+        };
+        let message = bitcoin::secp256k1::Message::from_slice(&id).expect("32-byte id");
+        hex::encode(id) == self.id
+            && Secp256k1::verification_only()
+                .verify_schnorr(&signature, &message, &author)
+                .is_ok()
+    }
+
+    pub fn author(&self) -> Result<XOnlyPublicKey, NostrError> {
+        XOnlyPublicKey::from_str(&self.pubkey).map_err(|_| NostrError::Malformed("pubkey"))
+    }
+
+    /// Gets the keys named in the event's `p` tags.
+    fn recipients(&self) -> impl Iterator<Item = &str> {
+        self.tags
+            .iter()
+            .filter(|tag| tag.first().map(String::as_str) == Some("p"))
+            .filter_map(|tag| tag.get(1).map(String::as_str))
+    }
+}
+
+/// A channel message received through a relay.
+#[derive(Clone, Debug)]
+pub struct Inbound {
+    pub from: XOnlyPublicKey,
+    pub event_id: String,
+    pub created_at: u64,
+    pub message: Message,
+}
+
+fn now() -> Result<u64, SystemTimeError> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs())
+}
+
+/// State the relay connections share.
+struct Shared {
+    keys: KeyPair,
+    public_key: String,
+    seen: Mutex<LruCache<String, ()>>,
+    /// Sends waiting for relays to acknowledge their event, by event id.
+    pending: Mutex<HashMap<String, mpsc::Sender<Result<(), String>>>>,
+    inbound: mpsc::Sender<Inbound>,
+    /// Newest message received, which a reconnecting relay is asked for messages from.
+    since: AtomicU64,
+}
+
+impl Shared {
+    fn subscription(&self) -> String {
+        let filter = json!({
+            "kinds": [CHANNEL_MESSAGE_KIND],
+            "#p": [self.public_key],
+            "since": self.since.load(Ordering::Relaxed),
+        });
+        json!(["REQ", SUBSCRIPTION, filter]).to_string()
+    }
+
+    fn handle(&self, relay: &str, text: &str) {
+        let Ok(frame) = serde_json::from_str::<Vec<Value>>(text) else {
+            return;
+        };
+        match frame.first().and_then(Value::as_str) {
+            Some("EVENT") => {
+                let Some(event) = frame.get(2).cloned() else {
+                    return;
+                };
+                match serde_json::from_value(event)
+                    .map_err(NostrError::from)
+                    .and_then(|event| self.receive(event))
+                {
+                    Ok(Some(inbound)) => {
+                        self.since.fetch_max(inbound.created_at, Ordering::Relaxed);
+                        #[cfg(feature = "metrics")]
+                        crate::services::metrics::global().record_message("nostr", "received");
+                        let _ = self.inbound.try_send(inbound);
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        #[cfg(feature = "metrics")]
+                        crate::services::metrics::global().record_error("nostr");
+                        log::debug!("Dropped event from {}: {}", relay, e)
+                    }
+                }
+            }
+            Some("OK") => {
+                let (Some(id), Some(accepted)) = (
+                    frame.get(1).and_then(Value::as_str),
+                    frame.get(2).and_then(Value::as_bool),
+                ) else {
+                    return;
+                };
+                let pending = self
+                    .pending
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner());
+                if let Some(ack) = pending.get(id) {
+                    let reason = frame.get(3).and_then(Value::as_str).unwrap_or_default();
+                    let _ = ack.try_send(match accepted {
+                        true => Ok(()),
+                        false => Err(format!("{}: {}", relay, reason)),
+                    });
+                }
+            }
+            Some("NOTICE") => log::warn!("Relay {} says {:?}", relay, frame.get(1)),
+            _ => {}
+        }
+    }
+
+    /// Opens an event addressed to this wallet, or returns none for a copy already seen
+    /// or a message type it may ignore.
+    fn receive(&self, event: Event) -> Result<Option<Inbound>, NostrError> {
+        if event.kind != CHANNEL_MESSAGE_KIND
+            || !event.recipients().any(|key| key == self.public_key)
+        {
+            return Err(NostrError::Malformed("not addressed to this wallet"));
+        }
+        {
+            let mut seen = self
+                .seen
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            if seen.put(event.id.clone(), ()).is_some() {
+                return Ok(None);
+            }
+        }
+        if !event.verify() {
+            return Err(NostrError::Malformed("invalid signature"));
+        }
+        let from = event.author()?;
+        let key = nip44::conversation_key(&self.keys.secret_key(), &from);
+        let payload = BASE64
+            .decode(&event.content)
+            .map_err(|_| NostrError::Malformed("content is not base64"))?;
+        let plaintext = nip44::decrypt(&key, &payload)?;
+        let bytes = BASE64
+            .decode(plaintext)
+            .map_err(|_| NostrError::Malformed("message is not base64"))?;
+        Ok(match Message::decode(&bytes)? {
+            Decoded::Message(message) => Some(Inbound {
+                from,
+                event_id: event.id,
+                created_at: event.created_at,
+                message,
+            }),
+            Decoded::Ignored(_) => None,
+        })
+    }
+}
+
+/// Keeps one relay connected, reconnecting after it drops, until the transport is dropped.
+async fn run_relay(url: String, shared: Arc<Shared>, mut outgoing: mpsc::Receiver<String>) {
+    loop {
+        match tokio_tungstenite::connect_async(url.as_str()).await {
+            Ok((socket, _)) => {
+                let (mut sink, mut stream) = socket.split();
+                if sink.send(Frame::Text(shared.subscription())).await.is_ok() {
+                    loop {
+                        tokio::select! {
+                            frame = outgoing.recv() => match frame {
+                                Some(text) => {
+                                    if sink.send(Frame::Text(text)).await.is_err() {
+                                        break;
+                                    }
+                                }
+                                None => return,
+                            },
+                            incoming = stream.next() => match incoming {
+                                Some(Ok(Frame::Text(text))) => shared.handle(&url, &text),
+                                Some(Ok(_)) => {}
+                                Some(Err(_)) | None => break,
+                            },
+                        }
+                    }
+                }
+            }
+            Err(e) => log::warn!("Failed to connect to relay {}: {}", url, e),
+        }
+        if outgoing.is_closed() {
+            return;
+        }
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+/// Carries channel messages as encrypted Nostr events through public relays, so peers
+/// that cannot accept connections still get them once they come online.
+///
+/// Each message is a wire message, NIP-44 encrypted to the recipient, sent as an event
+/// of [`CHANNEL_MESSAGE_KIND`] that names the recipient in a `p` tag and is signed by
+/// the wallet's transport key.
+pub struct NostrTransport {
+    shared: Arc<Shared>,
+    relays: Vec<mpsc::Sender<String>>,
+    inbound: mpsc::Receiver<Inbound>,
+}
+
+impl NostrTransport {
+    /// Connects to `relays` in the background and asks each for the messages addressed to
+    /// this wallet since `since`, in Unix seconds. Must be called within a tokio runtime.
+    pub fn connect(keys: &KeyManager, relays: &[String], since: u64) -> Result<Self, NostrError> {
+        let keys = keys.transport_key()?;
+        let (inbound_sender, inbound) = mpsc::channel(INBOUND_CAPACITY);
+        let shared = Arc::new(Shared {
+            public_key: hex::encode(keys.x_only_public_key().0.serialize()),
+            keys,
+            seen: Mutex::new(LruCache::new(
+                NonZeroUsize::new(SEEN_CAPACITY).expect("nonzero"),
+            )),
+            pending: Mutex::new(HashMap::new()),
+            inbound: inbound_sender,
+            since: AtomicU64::new(since),
+        });
+        let relays = relays
+            .iter()
+            .map(|url| {
+                let (sender, receiver) = mpsc::channel(OUTGOING_CAPACITY);
+                tokio::spawn(run_relay(url.clone(), shared.clone(), receiver));
+                sender
+            })
+            .collect();
+        Ok(Self {
+            shared,
+            relays,
+            inbound,
+        })
+    }
+
+    pub fn public_key(&self) -> XOnlyPublicKey {
+        self.shared.keys.x_only_public_key().0
+    }
+
+    /// Publishes a message to `recipient` on every relay and waits until one accepts it.
+    /// Returns the id of the event.
+    pub async fn send(
+        &self,
+        recipient: &XOnlyPublicKey,
+        message: &Message,
+    ) -> Result<String, NostrError> {
+        let key = nip44::conversation_key(&self.shared.keys.secret_key(), recipient);
+        let mut nonce = [0u8; 32];
+        rand::rngs::OsRng.fill_bytes(&mut nonce);
+        let plaintext = BASE64.encode(message.encode());
+        let content = BASE64.encode(nip44::encrypt(&key, &plaintext, &nonce)?);
+        let tags = vec![vec!["p".into(), hex::encode(recipient.serialize())]];
+        let event = Event::sign(
+            &self.shared.keys,
+            now()?,
+            CHANNEL_MESSAGE_KIND,
+            tags,
+            content,
+        );
+
+        let (ack, mut acks) = mpsc::channel(self.relays.len().max(1));
+        self.shared
+            .pending
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .insert(event.id.clone(), ack);
+        let result = self.publish(&event, &mut acks).await;
+        self.shared
+            .pending
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .remove(&event.id);
+        #[cfg(feature = "metrics")]
+        match &result {
+            Ok(()) => crate::services::metrics::global().record_message("nostr", "sent"),
+            Err(_) => crate::services::metrics::global().record_error("nostr"),
+        }
+        result.map(|()| event.id)
+    }
+
+    async fn publish(
+        &self,
+        event: &Event,
+        acks: &mut mpsc::Receiver<Result<(), String>>,
+    ) -> Result<(), NostrError> {
+        let frame = json!(["EVENT", event]).to_string();
+        for relay in &self.relays {
+            relay
+                .send(frame.clone())
+                .await
+                .map_err(|_| NostrError::Closed)?;
+        }
+        let mut rejections = Vec::new();
+        while rejections.len() < self.relays.len() {
+            match tokio::time::timeout(ACK_TIMEOUT, acks.recv()).await {
+                Ok(Some(Ok(()))) => return Ok(()),
+                Ok(Some(Err(reason))) => rejections.push(reason),
+                Ok(None) => return Err(NostrError::Closed),
+                Err(_) => return Err(NostrError::Timeout),
+            }
+        }
+        Err(NostrError::Rejected(rejections))
+    }
+
+    /// Waits for the next message addressed to this wallet; none once shut down.
+    pub async fn recv(&mut self) -> Option<Inbound> {
+        self.inbound.recv().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::wire::{Ping, Pong};
+    use crate::test_util::PHRASE;
+    use axum::extract::ws::{Message as WsMessage, WebSocket, WebSocketUpgrade};
+    use axum::extract::State;
+    use axum::response::IntoResponse;
+    use axum::routing::get;
+    use axum::Router;
+    use bitcoin::secp256k1::SecretKey;
+    use bitcoin::Network;
+    use tokio::sync::broadcast;
+
+    #[test]
+    fn test_nip44_and_event_signatures() {
+        // First encrypt_decrypt vector of the NIP-44 specification.
+        let mut one = [0u8; 32];
+        one[31] = 1;
+        let mut two = [0u8; 32];
+        two[31] = 2;
+        let secp = Secp256k1::new();
+        let sender = SecretKey::from_slice(&one).unwrap();
+        let recipient = SecretKey::from_slice(&two).unwrap();
+        let key = nip44::conversation_key(&sender, &recipient.x_only_public_key(&secp).0);
+        assert_eq!(
+            hex::encode(key),
+            "c41c775356fd92eadc63ff5a0dc1da211b268cbea22316767095b2871ea1412d"
+        );
+        let payload = nip44::encrypt(&key, "a", &one).unwrap();
+        assert_eq!(
+            BASE64.encode(&payload),
+            "AgAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAABee0G5VSK0/9YypIObAtDKfYEAjD35uVkHyB0F4DwrcNaCXlCWZKaArsGrY6M9wnuTMxWfp1RTN9Xga8no+kF5Vsb"
+        );
+        assert_eq!(nip44::decrypt(&key, &payload).unwrap(), "a");
+        let mut tampered = payload.clone();
+        tampered[40] ^= 1;
+        assert!(nip44::decrypt(&key, &tampered).is_err());
+
+        let keys = KeyPair::from_secret_key(&secp, &sender);
+        let event = Event::sign(
+            &keys,
+            1_700_000_000,
+            CHANNEL_MESSAGE_KIND,
+            vec![],
+            "hi".into(),
+        );
+        assert!(event.verify());
+        let mut forged = event.clone();
+        forged.content = "bye".into();
+        assert!(!forged.verify());
+    }
+
+    /// Stores every event and sends those tagged for a subscriber, as a relay would.
+    #[derive(Clone)]
+    struct Relay {
+        events: Arc<Mutex<Vec<Event>>>,
+        live: broadcast::Sender<Event>,
+    }
+
+    fn matches(event: &Event, filter: &Value) -> bool {
+        let wanted = filter["#p"].as_array().cloned().unwrap_or_default();
+        event
+            .recipients()
+            .any(|key| wanted.iter().any(|want| want.as_str() == Some(key)))
+    }
+
+    async fn relay_session(mut socket: WebSocket, relay: Relay) {
+        let mut live = relay.live.subscribe();
+        let mut subscription: Option<(String, Value)> = None;
+        loop {
+            tokio::select! {
+                Some(Ok(WsMessage::Text(text))) = socket.recv() => {
+                    let frame: Vec<Value> = serde_json::from_str(&text).unwrap();
+                    match frame[0].as_str().unwrap() {
+                        "EVENT" => {
+                            let event: Event = serde_json::from_value(frame[1].clone()).unwrap();
+                            let ok = json!(["OK", event.id, event.verify(), ""]).to_string();
+                            relay.events.lock().unwrap().push(event.clone());
+                            let _ = relay.live.send(event);
+                            socket.send(WsMessage::Text(ok)).await.unwrap();
+                        }
+                        "REQ" => {
+                            let (id, filter) = (frame[1].as_str().unwrap().to_string(), frame[2].clone());
+                            let stored: Vec<Event> = relay.events.lock().unwrap().clone();
+                            for event in stored.iter().filter(|event| matches(event, &filter)) {
+                                let frame = json!(["EVENT", id, event]).to_string();
+                                socket.send(WsMessage::Text(frame)).await.unwrap();
+                            }
+                            subscription = Some((id, filter));
+                        }
+                        _ => {}
+                    }
+                }
+                Ok(event) = live.recv() => {
+                    if let Some((id, filter)) = &subscription {
+                        if matches(&event, filter) {
+                            let frame = json!(["EVENT", id, event]).to_string();
+                            socket.send(WsMessage::Text(frame)).await.unwrap();
+                        }
+                    }
+                }
+                else => return,
+            }
+        }
+    }
+
+    async fn relay_handler(ws: WebSocketUpgrade, State(relay): State<Relay>) -> impl IntoResponse {
+        ws.on_upgrade(move |socket| relay_session(socket, relay))
+    }
+
+    fn keys(phrase: &str) -> KeyManager {
+        KeyManager::from_mnemonic(phrase, "", Network::Regtest).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_relay_works_as_a_mailbox() {
+        let relay = Relay {
+            events: Arc::default(),
+            live: broadcast::channel(16).0,
+        };
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let app = Router::new()
+            .route("/", get(relay_handler))
+            .with_state(relay);
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let relays = vec![url];
+        let alice = NostrTransport::connect(&keys(PHRASE), &relays, 0).unwrap();
+        let bob_keys = keys("zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo wrong");
+        let bob_key = bob_keys.transport_key().unwrap().x_only_public_key().0;
+
+        // Bob is offline when Alice sends.
+        alice
+            .send(&bob_key, &Message::Ping(Ping { nonce: 7 }))
+            .await
+            .unwrap();
+        let mut bob = NostrTransport::connect(&bob_keys, &relays, 0).unwrap();
+        let received = tokio::time::timeout(Duration::from_secs(5), bob.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(received.from, alice.public_key());
+        assert!(matches!(received.message, Message::Ping(Ping { nonce: 7 })));
+
+        let mut alice = alice;
+        bob.send(&alice.public_key(), &Message::Pong(Pong { nonce: 7 }))
+            .await
+            .unwrap();
+        let reply = tokio::time::timeout(Duration::from_secs(5), alice.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(reply.from, bob_key);
+        assert!(matches!(reply.message, Message::Pong(Pong { nonce: 7 })));
+    }
+}
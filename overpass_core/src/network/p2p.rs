@@ -0,0 +1,536 @@
+// ./src/network/p2p.rs
+
+use crate::bitcoin::keys::KeyManager;
+use crate::bitcoin::wallet::WalletError;
+use crate::zkp::channel_backup::ChannelBackup;
+use crate::zkp::helpers::Bytes32;
+use crate::zkp::watch_only::SignedTransition;
+use libp2p::core::transport::ListenerId;
+use libp2p::futures::StreamExt;
+use libp2p::identity::{self, Keypair};
+use libp2p::request_response::{self, cbor, OutboundRequestId, ProtocolSupport};
+use libp2p::swarm::dial_opts::DialOpts;
+use libp2p::swarm::{DialError, SwarmEvent};
+use libp2p::{noise, tcp, yamux, Multiaddr, PeerId, StreamProtocol, Swarm};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::{mpsc, oneshot};
+
+/// Protocol channel messages are exchanged over.
+const PROTOCOL: &str = "/overpass/channel/1.0.0";
+/// How long a connection carrying no messages is kept open.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+/// Messages a channel's receiver can fall behind by before the sender is turned away.
+const INBOUND_CAPACITY: usize = 64;
+const COMMAND_CAPACITY: usize = 64;
+
+#[derive(Error, Debug)]
+pub enum P2pError {
+    #[error("Invalid transport key: {0}")]
+    Key(String),
+    #[error("Transport error: {0}")]
+    Transport(String),
+    #[error("Failed to reach the counterparty: {0}")]
+    Dial(String),
+    #[error("No counterparty is routed for channel {0}")]
+    NoRoute(String),
+    #[error("Request failed: {0}")]
+    Request(String),
+    #[error("Counterparty rejected the message: {0}")]
+    Rejected(String),
+    #[error("The transport has shut down")]
+    Closed,
+    #[error(transparent)]
+    Wallet(#[from] WalletError),
+}
+
+/// What counterparties exchange over a channel.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum ChannelMessage {
+    /// A signed state update.
+    Transition(SignedTransition),
+    /// The sender's latest state of the channel, proven under an anchored global root.
+    Backup(ChannelBackup),
+}
+
+/// A message received on a channel from its counterparty.
+#[derive(Clone, Debug)]
+pub struct Inbound {
+    pub channel_id: Bytes32,
+    pub peer: PeerId,
+    pub message: ChannelMessage,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Envelope {
+    channel_id: Bytes32,
+    message: ChannelMessage,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+enum Reply {
+    Delivered,
+    Rejected(String),
+}
+
+type Behaviour = cbor::Behaviour<Envelope, Reply>;
+type Delivery = oneshot::Sender<Result<(), P2pError>>;
+
+/// Builds the wallet's libp2p identity from its transport key, so its peer id is fixed
+/// by the seed.
+pub fn identity(keys: &KeyManager) -> Result<Keypair, P2pError> {
+    let mut secret = keys.transport_key()?.secret_bytes();
+    let secret = identity::secp256k1::SecretKey::try_from_bytes(&mut secret)
+        .map_err(|e| P2pError::Key(e.to_string()))?;
+    Ok(identity::secp256k1::Keypair::from(secret).into())
+}
+
+/// Gets the peer id a counterparty with the given transport key listens under.
+pub fn peer_id(transport_key: &bitcoin::secp256k1::PublicKey) -> Result<PeerId, P2pError> {
+    let key = identity::secp256k1::PublicKey::try_from_bytes(&transport_key.serialize())
+        .map_err(|e| P2pError::Key(e.to_string()))?;
+    Ok(identity::PublicKey::from(key).to_peer_id())
+}
+
+enum Command {
+    Listen(Multiaddr, oneshot::Sender<Result<Multiaddr, P2pError>>),
+    AddAddress(PeerId, Multiaddr),
+    Route(Bytes32, PeerId, mpsc::Sender<Inbound>),
+    Unroute(Bytes32),
+    Send(Box<Envelope>, Delivery),
+    ConnectedPeers(oneshot::Sender<Vec<PeerId>>),
+    Disconnect(PeerId),
+}
+
+/// Handle to a transport running on the tokio runtime. Clones share the transport, which
+/// shuts down once every handle is dropped.
+///
+/// Connections are secured with Noise over TCP or QUIC. Each channel is routed to one
+/// counterparty: messages for it go only to that peer, and messages for it are only
+/// accepted from that peer.
+#[derive(Clone)]
+pub struct P2pTransport {
+    peer_id: PeerId,
+    commands: mpsc::Sender<Command>,
+}
+
+impl P2pTransport {
+    /// Starts a transport under the wallet's identity. Must be called within a tokio runtime.
+    pub fn spawn(keys: &KeyManager) -> Result<Self, P2pError> {
+        let swarm = libp2p::SwarmBuilder::with_existing_identity(identity(keys)?)
+            .with_tokio()
+            .with_tcp(
+                tcp::Config::default(),
+                noise::Config::new,
+                yamux::Config::default,
+            )
+            .map_err(|e| P2pError::Transport(e.to_string()))?
+            .with_quic()
+            .with_behaviour(|_| {
+                Behaviour::new(
+                    [(StreamProtocol::new(PROTOCOL), ProtocolSupport::Full)],
+                    request_response::Config::default(),
+                )
+            })
+            .map_err(|e| P2pError::Transport(e.to_string()))?
+            .with_swarm_config(|config| config.with_idle_connection_timeout(IDLE_TIMEOUT))
+            .build();
+        let peer_id = *swarm.local_peer_id();
+        let (commands, receiver) = mpsc::channel(COMMAND_CAPACITY);
+        tokio::spawn(Driver::new(swarm).run(receiver));
+        Ok(Self { peer_id, commands })
+    }
+
+    pub fn peer_id(&self) -> PeerId {
+        self.peer_id
+    }
+
+    async fn command(&self, command: Command) -> Result<(), P2pError> {
+        self.commands
+            .send(command)
+            .await
+            .map_err(|_| P2pError::Closed)
+    }
+
+    async fn request<T>(
+        &self,
+        command: impl FnOnce(oneshot::Sender<T>) -> Command,
+    ) -> Result<T, P2pError> {
+        let (reply, response) = oneshot::channel();
+        self.command(command(reply)).await?;
+        response.await.map_err(|_| P2pError::Closed)
+    }
+
+    /// Listens on `addr`, e.g. `/ip4/0.0.0.0/udp/0/quic-v1`, and returns the address
+    /// actually bound.
+    pub async fn listen(&self, addr: Multiaddr) -> Result<Multiaddr, P2pError> {
+        self.request(|reply| Command::Listen(addr, reply)).await?
+    }
+
+    /// Records an address `peer` can be dialed at.
+    pub async fn add_address(&self, peer: PeerId, addr: Multiaddr) -> Result<(), P2pError> {
+        self.command(Command::AddAddress(peer, addr)).await
+    }
+
+    /// Routes a channel to its counterparty, replacing any earlier route, and returns the
+    /// receiver of the messages the counterparty sends on it.
+    pub async fn open_channel(
+        &self,
+        channel_id: Bytes32,
+        counterparty: PeerId,
+    ) -> Result<mpsc::Receiver<Inbound>, P2pError> {
+        let (sender, receiver) = mpsc::channel(INBOUND_CAPACITY);
+        self.command(Command::Route(channel_id, counterparty, sender))
+            .await?;
+        Ok(receiver)
+    }
+
+    /// Stops routing a channel; its counterparty's messages are rejected from now on.
+    pub async fn close_channel(&self, channel_id: Bytes32) -> Result<(), P2pError> {
+        self.command(Command::Unroute(channel_id)).await
+    }
+
+    /// Sends a message to a channel's counterparty, dialing it when not connected, and
+    /// waits for the counterparty to accept it.
+    pub async fn send(&self, channel_id: Bytes32, message: ChannelMessage) -> Result<(), P2pError> {
+        let envelope = Envelope {
+            channel_id,
+            message,
+        };
+        let result = self
+            .request(|reply| Command::Send(Box::new(envelope), reply))
+            .await
+            .and_then(|delivered| delivered);
+        #[cfg(feature = "metrics")]
+        match &result {
+            Ok(()) => crate::services::metrics::global().record_message("p2p", "sent"),
+            Err(_) => crate::services::metrics::global().record_error("p2p"),
+        }
+        result
+    }
+
+    pub async fn connected_peers(&self) -> Result<Vec<PeerId>, P2pError> {
+        self.request(Command::ConnectedPeers).await
+    }
+
+    pub async fn disconnect(&self, peer: PeerId) -> Result<(), P2pError> {
+        self.command(Command::Disconnect(peer)).await
+    }
+}
+
+struct Route {
+    counterparty: PeerId,
+    inbound: mpsc::Sender<Inbound>,
+}
+
+/// Owns the swarm and everything the handles ask of it.
+struct Driver {
+    swarm: Swarm<Behaviour>,
+    routes: HashMap<Bytes32, Route>,
+    addresses: HashMap<PeerId, Vec<Multiaddr>>,
+    listening: HashMap<ListenerId, oneshot::Sender<Result<Multiaddr, P2pError>>>,
+    /// Messages waiting for a connection to their counterparty.
+    queued: HashMap<PeerId, Vec<(Envelope, Delivery)>>,
+    in_flight: HashMap<OutboundRequestId, Delivery>,
+}
+
+impl Driver {
+    fn new(swarm: Swarm<Behaviour>) -> Self {
+        Self {
+            swarm,
+            routes: HashMap::new(),
+            addresses: HashMap::new(),
+            listening: HashMap::new(),
+            queued: HashMap::new(),
+            in_flight: HashMap::new(),
+        }
+    }
+
+    async fn run(mut self, mut commands: mpsc::Receiver<Command>) {
+        loop {
+            tokio::select! {
+                command = commands.recv() => match command {
+                    Some(command) => self.command(command),
+                    None => break,
+                },
+                event = self.swarm.select_next_some() => self.event(event),
+            }
+        }
+    }
+
+    fn command(&mut self, command: Command) {
+        match command {
+            Command::Listen(addr, reply) => match self.swarm.listen_on(addr) {
+                Ok(listener) => {
+                    self.listening.insert(listener, reply);
+                }
+                Err(e) => {
+                    let _ = reply.send(Err(P2pError::Transport(e.to_string())));
+                }
+            },
+            Command::AddAddress(peer, addr) => {
+                let known = self.addresses.entry(peer).or_default();
+                if !known.contains(&addr) {
+                    known.push(addr);
+                }
+            }
+            Command::Route(channel_id, counterparty, inbound) => {
+                self.routes.insert(
+                    channel_id,
+                    Route {
+                        counterparty,
+                        inbound,
+                    },
+                );
+            }
+            Command::Unroute(channel_id) => {
+                self.routes.remove(&channel_id);
+            }
+            Command::Send(envelope, delivery) => self.send(*envelope, delivery),
+            Command::ConnectedPeers(reply) => {
+                let _ = reply.send(self.swarm.connected_peers().copied().collect());
+            }
+            Command::Disconnect(peer) => {
+                let _ = self.swarm.disconnect_peer_id(peer);
+            }
+        }
+    }
+
+    fn send(&mut self, envelope: Envelope, delivery: Delivery) {
+        let Some(route) = self.routes.get(&envelope.channel_id) else {
+            let _ = delivery.send(Err(P2pError::NoRoute(hex::encode(envelope.channel_id))));
+            return;
+        };
+        let peer = route.counterparty;
+        if self.swarm.is_connected(&peer) {
+            let request = self.swarm.behaviour_mut().send_request(&peer, envelope);
+            self.in_flight.insert(request, delivery);
+            return;
+        }
+
+        self.queued
+            .entry(peer)
+            .or_default()
+            .push((envelope, delivery));
+        let addresses = self.addresses.get(&peer).cloned().unwrap_or_default();
+        match self
+            .swarm
+            .dial(DialOpts::peer_id(peer).addresses(addresses).build())
+        {
+            // Already dialing; the message goes out with the rest once connected.
+            Ok(()) | Err(DialError::DialPeerConditionFalse(_)) => {}
+            Err(e) => self.fail_queued(&peer, &e.to_string()),
+        }
+    }
+
+    fn fail_queued(&mut self, peer: &PeerId, error: &str) {
+        for (_, delivery) in self.queued.remove(peer).unwrap_or_default() {
+            let _ = delivery.send(Err(P2pError::Dial(error.to_string())));
+        }
+    }
+
+    /// Hands a request to its channel's receiver if it came from the channel's counterparty.
+    fn deliver(&mut self, peer: PeerId, envelope: Envelope) -> Reply {
+        let Some(route) = self.routes.get(&envelope.channel_id) else {
+            return Reply::Rejected("unknown channel".into());
+        };
+        if route.counterparty != peer {
+            return Reply::Rejected("not the channel's counterparty".into());
+        }
+        let inbound = Inbound {
+            channel_id: envelope.channel_id,
+            peer,
+            message: envelope.message,
+        };
+        match route.inbound.try_send(inbound) {
+            Ok(()) => {
+                #[cfg(feature = "metrics")]
+                crate::services::metrics::global().record_message("p2p", "received");
+                Reply::Delivered
+            }
+            Err(mpsc::error::TrySendError::Full(_)) => Reply::Rejected("channel is busy".into()),
+            Err(mpsc::error::TrySendError::Closed(_)) => {
+                Reply::Rejected("channel is closed".into())
+            }
+        }
+    }
+
+    fn event(&mut self, event: SwarmEvent<request_response::Event<Envelope, Reply>>) {
+        match event {
+            SwarmEvent::NewListenAddr {
+                listener_id,
+                address,
+            } => {
+                if let Some(reply) = self.listening.remove(&listener_id) {
+                    let _ = reply.send(Ok(address));
+                }
+            }
+            SwarmEvent::ListenerClosed {
+                listener_id,
+                reason,
+                ..
+            } => {
+                if let Some(reply) = self.listening.remove(&listener_id) {
+                    let error = reason
+                        .err()
+                        .map_or("listener closed".into(), |e| e.to_string());
+                    let _ = reply.send(Err(P2pError::Transport(error)));
+                }
+            }
+            SwarmEvent::ConnectionEstablished { peer_id, .. } => {
+                for (envelope, delivery) in self.queued.remove(&peer_id).unwrap_or_default() {
+                    let request = self.swarm.behaviour_mut().send_request(&peer_id, envelope);
+                    self.in_flight.insert(request, delivery);
+                }
+            }
+            SwarmEvent::OutgoingConnectionError {
+                peer_id: Some(peer),
+                error,
+                ..
+            } => {
+                if !self.swarm.is_connected(&peer) {
+                    self.fail_queued(&peer, &error.to_string());
+                }
+            }
+            SwarmEvent::Behaviour(request_response::Event::Message { peer, message }) => {
+                match message {
+                    request_response::Message::Request {
+                        request, channel, ..
+                    } => {
+                        let reply = self.deliver(peer, request);
+                        // A failed response surfaces as an outbound failure on the sender.
+                        let _ = self.swarm.behaviour_mut().send_response(channel, reply);
+                    }
+                    request_response::Message::Response {
+                        request_id,
+                        response,
+                    } => {
+                        if let Some(delivery) = self.in_flight.remove(&request_id) {
+                            let _ = delivery.send(match response {
+                                Reply::Delivered => Ok(()),
+                                Reply::Rejected(reason) => Err(P2pError::Rejected(reason)),
+                            });
+                        }
+                    }
+                }
+            }
+            SwarmEvent::Behaviour(request_response::Event::OutboundFailure {
+                request_id,
+                error,
+                ..
+            }) => {
+                if let Some(delivery) = self.in_flight.remove(&request_id) {
+                    let _ = delivery.send(Err(P2pError::Request(error.to_string())));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::PHRASE;
+    use crate::zkp::state_proof::StateProof;
+    use crate::zkp::watch_only::UnsignedTransition;
+    use bitcoin::Network;
+
+    fn keys(phrase: &str) -> KeyManager {
+        KeyManager::from_mnemonic(phrase, "", Network::Regtest).unwrap()
+    }
+
+    fn transition(channel_id: Bytes32) -> ChannelMessage {
+        ChannelMessage::Transition(SignedTransition {
+            transition: UnsignedTransition {
+                wallet_id: [1u8; 32],
+                channel_id,
+                nonce: 4,
+                state_hash: [2u8; 32],
+                old_commitment: [3u8; 32],
+                wallet_root: [4u8; 32],
+                new_balance: 90,
+                metadata: b"memo".to_vec(),
+            },
+            commitment: [5u8; 32],
+            proof: StateProof {
+                pi: [6u8; 32],
+                public_inputs: vec![[3u8; 32], [5u8; 32], [4u8; 32]],
+                timestamp: 0,
+            },
+        })
+    }
+
+    const ALICE: &str = PHRASE;
+    const BOB: &str = "zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo wrong";
+
+    #[test]
+    fn test_peer_id_follows_transport_key() {
+        let alice = keys(ALICE);
+        let expected = identity(&alice).unwrap().public().to_peer_id();
+        let public = alice.transport_key().unwrap().public_key();
+        assert_eq!(peer_id(&public).unwrap(), expected);
+        assert_eq!(
+            identity(&keys(ALICE)).unwrap().public().to_peer_id(),
+            expected
+        );
+        assert_ne!(
+            identity(&keys(BOB)).unwrap().public().to_peer_id(),
+            expected
+        );
+    }
+
+    #[tokio::test]
+    async fn test_messages_route_between_counterparties() {
+        let alice = P2pTransport::spawn(&keys(ALICE)).unwrap();
+        let bob = P2pTransport::spawn(&keys(BOB)).unwrap();
+        let addr = bob
+            .listen("/ip4/127.0.0.1/udp/0/quic-v1".parse().unwrap())
+            .await
+            .unwrap();
+        alice.add_address(bob.peer_id(), addr).await.unwrap();
+
+        let channel_id = [9u8; 32];
+        assert!(matches!(
+            alice.send(channel_id, transition(channel_id)).await,
+            Err(P2pError::NoRoute(_))
+        ));
+        alice.open_channel(channel_id, bob.peer_id()).await.unwrap();
+        // Bob has not routed the channel yet.
+        assert!(matches!(
+            alice.send(channel_id, transition(channel_id)).await,
+            Err(P2pError::Rejected(_))
+        ));
+
+        let mut inbound = bob.open_channel(channel_id, alice.peer_id()).await.unwrap();
+        alice
+            .send(channel_id, transition(channel_id))
+            .await
+            .unwrap();
+        let received = inbound.recv().await.unwrap();
+        assert_eq!(received.peer, alice.peer_id());
+        match received.message {
+            ChannelMessage::Transition(signed) => {
+                assert_eq!(signed.transition.new_balance, 90);
+                assert!(signed.is_bound());
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+        assert!(alice
+            .connected_peers()
+            .await
+            .unwrap()
+            .contains(&bob.peer_id()));
+
+        // A channel routed to someone else turns Alice away.
+        let other = [8u8; 32];
+        alice.open_channel(other, bob.peer_id()).await.unwrap();
+        let _stranger = bob.open_channel(other, PeerId::random()).await.unwrap();
+        assert!(matches!(
+            alice.send(other, transition(other)).await,
+            Err(P2pError::Rejected(_))
+        ));
+    }
+}
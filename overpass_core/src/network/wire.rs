@@ -0,0 +1,673 @@
+// ./src/network/wire.rs
+
+use crate::zkp::helpers::Bytes32;
+use crate::zkp::state_proof::StateProof;
+use crate::zkp::watch_only::{SignedTransition, UnsignedTransition};
+use thiserror::Error;
+
+/// Longest variable-length field, e.g. transition metadata or an error text.
+const MAX_FIELD: usize = 64 * 1024;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum WireError {
+    #[error("Message is truncated")]
+    Truncated,
+    #[error("Message has {0} unexpected trailing bytes")]
+    TrailingBytes(usize),
+    #[error("Unknown required message type {0}")]
+    UnknownRequired(u16),
+    #[error("Message type {0} has unsupported version {1}")]
+    UnsupportedVersion(u16, u16),
+    #[error("Field of {0} bytes is too long")]
+    TooLong(usize),
+    #[error("Text field is not valid UTF-8")]
+    InvalidUtf8,
+    #[error("Peer requires unsupported feature bit {0}")]
+    UnsupportedFeature(u32),
+}
+
+/// Features a peer can offer. Each takes a pair of bits: the even one when the peer
+/// requires it, the odd one when it merely supports it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Feature {
+    /// Proofs of channel state are sent along with updates.
+    StateProofs = 0,
+    /// Payments may carry a routing fee for an intermediary.
+    RoutingFees = 1,
+    /// Channel keys may be rotated while the channel is open.
+    KeyRotation = 2,
+}
+
+const KNOWN_FEATURES: [Feature; 3] = [
+    Feature::StateProofs,
+    Feature::RoutingFees,
+    Feature::KeyRotation,
+];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Features(u64);
+
+impl Features {
+    pub fn from_bits(bits: u64) -> Self {
+        Self(bits)
+    }
+
+    pub fn bits(&self) -> u64 {
+        self.0
+    }
+
+    pub fn require(self, feature: Feature) -> Self {
+        Self(self.0 | 1 << (2 * feature as u32))
+    }
+
+    pub fn support(self, feature: Feature) -> Self {
+        Self(self.0 | 1 << (2 * feature as u32 + 1))
+    }
+
+    /// Checks whether either bit of the feature is set.
+    pub fn has(&self, feature: Feature) -> bool {
+        self.0 & 0b11 << (2 * feature as u32) != 0
+    }
+
+    /// Gets the features both sides can use, failing if the remote side requires one
+    /// this side does not know or offer.
+    pub fn negotiate(&self, remote: &Features) -> Result<Features, WireError> {
+        for bit in (0..64).step_by(2) {
+            let pair = 0b11 << bit;
+            if remote.0 & 1 << bit != 0 && self.0 & pair == 0 {
+                return Err(WireError::UnsupportedFeature(bit));
+            }
+        }
+        Ok(KNOWN_FEATURES
+            .iter()
+            .filter(|feature| self.has(**feature) && remote.has(**feature))
+            .fold(Features::default(), |common, feature| {
+                common.support(*feature)
+            }))
+    }
+}
+
+/// Writes fields in their canonical encoding: integers big-endian, variable-length
+/// fields after their length.
+#[derive(Default)]
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u16(&mut self, value: u16) {
+        self.0.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.0.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.0.extend_from_slice(bytes);
+    }
+
+    fn var_bytes(&mut self, bytes: &[u8]) {
+        self.0
+            .extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+        self.0.extend_from_slice(bytes);
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], WireError> {
+        if self.0.len() < len {
+            return Err(WireError::Truncated);
+        }
+        let (head, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(head)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], WireError> {
+        Ok(self.take(N)?.try_into().expect("took N bytes"))
+    }
+
+    fn u16(&mut self) -> Result<u16, WireError> {
+        Ok(u16::from_be_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, WireError> {
+        Ok(u64::from_be_bytes(self.array()?))
+    }
+
+    fn var_bytes(&mut self) -> Result<Vec<u8>, WireError> {
+        let len = u32::from_be_bytes(self.array()?) as usize;
+        if len > MAX_FIELD {
+            return Err(WireError::TooLong(len));
+        }
+        Ok(self.take(len)?.to_vec())
+    }
+
+    fn string(&mut self) -> Result<String, WireError> {
+        String::from_utf8(self.var_bytes()?).map_err(|_| WireError::InvalidUtf8)
+    }
+}
+
+/// A message body in its current version. Later versions may only append fields, which
+/// readers of an older version skip.
+trait Body: Sized {
+    const TYPE: u16;
+    const VERSION: u16;
+    fn write(&self, w: &mut Writer);
+    fn read(r: &mut Reader) -> Result<Self, WireError>;
+}
+
+/// Proposes a channel to a counterparty.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OpenChannel {
+    pub channel_id: Bytes32,
+    pub wallet_id: Bytes32,
+    /// Compressed secp256k1 funding key of the opener.
+    pub funding_key: [u8; 33],
+    /// Static Noise key the opener authenticates its sessions with.
+    pub transport_key: [u8; 32],
+    pub balance: u64,
+    pub features: Features,
+}
+
+impl Body for OpenChannel {
+    const TYPE: u16 = 32;
+    const VERSION: u16 = 1;
+
+    fn write(&self, w: &mut Writer) {
+        w.bytes(&self.channel_id);
+        w.bytes(&self.wallet_id);
+        w.bytes(&self.funding_key);
+        w.bytes(&self.transport_key);
+        w.u64(self.balance);
+        w.u64(self.features.bits());
+    }
+
+    fn read(r: &mut Reader) -> Result<Self, WireError> {
+        Ok(Self {
+            channel_id: r.array()?,
+            wallet_id: r.array()?,
+            funding_key: r.array()?,
+            transport_key: r.array()?,
+            balance: r.u64()?,
+            features: Features::from_bits(r.u64()?),
+        })
+    }
+}
+
+/// Accepts an `OpenChannel`, answering with the acceptor's keys and features.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AcceptChannel {
+    pub channel_id: Bytes32,
+    pub wallet_id: Bytes32,
+    pub funding_key: [u8; 33],
+    pub transport_key: [u8; 32],
+    pub features: Features,
+}
+
+impl Body for AcceptChannel {
+    const TYPE: u16 = 34;
+    const VERSION: u16 = 1;
+
+    fn write(&self, w: &mut Writer) {
+        w.bytes(&self.channel_id);
+        w.bytes(&self.wallet_id);
+        w.bytes(&self.funding_key);
+        w.bytes(&self.transport_key);
+        w.u64(self.features.bits());
+    }
+
+    fn read(r: &mut Reader) -> Result<Self, WireError> {
+        Ok(Self {
+            channel_id: r.array()?,
+            wallet_id: r.array()?,
+            funding_key: r.array()?,
+            transport_key: r.array()?,
+            features: Features::from_bits(r.u64()?),
+        })
+    }
+}
+
+fn write_proof(w: &mut Writer, proof: &StateProof) {
+    w.bytes(&proof.pi);
+    w.u16(proof.public_inputs.len() as u16);
+    for input in &proof.public_inputs {
+        w.bytes(input);
+    }
+    w.u64(proof.timestamp);
+}
+
+fn read_proof(r: &mut Reader) -> Result<StateProof, WireError> {
+    let pi = r.array()?;
+    let count = r.u16()?;
+    let public_inputs = (0..count)
+        .map(|_| r.array())
+        .collect::<Result<Vec<Bytes32>, _>>()?;
+    Ok(StateProof {
+        pi,
+        public_inputs,
+        timestamp: r.u64()?,
+    })
+}
+
+/// Proposes a signed update of a channel's state.
+#[derive(Clone, Debug)]
+pub struct UpdatePropose {
+    pub transition: SignedTransition,
+}
+
+impl Body for UpdatePropose {
+    const TYPE: u16 = 36;
+    const VERSION: u16 = 1;
+
+    fn write(&self, w: &mut Writer) {
+        let transition = &self.transition.transition;
+        w.bytes(&transition.channel_id);
+        w.bytes(&transition.wallet_id);
+        w.u64(transition.nonce);
+        w.bytes(&transition.state_hash);
+        w.bytes(&transition.old_commitment);
+        w.bytes(&transition.wallet_root);
+        w.u64(transition.new_balance);
+        w.var_bytes(&transition.metadata);
+        w.bytes(&self.transition.commitment);
+        write_proof(w, &self.transition.proof);
+    }
+
+    fn read(r: &mut Reader) -> Result<Self, WireError> {
+        let channel_id = r.array()?;
+        let transition = UnsignedTransition {
+            channel_id,
+            wallet_id: r.array()?,
+            nonce: r.u64()?,
+            state_hash: r.array()?,
+            old_commitment: r.array()?,
+            wallet_root: r.array()?,
+            new_balance: r.u64()?,
+            metadata: r.var_bytes()?,
+        };
+        Ok(Self {
+            transition: SignedTransition {
+                transition,
+                commitment: r.array()?,
+                proof: read_proof(r)?,
+            },
+        })
+    }
+}
+
+/// Acknowledges the update that produced the state at `nonce`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UpdateAck {
+    pub channel_id: Bytes32,
+    pub nonce: u64,
+    pub commitment: Bytes32,
+}
+
+impl Body for UpdateAck {
+    const TYPE: u16 = 38;
+    const VERSION: u16 = 1;
+
+    fn write(&self, w: &mut Writer) {
+        w.bytes(&self.channel_id);
+        w.u64(self.nonce);
+        w.bytes(&self.commitment);
+    }
+
+    fn read(r: &mut Reader) -> Result<Self, WireError> {
+        Ok(Self {
+            channel_id: r.array()?,
+            nonce: r.u64()?,
+            commitment: r.array()?,
+        })
+    }
+}
+
+/// Proves a channel's state at `nonce`, e.g. when asked to after reconnecting.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Proof {
+    pub channel_id: Bytes32,
+    pub nonce: u64,
+    pub proof: StateProof,
+}
+
+impl Body for Proof {
+    const TYPE: u16 = 40;
+    const VERSION: u16 = 1;
+
+    fn write(&self, w: &mut Writer) {
+        w.bytes(&self.channel_id);
+        w.u64(self.nonce);
+        write_proof(w, &self.proof);
+    }
+
+    fn read(r: &mut Reader) -> Result<Self, WireError> {
+        Ok(Self {
+            channel_id: r.array()?,
+            nonce: r.u64()?,
+            proof: read_proof(r)?,
+        })
+    }
+}
+
+/// Closes a channel at its final state.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Close {
+    pub channel_id: Bytes32,
+    pub final_nonce: u64,
+}
+
+impl Body for Close {
+    const TYPE: u16 = 42;
+    const VERSION: u16 = 1;
+
+    fn write(&self, w: &mut Writer) {
+        w.bytes(&self.channel_id);
+        w.u64(self.final_nonce);
+    }
+
+    fn read(r: &mut Reader) -> Result<Self, WireError> {
+        Ok(Self {
+            channel_id: r.array()?,
+            final_nonce: r.u64()?,
+        })
+    }
+}
+
+/// Reports a failure, about one channel or, without a channel, the whole connection.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PeerError {
+    pub channel_id: Option<Bytes32>,
+    pub message: String,
+}
+
+impl Body for PeerError {
+    const TYPE: u16 = 16;
+    const VERSION: u16 = 1;
+
+    fn write(&self, w: &mut Writer) {
+        // The all-zero id stands for the connection.
+        w.bytes(&self.channel_id.unwrap_or_default());
+        w.var_bytes(self.message.as_bytes());
+    }
+
+    fn read(r: &mut Reader) -> Result<Self, WireError> {
+        let channel_id: Bytes32 = r.array()?;
+        Ok(Self {
+            channel_id: (channel_id != [0u8; 32]).then_some(channel_id),
+            message: r.string()?,
+        })
+    }
+}
+
+/// Keeps a connection alive; answered with a `Pong` carrying the same nonce.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Ping {
+    pub nonce: u64,
+}
+
+impl Body for Ping {
+    const TYPE: u16 = 18;
+    const VERSION: u16 = 1;
+
+    fn write(&self, w: &mut Writer) {
+        w.u64(self.nonce);
+    }
+
+    fn read(r: &mut Reader) -> Result<Self, WireError> {
+        Ok(Self { nonce: r.u64()? })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Pong {
+    pub nonce: u64,
+}
+
+impl Body for Pong {
+    const TYPE: u16 = 20;
+    const VERSION: u16 = 1;
+
+    fn write(&self, w: &mut Writer) {
+        w.u64(self.nonce);
+    }
+
+    fn read(r: &mut Reader) -> Result<Self, WireError> {
+        Ok(Self { nonce: r.u64()? })
+    }
+}
+
+/// A peer message.
+///
+/// On the wire a message is its type and version, both `u16`, followed by its fields.
+/// Types are paired like feature bits: a peer must understand an even type, and may
+/// ignore an odd one it does not know.
+#[derive(Clone, Debug)]
+pub enum Message {
+    OpenChannel(OpenChannel),
+    AcceptChannel(AcceptChannel),
+    UpdatePropose(Box<UpdatePropose>),
+    UpdateAck(UpdateAck),
+    Proof(Proof),
+    Close(Close),
+    Error(PeerError),
+    Ping(Ping),
+    Pong(Pong),
+}
+
+/// What a received message decoded to.
+#[derive(Clone, Debug)]
+pub enum Decoded {
+    Message(Message),
+    /// An odd type this side does not know, safe to drop.
+    Ignored(u16),
+}
+
+fn encode_body<B: Body>(body: &B) -> Vec<u8> {
+    let mut w = Writer::default();
+    w.u16(B::TYPE);
+    w.u16(B::VERSION);
+    body.write(&mut w);
+    w.0
+}
+
+fn decode_body<B: Body>(version: u16, r: &mut Reader) -> Result<B, WireError> {
+    if version == 0 {
+        return Err(WireError::UnsupportedVersion(B::TYPE, version));
+    }
+    let body = B::read(r)?;
+    // Only a newer version may carry fields this side does not know.
+    if version <= B::VERSION && !r.0.is_empty() {
+        return Err(WireError::TrailingBytes(r.0.len()));
+    }
+    Ok(body)
+}
+
+impl Message {
+    pub fn message_type(&self) -> u16 {
+        match self {
+            Message::OpenChannel(_) => OpenChannel::TYPE,
+            Message::AcceptChannel(_) => AcceptChannel::TYPE,
+            Message::UpdatePropose(_) => UpdatePropose::TYPE,
+            Message::UpdateAck(_) => UpdateAck::TYPE,
+            Message::Proof(_) => Proof::TYPE,
+            Message::Close(_) => Close::TYPE,
+            Message::Error(_) => PeerError::TYPE,
+            Message::Ping(_) => Ping::TYPE,
+            Message::Pong(_) => Pong::TYPE,
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        match self {
+            Message::OpenChannel(body) => encode_body(body),
+            Message::AcceptChannel(body) => encode_body(body),
+            Message::UpdatePropose(body) => encode_body(body.as_ref()),
+            Message::UpdateAck(body) => encode_body(body),
+            Message::Proof(body) => encode_body(body),
+            Message::Close(body) => encode_body(body),
+            Message::Error(body) => encode_body(body),
+            Message::Ping(body) => encode_body(body),
+            Message::Pong(body) => encode_body(body),
+        }
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Decoded, WireError> {
+        let mut r = Reader(bytes);
+        let message_type = r.u16()?;
+        let version = r.u16()?;
+        let message = match message_type {
+            OpenChannel::TYPE => Message::OpenChannel(decode_body(version, &mut r)?),
+            AcceptChannel::TYPE => Message::AcceptChannel(decode_body(version, &mut r)?),
+            UpdatePropose::TYPE => Message::UpdatePropose(Box::new(decode_body(version, &mut r)?)),
+            UpdateAck::TYPE => Message::UpdateAck(decode_body(version, &mut r)?),
+            Proof::TYPE => Message::Proof(decode_body(version, &mut r)?),
+            Close::TYPE => Message::Close(decode_body(version, &mut r)?),
+            PeerError::TYPE => Message::Error(decode_body(version, &mut r)?),
+            Ping::TYPE => Message::Ping(decode_body(version, &mut r)?),
+            Pong::TYPE => Message::Pong(decode_body(version, &mut r)?),
+            unknown if unknown % 2 == 1 => return Ok(Decoded::Ignored(unknown)),
+            unknown => return Err(WireError::UnknownRequired(unknown)),
+        };
+        Ok(Decoded::Message(message))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proof() -> StateProof {
+        StateProof {
+            pi: [6u8; 32],
+            public_inputs: vec![[3u8; 32], [5u8; 32], [4u8; 32]],
+            timestamp: 1_700_000_000,
+        }
+    }
+
+    fn messages() -> Vec<Message> {
+        let features = Features::default()
+            .require(Feature::StateProofs)
+            .support(Feature::RoutingFees);
+        vec![
+            Message::OpenChannel(OpenChannel {
+                channel_id: [1u8; 32],
+                wallet_id: [2u8; 32],
+                funding_key: [3u8; 33],
+                transport_key: [4u8; 32],
+                balance: 100,
+                features,
+            }),
+            Message::AcceptChannel(AcceptChannel {
+                channel_id: [1u8; 32],
+                wallet_id: [5u8; 32],
+                funding_key: [6u8; 33],
+                transport_key: [7u8; 32],
+                features,
+            }),
+            Message::UpdatePropose(Box::new(UpdatePropose {
+                transition: SignedTransition {
+                    transition: UnsignedTransition {
+                        wallet_id: [2u8; 32],
+                        channel_id: [1u8; 32],
+                        nonce: 4,
+                        state_hash: [8u8; 32],
+                        old_commitment: [3u8; 32],
+                        wallet_root: [4u8; 32],
+                        new_balance: 90,
+                        metadata: b"memo".to_vec(),
+                    },
+                    commitment: [5u8; 32],
+                    proof: proof(),
+                },
+            })),
+            Message::UpdateAck(UpdateAck {
+                channel_id: [1u8; 32],
+                nonce: 5,
+                commitment: [5u8; 32],
+            }),
+            Message::Proof(Proof {
+                channel_id: [1u8; 32],
+                nonce: 5,
+                proof: proof(),
+            }),
+            Message::Close(Close {
+                channel_id: [1u8; 32],
+                final_nonce: 5,
+            }),
+            Message::Error(PeerError {
+                channel_id: None,
+                message: "unknown channel".into(),
+            }),
+            Message::Ping(Ping { nonce: 9 }),
+            Message::Pong(Pong { nonce: 9 }),
+        ]
+    }
+
+    #[test]
+    fn test_messages_round_trip_canonically() {
+        for message in messages() {
+            let bytes = message.encode();
+            let Decoded::Message(decoded) = Message::decode(&bytes).unwrap() else {
+                panic!("message type {} was ignored", message.message_type());
+            };
+            assert_eq!(decoded.message_type(), message.message_type());
+            assert_eq!(decoded.encode(), bytes);
+
+            assert_eq!(
+                Message::decode(&bytes[..bytes.len() - 1]).unwrap_err(),
+                WireError::Truncated
+            );
+            let mut extended = bytes.clone();
+            extended.push(0);
+            assert_eq!(
+                Message::decode(&extended).unwrap_err(),
+                WireError::TrailingBytes(1)
+            );
+            // A newer version's appended fields are skipped.
+            extended[3] += 1;
+            assert!(Message::decode(&extended).is_ok());
+        }
+
+        let unknown_odd = [0, 101, 0, 1, 0xff];
+        assert!(matches!(
+            Message::decode(&unknown_odd),
+            Ok(Decoded::Ignored(101))
+        ));
+        let unknown_even = [0, 100, 0, 1];
+        assert_eq!(
+            Message::decode(&unknown_even).unwrap_err(),
+            WireError::UnknownRequired(100)
+        );
+    }
+
+    #[test]
+    fn test_features_negotiate() {
+        let local = Features::default()
+            .require(Feature::StateProofs)
+            .support(Feature::KeyRotation);
+        let remote = Features::default()
+            .support(Feature::StateProofs)
+            .support(Feature::RoutingFees)
+            .support(Feature::KeyRotation);
+        let common = local.negotiate(&remote).unwrap();
+        assert!(common.has(Feature::StateProofs));
+        assert!(common.has(Feature::KeyRotation));
+        assert!(!common.has(Feature::RoutingFees));
+        assert_eq!(remote.negotiate(&local), Ok(common));
+
+        // An unknown optional feature is ignored, an unknown required one is not.
+        let future = remote.bits() | 1 << 41;
+        assert!(local.negotiate(&Features::from_bits(future)).is_ok());
+        let future = remote.bits() | 1 << 40;
+        assert_eq!(
+            local.negotiate(&Features::from_bits(future)),
+            Err(WireError::UnsupportedFeature(40))
+        );
+        assert_eq!(
+            Features::default().negotiate(&local),
+            Err(WireError::UnsupportedFeature(0))
+        );
+    }
+}
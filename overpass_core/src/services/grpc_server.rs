@@ -0,0 +1,582 @@
+// ./src/services/grpc_server.rs
+
+use crate::services::server::{AuthToken, ServeError, ServeOptions};
+use crate::zkp::channel::ChannelState;
+use crate::zkp::helpers::Bytes32;
+use crate::zkp::payment::{Payment, PaymentStatus};
+use crate::zkp::wallet_audit::{AuditRecord, WalletEvent};
+use crate::zkp::wallet_contract::{WalletContract, WalletContractError};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc};
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tokio_stream::wrappers::{BroadcastStream, ReceiverStream};
+use tokio_stream::{Stream, StreamExt};
+use tonic::{Request, Response, Status};
+
+/// Messages and service stubs generated from `proto/overpass.proto`.
+pub mod proto {
+    tonic::include_proto!("overpass.v1");
+}
+
+use proto::wallet_server::WalletServer;
+
+/// Wallet events a subscriber can fall behind by before its stream ends.
+const EVENT_CAPACITY: usize = 256;
+/// How often a watched payment is checked for having expired.
+const PAYMENT_POLL: Duration = Duration::from_secs(1);
+
+type GrpcStream<T> = Pin<Box<dyn Stream<Item = Result<T, Status>> + Send>>;
+
+fn status(error: WalletContractError) -> Status {
+    let message = error.to_string();
+    match error {
+        WalletContractError::ChannelNotFound => Status::not_found(message),
+        WalletContractError::InsufficientFunds(_)
+        | WalletContractError::DeadlinePassed(_)
+        | WalletContractError::UnrecordedRoot
+        | WalletContractError::NoKeyManager
+        | WalletContractError::WatchOnly
+        | WalletContractError::Locked => Status::failed_precondition(message),
+        WalletContractError::PolicyError(_) => Status::permission_denied(message),
+        WalletContractError::InvalidTransition(_)
+        | WalletContractError::CounterpartyMismatch(_) => Status::invalid_argument(message),
+        _ => Status::internal(message),
+    }
+}
+
+/// A request field that should hold 32 bytes but does not.
+struct NotBytes32(&'static str);
+
+impl From<NotBytes32> for Status {
+    fn from(NotBytes32(field): NotBytes32) -> Self {
+        Status::invalid_argument(format!("{} must be 32 bytes", field))
+    }
+}
+
+fn bytes32(field: &'static str, bytes: &[u8]) -> Result<Bytes32, NotBytes32> {
+    bytes.try_into().map_err(|_| NotBytes32(field))
+}
+
+fn proof_json(proof: &impl serde::Serialize) -> Result<proto::Proof, serde_json::Error> {
+    serde_json::to_vec(proof).map(|json| proto::Proof { json })
+}
+
+fn channel(channel_id: &Bytes32, state: &ChannelState) -> proto::Channel {
+    proto::Channel {
+        channel_id: channel_id.to_vec(),
+        balances: state.balances.clone(),
+        nonce: state.nonce,
+        commitment: state.merkle_root.to_vec(),
+    }
+}
+
+fn payment(payment: &Payment) -> proto::Payment {
+    let status = match payment.status {
+        PaymentStatus::Pending => proto::PaymentStatus::Pending,
+        PaymentStatus::Recorded => proto::PaymentStatus::Recorded,
+        PaymentStatus::Expired => proto::PaymentStatus::Expired,
+    };
+    proto::Payment {
+        payment_id: payment.id.to_vec(),
+        channel_id: payment.channel_id.to_vec(),
+        amount: payment.amount,
+        routing_fee: payment.routing_fee,
+        nonce: payment.nonce,
+        status: status.into(),
+    }
+}
+
+fn wallet_event(record: &AuditRecord) -> proto::WalletEvent {
+    use proto::wallet_event::Event;
+    let event = match &record.event {
+        WalletEvent::ChannelOpened {
+            channel_id,
+            counterparty,
+            balance,
+        } => Event::ChannelOpened(proto::ChannelOpened {
+            channel_id: channel_id.to_vec(),
+            counterparty: counterparty.to_vec(),
+            balance: *balance,
+        }),
+        WalletEvent::ChannelClosed {
+            channel_id,
+            balance,
+        } => Event::ChannelClosed(proto::ChannelClosed {
+            channel_id: channel_id.to_vec(),
+            balance: *balance,
+        }),
+        WalletEvent::PaymentSent {
+            payment_id,
+            channel_id,
+            amount,
+        } => Event::PaymentSent(proto::PaymentSent {
+            payment_id: payment_id.to_vec(),
+            channel_id: channel_id.to_vec(),
+            amount: *amount,
+        }),
+        WalletEvent::PaymentReceived { channel_id, amount } => {
+            Event::PaymentReceived(proto::PaymentReceived {
+                channel_id: channel_id.to_vec(),
+                amount: *amount,
+            })
+        }
+        WalletEvent::KeyRotated {
+            channel_id,
+            generation,
+        } => Event::KeyRotated(proto::KeyRotated {
+            channel_id: channel_id.to_vec(),
+            generation: *generation,
+        }),
+        WalletEvent::PolicyChanged {
+            daily_limit,
+            confirm_above,
+            ..
+        } => Event::PolicyChanged(proto::PolicyChanged {
+            daily_limit: *daily_limit,
+            confirm_above: *confirm_above,
+        }),
+    };
+    proto::WalletEvent {
+        sequence: record.sequence,
+        timestamp: record.timestamp,
+        hash: record.hash.to_vec(),
+        event: Some(event),
+    }
+}
+
+// tonic fixes the item type of a response stream.
+#[allow(clippy::result_large_err)]
+fn streamed_event(
+    record: Result<AuditRecord, BroadcastStreamRecvError>,
+) -> Result<proto::WalletEvent, Status> {
+    match record {
+        Ok(record) => Ok(wallet_event(&record)),
+        // Ending the stream tells the subscriber to re-read the audit log.
+        Err(BroadcastStreamRecvError::Lagged(missed)) => Err(Status::data_loss(format!(
+            "Missed {} wallet events",
+            missed
+        ))),
+    }
+}
+
+struct Published {
+    wallet: WalletContract,
+    /// Audit records already sent to subscribers.
+    sent: usize,
+}
+
+/// gRPC interface to one wallet, with channel events read from its audit log.
+#[derive(Clone)]
+pub struct GrpcServer {
+    inner: Arc<Mutex<Published>>,
+    events: broadcast::Sender<AuditRecord>,
+}
+
+impl GrpcServer {
+    pub fn new(wallet: WalletContract) -> Self {
+        let sent = wallet.audit_log().len();
+        Self {
+            inner: Arc::new(Mutex::new(Published { wallet, sent })),
+            events: broadcast::channel(EVENT_CAPACITY).0,
+        }
+    }
+
+    fn lock(inner: &Mutex<Published>) -> MutexGuard<'_, Published> {
+        inner.lock().expect("wallet lock poisoned")
+    }
+
+    /// Runs `call` on the wallet off the async runtime, then sends subscribers whatever
+    /// it recorded, even if it went on to fail.
+    async fn with_wallet<T, F>(&self, call: F) -> Result<T, Status>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut WalletContract) -> Result<T, WalletContractError> + Send + 'static,
+    {
+        let (inner, events) = (self.inner.clone(), self.events.clone());
+        tokio::task::spawn_blocking(move || {
+            let mut inner = Self::lock(&inner);
+            let Published { wallet, sent } = &mut *inner;
+            let result = call(wallet);
+            let records = wallet.audit_log().records();
+            for record in &records[*sent..] {
+                // No subscribers is not an error.
+                let _ = events.send(record.clone());
+            }
+            *sent = records.len();
+            result
+        })
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?
+        .map_err(status)
+    }
+}
+
+#[tonic::async_trait]
+impl proto::wallet_server::Wallet for GrpcServer {
+    async fn open_channel(
+        &self,
+        request: Request<proto::OpenChannelRequest>,
+    ) -> Result<Response<proto::OpenChannelResponse>, Status> {
+        let request = request.into_inner();
+        let counterparty = bytes32("counterparty", &request.counterparty)?;
+        let channel_id = self
+            .with_wallet(move |wallet| {
+                wallet.open_channel(request.balance, counterparty, Vec::new())
+            })
+            .await?;
+        Ok(Response::new(proto::OpenChannelResponse {
+            channel_id: channel_id.to_vec(),
+        }))
+    }
+
+    async fn pay(
+        &self,
+        request: Request<proto::PayRequest>,
+    ) -> Result<Response<proto::Payment>, Status> {
+        let request = request.into_inner();
+        let counterparty = bytes32("counterparty", &request.counterparty)?;
+        self.with_wallet(move |wallet| {
+            let mut builder = wallet.pay(counterparty, request.amount);
+            if let Some(memo) = request.memo {
+                builder = builder.with_memo(memo);
+            }
+            if let Some(fee) = request.routing_fee {
+                builder = builder.with_routing_fee(fee);
+            }
+            builder.send()
+        })
+        .await
+        .map(|sent| Response::new(payment(&sent)))
+    }
+
+    async fn close_channel(
+        &self,
+        request: Request<proto::ChannelRequest>,
+    ) -> Result<Response<proto::Channel>, Status> {
+        let channel_id = bytes32("channel_id", &request.into_inner().channel_id)?;
+        let state = self
+            .with_wallet(move |wallet| wallet.close_channel(&channel_id))
+            .await?;
+        Ok(Response::new(channel(&channel_id, &state)))
+    }
+
+    async fn get_channel(
+        &self,
+        request: Request<proto::ChannelRequest>,
+    ) -> Result<Response<proto::Channel>, Status> {
+        let channel_id = bytes32("channel_id", &request.into_inner().channel_id)?;
+        self.with_wallet(move |wallet| {
+            wallet
+                .get_channel(&channel_id)
+                .map(|state| channel(&channel_id, state))
+                .ok_or(WalletContractError::ChannelNotFound)
+        })
+        .await
+        .map(Response::new)
+    }
+
+    async fn list_channels(
+        &self,
+        _request: Request<proto::Empty>,
+    ) -> Result<Response<proto::ChannelList>, Status> {
+        let mut channel_ids = self
+            .with_wallet(|wallet| Ok(wallet.list_channels()))
+            .await?;
+        channel_ids.sort_unstable();
+        Ok(Response::new(proto::ChannelList {
+            channel_ids: channel_ids.iter().map(|id| id.to_vec()).collect(),
+        }))
+    }
+
+    async fn get_balance(
+        &self,
+        _request: Request<proto::Empty>,
+    ) -> Result<Response<proto::Balance>, Status> {
+        let balance = self.with_wallet(|wallet| Ok(wallet.balance())).await?;
+        Ok(Response::new(proto::Balance {
+            open: balance.open,
+            closed: balance.closed,
+            open_channels: balance.open_channels as u64,
+        }))
+    }
+
+    async fn channel_history(
+        &self,
+        request: Request<proto::ChannelRequest>,
+    ) -> Result<Response<proto::History>, Status> {
+        let channel_id = bytes32("channel_id", &request.into_inner().channel_id)?;
+        let summary = self
+            .with_wallet(move |wallet| wallet.replay_channel(&channel_id))
+            .await?
+            .summary;
+        Ok(Response::new(proto::History {
+            records: summary.records as u64,
+            updates: summary.updates as u64,
+            head: summary.head.map(|head| head.to_vec()),
+            last_timestamp: summary.last_timestamp,
+        }))
+    }
+
+    async fn proof_of_funds(
+        &self,
+        request: Request<proto::ProofOfFundsRequest>,
+    ) -> Result<Response<proto::Proof>, Status> {
+        let request = request.into_inner();
+        let proof = self
+            .with_wallet(move |wallet| wallet.proof_of_funds(request.minimum, &request.context))
+            .await?;
+        let proof = proof_json(&proof).map_err(|e| Status::internal(e.to_string()))?;
+        Ok(Response::new(proof))
+    }
+
+    async fn wallet_root_proof(
+        &self,
+        _request: Request<proto::Empty>,
+    ) -> Result<Response<proto::Proof>, Status> {
+        let proof = self
+            .with_wallet(|wallet| wallet.wallet_root_proof())
+            .await?;
+        let proof = proof_json(&proof).map_err(|e| Status::internal(e.to_string()))?;
+        Ok(Response::new(proof))
+    }
+
+    async fn rotate_keys(
+        &self,
+        _request: Request<proto::Empty>,
+    ) -> Result<Response<proto::Empty>, Status> {
+        self.with_wallet(|wallet| wallet.rotate_keys()).await?;
+        Ok(Response::new(proto::Empty {}))
+    }
+
+    async fn publish_root(
+        &self,
+        request: Request<proto::PublishRootRequest>,
+    ) -> Result<Response<proto::Empty>, Status> {
+        let height = request.into_inner().height;
+        self.with_wallet(move |wallet| wallet.repair_against_global(height))
+            .await?;
+        Ok(Response::new(proto::Empty {}))
+    }
+
+    type SubscribeChannelEventsStream = GrpcStream<proto::WalletEvent>;
+
+    async fn subscribe_channel_events(
+        &self,
+        _request: Request<proto::Empty>,
+    ) -> Result<Response<Self::SubscribeChannelEventsStream>, Status> {
+        let events = BroadcastStream::new(self.events.subscribe()).map(streamed_event);
+        Ok(Response::new(Box::pin(events)))
+    }
+
+    type WatchPaymentStream = GrpcStream<proto::Payment>;
+
+    async fn watch_payment(
+        &self,
+        request: Request<proto::PaymentRequest>,
+    ) -> Result<Response<Self::WatchPaymentStream>, Status> {
+        let payment_id = bytes32("payment_id", &request.into_inner().payment_id)?;
+        let lookup = {
+            let inner = self.inner.clone();
+            move || Self::lock(&inner).wallet.payment(&payment_id)
+        };
+        if lookup().is_none() {
+            return Err(Status::not_found("Unknown payment"));
+        }
+
+        let (sender, receiver) = mpsc::channel(4);
+        let mut events = self.events.subscribe();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(PAYMENT_POLL);
+            let mut last = None;
+            while let Some(current) = lookup() {
+                if last != Some(current.status) {
+                    last = Some(current.status);
+                    if sender.send(Ok(payment(&current))).await.is_err() {
+                        return;
+                    }
+                }
+                if current.status != PaymentStatus::Pending {
+                    return;
+                }
+                // Any wallet event may have recorded the payment; expiry needs the clock.
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = events.recv() => {}
+                }
+            }
+        });
+        Ok(Response::new(Box::pin(ReceiverStream::new(receiver))))
+    }
+}
+
+/// Interceptor refusing calls whose `authorization` metadata lacks the token.
+// tonic fixes the error type of an interceptor.
+#[allow(clippy::result_large_err)]
+fn authorize(token: &AuthToken, request: Request<()>) -> Result<Request<()>, Status> {
+    let header = request
+        .metadata()
+        .get("authorization")
+        .and_then(|value| value.to_str().ok());
+    if token.authorizes(header) {
+        Ok(request)
+    } else {
+        Err(Status::unauthenticated("Missing or wrong auth token"))
+    }
+}
+
+/// Serves the wallet's gRPC API as `options` set out until the server fails.
+pub async fn serve(server: GrpcServer, options: ServeOptions) -> Result<(), ServeError> {
+    options.check()?;
+    let token = options.token().clone();
+    tonic::transport::Server::builder()
+        .add_service(WalletServer::with_interceptor(server, move |request| {
+            authorize(&token, request)
+        }))
+        .serve(options.addr())
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::proto::wallet_server::Wallet;
+    use super::*;
+    use crate::test_util;
+
+    fn server() -> GrpcServer {
+        GrpcServer::new(test_util::wallet([1u8; 32]))
+    }
+
+    fn channel_request(channel_id: &[u8]) -> Request<proto::ChannelRequest> {
+        Request::new(proto::ChannelRequest {
+            channel_id: channel_id.to_vec(),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_wallet_calls_and_event_stream() -> anyhow::Result<()> {
+        let server = server();
+        let mut events = server
+            .subscribe_channel_events(Request::new(proto::Empty {}))
+            .await?
+            .into_inner();
+
+        let channel_id = server
+            .open_channel(Request::new(proto::OpenChannelRequest {
+                balance: 100,
+                counterparty: vec![7u8; 32],
+            }))
+            .await?
+            .into_inner()
+            .channel_id;
+        server.rotate_keys(Request::new(proto::Empty {})).await?;
+        let channel = server
+            .get_channel(channel_request(&channel_id))
+            .await?
+            .into_inner();
+        assert_eq!(channel.balances[0], 100);
+        let history = server
+            .channel_history(channel_request(&channel_id))
+            .await?
+            .into_inner();
+        assert_eq!(history.head, Some(channel.commitment));
+
+        server
+            .publish_root(Request::new(proto::PublishRootRequest { height: 1 }))
+            .await?;
+        let proof = server
+            .proof_of_funds(Request::new(proto::ProofOfFundsRequest {
+                minimum: 60,
+                context: b"landlord".to_vec(),
+            }))
+            .await?
+            .into_inner();
+        let proof: serde_json::Value = serde_json::from_slice(&proof.json).unwrap();
+        assert_eq!(proof["minimum"], 60);
+        server.close_channel(channel_request(&channel_id)).await?;
+
+        let mut kinds = Vec::new();
+        for _ in 0..3 {
+            match events.next().await.unwrap()?.event {
+                Some(proto::wallet_event::Event::ChannelOpened(opened)) => {
+                    assert_eq!(opened.channel_id, channel_id);
+                    kinds.push("opened");
+                }
+                Some(proto::wallet_event::Event::KeyRotated(_)) => kinds.push("rotated"),
+                Some(proto::wallet_event::Event::ChannelClosed(closed)) => {
+                    assert_eq!(closed.balance, 100);
+                    kinds.push("closed");
+                }
+                other => panic!("Unexpected event {:?}", other),
+            }
+        }
+        assert_eq!(kinds, ["opened", "rotated", "closed"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_calls_need_the_token() {
+        let token = AuthToken::generate();
+        let mut request = Request::new(());
+        assert_eq!(
+            authorize(&token, Request::new(())).unwrap_err().code(),
+            tonic::Code::Unauthenticated
+        );
+        request
+            .metadata_mut()
+            .insert("authorization", "Bearer 00".parse().unwrap());
+        assert!(authorize(&token, request).is_err());
+
+        let mut request = Request::new(());
+        request
+            .metadata_mut()
+            .insert("authorization", token.bearer().parse().unwrap());
+        assert!(authorize(&token, request).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_serve_refuses_remote_addresses() {
+        let options = ServeOptions::local(0).with_addr("0.0.0.0:0".parse().unwrap());
+        assert!(matches!(
+            serve(server(), options).await,
+            Err(ServeError::NonLoopback(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_errors_map_to_status_codes() {
+        let server = server();
+
+        assert_eq!(
+            server
+                .get_channel(channel_request(&[1u8; 4]))
+                .await
+                .unwrap_err()
+                .code(),
+            tonic::Code::InvalidArgument
+        );
+        assert_eq!(
+            server
+                .close_channel(channel_request(&[9u8; 32]))
+                .await
+                .unwrap_err()
+                .code(),
+            tonic::Code::NotFound
+        );
+        let proof = server
+            .proof_of_funds(Request::new(proto::ProofOfFundsRequest {
+                minimum: 1,
+                context: Vec::new(),
+            }))
+            .await;
+        assert!(proof.is_err());
+        let watched = server
+            .watch_payment(Request::new(proto::PaymentRequest {
+                payment_id: vec![3u8; 32],
+            }))
+            .await;
+        assert_eq!(watched.err().map(|e| e.code()), Some(tonic::Code::NotFound));
+    }
+}
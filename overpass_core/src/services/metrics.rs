@@ -0,0 +1,229 @@
+// ./src/services/metrics.rs
+
+use crate::services::overpass_db::OverpassDB;
+use crate::services::server::{bind, ServeError};
+use crate::zkp::wallet_contract::WalletContract;
+use axum::{
+    extract::State,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramTimer, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec,
+    Opts, Registry, TextEncoder,
+};
+use std::net::SocketAddr;
+use std::sync::{Mutex, OnceLock};
+
+type ScrapeHook = Box<dyn Fn(&Metrics) + Send + Sync>;
+
+/// Counters and gauges of one wallet process, exported in the Prometheus text format.
+pub struct Metrics {
+    registry: Registry,
+    proof_seconds: HistogramVec,
+    verifications: IntCounterVec,
+    messages: IntCounterVec,
+    errors: IntCounterVec,
+    storage_bytes: IntGaugeVec,
+    open_channels: IntGauge,
+    /// Run before every export to sample what is cheaper to read than to track.
+    hooks: Mutex<Vec<ScrapeHook>>,
+}
+
+impl Metrics {
+    pub fn new() -> Result<Self, prometheus::Error> {
+        let registry = Registry::new_custom(Some("overpass".into()), None)?;
+        let proof_seconds = HistogramVec::new(
+            HistogramOpts::new("proof_seconds", "Time spent generating proofs")
+                .buckets(prometheus::exponential_buckets(0.0001, 4.0, 10)?),
+            &["kind"],
+        )?;
+        let verifications = IntCounterVec::new(
+            Opts::new("verifications_total", "Proofs verified, by outcome"),
+            &["kind", "outcome"],
+        )?;
+        let messages = IntCounterVec::new(
+            Opts::new("messages_total", "Channel messages sent and received"),
+            &["transport", "direction"],
+        )?;
+        let errors = IntCounterVec::new(
+            Opts::new("errors_total", "Failed requests and dropped messages"),
+            &["source"],
+        )?;
+        let storage_bytes = IntGaugeVec::new(
+            Opts::new("storage_bytes", "Size of each database on disk"),
+            &["database"],
+        )?;
+        let open_channels = IntGauge::new("open_channels", "Channels the wallet has open")?;
+        registry.register(Box::new(proof_seconds.clone()))?;
+        registry.register(Box::new(verifications.clone()))?;
+        registry.register(Box::new(messages.clone()))?;
+        registry.register(Box::new(errors.clone()))?;
+        registry.register(Box::new(storage_bytes.clone()))?;
+        registry.register(Box::new(open_channels.clone()))?;
+        Ok(Self {
+            registry,
+            proof_seconds,
+            verifications,
+            messages,
+            errors,
+            storage_bytes,
+            open_channels,
+            hooks: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Starts timing a proof; it is recorded when the timer drops.
+    pub fn proof_timer(&self, kind: &str) -> HistogramTimer {
+        self.proof_seconds.with_label_values(&[kind]).start_timer()
+    }
+
+    pub fn record_verification(&self, kind: &str, valid: bool) {
+        let outcome = if valid { "valid" } else { "invalid" };
+        self.verifications.with_label_values(&[kind, outcome]).inc();
+    }
+
+    /// Counts a message over `transport`, `direction` being `sent` or `received`.
+    pub fn record_message(&self, transport: &str, direction: &str) {
+        self.messages
+            .with_label_values(&[transport, direction])
+            .inc();
+    }
+
+    pub fn record_error(&self, source: &str) {
+        self.errors.with_label_values(&[source]).inc();
+    }
+
+    pub fn observe_storage(&self, name: &str, db: &OverpassDB) {
+        if let Ok(size) = db.size_on_disk() {
+            self.storage_bytes
+                .with_label_values(&[name])
+                .set(size.min(i64::MAX as u64) as i64);
+        }
+    }
+
+    pub fn observe_wallet(&self, wallet: &WalletContract) {
+        self.open_channels.set(wallet.list_channels().len() as i64);
+    }
+
+    /// Runs `hook` before every export, e.g. to observe a wallet and its database.
+    pub fn on_scrape(&self, hook: impl Fn(&Metrics) + Send + Sync + 'static) {
+        self.hooks
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push(Box::new(hook));
+    }
+
+    /// Exports every metric in the Prometheus text format.
+    pub fn encode(&self) -> String {
+        for hook in self
+            .hooks
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .iter()
+        {
+            hook(self);
+        }
+        let mut text = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut text)
+            .expect("metric families encode as text");
+        String::from_utf8(text).expect("the text format is UTF-8")
+    }
+}
+
+/// Gets the metrics the core records proofs, verifications and messages into, which a
+/// daemon exports.
+pub fn global() -> &'static Metrics {
+    static GLOBAL: OnceLock<Metrics> = OnceLock::new();
+    GLOBAL.get_or_init(|| Metrics::new().expect("metric definitions are valid"))
+}
+
+/// Exports the metrics, failing the scrape rather than answering with an empty page when
+/// the export does not finish.
+async fn metrics_handler(State(metrics): State<&'static Metrics>) -> Response {
+    match tokio::task::spawn_blocking(move || metrics.encode()).await {
+        Ok(text) => ([(header::CONTENT_TYPE, prometheus::TEXT_FORMAT)], text).into_response(),
+        Err(e) => {
+            tracing::error!("Metrics export failed: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+    }
+}
+
+/// Builds the endpoint Prometheus scrapes at `/metrics`.
+pub fn router(metrics: &'static Metrics) -> Router {
+    Router::new()
+        .route("/metrics", get(metrics_handler))
+        .with_state(metrics)
+}
+
+/// Serves the metrics endpoint on `addr` until the listener fails.
+pub async fn serve(metrics: &'static Metrics, addr: SocketAddr) -> Result<(), ServeError> {
+    let listener = bind(addr).await?;
+    axum::serve(listener, router(metrics)).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+    use crate::zkp::helpers::{generate_state_proof, verify_zk_proof};
+    use crate::zkp::pedersen_parameters::PedersenParameters;
+    use std::sync::Arc;
+
+    /// Reads the value of the sample on the line starting with `series`.
+    fn sample(text: &str, series: &str) -> f64 {
+        text.lines()
+            .find_map(|line| line.strip_prefix(series)?.trim().parse().ok())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn test_core_records_into_global_metrics() {
+        let params = PedersenParameters::default();
+        let before = global().encode();
+        let proof = generate_state_proof([1u8; 32], [2u8; 32], [3u8; 32], &params);
+        assert!(!verify_zk_proof(&proof.pi, &proof.public_inputs, &params));
+        let after = global().encode();
+
+        let proofs = r#"overpass_proof_seconds_count{kind="state"}"#;
+        assert!(sample(&after, proofs) >= sample(&before, proofs) + 1.0);
+        let rejected = r#"overpass_verifications_total{kind="state",outcome="invalid"}"#;
+        assert!(sample(&after, rejected) >= sample(&before, rejected) + 1.0);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_endpoint_exports_observed_state() {
+        let mut wallet = test_util::wallet([1u8; 32]);
+        wallet.open_channel(100, [2u8; 32], Vec::new()).unwrap();
+        let wallet = Arc::new(Mutex::new(wallet));
+
+        let metrics: &'static Metrics = Box::leak(Box::new(Metrics::new().unwrap()));
+        metrics.on_scrape(move |metrics| metrics.observe_wallet(&wallet.lock().unwrap()));
+        metrics.record_message("p2p", "sent");
+        metrics.record_error("rpc");
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/metrics", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, router(metrics)).await.unwrap() });
+        let text = tokio::task::spawn_blocking(move || {
+            ureq::get(&url).call().unwrap().into_string().unwrap()
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(sample(&text, "overpass_open_channels"), 1.0);
+        assert_eq!(
+            sample(
+                &text,
+                r#"overpass_messages_total{direction="sent",transport="p2p"}"#
+            ),
+            1.0
+        );
+        assert_eq!(sample(&text, r#"overpass_errors_total{source="rpc"}"#), 1.0);
+    }
+}
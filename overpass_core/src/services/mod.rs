@@ -1,4 +1,18 @@
 // ,./src/services/mod.rs
 
+#[cfg(feature = "grpc")]
+pub mod grpc_server;
+#[cfg(feature = "metrics")]
+pub mod metrics;
 pub mod overpass;
 pub mod overpass_db;
+#[cfg(feature = "rest")]
+pub mod rest_server;
+#[cfg(feature = "rpc")]
+pub mod rpc_server;
+#[cfg(feature = "networking")]
+pub mod server;
+#[cfg(feature = "prover")]
+pub mod storage_migration;
+#[cfg(feature = "networking")]
+pub mod watch_service;
@@ -1,8 +1,47 @@
 // ./src/services/overpass_db.rs
+#[cfg(feature = "storage-sled")]
 use sled::{self, Db};
-use anyhow::{Result, Context};
+#[cfg(all(feature = "storage-sqlite", not(feature = "storage-sled")))]
+use sqlite::Db;
+#[cfg(not(any(feature = "storage-sled", feature = "storage-sqlite")))]
+use unavailable::Db;
+use thiserror::Error;
 
-/// Wrapper around the sled database for managing Overpass states and transactions.
+#[cfg(feature = "tokio")]
+use crate::utils::blocking::run_blocking;
+
+#[cfg(feature = "storage-sled")]
+type EngineError = sled::Error;
+#[cfg(all(feature = "storage-sqlite", not(feature = "storage-sled")))]
+type EngineError = sqlite::Error;
+#[cfg(not(any(feature = "storage-sled", feature = "storage-sqlite")))]
+type EngineError = std::convert::Infallible;
+
+/// A database operation that failed, with the storage engine error behind it.
+#[derive(Error, Debug)]
+pub enum DbError {
+    #[error("No storage engine is built in; enable the storage-sled or storage-sqlite feature")]
+    NoBackend,
+    #[error("Failed to open database")]
+    Open(#[source] EngineError),
+    #[error("Database {operation} operation failed")]
+    Operation {
+        operation: &'static str,
+        #[source]
+        source: EngineError,
+    },
+}
+
+type Result<T> = std::result::Result<T, DbError>;
+
+fn operation(operation: &'static str) -> impl FnOnce(EngineError) -> DbError {
+    move |source| DbError::Operation { operation, source }
+}
+
+/// Wrapper around the storage engine for managing Overpass states and transactions.
+///
+/// The engine is sled with the `storage-sled` feature, or else SQLite with
+/// `storage-sqlite`. A build with neither cannot open a database.
 pub struct OverpassDB {
     db: Db,
 }
@@ -16,9 +55,15 @@ impl OverpassDB {
     /// 
     /// # Returns
     /// 
-    /// Result containing the OverpassDB instance or an error if the database cannot be opened.
+    /// Result containing the OverpassDB instance or an error if the database cannot be opened,
+    /// including `DbError::NoBackend` when no storage engine is built in.
     pub fn new(path: &str) -> Result<Self> {
-        let db = sled::open(path).context("Failed to open database")?;
+        #[cfg(feature = "storage-sled")]
+        let db = sled::open(path).map_err(DbError::Open)?;
+        #[cfg(all(feature = "storage-sqlite", not(feature = "storage-sled")))]
+        let db = Db::open(path).map_err(DbError::Open)?;
+        #[cfg(not(any(feature = "storage-sled", feature = "storage-sqlite")))]
+        let db = Db::open(path)?;
         Ok(Self { db })
     }
 
@@ -35,7 +80,7 @@ impl OverpassDB {
     pub fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
         self.db
             .get(key)
-            .context("Database get operation failed")
+            .map_err(operation("get"))
             .map(|opt| opt.map(|ivec| ivec.to_vec()))
     }
 
@@ -53,7 +98,7 @@ impl OverpassDB {
     pub fn put(&self, key: &[u8], value: &[u8]) -> Result<Option<Vec<u8>>> {
         self.db
             .insert(key, value)
-            .context("Database put operation failed")
+            .map_err(operation("put"))
             .map(|opt| opt.map(|ivec| ivec.to_vec()))
     }
 
@@ -70,7 +115,7 @@ impl OverpassDB {
     pub fn delete(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
         self.db
             .remove(key)
-            .context("Database delete operation failed")
+            .map_err(operation("delete"))
             .map(|opt| opt.map(|ivec| ivec.to_vec()))
     }
 
@@ -87,7 +132,7 @@ impl OverpassDB {
     pub fn scan(&self, start: &[u8], end: &[u8]) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
         let mut result = Vec::new();
         for item in self.db.range(start..end) {
-            let (key, value) = item.context("Database range scan failed")?;
+            let (key, value) = item.map_err(operation("range scan"))?;
             result.push((key.to_vec(), value.to_vec()));
         }
         Ok(result)
@@ -97,9 +142,210 @@ impl OverpassDB {
     /// 
     /// Ensures that all changes made to the database are durable.
     pub fn flush(&self) -> Result<()> {
-        self.db.flush().context("Database flush failed")?;
+        self.db.flush().map_err(operation("flush"))?;
         Ok(())
     }
+
+    /// Returns the number of bytes the database takes up on disk.
+    pub fn size_on_disk(&self) -> Result<u64> {
+        self.db.size_on_disk().map_err(operation("size_on_disk"))
+    }
+}
+
+/// A SQLite stand-in for the subset of sled's API the database uses.
+#[cfg(all(feature = "storage-sqlite", not(feature = "storage-sled")))]
+mod sqlite {
+    use rusqlite::{params, Connection, OptionalExtension};
+    use std::ops::Range;
+    use std::path::Path;
+    use std::sync::{Arc, Mutex, MutexGuard};
+    use thiserror::Error;
+
+    /// File the database keeps in its directory.
+    const FILE: &str = "overpass.sqlite3";
+
+    #[derive(Error, Debug)]
+    pub enum Error {
+        #[error(transparent)]
+        Sqlite(#[from] rusqlite::Error),
+        #[error(transparent)]
+        Io(#[from] std::io::Error),
+        #[error("Database lock poisoned")]
+        Poisoned,
+    }
+
+    type Result<T> = std::result::Result<T, Error>;
+
+    /// One table of key-value pairs. SQLite compares blobs bytewise, so ranges come back
+    /// in the order sled keeps them.
+    #[derive(Clone)]
+    pub struct Db {
+        conn: Arc<Mutex<Connection>>,
+    }
+
+    impl Db {
+        /// Opens the database in the directory at `path`, as sled does.
+        pub fn open(path: &str) -> Result<Self> {
+            std::fs::create_dir_all(path)?;
+            let conn = Connection::open(Path::new(path).join(FILE))?;
+            conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS entries \
+                 (key BLOB PRIMARY KEY, value BLOB NOT NULL) WITHOUT ROWID;",
+            )?;
+            Ok(Self {
+                conn: Arc::new(Mutex::new(conn)),
+            })
+        }
+
+        fn conn(&self) -> Result<MutexGuard<'_, Connection>> {
+            self.conn.lock().map_err(|_| Error::Poisoned)
+        }
+
+        fn lookup(conn: &Connection, key: &[u8]) -> Result<Option<Vec<u8>>> {
+            Ok(conn
+                .query_row(
+                    "SELECT value FROM entries WHERE key = ?1",
+                    params![key],
+                    |row| row.get(0),
+                )
+                .optional()?)
+        }
+
+        pub fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+            let conn = self.conn()?;
+            Self::lookup(&conn, key)
+        }
+
+        pub fn insert(&self, key: &[u8], value: &[u8]) -> Result<Option<Vec<u8>>> {
+            let conn = self.conn()?;
+            let previous = Self::lookup(&conn, key)?;
+            conn.execute(
+                "INSERT OR REPLACE INTO entries (key, value) VALUES (?1, ?2)",
+                params![key, value],
+            )?;
+            Ok(previous)
+        }
+
+        pub fn remove(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+            let conn = self.conn()?;
+            let previous = Self::lookup(&conn, key)?;
+            conn.execute("DELETE FROM entries WHERE key = ?1", params![key])?;
+            Ok(previous)
+        }
+
+        pub fn range(&self, range: Range<&[u8]>) -> impl Iterator<Item = Result<(Vec<u8>, Vec<u8>)>> {
+            self.rows(range)
+                .unwrap_or_else(|e| vec![Err(e)])
+                .into_iter()
+        }
+
+        fn rows(&self, range: Range<&[u8]>) -> Result<Vec<Result<(Vec<u8>, Vec<u8>)>>> {
+            let conn = self.conn()?;
+            let mut statement = conn.prepare(
+                "SELECT key, value FROM entries WHERE key >= ?1 AND key < ?2 ORDER BY key",
+            )?;
+            let rows: Vec<_> = statement
+                .query_map(params![range.start, range.end], |row| {
+                    Ok((row.get(0)?, row.get(1)?))
+                })?
+                .map(|row| row.map_err(Error::from))
+                .collect();
+            Ok(rows)
+        }
+
+        /// Every write commits on its own, so there is nothing left to flush.
+        pub fn flush(&self) -> Result<usize> {
+            Ok(0)
+        }
+
+        pub fn size_on_disk(&self) -> Result<u64> {
+            let size: i64 = self.conn()?.query_row(
+                "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+                [],
+                |row| row.get(0),
+            )?;
+            Ok(size as u64)
+        }
+    }
+}
+
+/// Stands in for a storage engine in builds without one, which cannot open a database.
+#[cfg(not(any(feature = "storage-sled", feature = "storage-sqlite")))]
+mod unavailable {
+    use super::DbError;
+    use std::convert::Infallible;
+    use std::ops::Range;
+
+    type Result<T> = std::result::Result<T, Infallible>;
+
+    #[derive(Clone)]
+    pub struct Db(Infallible);
+
+    impl Db {
+        pub fn open(_path: &str) -> std::result::Result<Self, DbError> {
+            Err(DbError::NoBackend)
+        }
+
+        pub fn get(&self, _key: &[u8]) -> Result<Option<Vec<u8>>> {
+            match self.0 {}
+        }
+
+        pub fn insert(&self, _key: &[u8], _value: &[u8]) -> Result<Option<Vec<u8>>> {
+            match self.0 {}
+        }
+
+        pub fn remove(&self, _key: &[u8]) -> Result<Option<Vec<u8>>> {
+            match self.0 {}
+        }
+
+        pub fn range(&self, _range: Range<&[u8]>) -> std::iter::Empty<Result<(Vec<u8>, Vec<u8>)>> {
+            match self.0 {}
+        }
+
+        pub fn flush(&self) -> Result<usize> {
+            match self.0 {}
+        }
+
+        pub fn size_on_disk(&self) -> Result<u64> {
+            match self.0 {}
+        }
+    }
+}
+
+/// Async variants of the database calls, each run on tokio's blocking pool against a
+/// handle to the same database.
+#[cfg(feature = "tokio")]
+impl OverpassDB {
+    fn handle(&self) -> Self {
+        Self {
+            db: self.db.clone(),
+        }
+    }
+
+    pub async fn get_async(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let (db, key) = (self.handle(), key.to_vec());
+        run_blocking(move || db.get(&key)).await
+    }
+
+    pub async fn put_async(&self, key: &[u8], value: &[u8]) -> Result<Option<Vec<u8>>> {
+        let (db, key, value) = (self.handle(), key.to_vec(), value.to_vec());
+        run_blocking(move || db.put(&key, &value)).await
+    }
+
+    pub async fn delete_async(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let (db, key) = (self.handle(), key.to_vec());
+        run_blocking(move || db.delete(&key)).await
+    }
+
+    pub async fn scan_async(&self, start: &[u8], end: &[u8]) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let (db, start, end) = (self.handle(), start.to_vec(), end.to_vec());
+        run_blocking(move || db.scan(&start, &end)).await
+    }
+
+    pub async fn flush_async(&self) -> Result<()> {
+        let db = self.handle();
+        run_blocking(move || db.flush()).await
+    }
 }
 
 #[cfg(test)]
@@ -177,6 +423,12 @@ mod tests {
         Ok(())
     }
 
+    #[cfg(not(any(feature = "storage-sled", feature = "storage-sqlite")))]
+    #[test]
+    fn test_no_engine_refuses_to_open() {
+        assert!(matches!(OverpassDB::new(TEST_DB_PATH), Err(DbError::NoBackend)));
+    }
+
     #[test]
     fn test_flush() -> Result<()> {
         let db = setup_db()?;
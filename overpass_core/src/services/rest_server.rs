@@ -0,0 +1,1140 @@
+// ./src/services/rest_server.rs
+
+use crate::clock::SharedClock;
+use crate::services::server::{
+    bind_checked, require_origin, require_token, ServeError, ServeOptions,
+};
+use crate::zkp::channel::ChannelState;
+use crate::zkp::global_root_contract::{GlobalRootContractError, GlobalRootEvent};
+use crate::zkp::helpers::Bytes32;
+use crate::zkp::payment::{Payment, PaymentStatus};
+use crate::zkp::wallet_audit::{AuditRecord, WalletEvent};
+use crate::zkp::wallet_contract::{WalletContract, WalletContractError, WalletUpdate};
+use axum::{
+    extract::ws::{Message, WebSocket, WebSocketUpgrade},
+    extract::{Path, State},
+    http::StatusCode,
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::sync::broadcast::{self, error::RecvError};
+use utoipa::openapi::security::{Http, HttpAuthScheme, SecurityScheme};
+use utoipa::{Modify, OpenApi, ToSchema};
+
+
+/// A failed call, as its HTTP status and a JSON `{ "message": ... }` body.
+#[derive(Debug)]
+pub struct RestError {
+    pub status: StatusCode,
+    pub message: String,
+}
+
+impl RestError {
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<WalletContractError> for RestError {
+    fn from(error: WalletContractError) -> Self {
+        let status = match error {
+            WalletContractError::ChannelNotFound => StatusCode::NOT_FOUND,
+            WalletContractError::InsufficientFunds(_)
+            | WalletContractError::DeadlinePassed(_)
+            | WalletContractError::UnrecordedRoot
+            | WalletContractError::GlobalRootError(GlobalRootContractError::WalletNotFound)
+            | WalletContractError::NoKeyManager
+            | WalletContractError::WatchOnly
+            | WalletContractError::Locked => StatusCode::CONFLICT,
+            WalletContractError::PolicyError(_) => StatusCode::FORBIDDEN,
+            WalletContractError::InvalidTransition(_)
+            | WalletContractError::CounterpartyMismatch(_) => StatusCode::BAD_REQUEST,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        Self::new(status, error.to_string())
+    }
+}
+
+impl IntoResponse for RestError {
+    fn into_response(self) -> Response {
+        #[cfg(feature = "metrics")]
+        crate::services::metrics::global().record_error("rest");
+        let body = ErrorBody {
+            message: self.message,
+        };
+        (self.status, Json(body)).into_response()
+    }
+}
+
+fn bytes32(field: &str, value: &str) -> Result<Bytes32, RestError> {
+    hex::decode(value)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| {
+            RestError::new(
+                StatusCode::BAD_REQUEST,
+                format!("{} must be 32 hex-encoded bytes", field),
+            )
+        })
+}
+
+fn proof(proof: impl Serialize) -> Result<Json<ProofBody>, RestError> {
+    serde_json::to_value(proof)
+        .map(|proof| Json(ProofBody { proof }))
+        .map_err(|e| RestError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ErrorBody {
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct OpenChannelBody {
+    pub balance: u64,
+    /// Hex-encoded id of the counterparty.
+    pub counterparty: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct OpenedChannel {
+    pub channel_id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct PayBody {
+    /// Hex-encoded id of the counterparty.
+    pub counterparty: String,
+    pub amount: u64,
+    pub memo: Option<String>,
+    pub routing_fee: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ProofOfFundsBody {
+    pub minimum: u64,
+    /// Hex-encoded challenge from the verifier the proof is for.
+    #[serde(default)]
+    pub context: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct PublishRootBody {
+    /// Height the global contract records the wallet root at.
+    pub height: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ChannelView {
+    pub channel_id: String,
+    pub balances: Vec<u64>,
+    pub nonce: u64,
+    /// Hex-encoded commitment to the channel's latest state.
+    pub commitment: String,
+}
+
+impl ChannelView {
+    fn new(channel_id: &Bytes32, state: &ChannelState) -> Self {
+        Self {
+            channel_id: hex::encode(channel_id),
+            balances: state.balances.clone(),
+            nonce: state.nonce,
+            commitment: hex::encode(state.merkle_root),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct HistoryView {
+    pub records: usize,
+    pub updates: usize,
+    pub head: Option<String>,
+    pub last_timestamp: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct BalanceView {
+    pub open: u64,
+    pub closed: u64,
+    pub open_channels: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct PaymentView {
+    pub payment_id: String,
+    pub channel_id: String,
+    pub amount: u64,
+    pub routing_fee: u64,
+    pub nonce: u64,
+    #[schema(value_type = String, example = "Pending")]
+    pub status: PaymentStatus,
+}
+
+impl From<&Payment> for PaymentView {
+    fn from(payment: &Payment) -> Self {
+        Self {
+            payment_id: hex::encode(payment.id),
+            channel_id: hex::encode(payment.channel_id),
+            amount: payment.amount,
+            routing_fee: payment.routing_fee,
+            nonce: payment.nonce,
+            status: payment.status,
+        }
+    }
+}
+
+/// A proof in its serde form, to be handed to a verifier as it is.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ProofBody {
+    #[schema(value_type = Object)]
+    pub proof: Value,
+}
+
+/// The subprotocol the `/v1/events` WebSocket speaks, picked from those a client offers.
+pub const EVENTS_PROTOCOL: &str = "overpass.events";
+
+/// A message on the `/v1/events` WebSocket, sent as a JSON text frame tagged by `type`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StreamEvent {
+    /// A record appended to the wallet's audit log, e.g. a channel opening or closing.
+    Channel {
+        sequence: u64,
+        timestamp: u64,
+        event: WalletEvent,
+    },
+    /// A payment was sent, or its status changed since it was last streamed.
+    Payment(PaymentView),
+    /// The global contract sealed an epoch whose root is due to be anchored.
+    EpochSealed { epoch: u64, root: String },
+    /// A sealed epoch's root was confirmed on-chain.
+    RootAnchored {
+        epoch: u64,
+        root: String,
+        anchor_txid: String,
+    },
+    /// An anchored root became final once its epoch was past challenge.
+    RootFinalized {
+        epoch: u64,
+        root: String,
+        anchor_txid: String,
+    },
+    /// The subscriber fell behind and missed events; re-read the history API to catch up.
+    Lagged { missed: u64 },
+}
+
+impl From<&AuditRecord> for StreamEvent {
+    fn from(record: &AuditRecord) -> Self {
+        StreamEvent::Channel {
+            sequence: record.sequence,
+            timestamp: record.timestamp,
+            event: record.event.clone(),
+        }
+    }
+}
+
+impl StreamEvent {
+    /// Picks out the global contract events that concern root anchoring.
+    fn from_root(event: GlobalRootEvent) -> Option<Self> {
+        match event {
+            GlobalRootEvent::EpochSealed(sealed) => Some(StreamEvent::EpochSealed {
+                epoch: sealed.epoch,
+                root: hex::encode(sealed.root),
+            }),
+            GlobalRootEvent::Anchored {
+                epoch,
+                root,
+                anchor_txid,
+            } => Some(StreamEvent::RootAnchored {
+                epoch,
+                root: hex::encode(root),
+                anchor_txid: anchor_txid.to_string(),
+            }),
+            GlobalRootEvent::Checkpointed(checkpoint) => Some(StreamEvent::RootFinalized {
+                epoch: checkpoint.epoch,
+                root: hex::encode(checkpoint.root),
+                anchor_txid: checkpoint.anchor_txid.to_string(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// The events `/v1/events` sends one subscriber: the wallet's updates, the global
+/// contract's anchoring events, and the expiry of pending payments it knows of.
+pub struct EventStream {
+    updates: broadcast::Receiver<WalletUpdate>,
+    roots: broadcast::Receiver<GlobalRootEvent>,
+    clock: SharedClock,
+    /// Pending payments with a deadline, by id.
+    pending: HashMap<Bytes32, Payment>,
+    expired: VecDeque<StreamEvent>,
+}
+
+impl EventStream {
+    fn new(wallet: &WalletContract) -> Self {
+        let mut stream = Self {
+            updates: wallet.subscribe(),
+            roots: wallet.global_contract.subscribe(),
+            clock: wallet.clock().clone(),
+            pending: HashMap::new(),
+            expired: VecDeque::new(),
+        };
+        for payment in wallet.payments() {
+            stream.track(&payment);
+        }
+        stream
+    }
+
+    fn track(&mut self, payment: &Payment) {
+        if payment.status == PaymentStatus::Pending && payment.deadline.is_some() {
+            self.pending.insert(payment.id, payment.clone());
+        } else {
+            self.pending.remove(&payment.id);
+        }
+    }
+
+    /// Gets how long until the next tracked payment expires.
+    fn next_expiry(&self) -> Option<Duration> {
+        let deadline = self.pending.values().filter_map(|p| p.deadline).min()?;
+        // A payment expires once the clock is past its deadline.
+        let wait = deadline.saturating_add(1).saturating_sub(self.clock.now());
+        Some(Duration::from_secs(wait))
+    }
+
+    /// Queues every tracked payment whose deadline has passed, oldest first.
+    fn expire(&mut self) {
+        let now = self.clock.now();
+        let mut expired: Vec<Payment> = self
+            .pending
+            .values()
+            .filter(|payment| payment.status_at(now) == PaymentStatus::Expired)
+            .cloned()
+            .collect();
+        expired.sort_by_key(|payment| (payment.created_at, payment.nonce));
+        for mut payment in expired {
+            self.pending.remove(&payment.id);
+            payment.status = PaymentStatus::Expired;
+            self.expired
+                .push_back(StreamEvent::Payment(PaymentView::from(&payment)));
+        }
+    }
+
+    /// Waits for the next event, or gets `None` once the wallet is gone.
+    pub async fn next(&mut self) -> Option<StreamEvent> {
+        loop {
+            if let Some(event) = self.expired.pop_front() {
+                return Some(event);
+            }
+            let expiry = self.next_expiry();
+            tokio::select! {
+                update = self.updates.recv() => match update {
+                    Ok(WalletUpdate::Audited(record)) => return Some(StreamEvent::from(&record)),
+                    Ok(WalletUpdate::Payment(payment)) => {
+                        self.track(&payment);
+                        return Some(StreamEvent::Payment(PaymentView::from(&*payment)));
+                    }
+                    Err(RecvError::Lagged(missed)) => return Some(StreamEvent::Lagged { missed }),
+                    Err(RecvError::Closed) => return None,
+                },
+                event = self.roots.recv() => match event {
+                    Ok(event) => {
+                        if let Some(event) = StreamEvent::from_root(event) {
+                            return Some(event);
+                        }
+                    }
+                    Err(RecvError::Lagged(missed)) => return Some(StreamEvent::Lagged { missed }),
+                    Err(RecvError::Closed) => return None,
+                },
+                _ = async {
+                    match expiry {
+                        Some(wait) => tokio::time::sleep(wait).await,
+                        None => std::future::pending().await,
+                    }
+                } => self.expire(),
+            }
+        }
+    }
+}
+
+/// REST interface to one wallet, for integrators without gRPC or JSON-RPC tooling.
+///
+/// Ids and hashes are hex strings. The OpenAPI document for the routes is served at
+/// `/openapi.json`, and channel, payment and anchoring events are streamed over a
+/// WebSocket at `/v1/events`.
+#[derive(Clone)]
+pub struct RestServer {
+    wallet: Arc<Mutex<WalletContract>>,
+}
+
+impl RestServer {
+    pub fn new(wallet: WalletContract) -> Self {
+        Self {
+            wallet: Arc::new(Mutex::new(wallet)),
+        }
+    }
+
+    fn lock(wallet: &Mutex<WalletContract>) -> MutexGuard<'_, WalletContract> {
+        wallet.lock().expect("wallet lock poisoned")
+    }
+
+    /// Runs `call` on the wallet off the async runtime.
+    async fn with_wallet<T, F>(&self, call: F) -> Result<T, RestError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut WalletContract) -> Result<T, WalletContractError> + Send + 'static,
+    {
+        let wallet = self.wallet.clone();
+        tokio::task::spawn_blocking(move || call(&mut Self::lock(&wallet)))
+            .await
+            .map_err(|e| RestError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+            .map_err(RestError::from)
+    }
+
+    /// Subscribes to the events streamed over `/v1/events`, from now on.
+    pub async fn subscribe(&self) -> Result<EventStream, RestError> {
+        self.with_wallet(|wallet| Ok(EventStream::new(wallet))).await
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/channels",
+    tag = "channels",
+    request_body = OpenChannelBody,
+    responses(
+        (status = 201, description = "Channel opened", body = OpenedChannel),
+        (status = 400, description = "Malformed counterparty", body = ErrorBody),
+    )
+)]
+async fn open_channel(
+    State(server): State<RestServer>,
+    Json(body): Json<OpenChannelBody>,
+) -> Result<(StatusCode, Json<OpenedChannel>), RestError> {
+    let counterparty = bytes32("counterparty", &body.counterparty)?;
+    let channel_id = server
+        .with_wallet(move |wallet| wallet.open_channel(body.balance, counterparty, Vec::new()))
+        .await?;
+    Ok((
+        StatusCode::CREATED,
+        Json(OpenedChannel {
+            channel_id: hex::encode(channel_id),
+        }),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/channels",
+    tag = "channels",
+    responses((status = 200, description = "Ids of the wallet's channels", body = [String]))
+)]
+async fn list_channels(State(server): State<RestServer>) -> Result<Json<Vec<String>>, RestError> {
+    let mut channel_ids = server
+        .with_wallet(|wallet| Ok(wallet.list_channels()))
+        .await?;
+    channel_ids.sort_unstable();
+    Ok(Json(channel_ids.iter().map(hex::encode).collect()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/channels/{channel_id}",
+    tag = "channels",
+    params(("channel_id" = String, Path, description = "Hex-encoded channel id")),
+    responses(
+        (status = 200, description = "The channel's latest state", body = ChannelView),
+        (status = 404, description = "No such channel", body = ErrorBody),
+    )
+)]
+async fn get_channel(
+    State(server): State<RestServer>,
+    Path(channel_id): Path<String>,
+) -> Result<Json<ChannelView>, RestError> {
+    let channel_id = bytes32("channel_id", &channel_id)?;
+    server
+        .with_wallet(move |wallet| {
+            wallet
+                .get_channel(&channel_id)
+                .map(|state| ChannelView::new(&channel_id, state))
+                .ok_or(WalletContractError::ChannelNotFound)
+        })
+        .await
+        .map(Json)
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/channels/{channel_id}/close",
+    tag = "channels",
+    params(("channel_id" = String, Path, description = "Hex-encoded channel id")),
+    responses(
+        (status = 200, description = "The channel's final state", body = ChannelView),
+        (status = 404, description = "No such channel", body = ErrorBody),
+    )
+)]
+async fn close_channel(
+    State(server): State<RestServer>,
+    Path(channel_id): Path<String>,
+) -> Result<Json<ChannelView>, RestError> {
+    let channel_id = bytes32("channel_id", &channel_id)?;
+    let state = server
+        .with_wallet(move |wallet| wallet.close_channel(&channel_id))
+        .await?;
+    Ok(Json(ChannelView::new(&channel_id, &state)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/channels/{channel_id}/history",
+    tag = "channels",
+    params(("channel_id" = String, Path, description = "Hex-encoded channel id")),
+    responses(
+        (status = 200, description = "Summary of the channel's replayed history", body = HistoryView),
+        (status = 404, description = "No such channel", body = ErrorBody),
+    )
+)]
+async fn channel_history(
+    State(server): State<RestServer>,
+    Path(channel_id): Path<String>,
+) -> Result<Json<HistoryView>, RestError> {
+    let channel_id = bytes32("channel_id", &channel_id)?;
+    let summary = server
+        .with_wallet(move |wallet| wallet.replay_channel(&channel_id))
+        .await?
+        .summary;
+    Ok(Json(HistoryView {
+        records: summary.records,
+        updates: summary.updates,
+        head: summary.head.map(hex::encode),
+        last_timestamp: summary.last_timestamp,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/payments",
+    tag = "payments",
+    request_body = PayBody,
+    responses(
+        (status = 201, description = "Payment sent", body = PaymentView),
+        (status = 409, description = "Not enough open balance", body = ErrorBody),
+    )
+)]
+async fn pay(
+    State(server): State<RestServer>,
+    Json(body): Json<PayBody>,
+) -> Result<(StatusCode, Json<PaymentView>), RestError> {
+    let counterparty = bytes32("counterparty", &body.counterparty)?;
+    let payment = server
+        .with_wallet(move |wallet| {
+            let mut payment = wallet.pay(counterparty, body.amount);
+            if let Some(memo) = body.memo {
+                payment = payment.with_memo(memo);
+            }
+            if let Some(fee) = body.routing_fee {
+                payment = payment.with_routing_fee(fee);
+            }
+            payment.send()
+        })
+        .await?;
+    Ok((StatusCode::CREATED, Json(PaymentView::from(&payment))))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/payments/{payment_id}",
+    tag = "payments",
+    params(("payment_id" = String, Path, description = "Hex-encoded payment id")),
+    responses(
+        (status = 200, description = "The payment and where it stands", body = PaymentView),
+        (status = 404, description = "No such payment", body = ErrorBody),
+    )
+)]
+async fn get_payment(
+    State(server): State<RestServer>,
+    Path(payment_id): Path<String>,
+) -> Result<Json<PaymentView>, RestError> {
+    let payment_id = bytes32("payment_id", &payment_id)?;
+    server
+        .with_wallet(move |wallet| Ok(wallet.payment(&payment_id)))
+        .await?
+        .map(|payment| Json(PaymentView::from(&payment)))
+        .ok_or_else(|| RestError::new(StatusCode::NOT_FOUND, "Unknown payment"))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/balance",
+    tag = "wallet",
+    responses((status = 200, description = "Balances summed over the wallet's channels", body = BalanceView))
+)]
+async fn get_balance(State(server): State<RestServer>) -> Result<Json<BalanceView>, RestError> {
+    let balance = server.with_wallet(|wallet| Ok(wallet.balance())).await?;
+    Ok(Json(BalanceView {
+        open: balance.open,
+        closed: balance.closed,
+        open_channels: balance.open_channels,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/keys/rotate",
+    tag = "wallet",
+    responses((status = 204, description = "Channel keys rotated"))
+)]
+async fn rotate_keys(State(server): State<RestServer>) -> Result<StatusCode, RestError> {
+    server.with_wallet(|wallet| wallet.rotate_keys()).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/root",
+    tag = "wallet",
+    request_body = PublishRootBody,
+    responses((status = 204, description = "Wallet root recorded by the global contract"))
+)]
+async fn publish_root(
+    State(server): State<RestServer>,
+    Json(body): Json<PublishRootBody>,
+) -> Result<StatusCode, RestError> {
+    server
+        .with_wallet(move |wallet| wallet.repair_against_global(body.height))
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/proofs/funds",
+    tag = "proofs",
+    request_body = ProofOfFundsBody,
+    responses(
+        (status = 200, description = "Proof the wallet holds at least the minimum", body = ProofBody),
+        (status = 409, description = "Open balance is below the minimum", body = ErrorBody),
+    )
+)]
+async fn proof_of_funds(
+    State(server): State<RestServer>,
+    Json(body): Json<ProofOfFundsBody>,
+) -> Result<Json<ProofBody>, RestError> {
+    let context = hex::decode(&body.context)
+        .map_err(|_| RestError::new(StatusCode::BAD_REQUEST, "context must be hex-encoded"))?;
+    let funds = server
+        .with_wallet(move |wallet| wallet.proof_of_funds(body.minimum, &context))
+        .await?;
+    proof(funds)
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/proofs/wallet-root",
+    tag = "proofs",
+    responses(
+        (status = 200, description = "Proof of the wallet root the global contract records", body = ProofBody),
+        (status = 409, description = "The wallet root is not recorded", body = ErrorBody),
+    )
+)]
+async fn wallet_root_proof(State(server): State<RestServer>) -> Result<Json<ProofBody>, RestError> {
+    let root = server
+        .with_wallet(|wallet| wallet.wallet_root_proof())
+        .await?;
+    proof(root)
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/events",
+    tag = "events",
+    responses((
+        status = 101,
+        description = "Switches to a WebSocket of JSON events tagged by `type`: channel, \
+                       payment, epoch_sealed, root_anchored, root_finalized and lagged"
+    ))
+)]
+async fn events(State(server): State<RestServer>, ws: WebSocketUpgrade) -> Response {
+    ws.protocols([EVENTS_PROTOCOL])
+        .on_upgrade(move |socket| stream_events(server, socket))
+}
+
+/// Sends `socket` the wallet's events until either side goes away.
+async fn stream_events(server: RestServer, mut socket: WebSocket) {
+    let Ok(mut events) = server.subscribe().await else {
+        return;
+    };
+    loop {
+        let event = tokio::select! {
+            event = events.next() => match event {
+                Some(event) => event,
+                None => return,
+            },
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                // axum answers pings; anything else from the client is ignored.
+                Some(Ok(_)) => continue,
+            },
+        };
+        let text = serde_json::to_string(&event).expect("stream events serialize");
+        if socket.send(Message::Text(text)).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// The OpenAPI description of [`router`].
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Overpass wallet API"),
+    paths(
+        open_channel,
+        list_channels,
+        get_channel,
+        close_channel,
+        channel_history,
+        pay,
+        get_payment,
+        get_balance,
+        rotate_keys,
+        publish_root,
+        proof_of_funds,
+        wallet_root_proof,
+        events,
+    ),
+    components(schemas(
+        ErrorBody,
+        OpenChannelBody,
+        OpenedChannel,
+        PayBody,
+        ProofOfFundsBody,
+        PublishRootBody,
+        ChannelView,
+        HistoryView,
+        BalanceView,
+        PaymentView,
+        ProofBody,
+    )),
+    modifiers(&BearerAuth),
+    security(("bearer" = []))
+)]
+pub struct ApiDoc;
+
+/// Declares the bearer token every `/v1` route takes.
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)),
+        );
+    }
+}
+
+async fn openapi() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+/// Builds the HTTP API, with the wallet's routes under `/v1` open to callers presenting
+/// the token in `options`. The OpenAPI document needs no token.
+///
+/// Browsers open `/v1/events` offering the subprotocols [`EVENTS_PROTOCOL`] and
+/// [`AuthToken::protocol`](crate::services::server::AuthToken::protocol), from the
+/// server's own origin or one `options` allows.
+pub fn router(server: RestServer, options: &ServeOptions) -> Router {
+    let origins: Arc<[String]> = options.origins().into();
+    Router::new()
+        .route("/v1/channels", get(list_channels).post(open_channel))
+        .route("/v1/channels/:channel_id", get(get_channel))
+        .route("/v1/channels/:channel_id/close", post(close_channel))
+        .route("/v1/channels/:channel_id/history", get(channel_history))
+        .route("/v1/payments", post(pay))
+        .route("/v1/payments/:payment_id", get(get_payment))
+        .route("/v1/balance", get(get_balance))
+        .route("/v1/keys/rotate", post(rotate_keys))
+        .route("/v1/root", post(publish_root))
+        .route("/v1/proofs/funds", post(proof_of_funds))
+        .route("/v1/proofs/wallet-root", get(wallet_root_proof))
+        .route(
+            "/v1/events",
+            get(events).route_layer(middleware::from_fn_with_state(origins, require_origin)),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            options.token().clone(),
+            require_token,
+        ))
+        .route("/openapi.json", get(openapi))
+        .with_state(server)
+}
+
+/// Serves the wallet's REST API as `options` say until the listener fails.
+///
+/// Write the token with `AuthToken::write_cookie` first for local clients to find it.
+pub async fn serve(server: RestServer, options: ServeOptions) -> Result<(), ServeError> {
+    let listener = bind_checked(&options).await?;
+    axum::serve(listener, router(server, &options)).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::server::AuthToken;
+    use crate::test_util;
+    use axum::body::Body;
+    use axum::http::Request;
+    use serde_json::json;
+    use tower::ServiceExt;
+
+    fn server() -> RestServer {
+        RestServer::new(test_util::wallet([1u8; 32]))
+    }
+
+    fn token() -> AuthToken {
+        AuthToken::new("rest-test-token").unwrap()
+    }
+
+    fn options() -> ServeOptions {
+        ServeOptions::local(0)
+            .with_token(token())
+            .allow_origin("http://localhost:3000")
+    }
+
+    async fn call(
+        server: &RestServer,
+        method: &str,
+        uri: &str,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .header("authorization", token().bearer());
+        let body = body.map_or_else(Body::empty, |body| Body::from(body.to_string()));
+        let response = router(server.clone(), &options())
+            .oneshot(request.body(body).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (
+            status,
+            serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_wallet_operations_over_rest() {
+        let server = server();
+        let (status, opened) = call(
+            &server,
+            "POST",
+            "/v1/channels",
+            Some(json!({ "balance": 100, "counterparty": hex::encode([7u8; 32]) })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        let channel_id = opened["channel_id"].as_str().unwrap().to_string();
+        let channel_uri = format!("/v1/channels/{}", channel_id);
+
+        // Rotation gives the channel a history to replay.
+        assert_eq!(
+            call(&server, "POST", "/v1/keys/rotate", None).await.0,
+            StatusCode::NO_CONTENT
+        );
+        let (_, channel) = call(&server, "GET", &channel_uri, None).await;
+        assert_eq!(channel["balances"][0], 100);
+        assert_eq!(
+            call(&server, "GET", "/v1/channels", None).await.1,
+            json!([channel_id])
+        );
+        assert_eq!(
+            call(&server, "GET", "/v1/balance", None).await.1["open"],
+            100
+        );
+        let (_, history) = call(&server, "GET", &format!("{}/history", channel_uri), None).await;
+        assert_eq!(history["head"], channel["commitment"]);
+
+        let (status, payment) = call(
+            &server,
+            "POST",
+            "/v1/payments",
+            Some(json!({ "counterparty": hex::encode([7u8; 32]), "amount": 10 })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        let payment_uri = format!("/v1/payments/{}", payment["payment_id"].as_str().unwrap());
+        assert_eq!(
+            call(&server, "GET", &payment_uri, None).await.1["status"],
+            "Pending"
+        );
+
+        call(&server, "POST", "/v1/root", Some(json!({ "height": 1 }))).await;
+        let (status, funds) = call(
+            &server,
+            "POST",
+            "/v1/proofs/funds",
+            Some(json!({ "minimum": 60, "context": "00ff" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(funds["proof"]["minimum"], 60);
+
+        let (status, closed) = call(&server, "POST", &format!("{}/close", channel_uri), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(closed["channel_id"], channel_id);
+        assert_eq!(
+            call(&server, "GET", &channel_uri, None).await.0,
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[tokio::test]
+    async fn test_errors_map_to_status_codes() {
+        let server = server();
+        let (status, error) = call(&server, "GET", "/v1/channels/abcd", None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(error["message"], "channel_id must be 32 hex-encoded bytes");
+        assert_eq!(
+            call(
+                &server,
+                "POST",
+                &format!("/v1/channels/{}/close", hex::encode([9u8; 32])),
+                None
+            )
+            .await
+            .0,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            call(
+                &server,
+                "GET",
+                &format!("/v1/payments/{}", hex::encode([9u8; 32])),
+                None
+            )
+            .await
+            .0,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            call(
+                &server,
+                "POST",
+                "/v1/proofs/funds",
+                Some(json!({ "minimum": 6 }))
+            )
+            .await
+            .0,
+            StatusCode::CONFLICT
+        );
+    }
+
+    #[tokio::test]
+    async fn test_wallet_changes_are_streamed() {
+        let server = server();
+        let mut events = server.subscribe().await.unwrap();
+        let counterparty = hex::encode([7u8; 32]);
+        call(
+            &server,
+            "POST",
+            "/v1/channels",
+            Some(json!({ "balance": 100, "counterparty": counterparty })),
+        )
+        .await;
+        call(
+            &server,
+            "POST",
+            "/v1/payments",
+            Some(json!({ "counterparty": counterparty, "amount": 10 })),
+        )
+        .await;
+        call(&server, "POST", "/v1/root", Some(json!({ "height": 1 }))).await;
+
+        let mut streamed = Vec::new();
+        while let Ok(Some(event)) =
+            tokio::time::timeout(Duration::from_millis(100), events.next()).await
+        {
+            streamed.push(serde_json::to_value(event).unwrap());
+        }
+        assert_eq!(streamed[0]["type"], "channel");
+        assert!(streamed[0]["event"]["ChannelOpened"].is_object());
+        let statuses: Vec<_> = streamed
+            .iter()
+            .filter(|event| event["type"] == "payment")
+            .map(|event| event["status"].as_str().unwrap())
+            .collect();
+        assert_eq!(statuses, ["Pending", "Recorded"]);
+    }
+
+    #[tokio::test]
+    async fn test_payments_are_streamed_as_they_expire() -> Result<(), WalletContractError> {
+        use crate::clock::ManualClock;
+
+        let clock = Arc::new(ManualClock::new(1_000));
+        let mut wallet = test_util::wallet([1u8; 32]).with_clock(clock.clone());
+        wallet.open_channel(100, [7u8; 32], Vec::new())?;
+        let payment = wallet.pay([7u8; 32], 10).with_deadline(1_001).send()?;
+        let server = RestServer::new(wallet);
+        let mut events = server.subscribe().await.unwrap();
+
+        clock.advance(2);
+        assert_eq!(
+            events.next().await,
+            Some(StreamEvent::Payment(PaymentView {
+                status: PaymentStatus::Expired,
+                ..PaymentView::from(&payment)
+            }))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_only_anchoring_root_events_are_streamed() {
+        use crate::zkp::global_root_contract::Checkpoint;
+        use bitcoin::hashes::Hash;
+        use bitcoin::Txid;
+
+        let checkpoint = Checkpoint {
+            epoch: 3,
+            root: [4u8; 32],
+            anchor_txid: Txid::all_zeros(),
+        };
+        assert_eq!(
+            StreamEvent::from_root(GlobalRootEvent::Anchored {
+                epoch: 3,
+                root: [4u8; 32],
+                anchor_txid: Txid::all_zeros(),
+            }),
+            Some(StreamEvent::RootAnchored {
+                epoch: 3,
+                root: hex::encode([4u8; 32]),
+                anchor_txid: Txid::all_zeros().to_string(),
+            })
+        );
+        assert_eq!(
+            StreamEvent::from_root(GlobalRootEvent::Checkpointed(checkpoint)),
+            Some(StreamEvent::RootFinalized {
+                epoch: 3,
+                root: hex::encode([4u8; 32]),
+                anchor_txid: Txid::all_zeros().to_string(),
+            })
+        );
+        assert_eq!(StreamEvent::from_root(GlobalRootEvent::Resumed), None);
+    }
+
+    #[tokio::test]
+    async fn test_openapi_describes_every_route() {
+        let (status, document) = call(&server(), "GET", "/openapi.json", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let paths = document["paths"].as_object().unwrap();
+        for path in [
+            "/v1/channels",
+            "/v1/channels/{channel_id}",
+            "/v1/channels/{channel_id}/close",
+            "/v1/channels/{channel_id}/history",
+            "/v1/payments",
+            "/v1/payments/{payment_id}",
+            "/v1/balance",
+            "/v1/keys/rotate",
+            "/v1/root",
+            "/v1/proofs/funds",
+            "/v1/proofs/wallet-root",
+            "/v1/events",
+        ] {
+            assert!(paths.contains_key(path), "{} is undocumented", path);
+        }
+        assert!(document["components"]["schemas"]["ChannelView"].is_object());
+        assert_eq!(
+            document["components"]["securitySchemes"]["bearer"]["scheme"],
+            "bearer"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_wallet_routes_need_the_token() {
+        let app = router(server(), &options());
+        let get = |uri: &str, authorization: Option<String>| {
+            let mut request = Request::get(uri);
+            if let Some(authorization) = authorization {
+                request = request.header("authorization", authorization);
+            }
+            app.clone().oneshot(request.body(Body::empty()).unwrap())
+        };
+
+        assert_eq!(
+            get("/v1/balance", None).await.unwrap().status(),
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            get("/v1/balance", Some(AuthToken::generate().bearer()))
+                .await
+                .unwrap()
+                .status(),
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            get("/v1/balance", Some(token().bearer())).await.unwrap().status(),
+            StatusCode::OK
+        );
+        assert_eq!(
+            get("/openapi.json", None).await.unwrap().status(),
+            StatusCode::OK
+        );
+    }
+
+    /// Sends the upgrade request a browser page at `origin` makes for `/v1/events`, which
+    /// cannot carry `Authorization`, and gets the response head in lowercase.
+    async fn upgrade(addr: std::net::SocketAddr, origin: &str, protocols: &str) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let request = format!(
+            "GET /v1/events HTTP/1.1\r\nHost: {addr}\r\nOrigin: {origin}\r\n\
+             Connection: Upgrade\r\nUpgrade: websocket\r\nSec-WebSocket-Version: 13\r\n\
+             Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+             Sec-WebSocket-Protocol: {protocols}\r\n\r\n"
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut head = vec![0u8; 1024];
+        let read = stream.read(&mut head).await.unwrap();
+        String::from_utf8_lossy(&head[..read]).to_lowercase()
+    }
+
+    #[tokio::test]
+    async fn test_browsers_open_events_with_the_token_protocol() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = router(server(), &options());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        let offered = format!("{}, {}", EVENTS_PROTOCOL, token().protocol());
+
+        let head = upgrade(addr, "http://localhost:3000", &offered).await;
+        assert!(head.starts_with("http/1.1 101"), "{}", head);
+        assert!(head.contains(&format!("sec-websocket-protocol: {}", EVENTS_PROTOCOL)));
+        assert!(!head.contains(token().as_str()));
+        let own = format!("http://{}", addr);
+        assert!(upgrade(addr, &own, &offered).await.starts_with("http/1.1 101"));
+
+        let head = upgrade(addr, "https://evil.example", &offered).await;
+        assert!(head.starts_with("http/1.1 403"), "{}", head);
+        let stolen = format!("{}, {}", EVENTS_PROTOCOL, AuthToken::generate().protocol());
+        let head = upgrade(addr, "http://localhost:3000", &stolen).await;
+        assert!(head.starts_with("http/1.1 401"), "{}", head);
+    }
+}
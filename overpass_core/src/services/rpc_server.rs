@@ -0,0 +1,472 @@
+// ./src/services/rpc_server.rs
+
+use crate::services::server::{bind_checked, require_token, AuthToken, ServeError, ServeOptions};
+use crate::zkp::channel::ChannelState;
+use crate::zkp::helpers::Bytes32;
+use crate::zkp::wallet_contract::{WalletContract, WalletContractError};
+use axum::{
+    body::Bytes, extract::State, http::StatusCode, middleware, response::IntoResponse,
+    routing::post, Json, Router,
+};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+/// A wallet operation was refused or failed.
+pub const WALLET_ERROR: i64 = -32000;
+pub const INSUFFICIENT_FUNDS: i64 = -32001;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<WalletContractError> for RpcError {
+    fn from(error: WalletContractError) -> Self {
+        let code = match error {
+            WalletContractError::InsufficientFunds(_) => INSUFFICIENT_FUNDS,
+            _ => WALLET_ERROR,
+        };
+        Self::new(code, error.to_string())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Request {
+    jsonrpc: String,
+    method: String,
+    #[serde(default)]
+    params: Value,
+    /// Absent for notifications, which get no response.
+    id: Option<Value>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Response {
+    pub jsonrpc: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+    pub id: Value,
+}
+
+impl Response {
+    fn new(id: Value, outcome: Result<Value, RpcError>) -> Self {
+        let (result, error) = match outcome {
+            Ok(result) => (Some(result), None),
+            Err(error) => {
+                #[cfg(feature = "metrics")]
+                crate::services::metrics::global().record_error("rpc");
+                (None, Some(error))
+            }
+        };
+        Self {
+            jsonrpc: "2.0".to_string(),
+            result,
+            error,
+            id,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct OpenChannelParams {
+    balance: u64,
+    counterparty: String,
+}
+
+#[derive(Deserialize)]
+struct PayParams {
+    counterparty: String,
+    amount: u64,
+    memo: Option<String>,
+    routing_fee: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct ChannelParams {
+    channel_id: String,
+}
+
+#[derive(Deserialize)]
+struct ProofOfFundsParams {
+    minimum: u64,
+    /// Hex-encoded challenge from the verifier the proof is for.
+    #[serde(default)]
+    context: String,
+}
+
+fn params<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))
+}
+
+fn bytes32_param(name: &str, value: &str) -> Result<Bytes32, RpcError> {
+    hex::decode(value)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| {
+            RpcError::new(
+                INVALID_PARAMS,
+                format!("{} must be 32 hex-encoded bytes", name),
+            )
+        })
+}
+
+fn to_value(value: impl Serialize) -> Result<Value, RpcError> {
+    serde_json::to_value(value).map_err(|e| RpcError::new(WALLET_ERROR, e.to_string()))
+}
+
+fn channel_json(channel_id: &Bytes32, state: &ChannelState) -> Value {
+    json!({
+        "channel_id": hex::encode(channel_id),
+        "balances": state.balances,
+        "nonce": state.nonce,
+        "commitment": hex::encode(state.merkle_root),
+    })
+}
+
+/// JSON-RPC 2.0 interface to one wallet, for running it as a headless daemon.
+///
+/// Ids and hashes are hex strings. Proofs are returned in their serde form, so they can
+/// be handed to a verifier as they are.
+pub struct RpcServer {
+    wallet: Mutex<WalletContract>,
+}
+
+impl RpcServer {
+    pub fn new(wallet: WalletContract) -> Self {
+        Self {
+            wallet: Mutex::new(wallet),
+        }
+    }
+
+    /// Gives the wallet back, e.g. to persist it after serving a call.
+    pub fn into_wallet(self) -> WalletContract {
+        self.wallet.into_inner().expect("wallet lock poisoned")
+    }
+
+    fn wallet(&self) -> MutexGuard<'_, WalletContract> {
+        self.wallet.lock().expect("wallet lock poisoned")
+    }
+
+    /// Handles one request body. Returns `None` for notifications.
+    pub fn handle(&self, body: &[u8]) -> Option<Response> {
+        let value: Value = match serde_json::from_slice(body) {
+            Ok(value) => value,
+            Err(e) => {
+                return Some(Response::new(
+                    Value::Null,
+                    Err(RpcError::new(PARSE_ERROR, e.to_string())),
+                ))
+            }
+        };
+        let request = match serde_json::from_value::<Request>(value) {
+            Ok(request) if request.jsonrpc == "2.0" => request,
+            Ok(_) => {
+                return Some(Response::new(
+                    Value::Null,
+                    Err(RpcError::new(
+                        INVALID_REQUEST,
+                        "Only JSON-RPC 2.0 is supported",
+                    )),
+                ))
+            }
+            Err(e) => {
+                return Some(Response::new(
+                    Value::Null,
+                    Err(RpcError::new(INVALID_REQUEST, e.to_string())),
+                ))
+            }
+        };
+        let outcome = self.call(&request.method, request.params);
+        request.id.map(|id| Response::new(id, outcome))
+    }
+
+    /// Runs a single method against the wallet.
+    pub fn call(&self, method: &str, params: Value) -> Result<Value, RpcError> {
+        match method {
+            "open_channel" => {
+                let OpenChannelParams {
+                    balance,
+                    counterparty,
+                } = self::params(params)?;
+                let counterparty = bytes32_param("counterparty", &counterparty)?;
+                let channel_id = self
+                    .wallet()
+                    .open_channel(balance, counterparty, Vec::new())?;
+                Ok(json!({ "channel_id": hex::encode(channel_id) }))
+            }
+            "pay" => {
+                let PayParams {
+                    counterparty,
+                    amount,
+                    memo,
+                    routing_fee,
+                } = self::params(params)?;
+                let counterparty = bytes32_param("counterparty", &counterparty)?;
+                let mut wallet = self.wallet();
+                let mut payment = wallet.pay(counterparty, amount);
+                if let Some(memo) = memo {
+                    payment = payment.with_memo(memo);
+                }
+                if let Some(fee) = routing_fee {
+                    payment = payment.with_routing_fee(fee);
+                }
+                let payment = payment.send()?;
+                Ok(json!({
+                    "payment_id": hex::encode(payment.id),
+                    "channel_id": hex::encode(payment.channel_id),
+                    "amount": payment.amount,
+                    "routing_fee": payment.routing_fee,
+                    "nonce": payment.nonce,
+                }))
+            }
+            "close_channel" => {
+                let ChannelParams { channel_id } = self::params(params)?;
+                let channel_id = bytes32_param("channel_id", &channel_id)?;
+                let state = self.wallet().close_channel(&channel_id)?;
+                Ok(channel_json(&channel_id, &state))
+            }
+            "get_channel" => {
+                let ChannelParams { channel_id } = self::params(params)?;
+                let channel_id = bytes32_param("channel_id", &channel_id)?;
+                Ok(self
+                    .wallet()
+                    .get_channel(&channel_id)
+                    .map_or(Value::Null, |state| channel_json(&channel_id, state)))
+            }
+            "list_channels" => {
+                let mut channel_ids = self.wallet().list_channels();
+                channel_ids.sort_unstable();
+                Ok(channel_ids.iter().map(hex::encode).collect())
+            }
+            "get_balance" => to_value(self.wallet().balance()),
+            "channel_history" => {
+                let ChannelParams { channel_id } = self::params(params)?;
+                let channel_id = bytes32_param("channel_id", &channel_id)?;
+                let summary = self.wallet().replay_channel(&channel_id)?.summary;
+                Ok(json!({
+                    "records": summary.records,
+                    "updates": summary.updates,
+                    "head": summary.head.map(hex::encode),
+                    "last_timestamp": summary.last_timestamp,
+                }))
+            }
+            "proof_of_funds" => {
+                let ProofOfFundsParams { minimum, context } = self::params(params)?;
+                let context = hex::decode(&context)
+                    .map_err(|_| RpcError::new(INVALID_PARAMS, "context must be hex-encoded"))?;
+                to_value(self.wallet().proof_of_funds(minimum, &context)?)
+            }
+            "wallet_root_proof" => to_value(self.wallet().wallet_root_proof()?),
+            _ => Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("Unknown method {}", method),
+            )),
+        }
+    }
+}
+
+async fn rpc_handler(
+    State(server): State<Arc<RpcServer>>,
+    body: Bytes,
+) -> axum::response::Response {
+    let response = tokio::task::spawn_blocking(move || server.handle(&body)).await;
+    match response {
+        Ok(Some(response)) => Json(response).into_response(),
+        Ok(None) => StatusCode::NO_CONTENT.into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+/// Builds the HTTP API that takes JSON-RPC requests by POST to `/`, from callers
+/// presenting `token`.
+pub fn router(server: Arc<RpcServer>, token: AuthToken) -> Router {
+    Router::new()
+        .route("/", post(rpc_handler))
+        .route_layer(middleware::from_fn_with_state(token, require_token))
+        .with_state(server)
+}
+
+/// Serves the wallet's JSON-RPC API as `options` say until the listener fails.
+///
+/// Write the token with `AuthToken::write_cookie` first for local clients to find it.
+pub async fn serve(server: Arc<RpcServer>, options: ServeOptions) -> Result<(), ServeError> {
+    let listener = bind_checked(&options).await?;
+    axum::serve(listener, router(server, options.token().clone())).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+
+    fn server() -> RpcServer {
+        RpcServer::new(test_util::wallet([1u8; 32]))
+    }
+
+    fn request(server: &RpcServer, method: &str, params: Value) -> Response {
+        let body = json!({ "jsonrpc": "2.0", "method": method, "params": params, "id": 1 });
+        server.handle(body.to_string().as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_wallet_operations_over_rpc() {
+        let server = server();
+        let counterparty = hex::encode([7u8; 32]);
+        let opened = request(
+            &server,
+            "open_channel",
+            json!({ "balance": 100, "counterparty": counterparty }),
+        );
+        let channel_id = opened.result.unwrap()["channel_id"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        // Rotation gives the channel a history to replay.
+        server.wallet().rotate_keys().unwrap();
+
+        let channel = request(&server, "get_channel", json!({ "channel_id": channel_id }))
+            .result
+            .unwrap();
+        assert_eq!(channel["balances"][0], 100);
+        assert_eq!(
+            request(&server, "list_channels", Value::Null).result,
+            Some(json!([channel_id]))
+        );
+        assert_eq!(
+            request(&server, "get_balance", Value::Null).result.unwrap()["open"],
+            100
+        );
+        let history = request(
+            &server,
+            "channel_history",
+            json!({ "channel_id": channel_id }),
+        )
+        .result
+        .unwrap();
+        assert_eq!(history["head"], channel["commitment"]);
+
+        server.wallet().repair_against_global(1).unwrap();
+        let proof = request(
+            &server,
+            "proof_of_funds",
+            json!({ "minimum": 60, "context": "00ff" }),
+        );
+        assert_eq!(proof.result.unwrap()["minimum"], 60);
+        let closed = request(
+            &server,
+            "close_channel",
+            json!({ "channel_id": channel_id }),
+        )
+        .result
+        .unwrap();
+        assert_eq!(closed["channel_id"], channel_id);
+        assert_eq!(
+            request(&server, "get_channel", json!({ "channel_id": channel_id })).result,
+            Some(Value::Null)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_http_calls_need_the_token() {
+        use axum::body::Body;
+        use axum::http::{header, Request};
+        use tower::ServiceExt;
+
+        let token = AuthToken::generate();
+        let app = router(Arc::new(server()), token.clone());
+        let body = json!({ "jsonrpc": "2.0", "method": "get_balance", "id": 1 }).to_string();
+        let call = |authorization: Option<String>| {
+            let mut request = Request::post("/").header(header::CONTENT_TYPE, "application/json");
+            if let Some(authorization) = authorization {
+                request = request.header(header::AUTHORIZATION, authorization);
+            }
+            app.clone().oneshot(request.body(Body::from(body.clone())).unwrap())
+        };
+
+        assert_eq!(call(None).await.unwrap().status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            call(Some(AuthToken::generate().bearer())).await.unwrap().status(),
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(call(Some(token.bearer())).await.unwrap().status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn test_protocol_errors() {
+        let server = server();
+        let code = |response: Response| response.error.map(|error| error.code);
+
+        assert_eq!(
+            code(server.handle(b"{not json").unwrap()),
+            Some(PARSE_ERROR)
+        );
+        assert_eq!(
+            code(
+                server
+                    .handle(br#"{"jsonrpc":"1.0","method":"get_balance","id":1}"#)
+                    .unwrap()
+            ),
+            Some(INVALID_REQUEST)
+        );
+        assert_eq!(
+            code(request(&server, "mint", Value::Null)),
+            Some(METHOD_NOT_FOUND)
+        );
+        assert_eq!(
+            code(request(
+                &server,
+                "get_channel",
+                json!({ "channel_id": "abcd" })
+            )),
+            Some(INVALID_PARAMS)
+        );
+        assert_eq!(
+            code(request(
+                &server,
+                "pay",
+                json!({ "counterparty": hex::encode([7u8; 32]) })
+            )),
+            Some(INVALID_PARAMS)
+        );
+        assert_eq!(
+            code(request(
+                &server,
+                "close_channel",
+                json!({ "channel_id": hex::encode([9u8; 32]) })
+            )),
+            Some(WALLET_ERROR)
+        );
+
+        // Notifications are carried out without a response.
+        let notification = json!({ "jsonrpc": "2.0", "method": "open_channel", "params": { "balance": 5, "counterparty": hex::encode([8u8; 32]) } });
+        assert!(server.handle(notification.to_string().as_bytes()).is_none());
+        assert_eq!(
+            request(&server, "get_balance", Value::Null).result.unwrap()["open"],
+            5
+        );
+        server.wallet().repair_against_global(1).unwrap();
+        assert_eq!(
+            code(request(&server, "proof_of_funds", json!({ "minimum": 6 }))),
+            Some(INSUFFICIENT_FUNDS)
+        );
+    }
+}
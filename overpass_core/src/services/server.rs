@@ -0,0 +1,367 @@
+// ./src/services/server.rs
+
+//! What the HTTP and gRPC servers share: how they bind, who may call them and how they
+//! fail.
+
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use std::fmt;
+use std::io::Write;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::net::TcpListener;
+
+/// Name of the cookie file a server writes its token to, as bitcoind does.
+pub const COOKIE_FILE: &str = ".cookie";
+
+/// Prefix of the WebSocket subprotocol a browser presents the token as, since it cannot
+/// set an `Authorization` header on a WebSocket.
+pub const TOKEN_PROTOCOL_PREFIX: &str = "overpass.bearer.";
+
+/// Errors that keep a server from starting or stop it while serving.
+#[derive(Error, Debug)]
+pub enum ServeError {
+    #[error("Failed to bind {addr}: {source}")]
+    Bind {
+        addr: SocketAddr,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("Refusing to serve the wallet on non-loopback address {0} without allow_remote")]
+    NonLoopback(SocketAddr),
+    #[error("Auth token must be non-empty and contain no whitespace")]
+    MalformedToken,
+    #[error("Malformed auth token in {}", .0.display())]
+    MalformedCookie(PathBuf),
+    #[error("Server failed: {0}")]
+    Io(#[from] std::io::Error),
+    #[cfg(feature = "grpc")]
+    #[error("gRPC transport error: {0}")]
+    Transport(#[from] tonic::transport::Error),
+}
+
+/// The secret a client presents as `Authorization: Bearer <token>`.
+#[derive(Clone)]
+pub struct AuthToken(String);
+
+impl AuthToken {
+    /// Generates a random 32-byte token, hex-encoded.
+    pub fn generate() -> Self {
+        let mut bytes = [0u8; 32];
+        OsRng.fill_bytes(&mut bytes);
+        Self(hex::encode(bytes))
+    }
+
+    /// Takes a token configured by the operator. An empty token would match any
+    /// `Bearer` header that trims to nothing, so it is refused along with whitespace.
+    pub fn new(token: impl Into<String>) -> Result<Self, ServeError> {
+        let token = token.into();
+        if token.is_empty() || token.contains(char::is_whitespace) {
+            return Err(ServeError::MalformedToken);
+        }
+        Ok(Self(token))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Writes the token to `path`, readable only by its owner, for local clients to pick
+    /// up.
+    pub fn write_cookie(&self, path: &Path) -> Result<(), ServeError> {
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options.open(path)?;
+        // A cookie left by an earlier run keeps its mode when reopened.
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+        }
+        file.write_all(self.0.as_bytes())?;
+        Ok(())
+    }
+
+    /// Reads a token written by `write_cookie`.
+    pub fn read_cookie(path: &Path) -> Result<Self, ServeError> {
+        Self::new(std::fs::read_to_string(path)?.trim())
+            .map_err(|_| ServeError::MalformedCookie(path.to_path_buf()))
+    }
+
+    /// Gets the value of the `Authorization` header that presents this token.
+    pub fn bearer(&self) -> String {
+        format!("Bearer {}", self.0)
+    }
+
+    /// Checks a presented token in time independent of where it differs.
+    pub fn matches(&self, presented: &str) -> bool {
+        let (ours, theirs) = (self.0.as_bytes(), presented.as_bytes());
+        ours.len() == theirs.len()
+            && ours.iter().zip(theirs).fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0
+    }
+
+    /// Checks the bearer token in an `Authorization` header.
+    pub fn authorizes(&self, header: Option<&str>) -> bool {
+        header
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|presented| self.matches(presented.trim()))
+    }
+
+    /// Gets the WebSocket subprotocol that presents this token.
+    pub fn protocol(&self) -> String {
+        format!("{}{}", TOKEN_PROTOCOL_PREFIX, self.0)
+    }
+
+    /// Checks for the token among the subprotocols of a `Sec-WebSocket-Protocol` header.
+    pub fn authorizes_protocols(&self, header: Option<&str>) -> bool {
+        header.is_some_and(|value| {
+            value
+                .split(',')
+                .filter_map(|protocol| protocol.trim().strip_prefix(TOKEN_PROTOCOL_PREFIX))
+                .any(|presented| self.matches(presented))
+        })
+    }
+}
+
+impl fmt::Debug for AuthToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("AuthToken(..)")
+    }
+}
+
+/// Where a wallet API listens, the token its callers must present and the web origins
+/// its WebSockets take connections from besides its own.
+///
+/// Listens on loopback by default. A non-loopback address is refused unless
+/// `allow_remote` is set, since the token travels in the clear.
+#[derive(Clone, Debug)]
+pub struct ServeOptions {
+    addr: SocketAddr,
+    token: AuthToken,
+    allow_remote: bool,
+    origins: Vec<String>,
+}
+
+impl ServeOptions {
+    /// Listens on `127.0.0.1:port` behind a freshly generated token.
+    pub fn local(port: u16) -> Self {
+        Self {
+            addr: SocketAddr::from((Ipv4Addr::LOCALHOST, port)),
+            token: AuthToken::generate(),
+            allow_remote: false,
+            origins: Vec::new(),
+        }
+    }
+
+    pub fn with_addr(mut self, addr: SocketAddr) -> Self {
+        self.addr = addr;
+        self
+    }
+
+    pub fn with_token(mut self, token: AuthToken) -> Self {
+        self.token = token;
+        self
+    }
+
+    /// Allows listening on an address other hosts can reach.
+    pub fn allow_remote(mut self) -> Self {
+        self.allow_remote = true;
+        self
+    }
+
+    /// Lets browser pages from `origin`, such as `http://localhost:3000`, open WebSockets.
+    pub fn allow_origin(mut self, origin: impl Into<String>) -> Self {
+        self.origins.push(origin.into());
+        self
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn token(&self) -> &AuthToken {
+        &self.token
+    }
+
+    pub fn origins(&self) -> &[String] {
+        &self.origins
+    }
+
+    /// Checks the address is loopback or remote access was allowed.
+    pub fn check(&self) -> Result<(), ServeError> {
+        if !self.allow_remote && !self.addr.ip().is_loopback() {
+            return Err(ServeError::NonLoopback(self.addr));
+        }
+        Ok(())
+    }
+}
+
+/// Binds a TCP listener on `addr`.
+pub async fn bind(addr: SocketAddr) -> Result<TcpListener, ServeError> {
+    TcpListener::bind(addr)
+        .await
+        .map_err(|source| ServeError::Bind { addr, source })
+}
+
+/// Checks the options and binds their address.
+pub async fn bind_checked(options: &ServeOptions) -> Result<TcpListener, ServeError> {
+    options.check()?;
+    bind(options.addr).await
+}
+
+fn authorization(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+}
+
+/// Gets the subprotocols a WebSocket upgrade offers, or `None` for any other request.
+fn offered_protocols(headers: &HeaderMap) -> Option<&str> {
+    let upgrade = headers
+        .get(header::UPGRADE)
+        .is_some_and(|value| value.as_bytes().eq_ignore_ascii_case(b"websocket"));
+    upgrade
+        .then(|| headers.get(header::SEC_WEBSOCKET_PROTOCOL))
+        .flatten()
+        .and_then(|value| value.to_str().ok())
+}
+
+/// Checks a browser request comes from the server's own origin or one of `allowed`.
+/// Requests without an `Origin`, from clients other than browsers, pass.
+fn origin_allowed(headers: &HeaderMap, allowed: &[String]) -> bool {
+    let Some(origin) = headers.get(header::ORIGIN) else {
+        return true;
+    };
+    let Ok(origin) = origin.to_str() else {
+        return false;
+    };
+    let host = headers
+        .get(header::HOST)
+        .and_then(|value| value.to_str().ok());
+    allowed.iter().any(|allowed| allowed == origin)
+        || origin
+            .split_once("://")
+            .is_some_and(|(_, authority)| Some(authority) == host)
+}
+
+/// Middleware refusing requests without the token, for `middleware::from_fn_with_state`.
+///
+/// WebSocket upgrades may present the token as a subprotocol instead.
+pub async fn require_token(
+    State(token): State<AuthToken>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let headers = request.headers();
+    if token.authorizes(authorization(headers))
+        || token.authorizes_protocols(offered_protocols(headers))
+    {
+        return next.run(request).await;
+    }
+    (
+        StatusCode::UNAUTHORIZED,
+        [(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"))],
+    )
+        .into_response()
+}
+
+/// Middleware refusing browser requests from origins other than the server's own and
+/// `allowed`, for `middleware::from_fn_with_state`.
+pub async fn require_origin(
+    State(allowed): State<Arc<[String]>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if origin_allowed(request.headers(), &allowed) {
+        return next.run(request).await;
+    }
+    StatusCode::FORBIDDEN.into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv6Addr;
+
+    #[test]
+    fn test_tokens_and_cookies() -> Result<(), ServeError> {
+        let token = AuthToken::generate();
+        assert_eq!(token.as_str().len(), 64);
+        assert_ne!(token.as_str(), AuthToken::generate().as_str());
+        assert!(token.authorizes(Some(&token.bearer())));
+        assert!(!token.authorizes(Some(token.as_str())));
+        assert!(!token.authorizes(Some("Bearer 00")));
+        assert!(!token.authorizes(None));
+        assert_eq!(format!("{:?}", token), "AuthToken(..)");
+        let offered = format!("overpass.events, {}", token.protocol());
+        assert!(token.authorizes_protocols(Some(&offered)));
+        assert!(!token.authorizes_protocols(Some("overpass.events")));
+        assert!(!token.authorizes_protocols(Some(&AuthToken::generate().protocol())));
+        for blank in ["", " ", "two words"] {
+            assert!(matches!(AuthToken::new(blank), Err(ServeError::MalformedToken)));
+        }
+        assert_eq!(AuthToken::new("operator-token")?.as_str(), "operator-token");
+
+        let path = std::env::temp_dir().join(format!("overpass_cookie_{}", std::process::id()));
+        token.write_cookie(&path)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&path)?.permissions().mode() & 0o777, 0o600);
+        }
+        assert_eq!(AuthToken::read_cookie(&path)?.as_str(), token.as_str());
+        std::fs::write(&path, "")?;
+        assert!(matches!(
+            AuthToken::read_cookie(&path),
+            Err(ServeError::MalformedCookie(_))
+        ));
+        let _ = std::fs::remove_file(path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_remote_addresses_need_opting_in() {
+        let options = ServeOptions::local(7070);
+        assert_eq!(options.addr(), "127.0.0.1:7070".parse().unwrap());
+        assert!(options.check().is_ok());
+        let ipv6 = options
+            .clone()
+            .with_addr(SocketAddr::from((Ipv6Addr::LOCALHOST, 7070)));
+        assert!(ipv6.check().is_ok());
+
+        let any: SocketAddr = "0.0.0.0:7070".parse().unwrap();
+        assert!(matches!(
+            options.clone().with_addr(any).check(),
+            Err(ServeError::NonLoopback(addr)) if addr == any
+        ));
+        assert!(options.with_addr(any).allow_remote().check().is_ok());
+    }
+
+    #[test]
+    fn test_origins_must_match_the_host_or_be_allowed() {
+        let headers = |origin: Option<&str>| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::HOST, HeaderValue::from_static("127.0.0.1:7070"));
+            if let Some(origin) = origin {
+                headers.insert(header::ORIGIN, HeaderValue::from_str(origin).unwrap());
+            }
+            headers
+        };
+        let allowed = ["http://localhost:3000".to_string()];
+        assert!(origin_allowed(&headers(None), &[]));
+        assert!(origin_allowed(&headers(Some("http://127.0.0.1:7070")), &[]));
+        assert!(origin_allowed(&headers(Some("http://localhost:3000")), &allowed));
+        assert!(!origin_allowed(&headers(Some("http://localhost:3000")), &[]));
+        assert!(!origin_allowed(&headers(Some("https://evil.example")), &allowed));
+    }
+}
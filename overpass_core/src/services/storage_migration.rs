@@ -0,0 +1,197 @@
+// src/services/storage_migration.rs
+
+use thiserror::Error;
+
+use crate::services::overpass_db::OverpassDB;
+use crate::zkp::compressed_transaction::{upgrade_history, CompressedTransaction};
+use crate::zkp::mobile_optimized_storage::HISTORY_PREFIX;
+
+/// Key the schema version of a database is kept under.
+pub const SCHEMA_VERSION_KEY: &[u8] = b"schema_version";
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum MigrationError {
+    #[error("Database schema {found} is newer than the supported {supported}")]
+    UnsupportedSchema { found: u32, supported: u32 },
+    #[error("Storage error: {0}")]
+    StorageError(String),
+    #[error("Corrupt state: {0}")]
+    CorruptState(String),
+}
+
+/// One step from the schema version before `version` to `version`.
+#[derive(Clone, Copy)]
+pub struct Migration {
+    pub version: u32,
+    pub description: &'static str,
+    pub apply: fn(&OverpassDB) -> Result<(), MigrationError>,
+}
+
+/// Every migration, in version order. A database that was never migrated is at
+/// version 0.
+pub const MIGRATIONS: &[Migration] = &[Migration {
+    version: 1,
+    description: "Upgrade saved channel histories to the current transaction format",
+    apply: upgrade_histories,
+}];
+
+/// Gets the schema version of `db`, 0 if it has none.
+pub fn schema_version(db: &OverpassDB) -> Result<u32, MigrationError> {
+    match db
+        .get(SCHEMA_VERSION_KEY)
+        .map_err(|e| MigrationError::StorageError(e.to_string()))?
+    {
+        Some(bytes) => Ok(u32::from_le_bytes(bytes.as_slice().try_into().map_err(
+            |_| MigrationError::CorruptState("schema version is not 4 bytes".into()),
+        )?)),
+        None => Ok(0),
+    }
+}
+
+/// Applies the migrations `db` has not had yet and returns their versions.
+pub fn migrate(db: &OverpassDB) -> Result<Vec<u32>, MigrationError> {
+    migrate_with(db, MIGRATIONS)
+}
+
+/// Applies the pending `migrations`, recording the schema version after each so an
+/// interrupted run resumes where it stopped.
+pub fn migrate_with(db: &OverpassDB, migrations: &[Migration]) -> Result<Vec<u32>, MigrationError> {
+    let current = schema_version(db)?;
+    let supported = migrations.last().map_or(0, |migration| migration.version);
+    if current > supported {
+        return Err(MigrationError::UnsupportedSchema {
+            found: current,
+            supported,
+        });
+    }
+    let mut applied = Vec::new();
+    for migration in migrations.iter().filter(|m| m.version > current) {
+        (migration.apply)(db)?;
+        db.put(SCHEMA_VERSION_KEY, &migration.version.to_le_bytes())
+            .and_then(|_| db.flush())
+            .map_err(|e| MigrationError::StorageError(e.to_string()))?;
+        applied.push(migration.version);
+    }
+    Ok(applied)
+}
+
+/// Rewrites every saved channel history in the current transaction format.
+fn upgrade_histories(db: &OverpassDB) -> Result<(), MigrationError> {
+    let mut end = HISTORY_PREFIX.to_vec();
+    if let Some(last) = end.last_mut() {
+        *last += 1;
+    }
+    let saved = db
+        .scan(HISTORY_PREFIX, &end)
+        .map_err(|e| MigrationError::StorageError(e.to_string()))?;
+    for (key, bytes) in saved {
+        let history = CompressedTransaction::decode_proven(&bytes)
+            .map_err(|e| MigrationError::CorruptState(e.to_string()))?;
+        if history
+            .iter()
+            .all(|tx| tx.version == CompressedTransaction::CURRENT_VERSION)
+        {
+            continue;
+        }
+        let upgraded = CompressedTransaction::encode_proven(&upgrade_history(history));
+        db.put(&key, &upgraded)
+            .map_err(|e| MigrationError::StorageError(e.to_string()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zkp::history_replay::verify_chain;
+    use crate::zkp::mobile_optimized_storage::{history_key, MobileOptimizedStorage};
+    use crate::zkp::state_proof::StateProof;
+    use crate::zkp::tx_metadata::TxMetadata;
+
+    /// A history as a first-version device stored it, with every update starting from
+    /// a state hash rather than the commitment before it.
+    fn legacy_history(updates: u8) -> Vec<CompressedTransaction> {
+        let mut storage = MobileOptimizedStorage::new(3, 30 * 24 * 3600);
+        for i in 0..updates {
+            let proof = StateProof {
+                pi: [i; 32],
+                public_inputs: Vec::new(),
+                timestamp: 1_700_000_000 + u64::from(i),
+            };
+            storage
+                .store_transaction([1u8; 32], [i; 32], [i + 1; 32], proof, TxMetadata::None)
+                .unwrap();
+        }
+        let mut history = storage.history(&[1u8; 32]).to_vec();
+        for (index, tx) in history.iter_mut().enumerate() {
+            tx.version = CompressedTransaction::LEGACY_VERSION;
+            tx.old_commitment = [200 + index as u8; 32];
+        }
+        history
+    }
+
+    #[test]
+    fn test_migration_upgrades_saved_histories() -> anyhow::Result<()> {
+        let path = std::env::temp_dir().join(format!("storage_migration_{}", std::process::id()));
+        let db = OverpassDB::new(path.to_str().unwrap())?;
+        let legacy = legacy_history(8);
+        assert!(verify_chain(&legacy).is_err());
+        db.put(
+            &history_key(&[1u8; 32]),
+            &CompressedTransaction::encode_proven(&legacy),
+        )?;
+
+        assert_eq!(schema_version(&db)?, 0);
+        assert_eq!(migrate(&db)?, vec![1]);
+        assert_eq!(schema_version(&db)?, 1);
+        assert_eq!(migrate(&db)?, Vec::<u32>::new());
+
+        let mut storage = MobileOptimizedStorage::new(3, 30 * 24 * 3600);
+        storage
+            .restore_history(&db, [1u8; 32])
+            .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+        let history = storage.history(&[1u8; 32]);
+        assert!(history
+            .iter()
+            .all(|tx| tx.version == CompressedTransaction::CURRENT_VERSION));
+        let summary = verify_chain(history)?;
+        assert_eq!((summary.updates, summary.checkpoints), (8, 2));
+        assert_eq!(summary.head, Some([8u8; 32]));
+
+        db.put(SCHEMA_VERSION_KEY, &7u32.to_le_bytes())?;
+        assert_eq!(
+            migrate(&db),
+            Err(MigrationError::UnsupportedSchema {
+                found: 7,
+                supported: 1
+            })
+        );
+        drop(db);
+        let _ = std::fs::remove_dir_all(path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_legacy_records_upgrade_on_import() {
+        let legacy = legacy_history(5);
+        let json = serde_json::to_value(&legacy[0]).unwrap();
+        let mut unversioned = json.as_object().unwrap().clone();
+        unversioned.remove("version");
+        let decoded: CompressedTransaction =
+            serde_json::from_value(serde_json::Value::Object(unversioned)).unwrap();
+        assert_eq!(decoded, legacy[0]);
+
+        let mut storage = MobileOptimizedStorage::new(3, 30 * 24 * 3600);
+        storage.import_history([1u8; 32], legacy.clone());
+        let upgraded = storage.history(&[1u8; 32]);
+        assert_eq!(upgraded[0].old_commitment, legacy[0].old_commitment);
+        assert_eq!(verify_chain(upgraded).unwrap().updates, 5);
+
+        let mut bytes = legacy[0].to_bytes();
+        bytes[0] = 9;
+        assert_eq!(
+            CompressedTransaction::from_bytes(&bytes),
+            Err(crate::zkp::compressed_transaction::DeltaError::UnsupportedVersion(9))
+        );
+    }
+}
@@ -0,0 +1,544 @@
+// ./src/services/watch_service.rs
+
+use crate::bitcoin::broadcast::{BroadcastOutcome, BroadcastQueue, RetryPolicy};
+use crate::bitcoin::chain::ChainBackend;
+use crate::services::overpass_db::OverpassDB;
+use crate::services::server::{bind, ServeError};
+use axum::{
+    extract::{ConnectInfo, State},
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use bitcoin::consensus::encode;
+use bitcoin::{OutPoint, Transaction, Txid};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit},
+    ChaCha20Poly1305, Nonce,
+};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+use thiserror::Error;
+
+/// Key prefix under which appointments are persisted.
+const APPOINTMENT_PREFIX: &[u8] = b"watch_appointment:";
+const NONCE_LEN: usize = 12;
+/// Default number of appointments one client may have stored at a time.
+pub const DEFAULT_MAX_APPOINTMENTS_PER_CLIENT: usize = 1_000;
+
+#[derive(Error, Debug)]
+pub enum WatchServiceError {
+    #[error("Storage error: {0}")]
+    StorageError(String),
+    #[error("Encryption error: {0}")]
+    EncryptionError(String),
+    #[error("Chain error: {0}")]
+    ChainError(String),
+    #[error("Invalid appointment: {0}")]
+    InvalidAppointment(String),
+    #[error("Client {0} has too many appointments")]
+    TooManyAppointments(String),
+}
+
+/// Encrypted penalty data handed to a watch service.
+///
+/// The penalty transaction is encrypted under a key derived from the revoked commitment's
+/// txid, so the service learns nothing about the channel until that commitment is published.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Appointment {
+    /// Funding outpoint the revoked commitment spends.
+    pub funding_outpoint: OutPoint,
+    /// Locator derived from the revoked commitment's txid.
+    pub hint: [u8; 16],
+    /// `nonce || ChaCha20-Poly1305(penalty transaction)`.
+    pub encrypted_penalty: Vec<u8>,
+}
+
+impl Appointment {
+    /// Encrypts a penalty transaction for the revoked commitment `breach_txid`.
+    pub fn new(
+        funding_outpoint: OutPoint,
+        breach_txid: &Txid,
+        penalty_tx: &Transaction,
+    ) -> Result<Self, WatchServiceError> {
+        let cipher = ChaCha20Poly1305::new_from_slice(&derive_key(breach_txid))
+            .map_err(|e| WatchServiceError::EncryptionError(e.to_string()))?;
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(&nonce), encode::serialize(penalty_tx).as_slice())
+            .map_err(|e| WatchServiceError::EncryptionError(e.to_string()))?;
+
+        let mut encrypted_penalty = nonce.to_vec();
+        encrypted_penalty.extend(ciphertext);
+        Ok(Self {
+            funding_outpoint,
+            hint: derive_hint(breach_txid),
+            encrypted_penalty,
+        })
+    }
+
+    /// Decrypts the penalty transaction once the breach txid is known.
+    pub fn decrypt(&self, breach_txid: &Txid) -> Result<Transaction, WatchServiceError> {
+        if self.encrypted_penalty.len() <= NONCE_LEN {
+            return Err(WatchServiceError::InvalidAppointment(
+                "Encrypted penalty too short".to_string(),
+            ));
+        }
+        let (nonce, ciphertext) = self.encrypted_penalty.split_at(NONCE_LEN);
+        let cipher = ChaCha20Poly1305::new_from_slice(&derive_key(breach_txid))
+            .map_err(|e| WatchServiceError::EncryptionError(e.to_string()))?;
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|e| WatchServiceError::EncryptionError(e.to_string()))?;
+        encode::deserialize(&plaintext)
+            .map_err(|e| WatchServiceError::InvalidAppointment(e.to_string()))
+    }
+}
+
+fn derive_hint(txid: &Txid) -> [u8; 16] {
+    let digest = Sha256::new()
+        .chain_update(b"overpass/watch/hint")
+        .chain_update(txid.as_ref() as &[u8])
+        .finalize();
+    let mut hint = [0u8; 16];
+    hint.copy_from_slice(&digest[..16]);
+    hint
+}
+
+fn derive_key(txid: &Txid) -> [u8; 32] {
+    Sha256::new()
+        .chain_update(b"overpass/watch/key")
+        .chain_update(txid.as_ref() as &[u8])
+        .finalize()
+        .into()
+}
+
+/// A penalty the service has broadcast.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PenaltyBroadcast {
+    pub funding_outpoint: OutPoint,
+    pub breach_txid: Txid,
+    pub penalty_txid: Txid,
+}
+
+/// Watches funding outpoints on behalf of offline clients and broadcasts their penalties.
+///
+/// Usable in-process by calling [`WatchService::poll`], or as a daemon via [`serve`]. Only
+/// admitting an appointment takes a lock, so the chain poll never waits on the API.
+pub struct WatchService {
+    db: Arc<OverpassDB>,
+    queue: BroadcastQueue,
+    max_appointments_per_client: usize,
+    /// Held from counting a client's appointments until the new one is stored.
+    admission: Mutex<()>,
+}
+
+impl WatchService {
+    pub fn new(db: Arc<OverpassDB>, retry_policy: RetryPolicy) -> Self {
+        Self {
+            queue: BroadcastQueue::new(db.clone(), retry_policy),
+            db,
+            max_appointments_per_client: DEFAULT_MAX_APPOINTMENTS_PER_CLIENT,
+            admission: Mutex::new(()),
+        }
+    }
+
+    /// Limits how many appointments one client may have stored at a time.
+    pub fn with_max_appointments_per_client(mut self, max: usize) -> Self {
+        self.max_appointments_per_client = max;
+        self
+    }
+
+    /// Stores an appointment on behalf of `client`; duplicates are ignored.
+    pub fn add_appointment(&self, client: &str, appointment: &Appointment) -> Result<(), WatchServiceError> {
+        if appointment.encrypted_penalty.len() <= NONCE_LEN {
+            return Err(WatchServiceError::InvalidAppointment(
+                "Encrypted penalty too short".to_string(),
+            ));
+        }
+        let key = appointment_key(client, appointment);
+        let (start, end) = client_range(client);
+        let _admission = self.admission.lock().unwrap_or_else(PoisonError::into_inner);
+        let stored = self
+            .db
+            .scan(&start, &end)
+            .map_err(|e| WatchServiceError::StorageError(e.to_string()))?;
+        if stored.len() >= self.max_appointments_per_client && !stored.iter().any(|(k, _)| *k == key) {
+            return Err(WatchServiceError::TooManyAppointments(client.to_string()));
+        }
+
+        let bytes = serde_json::to_vec(appointment)
+            .map_err(|e| WatchServiceError::StorageError(e.to_string()))?;
+        self.db
+            .put(&key, &bytes)
+            .map_err(|e| WatchServiceError::StorageError(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Lists stored appointments.
+    pub fn appointments(&self) -> Result<Vec<Appointment>, WatchServiceError> {
+        Ok(self.stored()?.into_iter().map(|(_, appointment)| appointment).collect())
+    }
+
+    fn stored(&self) -> Result<Vec<(Vec<u8>, Appointment)>, WatchServiceError> {
+        let mut end = APPOINTMENT_PREFIX.to_vec();
+        *end.last_mut().expect("non-empty prefix") += 1;
+        self.db
+            .scan(APPOINTMENT_PREFIX, &end)
+            .map_err(|e| WatchServiceError::StorageError(e.to_string()))?
+            .into_iter()
+            .map(|(key, bytes)| {
+                serde_json::from_slice(&bytes)
+                    .map(|appointment| (key, appointment))
+                    .map_err(|e| WatchServiceError::StorageError(e.to_string()))
+            })
+            .collect()
+    }
+
+    /// Checks watched outpoints for revoked commitments and broadcasts matching penalties.
+    pub fn poll(
+        &self,
+        backend: &dyn ChainBackend,
+        now: u64,
+    ) -> Result<Vec<PenaltyBroadcast>, WatchServiceError> {
+        let appointments = self.stored()?;
+        let mut outpoints: Vec<OutPoint> = appointments.iter().map(|(_, a)| a.funding_outpoint).collect();
+        outpoints.sort();
+        outpoints.dedup();
+
+        let mut penalties = Vec::new();
+        for outpoint in outpoints {
+            // One outpoint's lookup failing must not stop penalties for the others.
+            let spend = match backend.spending_transaction(&outpoint) {
+                Ok(spend) => spend,
+                Err(e) => {
+                    tracing::warn!(
+                        funding_outpoint = %outpoint,
+                        "Failed to look up funding spend: {}",
+                        e
+                    );
+                    continue;
+                }
+            };
+            let Some(spend) = spend else {
+                continue;
+            };
+            let breach_txid = spend.txid();
+            let hint = derive_hint(&breach_txid);
+
+            // A spend by the latest state or a cooperative close matches no hint.
+            for (_, appointment) in appointments
+                .iter()
+                .filter(|(_, a)| a.funding_outpoint == outpoint && a.hint == hint)
+            {
+                let Ok(penalty) = appointment.decrypt(&breach_txid) else {
+                    continue;
+                };
+                let penalty_txid = self
+                    .queue
+                    .enqueue(penalty, now)
+                    .map_err(|e| WatchServiceError::StorageError(e.to_string()))?;
+                penalties.push(PenaltyBroadcast {
+                    funding_outpoint: outpoint,
+                    breach_txid,
+                    penalty_txid,
+                });
+            }
+            for (key, _) in appointments.iter().filter(|(_, a)| a.funding_outpoint == outpoint) {
+                self.db
+                    .delete(key)
+                    .map_err(|e| WatchServiceError::StorageError(e.to_string()))?;
+            }
+        }
+
+        self.process_broadcasts(backend, now)?;
+        Ok(penalties)
+    }
+
+    /// Retries and rebroadcasts queued penalties until they confirm.
+    pub fn process_broadcasts(
+        &self,
+        backend: &dyn ChainBackend,
+        now: u64,
+    ) -> Result<Vec<BroadcastOutcome>, WatchServiceError> {
+        self.queue
+            .process(backend, now)
+            .map_err(|e| WatchServiceError::StorageError(e.to_string()))
+    }
+}
+
+/// Gets the key range holding `client`'s appointments.
+fn client_range(client: &str) -> (Vec<u8>, Vec<u8>) {
+    let mut start = APPOINTMENT_PREFIX.to_vec();
+    start.extend_from_slice(hex::encode(client).as_bytes());
+    start.push(b':');
+    let mut end = start.clone();
+    *end.last_mut().expect("non-empty prefix") += 1;
+    (start, end)
+}
+
+fn appointment_key(client: &str, appointment: &Appointment) -> Vec<u8> {
+    let (mut key, _) = client_range(client);
+    key.extend_from_slice(appointment.funding_outpoint.to_string().as_bytes());
+    key.push(b':');
+    key.extend_from_slice(hex::encode(appointment.hint).as_bytes());
+    key.push(b':');
+    key.extend_from_slice(hex::encode(Sha256::digest(&appointment.encrypted_penalty)).as_bytes());
+    key
+}
+
+async fn add_appointment_handler(
+    State(service): State<Arc<WatchService>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    Json(appointment): Json<Appointment>,
+) -> StatusCode {
+    let client = peer.ip().to_string();
+    let result = tokio::task::spawn_blocking(move || service.add_appointment(&client, &appointment)).await;
+    match result {
+        Ok(Ok(())) => StatusCode::ACCEPTED,
+        Ok(Err(WatchServiceError::InvalidAppointment(_))) => StatusCode::BAD_REQUEST,
+        Ok(Err(WatchServiceError::TooManyAppointments(_))) => StatusCode::TOO_MANY_REQUESTS,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// Builds the HTTP API used by remote clients to submit appointments.
+///
+/// Appointments are counted per client IP, so the router must be served with
+/// `into_make_service_with_connect_info::<SocketAddr>()`.
+pub fn router(service: Arc<WatchService>) -> Router {
+    Router::new()
+        .route("/appointments", post(add_appointment_handler))
+        .route("/health", get(|| async { StatusCode::OK }))
+        .with_state(service)
+}
+
+/// Runs the watch service as a daemon: serves the appointment API and polls the chain.
+pub async fn serve(
+    service: Arc<WatchService>,
+    backend: Arc<dyn ChainBackend>,
+    addr: SocketAddr,
+    poll_interval: Duration,
+) -> Result<(), ServeError> {
+    let watcher = service.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(poll_interval);
+        loop {
+            interval.tick().await;
+            let service = watcher.clone();
+            let backend = backend.clone();
+            let result = tokio::task::spawn_blocking(move || {
+                let now = chrono::Utc::now().timestamp() as u64;
+                service.poll(backend.as_ref(), now)
+            })
+            .await;
+            match result {
+                Ok(Ok(penalties)) => {
+                    for penalty in penalties {
+                        tracing::warn!(
+                            "Broadcasting penalty {} against revoked commitment {}",
+                            penalty.penalty_txid,
+                            penalty.breach_txid
+                        );
+                    }
+                }
+                Ok(Err(e)) => tracing::error!("Watch service poll failed: {}", e),
+                Err(e) => tracing::error!("Watch service task failed: {}", e),
+            }
+        }
+    });
+
+    let listener = bind(addr).await?;
+    axum::serve(
+        listener,
+        router(service).into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::chain::ChainError;
+    use crate::bitcoin::monitor::apply_state_number;
+    use bitcoin::absolute::LockTime;
+    use bitcoin::hashes::Hash;
+    use bitcoin::{ScriptBuf, Sequence, TxIn, TxOut, Witness};
+    use std::sync::Mutex;
+
+    struct MockChain {
+        spend: Option<Transaction>,
+        /// An outpoint whose lookup fails, as Core's does without `-txindex`.
+        failing: Option<OutPoint>,
+        broadcasts: Mutex<Vec<Txid>>,
+    }
+
+    impl ChainBackend for MockChain {
+        fn broadcast(&self, tx: &Transaction) -> Result<Txid, ChainError> {
+            self.broadcasts.lock().unwrap().push(tx.txid());
+            Ok(tx.txid())
+        }
+
+        fn confirmations(&self, _txid: &Txid) -> Result<Option<u32>, ChainError> {
+            Ok(Some(0))
+        }
+
+        fn tip_height(&self) -> Result<u32, ChainError> {
+            Ok(0)
+        }
+
+        fn spending_transaction(&self, outpoint: &OutPoint) -> Result<Option<Transaction>, ChainError> {
+            if self.failing == Some(*outpoint) {
+                return Err(ChainError::BackendError(format!(
+                    "{} is spent but no spending transaction was found",
+                    outpoint
+                )));
+            }
+            Ok(self.spend.clone())
+        }
+    }
+
+    fn spend(previous_output: OutPoint, state_number: u64) -> Transaction {
+        let mut tx = Transaction {
+            version: 2,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output,
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::MAX,
+                witness: Witness::new(),
+            }],
+            output: vec![TxOut {
+                value: 10_000,
+                script_pubkey: ScriptBuf::new(),
+            }],
+        };
+        apply_state_number(&mut tx, state_number).unwrap();
+        tx
+    }
+
+    #[test]
+    fn test_appointment_roundtrip() {
+        let funding = OutPoint::new(Txid::all_zeros(), 0);
+        let breach = spend(funding, 1);
+        let penalty = spend(OutPoint::new(breach.txid(), 0), 0);
+
+        let appointment = Appointment::new(funding, &breach.txid(), &penalty).unwrap();
+        assert_eq!(appointment.decrypt(&breach.txid()).unwrap(), penalty);
+        assert!(appointment.decrypt(&spend(funding, 2).txid()).is_err());
+    }
+
+    #[test]
+    fn test_broadcasts_penalty_only_on_breach() -> anyhow::Result<()> {
+        let path = std::env::temp_dir().join(format!("watch_service_{}", std::process::id()));
+        let service = WatchService::new(
+            Arc::new(OverpassDB::new(path.to_str().unwrap())?),
+            RetryPolicy::default(),
+        );
+
+        let funding = OutPoint::new(Txid::all_zeros(), 0);
+        let revoked = spend(funding, 1);
+        let penalty = spend(OutPoint::new(revoked.txid(), 0), 0);
+        let appointment = Appointment::new(funding, &revoked.txid(), &penalty)?;
+        service.add_appointment("alice", &appointment)?;
+        service.add_appointment("alice", &appointment)?;
+        assert_eq!(service.appointments()?.len(), 1);
+
+        // A close with the latest state matches no appointment.
+        let honest = MockChain {
+            spend: Some(spend(funding, 2)),
+            failing: None,
+            broadcasts: Mutex::new(Vec::new()),
+        };
+        assert!(service.poll(&honest, 1_000)?.is_empty());
+        assert!(honest.broadcasts.lock().unwrap().is_empty());
+
+        service.add_appointment("alice", &appointment)?;
+        let breach = MockChain {
+            spend: Some(revoked.clone()),
+            failing: None,
+            broadcasts: Mutex::new(Vec::new()),
+        };
+        let penalties = service.poll(&breach, 2_000)?;
+        assert_eq!(penalties.len(), 1);
+        assert_eq!(penalties[0].breach_txid, revoked.txid());
+        assert_eq!(*breach.broadcasts.lock().unwrap(), vec![penalty.txid()]);
+        assert!(service.appointments()?.is_empty());
+
+        std::fs::remove_dir_all(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_caps_appointments_per_client() -> anyhow::Result<()> {
+        let path = std::env::temp_dir().join(format!("watch_service_cap_{}", std::process::id()));
+        let service = WatchService::new(
+            Arc::new(OverpassDB::new(path.to_str().unwrap())?),
+            RetryPolicy::default(),
+        )
+        .with_max_appointments_per_client(1);
+
+        let funding = OutPoint::new(Txid::all_zeros(), 0);
+        let appointment = |state| {
+            let revoked = spend(funding, state);
+            Appointment::new(funding, &revoked.txid(), &spend(OutPoint::new(revoked.txid(), 0), 0))
+        };
+        let first = appointment(1)?;
+        service.add_appointment("alice", &first)?;
+        // Resubmitting a stored appointment is not counted twice.
+        service.add_appointment("alice", &first)?;
+        assert!(matches!(
+            service.add_appointment("alice", &appointment(2)?),
+            Err(WatchServiceError::TooManyAppointments(_))
+        ));
+        service.add_appointment("bob", &appointment(2)?)?;
+        assert_eq!(service.appointments()?.len(), 2);
+
+        std::fs::remove_dir_all(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_failed_lookup_does_not_stop_other_penalties() -> anyhow::Result<()> {
+        let path = std::env::temp_dir().join(format!("watch_service_lookup_{}", std::process::id()));
+        let service = WatchService::new(
+            Arc::new(OverpassDB::new(path.to_str().unwrap())?),
+            RetryPolicy::default(),
+        );
+
+        // The unreadable outpoint sorts first, so it is looked up first.
+        let unreadable = OutPoint::new(Txid::all_zeros(), 0);
+        let breached = OutPoint::new(Txid::all_zeros(), 1);
+        let revoked = spend(breached, 1);
+        let penalty = spend(OutPoint::new(revoked.txid(), 0), 0);
+        let stuck = spend(unreadable, 1);
+        service.add_appointment(
+            "alice",
+            &Appointment::new(unreadable, &stuck.txid(), &spend(OutPoint::new(stuck.txid(), 0), 0))?,
+        )?;
+        service.add_appointment("bob", &Appointment::new(breached, &revoked.txid(), &penalty)?)?;
+
+        let chain = MockChain {
+            spend: Some(revoked.clone()),
+            failing: Some(unreadable),
+            broadcasts: Mutex::new(Vec::new()),
+        };
+        let penalties = service.poll(&chain, 1_000)?;
+        assert_eq!(penalties.len(), 1);
+        assert_eq!(penalties[0].funding_outpoint, breached);
+        assert_eq!(*chain.broadcasts.lock().unwrap(), vec![penalty.txid()]);
+        // The unreadable outpoint stays watched for the next poll.
+        let remaining = service.appointments()?;
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].funding_outpoint, unreadable);
+
+        std::fs::remove_dir_all(&path)?;
+        Ok(())
+    }
+}
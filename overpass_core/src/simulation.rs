@@ -0,0 +1,519 @@
+// ./src/simulation.rs
+
+//! Many wallets paying each other in-process under a scripted or random workload, with
+//! the protocol's invariants checked after every step.
+
+use crate::bitcoin::keys::KeyManager;
+use crate::zkp::global_root_contract::GlobalRootContract;
+use crate::zkp::helpers::Bytes32;
+use crate::zkp::pedersen_parameters::PedersenParameters;
+use crate::zkp::reconciliation::{resolve, ChannelEvidence, ReconciliationError, Side};
+use crate::zkp::wallet_contract::{WalletContract, WalletContractError};
+use crate::zkp::watch_only::sign_transition;
+use bip39::Mnemonic;
+use bitcoin::Network;
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore, SeedableRng};
+use sha2::{Digest, Sha256};
+use std::collections::VecDeque;
+use std::sync::Arc;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum SimulationError {
+    #[error("Wallet error: {0}")]
+    Wallet(#[from] WalletContractError),
+    #[error("Reconciliation error: {0}")]
+    Reconciliation(#[from] ReconciliationError),
+    #[error("Unknown wallet {0}")]
+    UnknownWallet(usize),
+    #[error("Invariant broken after step {step}: {violation}")]
+    Invariant { step: usize, violation: String },
+}
+
+/// One end of a link.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Party {
+    A,
+    B,
+}
+
+impl Party {
+    fn index(self) -> usize {
+        match self {
+            Party::A => 0,
+            Party::B => 1,
+        }
+    }
+
+    fn other(self) -> Party {
+        match self {
+            Party::A => Party::B,
+            Party::B => Party::A,
+        }
+    }
+}
+
+/// A step of a workload. Steps on closed links are skipped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Pay {
+        link: usize,
+        from: Party,
+        amount: u64,
+    },
+    /// Holds payments over the link until it reconnects.
+    Disconnect {
+        link: usize,
+    },
+    Reconnect {
+        link: usize,
+    },
+    /// `cheater` closes with the state the link opened at, which its counterparty must
+    /// refute with a later one.
+    Dispute {
+        link: usize,
+        cheater: Party,
+    },
+    Close {
+        link: usize,
+    },
+}
+
+/// Counts of what a run did.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Report {
+    pub steps: usize,
+    pub payments: usize,
+    /// Payments the payer's side of the link could not cover.
+    pub failed_payments: usize,
+    pub delivered: usize,
+    pub disconnects: usize,
+    pub disputes: usize,
+    pub stale_claims_refuted: usize,
+    pub closes: usize,
+    pub skipped: usize,
+}
+
+struct SimWallet {
+    keys: Arc<KeyManager>,
+    contract: WalletContract,
+}
+
+struct Transfer {
+    to: Party,
+    amount: u64,
+}
+
+/// Two channels, one in each wallet, sharing a capacity.
+struct Link {
+    wallets: [usize; 2],
+    channels: [Bytes32; 2],
+    capacity: u64,
+    connected: bool,
+    closed: bool,
+    in_flight: VecDeque<Transfer>,
+    /// Each side's state when the link opened, what a cheating close presents.
+    opened: [ChannelEvidence; 2],
+}
+
+impl Link {
+    fn pending(&self) -> u64 {
+        self.in_flight.iter().map(|transfer| transfer.amount).sum()
+    }
+}
+
+pub struct Simulation {
+    params: PedersenParameters,
+    wallets: Vec<SimWallet>,
+    links: Vec<Link>,
+    /// Value put into links, which must always be accounted for.
+    total: u64,
+    report: Report,
+}
+
+impl Simulation {
+    /// Creates `wallets` wallets with keys drawn from `seed`, so a run can be replayed.
+    pub fn new(wallets: usize, seed: u64) -> Result<Self, SimulationError> {
+        let params = PedersenParameters::default();
+        let mut rng = StdRng::seed_from_u64(seed);
+        let wallets = (0..wallets)
+            .map(|index| {
+                let mut entropy = [0u8; 16];
+                rng.fill_bytes(&mut entropy);
+                let phrase = Mnemonic::from_entropy(&entropy)
+                    .expect("16 bytes is a valid entropy length")
+                    .to_string();
+                let keys = Arc::new(
+                    KeyManager::from_mnemonic(&phrase, "", Network::Regtest)
+                        .map_err(WalletContractError::KeyDerivationError)?,
+                );
+                let wallet_id: Bytes32 = Sha256::new()
+                    .chain_update(b"overpass/simulation-wallet")
+                    .chain_update((index as u64).to_le_bytes())
+                    .finalize()
+                    .into();
+                let contract = WalletContract::new(
+                    wallet_id,
+                    params.clone(),
+                    GlobalRootContract::new(params.clone()),
+                )
+                .with_key_manager(keys.clone());
+                Ok(SimWallet { keys, contract })
+            })
+            .collect::<Result<_, SimulationError>>()?;
+        Ok(Self {
+            params,
+            wallets,
+            links: Vec::new(),
+            total: 0,
+            report: Report::default(),
+        })
+    }
+
+    /// Opens a link between wallets `a` and `b` with the given balances on each side.
+    pub fn open_link(
+        &mut self,
+        a: usize,
+        b: usize,
+        a_balance: u64,
+        b_balance: u64,
+    ) -> Result<usize, SimulationError> {
+        let ids = [self.wallet(a)?.wallet_id, self.wallet(b)?.wallet_id];
+        let a_channel = self.wallets[a]
+            .contract
+            .open_channel(a_balance, ids[1], Vec::new())?;
+        let b_channel = self.wallets[b]
+            .contract
+            .open_channel(b_balance, ids[0], Vec::new())?;
+        let opened = [
+            self.wallets[a].contract.channel_evidence(&a_channel)?,
+            self.wallets[b].contract.channel_evidence(&b_channel)?,
+        ];
+        self.links.push(Link {
+            wallets: [a, b],
+            channels: [a_channel, b_channel],
+            capacity: a_balance + b_balance,
+            connected: true,
+            closed: false,
+            in_flight: VecDeque::new(),
+            opened,
+        });
+        self.total += a_balance + b_balance;
+        self.check(self.report.steps)?;
+        Ok(self.links.len() - 1)
+    }
+
+    pub fn wallet(&self, index: usize) -> Result<&WalletContract, SimulationError> {
+        self.wallets
+            .get(index)
+            .map(|wallet| &wallet.contract)
+            .ok_or(SimulationError::UnknownWallet(index))
+    }
+
+    pub fn report(&self) -> &Report {
+        &self.report
+    }
+
+    /// Runs every action, failing at the first broken invariant.
+    pub fn run(
+        &mut self,
+        actions: impl IntoIterator<Item = Action>,
+    ) -> Result<&Report, SimulationError> {
+        for action in actions {
+            self.step(action)?;
+        }
+        Ok(&self.report)
+    }
+
+    /// Applies one action, then checks the invariants.
+    pub fn step(&mut self, action: Action) -> Result<(), SimulationError> {
+        self.report.steps += 1;
+        let link = match action {
+            Action::Pay { link, .. }
+            | Action::Disconnect { link }
+            | Action::Reconnect { link }
+            | Action::Dispute { link, .. }
+            | Action::Close { link } => link,
+        };
+        if self.links.get(link).is_none_or(|link| link.closed) {
+            self.report.skipped += 1;
+            return Ok(());
+        }
+        match action {
+            Action::Pay { link, from, amount } => self.pay(link, from, amount)?,
+            Action::Disconnect { link } => {
+                self.links[link].connected = false;
+                self.report.disconnects += 1;
+            }
+            Action::Reconnect { link } => {
+                self.links[link].connected = true;
+                self.deliver(link)?;
+            }
+            Action::Dispute { link, cheater } => self.dispute(link, cheater)?,
+            Action::Close { link } => {
+                self.deliver_all(link)?;
+                self.close(link)?;
+            }
+        }
+        self.check(self.report.steps)
+    }
+
+    fn pay(&mut self, index: usize, from: Party, amount: u64) -> Result<(), SimulationError> {
+        let link = &self.links[index];
+        let payer = link.wallets[from.index()];
+        let channel = link.channels[from.index()];
+        let payee_id = self.wallets[link.wallets[from.other().index()]]
+            .contract
+            .wallet_id;
+        let balance = self.own_balance(payer, &channel);
+        if amount == 0 || balance < amount {
+            self.report.failed_payments += 1;
+            return Ok(());
+        }
+        let payment = self.wallets[payer]
+            .contract
+            .pay(payee_id, amount)
+            .with_channel_hints(vec![channel])
+            .send()?;
+        if payment.channel_id != channel {
+            return Err(self.violation(format!(
+                "payment over link {} left through another channel",
+                index
+            )));
+        }
+        self.report.payments += 1;
+        self.links[index].in_flight.push_back(Transfer {
+            to: from.other(),
+            amount,
+        });
+        self.deliver(index)
+    }
+
+    /// Credits the payments held on a link, if it is connected.
+    fn deliver(&mut self, index: usize) -> Result<(), SimulationError> {
+        if self.links[index].connected {
+            self.deliver_all(index)?;
+        }
+        Ok(())
+    }
+
+    fn deliver_all(&mut self, index: usize) -> Result<(), SimulationError> {
+        while let Some(transfer) = self.links[index].in_flight.pop_front() {
+            let link = &self.links[index];
+            let wallet = &mut self.wallets[link.wallets[transfer.to.index()]];
+            let channel = link.channels[transfer.to.index()];
+            let balance = wallet
+                .contract
+                .get_channel(&channel)
+                .and_then(|state| state.balances.first().copied())
+                .unwrap_or(0);
+            let transition = wallet.contract.prepare_transition(
+                channel,
+                balance + transfer.amount,
+                Vec::new(),
+            )?;
+            let signed = sign_transition(&wallet.keys, &self.params, &transition)?;
+            wallet.contract.apply_signed_transition(signed)?;
+            self.report.delivered += 1;
+        }
+        Ok(())
+    }
+
+    /// Refutes a close at the cheater's opening state with its latest one, then closes.
+    fn dispute(&mut self, index: usize, cheater: Party) -> Result<(), SimulationError> {
+        self.report.disputes += 1;
+        // A close on chain reaches the counterparty whatever the connection.
+        self.deliver_all(index)?;
+        let link = &self.links[index];
+        let claim = &link.opened[cheater.index()];
+        let latest = self.wallets[link.wallets[cheater.index()]]
+            .contract
+            .channel_evidence(&link.channels[cheater.index()])?;
+        // Equal nonces with different states are a conflict, which `resolve` reports.
+        let refuted = claim.state.nonce < latest.state.nonce;
+        if resolve(&latest, claim)? != Side::Local {
+            return Err(self.violation(format!("stale close on link {} was accepted", index)));
+        }
+        if refuted {
+            self.report.stale_claims_refuted += 1;
+        }
+        self.close(index)
+    }
+
+    fn close(&mut self, index: usize) -> Result<(), SimulationError> {
+        let link = &mut self.links[index];
+        link.closed = true;
+        for party in [Party::A, Party::B] {
+            self.wallets[link.wallets[party.index()]]
+                .contract
+                .close_channel(&link.channels[party.index()])?;
+        }
+        self.report.closes += 1;
+        Ok(())
+    }
+
+    fn own_balance(&self, wallet: usize, channel: &Bytes32) -> u64 {
+        self.wallets[wallet]
+            .contract
+            .get_channel(channel)
+            .and_then(|state| state.balances.first().copied())
+            .unwrap_or(0)
+    }
+
+    fn violation(&self, violation: String) -> SimulationError {
+        SimulationError::Invariant {
+            step: self.report.steps,
+            violation,
+        }
+    }
+
+    /// Checks that no value was created or lost, that each link still holds its capacity
+    /// and that every open channel's commitment opens to its balance.
+    fn check(&self, step: usize) -> Result<(), SimulationError> {
+        let fail = |violation: String| Err(SimulationError::Invariant { step, violation });
+        let held: u64 = self
+            .wallets
+            .iter()
+            .map(|wallet| {
+                let balance = wallet.contract.balance();
+                balance.open + balance.closed
+            })
+            .sum();
+        let pending: u64 = self.links.iter().map(Link::pending).sum();
+        if held + pending != self.total {
+            return fail(format!(
+                "wallets hold {} with {} in flight, but {} was put in",
+                held, pending, self.total
+            ));
+        }
+        for (index, link) in self.links.iter().enumerate() {
+            if link.closed {
+                continue;
+            }
+            let sides = self.own_balance(link.wallets[0], &link.channels[0])
+                + self.own_balance(link.wallets[1], &link.channels[1]);
+            if sides + link.pending() != link.capacity {
+                return fail(format!(
+                    "link {} holds {} of its capacity {}",
+                    index,
+                    sides + link.pending(),
+                    link.capacity
+                ));
+            }
+            for party in [Party::A, Party::B] {
+                let wallet = &self.wallets[link.wallets[party.index()]].contract;
+                if !wallet.reopen_channel(&link.channels[party.index()])? {
+                    return fail(format!(
+                        "commitment of link {} side {:?} does not open to its balance",
+                        index, party
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Draws `steps` actions over `links` links from `seed`, mostly payments of up to
+/// `max_amount`.
+pub fn random_workload(seed: u64, links: usize, steps: usize, max_amount: u64) -> Vec<Action> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let party = |rng: &mut StdRng| if rng.gen() { Party::A } else { Party::B };
+    (0..steps)
+        .map(|_| {
+            let link = rng.gen_range(0..links.max(1));
+            match rng.gen_range(0..200) {
+                0..=169 => Action::Pay {
+                    link,
+                    from: party(&mut rng),
+                    amount: rng.gen_range(1..=max_amount.max(1)),
+                },
+                170..=183 => Action::Disconnect { link },
+                184..=197 => Action::Reconnect { link },
+                198 => Action::Dispute {
+                    link,
+                    cheater: party(&mut rng),
+                },
+                _ => Action::Close { link },
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scripted_run_settles_every_link() {
+        let mut simulation = Simulation::new(3, 7).unwrap();
+        let first = simulation.open_link(0, 1, 100, 50).unwrap();
+        let second = simulation.open_link(1, 2, 80, 0).unwrap();
+
+        let report = simulation
+            .run([
+                Action::Pay {
+                    link: first,
+                    from: Party::A,
+                    amount: 30,
+                },
+                Action::Disconnect { link: first },
+                Action::Pay {
+                    link: first,
+                    from: Party::B,
+                    amount: 60,
+                },
+                Action::Pay {
+                    link: first,
+                    from: Party::B,
+                    amount: 30,
+                },
+                Action::Reconnect { link: first },
+                Action::Pay {
+                    link: second,
+                    from: Party::A,
+                    amount: 5,
+                },
+                Action::Dispute {
+                    link: first,
+                    cheater: Party::A,
+                },
+                Action::Close { link: second },
+                Action::Pay {
+                    link: second,
+                    from: Party::A,
+                    amount: 1,
+                },
+            ])
+            .unwrap()
+            .clone();
+
+        assert_eq!(report.payments, 3);
+        assert_eq!(report.failed_payments, 1);
+        assert_eq!(report.delivered, 3);
+        assert_eq!(report.stale_claims_refuted, 1);
+        assert_eq!(report.closes, 2);
+        assert_eq!(report.skipped, 1);
+        let closed = |index| simulation.wallet(index).unwrap().balance().closed;
+        assert_eq!(closed(0), 70 + 60);
+        assert_eq!(closed(1), 20 + 75);
+        assert_eq!(closed(2), 5);
+    }
+
+    #[test]
+    fn test_random_workload_keeps_invariants() {
+        let mut simulation = Simulation::new(4, 42).unwrap();
+        let pairs = [(0, 1), (1, 2), (2, 3), (3, 0), (0, 2)];
+        for &(a, b) in &pairs {
+            simulation.open_link(a, b, 1_000, 500).unwrap();
+        }
+
+        let report = simulation
+            .run(random_workload(42, pairs.len(), 120, 400))
+            .unwrap();
+        assert_eq!(report.steps, 120);
+        assert!(report.payments > 0);
+        assert!(report.delivered > 0);
+    }
+}
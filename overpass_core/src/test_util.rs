@@ -0,0 +1,45 @@
+// ./src/test_util.rs
+
+//! Fixtures shared by the unit tests: the BIP-39 test mnemonic and wallets built on it.
+
+use crate::bitcoin::keys::KeyManager;
+use crate::zkp::channel::ChannelState;
+use crate::zkp::global_root_contract::GlobalRootContract;
+use crate::zkp::helpers::Bytes32;
+use crate::zkp::pedersen_parameters::PedersenParameters;
+use crate::zkp::wallet_contract::WalletContract;
+use bitcoin::Network;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// The all-`abandon` BIP-39 test mnemonic.
+pub(crate) const PHRASE: &str = "abandon abandon abandon abandon abandon abandon abandon abandon \
+                                 abandon abandon abandon about";
+
+/// Gets the regtest key manager for [`PHRASE`].
+pub(crate) fn key_manager() -> Arc<KeyManager> {
+    Arc::new(KeyManager::from_mnemonic(PHRASE, "", Network::Regtest).unwrap())
+}
+
+/// Creates a wallet without keys, under default parameters and its own global root contract.
+pub(crate) fn unkeyed_wallet(wallet_id: Bytes32) -> WalletContract {
+    let params = PedersenParameters::default();
+    WalletContract::new(wallet_id, params.clone(), GlobalRootContract::new(params))
+}
+
+/// Creates a wallet like [`unkeyed_wallet`] that signs with [`key_manager`]'s keys.
+pub(crate) fn wallet(wallet_id: Bytes32) -> WalletContract {
+    unkeyed_wallet(wallet_id).with_key_manager(key_manager())
+}
+
+/// Creates a channel state holding `balances` at `nonce`, without metadata, proof or assets.
+pub(crate) fn channel_state(balances: Vec<u64>, nonce: u64) -> ChannelState {
+    ChannelState {
+        balances,
+        nonce,
+        metadata: Vec::new(),
+        merkle_root: [0u8; 32],
+        proof: None,
+        assets: BTreeMap::new(),
+    }
+}
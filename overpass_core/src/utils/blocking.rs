@@ -0,0 +1,16 @@
+// src/utils/blocking.rs
+
+/// Runs `work` on tokio's blocking pool and waits for it without holding up the runtime.
+///
+/// A panic in `work` is raised again in the caller.
+pub(crate) async fn run_blocking<F, T>(work: F) -> T
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    match tokio::task::spawn_blocking(work).await {
+        Ok(value) => value,
+        Err(error) if error.is_panic() => std::panic::resume_unwind(error.into_panic()),
+        Err(error) => panic!("Blocking task did not complete: {}", error),
+    }
+}
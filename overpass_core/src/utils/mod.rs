@@ -1,3 +1,5 @@
 // ./src/utils/mod.rs
+#[cfg(feature = "tokio")]
+pub(crate) mod blocking;
 pub mod convert;
 pub mod json;
\ No newline at end of file
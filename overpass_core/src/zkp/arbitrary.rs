@@ -0,0 +1,369 @@
+// src/zkp/arbitrary.rs
+
+//! proptest strategies for the protocol's data types, and the invariants every value
+//! they generate must keep, so correctness can be checked over generated inputs rather
+//! than hand-picked ones.
+
+use crate::zkp::channel::ChannelState;
+use crate::zkp::compressed_transaction::{CompressedTransaction, DeltaHistory, ProofRef};
+use crate::zkp::global_root_contract::GlobalRootContract;
+use crate::zkp::helpers::{hash_pair, Bytes32};
+use crate::zkp::pedersen_parameters::PedersenParameters;
+use crate::zkp::state_proof::StateProof;
+use crate::zkp::tree::{MerkleProof, MerkleTree, MerkleTreeError};
+use proptest::prelude::*;
+use proptest::test_runner::TestCaseError;
+use std::collections::BTreeMap;
+
+/// Mixed into a leaf to get the value an update replaces it with, so replacements never
+/// collide with the leaves a strategy drew.
+const UPDATE_TAG: Bytes32 = [0xa5; 32];
+
+impl Arbitrary for StateProof {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        (
+            any::<Bytes32>(),
+            prop::collection::vec(any::<Bytes32>(), 0..4),
+            any::<u64>(),
+        )
+            .prop_map(|(pi, public_inputs, timestamp)| StateProof {
+                pi,
+                public_inputs,
+                timestamp,
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for ProofRef {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        prop_oneof![
+            any::<StateProof>().prop_map(ProofRef::Embedded),
+            any::<Bytes32>().prop_map(ProofRef::Digest),
+        ]
+        .boxed()
+    }
+}
+
+impl Arbitrary for CompressedTransaction {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    /// Transactions in every known format version, with or without a proof.
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        (
+            CompressedTransaction::LEGACY_VERSION..=CompressedTransaction::CURRENT_VERSION,
+            any::<u64>(),
+            any::<[Bytes32; 4]>(),
+            prop::option::of(any::<ProofRef>()),
+        )
+            .prop_map(|(version, timestamp, [old, new, metadata, root], proof)| {
+                CompressedTransaction {
+                    version,
+                    timestamp,
+                    old_commitment: old,
+                    new_commitment: new,
+                    metadata_hash: metadata,
+                    merkle_root: root,
+                    proof,
+                }
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for ChannelState {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    /// States holding native balances only: asset ids are byte arrays, which JSON, and
+    /// so `hash_state`, cannot take as map keys.
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        (
+            prop::collection::vec(any::<u64>(), 1..4),
+            any::<u64>(),
+            prop::collection::vec(any::<u8>(), 0..64),
+            any::<Bytes32>(),
+            prop::option::of(prop::collection::vec(any::<u8>(), 0..64)),
+        )
+            .prop_map(|(balances, nonce, metadata, merkle_root, proof)| ChannelState {
+                balances,
+                nonce,
+                metadata,
+                merkle_root,
+                proof,
+                assets: BTreeMap::new(),
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for MerkleProof {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        prop::collection::vec(any::<Bytes32>(), 0..16)
+            .prop_map(|path| MerkleProof { path })
+            .boxed()
+    }
+}
+
+/// An operation on a `MerkleTree`. Indexes pick a current leaf modulo the leaf count,
+/// so any sequence of operations applies to any tree; on an empty tree only inserts do
+/// anything.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TreeOp {
+    Insert(Bytes32),
+    Update { index: usize, leaf: Bytes32 },
+    Delete { index: usize },
+}
+
+impl Arbitrary for TreeOp {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        prop_oneof![
+            2 => any::<Bytes32>().prop_map(TreeOp::Insert),
+            1 => (any::<usize>(), any::<Bytes32>())
+                .prop_map(|(index, leaf)| TreeOp::Update { index, leaf }),
+            1 => any::<usize>().prop_map(|index| TreeOp::Delete { index }),
+        ]
+        .boxed()
+    }
+}
+
+impl TreeOp {
+    /// Applies the operation to `tree`.
+    pub fn apply(&self, tree: &mut MerkleTree) -> Result<(), MerkleTreeError> {
+        match *self {
+            TreeOp::Insert(leaf) => tree.insert(leaf),
+            _ if tree.leaves.is_empty() => Ok(()),
+            TreeOp::Update { index, leaf } => {
+                let old = tree.leaves[index % tree.leaves.len()];
+                tree.update(old, leaf)
+            }
+            TreeOp::Delete { index } => {
+                let old = tree.leaves[index % tree.leaves.len()];
+                tree.delete(old)
+            }
+        }
+    }
+
+    /// Applies the operation to a plain list of leaves, the model `apply` is held to.
+    /// Updates and deletes act on the first leaf equal to the one picked, as the tree
+    /// looks leaves up by value.
+    pub fn apply_to_model(&self, leaves: &mut Vec<Bytes32>) {
+        let index = match *self {
+            TreeOp::Insert(leaf) => return leaves.push(leaf),
+            TreeOp::Update { index, .. } | TreeOp::Delete { index } => index,
+        };
+        if leaves.is_empty() {
+            return;
+        }
+        let picked = leaves[index % leaves.len()];
+        let pos = leaves.iter().position(|leaf| *leaf == picked).unwrap();
+        match *self {
+            TreeOp::Update { leaf, .. } => leaves[pos] = leaf,
+            _ => {
+                leaves.remove(pos);
+            }
+        }
+    }
+}
+
+/// Distinct leaves, a set of updates to distinct leaves among them, and the same
+/// updates in a shuffled order.
+pub fn independent_updates(
+) -> impl Strategy<Value = (Vec<Bytes32>, Vec<(Bytes32, Bytes32)>, Vec<(Bytes32, Bytes32)>)> {
+    prop::collection::btree_set(any::<Bytes32>(), 1..32)
+        .prop_flat_map(|leaves| {
+            let leaves: Vec<_> = leaves.into_iter().collect();
+            let picks = prop::sample::subsequence(leaves.clone(), 0..=leaves.len());
+            (Just(leaves), picks)
+        })
+        .prop_flat_map(|(leaves, picked)| {
+            let updates: Vec<_> = picked
+                .into_iter()
+                .map(|old| (old, hash_pair(old, UPDATE_TAG)))
+                .collect();
+            (Just(leaves), Just(updates.clone()), Just(updates).prop_shuffle())
+        })
+}
+
+/// Wallets with distinct ids, and the same wallets in a shuffled order.
+pub fn wallet_registrations(
+) -> impl Strategy<Value = (Vec<(Bytes32, Bytes32)>, Vec<(Bytes32, Bytes32)>)> {
+    prop::collection::btree_map(any::<Bytes32>(), any::<Bytes32>(), 1..16).prop_flat_map(
+        |wallets| {
+            let wallets: Vec<_> = wallets.into_iter().collect();
+            (Just(wallets.clone()), Just(wallets).prop_shuffle())
+        },
+    )
+}
+
+fn build_tree(leaves: &[Bytes32]) -> Result<MerkleTree, TestCaseError> {
+    let mut tree = MerkleTree::new();
+    for leaf in leaves {
+        tree.insert(*leaf).map_err(fail)?;
+    }
+    Ok(tree)
+}
+
+fn fail(err: impl ToString) -> TestCaseError {
+    TestCaseError::fail(err.to_string())
+}
+
+/// Checks that a transaction survives its canonical encoding, its encoding with proof,
+/// and delta encoding within a history.
+pub fn check_transaction_round_trip(tx: &CompressedTransaction) -> Result<(), TestCaseError> {
+    let decoded = CompressedTransaction::from_bytes(&tx.to_bytes()).map_err(fail)?;
+    prop_assert_eq!(&decoded, &CompressedTransaction { proof: None, ..tx.clone() });
+
+    let proven = CompressedTransaction::decode_proven(&CompressedTransaction::encode_proven(
+        std::slice::from_ref(tx),
+    ))
+    .map_err(fail)?;
+    prop_assert_eq!(&proven, std::slice::from_ref(tx));
+    Ok(())
+}
+
+/// Checks that a history survives delta encoding and its byte form, whatever runs its
+/// timestamps and versions split it into.
+pub fn check_history_round_trip(history: &[CompressedTransaction]) -> Result<(), TestCaseError> {
+    let encoded = DeltaHistory::encode(history);
+    let bytes = encoded.to_bytes();
+    let restored = DeltaHistory::from_bytes(&bytes).map_err(fail)?;
+    prop_assert_eq!(&restored, &encoded);
+    prop_assert_eq!(restored.decode().map_err(fail)?, history.to_vec());
+    Ok(())
+}
+
+/// Checks that a channel state survives JSON and keeps its hash.
+pub fn check_channel_state_round_trip(state: &ChannelState) -> Result<(), TestCaseError> {
+    let json = serde_json::to_vec(state).map_err(fail)?;
+    let decoded: ChannelState = serde_json::from_slice(&json).map_err(fail)?;
+    prop_assert_eq!(
+        serde_json::to_value(&decoded).map_err(fail)?,
+        serde_json::to_value(state).map_err(fail)?
+    );
+    prop_assert_eq!(decoded.hash_state().map_err(fail)?, state.hash_state().map_err(fail)?);
+    Ok(())
+}
+
+/// Checks that a tree's leaves follow the model through `ops`, and that, as long as no
+/// leaf was deleted, its root is the one a tree built from its final leaves has.
+pub fn check_tree_ops(ops: &[TreeOp]) -> Result<(), TestCaseError> {
+    let mut tree = MerkleTree::new();
+    let mut model = Vec::new();
+    for op in ops {
+        op.apply(&mut tree).map_err(fail)?;
+        op.apply_to_model(&mut model);
+        prop_assert_eq!(&tree.leaves, &model);
+    }
+    if !ops.iter().any(|op| matches!(op, TreeOp::Delete { .. })) {
+        prop_assert_eq!(tree.root, build_tree(&model)?.root);
+    }
+    Ok(())
+}
+
+/// Checks that updates to distinct leaves reach the same root in either order, and that
+/// it is the root of a tree built from the updated leaves.
+pub fn check_updates_commute(
+    leaves: &[Bytes32],
+    updates: &[(Bytes32, Bytes32)],
+    reordered: &[(Bytes32, Bytes32)],
+) -> Result<(), TestCaseError> {
+    let mut roots = Vec::new();
+    for order in [updates, reordered] {
+        let mut tree = build_tree(leaves)?;
+        for (old, new) in order {
+            tree.update(*old, *new).map_err(fail)?;
+        }
+        roots.push(tree.root);
+    }
+    let updated: Vec<_> = leaves
+        .iter()
+        .map(|leaf| {
+            updates
+                .iter()
+                .find(|(old, _)| old == leaf)
+                .map_or(*leaf, |(_, new)| *new)
+        })
+        .collect();
+    prop_assert_eq!(roots[0], roots[1]);
+    prop_assert_eq!(roots[0], build_tree(&updated)?.root);
+    Ok(())
+}
+
+/// Checks that the global root does not depend on the order wallets register in.
+pub fn check_registration_order(
+    wallets: &[(Bytes32, Bytes32)],
+    reordered: &[(Bytes32, Bytes32)],
+) -> Result<(), TestCaseError> {
+    let params = PedersenParameters::default();
+    let mut roots = Vec::new();
+    for order in [wallets, reordered] {
+        let mut contract = GlobalRootContract::new(params.clone());
+        for (wallet_id, root) in order {
+            contract.register_wallet(*wallet_id, *root).map_err(fail)?;
+        }
+        roots.push(contract.get_global_merkle_root());
+    }
+    prop_assert_eq!(roots[0], roots[1]);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn test_transactions_round_trip(tx in any::<CompressedTransaction>()) {
+            check_transaction_round_trip(&tx)?;
+        }
+
+        #[test]
+        fn test_histories_round_trip(
+            history in prop::collection::vec(any::<CompressedTransaction>(), 0..12),
+        ) {
+            check_history_round_trip(&history)?;
+        }
+
+        #[test]
+        fn test_channel_states_round_trip(state in any::<ChannelState>()) {
+            check_channel_state_round_trip(&state)?;
+        }
+
+        #[test]
+        fn test_tree_follows_model(ops in prop::collection::vec(any::<TreeOp>(), 0..48)) {
+            check_tree_ops(&ops)?;
+        }
+
+        #[test]
+        fn test_independent_updates_commute(
+            (leaves, updates, reordered) in independent_updates(),
+        ) {
+            check_updates_commute(&leaves, &updates, &reordered)?;
+        }
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(32))]
+
+        #[test]
+        fn test_global_root_ignores_registration_order(
+            (wallets, reordered) in wallet_registrations(),
+        ) {
+            check_registration_order(&wallets, &reordered)?;
+        }
+    }
+}
@@ -1,15 +1,39 @@
 // src/zkp/bitcoin_ephemeral_state.rs
 
 use std::str::FromStr;
-use anyhow::{anyhow, Context, Result};
 use bitcoincore_rpc::{Auth, Client, RpcApi};
 use bitcoin::{
-    blockdata::transaction::{Transaction, TxIn, TxOut}, consensus::encode, Address, Network, OutPoint, ScriptBuf
+    blockdata::transaction::{Transaction, TxIn, TxOut}, consensus::encode, Address, BlockHash, Network, OutPoint, ScriptBuf
 };
 use bitcoin::PublicKey;
 use bitcoin::blockdata::script::Builder;
 use bitcoin::secp256k1::{Secp256k1, SecretKey, All};
 use std::collections::HashMap;
+use crate::bitcoin::fees::{fee_for_vsize, FeeEstimationError, FeeEstimator};
+use crate::bitcoin::networks::ChainNetwork;
+use thiserror::Error;
+
+/// Virtual size of a signed one-input P2PKH transaction with an OP_RETURN and a change output.
+const OP_RETURN_TX_VSIZE: u64 = 204;
+
+/// Errors from the test client and the node behind it.
+#[derive(Error, Debug)]
+pub enum EphemeralStateError {
+    #[error("Bitcoin Core RPC error: {0}")]
+    Rpc(#[from] bitcoincore_rpc::Error),
+    #[error("Node is not on {chain} (genesis {genesis})")]
+    WrongChain { chain: ChainNetwork, genesis: BlockHash },
+    #[error("Address error: {0}")]
+    Address(#[from] bitcoin::address::Error),
+    #[error("Invalid transaction hex: {0}")]
+    Hex(#[from] hex::FromHexError),
+    #[error("Invalid transaction: {0}")]
+    Decode(#[from] encode::Error),
+    #[error("Fee estimation failed: {0}")]
+    Fee(#[from] FeeEstimationError),
+    #[error("No spendable UTXO holds {0} sats")]
+    NoSpendableUtxo(u64),
+}
 
 /// Represents a simple Bitcoin client for testing purposes.
 pub struct BitcoinClient {
@@ -17,50 +41,70 @@ pub struct BitcoinClient {
     utxos: HashMap<String, (TxOut, bitcoin::OutPoint)>, // Keyed by txid
     secp: Secp256k1<All>,
     network: Network,
+    chain: ChainNetwork,
 }
 
 impl BitcoinClient {
     /// Creates a new Bitcoin client.
-    pub fn new(rpc_url: &str, rpc_user: &str, rpc_password: &str, network: Network) -> Result<Self> {
+    pub fn new(rpc_url: &str, rpc_user: &str, rpc_password: &str, network: Network) -> Result<Self, EphemeralStateError> {
+        Self::with_chain(rpc_url, rpc_user, rpc_password, ChainNetwork::from(network))
+    }
+
+    /// Creates a client for a node on the default local RPC port of `chain`.
+    pub fn for_network(chain: ChainNetwork, rpc_user: &str, rpc_password: &str) -> Result<Self, EphemeralStateError> {
+        Self::with_chain(&chain.default_rpc_url(), rpc_user, rpc_password, chain)
+    }
+
+    /// Creates a new Bitcoin client for the given chain, including testnet4.
+    pub fn with_chain(rpc_url: &str, rpc_user: &str, rpc_password: &str, chain: ChainNetwork) -> Result<Self, EphemeralStateError> {
         // Initialize the RPC client with credentials.
         let auth = Auth::UserPass(rpc_user.to_string(), rpc_password.to_string());
-        let rpc = Client::new(rpc_url, auth)
-            .context("Failed to create RPC client. Check RPC URL and credentials.")?;
+        let rpc = Client::new(rpc_url, auth)?;
         
         Ok(Self {
             rpc,
             utxos: HashMap::new(),
             secp: Secp256k1::new(),
-            network,
+            network: chain.network(),
+            chain,
         })
     }
 
+    /// Gets the chain this client is configured for.
+    pub fn chain(&self) -> ChainNetwork {
+        self.chain
+    }
+
+    /// Checks the node's genesis block against the configured chain.
+    pub fn verify_network(&self) -> Result<(), EphemeralStateError> {
+        let genesis = self.rpc.get_block_hash(0)?;
+        if genesis != self.chain.genesis_hash() {
+            return Err(EphemeralStateError::WrongChain { chain: self.chain, genesis });
+        }
+        Ok(())
+    }
+
     /// Retrieves a new Bitcoin address.
-    pub fn get_new_address(&self) -> Result<Address> {
-        let address = self.rpc.get_new_address(None, None)
-            .context("Failed to get new address")?;
-        Ok(address.assume_checked())
+    pub fn get_new_address(&self) -> Result<Address, EphemeralStateError> {
+        let address = self.rpc.get_new_address(None, None)?;
+        Ok(address.require_network(self.network)?)
     }
 
     /// Generates a specified number of blocks to the given address (Regtest only).
-    pub fn generate_blocks(&self, count: u32, address: &str) -> Result<()> {
-        let addr = Address::from_str(address)
-            .context("Invalid Bitcoin address")?
-            .assume_checked();
-        self.rpc.generate_to_address(count.into(), &addr)
-            .context("Failed to generate blocks")?;
+    pub fn generate_blocks(&self, count: u32, address: &str) -> Result<(), EphemeralStateError> {
+        let addr = Address::from_str(address)?.require_network(self.network)?;
+        self.rpc.generate_to_address(count.into(), &addr)?;
         Ok(())
     }
 
     /// Retrieves the balance of the wallet.
-    pub fn get_balance(&self) -> Result<u64> {
-        let balance = self.rpc.get_balance(None, None)
-            .context("Failed to get balance")?;
+    pub fn get_balance(&self) -> Result<u64, EphemeralStateError> {
+        let balance = self.rpc.get_balance(None, None)?;
         Ok(balance.to_sat())
     }
 
     /// Retrieves a spendable UTXO with the specified amount.
-    pub fn get_spendable_utxo(&mut self, amount: u64) -> Result<(bitcoin::OutPoint, TxOut)> {
+    pub fn get_spendable_utxo(&mut self, amount: u64) -> Result<(bitcoin::OutPoint, TxOut), EphemeralStateError> {
         // Refresh UTXO set
         self.refresh_utxos()?;
 
@@ -71,14 +115,13 @@ impl BitcoinClient {
             }
         }
 
-        Err(anyhow!("No spendable UTXO found with the specified amount"))
+        Err(EphemeralStateError::NoSpendableUtxo(amount))
     }
 
     /// Refreshes the UTXO set by fetching from the RPC.
-    fn refresh_utxos(&mut self) -> Result<()> {
+    fn refresh_utxos(&mut self) -> Result<(), EphemeralStateError> {
         self.utxos.clear();
-        let utxos = self.rpc.list_unspent(None, None, None, None, None)
-            .context("Failed to list unspent transactions")?;
+        let utxos = self.rpc.list_unspent(None, None, None, None, None)?;
         for utxo in utxos {
             let txid = utxo.txid.to_string();
             let tx_out = TxOut {
@@ -95,23 +138,19 @@ impl BitcoinClient {
     }
 
     /// Signs a raw transaction hex.
-    pub fn sign_raw_transaction(&self, raw_tx_hex: &str) -> Result<String> {
-        let tx_bytes = hex::decode(raw_tx_hex)
-            .context("Failed to decode raw transaction hex")?;
-        let tx: Transaction = encode::deserialize(&tx_bytes)
-            .context("Failed to deserialize transaction")?;
+    pub fn sign_raw_transaction(&self, raw_tx_hex: &str) -> Result<String, EphemeralStateError> {
+        let tx_bytes = hex::decode(raw_tx_hex)?;
+        let tx: Transaction = encode::deserialize(&tx_bytes)?;
         
-        let signed_tx = self.rpc.sign_raw_transaction_with_wallet(&tx, None, None)
-            .context("Failed to sign transaction")?;
+        let signed_tx = self.rpc.sign_raw_transaction_with_wallet(&tx, None, None)?;
 
         let signed_tx_hex = hex::encode(signed_tx.hex);
         Ok(signed_tx_hex)
     }
 
     /// Sends a raw transaction given its hex representation.
-    pub fn send_raw_transaction_hex(&self, raw_tx_hex: &str) -> Result<String> {
-        let tx = self.rpc.send_raw_transaction(raw_tx_hex)
-            .context("Failed to send raw transaction")?;
+    pub fn send_raw_transaction_hex(&self, raw_tx_hex: &str) -> Result<String, EphemeralStateError> {
+        let tx = self.rpc.send_raw_transaction(raw_tx_hex)?;
         Ok(tx.to_string())
     }
 
@@ -133,14 +172,22 @@ impl BitcoinClient {
 }
 
 /// Builds an OP_RETURN transaction embedding the provided data.
-pub fn build_op_return_transaction(client: &mut BitcoinClient, _data: &[u8; 32], private_key: &SecretKey) -> Result<String> {
+pub fn build_op_return_transaction(
+    client: &mut BitcoinClient,
+    _data: &[u8; 32],
+    private_key: &SecretKey,
+    fee_estimator: &dyn FeeEstimator,
+    target_blocks: u16,
+) -> Result<String, EphemeralStateError> {
     // Generate key pair
     let public_key = client.generate_keypair(private_key);
     let script_pubkey = client.create_p2pkh_script(&public_key);
 
     // Amount to send to OP_RETURN
     let op_return_amount = 0;
-    let fee = 1_000;
+    let fee = fee_estimator
+        .estimate_fee(OP_RETURN_TX_VSIZE, target_blocks)?
+        .max(fee_for_vsize(client.chain().fee_floor(), OP_RETURN_TX_VSIZE)?);
     let total_amount = op_return_amount + fee;
 
     // Get a spendable UTXO
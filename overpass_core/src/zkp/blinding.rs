@@ -0,0 +1,109 @@
+// src/zkp/blinding.rs
+
+use curve25519_dalek::scalar::Scalar;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use std::fmt;
+use std::ops::{Add, Neg, Sub};
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+use crate::zkp::helpers::Bytes32;
+
+/// A Pedersen blinding factor, wiped from memory when dropped.
+///
+/// It has no `Display` and its `Debug` output is redacted so it cannot end up in logs.
+#[derive(Clone, PartialEq, Eq)]
+pub struct BlindingFactor(Scalar);
+
+impl BlindingFactor {
+    /// Draws a uniformly random blinding factor.
+    pub fn random() -> Self {
+        let mut wide = Zeroizing::new([0u8; 64]);
+        OsRng.fill_bytes(wide.as_mut());
+        Self::from_wide_bytes(&wide)
+    }
+
+    pub fn from_scalar(scalar: Scalar) -> Self {
+        Self(scalar)
+    }
+
+    /// Reduces 32 bytes into a blinding factor, as `pedersen_commit` does.
+    pub fn from_bytes(bytes: &Bytes32) -> Self {
+        Self(Scalar::from_bytes_mod_order(*bytes))
+    }
+
+    /// Reduces 64 uniformly random bytes into a blinding factor.
+    pub fn from_wide_bytes(bytes: &[u8; 64]) -> Self {
+        Self(Scalar::from_bytes_mod_order_wide(bytes))
+    }
+
+    /// Gets the secret scalar, for use in group operations.
+    pub fn scalar(&self) -> &Scalar {
+        &self.0
+    }
+
+    /// Gets the canonical encoding in a buffer that is wiped when dropped.
+    pub fn to_bytes(&self) -> Zeroizing<Bytes32> {
+        Zeroizing::new(self.0.to_bytes())
+    }
+}
+
+impl fmt::Debug for BlindingFactor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("BlindingFactor(<redacted>)")
+    }
+}
+
+impl Zeroize for BlindingFactor {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl Drop for BlindingFactor {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for BlindingFactor {}
+
+impl Add for &BlindingFactor {
+    type Output = BlindingFactor;
+
+    fn add(self, other: Self) -> BlindingFactor {
+        BlindingFactor(self.0 + other.0)
+    }
+}
+
+impl Sub for &BlindingFactor {
+    type Output = BlindingFactor;
+
+    fn sub(self, other: Self) -> BlindingFactor {
+        BlindingFactor(self.0 - other.0)
+    }
+}
+
+impl Neg for &BlindingFactor {
+    type Output = BlindingFactor;
+
+    fn neg(self) -> BlindingFactor {
+        BlindingFactor(-self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_debug_is_redacted_and_zeroize_clears() {
+        let mut blinding = BlindingFactor::from_scalar(Scalar::from(1234u64));
+        assert_eq!(format!("{:?}", blinding), "BlindingFactor(<redacted>)");
+        assert_eq!(*blinding.to_bytes(), Scalar::from(1234u64).to_bytes());
+
+        blinding.zeroize();
+        assert_eq!(blinding.scalar(), &Scalar::ZERO);
+        assert_ne!(BlindingFactor::random(), BlindingFactor::random());
+    }
+}
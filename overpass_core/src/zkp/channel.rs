@@ -2,30 +2,45 @@
 
 use plonky2_field::types::PrimeField64;
 use plonky2_field::types::Field;
-use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
 use crate::zkp::tree::{MerkleTree, MerkleTreeError};
 use plonky2_field::goldilocks_field::GoldilocksField;
 use plonky2::plonk::config::Hasher;
 use plonky2::hash::poseidon::PoseidonHash;
+use std::collections::BTreeMap;
+use crate::zkp::helpers::{hash_pair, Bytes32};
+
+/// Identifies an asset issued on top of the channel's native bitcoin balances.
+pub type AssetId = Bytes32;
+
+/// Errors from hashing a channel state.
+#[derive(Error, Debug)]
+pub enum ChannelError {
+    #[error("Failed to serialize channel state: {0}")]
+    Serialization(#[from] serde_json::Error),
+}
 
 /// Represents the state of a channel.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ChannelState {
+    /// Native bitcoin balance of each party.
     pub balances: Vec<u64>,
     pub nonce: u64,
     pub metadata: Vec<u8>,
     pub merkle_root: [u8; 32],
     pub proof: Option<Vec<u8>>,
+    /// Per-party balances of each other asset the channel holds.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub assets: BTreeMap<AssetId, Vec<u64>>,
 }
 
 
 impl ChannelState {
     /// Converts the ChannelState into a 32-byte hash using PoseidonHash.
-    pub fn hash_state(&self) -> Result<[u8; 32]> {
+    pub fn hash_state(&self) -> Result<[u8; 32], ChannelError> {
         // Serialize the entire state using serde_json for consistency
-        let serialized = serde_json::to_vec(self)
-            .context("Failed to serialize channel state")?;
+        let serialized = serde_json::to_vec(self)?;
 
         // Convert serialized bytes to field elements
         let mut inputs = Vec::new();
@@ -71,6 +86,52 @@ impl ChannelState {
         true
     }
 
+    /// Gets the per-party balances of `asset`, or `None` when the channel does not hold it.
+    pub fn asset_balances(&self, asset: &AssetId) -> Option<&[u64]> {
+        self.assets.get(asset).map(Vec::as_slice)
+    }
+
+    /// Sums the native balances and each asset's balances over all parties, failing on
+    /// overflow.
+    pub fn totals(&self) -> Option<(u64, BTreeMap<AssetId, u64>)> {
+        let sum = |balances: &[u64]| {
+            balances
+                .iter()
+                .try_fold(0u64, |total, balance| total.checked_add(*balance))
+        };
+        let mut assets = BTreeMap::new();
+        for (asset, balances) in &self.assets {
+            assets.insert(*asset, sum(balances)?);
+        }
+        Some((sum(&self.balances)?, assets))
+    }
+
+    /// Checks that the transition from `old_state` neither creates nor destroys any asset:
+    /// every party keeps a balance entry and each asset's total is unchanged. An asset
+    /// missing from one side counts as a total of zero.
+    pub fn conserves(&self, old_state: &ChannelState) -> bool {
+        let (Some((native, assets)), Some((old_native, old_assets))) =
+            (self.totals(), old_state.totals())
+        else {
+            return false;
+        };
+        let parties_kept = self.balances.len() == old_state.balances.len()
+            && self.assets.iter().all(|(asset, balances)| {
+                old_state
+                    .asset_balances(asset)
+                    .is_none_or(|old| old.len() == balances.len())
+            });
+        let total = |totals: &BTreeMap<AssetId, u64>, asset| {
+            totals.get(asset).copied().unwrap_or(0)
+        };
+        parties_kept
+            && native == old_native
+            && assets
+                .keys()
+                .chain(old_assets.keys())
+                .all(|asset| total(&assets, asset) == total(&old_assets, asset))
+    }
+
     /// Updates the Sparse Merkle Tree with the new state.
     pub fn update_in_tree(
         &self,
@@ -96,9 +157,11 @@ impl ChannelState {
         Ok((new_leaf, new_root))
     }
 
-    /// Calculates hash of the channel state for consistent referencing.
-    pub fn hash(&self) -> Result<[u8; 32]> {
-        self.hash_state()
+    /// Calculates hash of the channel state for consistent referencing: the digest of its
+    /// commitment paired with [`Self::hash_state`], so the commitment can be shown to be a
+    /// wallet root leaf without revealing the rest of the state.
+    pub fn hash(&self) -> Result<[u8; 32], ChannelError> {
+        Ok(hash_pair(self.merkle_root, self.hash_state()?))
     }
 }
 
@@ -121,6 +184,7 @@ mod tests {
             metadata: vec![1, 2, 3],
             merkle_root: [0u8; 32],
             proof: None,
+            assets: BTreeMap::new(),
         };
         let old_key = [1u8; 32];
         let old_leaf = old_state.hash_state().unwrap();
@@ -133,6 +197,7 @@ mod tests {
             metadata: vec![1, 2, 3, 4],
             merkle_root: [0u8; 32],
             proof: None,
+            assets: BTreeMap::new(),
         };
         let new_key = [1u8; 32];
 
@@ -145,4 +210,35 @@ mod tests {
 
         Ok(())
     }
-}
\ No newline at end of file
+
+    fn state(balances: Vec<u64>, assets: &[(u8, Vec<u64>)]) -> ChannelState {
+        ChannelState {
+            balances,
+            nonce: 0,
+            metadata: Vec::new(),
+            merkle_root: [0u8; 32],
+            proof: None,
+            assets: assets
+                .iter()
+                .map(|(asset, balances)| ([*asset; 32], balances.clone()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_transitions_conserve_each_asset() {
+        let old = state(vec![100, 50], &[(1, vec![10, 0]), (2, vec![5, 5])]);
+        assert!(state(vec![90, 60], &[(1, vec![3, 7]), (2, vec![0, 10])]).conserves(&old));
+        assert!(!state(vec![90, 60], &[(1, vec![3, 8]), (2, vec![0, 10])]).conserves(&old));
+        assert!(!state(vec![90, 60], &[(1, vec![10, 0])]).conserves(&old));
+        assert!(!state(vec![150], &[(1, vec![10, 0]), (2, vec![5, 5])]).conserves(&old));
+        assert!(state(vec![100, 50], &[(1, vec![10, 0]), (2, vec![5, 5]), (3, vec![0, 0])])
+            .conserves(&old));
+        assert!(!state(vec![u64::MAX, 1], &[]).conserves(&state(vec![0, 0], &[])));
+        assert_eq!(old.asset_balances(&[2u8; 32]), Some(&[5u64, 5][..]));
+
+        // States without assets serialize, and so hash, as before assets existed.
+        let json = serde_json::to_string(&state(vec![1], &[])).unwrap();
+        assert!(!json.contains("assets"));
+    }
+}
@@ -0,0 +1,91 @@
+// src/zkp/channel_backup.rs
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::zkp::channel::ChannelState;
+use crate::zkp::compressed_transaction::CompressedTransaction;
+use crate::zkp::global_root_contract::WalletInclusionProof;
+use crate::zkp::helpers::Bytes32;
+use crate::zkp::wallet_contract::ChannelInclusionProof;
+
+/// Seed-derived channel sequences scanned past the last one found during recovery.
+pub const RECOVERY_GAP_LIMIT: u64 = 20;
+
+/// Why a channel backup was not restored.
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BackupError {
+    #[error("Backup belongs to another wallet")]
+    WrongWallet,
+    #[error("Backed up state does not match its proof")]
+    StateMismatch,
+    #[error("State is not a leaf of the backed up wallet root")]
+    NotInWalletRoot,
+    #[error("Wallet root is not part of the backed up global root")]
+    NotInGlobalRoot,
+    #[error("Global root {} is not anchored", hex::encode(.0))]
+    NotAnchored(Bytes32),
+    #[error("A later backup of the channel was restored")]
+    Superseded,
+}
+
+/// A static backup of one channel: its latest state, proven under a global root that
+/// was anchored on Bitcoin, plus its stored transaction history.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ChannelBackup {
+    pub counterparty: Bytes32,
+    pub state: ChannelState,
+    pub channel_proof: ChannelInclusionProof,
+    pub wallet_proof: WalletInclusionProof,
+    pub global_root: Bytes32,
+    pub history: Vec<CompressedTransaction>,
+}
+
+impl ChannelBackup {
+    pub fn channel_id(&self) -> &Bytes32 {
+        &self.channel_proof.channel_id
+    }
+
+    /// Checks the chain of proofs from the backed up state to one of `anchored_roots`.
+    #[tracing::instrument(
+        name = "verify",
+        skip_all,
+        fields(proof = "backup", channel_id = %hex::encode(self.channel_id())),
+        err
+    )]
+    pub fn verify(
+        &self,
+        wallet_id: &Bytes32,
+        anchored_roots: &[Bytes32],
+    ) -> Result<(), BackupError> {
+        if self.wallet_proof.wallet_id != *wallet_id {
+            return Err(BackupError::WrongWallet);
+        }
+        let matches = self
+            .state
+            .hash()
+            .is_ok_and(|hash| hash == self.channel_proof.channel_hash);
+        if !matches {
+            return Err(BackupError::StateMismatch);
+        }
+        if !self.channel_proof.verify(&self.wallet_proof.wallet_root) {
+            return Err(BackupError::NotInWalletRoot);
+        }
+        if !self.wallet_proof.verify(&self.global_root) {
+            return Err(BackupError::NotInGlobalRoot);
+        }
+        if !anchored_roots.contains(&self.global_root) {
+            return Err(BackupError::NotAnchored(self.global_root));
+        }
+        Ok(())
+    }
+}
+
+/// What a recovery restored from the backups it was given.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RecoveryReport {
+    pub restored: Vec<Bytes32>,
+    pub rejected: Vec<(Bytes32, BackupError)>,
+    /// Sequence the next seed-derived channel will be opened under.
+    pub next_channel: u64,
+}
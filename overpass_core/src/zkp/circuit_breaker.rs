@@ -0,0 +1,59 @@
+// src/zkp/circuit_breaker.rs
+
+use bitcoin::secp256k1::{schnorr, Message, XOnlyPublicKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::zkp::helpers::Bytes32;
+use crate::zkp::operator_keys::message_from;
+
+/// An internal inconsistency in the global root contract.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InvariantViolation {
+    /// The global root is not what the wallet roots hash to.
+    GlobalRootMismatch { stored: Bytes32, computed: Bytes32 },
+    /// The newest root history record is not the current global root.
+    HistoryDiverged,
+    /// A sealed epoch does not follow the one before it, or the open epoch does not start
+    /// where the last sealed one ended.
+    BrokenEpochChain(u64),
+    /// An open submission has no bond locked behind it.
+    UnbackedSubmission(u64),
+}
+
+/// Why the contract stopped accepting new submissions.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HaltReason {
+    Violations(Vec<InvariantViolation>),
+    /// Halted by hand, e.g. on an off-chain alert.
+    Manual(String),
+}
+
+/// A halt in force, with the number of the root history record it froze.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Halt {
+    pub reason: HaltReason,
+    pub sequence: u64,
+}
+
+/// Operator signatures lifting a halt, made at `height`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResumeAuthorization {
+    pub height: u32,
+    pub signatures: Vec<(XOnlyPublicKey, schnorr::Signature)>,
+}
+
+impl ResumeAuthorization {
+    /// Gets the message operators sign to resume from a halt with the contract at `root`.
+    ///
+    /// Binding the root and the halted sequence keeps an authorization from being
+    /// replayed against a later halt or a state the operators did not inspect.
+    pub fn message(halt: &Halt, root: &Bytes32, height: u32) -> Message {
+        let mut hasher = Sha256::new();
+        hasher.update(b"overpass/operator/resume/v1");
+        hasher.update(halt.sequence.to_le_bytes());
+        hasher.update(root);
+        hasher.update(height.to_le_bytes());
+        message_from(hasher)
+    }
+}
@@ -1,6 +1,11 @@
 // src/zkp/compressed_transaction.rs (continued)
 
-use serde::{Serialize, Deserialize};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::zkp::fraud_proof::proof_binds;
+use crate::zkp::helpers::compute_merkle_root;
+use crate::zkp::state_proof::StateProof;
 
 /// Type alias for bytes32.
 pub type Bytes32 = [u8; 32];
@@ -8,6 +13,10 @@ pub type Bytes32 = [u8; 32];
 /// Efficient storage format for historical transactions.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct CompressedTransaction {
+    /// Format version the record was written in; records from before versioning are
+    /// the first version.
+    #[serde(default = "legacy_version")]
+    pub version: u8,
     /// Transaction timestamp.
     pub timestamp: u64,
     /// Previous balance commitment.
@@ -18,4 +27,661 @@ pub struct CompressedTransaction {
     pub metadata_hash: Bytes32,
     /// Merkle root after this transaction.
     pub merkle_root: Bytes32,
-}
\ No newline at end of file
+    /// Proof of the transition, if storage keeps one with the record.
+    #[serde(default)]
+    pub proof: Option<ProofRef>,
+}
+
+/// A transition's proof, carried in full or referenced by its digest.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum ProofRef {
+    Embedded(StateProof),
+    Digest(Bytes32),
+}
+
+const PROOF_DOMAIN: &[u8] = b"overpass/state-proof/v1";
+const PROOF_NONE: u8 = 0;
+const PROOF_DIGEST: u8 = 1;
+const PROOF_EMBEDDED: u8 = 2;
+
+impl ProofRef {
+    /// Hashes a proof's fields, as a reference to it.
+    pub fn digest_of(proof: &StateProof) -> Bytes32 {
+        let mut hasher = Sha256::new()
+            .chain_update(PROOF_DOMAIN)
+            .chain_update(proof.pi)
+            .chain_update(proof.timestamp.to_le_bytes())
+            .chain_update((proof.public_inputs.len() as u64).to_le_bytes());
+        for input in &proof.public_inputs {
+            hasher.update(input);
+        }
+        hasher.finalize().into()
+    }
+
+    pub fn digest(&self) -> Bytes32 {
+        match self {
+            Self::Embedded(proof) => Self::digest_of(proof),
+            Self::Digest(digest) => *digest,
+        }
+    }
+}
+
+fn legacy_version() -> u8 {
+    CompressedTransaction::LEGACY_VERSION
+}
+
+impl CompressedTransaction {
+    /// First format: the old commitment of an update is the state hash it replaced.
+    pub const LEGACY_VERSION: u8 = 1;
+    /// Current format: the old commitment of an update is the balance commitment it
+    /// replaced, so updates chain on their commitments.
+    pub const CURRENT_VERSION: u8 = 2;
+    /// Length of the canonical encoding.
+    pub const ENCODED_LEN: usize = 1 + 8 + 4 * 32;
+
+    /// Encodes the transaction canonically: the format version, the timestamp as
+    /// little-endian `u64`, then the old commitment, new commitment, metadata hash and
+    /// Merkle root as raw bytes.
+    ///
+    /// Hashes over transactions are taken over this encoding, so they do not depend on
+    /// how any serde format lays the struct out. The proof is left out: an embedded one
+    /// is checked against the commitments, a referenced one against its digest.
+    pub fn to_bytes(&self) -> [u8; Self::ENCODED_LEN] {
+        let mut out = [0u8; Self::ENCODED_LEN];
+        out[0] = self.version;
+        out[1..9].copy_from_slice(&self.timestamp.to_le_bytes());
+        for (chunk, field) in out[9..].chunks_exact_mut(32).zip([
+            &self.old_commitment,
+            &self.new_commitment,
+            &self.metadata_hash,
+            &self.merkle_root,
+        ]) {
+            chunk.copy_from_slice(field);
+        }
+        out
+    }
+
+    /// Decodes `to_bytes` output written in any known version. The fields are laid out
+    /// alike in every version; only what the old commitment means differs.
+    pub fn from_bytes(bytes: &[u8; Self::ENCODED_LEN]) -> Result<Self, DeltaError> {
+        let version = bytes[0];
+        if !(Self::LEGACY_VERSION..=Self::CURRENT_VERSION).contains(&version) {
+            return Err(DeltaError::UnsupportedVersion(version));
+        }
+        let field = |index: usize| -> Bytes32 {
+            bytes[9 + index * 32..9 + (index + 1) * 32]
+                .try_into()
+                .unwrap()
+        };
+        Ok(Self {
+            version,
+            timestamp: u64::from_le_bytes(bytes[1..9].try_into().unwrap()),
+            old_commitment: field(0),
+            new_commitment: field(1),
+            metadata_hash: field(2),
+            merkle_root: field(3),
+            proof: None,
+        })
+    }
+
+    /// Checks the transaction's proof is over its old and new commitments, taking a
+    /// referenced proof from `referenced`. A transaction without a proof fails.
+    pub fn verify_proof(&self, referenced: Option<&StateProof>) -> bool {
+        let proof = match &self.proof {
+            Some(ProofRef::Embedded(proof)) => proof,
+            Some(ProofRef::Digest(digest)) => match referenced {
+                Some(proof) if ProofRef::digest_of(proof) == *digest => proof,
+                _ => return false,
+            },
+            None => return false,
+        };
+        proof_binds(proof, &self.old_commitment, &self.new_commitment)
+    }
+
+    /// Concatenates the canonical encodings of `txs`.
+    pub fn encode_all(txs: &[Self]) -> Vec<u8> {
+        txs.iter().flat_map(|tx| tx.to_bytes()).collect()
+    }
+
+    /// Concatenates the canonical encodings of `txs`, each followed by its proof, for
+    /// storage that keeps proofs.
+    pub fn encode_proven(txs: &[Self]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(txs.len() * (Self::ENCODED_LEN + 1));
+        for tx in txs {
+            put_proven(&mut out, tx);
+        }
+        out
+    }
+
+    /// Decodes `encode_proven` output.
+    pub fn decode_proven(bytes: &[u8]) -> Result<Vec<Self>, DeltaError> {
+        let mut cursor = Cursor(bytes);
+        let mut txs = Vec::new();
+        while !cursor.0.is_empty() {
+            txs.push(cursor.proven()?);
+        }
+        Ok(txs)
+    }
+
+    /// Decodes `encode_all` output.
+    pub fn decode_all(bytes: &[u8]) -> Result<Vec<Self>, DeltaError> {
+        if bytes.len() % Self::ENCODED_LEN != 0 {
+            return Err(DeltaError::Truncated);
+        }
+        bytes
+            .chunks_exact(Self::ENCODED_LEN)
+            .map(|chunk| Self::from_bytes(chunk.try_into().unwrap()))
+            .collect()
+    }
+}
+
+/// Brings a channel's history, oldest first, up to the current format.
+///
+/// A first-version update started from the state hash it replaced; it now starts from
+/// the commitment of the update before it. A compression checkpoint, which shares its
+/// new commitment and root with the record after it, starts from where its run did.
+/// The first record keeps its old commitment, as nothing before it is known.
+pub fn upgrade_history(mut history: Vec<CompressedTransaction>) -> Vec<CompressedTransaction> {
+    let mut head: Option<Bytes32> = None;
+    let mut run_start: Option<Bytes32> = None;
+    let mut closes_run = false;
+    for index in 0..history.len() {
+        let checkpoint = history.get(index + 1).is_some_and(|next| {
+            next.new_commitment == history[index].new_commitment
+                && next.merkle_root == history[index].merkle_root
+        });
+        let tx = &mut history[index];
+        if tx.version < CompressedTransaction::CURRENT_VERSION {
+            let old = if checkpoint { run_start } else { head };
+            tx.old_commitment = old.unwrap_or(tx.old_commitment);
+            tx.version = CompressedTransaction::CURRENT_VERSION;
+        }
+        if checkpoint {
+            run_start = None;
+            closes_run = true;
+        } else {
+            if !std::mem::take(&mut closes_run) {
+                run_start.get_or_insert(tx.old_commitment);
+            }
+            head = Some(tx.new_commitment);
+        }
+    }
+    history
+}
+
+/// Errors decoding encoded transactions or a delta-encoded history.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum DeltaError {
+    #[error("Delta encoding is truncated")]
+    Truncated,
+    #[error("Delta encoding has unknown flags {0:#04x}")]
+    InvalidFlags(u8),
+    #[error("Run {0} does not match its chain hash")]
+    ChainMismatch(usize),
+    #[error("Unsupported transaction format version {0}")]
+    UnsupportedVersion(u8),
+    #[error("Unknown proof tag {0}")]
+    InvalidProof(u8),
+}
+
+/// Distance-to-previous timestamp and the fields a transaction does not share with the
+/// one before it.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TransactionDelta {
+    /// Seconds since the previous transaction.
+    pub elapsed: u64,
+    pub new_commitment: Bytes32,
+    pub metadata_hash: Bytes32,
+    /// Set only when the transaction does not start from the previous new commitment.
+    pub old_commitment: Option<Bytes32>,
+    /// Set only when the root is not the one over the history before the transaction.
+    pub merkle_root: Option<Bytes32>,
+    #[serde(default)]
+    pub proof: Option<ProofRef>,
+}
+
+/// A run of sequential transactions: the first in full, then one delta per transaction,
+/// closed by a hash chained over every transaction in the run.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DeltaRun {
+    pub first: CompressedTransaction,
+    pub deltas: Vec<TransactionDelta>,
+    pub chain_hash: Bytes32,
+}
+
+/// A channel's history as delta-encoded runs, for cold storage.
+///
+/// A transaction continues the current run when its timestamp does not go backwards
+/// and it is in the run's format version;
+/// its old commitment and Merkle root are dropped whenever they follow from the
+/// transactions before it, which holds for every update the storage records itself.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct DeltaHistory {
+    pub runs: Vec<DeltaRun>,
+}
+
+const FLAG_OLD_COMMITMENT: u8 = 0x01;
+const FLAG_MERKLE_ROOT: u8 = 0x02;
+const FLAG_PROOF: u8 = 0x04;
+const CHAIN_DOMAIN: &[u8] = b"overpass/delta-history/v1";
+
+impl DeltaHistory {
+    /// Delta-encodes a channel's full history, oldest first.
+    pub fn encode(history: &[CompressedTransaction]) -> Self {
+        let mut runs: Vec<DeltaRun> = Vec::new();
+        let mut roots = Vec::with_capacity(history.len());
+        let mut previous: Option<&CompressedTransaction> = None;
+        for tx in history {
+            let expected_root = compute_merkle_root(roots.clone());
+            match (previous, runs.last_mut()) {
+                (Some(prev), Some(run))
+                    if tx.timestamp >= prev.timestamp && tx.version == prev.version =>
+                {
+                    run.deltas.push(TransactionDelta {
+                        elapsed: tx.timestamp - prev.timestamp,
+                        new_commitment: tx.new_commitment,
+                        metadata_hash: tx.metadata_hash,
+                        old_commitment: (tx.old_commitment != prev.new_commitment)
+                            .then_some(tx.old_commitment),
+                        merkle_root: (tx.merkle_root != expected_root).then_some(tx.merkle_root),
+                        proof: tx.proof.clone(),
+                    });
+                    run.chain_hash = chain(&run.chain_hash, tx);
+                }
+                _ => runs.push(DeltaRun {
+                    first: tx.clone(),
+                    deltas: Vec::new(),
+                    chain_hash: chain(&[0u8; 32], tx),
+                }),
+            }
+            roots.push(tx.merkle_root);
+            previous = Some(tx);
+        }
+        Self { runs }
+    }
+
+    /// Rebuilds the full history, checking each run against its chain hash.
+    pub fn decode(&self) -> Result<Vec<CompressedTransaction>, DeltaError> {
+        let mut history: Vec<CompressedTransaction> = Vec::new();
+        let mut roots = Vec::new();
+        for (index, run) in self.runs.iter().enumerate() {
+            let mut hash = chain(&[0u8; 32], &run.first);
+            roots.push(run.first.merkle_root);
+            let mut prev = run.first.clone();
+            history.push(run.first.clone());
+            for delta in &run.deltas {
+                let tx = CompressedTransaction {
+                    version: prev.version,
+                    timestamp: prev
+                        .timestamp
+                        .checked_add(delta.elapsed)
+                        .ok_or(DeltaError::ChainMismatch(index))?,
+                    old_commitment: delta.old_commitment.unwrap_or(prev.new_commitment),
+                    new_commitment: delta.new_commitment,
+                    metadata_hash: delta.metadata_hash,
+                    merkle_root: delta
+                        .merkle_root
+                        .unwrap_or_else(|| compute_merkle_root(roots.clone())),
+                    proof: delta.proof.clone(),
+                };
+                hash = chain(&hash, &tx);
+                roots.push(tx.merkle_root);
+                history.push(tx.clone());
+                prev = tx;
+            }
+            if hash != run.chain_hash {
+                return Err(DeltaError::ChainMismatch(index));
+            }
+        }
+        Ok(history)
+    }
+
+    /// Serializes the runs compactly: fixed-size hashes, LEB128 counts and intervals,
+    /// and a flag byte per delta naming the optional fields present, proofs last.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        put_varint(&mut out, self.runs.len() as u64);
+        for run in &self.runs {
+            put_proven(&mut out, &run.first);
+            put_varint(&mut out, run.deltas.len() as u64);
+            for delta in &run.deltas {
+                let mut flags = 0;
+                if delta.old_commitment.is_some() {
+                    flags |= FLAG_OLD_COMMITMENT;
+                }
+                if delta.merkle_root.is_some() {
+                    flags |= FLAG_MERKLE_ROOT;
+                }
+                if delta.proof.is_some() {
+                    flags |= FLAG_PROOF;
+                }
+                out.push(flags);
+                put_varint(&mut out, delta.elapsed);
+                out.extend_from_slice(&delta.new_commitment);
+                out.extend_from_slice(&delta.metadata_hash);
+                for field in [&delta.old_commitment, &delta.merkle_root]
+                    .into_iter()
+                    .flatten()
+                {
+                    out.extend_from_slice(field);
+                }
+                if delta.proof.is_some() {
+                    put_proof(&mut out, &delta.proof);
+                }
+            }
+            out.extend_from_slice(&run.chain_hash);
+        }
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DeltaError> {
+        let mut cursor = Cursor(bytes);
+        let mut runs = Vec::new();
+        for _ in 0..cursor.varint()? {
+            let first = cursor.proven()?;
+            let mut deltas = Vec::new();
+            for _ in 0..cursor.varint()? {
+                let flags = cursor.take(1)?[0];
+                if flags & !(FLAG_OLD_COMMITMENT | FLAG_MERKLE_ROOT | FLAG_PROOF) != 0 {
+                    return Err(DeltaError::InvalidFlags(flags));
+                }
+                let elapsed = cursor.varint()?;
+                let new_commitment = cursor.bytes32()?;
+                let metadata_hash = cursor.bytes32()?;
+                let old_commitment = match flags & FLAG_OLD_COMMITMENT {
+                    0 => None,
+                    _ => Some(cursor.bytes32()?),
+                };
+                let merkle_root = match flags & FLAG_MERKLE_ROOT {
+                    0 => None,
+                    _ => Some(cursor.bytes32()?),
+                };
+                let proof = match flags & FLAG_PROOF {
+                    0 => None,
+                    _ => cursor.proof()?,
+                };
+                deltas.push(TransactionDelta {
+                    elapsed,
+                    new_commitment,
+                    metadata_hash,
+                    old_commitment,
+                    merkle_root,
+                    proof,
+                });
+            }
+            runs.push(DeltaRun {
+                first,
+                deltas,
+                chain_hash: cursor.bytes32()?,
+            });
+        }
+        if !cursor.0.is_empty() {
+            return Err(DeltaError::Truncated);
+        }
+        Ok(Self { runs })
+    }
+}
+
+/// Extends a run's chain hash with one transaction.
+fn chain(prev: &Bytes32, tx: &CompressedTransaction) -> Bytes32 {
+    Sha256::new()
+        .chain_update(CHAIN_DOMAIN)
+        .chain_update(prev)
+        .chain_update(tx.to_bytes())
+        .finalize()
+        .into()
+}
+
+/// Writes a transaction's canonical encoding and then its proof.
+fn put_proven(out: &mut Vec<u8>, tx: &CompressedTransaction) {
+    out.extend_from_slice(&tx.to_bytes());
+    put_proof(out, &tx.proof);
+}
+
+/// Writes a tag byte, then a digest or the proof's hash, timestamp, input count and
+/// inputs.
+fn put_proof(out: &mut Vec<u8>, proof: &Option<ProofRef>) {
+    match proof {
+        None => out.push(PROOF_NONE),
+        Some(ProofRef::Digest(digest)) => {
+            out.push(PROOF_DIGEST);
+            out.extend_from_slice(digest);
+        }
+        Some(ProofRef::Embedded(proof)) => {
+            out.push(PROOF_EMBEDDED);
+            out.extend_from_slice(&proof.pi);
+            out.extend_from_slice(&proof.timestamp.to_le_bytes());
+            put_varint(out, proof.public_inputs.len() as u64);
+            for input in &proof.public_inputs {
+                out.extend_from_slice(input);
+            }
+        }
+    }
+}
+
+fn put_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+struct Cursor<'a>(&'a [u8]);
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], DeltaError> {
+        if self.0.len() < len {
+            return Err(DeltaError::Truncated);
+        }
+        let (head, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(head)
+    }
+
+    fn bytes32(&mut self) -> Result<Bytes32, DeltaError> {
+        Ok(self.take(32)?.try_into().unwrap())
+    }
+
+    fn proven(&mut self) -> Result<CompressedTransaction, DeltaError> {
+        let bytes = self.take(CompressedTransaction::ENCODED_LEN)?;
+        let mut tx = CompressedTransaction::from_bytes(bytes.try_into().unwrap())?;
+        tx.proof = self.proof()?;
+        Ok(tx)
+    }
+
+    fn proof(&mut self) -> Result<Option<ProofRef>, DeltaError> {
+        match self.take(1)?[0] {
+            PROOF_NONE => Ok(None),
+            PROOF_DIGEST => Ok(Some(ProofRef::Digest(self.bytes32()?))),
+            PROOF_EMBEDDED => {
+                let pi = self.bytes32()?;
+                let timestamp = u64::from_le_bytes(self.take(8)?.try_into().unwrap());
+                let count = self.varint()?;
+                let mut public_inputs = Vec::new();
+                for _ in 0..count {
+                    public_inputs.push(self.bytes32()?);
+                }
+                Ok(Some(ProofRef::Embedded(StateProof {
+                    pi,
+                    public_inputs,
+                    timestamp,
+                })))
+            }
+            tag => Err(DeltaError::InvalidProof(tag)),
+        }
+    }
+
+    fn varint(&mut self) -> Result<u64, DeltaError> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.take(1)?[0];
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(DeltaError::Truncated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zkp::mobile_optimized_storage::{MobileOptimizedStorage, ProofRetention};
+    use crate::zkp::state_proof::StateProof;
+    use crate::zkp::tx_metadata::TxMetadata;
+
+    fn busy_channel(updates: u8) -> MobileOptimizedStorage {
+        let mut storage = MobileOptimizedStorage::new(1_000, 30 * 24 * 3600);
+        for i in 0..updates {
+            let proof = StateProof {
+                pi: [i; 32],
+                public_inputs: Vec::new(),
+                timestamp: 1_700_000_000 + u64::from(i) * 7,
+            };
+            let metadata = TxMetadata::Custom {
+                schema: "test/update".into(),
+                payload: vec![i],
+            };
+            storage
+                .store_transaction([1u8; 32], [i; 32], [i + 1; 32], proof, metadata)
+                .unwrap();
+        }
+        storage
+    }
+
+    #[test]
+    fn test_canonical_encoding_round_trips() {
+        let tx = CompressedTransaction {
+            version: CompressedTransaction::CURRENT_VERSION,
+            timestamp: 0x0102_0304_0506_0708,
+            old_commitment: [0xaa; 32],
+            new_commitment: [0xbb; 32],
+            metadata_hash: [0xcc; 32],
+            merkle_root: [0xdd; 32],
+            proof: None,
+        };
+        let bytes = tx.to_bytes();
+        assert_eq!(bytes[0], 2);
+        assert_eq!(bytes[1..9], [8, 7, 6, 5, 4, 3, 2, 1]);
+        assert_eq!(bytes[9..41], [0xaa; 32]);
+        assert_eq!(bytes[105..], [0xdd; 32]);
+        assert_eq!(CompressedTransaction::from_bytes(&bytes), Ok(tx));
+
+        let history = busy_channel(4).history(&[1u8; 32]).to_vec();
+        let encoded = CompressedTransaction::encode_all(&history);
+        assert_eq!(encoded.len(), 4 * CompressedTransaction::ENCODED_LEN);
+        assert_eq!(CompressedTransaction::decode_all(&encoded), Ok(history));
+        assert_eq!(
+            CompressedTransaction::decode_all(&encoded[1..]),
+            Err(DeltaError::Truncated)
+        );
+    }
+
+    #[test]
+    fn test_delta_history_round_trips() {
+        let storage = busy_channel(50);
+        let history = storage.history(&[1u8; 32]).to_vec();
+        let encoded = DeltaHistory::encode(&history);
+        assert_eq!(encoded.runs.len(), 1);
+        assert!(encoded.runs[0]
+            .deltas
+            .iter()
+            .all(|delta| delta.old_commitment.is_none() && delta.merkle_root.is_none()));
+        let bytes = encoded.to_bytes();
+        assert!(bytes.len() < history.len() * 70);
+        assert!(bytes.len() * 2 < bincode::serialize(&history).unwrap().len() + 100);
+        assert_eq!(DeltaHistory::from_bytes(&bytes).unwrap(), encoded);
+        assert_eq!(encoded.decode().unwrap(), history);
+
+        let mut restored = MobileOptimizedStorage::new(1_000, 30 * 24 * 3600);
+        restored
+            .import_cold_history([1u8; 32], &storage.cold_history(&[1u8; 32]))
+            .unwrap();
+        assert_eq!(restored.history(&[1u8; 32]), history.as_slice());
+
+        // Out-of-order and non-sequential entries are kept, at the cost of a new run or
+        // an explicit field.
+        let mut irregular = history[..3].to_vec();
+        irregular[2].old_commitment = [9u8; 32];
+        irregular.push(CompressedTransaction {
+            timestamp: 1,
+            ..history[3].clone()
+        });
+        let encoded = DeltaHistory::encode(&irregular);
+        assert_eq!(encoded.runs.len(), 2);
+        assert_eq!(encoded.runs[0].deltas[1].old_commitment, Some([9u8; 32]));
+        assert_eq!(encoded.decode().unwrap(), irregular);
+        assert_eq!(DeltaHistory::encode(&[]).decode().unwrap(), Vec::new());
+    }
+
+    #[test]
+    fn test_tampered_runs_are_rejected() {
+        let history = busy_channel(5).history(&[1u8; 32]).to_vec();
+        let mut encoded = DeltaHistory::encode(&history);
+        encoded.runs[0].deltas[2].new_commitment = [0u8; 32];
+        assert_eq!(encoded.decode(), Err(DeltaError::ChainMismatch(0)));
+
+        let bytes = DeltaHistory::encode(&history).to_bytes();
+        assert_eq!(
+            DeltaHistory::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(DeltaError::Truncated)
+        );
+        let mut flagged = bytes.clone();
+        flagged[1 + CompressedTransaction::ENCODED_LEN + 2] = 0x80;
+        assert_eq!(
+            DeltaHistory::from_bytes(&flagged),
+            Err(DeltaError::InvalidFlags(0x80))
+        );
+    }
+
+    #[test]
+    fn test_proofs_travel_with_their_records() {
+        let mut storage = busy_channel(3).with_proof_retention(ProofRetention::Embedded);
+        let proof = crate::zkp::helpers::convert_helper_proof(
+            crate::zkp::helpers::generate_state_proof(
+                [3u8; 32],
+                [4u8; 32],
+                [0u8; 32],
+                &crate::zkp::pedersen_parameters::PedersenParameters::default(),
+            ),
+        );
+        storage
+            .store_transaction([1u8; 32], [3u8; 32], [4u8; 32], proof.clone(), TxMetadata::None)
+            .unwrap();
+        let mut history = storage.history(&[1u8; 32]).to_vec();
+        assert_eq!(history[3].proof, Some(ProofRef::Embedded(proof.clone())));
+        assert!(history[3].verify_proof(None));
+        assert!(!history[2].verify_proof(None));
+
+        let mut referenced = history[3].clone();
+        referenced.proof = Some(ProofRef::Digest(ProofRef::digest_of(&proof)));
+        assert!(referenced.verify_proof(Some(&proof)));
+        assert!(!referenced.verify_proof(None));
+        history.push(CompressedTransaction {
+            timestamp: history[3].timestamp + 1,
+            ..referenced
+        });
+
+        let encoded = CompressedTransaction::encode_proven(&history);
+        assert_eq!(CompressedTransaction::decode_proven(&encoded), Ok(history.clone()));
+        let delta = DeltaHistory::encode(&history);
+        assert_eq!(DeltaHistory::from_bytes(&delta.to_bytes()), Ok(delta.clone()));
+        assert_eq!(delta.decode(), Ok(history.clone()));
+        // The canonical encoding leaves proofs out of the hashes over a record.
+        assert_eq!(
+            CompressedTransaction::decode_all(&CompressedTransaction::encode_all(&history))
+                .unwrap()[3]
+                .proof,
+            None
+        );
+
+        let mut tagged = CompressedTransaction::encode_proven(&history[..1]);
+        *tagged.last_mut().unwrap() = 7;
+        assert_eq!(
+            CompressedTransaction::decode_proven(&tagged),
+            Err(DeltaError::InvalidProof(7))
+        );
+    }
+}
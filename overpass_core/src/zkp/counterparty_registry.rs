@@ -0,0 +1,280 @@
+// src/zkp/counterparty_registry.rs
+
+use bitcoin::secp256k1::PublicKey;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+use crate::services::overpass_db::OverpassDB;
+use crate::zkp::helpers::Bytes32;
+use crate::zkp::pedersen_parameters::FINGERPRINT_LEN;
+
+const REGISTRY_PREFIX: &[u8] = b"counterparty_registry:";
+const EXPORT_VERSION: u32 = 1;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum RegistryError {
+    #[error("Counterparty {} is known under another public key", hex::encode(.0))]
+    KeyConflict(Bytes32),
+    #[error("Unsupported export version {0}")]
+    UnsupportedVersion(u32),
+    #[error("Invalid export: {0}")]
+    InvalidExport(String),
+    #[error("Storage error: {0}")]
+    StorageError(String),
+    #[error("Corrupt registry state: {0}")]
+    CorruptState(String),
+}
+
+/// What the wallet knows about a counterparty it opens channels with.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Counterparty {
+    pub id: Bytes32,
+    pub name: String,
+    pub public_key: PublicKey,
+    /// Fingerprint of the Pedersen parameters the counterparty commits under.
+    pub params_fingerprint: [u8; FINGERPRINT_LEN],
+    /// Where channel updates for the counterparty are delivered, preferred first.
+    pub endpoints: Vec<String>,
+    /// Free-form notes on why and how far the counterparty is trusted.
+    pub trust_notes: String,
+}
+
+/// Counterparties merged into a registry by an import.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ImportReport {
+    pub added: Vec<Bytes32>,
+    pub updated: Vec<Bytes32>,
+    /// Entries skipped because they name a known counterparty under a different key.
+    pub conflicts: Vec<Bytes32>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RegistryExport {
+    version: u32,
+    counterparties: Vec<Counterparty>,
+}
+
+/// A wallet's address book of known counterparties, by id.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CounterpartyRegistry {
+    entries: BTreeMap<Bytes32, Counterparty>,
+}
+
+impl CounterpartyRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds or updates a counterparty. A known id under a different public key is refused,
+    /// so a key change has to be made by removing the old entry first.
+    pub fn insert(&mut self, counterparty: Counterparty) -> Result<(), RegistryError> {
+        if let Some(known) = self.entries.get(&counterparty.id) {
+            if known.public_key != counterparty.public_key {
+                return Err(RegistryError::KeyConflict(counterparty.id));
+            }
+        }
+        self.entries.insert(counterparty.id, counterparty);
+        Ok(())
+    }
+
+    pub fn get(&self, id: &Bytes32) -> Option<&Counterparty> {
+        self.entries.get(id)
+    }
+
+    /// Finds a counterparty by its name.
+    pub fn find_by_name(&self, name: &str) -> Option<&Counterparty> {
+        self.entries
+            .values()
+            .find(|counterparty| counterparty.name == name)
+    }
+
+    pub fn remove(&mut self, id: &Bytes32) -> Option<Counterparty> {
+        self.entries.remove(id)
+    }
+
+    /// Gets every counterparty, ordered by id.
+    pub fn iter(&self) -> impl Iterator<Item = &Counterparty> {
+        self.entries.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Exports every counterparty as versioned JSON, to import on another device.
+    pub fn export(&self) -> Result<String, RegistryError> {
+        serde_json::to_string(&RegistryExport {
+            version: EXPORT_VERSION,
+            counterparties: self.entries.values().cloned().collect(),
+        })
+        .map_err(|e| RegistryError::InvalidExport(e.to_string()))
+    }
+
+    /// Merges an export into the registry. Entries that would change a known key are
+    /// skipped and reported rather than failing the whole import.
+    pub fn import(&mut self, export: &str) -> Result<ImportReport, RegistryError> {
+        let export: RegistryExport = serde_json::from_str(export)
+            .map_err(|e| RegistryError::InvalidExport(e.to_string()))?;
+        if export.version != EXPORT_VERSION {
+            return Err(RegistryError::UnsupportedVersion(export.version));
+        }
+        let mut report = ImportReport::default();
+        for counterparty in export.counterparties {
+            let id = counterparty.id;
+            match self.entries.get(&id) {
+                Some(known) if *known == counterparty => continue,
+                Some(_) => {
+                    if self.insert(counterparty).is_err() {
+                        report.conflicts.push(id);
+                        continue;
+                    }
+                    report.updated.push(id);
+                }
+                None => {
+                    self.entries.insert(id, counterparty);
+                    report.added.push(id);
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    /// Writes the registry to `db` under `wallet_id`.
+    pub fn save(&self, db: &OverpassDB, wallet_id: &Bytes32) -> Result<(), RegistryError> {
+        let bytes =
+            bincode::serialize(self).map_err(|e| RegistryError::StorageError(e.to_string()))?;
+        db.put(&registry_key(wallet_id), &bytes)
+            .and_then(|_| db.flush())
+            .map_err(|e| RegistryError::StorageError(e.to_string()))
+    }
+
+    /// Loads the registry saved for `wallet_id`, or an empty one if nothing was saved.
+    pub fn restore(db: &OverpassDB, wallet_id: &Bytes32) -> Result<Self, RegistryError> {
+        match db
+            .get(&registry_key(wallet_id))
+            .map_err(|e| RegistryError::StorageError(e.to_string()))?
+        {
+            Some(bytes) => {
+                bincode::deserialize(&bytes).map_err(|e| RegistryError::CorruptState(e.to_string()))
+            }
+            None => Ok(Self::default()),
+        }
+    }
+}
+
+fn registry_key(wallet_id: &Bytes32) -> Vec<u8> {
+    let mut key = REGISTRY_PREFIX.to_vec();
+    key.extend_from_slice(hex::encode(wallet_id).as_bytes());
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+    use crate::zkp::pedersen_parameters::PedersenParameters;
+    use crate::zkp::wallet_contract::WalletContractError;
+    use bitcoin::secp256k1::{Secp256k1, SecretKey};
+
+    fn counterparty(id: u8, key: u8) -> Counterparty {
+        let secret = SecretKey::from_slice(&[key; 32]).unwrap();
+        Counterparty {
+            id: [id; 32],
+            name: format!("peer-{id}"),
+            public_key: PublicKey::from_secret_key(&Secp256k1::new(), &secret),
+            params_fingerprint: PedersenParameters::default().fingerprint(),
+            endpoints: vec![format!("https://peer-{id}.example")],
+            trust_notes: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_import_merges_and_reports_key_conflicts() {
+        let mut registry = CounterpartyRegistry::new();
+        registry.insert(counterparty(1, 1)).unwrap();
+        assert_eq!(
+            registry.insert(counterparty(1, 2)),
+            Err(RegistryError::KeyConflict([1u8; 32]))
+        );
+
+        let mut other = CounterpartyRegistry::new();
+        let mut noted = counterparty(1, 1);
+        noted.trust_notes = "met in person".into();
+        other.insert(noted).unwrap();
+        other.insert(counterparty(2, 2)).unwrap();
+        let mut device = registry.clone();
+        device.remove(&[1u8; 32]);
+        device.insert(counterparty(1, 3)).unwrap();
+        device.insert(counterparty(3, 3)).unwrap();
+
+        let report = registry.import(&other.export().unwrap()).unwrap();
+        assert_eq!(report.added, vec![[2u8; 32]]);
+        assert_eq!(report.updated, vec![[1u8; 32]]);
+        assert_eq!(
+            registry.get(&[1u8; 32]).unwrap().trust_notes,
+            "met in person"
+        );
+
+        let report = registry.import(&device.export().unwrap()).unwrap();
+        assert_eq!(report.added, vec![[3u8; 32]]);
+        assert_eq!(report.conflicts, vec![[1u8; 32]]);
+        assert_eq!(registry.len(), 3);
+        assert_eq!(
+            registry.find_by_name("peer-2").map(|c| c.id),
+            Some([2u8; 32])
+        );
+
+        assert_eq!(
+            registry.import(r#"{"version":2,"counterparties":[]}"#),
+            Err(RegistryError::UnsupportedVersion(2))
+        );
+        assert!(matches!(
+            registry.import("not json"),
+            Err(RegistryError::InvalidExport(_))
+        ));
+    }
+
+    #[test]
+    fn test_registry_survives_restart() -> anyhow::Result<()> {
+        let path =
+            std::env::temp_dir().join(format!("counterparty_registry_{}", std::process::id()));
+        let db = OverpassDB::new(path.to_str().unwrap())?;
+        assert!(CounterpartyRegistry::restore(&db, &[1u8; 32])?.is_empty());
+
+        let mut registry = CounterpartyRegistry::new();
+        registry.insert(counterparty(1, 1))?;
+        registry.save(&db, &[1u8; 32])?;
+        assert_eq!(CounterpartyRegistry::restore(&db, &[1u8; 32])?, registry);
+        assert!(CounterpartyRegistry::restore(&db, &[2u8; 32])?.is_empty());
+        drop(db);
+        let _ = std::fs::remove_dir_all(path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_wallet_checks_known_counterparties() -> Result<(), WalletContractError> {
+        let mut registry = CounterpartyRegistry::new();
+        registry.insert(counterparty(1, 1)).unwrap();
+        let mut foreign = counterparty(2, 2);
+        foreign.params_fingerprint = PedersenParameters::from_label(b"other").fingerprint();
+        registry.insert(foreign).unwrap();
+        let mut wallet = test_util::unkeyed_wallet([9u8; 32]).with_registry(registry);
+
+        assert!(matches!(
+            wallet.register_channel([5u8; 32], 100, [2u8; 32], Vec::new()),
+            Err(WalletContractError::CounterpartyMismatch(_))
+        ));
+        assert!(wallet.register_channel([5u8; 32], 100, [1u8; 32], Vec::new())?);
+        assert!(wallet.register_channel([6u8; 32], 100, [4u8; 32], Vec::new())?);
+
+        let payment = wallet.pay([1u8; 32], 10).send()?;
+        assert_eq!(payment.endpoint.as_deref(), Some("https://peer-1.example"));
+        assert_eq!(wallet.pay([4u8; 32], 10).send()?.endpoint, None);
+        Ok(())
+    }
+}
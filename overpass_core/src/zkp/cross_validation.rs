@@ -0,0 +1,140 @@
+// src/zkp/cross_validation.rs
+
+use serde::{Deserialize, Serialize};
+
+use crate::zkp::global_root_contract::GlobalRootContract;
+use crate::zkp::helpers::{verify_merkle_path, Bytes32};
+use crate::zkp::sub_account::AccountTree;
+
+/// A way a wallet's channels and the global root contract disagree.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RootInconsistency {
+    /// The wallet's cached root is not what its channels hash to.
+    StaleWalletRoot { cached: Bytes32, computed: Bytes32 },
+    /// The global contract has no root for the wallet.
+    NotRegistered,
+    /// The global contract records a different root than the wallet's channels hash to.
+    RootMismatch {
+        recorded: Bytes32,
+        computed: Bytes32,
+    },
+    /// A channel's state is not a leaf of the root the global contract records.
+    UnrecordedChannel(Bytes32),
+    /// The recorded wallet root cannot be proven part of the global root.
+    NotInGlobalRoot,
+}
+
+/// Outcome of checking one wallet against the global root contract.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValidationReport {
+    pub issues: Vec<RootInconsistency>,
+    /// Issues that were repaired before the report was taken.
+    pub repaired: Vec<RootInconsistency>,
+}
+
+impl ValidationReport {
+    pub fn is_consistent(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// Checks a wallet's channel tree and its cached root against what `global` records for
+/// `wallet_id`.
+pub fn check(
+    wallet_id: &Bytes32,
+    cached_root: &Bytes32,
+    tree: &AccountTree,
+    global: &GlobalRootContract,
+) -> ValidationReport {
+    let computed = tree.root();
+    let mut issues = Vec::new();
+    if *cached_root != computed {
+        issues.push(RootInconsistency::StaleWalletRoot {
+            cached: *cached_root,
+            computed,
+        });
+    }
+
+    let Some(recorded) = global.get_wallet_root(wallet_id) else {
+        issues.push(RootInconsistency::NotRegistered);
+        return ValidationReport {
+            issues,
+            repaired: Vec::new(),
+        };
+    };
+    if recorded != computed {
+        issues.push(RootInconsistency::RootMismatch { recorded, computed });
+    }
+    for (channel_id, hash) in tree.leaves() {
+        let recorded_leaf = tree.path(channel_id).is_some_and(|(index, siblings)| {
+            verify_merkle_path(*hash, index, &siblings, &recorded)
+        });
+        if !recorded_leaf {
+            issues.push(RootInconsistency::UnrecordedChannel(*channel_id));
+        }
+    }
+    let included = global
+        .inclusion_proof(wallet_id)
+        .is_ok_and(|proof| proof.verify(&global.get_global_merkle_root()));
+    if !included {
+        issues.push(RootInconsistency::NotInGlobalRoot);
+    }
+    ValidationReport {
+        issues,
+        repaired: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+    use crate::zkp::wallet_contract::WalletContract;
+
+    fn wallet(channels: &[(u8, u64)]) -> WalletContract {
+        let mut wallet = test_util::unkeyed_wallet([1u8; 32]);
+        for &(channel, balance) in channels {
+            wallet
+                .channels
+                .insert([channel; 32], test_util::channel_state(vec![balance], 0));
+        }
+        wallet
+    }
+
+    #[test]
+    fn test_divergence_is_reported_and_repaired() {
+        let mut wallet = wallet(&[(1, 100), (2, 50)]);
+        let report = wallet.validate_against_global().unwrap();
+        assert!(matches!(
+            report.issues.as_slice(),
+            [
+                RootInconsistency::StaleWalletRoot { .. },
+                RootInconsistency::NotRegistered
+            ]
+        ));
+
+        let report = wallet.repair_against_global(10).unwrap();
+        assert!(report.is_consistent());
+        assert_eq!(report.repaired.len(), 2);
+
+        let recorded = wallet.get_merkle_root();
+        wallet.channels.get_mut(&[2u8; 32]).unwrap().balances = vec![40];
+        let report = wallet.validate_against_global().unwrap();
+        assert_eq!(report.issues.len(), 4);
+        assert!(matches!(
+            report.issues[1],
+            RootInconsistency::RootMismatch { recorded: r, .. } if r == recorded
+        ));
+        assert!(report
+            .issues
+            .contains(&RootInconsistency::UnrecordedChannel([2u8; 32])));
+
+        let report = wallet.repair_against_global(20).unwrap();
+        assert!(report.is_consistent());
+        assert_eq!(wallet.global_contract.pending_submissions().len(), 1);
+        assert_eq!(
+            wallet.global_contract.get_wallet_root(&[1u8; 32]),
+            Some(wallet.get_merkle_root())
+        );
+    }
+}
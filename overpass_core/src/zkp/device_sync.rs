@@ -0,0 +1,255 @@
+// src/zkp/device_sync.rs
+
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use thiserror::Error;
+
+use crate::zkp::compressed_transaction::CompressedTransaction;
+use crate::zkp::helpers::Bytes32;
+use crate::zkp::reconciliation::{ChannelEvidence, ReconciliationError, TreeView};
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum SyncError {
+    #[error("Bundle belongs to wallet {}", hex::encode(.0))]
+    WrongWallet(Bytes32),
+    #[error("Reconciliation error: {0}")]
+    Reconciliation(#[from] ReconciliationError),
+    #[error("Wallet error: {0}")]
+    Wallet(String),
+}
+
+/// Everything one device of a wallet sends another so the two converge.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SyncBundle {
+    pub wallet_id: Bytes32,
+    pub device_id: Bytes32,
+    pub view: TreeView,
+    /// Every open channel's state with proof of its place in the view.
+    pub channels: Vec<ChannelEvidence>,
+    /// Channels the sending device has closed.
+    pub closed: Vec<Bytes32>,
+    /// Stored transaction history of each open channel.
+    pub history: Vec<(Bytes32, Vec<CompressedTransaction>)>,
+}
+
+impl SyncBundle {
+    pub fn evidence(&self, channel_id: &Bytes32) -> Option<&ChannelEvidence> {
+        self.channels
+            .iter()
+            .find(|evidence| evidence.proof.channel_id == *channel_id)
+    }
+}
+
+/// What a device holds for a channel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Holding {
+    /// The channel is open at this nonce and state hash.
+    Open(u64, Bytes32),
+    Closed,
+    Absent,
+}
+
+/// A channel both devices changed since they last agreed on it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncConflict {
+    pub channel_id: Bytes32,
+    pub local: Holding,
+    pub remote: Holding,
+}
+
+/// How a device settles a channel it and its peer disagree about.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyncAction {
+    /// Take the peer's state.
+    Pull,
+    /// Close the channel, as the peer has.
+    Close,
+    /// Keep this device's state; the peer picks it up when it syncs.
+    Keep,
+    Conflict,
+}
+
+/// What a sync changed on the receiving device.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SyncReport {
+    pub pulled: Vec<Bytes32>,
+    pub closed: Vec<Bytes32>,
+    /// Channels on which this device is ahead of the peer.
+    pub ahead: Vec<Bytes32>,
+    /// Channels left as they were because both devices moved them.
+    pub conflicts: Vec<SyncConflict>,
+}
+
+/// Decides how to settle a channel given what each device holds and `base`, the state
+/// hash both held when they last synced.
+///
+/// With a base, whichever side still holds it is behind, and neither holding it means
+/// both devices made transitions from it independently. Without one, the later nonce
+/// wins and equal nonces conflict, as in counterparty reconciliation.
+pub fn decide(local: Holding, remote: Holding, base: Option<&Bytes32>) -> SyncAction {
+    if local == remote {
+        return SyncAction::Keep;
+    }
+    let moved_from_base = |hash: &Bytes32| base.is_some_and(|base| base != hash);
+    match (local, remote) {
+        (Holding::Open(nonce, hash), Holding::Open(remote_nonce, remote_hash)) => match base {
+            Some(base) if *base == hash => SyncAction::Pull,
+            Some(base) if *base == remote_hash => SyncAction::Keep,
+            Some(_) => SyncAction::Conflict,
+            None => match nonce.cmp(&remote_nonce) {
+                Ordering::Less => SyncAction::Pull,
+                Ordering::Greater => SyncAction::Keep,
+                Ordering::Equal => SyncAction::Conflict,
+            },
+        },
+        (Holding::Absent, Holding::Open(..)) => SyncAction::Pull,
+        (Holding::Closed, Holding::Open(_, remote_hash)) if moved_from_base(&remote_hash) => {
+            SyncAction::Conflict
+        }
+        (Holding::Open(_, hash), Holding::Closed) if moved_from_base(&hash) => SyncAction::Conflict,
+        (Holding::Open(..), Holding::Closed) => SyncAction::Close,
+        _ => SyncAction::Keep,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+    use crate::zkp::global_root_contract::GlobalRootContract;
+    use crate::zkp::pedersen_parameters::PedersenParameters;
+    use crate::zkp::state_proof::StateProof;
+    use crate::zkp::tx_metadata::TxMetadata;
+    use crate::zkp::wallet_contract::WalletContract;
+
+    fn device() -> WalletContract {
+        let mut wallet = test_util::unkeyed_wallet([1u8; 32]);
+        for channel in 1..=4u8 {
+            wallet.channels.insert([channel; 32], test_util::channel_state(vec![100], 0));
+        }
+        wallet
+    }
+
+    fn transition(wallet: &mut WalletContract, channel: u8, balance: u64, nonce: u64) {
+        wallet
+            .channels
+            .insert([channel; 32], test_util::channel_state(vec![balance], nonce));
+    }
+
+    #[test]
+    fn test_devices_converge_and_flag_diverging_transitions() {
+        const PHONE: Bytes32 = [0xaa; 32];
+        const DESKTOP: Bytes32 = [0xdd; 32];
+        let (mut phone, mut desktop) = (device(), device());
+        phone
+            .sync_from(&desktop.sync_bundle(DESKTOP).unwrap())
+            .unwrap();
+        desktop
+            .sync_from(&phone.sync_bundle(PHONE).unwrap())
+            .unwrap();
+
+        transition(&mut phone, 1, 90, 1);
+        phone.close_channel(&[4u8; 32]).unwrap();
+        transition(&mut desktop, 2, 80, 1);
+        let proof = StateProof {
+            pi: [0u8; 32],
+            public_inputs: Vec::new(),
+            timestamp: 1,
+        };
+        desktop
+            .storage
+            .store_transaction(
+                [2u8; 32],
+                [0u8; 32],
+                [2u8; 32],
+                proof,
+                TxMetadata::None,
+            )
+            .unwrap();
+        // Both devices move channel 3 on from the state they last agreed on.
+        transition(&mut phone, 3, 70, 1);
+        transition(&mut desktop, 3, 60, 2);
+
+        let report = phone
+            .sync_from(&desktop.sync_bundle(DESKTOP).unwrap())
+            .unwrap();
+        assert_eq!(report.pulled, vec![[2u8; 32]]);
+        assert_eq!(report.ahead, vec![[1u8; 32], [4u8; 32]]);
+        assert_eq!(report.conflicts.len(), 1);
+        assert_eq!(report.conflicts[0].channel_id, [3u8; 32]);
+        assert!(matches!(report.conflicts[0].remote, Holding::Open(2, _)));
+        assert_eq!(phone.storage.history(&[2u8; 32]).len(), 1);
+
+        let report = desktop
+            .sync_from(&phone.sync_bundle(PHONE).unwrap())
+            .unwrap();
+        assert_eq!(report.pulled, vec![[1u8; 32]]);
+        assert_eq!(report.closed, vec![[4u8; 32]]);
+        assert!(report.ahead.is_empty());
+        assert_eq!(report.conflicts.len(), 1);
+        assert!(!desktop.has_channel(&[4u8; 32]));
+        assert_eq!(desktop.channels[&[3u8; 32]].balances, vec![60]);
+
+        // Once one side takes the other's state, the conflict clears on both.
+        transition(&mut phone, 3, 60, 2);
+        let report = desktop
+            .sync_from(&phone.sync_bundle(PHONE).unwrap())
+            .unwrap();
+        assert_eq!(report, SyncReport::default());
+        assert_eq!(phone.tree_view().unwrap(), desktop.tree_view().unwrap());
+    }
+
+    #[test]
+    fn test_bundles_are_checked() {
+        let mut phone = device();
+        let mut other = WalletContract::new(
+            [2u8; 32],
+            PedersenParameters::default(),
+            GlobalRootContract::new(PedersenParameters::default()),
+        );
+        other
+            .channels
+            .insert([5u8; 32], test_util::channel_state(vec![1], 0));
+        assert_eq!(
+            phone.sync_from(&other.sync_bundle([0u8; 32]).unwrap()),
+            Err(SyncError::WrongWallet([2u8; 32]))
+        );
+
+        let mut desktop = device();
+        transition(&mut desktop, 1, 50, 1);
+        let mut bundle = desktop.sync_bundle([0u8; 32]).unwrap();
+        bundle.channels[0].state.balances = vec![1_000];
+        assert_eq!(
+            phone.sync_from(&bundle),
+            Err(SyncError::Reconciliation(
+                ReconciliationError::InvalidEvidence([1u8; 32])
+            ))
+        );
+        assert_eq!(phone.channels[&[1u8; 32]].balances, vec![100]);
+    }
+
+    #[test]
+    fn test_decide_without_a_base_follows_nonces() {
+        let (a, b) = ([1u8; 32], [2u8; 32]);
+        assert_eq!(
+            decide(Holding::Open(1, a), Holding::Open(2, b), None),
+            SyncAction::Pull
+        );
+        assert_eq!(
+            decide(Holding::Open(2, a), Holding::Open(2, b), None),
+            SyncAction::Conflict
+        );
+        assert_eq!(
+            decide(Holding::Open(3, a), Holding::Open(2, b), Some(&b)),
+            SyncAction::Keep
+        );
+        assert_eq!(
+            decide(Holding::Closed, Holding::Open(2, b), Some(&a)),
+            SyncAction::Conflict
+        );
+        assert_eq!(
+            decide(Holding::Absent, Holding::Open(0, b), None),
+            SyncAction::Pull
+        );
+    }
+}
@@ -0,0 +1,294 @@
+// src/zkp/fee_ledger.rs
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+use crate::services::overpass_db::OverpassDB;
+use crate::zkp::helpers::Bytes32;
+
+pub const SECONDS_PER_DAY: u64 = 86_400;
+const LEDGER_PREFIX: &[u8] = b"fee_ledger:";
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum FeeLedgerError {
+    #[error("Report period must be positive")]
+    InvalidPeriod,
+    #[error("Storage error: {0}")]
+    StorageError(String),
+    #[error("Corrupt fee ledger: {0}")]
+    CorruptState(String),
+}
+
+/// What a fee was paid for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FeeKind {
+    /// Mining fees of funding, closing and sweep transactions.
+    OnChain,
+    /// Fees paid to intermediaries that forward a payment.
+    Routing,
+    /// The wallet's share of the transactions anchoring global roots.
+    Anchoring,
+}
+
+/// A fee the wallet paid, in satoshis.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeeEntry {
+    pub kind: FeeKind,
+    pub amount: u64,
+    /// Channel the fee was paid for; anchoring costs belong to the whole wallet.
+    pub channel_id: Option<Bytes32>,
+    pub counterparty: Option<Bytes32>,
+    /// Unix time in seconds.
+    pub timestamp: u64,
+    /// Transaction id, payment id or other reference to reconcile the fee against.
+    pub reference: Option<String>,
+}
+
+/// Fees summed by kind.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeeTotals {
+    pub on_chain: u64,
+    pub routing: u64,
+    pub anchoring: u64,
+}
+
+impl FeeTotals {
+    pub fn total(&self) -> u64 {
+        self.on_chain + self.routing + self.anchoring
+    }
+
+    fn add(&mut self, entry: &FeeEntry) {
+        let bucket = match entry.kind {
+            FeeKind::OnChain => &mut self.on_chain,
+            FeeKind::Routing => &mut self.routing,
+            FeeKind::Anchoring => &mut self.anchoring,
+        };
+        *bucket += entry.amount;
+    }
+}
+
+/// Every fee a wallet paid, oldest first.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeeLedger {
+    entries: Vec<FeeEntry>,
+}
+
+impl FeeLedger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, entry: FeeEntry) {
+        let at = self
+            .entries
+            .partition_point(|known| known.timestamp <= entry.timestamp);
+        self.entries.insert(at, entry);
+    }
+
+    pub fn entries(&self) -> &[FeeEntry] {
+        &self.entries
+    }
+
+    /// Gets the fees paid in `[from, to)`.
+    pub fn between(&self, from: u64, to: u64) -> impl Iterator<Item = &FeeEntry> {
+        self.entries
+            .iter()
+            .filter(move |entry| entry.timestamp >= from && entry.timestamp < to)
+    }
+
+    /// Sums the fees paid in `[from, to)`.
+    pub fn totals(&self, from: u64, to: u64) -> FeeTotals {
+        self.between(from, to)
+            .fold(FeeTotals::default(), |mut totals, entry| {
+                totals.add(entry);
+                totals
+            })
+    }
+
+    /// Sums the fees paid in `[from, to)` per period of `period` seconds, keyed by the
+    /// Unix time each period starts at. Periods without fees are left out.
+    pub fn totals_by_period(
+        &self,
+        from: u64,
+        to: u64,
+        period: u64,
+    ) -> Result<BTreeMap<u64, FeeTotals>, FeeLedgerError> {
+        if period == 0 {
+            return Err(FeeLedgerError::InvalidPeriod);
+        }
+        let mut totals = BTreeMap::<u64, FeeTotals>::new();
+        for entry in self.between(from, to) {
+            totals
+                .entry(entry.timestamp - entry.timestamp % period)
+                .or_default()
+                .add(entry);
+        }
+        Ok(totals)
+    }
+
+    /// Sums the fees paid in `[from, to)` per counterparty, leaving out fees that belong
+    /// to no counterparty.
+    pub fn totals_by_counterparty(&self, from: u64, to: u64) -> BTreeMap<Bytes32, FeeTotals> {
+        let mut totals = BTreeMap::<Bytes32, FeeTotals>::new();
+        for entry in self.between(from, to) {
+            if let Some(counterparty) = entry.counterparty {
+                totals.entry(counterparty).or_default().add(entry);
+            }
+        }
+        totals
+    }
+
+    /// Sums the fees paid for each channel over the ledger's lifetime.
+    pub fn totals_by_channel(&self) -> BTreeMap<Bytes32, FeeTotals> {
+        let mut totals = BTreeMap::<Bytes32, FeeTotals>::new();
+        for entry in &self.entries {
+            if let Some(channel_id) = entry.channel_id {
+                totals.entry(channel_id).or_default().add(entry);
+            }
+        }
+        totals
+    }
+
+    /// Writes the ledger to `db` under `wallet_id`.
+    pub fn save(&self, db: &OverpassDB, wallet_id: &Bytes32) -> Result<(), FeeLedgerError> {
+        let bytes =
+            bincode::serialize(self).map_err(|e| FeeLedgerError::StorageError(e.to_string()))?;
+        db.put(&ledger_key(wallet_id), &bytes)
+            .and_then(|_| db.flush())
+            .map_err(|e| FeeLedgerError::StorageError(e.to_string()))
+    }
+
+    /// Loads the ledger saved for `wallet_id`, or an empty one if nothing was saved.
+    pub fn restore(db: &OverpassDB, wallet_id: &Bytes32) -> Result<Self, FeeLedgerError> {
+        match db
+            .get(&ledger_key(wallet_id))
+            .map_err(|e| FeeLedgerError::StorageError(e.to_string()))?
+        {
+            Some(bytes) => bincode::deserialize(&bytes)
+                .map_err(|e| FeeLedgerError::CorruptState(e.to_string())),
+            None => Ok(Self::default()),
+        }
+    }
+}
+
+fn ledger_key(wallet_id: &Bytes32) -> Vec<u8> {
+    let mut key = LEDGER_PREFIX.to_vec();
+    key.extend_from_slice(hex::encode(wallet_id).as_bytes());
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+    use crate::zkp::wallet_contract::WalletContractError;
+
+    const SHOP: Bytes32 = [7u8; 32];
+
+    fn entry(
+        kind: FeeKind,
+        amount: u64,
+        counterparty: Option<Bytes32>,
+        timestamp: u64,
+    ) -> FeeEntry {
+        FeeEntry {
+            kind,
+            amount,
+            channel_id: counterparty.map(|_| [1u8; 32]),
+            counterparty,
+            timestamp,
+            reference: None,
+        }
+    }
+
+    #[test]
+    fn test_reports_by_period_and_counterparty() -> anyhow::Result<()> {
+        let mut ledger = FeeLedger::new();
+        ledger.record(entry(FeeKind::Routing, 3, Some(SHOP), SECONDS_PER_DAY + 5));
+        ledger.record(entry(FeeKind::OnChain, 200, Some(SHOP), 10));
+        ledger.record(entry(FeeKind::Anchoring, 40, None, SECONDS_PER_DAY + 1));
+        ledger.record(entry(
+            FeeKind::Routing,
+            1,
+            Some([8u8; 32]),
+            3 * SECONDS_PER_DAY,
+        ));
+        assert!(ledger
+            .entries()
+            .windows(2)
+            .all(|w| w[0].timestamp <= w[1].timestamp));
+
+        let days = ledger.totals_by_period(0, 3 * SECONDS_PER_DAY, SECONDS_PER_DAY)?;
+        assert_eq!(days.len(), 2);
+        assert_eq!(days[&0].on_chain, 200);
+        assert_eq!(
+            days[&SECONDS_PER_DAY],
+            FeeTotals {
+                on_chain: 0,
+                routing: 3,
+                anchoring: 40
+            }
+        );
+        assert_eq!(ledger.totals(0, u64::MAX).total(), 244);
+
+        let peers = ledger.totals_by_counterparty(0, u64::MAX);
+        assert_eq!(peers[&SHOP].total(), 203);
+        assert_eq!(peers[&[8u8; 32]].routing, 1);
+        assert_eq!(ledger.totals_by_channel()[&[1u8; 32]].total(), 204);
+        assert_eq!(
+            ledger.totals_by_period(0, 1, 0),
+            Err(FeeLedgerError::InvalidPeriod)
+        );
+
+        let path = std::env::temp_dir().join(format!("fee_ledger_{}", std::process::id()));
+        let db = OverpassDB::new(path.to_str().unwrap())?;
+        ledger.save(&db, &[1u8; 32])?;
+        assert_eq!(FeeLedger::restore(&db, &[1u8; 32])?, ledger);
+        assert_eq!(FeeLedger::restore(&db, &[2u8; 32])?, FeeLedger::new());
+        drop(db);
+        let _ = std::fs::remove_dir_all(path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_wallet_records_channel_fees() -> Result<(), WalletContractError> {
+        let mut wallet = test_util::unkeyed_wallet([1u8; 32]);
+        wallet.register_channel([1u8; 32], 100, SHOP, Vec::new())?;
+        wallet.record_fee(
+            FeeKind::OnChain,
+            Some([1u8; 32]),
+            150,
+            Some("funding".into()),
+        )?;
+        wallet.record_fee(FeeKind::Anchoring, None, 20, None)?;
+        assert!(matches!(
+            wallet.record_fee(FeeKind::OnChain, Some([9u8; 32]), 1, None),
+            Err(WalletContractError::ChannelNotFound)
+        ));
+
+        let payment = wallet.pay(SHOP, 60).with_routing_fee(2).send()?;
+        assert_eq!(payment.routing_fee, 2);
+        assert_eq!(wallet.get_channel(&[1u8; 32]).unwrap().balances, vec![38]);
+        assert!(matches!(
+            wallet.pay(SHOP, 37).with_routing_fee(2).send(),
+            Err(WalletContractError::NoPaymentChannel { amount: 39, .. })
+        ));
+
+        let totals = wallet.fees().totals(0, u64::MAX);
+        assert_eq!(
+            (totals.on_chain, totals.routing, totals.anchoring),
+            (150, 2, 20)
+        );
+        let by_peer = wallet.fees().totals_by_counterparty(0, u64::MAX);
+        assert_eq!(by_peer[&SHOP].total(), 152);
+        let routed = wallet
+            .fees()
+            .entries()
+            .iter()
+            .find(|entry| entry.kind == FeeKind::Routing)
+            .unwrap();
+        assert_eq!(routed.reference, Some(hex::encode(payment.id)));
+        Ok(())
+    }
+}
@@ -0,0 +1,252 @@
+// src/zkp/fraud_proof.rs
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::zkp::helpers::Bytes32;
+use crate::zkp::state_proof::StateProof;
+
+/// Number of blocks a submission stays open to challenge by default, about one day.
+pub const DEFAULT_CHALLENGE_WINDOW: u32 = 144;
+
+/// A wallet root applied optimistically and still open to challenge.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RootSubmission {
+    pub id: u64,
+    pub wallet_id: Bytes32,
+    /// Root the wallet had before the submission, restored if it is reverted.
+    pub previous_root: Bytes32,
+    pub root: Bytes32,
+    pub proof: StateProof,
+    /// Height at which the submission was accepted, and the epoch that was open then.
+    pub submitted_at: u32,
+    pub epoch: u64,
+}
+
+impl RootSubmission {
+    /// Last height at which the submission can still be challenged.
+    pub fn challenge_deadline(&self, window: u32) -> u32 {
+        self.submitted_at.saturating_add(window)
+    }
+}
+
+/// Evidence that a submission should not stand.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum FraudProof {
+    /// A valid proof moving the wallet from the same previous root to a different root,
+    /// generated after the submitted one, so the submitter published a stale state.
+    NewerState { proof: StateProof },
+    /// The submission's own proof does not bind the roots it claims to move between.
+    InvalidTransition,
+}
+
+impl FraudProof {
+    /// Checks the fraud proof against a submission.
+    pub fn verify(&self, submission: &RootSubmission) -> bool {
+        match self {
+            FraudProof::NewerState { proof } => {
+                let Some(root) = proof.public_inputs.get(1) else {
+                    return false;
+                };
+                proof.timestamp > submission.proof.timestamp
+                    && *root != submission.root
+                    && proof_binds(proof, &submission.previous_root, root)
+            }
+            FraudProof::InvalidTransition => {
+                !proof_binds(&submission.proof, &submission.previous_root, &submission.root)
+            }
+        }
+    }
+}
+
+/// A bond forfeited because its submission was proven fraudulent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Slash {
+    pub submission_id: u64,
+    /// Party that posted the bond.
+    pub offender: Bytes32,
+    /// Party whose fraud proof was accepted, credited with the bond.
+    pub challenger: Bytes32,
+    pub amount: u64,
+}
+
+/// Bonded balances, free per party and locked per open submission.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BondLedger {
+    available: HashMap<Bytes32, u64>,
+    locked: HashMap<u64, (Bytes32, u64)>,
+}
+
+impl BondLedger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn deposit(&mut self, owner: Bytes32, amount: u64) {
+        let balance = self.available.entry(owner).or_default();
+        *balance = balance.saturating_add(amount);
+    }
+
+    /// Takes `amount` out of the owner's free balance, or returns `false` if it is short.
+    pub fn withdraw(&mut self, owner: &Bytes32, amount: u64) -> bool {
+        match self.available.get_mut(owner) {
+            Some(balance) if *balance >= amount => {
+                *balance -= amount;
+                true
+            }
+            _ => amount == 0,
+        }
+    }
+
+    /// Gets the owner's free balance.
+    pub fn available(&self, owner: &Bytes32) -> u64 {
+        self.available.get(owner).copied().unwrap_or(0)
+    }
+
+    /// Gets the bond locked behind a submission.
+    pub fn locked(&self, submission_id: u64) -> Option<u64> {
+        self.locked.get(&submission_id).map(|(_, amount)| *amount)
+    }
+
+    /// Moves `amount` of the owner's free balance behind a submission.
+    pub fn lock(&mut self, submission_id: u64, owner: Bytes32, amount: u64) -> bool {
+        if !self.withdraw(&owner, amount) {
+            return false;
+        }
+        self.locked.insert(submission_id, (owner, amount));
+        true
+    }
+
+    /// Returns a submission's bond to its owner once the submission is final.
+    pub fn release(&mut self, submission_id: u64) -> u64 {
+        let Some((owner, amount)) = self.locked.remove(&submission_id) else {
+            return 0;
+        };
+        self.deposit(owner, amount);
+        amount
+    }
+
+    /// Forfeits a submission's bond to the challenger who proved it fraudulent.
+    pub fn slash(&mut self, submission_id: u64, challenger: Bytes32) -> Option<Slash> {
+        let (offender, amount) = self.locked.remove(&submission_id)?;
+        self.deposit(challenger, amount);
+        Some(Slash {
+            submission_id,
+            offender,
+            challenger,
+            amount,
+        })
+    }
+}
+
+/// Checks that `proof` was generated for the transition from `old_root` to `new_root`.
+///
+/// This recomputes the hash `generate_state_proof` commits to and ignores the proof's
+/// age, since a fraud proof may legitimately be checked well after the state was signed.
+pub fn proof_binds(proof: &StateProof, old_root: &Bytes32, new_root: &Bytes32) -> bool {
+    overpass_verify::proof::proof_binds(
+        &proof.pi,
+        &proof.public_inputs,
+        proof.timestamp,
+        old_root,
+        new_root,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zkp::helpers::{convert_helper_proof, generate_state_proof};
+    use crate::zkp::pedersen_parameters::PedersenParameters;
+    use sha2::{Digest, Sha256};
+
+    fn proof_at(old: Bytes32, new: Bytes32, timestamp: u64) -> StateProof {
+        let pi = Sha256::new()
+            .chain_update(old)
+            .chain_update(new)
+            .chain_update([0u8; 32])
+            .chain_update(timestamp.to_le_bytes())
+            .finalize()
+            .into();
+        StateProof {
+            pi,
+            public_inputs: vec![old, new, [0u8; 32]],
+            timestamp,
+        }
+    }
+
+    fn submission(proof: StateProof) -> RootSubmission {
+        RootSubmission {
+            id: 0,
+            wallet_id: [1u8; 32],
+            previous_root: proof.public_inputs[0],
+            root: proof.public_inputs[1],
+            proof,
+            submitted_at: 100,
+            epoch: 0,
+        }
+    }
+
+    #[test]
+    fn test_generated_proofs_bind_their_roots() {
+        let proof = convert_helper_proof(generate_state_proof(
+            [1u8; 32],
+            [2u8; 32],
+            [0u8; 32],
+            &PedersenParameters::default(),
+        ));
+        assert!(proof_binds(&proof, &[1u8; 32], &[2u8; 32]));
+        assert!(!proof_binds(&proof, &[1u8; 32], &[3u8; 32]));
+    }
+
+    #[test]
+    fn test_fraud_proofs() {
+        let honest = submission(proof_at([1u8; 32], [2u8; 32], 1_000));
+        assert!(!FraudProof::InvalidTransition.verify(&honest));
+        assert_eq!(honest.challenge_deadline(DEFAULT_CHALLENGE_WINDOW), 244);
+
+        let mut forged = honest.clone();
+        forged.root = [3u8; 32];
+        assert!(FraudProof::InvalidTransition.verify(&forged));
+
+        let newer = |old, new, timestamp| FraudProof::NewerState {
+            proof: proof_at(old, new, timestamp),
+        };
+        assert!(newer([1u8; 32], [4u8; 32], 1_001).verify(&honest));
+        assert!(!newer([1u8; 32], [4u8; 32], 999).verify(&honest));
+        assert!(!newer([1u8; 32], [2u8; 32], 1_001).verify(&honest));
+        assert!(!newer([5u8; 32], [4u8; 32], 1_001).verify(&honest));
+
+        let mut unbound = proof_at([1u8; 32], [4u8; 32], 1_001);
+        unbound.pi = [0u8; 32];
+        assert!(!FraudProof::NewerState { proof: unbound }.verify(&honest));
+    }
+
+    #[test]
+    fn test_bonds_are_released_or_slashed() {
+        let (operator, watcher) = ([1u8; 32], [2u8; 32]);
+        let mut ledger = BondLedger::new();
+        ledger.deposit(operator, 100);
+        assert!(!ledger.lock(0, operator, 101));
+        assert!(ledger.lock(0, operator, 60));
+        assert!(ledger.lock(1, operator, 40));
+        assert_eq!(ledger.available(&operator), 0);
+
+        assert_eq!(ledger.release(0), 60);
+        assert_eq!(ledger.release(0), 0);
+        assert_eq!(
+            ledger.slash(1, watcher),
+            Some(Slash {
+                submission_id: 1,
+                offender: operator,
+                challenger: watcher,
+                amount: 40,
+            })
+        );
+        assert_eq!(ledger.slash(1, watcher), None);
+        assert_eq!(ledger.available(&operator), 60);
+        assert_eq!(ledger.available(&watcher), 40);
+        assert!(ledger.withdraw(&watcher, 40));
+        assert!(!ledger.withdraw(&watcher, 1));
+    }
+}
@@ -1,14 +1,32 @@
 // src/zkp/global_root_contract.rs
 
-use anyhow::Result;
-use crate::zkp::helpers::{compute_global_root, verify_wallet_proof, Bytes32};
-use crate::zkp::pedersen_parameters::{PedersenParameters, SerdePedersenParameters};
-use std::collections::HashMap;
+use bitcoin::secp256k1::Secp256k1;
+use bitcoin::Txid;
+use crate::clock::{self, SharedClock, TimePolicy};
+use crate::zkp::helpers::{
+    compute_merkle_root, merkle_path, verify_merkle_path, verify_wallet_proof_at, Bytes32,
+};
+use crate::zkp::pedersen_parameters::{PedersenError, PedersenParameters, SerdePedersenParameters};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
 use thiserror::Error;
+#[cfg(any(test, feature = "tokio"))]
+use tokio::sync::broadcast;
 
+use crate::services::overpass_db::OverpassDB;
+
+use super::channel::ChannelState;
+use super::circuit_breaker::{Halt, HaltReason, InvariantViolation, ResumeAuthorization};
+use super::fraud_proof::{BondLedger, FraudProof, RootSubmission, Slash};
+use super::governance::{ContractParameters, GovernanceError, ParameterSchedule, ParameterUpgrade};
 use super::helpers;
+use super::mmr::{MerkleMountainRange, MmrProof};
+use super::operator_keys::{EpochAttestation, KeyRotation, OperatorKeyError, OperatorKeySchedule};
 use super::state_proof::{self, StateProof};
 use super::tree::{MerkleTree, MerkleTreeError};
+use super::wallet_contract::{ChannelInclusionProof, WalletContract};
+use super::wallet_root_proof::WalletRootProof;
 
 /// Represents errors in GlobalRootContract operations.
 #[derive(Error, Debug)]
@@ -34,13 +52,320 @@ pub enum GlobalRootContractError {
     #[error("Serialization error: {0}")]
     SerializationError(#[from] serde_json::Error),
     
-    #[error("Computation error: {0}")]
-    ComputationError(String),
+    #[error("Pedersen parameters: {0}")]
+    PedersenError(#[from] PedersenError),
+
+    #[error("Submission not found or already final")]
+    SubmissionNotFound,
+
+    #[error("Wallet already has a submission under challenge")]
+    SubmissionPending,
+
+    #[error("Challenge window closed at height {0}")]
+    ChallengeWindowClosed(u32),
+
+    #[error("Fraud proof does not apply to the submission")]
+    InvalidFraudProof,
+
+    #[error("Submission needs a bond of {required} but only {available} is free")]
+    InsufficientBond { required: u64, available: u64 },
+
+    #[error("Wallet root differs from the aggregated root; submit it with a proof")]
+    WalletRootMismatch,
+
+    #[error("Epoch {0} has not been sealed")]
+    EpochNotSealed(u64),
+
+    #[error("Contract has no operator keys")]
+    NoOperatorKeys,
+
+    #[error("Operator key error: {0}")]
+    OperatorKeyError(#[from] OperatorKeyError),
+
+    #[error("Governance error: {0}")]
+    GovernanceError(#[from] GovernanceError),
+
+    #[error("Epoch {0} is not after the latest checkpoint")]
+    NotAfterCheckpoint(u64),
+
+    #[error("Epoch {0} still has submissions open to challenge")]
+    EpochUnderChallenge(u64),
+
+    #[error("Anchored root does not match the root sealed at epoch {0}")]
+    AnchorMismatch(u64),
+
+    #[error("History of epoch {0} was pruned at a checkpoint")]
+    EpochPruned(u64),
+
+    #[error("Contract is halted; new wallets and submissions are frozen")]
+    Halted,
+
+    #[error("Contract is not halted")]
+    NotHalted,
+
+    #[error("{} invariants are still violated", .0.len())]
+    InvariantsViolated(Vec<InvariantViolation>),
+
+    #[error("Storage error: {0}")]
+    StorageError(String),
+
+    #[error("Persisted state is inconsistent: {0}")]
+    CorruptState(String),
+}
+
+/// A wallet root change accepted during an epoch.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EpochUpdate {
+    pub wallet_id: Bytes32,
+    /// All zeros when the wallet was registered in this epoch.
+    pub old_root: Bytes32,
+    pub new_root: Bytes32,
+    /// The `pi` of the proof that justified the update; all zeros for registrations.
+    pub proof: Bytes32,
+}
+
+/// The root and update digest of a closed epoch.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SealedEpoch {
+    pub epoch: u64,
+    pub previous_root: Bytes32,
+    pub root: Bytes32,
+    pub updates: Vec<EpochUpdate>,
+    /// Hash binding the epoch, both roots and every update in order.
+    pub digest: Bytes32,
+}
+
+impl SealedEpoch {
+    /// Checks that the digest covers exactly this epoch's roots and updates.
+    ///
+    /// The digest only commits to what was sealed; use [`SealedEpoch::replay`] to check
+    /// that the updates actually lead from `previous_root` to `root`.
+    pub fn matches_digest(&self) -> bool {
+        self.digest == epoch_digest(self.epoch, &self.previous_root, &self.root, &self.updates)
+    }
+
+    /// Replays the updates over `previous_wallets`, the `(wallet_id, root)` pairs the epoch
+    /// started from, and checks that they hash to `previous_root` and end at `root`.
+    ///
+    /// Updates keep only the `pi` of the proof that justified them, so the proofs are not
+    /// checked again here; the contract verified each one when it accepted the update.
+    pub fn replay(&self, previous_wallets: &[(Bytes32, Bytes32)]) -> bool {
+        let mut wallets: BTreeMap<Bytes32, Bytes32> = previous_wallets.iter().copied().collect();
+        let start: Vec<_> = wallets.iter().map(|(id, root)| (*id, *root)).collect();
+        if compute_merkle_root(global_leaves(&start)) != self.previous_root {
+            return false;
+        }
+        for update in &self.updates {
+            if wallets.get(&update.wallet_id).copied().unwrap_or([0u8; 32]) != update.old_root {
+                return false;
+            }
+            wallets.insert(update.wallet_id, update.new_root);
+        }
+        let end: Vec<_> = wallets.into_iter().collect();
+        self.matches_digest() && compute_merkle_root(global_leaves(&end)) == self.root
+    }
+
+    /// Checks that this epoch directly follows `previous`.
+    pub fn follows(&self, previous: &SealedEpoch) -> bool {
+        self.epoch == previous.epoch + 1 && self.previous_root == previous.root
+    }
+}
+
+/// Folds an epoch's roots and updates into a single hash, so a verifier compares one value
+/// per epoch however many updates it contains.
+pub fn epoch_digest(
+    epoch: u64,
+    previous_root: &Bytes32,
+    root: &Bytes32,
+    updates: &[EpochUpdate],
+) -> Bytes32 {
+    let mut hasher = Sha256::new();
+    hasher.update(b"overpass/epoch/v1");
+    hasher.update(epoch.to_le_bytes());
+    hasher.update(previous_root);
+    hasher.update(root);
+    hasher.update((updates.len() as u64).to_le_bytes());
+    for update in updates {
+        hasher.update(update.wallet_id);
+        hasher.update(update.old_root);
+        hasher.update(update.new_root);
+        hasher.update(update.proof);
+    }
+    hasher.finalize().into()
+}
+
+/// Gets the root history leaf recording that `root` was sealed as `epoch`.
+pub fn epoch_leaf(epoch: u64, root: &Bytes32) -> Bytes32 {
+    Sha256::new()
+        .chain_update(b"overpass/history/epoch")
+        .chain_update(epoch.to_le_bytes())
+        .chain_update(root)
+        .finalize()
+        .into()
+}
+
+/// Proof that `root` was the global root sealed at `epoch`, checked against the
+/// contract's history root without replaying any epochs.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EpochRootProof {
+    pub epoch: u64,
+    pub root: Bytes32,
+    pub proof: MmrProof,
+}
+
+impl EpochRootProof {
+    pub fn verify(&self, history_root: &Bytes32) -> bool {
+        self.proof.leaf_index == self.epoch
+            && self
+                .proof
+                .verify(&epoch_leaf(self.epoch, &self.root), history_root)
+    }
+}
+
+/// Gets the global tree leaf for a wallet, binding the root to the wallet that owns it.
+pub fn wallet_leaf(wallet_id: &Bytes32, wallet_root: &Bytes32) -> Bytes32 {
+    Sha256::new()
+        .chain_update(b"overpass/global/wallet")
+        .chain_update(wallet_id)
+        .chain_update(wallet_root)
+        .finalize()
+        .into()
+}
+
+/// Proof that a wallet's root is a leaf of a global root.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WalletInclusionProof {
+    pub wallet_id: Bytes32,
+    pub wallet_root: Bytes32,
+    /// Position of the wallet's leaf; leaves are ordered by wallet id.
+    pub index: u64,
+    /// Sibling hashes from the leaf up to the root.
+    pub siblings: Vec<Bytes32>,
+}
+
+impl WalletInclusionProof {
+    /// Checks that the proof leads from the wallet's leaf to `global_root`.
+    pub fn verify(&self, global_root: &Bytes32) -> bool {
+        verify_merkle_path(
+            wallet_leaf(&self.wallet_id, &self.wallet_root),
+            self.index,
+            &self.siblings,
+            global_root,
+        )
+    }
+}
+
+/// One entry of the append-only log of global roots.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RootRecord {
+    /// Position in the log, starting at zero.
+    pub sequence: u64,
+    /// Epoch that was open when the root was computed.
+    pub epoch: u64,
+    pub root: Bytes32,
+}
+
+/// A sealed epoch whose root survived its challenge window and was anchored on-chain.
+///
+/// Nothing before a checkpoint can be reverted, so history older than it may be pruned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub epoch: u64,
+    pub root: Bytes32,
+    pub anchor_txid: Txid,
+}
+
+/// What `prune_to_checkpoint` dropped.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PruneSummary {
+    pub root_records: usize,
+    pub snapshots: usize,
+    pub epoch_updates: usize,
+}
+
+/// Events subscribers can buffer before the oldest are dropped.
+pub const EVENT_CAPACITY: usize = 256;
+
+/// How a challenged submission ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChallengeOutcome {
+    /// A fraud proof was accepted and the wallet root restored.
+    Reverted,
+    /// The window passed without a valid challenge.
+    Finalized,
+}
+
+/// A change to the contract that wallets and watch services can react to.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GlobalRootEvent {
+    RootUpdated(RootRecord),
+    EpochSealed(SealedEpoch),
+    /// A submission was applied and can be challenged until `deadline`.
+    ChallengeOpened {
+        submission_id: u64,
+        wallet_id: Bytes32,
+        root: Bytes32,
+        deadline: u32,
+    },
+    ChallengeResolved {
+        submission_id: u64,
+        wallet_id: Bytes32,
+        outcome: ChallengeOutcome,
+    },
+    Halted(Halt),
+    Resumed,
+    /// A parameter upgrade took effect at the start of the open epoch.
+    ParametersActivated(ContractParameters),
+    /// A sealed epoch's root was confirmed on-chain by `anchor_txid`. It is final once
+    /// checkpointed.
+    Anchored {
+        epoch: u64,
+        root: Bytes32,
+        anchor_txid: Txid,
+    },
+    /// A sealed epoch's root was anchored on-chain and became final.
+    Checkpointed(Checkpoint),
+}
+
+/// Proof that a channel state was part of the global root sealed at an epoch.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoricalChannelProof {
+    pub epoch: u64,
+    /// Channel state hash up to the wallet root.
+    pub channel: ChannelInclusionProof,
+    /// Wallet root up to the epoch root.
+    pub wallet: WalletInclusionProof,
+}
+
+impl HistoricalChannelProof {
+    /// Checks that `state` hashes to the proven leaf and the path reaches `epoch_root`.
+    pub fn verify(&self, state: &ChannelState, epoch_root: &Bytes32) -> bool {
+        state
+            .hash()
+            .is_ok_and(|hash| hash == self.channel.channel_hash)
+            && self.channel.verify(&self.wallet.wallet_root)
+            && self.wallet.verify(epoch_root)
+    }
+}
+
+fn global_leaves(wallets: &[(Bytes32, Bytes32)]) -> Vec<Bytes32> {
+    wallets.iter().map(|(id, root)| wallet_leaf(id, root)).collect()
 }
 
-impl From<anyhow::Error> for GlobalRootContractError {
-    fn from(err: anyhow::Error) -> Self {
-        GlobalRootContractError::ComputationError(err.to_string())
+/// Builds the inclusion proof of a wallet among `wallets`, which are sorted by wallet id.
+fn prove_in(
+    wallets: &[(Bytes32, Bytes32)],
+    wallet_id: &Bytes32,
+    wallet_root: Bytes32,
+) -> WalletInclusionProof {
+    let index = wallets
+        .binary_search_by_key(wallet_id, |(id, _)| *id)
+        .expect("wallet is among the sorted wallets");
+    WalletInclusionProof {
+        wallet_id: *wallet_id,
+        wallet_root,
+        index: index as u64,
+        siblings: merkle_path(global_leaves(wallets), index),
     }
 }
 
@@ -51,6 +376,65 @@ pub struct GlobalRootContract {
     params: PedersenParameters,
     merkle_root: Bytes32,
     merkle_tree: MerkleTree,
+    /// Number of the open epoch and the updates accepted in it so far.
+    epoch: u64,
+    epoch_updates: Vec<EpochUpdate>,
+    /// Global root when the open epoch started.
+    epoch_start_root: Bytes32,
+    sealed_epochs: Vec<SealedEpoch>,
+    /// Every global root in order, and each sealed epoch's `(wallet_id, root)` pairs so
+    /// inclusion can be proven against old roots.
+    root_history: Vec<RootRecord>,
+    epoch_snapshots: Vec<Vec<(Bytes32, Bytes32)>>,
+    /// Sealed epoch roots, one leaf per epoch, for light client proofs.
+    epoch_mmr: MerkleMountainRange,
+    /// Keys that attest sealed epochs, and their attestations by epoch.
+    operator_keys: Option<OperatorKeySchedule>,
+    attestations: HashMap<u64, EpochAttestation>,
+    #[cfg(any(test, feature = "tokio"))]
+    events: broadcast::Sender<GlobalRootEvent>,
+    /// Challenge window, bond size and the other tunables, by epoch.
+    parameters: ParameterSchedule,
+    /// Submissions still open to challenge, and the bonds locked behind them.
+    submissions: Vec<RootSubmission>,
+    next_submission_id: u64,
+    bonds: BondLedger,
+    /// Latest finalized checkpoint, and the first epoch whose history is still kept.
+    checkpoint: Option<Checkpoint>,
+    pruned_epochs: u64,
+    /// Set while new wallets and submissions are frozen.
+    halt: Option<Halt>,
+    /// Time wallet proofs are checked against, and how far from it they may be.
+    clock: SharedClock,
+    time_policy: TimePolicy,
+}
+
+/// Key under which the contract's state is stored.
+pub const STATE_KEY: &[u8] = b"global_root:state";
+
+/// Everything needed to rebuild a contract after a restart. The event channel and
+/// Pedersen parameters are supplied again on restore.
+#[derive(Serialize, Deserialize)]
+struct ContractState {
+    wallet_roots: HashMap<Bytes32, Bytes32>,
+    latest_proofs: HashMap<Bytes32, StateProof>,
+    merkle_root: Bytes32,
+    tree_leaves: Vec<Bytes32>,
+    epoch: u64,
+    epoch_updates: Vec<EpochUpdate>,
+    epoch_start_root: Bytes32,
+    sealed_epochs: Vec<SealedEpoch>,
+    root_history: Vec<RootRecord>,
+    epoch_snapshots: Vec<Vec<(Bytes32, Bytes32)>>,
+    operator_keys: Option<OperatorKeySchedule>,
+    attestations: HashMap<u64, EpochAttestation>,
+    parameters: ParameterSchedule,
+    submissions: Vec<RootSubmission>,
+    next_submission_id: u64,
+    bonds: BondLedger,
+    checkpoint: Option<Checkpoint>,
+    pruned_epochs: u64,
+    halt: Option<Halt>,
 }
 
 impl GlobalRootContract {
@@ -64,9 +448,126 @@ impl GlobalRootContract {
             params,
             merkle_root,
             merkle_tree,
+            epoch: 0,
+            epoch_updates: Vec::new(),
+            epoch_start_root: merkle_root,
+            sealed_epochs: Vec::new(),
+            root_history: Vec::new(),
+            epoch_snapshots: Vec::new(),
+            epoch_mmr: MerkleMountainRange::new(),
+            operator_keys: None,
+            attestations: HashMap::new(),
+            #[cfg(any(test, feature = "tokio"))]
+            events: broadcast::channel(EVENT_CAPACITY).0,
+            parameters: ParameterSchedule::default(),
+            submissions: Vec::new(),
+            next_submission_id: 0,
+            bonds: BondLedger::new(),
+            checkpoint: None,
+            pruned_epochs: 0,
+            halt: None,
+            clock: clock::system(),
+            time_policy: TimePolicy::default(),
         }
     }
 
+    /// Checks wallet proof timestamps against `clock` instead of the system clock.
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.set_clock(clock);
+        self
+    }
+
+    pub fn set_clock(&mut self, clock: SharedClock) {
+        self.clock = clock;
+    }
+
+    /// Replaces how old, or how far ahead, a wallet proof's timestamp may be.
+    pub fn with_time_policy(mut self, policy: TimePolicy) -> Self {
+        self.time_policy = policy;
+        self
+    }
+
+    /// Sets how many blocks a submission stays open to challenge.
+    pub fn with_challenge_window(self, blocks: u32) -> Self {
+        let parameters = ContractParameters {
+            challenge_window: blocks,
+            ..*self.parameters.current()
+        };
+        self.with_parameters(ParameterSchedule::new(parameters))
+    }
+
+    /// Requires every submission to lock `amount` from its wallet's bond.
+    pub fn with_submission_bond(self, amount: u64) -> Self {
+        let parameters = ContractParameters {
+            submission_bond: amount,
+            ..*self.parameters.current()
+        };
+        self.with_parameters(ParameterSchedule::new(parameters))
+    }
+
+    /// Replaces the parameter schedule, e.g. to change the upgrade delay.
+    pub fn with_parameters(mut self, schedule: ParameterSchedule) -> Self {
+        self.parameters = schedule;
+        self
+    }
+
+    /// Gets the parameters in force for the open epoch.
+    pub fn parameters(&self) -> &ContractParameters {
+        self.parameters.current()
+    }
+
+    pub fn parameter_schedule(&self) -> &ParameterSchedule {
+        &self.parameters
+    }
+
+    /// Announces a parameter upgrade, which takes effect when its activation epoch opens.
+    ///
+    /// With operator keys set, the keys active at `height` must have signed it.
+    pub fn announce_parameter_upgrade(
+        &mut self,
+        upgrade: ParameterUpgrade,
+        height: u32,
+    ) -> Result<(), GlobalRootContractError> {
+        if let Some(schedule) = &self.operator_keys {
+            let message = ParameterUpgrade::message(&upgrade.parameters, upgrade.activation_epoch);
+            let keys = schedule.keys_at(height);
+            if !keys.verify(&Secp256k1::verification_only(), &message, &upgrade.signatures) {
+                return Err(OperatorKeyError::Unauthorized.into());
+            }
+        }
+        Ok(self.parameters.announce(upgrade, self.epoch)?)
+    }
+
+    /// Gets the challenge window a submission was accepted under.
+    fn challenge_window_for(&self, submission: &RootSubmission) -> u32 {
+        self.parameters.at_epoch(submission.epoch).challenge_window
+    }
+
+    /// Adds to a party's free bond.
+    pub fn deposit_bond(&mut self, owner: Bytes32, amount: u64) {
+        self.bonds.deposit(owner, amount);
+    }
+
+    /// Withdraws from a party's free bond; bonds behind open submissions stay locked.
+    pub fn withdraw_bond(
+        &mut self,
+        owner: &Bytes32,
+        amount: u64,
+    ) -> Result<(), GlobalRootContractError> {
+        if !self.bonds.withdraw(owner, amount) {
+            return Err(GlobalRootContractError::InsufficientBond {
+                required: amount,
+                available: self.bonds.available(owner),
+            });
+        }
+        Ok(())
+    }
+
+    /// Gets the bond ledger.
+    pub fn bonds(&self) -> &BondLedger {
+        &self.bonds
+    }
+
     /// Saves PedersenParameters to a file in serialized form.
     pub fn save_pedersen_parameters_to_file(
         params: PedersenParameters,
@@ -87,7 +588,100 @@ impl GlobalRootContract {
             .map_err(GlobalRootContractError::from)?;
         let serde_params: SerdePedersenParameters = serde_json::from_str(&serialized)
             .map_err(GlobalRootContractError::from)?;
-        Ok(serde_params.into())
+        Ok(PedersenParameters::try_from(serde_params)?)
+    }
+
+    /// Writes the contract's state to `db` and flushes it.
+    ///
+    /// The state is stored under a single key, so a crash mid-save leaves the previous
+    /// state intact. Call this after every change that must survive a restart.
+    pub fn save(&self, db: &OverpassDB) -> Result<(), GlobalRootContractError> {
+        let state = ContractState {
+            wallet_roots: self.wallet_roots.clone(),
+            latest_proofs: self.latest_proofs.clone(),
+            merkle_root: self.merkle_root,
+            tree_leaves: self.merkle_tree.leaves.clone(),
+            epoch: self.epoch,
+            epoch_updates: self.epoch_updates.clone(),
+            epoch_start_root: self.epoch_start_root,
+            sealed_epochs: self.sealed_epochs.clone(),
+            root_history: self.root_history.clone(),
+            epoch_snapshots: self.epoch_snapshots.clone(),
+            operator_keys: self.operator_keys.clone(),
+            attestations: self.attestations.clone(),
+            parameters: self.parameters.clone(),
+            submissions: self.submissions.clone(),
+            next_submission_id: self.next_submission_id,
+            bonds: self.bonds.clone(),
+            checkpoint: self.checkpoint,
+            pruned_epochs: self.pruned_epochs,
+            halt: self.halt.clone(),
+        };
+        let bytes = bincode::serialize(&state)
+            .map_err(|e| GlobalRootContractError::StorageError(e.to_string()))?;
+        db.put(STATE_KEY, &bytes)
+            .and_then(|_| db.flush())
+            .map_err(|e| GlobalRootContractError::StorageError(e.to_string()))
+    }
+
+    /// Rebuilds the contract saved in `db`, or returns `None` if nothing was saved.
+    ///
+    /// The wallet roots are rehashed and checked against the saved global root, so a
+    /// state that does not add up is refused rather than served.
+    pub fn restore(
+        db: &OverpassDB,
+        params: PedersenParameters,
+    ) -> Result<Option<Self>, GlobalRootContractError> {
+        let Some(bytes) = db
+            .get(STATE_KEY)
+            .map_err(|e| GlobalRootContractError::StorageError(e.to_string()))?
+        else {
+            return Ok(None);
+        };
+        let state: ContractState = bincode::deserialize(&bytes)
+            .map_err(|e| GlobalRootContractError::CorruptState(e.to_string()))?;
+
+        let mut merkle_tree = MerkleTree::new();
+        for leaf in state.tree_leaves {
+            merkle_tree.insert(leaf)?;
+        }
+        let mut contract = Self::new(params);
+        contract.wallet_roots = state.wallet_roots;
+        contract.latest_proofs = state.latest_proofs;
+        contract.merkle_tree = merkle_tree;
+        contract.epoch = state.epoch;
+        contract.epoch_updates = state.epoch_updates;
+        contract.epoch_start_root = state.epoch_start_root;
+        for sealed in &state.sealed_epochs {
+            contract
+                .epoch_mmr
+                .append(epoch_leaf(sealed.epoch, &sealed.root));
+        }
+        contract.sealed_epochs = state.sealed_epochs;
+        contract.root_history = state.root_history;
+        contract.epoch_snapshots = state.epoch_snapshots;
+        contract.operator_keys = state.operator_keys;
+        contract.attestations = state.attestations;
+        contract.parameters = state.parameters;
+        contract.submissions = state.submissions;
+        contract.next_submission_id = state.next_submission_id;
+        contract.bonds = state.bonds;
+        contract.checkpoint = state.checkpoint;
+        contract.pruned_epochs = state.pruned_epochs;
+        contract.halt = state.halt;
+
+        if !contract.wallet_roots.is_empty() {
+            contract.merkle_root = compute_merkle_root(global_leaves(&contract.sorted_wallets()));
+        }
+        let latest = contract.root_history.last();
+        if contract.merkle_root != state.merkle_root
+            || latest.is_some_and(|record| record.root != state.merkle_root)
+        {
+            return Err(GlobalRootContractError::CorruptState(
+                "wallet roots do not hash to the saved global root".to_string(),
+            ));
+        }
+        Ok(Some(contract))
     }
 
     /// Registers a new wallet with its Merkle root.
@@ -96,6 +690,7 @@ impl GlobalRootContract {
         wallet_id: Bytes32,
         merkle_root: Bytes32,
     ) -> Result<(), GlobalRootContractError> {
+        self.ensure_running()?;
         if self.wallet_roots.contains_key(&wallet_id) {
             return Err(GlobalRootContractError::WalletAlreadyRegistered);
         }
@@ -103,13 +698,14 @@ impl GlobalRootContract {
         self.wallet_roots.insert(wallet_id, merkle_root);
         self.merkle_tree.insert(merkle_root)?;
         
-        match compute_global_root(&self.wallet_roots) {
-            Ok(root) => {
-                self.merkle_root = root;
-                Ok(())
-            },
-            Err(e) => Err(GlobalRootContractError::ComputationError(e))
-        }
+        self.refresh_root();
+        self.epoch_updates.push(EpochUpdate {
+            wallet_id,
+            old_root: [0u8; 32],
+            new_root: merkle_root,
+            proof: [0u8; 32],
+        });
+        Ok(())
     }
 
     /// Updates a wallet's Merkle root with a new proof.
@@ -119,6 +715,7 @@ impl GlobalRootContract {
         _merkle_root: Bytes32,
         proof: state_proof::StateProof, // Use fully qualified type
     ) -> Result<(), GlobalRootContractError> {
+        self.ensure_running()?;
         let old_root = self.wallet_roots
             .get(&wallet_id)
             .ok_or(GlobalRootContractError::WalletNotFound)?
@@ -132,7 +729,14 @@ impl GlobalRootContract {
             params: self.params.clone(),
         };
     
-        if !verify_wallet_proof(&old_root, &proof.public_inputs[0], &helper_proof, &self.params) {
+        if !verify_wallet_proof_at(
+            &old_root,
+            &proof.public_inputs[0],
+            &helper_proof,
+            &self.params,
+            self.clock.now(),
+            &self.time_policy,
+        ) {
             return Err(GlobalRootContractError::ProofVerificationFailed);
         }
     
@@ -141,14 +745,15 @@ impl GlobalRootContract {
         self.merkle_tree.update(old_root, proof.public_inputs[0])
             .map_err(GlobalRootContractError::from)?;
             
-        match compute_global_root(&self.wallet_roots) {
-            Ok(root) => {
-                self.merkle_root = root;
-                self.latest_proofs.insert(wallet_id, proof);
-                Ok(())
-            },
-            Err(e) => Err(GlobalRootContractError::ComputationError(e))
-        }
+        self.refresh_root();
+        self.epoch_updates.push(EpochUpdate {
+            wallet_id,
+            old_root,
+            new_root: proof.public_inputs[0],
+            proof: proof.pi,
+        });
+        self.latest_proofs.insert(wallet_id, proof);
+        Ok(())
     }    /// Gets the current root for a wallet.
     pub fn get_wallet_root(&self, wallet_id: &Bytes32) -> Option<Bytes32> {
         self.wallet_roots.get(wallet_id).copied()
@@ -169,17 +774,628 @@ impl GlobalRootContract {
         self.merkle_root
     }
 
+    pub fn params(&self) -> &PedersenParameters {
+        &self.params
+    }
+
+    /// Checks a wallet's proof that its channels sum to a declared total under the root
+    /// recorded for it, and returns that total.
+    pub fn verify_wallet_total(
+        &self,
+        proof: &WalletRootProof,
+    ) -> Result<u64, GlobalRootContractError> {
+        let recorded = self
+            .get_wallet_root(&proof.wallet_id)
+            .ok_or(GlobalRootContractError::WalletNotFound)?;
+        if recorded != proof.wallet_root {
+            return Err(GlobalRootContractError::WalletRootMismatch);
+        }
+        if !proof.verify(&self.params) {
+            return Err(GlobalRootContractError::ProofVerificationFailed);
+        }
+        Ok(proof.total)
+    }
+
+    /// Subscribes to events from now on.
+    ///
+    /// A subscriber more than `EVENT_CAPACITY` events behind misses the oldest ones and is
+    /// told so by `RecvError::Lagged`; it should then re-read the contract state.
+    #[cfg(any(test, feature = "tokio"))]
+    pub fn subscribe(&self) -> broadcast::Receiver<GlobalRootEvent> {
+        self.events.subscribe()
+    }
+
+    #[cfg(any(test, feature = "tokio"))]
+    fn emit(&self, event: GlobalRootEvent) {
+        // Sending only fails when nobody is subscribed.
+        let _ = self.events.send(event);
+    }
+
+    /// Without tokio there is nobody to tell.
+    #[cfg(not(any(test, feature = "tokio")))]
+    fn emit(&self, _event: GlobalRootEvent) {}
+
+    /// Has sealed epochs attested by the operator keys in `schedule`.
+    pub fn with_operator_keys(mut self, schedule: OperatorKeySchedule) -> Self {
+        self.operator_keys = Some(schedule);
+        self
+    }
+
+    pub fn operator_keys(&self) -> Option<&OperatorKeySchedule> {
+        self.operator_keys.as_ref()
+    }
+
+    /// Announces a rotation of the operator keys at `height`.
+    pub fn announce_key_rotation(
+        &mut self,
+        rotation: KeyRotation,
+        height: u32,
+    ) -> Result<(), GlobalRootContractError> {
+        let schedule = self
+            .operator_keys
+            .as_mut()
+            .ok_or(GlobalRootContractError::NoOperatorKeys)?;
+        Ok(schedule.announce(&Secp256k1::verification_only(), rotation, height)?)
+    }
+
+    /// Records operator signatures over a sealed epoch, checked against the keys active at
+    /// the attestation's height.
+    pub fn attest_epoch(
+        &mut self,
+        attestation: EpochAttestation,
+    ) -> Result<(), GlobalRootContractError> {
+        let schedule = self
+            .operator_keys
+            .as_mut()
+            .ok_or(GlobalRootContractError::NoOperatorKeys)?;
+        schedule.activate(attestation.height);
+        let sealed = usize::try_from(attestation.epoch)
+            .ok()
+            .and_then(|index| self.sealed_epochs.get(index))
+            .ok_or(GlobalRootContractError::EpochNotSealed(attestation.epoch))?;
+        schedule.verify_attestation(&Secp256k1::verification_only(), sealed, &attestation)?;
+        self.attestations.insert(attestation.epoch, attestation);
+        Ok(())
+    }
+
+    pub fn epoch_attestation(&self, epoch: u64) -> Option<&EpochAttestation> {
+        self.attestations.get(&epoch)
+    }
+
+    /// Applies a wallet root optimistically, opening it to challenge until the window
+    /// passes. The proof's inputs are `[old_root, new_root, merkle_root]`, and the wallet
+    /// must have the submission bond free.
+    pub fn submit_root(
+        &mut self,
+        wallet_id: Bytes32,
+        proof: StateProof,
+        height: u32,
+    ) -> Result<u64, GlobalRootContractError> {
+        self.ensure_running()?;
+        let previous_root = self
+            .get_wallet_root(&wallet_id)
+            .ok_or(GlobalRootContractError::WalletNotFound)?;
+        if self.submissions.iter().any(|s| s.wallet_id == wallet_id) {
+            return Err(GlobalRootContractError::SubmissionPending);
+        }
+        let root = *proof.public_inputs.get(1).ok_or_else(|| {
+            GlobalRootContractError::InvalidInput("proof is missing the new root".to_string())
+        })?;
+        let bond = self.parameters.current().submission_bond;
+        let available = self.bonds.available(&wallet_id);
+        if available < bond {
+            return Err(GlobalRootContractError::InsufficientBond {
+                required: bond,
+                available,
+            });
+        }
+
+        self.set_wallet_root(wallet_id, previous_root, root, proof.pi)?;
+        let id = self.next_submission_id;
+        self.next_submission_id += 1;
+        self.bonds.lock(id, wallet_id, bond);
+        let submission = RootSubmission {
+            id,
+            wallet_id,
+            previous_root,
+            root,
+            proof,
+            submitted_at: height,
+            epoch: self.epoch,
+        };
+        self.emit(GlobalRootEvent::ChallengeOpened {
+            submission_id: id,
+            wallet_id,
+            root,
+            deadline: submission.challenge_deadline(self.challenge_window_for(&submission)),
+        });
+        self.submissions.push(submission);
+        Ok(id)
+    }
+
+    /// Reverts a submission if `fraud` proves it should not stand and its window is open,
+    /// forfeiting the submission's bond to `challenger`.
+    pub fn challenge(
+        &mut self,
+        submission_id: u64,
+        challenger: Bytes32,
+        fraud: &FraudProof,
+        height: u32,
+    ) -> Result<(RootSubmission, Slash), GlobalRootContractError> {
+        let index = self
+            .submissions
+            .iter()
+            .position(|s| s.id == submission_id)
+            .ok_or(GlobalRootContractError::SubmissionNotFound)?;
+        let submission = &self.submissions[index];
+        let deadline = submission.challenge_deadline(self.challenge_window_for(submission));
+        if height > deadline {
+            return Err(GlobalRootContractError::ChallengeWindowClosed(deadline));
+        }
+        if !fraud.verify(&self.submissions[index]) {
+            return Err(GlobalRootContractError::InvalidFraudProof);
+        }
+
+        let submission = self.submissions.remove(index);
+        self.set_wallet_root(
+            submission.wallet_id,
+            submission.root,
+            submission.previous_root,
+            [0u8; 32],
+        )?;
+        let slash = self.bonds.slash(submission.id, challenger).unwrap_or(Slash {
+            submission_id: submission.id,
+            offender: submission.wallet_id,
+            challenger,
+            amount: 0,
+        });
+        self.emit(GlobalRootEvent::ChallengeResolved {
+            submission_id: submission.id,
+            wallet_id: submission.wallet_id,
+            outcome: ChallengeOutcome::Reverted,
+        });
+        Ok((submission, slash))
+    }
+
+    /// Makes final every submission whose challenge window has passed by `height`.
+    pub fn finalize_submissions(&mut self, height: u32) -> Vec<RootSubmission> {
+        let (finalized, open): (Vec<_>, Vec<_>) = std::mem::take(&mut self.submissions)
+            .into_iter()
+            .partition(|s| height > s.challenge_deadline(self.challenge_window_for(s)));
+        self.submissions = open;
+        for submission in &finalized {
+            self.bonds.release(submission.id);
+            self.latest_proofs
+                .insert(submission.wallet_id, submission.proof.clone());
+            self.emit(GlobalRootEvent::ChallengeResolved {
+                submission_id: submission.id,
+                wallet_id: submission.wallet_id,
+                outcome: ChallengeOutcome::Finalized,
+            });
+        }
+        finalized
+    }
+
+    /// Gets the submissions still open to challenge.
+    pub fn pending_submissions(&self) -> &[RootSubmission] {
+        &self.submissions
+    }
+
+    /// Checks the contract's internal consistency.
+    pub fn check_invariants(&self) -> Vec<InvariantViolation> {
+        let mut violations = Vec::new();
+        if !self.wallet_roots.is_empty() {
+            let computed = compute_merkle_root(global_leaves(&self.sorted_wallets()));
+            if computed != self.merkle_root {
+                violations.push(InvariantViolation::GlobalRootMismatch {
+                    stored: self.merkle_root,
+                    computed,
+                });
+            }
+        }
+        if self
+            .root_history
+            .last()
+            .is_some_and(|record| record.root != self.merkle_root)
+        {
+            violations.push(InvariantViolation::HistoryDiverged);
+        }
+        for pair in self.sealed_epochs.windows(2) {
+            if !pair[1].follows(&pair[0]) {
+                violations.push(InvariantViolation::BrokenEpochChain(pair[1].epoch));
+            }
+        }
+        if self
+            .sealed_epochs
+            .last()
+            .is_some_and(|sealed| sealed.root != self.epoch_start_root)
+        {
+            violations.push(InvariantViolation::BrokenEpochChain(self.epoch));
+        }
+        for submission in &self.submissions {
+            if self.bonds.locked(submission.id).is_none() {
+                violations.push(InvariantViolation::UnbackedSubmission(submission.id));
+            }
+        }
+        violations
+    }
+
+    /// Checks the invariants and halts the contract if any is violated.
+    pub fn enforce_invariants(&mut self) -> Vec<InvariantViolation> {
+        let violations = self.check_invariants();
+        if !violations.is_empty() && self.halt.is_none() {
+            self.halt(HaltReason::Violations(violations.clone()));
+        }
+        violations
+    }
+
+    /// Freezes new wallets and submissions.
+    ///
+    /// Challenges, finalization and bond withdrawals keep working, so disputes already
+    /// open play out and honest parties can leave.
+    pub fn halt(&mut self, reason: HaltReason) {
+        let halt = Halt {
+            reason,
+            sequence: self.root_history.last().map_or(0, |record| record.sequence),
+        };
+        self.emit(GlobalRootEvent::Halted(halt.clone()));
+        self.halt = Some(halt);
+    }
+
+    pub fn halt_state(&self) -> Option<&Halt> {
+        self.halt.as_ref()
+    }
+
+    /// Lifts a halt once every invariant holds again.
+    ///
+    /// With operator keys set, the keys active at the authorization's height must have
+    /// signed `ResumeAuthorization::message` for the halt and the current root.
+    pub fn resume(
+        &mut self,
+        authorization: &ResumeAuthorization,
+    ) -> Result<(), GlobalRootContractError> {
+        let halt = self.halt.as_ref().ok_or(GlobalRootContractError::NotHalted)?;
+        let violations = self.check_invariants();
+        if !violations.is_empty() {
+            return Err(GlobalRootContractError::InvariantsViolated(violations));
+        }
+        if let Some(schedule) = &self.operator_keys {
+            let message =
+                ResumeAuthorization::message(halt, &self.merkle_root, authorization.height);
+            let keys = schedule.keys_at(authorization.height);
+            if !keys.verify(&Secp256k1::verification_only(), &message, &authorization.signatures) {
+                return Err(OperatorKeyError::Unauthorized.into());
+            }
+        }
+        self.halt = None;
+        self.emit(GlobalRootEvent::Resumed);
+        Ok(())
+    }
+
+    fn ensure_running(&self) -> Result<(), GlobalRootContractError> {
+        match self.halt {
+            Some(_) => Err(GlobalRootContractError::Halted),
+            None => Ok(()),
+        }
+    }
+
+    /// Moves a wallet from `old_root` to `new_root`, recording the change in the open epoch.
+    fn set_wallet_root(
+        &mut self,
+        wallet_id: Bytes32,
+        old_root: Bytes32,
+        new_root: Bytes32,
+        proof: Bytes32,
+    ) -> Result<(), GlobalRootContractError> {
+        self.merkle_tree.update(old_root, new_root)?;
+        self.wallet_roots.insert(wallet_id, new_root);
+        self.refresh_root();
+        self.epoch_updates.push(EpochUpdate {
+            wallet_id,
+            old_root,
+            new_root,
+            proof,
+        });
+        Ok(())
+    }
+
+    /// Aggregates a wallet's root into the global tree, registering the wallet on first
+    /// sight, and returns its inclusion proof.
+    ///
+    /// A wallet whose root moved since it was aggregated must go through `submit_root`.
+    pub fn aggregate_wallet(
+        &mut self,
+        wallet: &WalletContract,
+    ) -> Result<WalletInclusionProof, GlobalRootContractError> {
+        let root = wallet.get_merkle_root();
+        match self.get_wallet_root(&wallet.wallet_id) {
+            None => self.register_wallet(wallet.wallet_id, root)?,
+            Some(current) if current != root => {
+                return Err(GlobalRootContractError::WalletRootMismatch)
+            }
+            Some(_) => {}
+        }
+        self.inclusion_proof(&wallet.wallet_id)
+    }
+
+    /// Proves that a wallet's current root is part of the current global root.
+    pub fn inclusion_proof(
+        &self,
+        wallet_id: &Bytes32,
+    ) -> Result<WalletInclusionProof, GlobalRootContractError> {
+        let wallet_root = self
+            .get_wallet_root(wallet_id)
+            .ok_or(GlobalRootContractError::WalletNotFound)?;
+        Ok(prove_in(&self.sorted_wallets(), wallet_id, wallet_root))
+    }
+
+    /// Gets the wallets and their roots ordered by wallet id, the order of the global tree
+    /// leaves, so every node derives the same root.
+    fn sorted_wallets(&self) -> Vec<(Bytes32, Bytes32)> {
+        let mut wallets: Vec<_> = self
+            .wallet_roots
+            .iter()
+            .map(|(id, root)| (*id, *root))
+            .collect();
+        wallets.sort_unstable();
+        wallets
+    }
+
+    /// Recomputes the global root and appends it to the history.
+    fn refresh_root(&mut self) {
+        self.merkle_root = compute_merkle_root(global_leaves(&self.sorted_wallets()));
+        let record = RootRecord {
+            sequence: self
+                .root_history
+                .last()
+                .map_or(0, |record| record.sequence + 1),
+            epoch: self.epoch,
+            root: self.merkle_root,
+        };
+        self.root_history.push(record);
+        self.emit(GlobalRootEvent::RootUpdated(record));
+    }
+
+    /// Gets every global root computed so far, oldest first.
+    pub fn root_history(&self) -> &[RootRecord] {
+        &self.root_history
+    }
+
+    /// Gets the global root sealed at `epoch`.
+    pub fn root_at_epoch(&self, epoch: u64) -> Option<Bytes32> {
+        self.sealed_epoch(epoch).map(|sealed| sealed.root)
+    }
+
+    /// Proves that a wallet's root at the close of `epoch` was part of that epoch's root.
+    pub fn historical_inclusion_proof(
+        &self,
+        epoch: u64,
+        wallet_id: &Bytes32,
+    ) -> Result<WalletInclusionProof, GlobalRootContractError> {
+        if epoch < self.pruned_epochs {
+            return Err(GlobalRootContractError::EpochPruned(epoch));
+        }
+        let wallets = usize::try_from(epoch)
+            .ok()
+            .and_then(|index| self.epoch_snapshots.get(index))
+            .ok_or(GlobalRootContractError::EpochNotSealed(epoch))?;
+        let wallet_root = wallets
+            .iter()
+            .find(|(id, _)| id == wallet_id)
+            .map(|(_, root)| *root)
+            .ok_or(GlobalRootContractError::WalletNotFound)?;
+        Ok(prove_in(wallets, wallet_id, wallet_root))
+    }
+
+    /// Proves that a channel state, shown in the wallet tree by `channel`, was part of the
+    /// global root sealed at `epoch`.
+    pub fn historical_channel_proof(
+        &self,
+        epoch: u64,
+        wallet_id: &Bytes32,
+        channel: ChannelInclusionProof,
+    ) -> Result<HistoricalChannelProof, GlobalRootContractError> {
+        Ok(HistoricalChannelProof {
+            epoch,
+            channel,
+            wallet: self.historical_inclusion_proof(epoch, wallet_id)?,
+        })
+    }
+
+    /// Checks a historical channel proof against the root sealed at its epoch.
+    pub fn verify_historical_channel(
+        &self,
+        proof: &HistoricalChannelProof,
+        state: &ChannelState,
+    ) -> bool {
+        self.root_at_epoch(proof.epoch)
+            .is_some_and(|root| proof.verify(state, &root))
+    }
+
+    /// Gets the number of the open epoch.
+    pub fn current_epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// Gets the updates accepted since the last epoch was sealed.
+    pub fn pending_updates(&self) -> &[EpochUpdate] {
+        &self.epoch_updates
+    }
+
+    /// Closes the open epoch, sealing its updates under the current global root.
+    ///
+    /// Empty epochs are sealed too, so epochs advance at the caller's cadence whether or
+    /// not any wallet changed.
+    pub fn seal_epoch(&mut self) -> SealedEpoch {
+        let updates = std::mem::take(&mut self.epoch_updates);
+        let sealed = SealedEpoch {
+            epoch: self.epoch,
+            previous_root: self.epoch_start_root,
+            root: self.merkle_root,
+            digest: epoch_digest(
+                self.epoch,
+                &self.epoch_start_root,
+                &self.merkle_root,
+                &updates,
+            ),
+            updates,
+        };
+        self.sealed_epochs.push(sealed.clone());
+        self.epoch_snapshots.push(self.sorted_wallets());
+        self.epoch_mmr.append(epoch_leaf(sealed.epoch, &sealed.root));
+        self.emit(GlobalRootEvent::EpochSealed(sealed.clone()));
+        self.epoch += 1;
+        self.epoch_start_root = self.merkle_root;
+        if let Some(parameters) = self.parameters.activate(self.epoch) {
+            self.emit(GlobalRootEvent::ParametersActivated(parameters));
+        }
+        sealed
+    }
+
+    /// Gets a sealed epoch by number.
+    pub fn sealed_epoch(&self, epoch: u64) -> Option<&SealedEpoch> {
+        self.sealed_epochs.get(usize::try_from(epoch).ok()?)
+    }
+
+    /// Gets the most recently sealed epoch.
+    pub fn latest_sealed_epoch(&self) -> Option<&SealedEpoch> {
+        self.sealed_epochs.last()
+    }
+
+    /// Replays a sealed epoch's updates over the wallets the previous epoch sealed.
+    pub fn replay_epoch(&self, epoch: u64) -> Result<bool, GlobalRootContractError> {
+        // The epoch needs its own updates and the previous epoch's snapshot.
+        if self.pruned_epochs > 0 && epoch <= self.pruned_epochs {
+            return Err(GlobalRootContractError::EpochPruned(epoch));
+        }
+        let sealed = self
+            .sealed_epoch(epoch)
+            .ok_or(GlobalRootContractError::EpochNotSealed(epoch))?;
+        let previous = match epoch.checked_sub(1) {
+            Some(previous) => self.epoch_snapshots[previous as usize].as_slice(),
+            None => &[],
+        };
+        Ok(sealed.replay(previous))
+    }
+
+    /// Checks a sealed epoch, including one whose history was pruned.
+    ///
+    /// Kept epochs are replayed. Epochs before the checkpoint lost their updates, so they
+    /// are checked by their root's proof against the history root instead.
+    pub fn verify_epoch(&self, epoch: u64) -> Result<bool, GlobalRootContractError> {
+        match self.replay_epoch(epoch) {
+            Err(GlobalRootContractError::EpochPruned(_)) => {
+                let in_history = self.epoch_root_proof(epoch)?.verify(&self.history_root());
+                let kept_updates = epoch >= self.pruned_epochs;
+                Ok(in_history
+                    && (!kept_updates || self.sealed_epochs[epoch as usize].matches_digest()))
+            }
+            result => result,
+        }
+    }
+
+    /// Tells subscribers that `root`, sealed at `epoch`, was confirmed on-chain by
+    /// `anchor_txid`. Called by the anchoring side, which holds no mutable contract.
+    pub fn announce_anchor(&self, epoch: u64, root: Bytes32, anchor_txid: Txid) {
+        self.emit(GlobalRootEvent::Anchored {
+            epoch,
+            root,
+            anchor_txid,
+        });
+    }
+
+    /// Marks a sealed epoch final once its root is anchored by `anchor_txid`.
+    ///
+    /// Every submission accepted up to the epoch must have passed its challenge window,
+    /// and checkpoints only move forward.
+    pub fn finalize_checkpoint(
+        &mut self,
+        epoch: u64,
+        anchored_root: Bytes32,
+        anchor_txid: Txid,
+    ) -> Result<Checkpoint, GlobalRootContractError> {
+        if self.checkpoint.is_some_and(|checkpoint| checkpoint.epoch >= epoch) {
+            return Err(GlobalRootContractError::NotAfterCheckpoint(epoch));
+        }
+        let root = self
+            .root_at_epoch(epoch)
+            .ok_or(GlobalRootContractError::EpochNotSealed(epoch))?;
+        if root != anchored_root {
+            return Err(GlobalRootContractError::AnchorMismatch(epoch));
+        }
+        if self.submissions.iter().any(|s| s.epoch <= epoch) {
+            return Err(GlobalRootContractError::EpochUnderChallenge(epoch));
+        }
+        let checkpoint = Checkpoint {
+            epoch,
+            root,
+            anchor_txid,
+        };
+        self.checkpoint = Some(checkpoint);
+        self.emit(GlobalRootEvent::Checkpointed(checkpoint));
+        Ok(checkpoint)
+    }
+
+    pub fn latest_checkpoint(&self) -> Option<&Checkpoint> {
+        self.checkpoint.as_ref()
+    }
+
+    /// Drops history older than the latest checkpoint.
+    ///
+    /// Earlier epochs keep their roots, so the epoch chain and the history root still
+    /// verify, but lose their update lists and wallet snapshots; [`Self::verify_epoch`]
+    /// checks them through the history root from then on. Root records before the
+    /// checkpoint are dropped except the one in force when it began.
+    pub fn prune_to_checkpoint(&mut self) -> PruneSummary {
+        let Some(checkpoint) = self.checkpoint else {
+            return PruneSummary::default();
+        };
+        let mut summary = PruneSummary::default();
+        let start = usize::try_from(self.pruned_epochs).unwrap_or(usize::MAX);
+        let end = usize::try_from(checkpoint.epoch).unwrap_or(usize::MAX);
+        for sealed in self.sealed_epochs.iter_mut().take(end).skip(start) {
+            summary.epoch_updates += sealed.updates.len();
+            sealed.updates = Vec::new();
+        }
+        for snapshot in self.epoch_snapshots.iter_mut().take(end).skip(start) {
+            *snapshot = Vec::new();
+            summary.snapshots += 1;
+        }
+        if let Some(keep) = self
+            .root_history
+            .iter()
+            .rposition(|record| record.epoch < checkpoint.epoch)
+        {
+            summary.root_records = keep;
+            self.root_history.drain(..keep);
+        }
+        self.pruned_epochs = self.pruned_epochs.max(checkpoint.epoch);
+        summary
+    }
+
+    /// Gets the root committing to every sealed epoch's global root.
+    pub fn history_root(&self) -> Bytes32 {
+        self.epoch_mmr.root()
+    }
+
+    /// Proves which global root was sealed at `epoch`, against the current history root.
+    pub fn epoch_root_proof(&self, epoch: u64) -> Result<EpochRootProof, GlobalRootContractError> {
+        let root = self
+            .root_at_epoch(epoch)
+            .ok_or(GlobalRootContractError::EpochNotSealed(epoch))?;
+        let proof = self
+            .epoch_mmr
+            .proof(epoch)
+            .ok_or(GlobalRootContractError::EpochNotSealed(epoch))?;
+        Ok(EpochRootProof { epoch, root, proof })
+    }
+
     /// Generates a Merkle proof for a given wallet.
     pub fn generate_proof(
         &self,
         wallet_id: Bytes32,
     ) -> Result<Vec<Bytes32>, GlobalRootContractError> {
-        let root = self.wallet_roots
-            .get(&wallet_id)
-            .ok_or(GlobalRootContractError::WalletNotFound)?;
-            
-        self.merkle_tree.get_proof(root)
-            .ok_or(GlobalRootContractError::ProofVerificationFailed)
+        Ok(self.inclusion_proof(&wallet_id)?.siblings)
     }
 
     /// Verifies a Merkle proof for a given wallet.
@@ -188,11 +1404,11 @@ impl GlobalRootContract {
         wallet_id: Bytes32,
         proof: &[Bytes32],
     ) -> Result<bool, GlobalRootContractError> {
-        let wallet_root = self.wallet_roots
-            .get(&wallet_id)
-            .ok_or(GlobalRootContractError::WalletNotFound)?;
-            
-        Ok(self.merkle_tree.verify_proof(wallet_root, proof, &self.merkle_root))
+        let inclusion = WalletInclusionProof {
+            siblings: proof.to_vec(),
+            ..self.inclusion_proof(&wallet_id)?
+        };
+        Ok(inclusion.verify(&self.merkle_root))
     }
 }
 
@@ -245,6 +1461,556 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_epochs_seal_accumulated_updates() -> Result<(), GlobalRootContractError> {
+        let mut contract = setup_test_contract();
+        contract.register_wallet([1u8; 32], [2u8; 32])?;
+        contract.register_wallet([3u8; 32], [4u8; 32])?;
+        assert_eq!(contract.pending_updates().len(), 2);
+
+        let first = contract.seal_epoch();
+        assert_eq!(first.epoch, 0);
+        assert_eq!(first.previous_root, [0u8; 32]);
+        assert_eq!(first.root, contract.get_global_merkle_root());
+        assert_eq!(first.updates.len(), 2);
+        assert!(first.matches_digest());
+        assert!(first.replay(&[]));
+        assert!(contract.replay_epoch(0)?);
+        assert!(contract.pending_updates().is_empty());
+        assert_eq!(contract.current_epoch(), 1);
+
+        let second = contract.seal_epoch();
+        assert!(second.matches_digest());
+        assert!(contract.replay_epoch(1)?);
+        assert!(second.follows(&first));
+        assert_eq!(second.root, first.root);
+        assert_eq!(contract.sealed_epoch(0), Some(&first));
+        assert_eq!(contract.latest_sealed_epoch(), Some(&second));
+
+        let mut tampered = first.clone();
+        tampered.updates[0].new_root = [9u8; 32];
+        assert!(!tampered.matches_digest());
+
+        // An epoch whose digest is consistent but whose updates miss the root is caught
+        // by replaying them.
+        let mut forged = first.clone();
+        forged.updates.pop();
+        forged.digest = epoch_digest(forged.epoch, &forged.previous_root, &forged.root, &forged.updates);
+        assert!(forged.matches_digest());
+        assert!(!forged.replay(&[]));
+        assert!(!first.replay(&[([1u8; 32], [2u8; 32])]));
+        Ok(())
+    }
+
+    fn transition(old: Bytes32, new: Bytes32, timestamp: u64) -> StateProof {
+        let pi = Sha256::new()
+            .chain_update(old)
+            .chain_update(new)
+            .chain_update([0u8; 32])
+            .chain_update(timestamp.to_le_bytes())
+            .finalize()
+            .into();
+        StateProof {
+            pi,
+            public_inputs: vec![old, new, [0u8; 32]],
+            timestamp,
+        }
+    }
+
+    #[test]
+    fn test_challenge_reverts_submission() -> Result<(), GlobalRootContractError> {
+        let mut contract = setup_test_contract().with_challenge_window(10);
+        let wallet_id = [1u8; 32];
+        contract.register_wallet(wallet_id, [2u8; 32])?;
+        let registered_root = contract.get_global_merkle_root();
+
+        let id = contract.submit_root(wallet_id, transition([2u8; 32], [3u8; 32], 1_000), 100)?;
+        assert_eq!(contract.get_wallet_root(&wallet_id), Some([3u8; 32]));
+        assert!(matches!(
+            contract.submit_root(wallet_id, transition([3u8; 32], [4u8; 32], 1_001), 101),
+            Err(GlobalRootContractError::SubmissionPending)
+        ));
+        assert!(matches!(
+            contract.challenge(id, [9u8; 32], &FraudProof::InvalidTransition, 105),
+            Err(GlobalRootContractError::InvalidFraudProof)
+        ));
+
+        let newer = FraudProof::NewerState {
+            proof: transition([2u8; 32], [5u8; 32], 1_001),
+        };
+        assert!(matches!(
+            contract.challenge(id, [9u8; 32], &newer, 111),
+            Err(GlobalRootContractError::ChallengeWindowClosed(110))
+        ));
+        contract.challenge(id, [9u8; 32], &newer, 110)?;
+        assert_eq!(contract.get_wallet_root(&wallet_id), Some([2u8; 32]));
+        assert_eq!(contract.get_global_merkle_root(), registered_root);
+        assert!(contract.pending_submissions().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_unchallenged_submission_becomes_final() -> Result<(), GlobalRootContractError> {
+        let mut contract = setup_test_contract().with_challenge_window(10);
+        let wallet_id = [1u8; 32];
+        contract.register_wallet(wallet_id, [2u8; 32])?;
+        let id = contract.submit_root(wallet_id, transition([2u8; 32], [3u8; 32], 1_000), 100)?;
+
+        assert!(contract.finalize_submissions(110).is_empty());
+        assert_eq!(contract.finalize_submissions(111).len(), 1);
+        assert!(contract.get_latest_proof(&wallet_id).is_some());
+        assert!(matches!(
+            contract.challenge(id, [9u8; 32], &FraudProof::InvalidTransition, 111),
+            Err(GlobalRootContractError::SubmissionNotFound)
+        ));
+        assert_eq!(contract.get_wallet_root(&wallet_id), Some([3u8; 32]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_fraudulent_submission_forfeits_bond() -> Result<(), GlobalRootContractError> {
+        let mut contract = setup_test_contract()
+            .with_challenge_window(10)
+            .with_submission_bond(50);
+        let (wallet_id, watcher) = ([1u8; 32], [9u8; 32]);
+        contract.register_wallet(wallet_id, [2u8; 32])?;
+        contract.deposit_bond(wallet_id, 80);
+
+        let honest = contract.submit_root(wallet_id, transition([2u8; 32], [3u8; 32], 1_000), 100)?;
+        assert_eq!(contract.bonds().locked(honest), Some(50));
+        contract.finalize_submissions(111);
+        assert_eq!(contract.bonds().available(&wallet_id), 80);
+
+        let mut forged = transition([3u8; 32], [4u8; 32], 1_001);
+        forged.pi = [0u8; 32];
+        let id = contract.submit_root(wallet_id, forged, 120)?;
+        assert!(matches!(
+            contract.withdraw_bond(&wallet_id, 80),
+            Err(GlobalRootContractError::InsufficientBond { required: 80, available: 30 })
+        ));
+        let (_, slash) = contract.challenge(id, watcher, &FraudProof::InvalidTransition, 125)?;
+        assert_eq!(slash.amount, 50);
+        assert_eq!(slash.offender, wallet_id);
+        assert_eq!(contract.bonds().available(&wallet_id), 30);
+        assert_eq!(contract.bonds().available(&watcher), 50);
+        assert!(matches!(
+            contract.submit_root(wallet_id, transition([3u8; 32], [4u8; 32], 1_002), 130),
+            Err(GlobalRootContractError::InsufficientBond { required: 50, available: 30 })
+        ));
+        contract.withdraw_bond(&watcher, 50)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_aggregated_wallets_prove_inclusion() -> Result<(), GlobalRootContractError> {
+        let params = PedersenParameters::default();
+        let mut contract = setup_test_contract();
+        let wallets: Vec<WalletContract> = (1..=5u8)
+            .map(|i| {
+                let mut wallet =
+                    WalletContract::new([i; 32], params.clone(), setup_test_contract());
+                wallet.merkle_root = [i + 100; 32];
+                wallet
+            })
+            .collect();
+
+        let mut proofs = Vec::new();
+        for wallet in wallets.iter().rev() {
+            proofs.push(contract.aggregate_wallet(wallet)?);
+        }
+        let global_root = contract.get_global_merkle_root();
+        for wallet in &wallets {
+            let proof = contract.inclusion_proof(&wallet.wallet_id)?;
+            assert!(proof.verify(&global_root));
+            assert!(wallet.verify_inclusion(&proof, &global_root));
+        }
+        // Proofs issued before the last wallet joined are for an older root.
+        assert!(!proofs[0].verify(&global_root));
+
+        let mut forged = contract.inclusion_proof(&[1u8; 32])?;
+        forged.wallet_root = [0u8; 32];
+        assert!(!forged.verify(&global_root));
+        assert!(!wallets[1].verify_inclusion(&contract.inclusion_proof(&[1u8; 32])?, &global_root));
+
+        let mut moved = WalletContract::new([1u8; 32], params, setup_test_contract());
+        moved.merkle_root = [9u8; 32];
+        assert!(matches!(
+            contract.aggregate_wallet(&moved),
+            Err(GlobalRootContractError::WalletRootMismatch)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_historical_channel_proofs() -> Result<(), Box<dyn std::error::Error>> {
+        let params = PedersenParameters::default();
+        let mut contract = setup_test_contract();
+        let mut wallet = WalletContract::new([1u8; 32], params, setup_test_contract());
+        wallet.register_channel([2u8; 32], 100, [0u8; 32], vec![1])?;
+        contract.aggregate_wallet(&wallet)?;
+        contract.register_wallet([3u8; 32], [4u8; 32])?;
+
+        let state = wallet.get_channel(&[2u8; 32]).unwrap().clone();
+        let channel = wallet.channel_inclusion_proof(&[2u8; 32])?;
+        let old_root = wallet.get_merkle_root();
+        contract.seal_epoch();
+
+        contract.submit_root([1u8; 32], transition(old_root, [5u8; 32], 1_000), 10)?;
+        contract.seal_epoch();
+        assert_ne!(contract.root_at_epoch(0), contract.root_at_epoch(1));
+        assert_eq!(contract.root_history().len(), 3);
+        assert_eq!(contract.root_history()[2].epoch, 1);
+
+        let proof = contract.historical_channel_proof(0, &[1u8; 32], channel)?;
+        assert!(contract.verify_historical_channel(&proof, &state));
+        assert!(!proof.verify(&state, &contract.root_at_epoch(1).unwrap()));
+
+        let mut stale = state.clone();
+        stale.balances = vec![99];
+        assert!(!contract.verify_historical_channel(&proof, &stale));
+        assert!(contract
+            .historical_inclusion_proof(1, &[1u8; 32])?
+            .verify(&contract.root_at_epoch(1).unwrap()));
+        assert!(matches!(
+            contract.historical_inclusion_proof(2, &[1u8; 32]),
+            Err(GlobalRootContractError::EpochNotSealed(2))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_epoch_attestations_survive_key_rotation() -> Result<(), GlobalRootContractError> {
+        use crate::zkp::operator_keys::OperatorKeySet;
+        use bitcoin::secp256k1::{schnorr, KeyPair, Message, SecretKey, XOnlyPublicKey};
+
+        let secp = Secp256k1::new();
+        let keypair =
+            |byte| KeyPair::from_secret_key(&secp, &SecretKey::from_slice(&[byte; 32]).unwrap());
+        let sign = |byte, message: &Message| -> Vec<(XOnlyPublicKey, schnorr::Signature)> {
+            let keypair = keypair(byte);
+            vec![(keypair.x_only_public_key().0, secp.sign_schnorr(message, &keypair))]
+        };
+        let old_keys = OperatorKeySet::single(keypair(1).x_only_public_key().0);
+        let new_keys = OperatorKeySet::single(keypair(2).x_only_public_key().0);
+        let mut contract = setup_test_contract()
+            .with_operator_keys(OperatorKeySchedule::new(old_keys).with_grace_period(10));
+
+        contract.register_wallet([1u8; 32], [2u8; 32])?;
+        let first = contract.seal_epoch();
+        let attestation = |sealed: &SealedEpoch, height, byte| EpochAttestation {
+            epoch: sealed.epoch,
+            root: sealed.root,
+            height,
+            signatures: sign(byte, &EpochAttestation::message(sealed, height)),
+        };
+        contract.attest_epoch(attestation(&first, 5, 1))?;
+
+        contract.announce_key_rotation(
+            KeyRotation {
+                new_keys: new_keys.clone(),
+                activation_height: 20,
+                signatures: sign(1, &KeyRotation::message(&new_keys, 20)),
+            },
+            5,
+        )?;
+        contract.register_wallet([3u8; 32], [4u8; 32])?;
+        let second = contract.seal_epoch();
+        assert!(matches!(
+            contract.attest_epoch(attestation(&second, 20, 1)),
+            Err(GlobalRootContractError::OperatorKeyError(OperatorKeyError::Unauthorized))
+        ));
+        contract.attest_epoch(attestation(&second, 20, 2))?;
+
+        // Rotating did not touch the first epoch's root or attestation.
+        let schedule = contract.operator_keys().unwrap();
+        assert_eq!(schedule.keys_at(25), &new_keys);
+        let recorded = contract.epoch_attestation(0).unwrap();
+        schedule.verify_attestation(&secp, contract.sealed_epoch(0).unwrap(), recorded)?;
+        assert!(contract
+            .historical_inclusion_proof(0, &[1u8; 32])?
+            .verify(&contract.root_at_epoch(0).unwrap()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_subscribers_receive_events() -> Result<(), GlobalRootContractError> {
+        let mut contract = setup_test_contract().with_challenge_window(10);
+        let mut events = contract.subscribe();
+        let wallet_id = [1u8; 32];
+        contract.register_wallet(wallet_id, [2u8; 32])?;
+        let id = contract.submit_root(wallet_id, transition([2u8; 32], [3u8; 32], 1_000), 100)?;
+        contract.finalize_submissions(111);
+        let sealed = contract.seal_epoch();
+
+        let received: Vec<_> = std::iter::from_fn(|| events.try_recv().ok()).collect();
+        assert_eq!(
+            received,
+            vec![
+                GlobalRootEvent::RootUpdated(contract.root_history()[0]),
+                GlobalRootEvent::RootUpdated(contract.root_history()[1]),
+                GlobalRootEvent::ChallengeOpened {
+                    submission_id: id,
+                    wallet_id,
+                    root: [3u8; 32],
+                    deadline: 110,
+                },
+                GlobalRootEvent::ChallengeResolved {
+                    submission_id: id,
+                    wallet_id,
+                    outcome: ChallengeOutcome::Finalized,
+                },
+                GlobalRootEvent::EpochSealed(sealed),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_light_clients_verify_epoch_roots() -> Result<(), GlobalRootContractError> {
+        let mut contract = setup_test_contract();
+        for wallet in 1..=5u8 {
+            contract.register_wallet([wallet; 32], [wallet + 10; 32])?;
+            contract.seal_epoch();
+        }
+        let history_root = contract.history_root();
+        for epoch in 0..5 {
+            let proof = contract.epoch_root_proof(epoch)?;
+            assert_eq!(Some(proof.root), contract.root_at_epoch(epoch));
+            assert!(proof.verify(&history_root));
+        }
+
+        let mut forged = contract.epoch_root_proof(2)?;
+        forged.root = contract.root_at_epoch(3).unwrap();
+        assert!(!forged.verify(&history_root));
+        let mut shifted = contract.epoch_root_proof(3)?;
+        shifted.epoch = 2;
+        assert!(!shifted.verify(&history_root));
+        assert!(matches!(
+            contract.epoch_root_proof(5),
+            Err(GlobalRootContractError::EpochNotSealed(5))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_checkpoints_allow_pruning() -> Result<(), GlobalRootContractError> {
+        use bitcoin::hashes::Hash;
+
+        let mut contract = setup_test_contract().with_challenge_window(10);
+        let txid = Txid::all_zeros();
+        contract.register_wallet([1u8; 32], [2u8; 32])?;
+        contract.seal_epoch();
+        contract.submit_root([1u8; 32], transition([2u8; 32], [3u8; 32], 1_000), 100)?;
+        contract.seal_epoch();
+        contract.register_wallet([4u8; 32], [5u8; 32])?;
+        contract.seal_epoch();
+        let root = |contract: &GlobalRootContract, epoch| contract.root_at_epoch(epoch).unwrap();
+
+        assert!(matches!(
+            contract.finalize_checkpoint(0, root(&contract, 1), txid),
+            Err(GlobalRootContractError::AnchorMismatch(0))
+        ));
+        assert!(matches!(
+            contract.finalize_checkpoint(1, root(&contract, 1), txid),
+            Err(GlobalRootContractError::EpochUnderChallenge(1))
+        ));
+        contract.finalize_submissions(111);
+        let mut events = contract.subscribe();
+        let checkpoint = contract.finalize_checkpoint(1, root(&contract, 1), txid)?;
+        assert_eq!(events.try_recv().ok(), Some(GlobalRootEvent::Checkpointed(checkpoint)));
+        assert!(matches!(
+            contract.finalize_checkpoint(1, root(&contract, 1), txid),
+            Err(GlobalRootContractError::NotAfterCheckpoint(1))
+        ));
+
+        for epoch in 0..3 {
+            assert!(contract.verify_epoch(epoch)?);
+        }
+        let records = contract.root_history().len();
+        let summary = contract.prune_to_checkpoint();
+        assert_eq!(summary.epoch_updates, 1);
+        assert_eq!(summary.snapshots, 1);
+        assert_eq!(contract.root_history().len(), records - summary.root_records);
+        assert!(matches!(
+            contract.historical_inclusion_proof(0, &[1u8; 32]),
+            Err(GlobalRootContractError::EpochPruned(0))
+        ));
+        assert!(contract.historical_inclusion_proof(1, &[1u8; 32]).is_ok());
+        assert!(contract.epoch_root_proof(0)?.verify(&contract.history_root()));
+        assert!(matches!(contract.replay_epoch(1), Err(GlobalRootContractError::EpochPruned(1))));
+        for epoch in 0..3 {
+            assert!(contract.verify_epoch(epoch)?);
+        }
+        assert!(contract.check_invariants().is_empty());
+        assert_eq!(contract.prune_to_checkpoint(), PruneSummary::default());
+
+        contract.register_wallet([6u8; 32], [7u8; 32])?;
+        let history = contract.root_history();
+        assert_eq!(
+            history[history.len() - 1].sequence,
+            history[history.len() - 2].sequence + 1
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_halt_freezes_submissions_until_resumed() -> Result<(), GlobalRootContractError> {
+        use crate::zkp::operator_keys::OperatorKeySet;
+        use bitcoin::secp256k1::{KeyPair, SecretKey};
+
+        let secp = Secp256k1::new();
+        let keypair = KeyPair::from_secret_key(&secp, &SecretKey::from_slice(&[1u8; 32]).unwrap());
+        let keys = OperatorKeySet::single(keypair.x_only_public_key().0);
+        let mut contract = setup_test_contract()
+            .with_challenge_window(10)
+            .with_operator_keys(OperatorKeySchedule::new(keys));
+        contract.register_wallet([1u8; 32], [2u8; 32])?;
+        let id = contract.submit_root([1u8; 32], transition([2u8; 32], [3u8; 32], 1_000), 100)?;
+        assert!(contract.enforce_invariants().is_empty());
+
+        let honest_root = contract.merkle_root;
+        contract.merkle_root = [9u8; 32];
+        assert_eq!(contract.enforce_invariants().len(), 2);
+        assert!(contract.halt_state().is_some());
+        assert!(matches!(
+            contract.register_wallet([4u8; 32], [5u8; 32]),
+            Err(GlobalRootContractError::Halted)
+        ));
+        assert!(matches!(
+            contract.resume(&ResumeAuthorization { height: 0, signatures: Vec::new() }),
+            Err(GlobalRootContractError::InvariantsViolated(_))
+        ));
+
+        contract.merkle_root = honest_root;
+        let newer = FraudProof::NewerState {
+            proof: transition([2u8; 32], [4u8; 32], 1_001),
+        };
+        contract.challenge(id, [9u8; 32], &newer, 105)?;
+        assert!(matches!(
+            contract.resume(&ResumeAuthorization { height: 0, signatures: Vec::new() }),
+            Err(GlobalRootContractError::OperatorKeyError(OperatorKeyError::Unauthorized))
+        ));
+        let message = ResumeAuthorization::message(
+            contract.halt_state().unwrap(),
+            &contract.get_global_merkle_root(),
+            110,
+        );
+        contract.resume(&ResumeAuthorization {
+            height: 110,
+            signatures: vec![(
+                keypair.x_only_public_key().0,
+                secp.sign_schnorr(&message, &keypair),
+            )],
+        })?;
+        assert!(contract.halt_state().is_none());
+        contract.register_wallet([4u8; 32], [5u8; 32])?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_parameter_upgrades_are_staged() -> Result<(), GlobalRootContractError> {
+        use crate::zkp::governance::GovernanceError;
+        use crate::zkp::operator_keys::OperatorKeySet;
+        use bitcoin::secp256k1::{KeyPair, SecretKey};
+
+        let secp = Secp256k1::new();
+        let keypair = KeyPair::from_secret_key(&secp, &SecretKey::from_slice(&[1u8; 32]).unwrap());
+        let mut contract = setup_test_contract()
+            .with_parameters(ParameterSchedule::new(ContractParameters {
+                challenge_window: 10,
+                ..ContractParameters::default()
+            })
+            .with_delay(2))
+            .with_operator_keys(OperatorKeySchedule::new(OperatorKeySet::single(
+                keypair.x_only_public_key().0,
+            )));
+        let mut events = contract.subscribe();
+        let parameters = ContractParameters {
+            version: 2,
+            challenge_window: 50,
+            ..*contract.parameters()
+        };
+        let mut upgrade = ParameterUpgrade {
+            parameters,
+            activation_epoch: 2,
+            signatures: Vec::new(),
+        };
+        assert!(matches!(
+            contract.announce_parameter_upgrade(upgrade.clone(), 0),
+            Err(GlobalRootContractError::OperatorKeyError(OperatorKeyError::Unauthorized))
+        ));
+        let message = ParameterUpgrade::message(&parameters, 2);
+        upgrade.signatures = vec![(
+            keypair.x_only_public_key().0,
+            secp.sign_schnorr(&message, &keypair),
+        )];
+        contract.announce_parameter_upgrade(upgrade.clone(), 0)?;
+        assert!(matches!(
+            contract.announce_parameter_upgrade(upgrade, 0),
+            Err(GlobalRootContractError::GovernanceError(GovernanceError::UpgradePending(2)))
+        ));
+
+        contract.register_wallet([1u8; 32], [2u8; 32])?;
+        contract.seal_epoch();
+        let old = contract.submit_root([1u8; 32], transition([2u8; 32], [3u8; 32], 1_000), 100)?;
+        contract.seal_epoch();
+        assert_eq!(contract.parameters().version, 2);
+        assert!(std::iter::from_fn(|| events.try_recv().ok())
+            .any(|event| event == GlobalRootEvent::ParametersActivated(parameters)));
+
+        // The submission keeps the window it was accepted under.
+        assert_eq!(contract.finalize_submissions(111)[0].id, old);
+        contract.register_wallet([4u8; 32], [5u8; 32])?;
+        contract.submit_root([4u8; 32], transition([5u8; 32], [6u8; 32], 1_000), 200)?;
+        assert!(contract.finalize_submissions(211).is_empty());
+        assert_eq!(contract.finalize_submissions(251).len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_state_survives_restart() -> Result<(), Box<dyn std::error::Error>> {
+        let path = std::env::temp_dir().join(format!("global_root_{}", std::process::id()));
+        let db = OverpassDB::new(path.to_str().unwrap())?;
+        assert!(GlobalRootContract::restore(&db, PedersenParameters::default())?.is_none());
+
+        let mut contract = setup_test_contract()
+            .with_challenge_window(10)
+            .with_submission_bond(5);
+        contract.register_wallet([1u8; 32], [2u8; 32])?;
+        contract.register_wallet([3u8; 32], [4u8; 32])?;
+        contract.seal_epoch();
+        contract.deposit_bond([1u8; 32], 5);
+        let id = contract.submit_root([1u8; 32], transition([2u8; 32], [5u8; 32], 1_000), 100)?;
+        contract.save(&db)?;
+        drop(contract);
+
+        let mut restored = GlobalRootContract::restore(&db, PedersenParameters::default())?
+            .expect("state was saved");
+        assert_eq!(restored.current_epoch(), 1);
+        assert_eq!(restored.get_wallet_root(&[1u8; 32]), Some([5u8; 32]));
+        assert_eq!(restored.pending_updates().len(), 1);
+        assert_eq!(restored.bonds().locked(id), Some(5));
+        assert!(restored.root_at_epoch(0).is_some());
+        assert!(restored
+            .epoch_root_proof(0)?
+            .verify(&restored.history_root()));
+
+        let newer = FraudProof::NewerState {
+            proof: transition([2u8; 32], [6u8; 32], 1_001),
+        };
+        restored.challenge(id, [9u8; 32], &newer, 105)?;
+        assert_eq!(restored.get_wallet_root(&[1u8; 32]), Some([2u8; 32]));
+        assert_eq!(restored.bonds().available(&[9u8; 32]), 5);
+
+        db.put(STATE_KEY, b"not a contract")?;
+        assert!(matches!(
+            GlobalRootContract::restore(&db, PedersenParameters::default()),
+            Err(GlobalRootContractError::CorruptState(_))
+        ));
+        std::fs::remove_dir_all(path)?;
+        Ok(())
+    }
+
     #[test]
     fn test_list_wallets() -> Result<(), GlobalRootContractError> {
         let mut contract = setup_test_contract();
@@ -0,0 +1,232 @@
+// src/zkp/governance.rs
+
+use bitcoin::secp256k1::{schnorr, Message, XOnlyPublicKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::zkp::fraud_proof::DEFAULT_CHALLENGE_WINDOW;
+use crate::zkp::operator_keys::message_from;
+
+/// Epochs between announcing a parameter upgrade and its earliest activation by default.
+pub const DEFAULT_UPGRADE_DELAY: u64 = 6;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum GovernanceError {
+    #[error("Parameter version {proposed} does not follow version {current}")]
+    VersionNotIncreasing { current: u32, proposed: u32 },
+    #[error("Upgrade activates at epoch {0}, before the delay ends at epoch {1}")]
+    TooSoon(u64, u64),
+    #[error("An upgrade activating at epoch {0} is already pending")]
+    UpgradePending(u64),
+    #[error("Invalid parameters: {0}")]
+    InvalidParameters(&'static str),
+}
+
+/// The tunable parameters of a global root contract deployment.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContractParameters {
+    pub version: u32,
+    /// Blocks between epoch seals, the cadence operators seal at.
+    pub epoch_length: u32,
+    /// Blocks a submission stays open to challenge.
+    pub challenge_window: u32,
+    /// Bond each submission locks from its wallet.
+    pub submission_bond: u64,
+    /// Version of the proof circuit submissions are verified with.
+    pub circuit_version: u32,
+}
+
+impl Default for ContractParameters {
+    fn default() -> Self {
+        Self {
+            version: 1,
+            epoch_length: 144,
+            challenge_window: DEFAULT_CHALLENGE_WINDOW,
+            submission_bond: 0,
+            circuit_version: 1,
+        }
+    }
+}
+
+impl ContractParameters {
+    fn validate(&self) -> Result<(), GovernanceError> {
+        if self.epoch_length == 0 {
+            return Err(GovernanceError::InvalidParameters("epoch length is zero"));
+        }
+        if self.challenge_window == 0 {
+            return Err(GovernanceError::InvalidParameters(
+                "challenge window is zero",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// An announced parameter change, signed by the operator keys.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParameterUpgrade {
+    pub parameters: ContractParameters,
+    /// First epoch the new parameters apply to.
+    pub activation_epoch: u64,
+    pub signatures: Vec<(XOnlyPublicKey, schnorr::Signature)>,
+}
+
+impl ParameterUpgrade {
+    /// Gets the message operators sign to authorize the upgrade.
+    pub fn message(parameters: &ContractParameters, activation_epoch: u64) -> Message {
+        let mut hasher = Sha256::new();
+        hasher.update(b"overpass/governance/parameters/v1");
+        hasher.update(parameters.version.to_le_bytes());
+        hasher.update(parameters.epoch_length.to_le_bytes());
+        hasher.update(parameters.challenge_window.to_le_bytes());
+        hasher.update(parameters.submission_bond.to_le_bytes());
+        hasher.update(parameters.circuit_version.to_le_bytes());
+        hasher.update(activation_epoch.to_le_bytes());
+        message_from(hasher)
+    }
+}
+
+/// Every parameter set with the epoch it became active, plus any announced upgrade.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParameterSchedule {
+    /// `(activation_epoch, parameters)`, ordered by epoch; the first set applies from genesis.
+    sets: Vec<(u64, ContractParameters)>,
+    pending: Option<ParameterUpgrade>,
+    delay: u64,
+}
+
+impl Default for ParameterSchedule {
+    fn default() -> Self {
+        Self::new(ContractParameters::default())
+    }
+}
+
+impl ParameterSchedule {
+    pub fn new(initial: ContractParameters) -> Self {
+        Self {
+            sets: vec![(0, initial)],
+            pending: None,
+            delay: DEFAULT_UPGRADE_DELAY,
+        }
+    }
+
+    /// Sets the minimum number of epochs between announcing and activating an upgrade.
+    pub fn with_delay(mut self, epochs: u64) -> Self {
+        self.delay = epochs;
+        self
+    }
+
+    pub fn current(&self) -> &ContractParameters {
+        &self.sets[self.sets.len() - 1].1
+    }
+
+    /// Gets the parameters in force during `epoch`.
+    pub fn at_epoch(&self, epoch: u64) -> &ContractParameters {
+        self.sets
+            .iter()
+            .rev()
+            .find(|(activation, _)| *activation <= epoch)
+            .map(|(_, parameters)| parameters)
+            .unwrap_or(&self.sets[0].1)
+    }
+
+    pub fn pending(&self) -> Option<&ParameterUpgrade> {
+        self.pending.as_ref()
+    }
+
+    /// Accepts an upgrade announced during `epoch` if it raises the version, is valid, and
+    /// leaves at least the delay before it activates. Signatures are checked by the caller.
+    pub fn announce(
+        &mut self,
+        upgrade: ParameterUpgrade,
+        epoch: u64,
+    ) -> Result<(), GovernanceError> {
+        self.activate(epoch);
+        if let Some(pending) = &self.pending {
+            return Err(GovernanceError::UpgradePending(pending.activation_epoch));
+        }
+        let current = self.current().version;
+        if upgrade.parameters.version <= current {
+            return Err(GovernanceError::VersionNotIncreasing {
+                current,
+                proposed: upgrade.parameters.version,
+            });
+        }
+        upgrade.parameters.validate()?;
+        let earliest = epoch.saturating_add(self.delay);
+        if upgrade.activation_epoch < earliest {
+            return Err(GovernanceError::TooSoon(upgrade.activation_epoch, earliest));
+        }
+        self.pending = Some(upgrade);
+        Ok(())
+    }
+
+    /// Moves a pending upgrade into the schedule once `epoch` reaches its activation, and
+    /// returns the newly active parameters.
+    pub fn activate(&mut self, epoch: u64) -> Option<ContractParameters> {
+        match self.pending.take() {
+            Some(upgrade) if upgrade.activation_epoch <= epoch => {
+                self.sets
+                    .push((upgrade.activation_epoch, upgrade.parameters));
+                Some(upgrade.parameters)
+            }
+            pending => {
+                self.pending = pending;
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn upgrade(version: u32, challenge_window: u32, activation_epoch: u64) -> ParameterUpgrade {
+        ParameterUpgrade {
+            parameters: ContractParameters {
+                version,
+                challenge_window,
+                ..ContractParameters::default()
+            },
+            activation_epoch,
+            signatures: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_upgrades_activate_after_the_delay() {
+        let mut schedule = ParameterSchedule::default().with_delay(3);
+        assert_eq!(
+            schedule.announce(upgrade(1, 10, 5), 1),
+            Err(GovernanceError::VersionNotIncreasing {
+                current: 1,
+                proposed: 1
+            })
+        );
+        assert_eq!(
+            schedule.announce(upgrade(2, 10, 3), 1),
+            Err(GovernanceError::TooSoon(3, 4))
+        );
+        assert!(matches!(
+            schedule.announce(upgrade(2, 0, 4), 1),
+            Err(GovernanceError::InvalidParameters(_))
+        ));
+        schedule.announce(upgrade(2, 10, 4), 1).unwrap();
+        assert_eq!(
+            schedule.announce(upgrade(3, 10, 9), 2),
+            Err(GovernanceError::UpgradePending(4))
+        );
+
+        assert_eq!(schedule.activate(3), None);
+        assert_eq!(schedule.current().version, 1);
+        assert_eq!(schedule.activate(4).map(|p| p.challenge_window), Some(10));
+        assert_eq!(
+            schedule.at_epoch(3).challenge_window,
+            DEFAULT_CHALLENGE_WINDOW
+        );
+        assert_eq!(schedule.at_epoch(4).version, 2);
+        assert!(schedule.pending().is_none());
+    }
+}
@@ -1,15 +1,18 @@
 // src/zkp/helpers.rs
 
-use std::time::{UNIX_EPOCH, SystemTime};
 use sha2::{Sha256, Digest};
 use curve25519_dalek::ristretto::RistrettoPoint;
-use curve25519_dalek::scalar::Scalar;
 use rand::rngs::OsRng;
 use rand::RngCore;
 use std::collections::HashMap;
-use anyhow::Result;
 
+use crate::clock::{Clock, SystemClock, TimePolicy};
+use crate::zkp::blinding::BlindingFactor;
 use crate::zkp::pedersen_parameters::PedersenParameters;
+use overpass_verify::{commitment, proof};
+use zeroize::Zeroize;
+
+pub use overpass_verify::merkle::{compute_merkle_root, hash_pair, verify_merkle_path};
 
 
 /// Type alias for bytes32.
@@ -27,27 +30,21 @@ pub fn generate_random_blinding() -> Bytes32 {
 }
 
 /// Computes Pedersen commitment.
-pub fn pedersen_commit(value: u64, blinding: Bytes32, hparams: &PedersenParameters) -> Bytes32 {
-    let value_scalar = Scalar::from(value);
-    let blinding_scalar = Scalar::from_bytes_mod_order(blinding);
-    let commitment = hparams.g * value_scalar + hparams.h * blinding_scalar;
-    hash_point(commitment)
+pub fn pedersen_commit(value: u64, mut blinding: Bytes32, hparams: &PedersenParameters) -> Bytes32 {
+    let blinding_scalar = BlindingFactor::from_bytes(&blinding);
+    blinding.zeroize();
+    commitment::commit(value, blinding_scalar.scalar(), &hparams.g, &hparams.h)
 }
 
 /// Hashes a RistrettoPoint to bytes32 using SHA256.
 pub fn hash_point(point: Point) -> Bytes32 {
-    let mut hasher = Sha256::new();
-    hasher.update(point.compress().as_bytes());
-    let result = hasher.finalize();
-    let mut hash = [0u8; 32];
-    hash.copy_from_slice(&result);
-    hash
+    commitment::hash_point(&point)
 }
 
 /// Computes the Merkle root from wallet roots.
-pub fn compute_global_root(wallet_roots: &HashMap<Bytes32, Bytes32>) -> Result<Bytes32, String> {
+pub fn compute_global_root(wallet_roots: &HashMap<Bytes32, Bytes32>) -> Bytes32 {
     let leaves: Vec<Bytes32> = wallet_roots.values().cloned().collect();
-    Ok(compute_merkle_root(leaves))
+    compute_merkle_root(leaves)
 }
 
 /// Computes the Merkle root from channel state.
@@ -62,41 +59,27 @@ pub fn compute_channel_root(channel_id: Bytes32, commitment: Bytes32, nonce: u64
     root
 }
 
-/// Computes Merkle root from list of leaves.
-pub fn compute_merkle_root(leaves: Vec<Bytes32>) -> Bytes32 {
-    if leaves.is_empty() {
-        return [0u8; 32];
-    }
-    let mut current_level = leaves;
-    while current_level.len() > 1 {
-        if current_level.len() % 2 != 0 {
-            current_level.push(*current_level.last().unwrap());
+/// Gets the sibling hashes from leaf `index` up to the root `compute_merkle_root` builds.
+pub fn merkle_path(leaves: Vec<Bytes32>, mut index: usize) -> Vec<Bytes32> {
+    let mut level = leaves;
+    let mut siblings = Vec::new();
+    while level.len() > 1 {
+        if level.len() % 2 != 0 {
+            level.push(*level.last().unwrap());
         }
-        current_level = current_level
+        siblings.push(level[index ^ 1]);
+        level = level
             .chunks(2)
             .map(|pair| hash_pair(pair[0], pair[1]))
             .collect();
+        index /= 2;
     }
-    current_level[0]
-}
-
-/// Hashes two bytes32 together to form parent node.
-pub fn hash_pair(left: Bytes32, right: Bytes32) -> Bytes32 {
-    let mut hasher = Sha256::new();
-    hasher.update(&left);
-    hasher.update(&right);
-    let result = hasher.finalize();
-    let mut parent = [0u8; 32];
-    parent.copy_from_slice(&result);
-    parent
+    siblings
 }
 
-/// Current Unix timestamp.
+/// Current Unix timestamp, from the system clock.
 pub fn current_timestamp() -> u64 {
-    let now = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .expect("Time went backwards");
-    now.as_secs()
+    SystemClock.now()
 }
 
 /// Represents a state proof for wallet updates.
@@ -124,9 +107,26 @@ pub fn verify_wallet_proof(
     proof: &StateProof,
     params: &PedersenParameters,
 ) -> bool {
-    // Verify timestamp is recent enough (within last hour)
-    let current_time = current_timestamp();
-    if current_time - proof.timestamp > 3600 {
+    verify_wallet_proof_at(
+        old_root,
+        new_root,
+        proof,
+        params,
+        current_timestamp(),
+        &TimePolicy::default(),
+    )
+}
+
+/// Verifies a wallet proof whose timestamp `policy` accepts at `now`.
+pub fn verify_wallet_proof_at(
+    old_root: &Bytes32,
+    new_root: &Bytes32,
+    proof: &StateProof,
+    params: &PedersenParameters,
+    now: u64,
+    policy: &TimePolicy,
+) -> bool {
+    if !policy.accepts(proof.timestamp, now) {
         return false;
     }
 
@@ -139,19 +139,7 @@ pub fn verify_wallet_proof(
     }
 
     // Verify the proof using the Pedersen parameters
-    let mut hasher = Sha256::new();
-    hasher.update(&proof.pi);
-    for input in &proof.public_inputs {
-        hasher.update(input);
-    }
-    hasher.update(params.g.compress().as_bytes());
-    hasher.update(params.h.compress().as_bytes());
-    
-    let result = hasher.finalize();
-    let mut expected = [0u8; 32];
-    expected.copy_from_slice(&result);
-    
-    proof.pi == expected
+    proof::verify_zk_proof(&proof.pi, &proof.public_inputs, &params.g, &params.h)
 }
 
 /// Verifies a zero-knowledge proof using Pedersen commitments.
@@ -160,23 +148,10 @@ pub fn verify_zk_proof(
     public_inputs: &[Bytes32],
     params: &PedersenParameters,
 ) -> bool {
-    if public_inputs.is_empty() {
-        return false;
-    }
-
-    let mut hasher = Sha256::new();
-    hasher.update(proof);
-    for input in public_inputs {
-        hasher.update(input);
-    }
-    hasher.update(params.g.compress().as_bytes());
-    hasher.update(params.h.compress().as_bytes());
-    
-    let result = hasher.finalize();
-    let mut expected = [0u8; 32];
-    expected.copy_from_slice(&result);
-    
-    proof == &expected
+    let valid = proof::verify_zk_proof(proof, public_inputs, &params.g, &params.h);
+    #[cfg(feature = "metrics")]
+    crate::services::metrics::global().record_verification("state", valid);
+    valid
 }
 
 /// Generates a zero-knowledge proof of state transition.
@@ -186,18 +161,27 @@ pub fn generate_state_proof(
     merkle_root: Bytes32,
     params: &PedersenParameters,
 ) -> StateProof {
-    let mut hasher = Sha256::new();
-    hasher.update(&old_commitment);
-    hasher.update(&new_commitment);
-    hasher.update(&merkle_root);
-    
-    let timestamp = current_timestamp();
-    hasher.update(&timestamp.to_le_bytes());
+    generate_state_proof_at(
+        old_commitment,
+        new_commitment,
+        merkle_root,
+        params,
+        current_timestamp(),
+    )
+}
+
+/// Generates a proof of state transition stamped with `timestamp`.
+pub fn generate_state_proof_at(
+    old_commitment: Bytes32,
+    new_commitment: Bytes32,
+    merkle_root: Bytes32,
+    params: &PedersenParameters,
+    timestamp: u64,
+) -> StateProof {
+    #[cfg(feature = "metrics")]
+    let _timer = crate::services::metrics::global().proof_timer("state");
+    let pi = proof::transition_hash(&old_commitment, &new_commitment, &merkle_root, timestamp);
 
-    let result = hasher.finalize();
-    let mut pi = [0u8; 32];
-    pi.copy_from_slice(&result);
-    
     StateProof {
         pi,
         public_inputs: vec![old_commitment, new_commitment, merkle_root],
@@ -252,6 +236,25 @@ mod tests {
         assert_eq!(commitment, commitment2);
     }
 
+    #[test]
+    fn test_commitments_and_proofs_match_the_browser_wallet() {
+        // The same vectors `overpass_wasm` and `overpass_verify` check.
+        let params = PedersenParameters::default();
+        assert_eq!(
+            (params.g, params.h),
+            overpass_verify::commitment::default_generators()
+        );
+        assert_eq!(
+            hex::encode(pedersen_commit(100, [7u8; 32], &params)),
+            "c81065ff7cbc782cba44664ba13e490ece323abd030785a1b8c0c18be050aebd"
+        );
+        let proof = generate_state_proof_at([1u8; 32], [2u8; 32], [3u8; 32], &params, 1_700_000_000);
+        assert_eq!(
+            hex::encode(proof.pi),
+            "6853638facea72d0565427080e3084fc7bcde726abc7efdba96bdbfb83e096e3"
+        );
+    }
+
     #[test]
     fn test_verify_wallet_proof() {
         let params = PedersenParameters::default();
@@ -270,4 +273,15 @@ mod tests {
         // Wrong roots should fail verification
         assert!(!verify_wallet_proof(&[4u8; 32], &new_root, &proof, &params));
     }
+
+    #[test]
+    fn test_proofs_are_stamped_and_checked_against_the_given_time() {
+        let params = PedersenParameters::default();
+        let proof = generate_state_proof_at([1u8; 32], [2u8; 32], [3u8; 32], &params, 10_000);
+        assert_eq!(proof.timestamp, 10_000);
+
+        // A proof from the future is refused rather than underflowing the age check.
+        let policy = TimePolicy::default();
+        assert!(!verify_wallet_proof_at(&[1u8; 32], &[2u8; 32], &proof, &params, 0, &policy));
+    }
 }
\ No newline at end of file
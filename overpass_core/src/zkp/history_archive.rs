@@ -0,0 +1,325 @@
+// src/zkp/history_archive.rs
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+use crate::bitcoin::triggers::Interval;
+use crate::zkp::compressed_transaction::{CompressedTransaction, DeltaError};
+use crate::zkp::helpers::{compute_merkle_root, Bytes32};
+
+const LEAF_DOMAIN: &[u8] = b"overpass/archive-block/v1";
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ArchiveError {
+    #[error("Archive block encoding is truncated")]
+    Truncated,
+    #[error("Archive block records: {0}")]
+    Records(#[from] DeltaError),
+    #[error("Archive block for epoch {0} does not match its root")]
+    RootMismatch(u64),
+}
+
+/// When a channel counts as dormant, and how long an archive epoch is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchivePolicy {
+    /// Inactivity after which a channel's history moves into an archive block.
+    pub dormancy: Interval,
+    pub epoch_secs: u64,
+}
+
+impl ArchivePolicy {
+    /// Gets the epoch a channel last active at `unix` is archived in.
+    pub fn epoch_of(&self, unix: u64) -> u64 {
+        unix / self.epoch_secs.max(1)
+    }
+}
+
+/// Where one channel's records sit in an archive block.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChannelSpan {
+    pub channel_id: Bytes32,
+    pub start: u32,
+    pub len: u32,
+}
+
+/// The histories of every channel that went dormant in one epoch, in a single buffer
+/// indexed by channel id.
+///
+/// The root is the Merkle root over one leaf per channel, hashing the channel id with
+/// its records, so a block can be checked before any record is trusted.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ArchiveBlock {
+    pub epoch: u64,
+    index: Vec<ChannelSpan>,
+    records: Vec<CompressedTransaction>,
+    root: Bytes32,
+}
+
+impl ArchiveBlock {
+    pub fn new(epoch: u64, histories: BTreeMap<Bytes32, Vec<CompressedTransaction>>) -> Self {
+        let mut index = Vec::with_capacity(histories.len());
+        let mut records = Vec::new();
+        for (channel_id, history) in histories {
+            index.push(ChannelSpan {
+                channel_id,
+                start: records.len() as u32,
+                len: history.len() as u32,
+            });
+            records.extend(history);
+        }
+        let mut block = Self {
+            epoch,
+            index,
+            records,
+            root: [0u8; 32],
+        };
+        block.root = block.compute_root();
+        block
+    }
+
+    /// Gets the archived channels, ordered by id.
+    pub fn channels(&self) -> impl Iterator<Item = &Bytes32> {
+        self.index.iter().map(|span| &span.channel_id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    pub fn root(&self) -> Bytes32 {
+        self.root
+    }
+
+    /// Gets an archived channel's history, oldest first.
+    pub fn history(&self, channel_id: &Bytes32) -> Option<&[CompressedTransaction]> {
+        let position = self
+            .index
+            .binary_search_by(|span| span.channel_id.cmp(channel_id))
+            .ok()?;
+        let span = &self.index[position];
+        self.records
+            .get(span.start as usize..(span.start + span.len) as usize)
+    }
+
+    /// Takes a channel's history out of the block.
+    pub fn remove(&mut self, channel_id: &Bytes32) -> Option<Vec<CompressedTransaction>> {
+        let mut histories = self.histories();
+        let history = histories.remove(channel_id)?;
+        *self = Self::new(self.epoch, histories);
+        Some(history)
+    }
+
+    /// Splits the block back into per-channel histories.
+    pub fn histories(&self) -> BTreeMap<Bytes32, Vec<CompressedTransaction>> {
+        self.index
+            .iter()
+            .filter_map(|span| Some((span.channel_id, self.history(&span.channel_id)?.to_vec())))
+            .collect()
+    }
+
+    /// Checks the index against the records and the records against the root.
+    pub fn verify(&self) -> Result<(), ArchiveError> {
+        let ordered = self
+            .index
+            .windows(2)
+            .all(|pair| pair[0].channel_id < pair[1].channel_id);
+        let covered = self.index.iter().try_fold(0u32, |next, span| {
+            (next == span.start).then(|| span.start.checked_add(span.len))?
+        }) == Some(self.records.len() as u32);
+        if !ordered || !covered || self.compute_root() != self.root {
+            return Err(ArchiveError::RootMismatch(self.epoch));
+        }
+        Ok(())
+    }
+
+    /// Encodes the block: the epoch, the channel count and each channel's id and record
+    /// count, every record with its proof, then the root.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&self.epoch.to_le_bytes());
+        out.extend_from_slice(&(self.index.len() as u32).to_le_bytes());
+        for span in &self.index {
+            out.extend_from_slice(&span.channel_id);
+            out.extend_from_slice(&span.len.to_le_bytes());
+        }
+        out.extend_from_slice(&CompressedTransaction::encode_proven(&self.records));
+        out.extend_from_slice(&self.root);
+        out
+    }
+
+    /// Decodes `to_bytes` output and verifies it.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ArchiveError> {
+        let take = |at: usize, len: usize| bytes.get(at..at + len).ok_or(ArchiveError::Truncated);
+        let epoch = u64::from_le_bytes(take(0, 8)?.try_into().unwrap());
+        let channels = u32::from_le_bytes(take(8, 4)?.try_into().unwrap()) as usize;
+        let mut at = 12;
+        let mut index = Vec::with_capacity(channels.min(bytes.len() / 36));
+        let mut start = 0u32;
+        for _ in 0..channels {
+            let channel_id = take(at, 32)?.try_into().unwrap();
+            let len = u32::from_le_bytes(take(at + 32, 4)?.try_into().unwrap());
+            index.push(ChannelSpan {
+                channel_id,
+                start,
+                len,
+            });
+            start = start.checked_add(len).ok_or(ArchiveError::Truncated)?;
+            at += 36;
+        }
+        let root_at = bytes
+            .len()
+            .checked_sub(32)
+            .filter(|root_at| *root_at >= at)
+            .ok_or(ArchiveError::Truncated)?;
+        let records =
+            CompressedTransaction::decode_proven(&bytes[at..root_at]).map_err(|e| match e {
+                DeltaError::Truncated => ArchiveError::Truncated,
+                e => e.into(),
+            })?;
+        if records.len() != start as usize {
+            return Err(ArchiveError::Truncated);
+        }
+        let root = take(root_at, 32)?.try_into().unwrap();
+        let block = Self {
+            epoch,
+            index,
+            records,
+            root,
+        };
+        block.verify()?;
+        Ok(block)
+    }
+
+    fn compute_root(&self) -> Bytes32 {
+        compute_merkle_root(
+            self.index
+                .iter()
+                .map(|span| {
+                    Sha256::new()
+                        .chain_update(LEAF_DOMAIN)
+                        .chain_update(span.channel_id)
+                        .chain_update(CompressedTransaction::encode_proven(
+                            self.history(&span.channel_id).unwrap_or_default(),
+                        ))
+                        .finalize()
+                        .into()
+                })
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::triggers::ChainTime;
+    use crate::zkp::history_replay::verify_chain;
+    use crate::zkp::mobile_optimized_storage::MobileOptimizedStorage;
+    use crate::zkp::state_proof::StateProof;
+    use crate::zkp::tx_metadata::TxMetadata;
+
+    const DAY: u64 = 24 * 3600;
+    const START: u64 = 1_700_006_400;
+
+    fn store(storage: &mut MobileOptimizedStorage, channel: u8, updates: u8, at: u64) {
+        let offset = storage
+            .history(&[channel; 32])
+            .last()
+            .map_or(0, |tx| tx.new_commitment[0]);
+        for i in offset..offset + updates {
+            let proof = StateProof {
+                pi: [i; 32],
+                public_inputs: Vec::new(),
+                timestamp: at + u64::from(i),
+            };
+            storage
+                .store_transaction([channel; 32], [i; 32], [i + 1; 32], proof, TxMetadata::None)
+                .unwrap();
+        }
+    }
+
+    fn storage() -> MobileOptimizedStorage {
+        MobileOptimizedStorage::new(4, 30 * DAY).with_archive_policy(ArchivePolicy {
+            dormancy: Interval::secs(2 * DAY),
+            epoch_secs: DAY,
+        })
+    }
+
+    #[test]
+    fn test_dormant_channels_share_an_epoch_block() {
+        let mut storage = storage();
+        for channel in 1..=3 {
+            store(
+                &mut storage,
+                channel,
+                3 + channel,
+                START + u64::from(channel) * 3600,
+            );
+        }
+        store(&mut storage, 4, 2, START + 4 * DAY);
+        let before: Vec<_> = (1..=4)
+            .map(|c| storage.history(&[c; 32]).to_vec())
+            .collect();
+
+        assert!(storage.prune(ChainTime::new(START + 5 * DAY, 0)).is_empty());
+        let blocks: Vec<_> = storage.archive_blocks().collect();
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].epoch, START / DAY);
+        assert_eq!(
+            blocks[0].channels().copied().collect::<Vec<_>>(),
+            vec![[1u8; 32], [2u8; 32], [3u8; 32]]
+        );
+        assert_eq!(blocks[0].verify(), Ok(()));
+        assert!(storage.is_archived(&[2u8; 32]));
+        assert!(!storage.is_archived(&[4u8; 32]));
+        for (channel, history) in (1..=4).zip(&before) {
+            assert_eq!(storage.history(&[channel; 32]), history.as_slice());
+        }
+
+        let bytes = blocks[0].to_bytes();
+        assert_eq!(ArchiveBlock::from_bytes(&bytes).as_ref(), Ok(blocks[0]));
+        let mut tampered = bytes.clone();
+        tampered[12 + 3 * 36 + 20] ^= 1;
+        assert_eq!(
+            ArchiveBlock::from_bytes(&tampered),
+            Err(ArchiveError::RootMismatch(START / DAY))
+        );
+        assert_eq!(
+            ArchiveBlock::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(ArchiveError::Truncated)
+        );
+
+        // New activity brings a channel back out of its block.
+        store(&mut storage, 2, 1, START + 5 * DAY);
+        assert!(!storage.is_archived(&[2u8; 32]));
+        assert_eq!(storage.archive_blocks().next().unwrap().len(), 2);
+        let history = storage.history(&[2u8; 32]);
+        assert_eq!(history[..before[1].len()], before[1][..]);
+        assert_eq!(verify_chain(history).unwrap().updates, 6);
+    }
+
+    #[test]
+    fn test_archived_channels_expire_with_retention() {
+        let mut storage = storage();
+        store(&mut storage, 1, 2, START);
+        store(&mut storage, 2, 2, START + 3 * DAY);
+        storage.prune(ChainTime::new(START + 6 * DAY, 0));
+        let epochs: Vec<_> = storage.archive_blocks().map(|block| block.epoch).collect();
+        assert_eq!(epochs, vec![START / DAY, START / DAY + 3]);
+
+        storage.import_history([2u8; 32], Vec::new());
+        assert_eq!(storage.archive_blocks().count(), 1);
+        assert_eq!(
+            storage.prune(ChainTime::new(START + 31 * DAY, 0)),
+            vec![[1u8; 32]]
+        );
+        assert_eq!(storage.archive_blocks().count(), 0);
+        assert!(storage.history(&[1u8; 32]).is_empty());
+    }
+}
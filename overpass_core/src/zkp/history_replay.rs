@@ -0,0 +1,322 @@
+// src/zkp/history_replay.rs
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::zkp::compressed_transaction::{CompressedTransaction, ProofRef};
+use crate::zkp::helpers::{compute_merkle_root, Bytes32};
+
+/// Where and how a compressed history stops chaining.
+#[derive(Error, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChainError {
+    #[error(
+        "Gap before record {index}: it starts from {}, not {}",
+        hex::encode(.found),
+        hex::encode(.expected)
+    )]
+    Gap {
+        index: usize,
+        expected: Bytes32,
+        found: Bytes32,
+    },
+    #[error("Record {index} at {timestamp} is older than the record before it at {previous}")]
+    Reordered {
+        index: usize,
+        previous: u64,
+        timestamp: u64,
+    },
+    #[error(
+        "Record {index} has Merkle root {}, not {}",
+        hex::encode(.found),
+        hex::encode(.expected)
+    )]
+    Tampered {
+        index: usize,
+        expected: Bytes32,
+        found: Bytes32,
+    },
+    #[error("Checkpoint {0} does not span the records since the last checkpoint")]
+    InvalidCheckpoint(usize),
+    #[error("Record {0} carries a proof of another transition")]
+    InvalidProof(usize),
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ReplayError {
+    #[error(transparent)]
+    Chain(#[from] ChainError),
+    #[error("History ends at {}, not the channel's commitment", hex::encode(.0))]
+    HeadMismatch(Bytes32),
+}
+
+/// What an intact compressed history covers.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainSummary {
+    pub records: usize,
+    /// Records that are channel updates, not compression checkpoints.
+    pub updates: usize,
+    pub checkpoints: usize,
+    /// Updates whose embedded proof was checked.
+    pub proven: usize,
+    /// Commitment the history starts from, `None` for an empty history.
+    pub initial_commitment: Option<Bytes32>,
+    /// Commitment of the latest update.
+    pub head: Option<Bytes32>,
+    pub first_timestamp: Option<u64>,
+    pub last_timestamp: Option<u64>,
+}
+
+/// A channel state as far as its compressed record shows it: the balance itself stays
+/// behind the commitment.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplayedState {
+    /// Updates applied since the start of the history.
+    pub sequence: u64,
+    pub timestamp: u64,
+    pub commitment: Bytes32,
+    pub metadata_hash: Bytes32,
+    pub merkle_root: Bytes32,
+}
+
+/// The states a channel went through, rebuilt from its compressed history.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Replay {
+    pub summary: ChainSummary,
+    pub states: Vec<ReplayedState>,
+}
+
+impl Replay {
+    /// Gets the commitment of the latest state.
+    pub fn head(&self) -> Option<Bytes32> {
+        self.summary.head.or(self.summary.initial_commitment)
+    }
+}
+
+/// Checks that a channel's history, oldest first, is one unbroken chain.
+///
+/// Every record starts from the commitment the update before it ended on, carries the
+/// Merkle root storage gave it, and is no older than the record before it.
+///
+/// An update that embeds its proof must carry one over its own commitments; proofs
+/// referenced by digest are left to whoever holds them.
+///
+/// Storage inserts a checkpoint when it compresses a run of records, just before the
+/// record that completed the run: it spans from the run's first old commitment to that
+/// record's new one, and both carry the root from before the checkpoint.
+pub fn verify_chain(history: &[CompressedTransaction]) -> Result<ChainSummary, ChainError> {
+    walk(history, |_| ())
+}
+
+/// Verifies a channel's history like [`verify_chain`] and rebuilds the state after each
+/// update.
+pub fn replay(history: &[CompressedTransaction]) -> Result<Replay, ChainError> {
+    let mut states = Vec::new();
+    let summary = walk(history, |tx| {
+        states.push(ReplayedState {
+            sequence: states.len() as u64 + 1,
+            timestamp: tx.timestamp,
+            commitment: tx.new_commitment,
+            metadata_hash: tx.metadata_hash,
+            merkle_root: tx.merkle_root,
+        })
+    })?;
+    Ok(Replay { summary, states })
+}
+
+fn walk(
+    history: &[CompressedTransaction],
+    mut on_update: impl FnMut(&CompressedTransaction),
+) -> Result<ChainSummary, ChainError> {
+    let mut summary = ChainSummary {
+        records: history.len(),
+        initial_commitment: history.first().map(|tx| tx.old_commitment),
+        first_timestamp: history.first().map(|tx| tx.timestamp),
+        last_timestamp: history.last().map(|tx| tx.timestamp),
+        ..ChainSummary::default()
+    };
+    let mut roots = Vec::with_capacity(history.len());
+    let mut run_start = 0;
+    let mut after_checkpoint: Option<Bytes32> = None;
+    for (index, tx) in history.iter().enumerate() {
+        let expected = after_checkpoint
+            .take()
+            .unwrap_or_else(|| compute_merkle_root(roots.clone()));
+        if tx.merkle_root != expected {
+            return Err(ChainError::Tampered {
+                index,
+                expected,
+                found: tx.merkle_root,
+            });
+        }
+        roots.push(tx.merkle_root);
+        if let Some(prev) = index.checked_sub(1).map(|prev| &history[prev]) {
+            if tx.timestamp < prev.timestamp {
+                return Err(ChainError::Reordered {
+                    index,
+                    previous: prev.timestamp,
+                    timestamp: tx.timestamp,
+                });
+            }
+        }
+        let head = summary.head.unwrap_or(tx.old_commitment);
+        if head == tx.old_commitment {
+            if let Some(ProofRef::Embedded(_)) = tx.proof {
+                if !tx.verify_proof(None) {
+                    return Err(ChainError::InvalidProof(index));
+                }
+                summary.proven += 1;
+            }
+            summary.head = Some(tx.new_commitment);
+            summary.updates += 1;
+            on_update(tx);
+            continue;
+        }
+        let next = history.get(index + 1);
+        if next.is_none_or(|next| next.new_commitment != tx.new_commitment) {
+            return Err(ChainError::Gap {
+                index,
+                expected: head,
+                found: tx.old_commitment,
+            });
+        }
+        if tx.old_commitment != history[run_start].old_commitment {
+            return Err(ChainError::InvalidCheckpoint(index));
+        }
+        summary.checkpoints += 1;
+        after_checkpoint = Some(expected);
+        // The record after the checkpoint closes the compressed run; the next run
+        // starts after it.
+        run_start = index + 2;
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+    use crate::zkp::mobile_optimized_storage::{MobileOptimizedStorage, ProofRetention};
+    use crate::zkp::state_proof::StateProof;
+    use crate::zkp::tx_metadata::TxMetadata;
+    use crate::zkp::wallet_contract::WalletContractError;
+
+    fn history(updates: u8, compression_threshold: usize) -> Vec<CompressedTransaction> {
+        let mut storage = MobileOptimizedStorage::new(compression_threshold, 30 * 24 * 3600);
+        for i in 0..updates {
+            let proof = StateProof {
+                pi: [i; 32],
+                public_inputs: Vec::new(),
+                timestamp: 1_700_000_000 + u64::from(i),
+            };
+            storage
+                .store_transaction([1u8; 32], [i; 32], [i + 1; 32], proof, TxMetadata::None)
+                .unwrap();
+        }
+        storage.history(&[1u8; 32]).to_vec()
+    }
+
+    #[test]
+    fn test_replay_rebuilds_states_across_checkpoints() {
+        let history = history(8, 3);
+        let replay = replay(&history).unwrap();
+        assert_eq!(replay.summary, verify_chain(&history).unwrap());
+        assert_eq!(
+            (replay.summary.records, replay.summary.checkpoints),
+            (10, 2)
+        );
+        assert_eq!(replay.summary.initial_commitment, Some([0u8; 32]));
+        assert_eq!(replay.states.len(), 8);
+        assert_eq!(replay.states[4].sequence, 5);
+        assert_eq!(replay.head(), Some([8u8; 32]));
+        assert_eq!(super::replay(&[]), Ok(Replay::default()));
+
+        let mut broken = history.clone();
+        broken[1].old_commitment = [7u8; 32];
+        assert_eq!(
+            verify_chain(&broken),
+            Err(ChainError::Gap {
+                index: 1,
+                expected: [1u8; 32],
+                found: [7u8; 32]
+            })
+        );
+        let mut gapped = history.clone();
+        gapped.remove(4);
+        assert!(matches!(
+            verify_chain(&gapped),
+            Err(ChainError::Tampered { index: 4, .. })
+        ));
+        let mut rerooted = history.clone();
+        rerooted[5].merkle_root = [0u8; 32];
+        assert!(matches!(
+            verify_chain(&rerooted),
+            Err(ChainError::Tampered { index: 5, found, .. }) if found == [0u8; 32]
+        ));
+        let mut checkpoint = history.clone();
+        checkpoint[2].old_commitment = [1u8; 32];
+        assert_eq!(
+            verify_chain(&checkpoint),
+            Err(ChainError::InvalidCheckpoint(2))
+        );
+        let mut reordered = history;
+        reordered[3].timestamp = 0;
+        assert_eq!(
+            verify_chain(&reordered),
+            Err(ChainError::Reordered {
+                index: 3,
+                previous: 1_700_000_002,
+                timestamp: 0
+            })
+        );
+    }
+
+    #[test]
+    fn test_wallet_replays_channel_history() -> Result<(), WalletContractError> {
+        let mut wallet = test_util::wallet([1u8; 32]);
+        let channel_id = wallet.open_channel(100, [7u8; 32], Vec::new())?;
+        let opened = wallet.get_channel(&channel_id).unwrap().merkle_root;
+        wallet.rotate_keys()?;
+        wallet.rotate_keys()?;
+
+        let replay = wallet.replay_channel(&channel_id)?;
+        assert_eq!(replay.summary.initial_commitment, Some(opened));
+        assert_eq!(
+            replay.states.len() as u64,
+            wallet.get_channel(&channel_id).unwrap().nonce
+        );
+
+        assert_eq!(replay.summary.proven, 0);
+
+        let mut history = wallet.storage.history(&channel_id).to_vec();
+        history.pop();
+        wallet.storage.import_history(channel_id, history);
+        assert!(matches!(
+            wallet.replay_channel(&channel_id),
+            Err(WalletContractError::ReplayError(ReplayError::HeadMismatch(
+                _
+            )))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_embedded_proofs_verify_with_the_history() -> Result<(), WalletContractError> {
+        let mut wallet = test_util::wallet([1u8; 32]);
+        wallet.storage = MobileOptimizedStorage::new(100, 30 * 24 * 3600)
+            .with_proof_retention(ProofRetention::Embedded);
+        let channel_id = wallet.open_channel(100, [7u8; 32], Vec::new())?;
+        wallet.rotate_keys()?;
+        wallet.rotate_keys()?;
+
+        let history = wallet.storage.history(&channel_id).to_vec();
+        assert!(history.iter().all(|tx| tx.verify_proof(None)));
+        assert_eq!(wallet.replay_channel(&channel_id)?.summary.proven, 2);
+
+        let mut forged = history;
+        if let Some(ProofRef::Embedded(proof)) = &mut forged[1].proof {
+            proof.public_inputs[1] = [9u8; 32];
+        }
+        assert_eq!(verify_chain(&forged), Err(ChainError::InvalidProof(1)));
+        Ok(())
+    }
+}
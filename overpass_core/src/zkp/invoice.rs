@@ -0,0 +1,359 @@
+// src/zkp/invoice.rs
+
+use bech32::{FromBase32, ToBase32, Variant};
+use bitcoin::secp256k1::{schnorr, KeyPair, Message, Secp256k1, XOnlyPublicKey};
+use bitcoin::Network;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::zkp::helpers::Bytes32;
+use crate::zkp::operator_keys::message_from;
+
+const INVOICE_VERSION: u8 = 1;
+/// Most channel hints an invoice carries.
+pub const MAX_CHANNEL_HINTS: usize = 8;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum InvoiceError {
+    #[error("Invalid encoding: {0}")]
+    Encoding(String),
+    #[error("Unknown invoice prefix {0}")]
+    UnknownPrefix(String),
+    #[error("Unsupported invoice version {0}")]
+    UnsupportedVersion(u8),
+    #[error("Malformed payload: {0}")]
+    Malformed(&'static str),
+    #[error("Invoice signature does not match the payee key")]
+    InvalidSignature,
+    #[error("Invoice is for {0}")]
+    WrongNetwork(Network),
+    #[error("Invoice expired at {0}")]
+    Expired(u64),
+    #[error("Invoice has already been paid")]
+    AlreadyPaid,
+}
+
+/// What an invoice asks to be paid, before the payee signs it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvoiceTerms {
+    pub amount: u64,
+    /// SHA-256 of a preimage the payee reveals once paid.
+    pub payment_hash: Bytes32,
+    /// Channels the payee would rather be paid over, preferred first.
+    pub channel_hints: Vec<Bytes32>,
+    /// Unix time in seconds.
+    pub created_at: u64,
+    pub expiry_secs: u64,
+    pub memo: Option<String>,
+}
+
+/// A signed request for payment, passed out of band as a bech32m string.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Invoice {
+    pub network: Network,
+    /// Wallet id of the payee, which payers know it by as a counterparty.
+    pub payee: Bytes32,
+    pub payee_key: XOnlyPublicKey,
+    pub terms: InvoiceTerms,
+    /// Signature by `payee_key` over everything above.
+    pub signature: schnorr::Signature,
+}
+
+impl Invoice {
+    /// Signs `terms` as `payee` with `key`.
+    pub fn sign(
+        network: Network,
+        payee: Bytes32,
+        key: &KeyPair,
+        terms: InvoiceTerms,
+    ) -> Result<Self, InvoiceError> {
+        let payee_key = key.x_only_public_key().0;
+        let body = encode_body(&payee, &payee_key, &terms)?;
+        Ok(Self {
+            network,
+            payee,
+            payee_key,
+            terms,
+            signature: Secp256k1::new().sign_schnorr(&signing_message(network, &body), key),
+        })
+    }
+
+    /// Checks that the payee key signed the invoice.
+    pub fn verify(&self) -> bool {
+        let Ok(body) = encode_body(&self.payee, &self.payee_key, &self.terms) else {
+            return false;
+        };
+        Secp256k1::verification_only()
+            .verify_schnorr(
+                &self.signature,
+                &signing_message(self.network, &body),
+                &self.payee_key,
+            )
+            .is_ok()
+    }
+
+    pub fn expires_at(&self) -> u64 {
+        self.terms.created_at.saturating_add(self.terms.expiry_secs)
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        now > self.expires_at()
+    }
+
+    /// Encodes the invoice as bech32m under its network's prefix.
+    pub fn encode(&self) -> Result<String, InvoiceError> {
+        let mut bytes = encode_body(&self.payee, &self.payee_key, &self.terms)?;
+        bytes.extend_from_slice(self.signature.as_ref());
+        bech32::encode(prefix(self.network), bytes.to_base32(), Variant::Bech32m)
+            .map_err(|e| InvoiceError::Encoding(e.to_string()))
+    }
+
+    /// Parses an encoded invoice. The signature is parsed but not checked; see
+    /// [`Self::verify`].
+    pub fn decode(encoded: &str) -> Result<Self, InvoiceError> {
+        let (hrp, data, variant) =
+            bech32::decode(encoded).map_err(|e| InvoiceError::Encoding(e.to_string()))?;
+        if variant != Variant::Bech32m {
+            return Err(InvoiceError::Encoding("expected bech32m".into()));
+        }
+        let network = network_for(&hrp).ok_or(InvoiceError::UnknownPrefix(hrp))?;
+        let bytes =
+            Vec::<u8>::from_base32(&data).map_err(|e| InvoiceError::Encoding(e.to_string()))?;
+        let mut reader = Reader(&bytes);
+        let version = reader.u8()?;
+        if version != INVOICE_VERSION {
+            return Err(InvoiceError::UnsupportedVersion(version));
+        }
+        let payee = reader.bytes32()?;
+        let payee_key = XOnlyPublicKey::from_slice(reader.take(32)?)
+            .map_err(|_| InvoiceError::Malformed("payee key"))?;
+        let amount = reader.u64()?;
+        let payment_hash = reader.bytes32()?;
+        let created_at = reader.u64()?;
+        let expiry_secs = reader.u64()?;
+        let hints = reader.u8()? as usize;
+        if hints > MAX_CHANNEL_HINTS {
+            return Err(InvoiceError::Malformed("too many channel hints"));
+        }
+        let channel_hints = (0..hints)
+            .map(|_| reader.bytes32())
+            .collect::<Result<_, _>>()?;
+        let memo = reader.string()?;
+        let signature = schnorr::Signature::from_slice(reader.take(64)?)
+            .map_err(|_| InvoiceError::Malformed("signature"))?;
+        if !reader.0.is_empty() {
+            return Err(InvoiceError::Malformed("trailing bytes"));
+        }
+        Ok(Self {
+            network,
+            payee,
+            payee_key,
+            terms: InvoiceTerms {
+                amount,
+                payment_hash,
+                channel_hints,
+                created_at,
+                expiry_secs,
+                memo,
+            },
+            signature,
+        })
+    }
+}
+
+/// Gets the payment hash that commits to `preimage`.
+pub fn payment_hash(preimage: &Bytes32) -> Bytes32 {
+    Sha256::digest(preimage).into()
+}
+
+fn prefix(network: Network) -> &'static str {
+    match network {
+        Network::Bitcoin => "ovp",
+        Network::Testnet => "ovpt",
+        Network::Signet => "ovps",
+        _ => "ovprt",
+    }
+}
+
+fn network_for(prefix: &str) -> Option<Network> {
+    match prefix {
+        "ovp" => Some(Network::Bitcoin),
+        "ovpt" => Some(Network::Testnet),
+        "ovps" => Some(Network::Signet),
+        "ovprt" => Some(Network::Regtest),
+        _ => None,
+    }
+}
+
+/// Hashes the body under the network's prefix, so a signature does not carry over to
+/// another network.
+fn signing_message(network: Network, body: &[u8]) -> Message {
+    let mut hasher = Sha256::new();
+    hasher.update(b"overpass/invoice/v1");
+    hasher.update(prefix(network));
+    hasher.update(body);
+    message_from(hasher)
+}
+
+/// Lays out everything but the signature.
+fn encode_body(
+    payee: &Bytes32,
+    payee_key: &XOnlyPublicKey,
+    terms: &InvoiceTerms,
+) -> Result<Vec<u8>, InvoiceError> {
+    if terms.channel_hints.len() > MAX_CHANNEL_HINTS {
+        return Err(InvoiceError::Malformed("too many channel hints"));
+    }
+    let mut bytes = vec![INVOICE_VERSION];
+    bytes.extend_from_slice(payee);
+    bytes.extend_from_slice(&payee_key.serialize());
+    bytes.extend_from_slice(&terms.amount.to_le_bytes());
+    bytes.extend_from_slice(&terms.payment_hash);
+    bytes.extend_from_slice(&terms.created_at.to_le_bytes());
+    bytes.extend_from_slice(&terms.expiry_secs.to_le_bytes());
+    bytes.push(terms.channel_hints.len() as u8);
+    for hint in &terms.channel_hints {
+        bytes.extend_from_slice(hint);
+    }
+    push_string(&mut bytes, terms.memo.as_deref())?;
+    Ok(bytes)
+}
+
+/// Appends an optional string with a two-byte length; `None` and empty read back as `None`.
+pub(crate) fn push_string(bytes: &mut Vec<u8>, value: Option<&str>) -> Result<(), InvoiceError> {
+    let value = value.unwrap_or_default().as_bytes();
+    let len = u16::try_from(value.len()).map_err(|_| InvoiceError::Malformed("string"))?;
+    bytes.extend_from_slice(&len.to_le_bytes());
+    bytes.extend_from_slice(value);
+    Ok(())
+}
+
+/// Reads the fields of a binary encoded payload in order.
+pub(crate) struct Reader<'a>(pub(crate) &'a [u8]);
+
+impl<'a> Reader<'a> {
+    pub(crate) fn take(&mut self, len: usize) -> Result<&'a [u8], InvoiceError> {
+        if self.0.len() < len {
+            return Err(InvoiceError::Malformed("truncated"));
+        }
+        let (head, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(head)
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, InvoiceError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, InvoiceError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn bytes32(&mut self) -> Result<Bytes32, InvoiceError> {
+        Ok(self.take(32)?.try_into().unwrap())
+    }
+
+    pub(crate) fn string(&mut self) -> Result<Option<String>, InvoiceError> {
+        let len = u16::from_le_bytes(self.take(2)?.try_into().unwrap()) as usize;
+        if len == 0 {
+            return Ok(None);
+        }
+        String::from_utf8(self.take(len)?.to_vec())
+            .map(Some)
+            .map_err(|_| InvoiceError::Malformed("string"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+    use crate::zkp::wallet_contract::WalletContractError;
+
+    fn terms() -> InvoiceTerms {
+        InvoiceTerms {
+            amount: 1_500,
+            payment_hash: payment_hash(&[4u8; 32]),
+            channel_hints: vec![[5u8; 32]],
+            created_at: 1_000,
+            expiry_secs: 600,
+            memo: Some("order #12".into()),
+        }
+    }
+
+    #[test]
+    fn test_invoice_round_trips_and_is_signed() {
+        let key = test_util::key_manager().invoice_key().unwrap();
+        let invoice = Invoice::sign(Network::Regtest, [9u8; 32], &key, terms()).unwrap();
+        assert!(invoice.verify());
+        let encoded = invoice.encode().unwrap();
+        assert!(encoded.starts_with("ovprt1"));
+        assert_eq!(Invoice::decode(&encoded).unwrap(), invoice);
+        assert_eq!(Invoice::decode(&encoded.to_uppercase()).unwrap(), invoice);
+        assert_eq!(invoice.expires_at(), 1_600);
+        assert!(!invoice.is_expired(1_600) && invoice.is_expired(1_601));
+
+        let mut raised = invoice.clone();
+        raised.terms.amount += 1;
+        assert!(!raised.verify());
+        let mut moved = invoice.clone();
+        moved.network = Network::Bitcoin;
+        assert!(!moved.verify());
+
+        let mut corrupted = encoded.into_bytes();
+        let at = corrupted.len() - 10;
+        corrupted[at] = if corrupted[at] == b'q' { b'p' } else { b'q' };
+        assert!(matches!(
+            Invoice::decode(std::str::from_utf8(&corrupted).unwrap()),
+            Err(InvoiceError::Encoding(_))
+        ));
+        let foreign = bech32::encode("lnbc", [0u8; 4].to_base32(), Variant::Bech32m).unwrap();
+        assert_eq!(
+            Invoice::decode(&foreign),
+            Err(InvoiceError::UnknownPrefix("lnbc".into()))
+        );
+    }
+
+    #[test]
+    fn test_wallets_request_and_pay_invoices() -> Result<(), WalletContractError> {
+        let mut shop = test_util::wallet([7u8; 32]);
+        let shared = shop.open_channel(0, [1u8; 32], Vec::new())?;
+
+        let mut customer = test_util::unkeyed_wallet([1u8; 32]);
+        customer.register_channel([2u8; 32], 100, [7u8; 32], Vec::new())?;
+        customer.register_channel(shared, 500, [7u8; 32], Vec::new())?;
+
+        let invoice = shop.create_invoice(60, 3_600, Some("coffee".into()), Some([1u8; 32]))?;
+        assert_eq!(invoice.terms.channel_hints, vec![shared]);
+        let preimage = shop.invoice_preimage(&invoice.terms.payment_hash).unwrap();
+        assert_eq!(payment_hash(&preimage), invoice.terms.payment_hash);
+
+        let encoded = invoice.encode().unwrap();
+        let payment = customer.pay_invoice(&encoded)?;
+        assert_eq!(payment.channel_id, shared);
+        assert_eq!(payment.payment_hash, Some(invoice.terms.payment_hash));
+        assert_eq!(customer.get_channel(&shared).unwrap().balances, vec![440]);
+        assert!(matches!(
+            customer.pay_invoice(&encoded),
+            Err(WalletContractError::InvoiceError(InvoiceError::AlreadyPaid))
+        ));
+
+        let stale = Invoice::sign(
+            Network::Regtest,
+            [7u8; 32],
+            &test_util::key_manager().invoice_key().unwrap(),
+            InvoiceTerms {
+                created_at: 0,
+                ..invoice.terms.clone()
+            },
+        )
+        .unwrap();
+        assert!(matches!(
+            customer.pay_invoice(&stale.encode().unwrap()),
+            Err(WalletContractError::InvoiceError(InvoiceError::Expired(
+                3_600
+            )))
+        ));
+        Ok(())
+    }
+}
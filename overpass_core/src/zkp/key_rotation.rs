@@ -0,0 +1,147 @@
+// src/zkp/key_rotation.rs
+
+use bitcoin::secp256k1::{schnorr, KeyPair, Message, Secp256k1, XOnlyPublicKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::zkp::helpers::Bytes32;
+use crate::zkp::operator_keys::message_from;
+
+/// A channel's move to its next generation of signing keys, authorized by the funding
+/// key being retired and carried as the metadata of a proven state transition.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyRotation {
+    pub channel_id: Bytes32,
+    /// Generation of the new keys.
+    pub generation: u32,
+    /// Nonce of the channel state that carries the rotation.
+    pub nonce: u64,
+    pub old_key: XOnlyPublicKey,
+    pub new_key: XOnlyPublicKey,
+    /// Signature by `old_key` over the rotation.
+    pub signature: schnorr::Signature,
+}
+
+impl KeyRotation {
+    /// Gets the message the retiring key signs to hand the channel over to `new_key`.
+    pub fn message(
+        channel_id: &Bytes32,
+        generation: u32,
+        nonce: u64,
+        old_key: &XOnlyPublicKey,
+        new_key: &XOnlyPublicKey,
+    ) -> Message {
+        let mut hasher = Sha256::new();
+        hasher.update(b"overpass/key-rotation/v1");
+        hasher.update(channel_id);
+        hasher.update(generation.to_le_bytes());
+        hasher.update(nonce.to_le_bytes());
+        hasher.update(old_key.serialize());
+        hasher.update(new_key.serialize());
+        message_from(hasher)
+    }
+
+    /// Signs a rotation from `old` to `new_key` for the state at `nonce`.
+    pub fn sign(
+        old: &KeyPair,
+        new_key: XOnlyPublicKey,
+        channel_id: Bytes32,
+        generation: u32,
+        nonce: u64,
+    ) -> Self {
+        let old_key = old.x_only_public_key().0;
+        let message = Self::message(&channel_id, generation, nonce, &old_key, &new_key);
+        Self {
+            channel_id,
+            generation,
+            nonce,
+            old_key,
+            new_key,
+            signature: Secp256k1::new().sign_schnorr(&message, old),
+        }
+    }
+
+    /// Checks that the retiring key signed the rotation.
+    pub fn verify(&self) -> bool {
+        let message = Self::message(
+            &self.channel_id,
+            self.generation,
+            self.nonce,
+            &self.old_key,
+            &self.new_key,
+        );
+        Secp256k1::verification_only()
+            .verify_schnorr(&self.signature, &message, &self.old_key)
+            .is_ok()
+    }
+
+    /// Reads the rotation carried by a channel state's metadata, if there is one.
+    pub fn from_metadata(metadata: &[u8]) -> Option<Self> {
+        serde_json::from_slice(metadata).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::keys::{channel_index_for_id, KeyFamily};
+    use crate::test_util;
+    use crate::zkp::wallet_contract::WalletContractError;
+
+    #[test]
+    fn test_rotation_moves_channels_to_new_keys() -> Result<(), WalletContractError> {
+        let keys = test_util::key_manager();
+        let mut wallet = test_util::unkeyed_wallet([1u8; 32]).with_key_manager(keys.clone());
+        let first = wallet.open_channel(100, [7u8; 32], Vec::new())?;
+        let second = wallet.open_channel(50, [8u8; 32], Vec::new())?;
+        let original = wallet.channel_key(&first, KeyFamily::Funding)?;
+        let index = channel_index_for_id(&first);
+        assert_eq!(
+            original.secret_key(),
+            keys.channel_secret(KeyFamily::Funding, index).unwrap()
+        );
+
+        let rotations = wallet.rotate_keys()?;
+        assert_eq!(rotations.len(), 2);
+        assert!(rotations.iter().all(KeyRotation::verify));
+        let rotation = rotations
+            .iter()
+            .find(|rotation| rotation.channel_id == first)
+            .unwrap();
+        let rotated = wallet.channel_key(&first, KeyFamily::Funding)?;
+        assert_eq!(rotation.old_key, original.x_only_public_key().0);
+        assert_eq!(rotation.new_key, rotated.x_only_public_key().0);
+        assert_eq!(wallet.key_generation(&first), 1);
+        assert!(wallet.is_retired_key(&first, &rotation.old_key));
+        assert!(!wallet.is_retired_key(&first, &rotation.new_key));
+
+        // The rotation is carried by a proven transition that leaves the balance alone.
+        let state = wallet.get_channel(&first).unwrap();
+        assert_eq!((state.balances.clone(), state.nonce), (vec![100], 1));
+        assert_eq!(
+            KeyRotation::from_metadata(&state.metadata).as_ref(),
+            Some(rotation)
+        );
+        assert_eq!(wallet.storage.history(&first).len(), 1);
+        assert!(wallet.reopen_channel(&second)?);
+
+        wallet.rotate_keys()?;
+        assert_eq!(wallet.key_generation(&first), 2);
+        assert_eq!(wallet.key_rotations(&first).len(), 2);
+        assert!(wallet.is_retired_key(&first, &rotation.new_key));
+        Ok(())
+    }
+
+    #[test]
+    fn test_rotation_signature_covers_new_key() {
+        let keys = test_util::key_manager();
+        let old = keys.rotated_channel_key(KeyFamily::Funding, 3, 0).unwrap();
+        let new = keys.rotated_channel_key(KeyFamily::Funding, 3, 1).unwrap();
+        assert_ne!(old.secret_key(), new.secret_key());
+        let mut rotation = KeyRotation::sign(&old, new.x_only_public_key().0, [3u8; 32], 1, 4);
+        assert!(rotation.verify());
+
+        rotation.new_key = old.x_only_public_key().0;
+        assert!(!rotation.verify());
+    }
+}
@@ -0,0 +1,136 @@
+// src/zkp/mmr.rs
+
+use overpass_verify::mmr::{bag_peaks, mountain_of, verify_mmr_path};
+use serde::{Deserialize, Serialize};
+
+use crate::zkp::helpers::{hash_pair, merkle_path, Bytes32};
+
+/// An append-only Merkle Mountain Range.
+///
+/// Leaves are grouped into perfect trees ("mountains") of strictly decreasing height, one
+/// per set bit of the leaf count, and the root commits to the count and every peak. An
+/// inclusion proof is a path to one peak plus the peaks, so it stays logarithmic in size.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleMountainRange {
+    leaves: Vec<Bytes32>,
+    /// Height and root of each mountain, tallest first.
+    peaks: Vec<(u32, Bytes32)>,
+}
+
+impl MerkleMountainRange {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> u64 {
+        self.leaves.len() as u64
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    /// Appends a leaf, merging equal-height mountains, and returns the leaf's index.
+    pub fn append(&mut self, leaf: Bytes32) -> u64 {
+        let index = self.len();
+        self.leaves.push(leaf);
+        let mut peak = (0, leaf);
+        while let Some(&(height, left)) = self.peaks.last() {
+            if height != peak.0 {
+                break;
+            }
+            self.peaks.pop();
+            peak = (height + 1, hash_pair(left, peak.1));
+        }
+        self.peaks.push(peak);
+        index
+    }
+
+    pub fn root(&self) -> Bytes32 {
+        bag_peaks(self.len(), &self.peak_hashes())
+    }
+
+    /// Proves the leaf at `index` is part of the current root.
+    pub fn proof(&self, index: u64) -> Option<MmrProof> {
+        let (start, height) = mountain_of(self.len(), index)?;
+        let size = 1usize << height;
+        let start = start as usize;
+        let mountain = self.leaves[start..start + size].to_vec();
+        Some(MmrProof {
+            leaf_index: index,
+            leaf_count: self.len(),
+            siblings: merkle_path(mountain, index as usize - start),
+            peaks: self.peak_hashes(),
+        })
+    }
+
+    fn peak_hashes(&self) -> Vec<Bytes32> {
+        self.peaks.iter().map(|(_, peak)| *peak).collect()
+    }
+}
+
+/// Proof that a leaf sits at `leaf_index` of a range holding `leaf_count` leaves.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MmrProof {
+    pub leaf_index: u64,
+    pub leaf_count: u64,
+    /// Path from the leaf to the peak of its mountain.
+    pub siblings: Vec<Bytes32>,
+    pub peaks: Vec<Bytes32>,
+}
+
+impl MmrProof {
+    pub fn verify(&self, leaf: &Bytes32, root: &Bytes32) -> bool {
+        verify_mmr_path(
+            leaf,
+            self.leaf_index,
+            self.leaf_count,
+            &self.siblings,
+            &self.peaks,
+            root,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_leaf_proves_against_every_size() {
+        let mut mmr = MerkleMountainRange::new();
+        for n in 0..20u8 {
+            mmr.append([n; 32]);
+            let root = mmr.root();
+            for index in 0..=n as u64 {
+                let proof = mmr.proof(index).unwrap();
+                assert!(proof.verify(&[index as u8; 32], &root));
+                assert!(!proof.verify(&[index as u8 + 1; 32], &root));
+            }
+            assert!(mmr.proof(n as u64 + 1).is_none());
+        }
+        assert_eq!(mmr.len(), 20);
+    }
+
+    #[test]
+    fn test_tampered_proofs_fail() {
+        let mut mmr = MerkleMountainRange::new();
+        for n in 0..7u8 {
+            mmr.append([n; 32]);
+        }
+        let root = mmr.root();
+        let proof = mmr.proof(5).unwrap();
+        assert!(proof.verify(&[5u8; 32], &root));
+
+        let mut moved = proof.clone();
+        moved.leaf_index = 4;
+        assert!(!moved.verify(&[5u8; 32], &root));
+
+        let mut resized = proof.clone();
+        resized.leaf_count = 6;
+        assert!(!resized.verify(&[5u8; 32], &root));
+
+        mmr.append([7u8; 32]);
+        assert!(!proof.verify(&[5u8; 32], &mmr.root()));
+    }
+}
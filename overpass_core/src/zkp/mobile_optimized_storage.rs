@@ -1,24 +1,52 @@
 // src/zkp/mobile_optimized_storage.rs
 use std::num::NonZero;
 use crate::zkp::channel::ChannelState;
-use std::fmt;
 /// Local Storage Layer (Level 3)
 /// Hybrid hot/cold storage optimized for mobile devices.
 
-use crate::zkp::compressed_transaction::CompressedTransaction;
+use crate::bitcoin::triggers::{ChainTime, Interval};
+use crate::clock::{self, SharedClock};
+use crate::services::overpass_db::{DbError, OverpassDB};
+use crate::zkp::compressed_transaction::{
+    upgrade_history, CompressedTransaction, DeltaError, DeltaHistory, ProofRef,
+};
 use crate::zkp::helpers::Bytes32;
+use crate::zkp::history_archive::{ArchiveBlock, ArchivePolicy};
 use crate::zkp::state_proof::StateProof;
+use crate::zkp::tx_metadata::{MetadataError, TxMetadata};
 use lru::LruCache;
-
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+
+/// Prefix of the database keys channel histories are saved under.
+pub const HISTORY_PREFIX: &[u8] = b"channel_history:";
+
+/// What storage keeps of each transition's proof.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProofRetention {
+    #[default]
+    None,
+    /// Only the proof's digest, to match a proof kept elsewhere.
+    Digest,
+    /// The whole proof, so the history verifies on its own.
+    Embedded,
+}
 
 /// Represents errors in storage operations.
-#[derive(Debug)]
+#[derive(Debug, thiserror::Error)]
 pub enum StorageError {
+    #[error("Transaction is too old")]
     TransactionTooOld,
+    #[error("Storage limit exceeded")]
     StorageLimitExceeded,
-    Other(String),
+    #[error("Invalid metadata: {0}")]
+    InvalidMetadata(#[from] MetadataError),
+    #[error("History encoding: {0}")]
+    Encoding(#[from] DeltaError),
+    #[error("Storage error: {0}")]
+    Database(#[from] DbError),
 }
 
 /// MobileOptimizedStorage handles hybrid hot/cold storage for mobile devices.
@@ -30,13 +58,25 @@ pub struct MobileOptimizedStorage {
     
     /// Cold storage (compressed historical data).
     transaction_history: HashMap<Bytes32, Vec<CompressedTransaction>>,
-    #[allow(dead_code)]
     channel_roots: HashMap<Bytes32, Bytes32>,
     
     /// Performance parameters.
     compression_threshold: usize, // Number of transactions before compression
-    #[allow(dead_code)]
-    retention_period: u64,        // Retention period in seconds
+    retention: Interval,          // Inactivity after which history is pruned
+    proof_retention: ProofRetention,
+    max_transactions: Option<usize>, // Transactions held across all histories
+
+    /// Last chain time seen and the chain time of each channel's latest transaction.
+    chain_time: ChainTime,
+    last_activity: HashMap<Bytes32, ChainTime>,
+
+    /// Histories of dormant channels, folded into one block per epoch.
+    archive_policy: Option<ArchivePolicy>,
+    archive: BTreeMap<u64, ArchiveBlock>,
+    archived: HashMap<Bytes32, u64>,
+
+    /// Time `prune_now` prunes at.
+    clock: SharedClock,
 }
 impl MobileOptimizedStorage {
     /// Creates a new MobileOptimizedStorage instance.
@@ -47,29 +87,190 @@ impl MobileOptimizedStorage {
             transaction_history: HashMap::new(),
             channel_roots: HashMap::new(),
             compression_threshold,
-            retention_period,
+            retention: Interval::secs(retention_period),
+            proof_retention: ProofRetention::None,
+            max_transactions: None,
+            chain_time: ChainTime::default(),
+            last_activity: HashMap::new(),
+            archive_policy: None,
+            archive: BTreeMap::new(),
+            archived: HashMap::new(),
+            clock: clock::system(),
+        }
+    }
+
+    /// Takes the time to prune at from `clock` instead of the system clock.
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.set_clock(clock);
+        self
+    }
+
+    pub fn set_clock(&mut self, clock: SharedClock) {
+        self.clock = clock;
+    }
+
+    /// Keeps each stored transition's proof, or its digest, with its record.
+    pub fn with_proof_retention(mut self, proof_retention: ProofRetention) -> Self {
+        self.proof_retention = proof_retention;
+        self
+    }
+
+    /// Archives the history of channels that go dormant under `policy` when pruning.
+    pub fn with_archive_policy(mut self, policy: ArchivePolicy) -> Self {
+        self.archive_policy = Some(policy);
+        self
+    }
+
+    /// Resizes the caches of hot channels and of their recent transactions.
+    pub fn with_cache_sizes(
+        mut self,
+        active_channels: NonZero<usize>,
+        recent_transactions: NonZero<usize>,
+    ) -> Self {
+        self.active_channels.resize(active_channels);
+        self.recent_transactions.resize(recent_transactions);
+        self
+    }
+
+    /// Refuses new transactions once the histories hold `max_transactions`, until pruning
+    /// frees room.
+    pub fn with_transaction_quota(mut self, max_transactions: usize) -> Self {
+        self.max_transactions = Some(max_transactions);
+        self
+    }
+
+    /// Replaces the retention period, e.g. to prune after a number of blocks.
+    pub fn with_retention(mut self, retention: Interval) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// Records the current chain tip so new transactions are stamped with its height.
+    pub fn observe_chain(&mut self, now: ChainTime) {
+        self.chain_time = now;
+    }
+
+    /// Drops the history of channels inactive for the retention period, returning their ids,
+    /// then archives the channels that went dormant.
+    pub fn prune(&mut self, now: ChainTime) -> Vec<Bytes32> {
+        self.observe_chain(now);
+        let expired: Vec<Bytes32> = self
+            .last_activity
+            .iter()
+            .filter(|(_, since)| self.retention.has_elapsed(**since, now))
+            .map(|(channel_id, _)| *channel_id)
+            .collect();
+        for channel_id in &expired {
+            self.unarchive(channel_id);
+            self.last_activity.remove(channel_id);
+            self.transaction_history.remove(channel_id);
+            self.recent_transactions.pop(channel_id);
+            self.channel_roots.remove(channel_id);
+        }
+        self.archive_dormant(now);
+        expired
+    }
+
+    /// Prunes at the clock's current time and the last chain height observed.
+    pub fn prune_now(&mut self) -> Vec<Bytes32> {
+        self.prune(ChainTime::new(self.clock.now(), self.chain_time.height))
+    }
+
+    /// Moves the histories of channels dormant at `now` into the archive block of the
+    /// epoch they were last active in, returning the epochs whose blocks changed.
+    pub fn archive_dormant(&mut self, now: ChainTime) -> Vec<u64> {
+        let Some(policy) = self.archive_policy else {
+            return Vec::new();
+        };
+        let mut dormant: BTreeMap<u64, Vec<Bytes32>> = BTreeMap::new();
+        for (channel_id, since) in &self.last_activity {
+            if self.transaction_history.contains_key(channel_id)
+                && policy.dormancy.has_elapsed(*since, now)
+            {
+                dormant
+                    .entry(policy.epoch_of(since.unix))
+                    .or_default()
+                    .push(*channel_id);
+            }
+        }
+        for (epoch, channels) in &dormant {
+            let mut histories = self
+                .archive
+                .get(epoch)
+                .map(ArchiveBlock::histories)
+                .unwrap_or_default();
+            for channel_id in channels {
+                self.recent_transactions.pop(channel_id);
+                if let Some(history) = self.transaction_history.remove(channel_id) {
+                    histories.insert(*channel_id, history);
+                    self.archived.insert(*channel_id, *epoch);
+                }
+            }
+            self.archive.insert(*epoch, ArchiveBlock::new(*epoch, histories));
+        }
+        dormant.into_keys().collect()
+    }
+
+    /// Gets the archive blocks, oldest epoch first.
+    pub fn archive_blocks(&self) -> impl Iterator<Item = &ArchiveBlock> {
+        self.archive.values()
+    }
+
+    pub fn is_archived(&self, channel_id: &Bytes32) -> bool {
+        self.archived.contains_key(channel_id)
+    }
+
+    /// Moves a channel's history out of its archive block back into hot storage.
+    fn unarchive(&mut self, channel_id: &Bytes32) {
+        let Some(epoch) = self.archived.remove(channel_id) else {
+            return;
+        };
+        let Some(block) = self.archive.get_mut(&epoch) else {
+            return;
+        };
+        if let Some(history) = block.remove(channel_id) {
+            self.transaction_history.insert(*channel_id, history);
+        }
+        if block.is_empty() {
+            self.archive.remove(&epoch);
         }
     }
     
     /// Stores a transaction, possibly compressing history.
+    #[tracing::instrument(skip_all, fields(channel_id = %hex::encode(channel_id)), err)]
     pub fn store_transaction(
         &mut self,
         channel_id: Bytes32,
         old_commitment: Bytes32,
         new_commitment: Bytes32,
         proof: StateProof,
-        metadata: serde_json::Value,
+        metadata: TxMetadata,
     ) -> Result<(), StorageError> {
+        let metadata_hash = metadata.hash()?;
+        if let Some(max_transactions) = self.max_transactions {
+            let stored: usize = self.transaction_history.values().map(Vec::len).sum();
+            if stored >= max_transactions {
+                return Err(StorageError::StorageLimitExceeded);
+            }
+        }
         let timestamp = proof.timestamp;
-        let metadata_hash = sha256_hash(&serde_json::to_vec(&metadata).map_err(|e| StorageError::Other(e.to_string()))?);
+        self.unarchive(&channel_id);
+        self.last_activity
+            .insert(channel_id, ChainTime::new(timestamp, self.chain_time.height));
         let merkle_root = compute_merkle_root(&self.transaction_history, &channel_id);
         
         let compressed_tx = CompressedTransaction {
+            version: CompressedTransaction::CURRENT_VERSION,
             timestamp,
             old_commitment,
             new_commitment,
             metadata_hash,
             merkle_root,
+            proof: match self.proof_retention {
+                ProofRetention::None => None,
+                ProofRetention::Digest => Some(ProofRef::Digest(ProofRef::digest_of(&proof))),
+                ProofRetention::Embedded => Some(ProofRef::Embedded(proof)),
+            },
         };
         
         // Add to recent transactions
@@ -90,19 +291,92 @@ impl MobileOptimizedStorage {
         
         Ok(())
     }    
+    /// Gets a channel's stored transaction history, oldest first.
+    pub fn history(&self, channel_id: &Bytes32) -> &[CompressedTransaction] {
+        self.transaction_history
+            .get(channel_id)
+            .map(Vec::as_slice)
+            .or_else(|| {
+                let epoch = self.archived.get(channel_id)?;
+                self.archive.get(epoch)?.history(channel_id)
+            })
+            .unwrap_or_default()
+    }
+
+    /// Replaces a channel's history with one synced from another device, upgrading
+    /// records written in an older format.
+    pub fn import_history(&mut self, channel_id: Bytes32, history: Vec<CompressedTransaction>) {
+        let history = upgrade_history(history);
+        self.unarchive(&channel_id);
+        self.recent_transactions.pop(&channel_id);
+        match history.last() {
+            Some(latest) => {
+                self.last_activity
+                    .insert(channel_id, ChainTime::new(latest.timestamp, self.chain_time.height));
+                self.transaction_history.insert(channel_id, history);
+            }
+            None => {
+                self.last_activity.remove(&channel_id);
+                self.transaction_history.remove(&channel_id);
+            }
+        }
+    }
+
+    /// Gets a channel's history delta-encoded, for writing to cold storage.
+    pub fn cold_history(&self, channel_id: &Bytes32) -> DeltaHistory {
+        DeltaHistory::encode(self.history(channel_id))
+    }
+
+    /// Replaces a channel's history with one read back from cold storage.
+    pub fn import_cold_history(
+        &mut self,
+        channel_id: Bytes32,
+        history: &DeltaHistory,
+    ) -> Result<(), StorageError> {
+        let history = history.decode()?;
+        self.import_history(channel_id, history);
+        Ok(())
+    }
+
+    /// Writes a channel's history to `db` in the canonical encoding, with its proofs.
+    pub fn save_history(&self, db: &OverpassDB, channel_id: &Bytes32) -> Result<(), StorageError> {
+        db.put(
+            &history_key(channel_id),
+            &CompressedTransaction::encode_proven(self.history(channel_id)),
+        )
+        .and_then(|_| db.flush())?;
+        Ok(())
+    }
+
+    /// Replaces a channel's history with the one saved in `db`, if there is one.
+    pub fn restore_history(
+        &mut self,
+        db: &OverpassDB,
+        channel_id: Bytes32,
+    ) -> Result<(), StorageError> {
+        let Some(bytes) = db.get(&history_key(&channel_id))? else {
+            return Ok(());
+        };
+        let history = CompressedTransaction::decode_proven(&bytes)?;
+        self.import_history(channel_id, history);
+        Ok(())
+    }
+
     /// Compresses transactions for a channel.
     fn compress_transactions(&mut self, channel_id: Bytes32) -> Result<(), StorageError> {
         if let Some(recent_txs) = self.recent_transactions.pop(&channel_id) {
-            if recent_txs.is_empty() {
+            let (Some(first), Some(last)) = (recent_txs.first(), recent_txs.last()) else {
                 return Ok(());
-            }
+            };
             // Compress recent_txs into one
             let compressed = CompressedTransaction {
-                timestamp: recent_txs.last().unwrap().timestamp,
-                old_commitment: recent_txs.first().unwrap().old_commitment,
-                new_commitment: recent_txs.last().unwrap().new_commitment,
+                version: CompressedTransaction::CURRENT_VERSION,
+                timestamp: last.timestamp,
+                old_commitment: first.old_commitment,
+                new_commitment: last.new_commitment,
                 metadata_hash: sha256_hash(&serialize_metadata(&recent_txs)),
                 merkle_root: compute_merkle_root(&self.transaction_history, &channel_id),
+                proof: None,
             };
             // Add to history
             self.transaction_history
@@ -114,6 +388,13 @@ impl MobileOptimizedStorage {
     }
 }
 
+/// Gets the database key of a channel's saved history.
+pub fn history_key(channel_id: &Bytes32) -> Vec<u8> {
+    let mut key = HISTORY_PREFIX.to_vec();
+    key.extend_from_slice(hex::encode(channel_id).as_bytes());
+    key
+}
+
 /// Computes SHA256 hash.
 fn sha256_hash(data: &[u8]) -> [u8; 32] {
     let mut hasher = Sha256::new();
@@ -124,9 +405,9 @@ fn sha256_hash(data: &[u8]) -> [u8; 32] {
     hash
 }
 
-/// Serializes metadata for hashing.
+/// Serializes metadata for hashing, in the canonical transaction encoding.
 fn serialize_metadata(txs: &[CompressedTransaction]) -> Vec<u8> {
-    serde_json::to_vec(txs).unwrap_or_default()
+    CompressedTransaction::encode_all(txs)
 }
 
 /// Computes Merkle root from transaction history for a channel.
@@ -167,15 +448,3 @@ fn hash_pair(left: [u8; 32], right: [u8; 32]) -> [u8; 32] {
     parent.copy_from_slice(&result);
     parent
 }
-
-impl fmt::Display for StorageError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            StorageError::TransactionTooOld => write!(f, "Transaction is too old"),
-            StorageError::StorageLimitExceeded => write!(f, "Storage limit exceeded"),
-            StorageError::Other(msg) => write!(f, "Storage error: {}", msg),
-        }
-    }
-}
-
-impl std::error::Error for StorageError {}
\ No newline at end of file
@@ -1,11 +1,70 @@
+#[cfg(all(any(test, feature = "proptest"), feature = "prover"))]
+pub mod arbitrary;
+#[cfg(feature = "prover")]
 pub mod state_transition;
 pub mod tree;
+#[cfg(feature = "bitcoin-backend")]
 pub mod bitcoin_ephemeral_state;
 pub mod pedersen_parameters;
+pub mod blinding;
+#[cfg(feature = "prover")]
+pub mod pedersen_commitment;
+pub mod pedersen_group;
+#[cfg(feature = "prover")]
+pub mod opening_proofs;
+#[cfg(feature = "prover")]
+pub mod proof_of_funds;
 pub mod state_proof;
 pub mod helpers;
+pub mod fraud_proof;
+#[cfg(feature = "prover")]
 pub mod global_root_contract;
+#[cfg(feature = "prover")]
 pub mod channel;
+#[cfg(feature = "prover")]
+pub mod channel_backup;
+pub mod counterparty_registry;
 pub mod compressed_transaction;
+#[cfg(feature = "prover")]
 pub mod mobile_optimized_storage;
-pub mod wallet_contract;
\ No newline at end of file
+#[cfg(feature = "prover")]
+pub mod operator_keys;
+#[cfg(feature = "prover")]
+pub mod reconciliation;
+#[cfg(feature = "prover")]
+pub mod device_sync;
+pub mod fee_ledger;
+pub mod history_replay;
+pub mod history_archive;
+pub mod mmr;
+#[cfg(feature = "prover")]
+pub mod offer;
+#[cfg(feature = "prover")]
+pub mod multisig_wallet;
+#[cfg(feature = "prover")]
+pub mod cross_validation;
+#[cfg(feature = "prover")]
+pub mod circuit_breaker;
+#[cfg(feature = "prover")]
+pub mod governance;
+#[cfg(feature = "prover")]
+pub mod key_rotation;
+pub mod spending_policy;
+pub mod sub_account;
+pub mod tx_metadata;
+pub mod wallet_audit;
+#[cfg(feature = "prover")]
+pub mod wallet_contract;
+#[cfg(feature = "prover")]
+pub mod wallet_lock;
+#[cfg(feature = "prover")]
+pub mod wallet_signer;
+#[cfg(feature = "prover")]
+pub mod wallet_root_proof;
+#[cfg(feature = "prover")]
+pub mod invoice;
+#[cfg(feature = "prover")]
+pub mod payment;
+pub mod payment_schedule;
+#[cfg(feature = "prover")]
+pub mod watch_only;
\ No newline at end of file
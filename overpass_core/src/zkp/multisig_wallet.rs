@@ -0,0 +1,384 @@
+// src/zkp/multisig_wallet.rs
+
+use bitcoin::secp256k1::{schnorr, Message, Secp256k1, XOnlyPublicKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+use crate::zkp::helpers::Bytes32;
+use crate::zkp::operator_keys::message_from;
+use crate::zkp::wallet_contract::{WalletContract, WalletContractError};
+use crate::zkp::watch_only::{SignedTransition, UnsignedTransition};
+
+#[derive(Error, Debug)]
+pub enum MultisigError {
+    #[error("Multisig needs 1..={0} signers, got a threshold of {1}")]
+    InvalidThreshold(usize, usize),
+    #[error("{} is not a signer of this wallet", hex::encode(.0.serialize()))]
+    UnknownSigner(XOnlyPublicKey),
+    #[error("Signature does not verify for the transition")]
+    InvalidSignature,
+    #[error("Transition {} is already pending", hex::encode(.0))]
+    AlreadyPending(Bytes32),
+    #[error("No pending transition {}", hex::encode(.0))]
+    UnknownTransition(Bytes32),
+    #[error("Approval does not carry the threshold of valid signatures")]
+    NotApproved,
+    #[error("Wallet error: {0}")]
+    Wallet(#[from] WalletContractError),
+}
+
+/// The devices or parties of an M-of-N wallet, `threshold` of whom approve each
+/// transition.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MultisigPolicy {
+    pub keys: Vec<XOnlyPublicKey>,
+    pub threshold: usize,
+}
+
+impl MultisigPolicy {
+    pub fn new(keys: Vec<XOnlyPublicKey>, threshold: usize) -> Result<Self, MultisigError> {
+        if threshold == 0 || threshold > keys.len() {
+            return Err(MultisigError::InvalidThreshold(keys.len(), threshold));
+        }
+        Ok(Self { keys, threshold })
+    }
+}
+
+/// A transition with the approvals gathered for it so far.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PendingTransition {
+    pub id: Bytes32,
+    pub signed: SignedTransition,
+    /// Partial signatures, one per distinct signer.
+    pub approvals: Vec<(XOnlyPublicKey, schnorr::Signature)>,
+}
+
+impl PendingTransition {
+    /// Gets the signers still free to approve.
+    pub fn missing<'a>(&self, policy: &'a MultisigPolicy) -> Vec<&'a XOnlyPublicKey> {
+        policy
+            .keys
+            .iter()
+            .filter(|key| !self.approvals.iter().any(|(signer, _)| signer == *key))
+            .collect()
+    }
+}
+
+/// A transition applied with the threshold of approvals, which anyone holding the
+/// policy can check.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MultisigApproval {
+    pub signed: SignedTransition,
+    pub signatures: Vec<(XOnlyPublicKey, schnorr::Signature)>,
+}
+
+impl MultisigApproval {
+    pub fn verify(&self, policy: &MultisigPolicy) -> bool {
+        let message = transition_message(&self.signed);
+        let secp = Secp256k1::verification_only();
+        let mut signers: Vec<&XOnlyPublicKey> = self
+            .signatures
+            .iter()
+            .filter(|(key, signature)| {
+                policy.keys.contains(key) && secp.verify_schnorr(signature, &message, key).is_ok()
+            })
+            .map(|(key, _)| key)
+            .collect();
+        signers.sort_unstable();
+        signers.dedup();
+        signers.len() >= policy.threshold
+    }
+}
+
+/// What an approval did.
+#[derive(Clone, Debug)]
+pub enum ApprovalOutcome {
+    /// The transition still needs this many approvals.
+    Pending(usize),
+    /// The threshold was reached and the transition applied; other proposals for the
+    /// same channel state were dropped.
+    Applied {
+        approval: Box<MultisigApproval>,
+        dropped: Vec<Bytes32>,
+    },
+}
+
+/// Gets the message each signer approves a transition with, which is also its id.
+pub fn transition_message(signed: &SignedTransition) -> Message {
+    let transition = &signed.transition;
+    let mut hasher = Sha256::new();
+    hasher.update(b"overpass/multisig/transition/v1");
+    hasher.update(transition.wallet_id);
+    hasher.update(transition.channel_id);
+    hasher.update(transition.nonce.to_le_bytes());
+    hasher.update(transition.state_hash);
+    hasher.update(transition.old_commitment);
+    hasher.update(transition.wallet_root);
+    hasher.update(transition.new_balance.to_le_bytes());
+    hasher.update((transition.metadata.len() as u64).to_le_bytes());
+    hasher.update(&transition.metadata);
+    hasher.update(signed.commitment);
+    hasher.update(signed.proof.pi);
+    message_from(hasher)
+}
+
+/// A wallet whose transitions take effect only once `threshold` of its signers approve
+/// them.
+///
+/// Any device may propose a transition it has proven, for example one signed from a
+/// watch-only copy; it sits pending until enough partial signatures are gathered, then
+/// applies to the wallet like any transition signed offline. The wallet is never handed
+/// out mutably, so approvals are the only way its channels change.
+pub struct MultisigWallet {
+    wallet: WalletContract,
+    policy: MultisigPolicy,
+    pending: BTreeMap<Bytes32, PendingTransition>,
+}
+
+impl MultisigWallet {
+    pub fn new(wallet: WalletContract, policy: MultisigPolicy) -> Self {
+        Self {
+            wallet,
+            policy,
+            pending: BTreeMap::new(),
+        }
+    }
+
+    pub fn wallet(&self) -> &WalletContract {
+        &self.wallet
+    }
+
+    /// Prepares a channel update to prove and propose.
+    pub fn prepare_transition(
+        &mut self,
+        channel_id: Bytes32,
+        new_balance: u64,
+        metadata: Vec<u8>,
+    ) -> Result<UnsignedTransition, MultisigError> {
+        Ok(self
+            .wallet
+            .prepare_transition(channel_id, new_balance, metadata)?)
+    }
+
+    pub fn policy(&self) -> &MultisigPolicy {
+        &self.policy
+    }
+
+    /// Gets every transition awaiting approval, by id.
+    pub fn pending(&self) -> impl Iterator<Item = &PendingTransition> {
+        self.pending.values()
+    }
+
+    pub fn pending_transition(&self, id: &Bytes32) -> Option<&PendingTransition> {
+        self.pending.get(id)
+    }
+
+    /// Queues a proven transition for approval and returns its id.
+    pub fn propose(&mut self, signed: SignedTransition) -> Result<Bytes32, MultisigError> {
+        if !signed.is_bound() {
+            return Err(MultisigError::Wallet(
+                WalletContractError::InvalidTransition("proof is not over the transition"),
+            ));
+        }
+        let id = *transition_message(&signed).as_ref();
+        if self.pending.contains_key(&id) {
+            return Err(MultisigError::AlreadyPending(id));
+        }
+        self.pending.insert(
+            id,
+            PendingTransition {
+                id,
+                signed,
+                approvals: Vec::new(),
+            },
+        );
+        Ok(id)
+    }
+
+    /// Adds a signer's partial signature to a pending transition, applying it once the
+    /// threshold is reached. A repeated approval from the same signer counts once.
+    pub fn approve(
+        &mut self,
+        id: &Bytes32,
+        signer: XOnlyPublicKey,
+        signature: schnorr::Signature,
+    ) -> Result<ApprovalOutcome, MultisigError> {
+        if !self.policy.keys.contains(&signer) {
+            return Err(MultisigError::UnknownSigner(signer));
+        }
+        let pending = self
+            .pending
+            .get_mut(id)
+            .ok_or(MultisigError::UnknownTransition(*id))?;
+        let message = transition_message(&pending.signed);
+        Secp256k1::verification_only()
+            .verify_schnorr(&signature, &message, &signer)
+            .map_err(|_| MultisigError::InvalidSignature)?;
+        if !pending.approvals.iter().any(|(key, _)| *key == signer) {
+            pending.approvals.push((signer, signature));
+        }
+        if pending.approvals.len() < self.policy.threshold {
+            return Ok(ApprovalOutcome::Pending(
+                self.policy.threshold - pending.approvals.len(),
+            ));
+        }
+
+        // A transition the wallet refuses, e.g. because its channel moved on, stays pending.
+        let approval = MultisigApproval {
+            signed: pending.signed.clone(),
+            signatures: pending.approvals.clone(),
+        };
+        self.apply_approval(&approval)?;
+        self.pending.remove(id);
+        let applied = &approval.signed.transition;
+        let dropped: Vec<Bytes32> = self
+            .pending
+            .values()
+            .filter(|other| {
+                other.signed.transition.channel_id == applied.channel_id
+                    && other.signed.transition.nonce <= applied.nonce
+            })
+            .map(|other| other.id)
+            .collect();
+        for other in &dropped {
+            self.pending.remove(other);
+        }
+        Ok(ApprovalOutcome::Applied {
+            approval: Box::new(approval),
+            dropped,
+        })
+    }
+
+    /// Applies a transition whose signatures were gathered elsewhere, e.g. on another
+    /// device holding the same policy, once they meet the threshold.
+    pub fn apply_approval(&mut self, approval: &MultisigApproval) -> Result<(), MultisigError> {
+        if !approval.verify(&self.policy) {
+            return Err(MultisigError::NotApproved);
+        }
+        self.wallet.apply_signed_transition(approval.signed.clone())?;
+        Ok(())
+    }
+
+    /// Withdraws a pending transition.
+    pub fn cancel(&mut self, id: &Bytes32) -> Option<PendingTransition> {
+        self.pending.remove(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+    use crate::zkp::pedersen_parameters::PedersenParameters;
+    use crate::zkp::watch_only::sign_transition;
+    use bitcoin::secp256k1::{KeyPair, SecretKey};
+
+    fn cosigner(seed: u8) -> KeyPair {
+        let secret = SecretKey::from_slice(&[seed; 32]).unwrap();
+        KeyPair::from_secret_key(&Secp256k1::new(), &secret)
+    }
+
+    fn approve(
+        wallet: &mut MultisigWallet,
+        id: &Bytes32,
+        signer: &KeyPair,
+    ) -> Result<ApprovalOutcome, MultisigError> {
+        let message = transition_message(&wallet.pending_transition(id).unwrap().signed);
+        let signature = Secp256k1::new().sign_schnorr(&message, signer);
+        wallet.approve(id, signer.x_only_public_key().0, signature)
+    }
+
+    #[test]
+    fn test_transitions_apply_at_the_threshold() -> Result<(), MultisigError> {
+        let keys = test_util::key_manager();
+        let params = PedersenParameters::default();
+        let mut inner = test_util::unkeyed_wallet([1u8; 32]).with_key_manager(keys.clone());
+        let channel_id = inner.open_channel(100, [7u8; 32], Vec::new())?;
+        let signers = [cosigner(1), cosigner(2), cosigner(3)];
+        let policy = MultisigPolicy::new(
+            signers
+                .iter()
+                .map(|pair| pair.x_only_public_key().0)
+                .collect(),
+            2,
+        )?;
+        assert!(matches!(
+            MultisigPolicy::new(policy.keys.clone(), 4),
+            Err(MultisigError::InvalidThreshold(3, 4))
+        ));
+        let mut wallet = MultisigWallet::new(inner, policy.clone());
+
+        let propose = |wallet: &mut MultisigWallet, balance| {
+            let transition = wallet.prepare_transition(channel_id, balance, Vec::new())?;
+            let signed = sign_transition(&keys, &params, &transition)?;
+            wallet.propose(signed)
+        };
+        let id = propose(&mut wallet, 70)?;
+        let competing = propose(&mut wallet, 60)?;
+        assert!(matches!(
+            propose(&mut wallet, 70),
+            Err(MultisigError::AlreadyPending(pending)) if pending == id
+        ));
+
+        assert!(matches!(
+            approve(&mut wallet, &id, &cosigner(9)),
+            Err(MultisigError::UnknownSigner(_))
+        ));
+        let wrong = transition_message(&wallet.pending_transition(&competing).unwrap().signed);
+        let signature = Secp256k1::new().sign_schnorr(&wrong, &signers[0]);
+        assert!(matches!(
+            wallet.approve(&id, signers[0].x_only_public_key().0, signature),
+            Err(MultisigError::InvalidSignature)
+        ));
+
+        assert!(matches!(
+            approve(&mut wallet, &id, &signers[0])?,
+            ApprovalOutcome::Pending(1)
+        ));
+        assert!(matches!(
+            approve(&mut wallet, &id, &signers[0])?,
+            ApprovalOutcome::Pending(1)
+        ));
+        let pending = wallet.pending_transition(&id).unwrap();
+        assert_eq!(pending.missing(&policy).len(), 2);
+        assert_eq!(wallet.wallet().get_channel(&channel_id).unwrap().nonce, 0);
+
+        let ApprovalOutcome::Applied { approval, dropped } =
+            approve(&mut wallet, &id, &signers[2])?
+        else {
+            panic!("threshold reached without applying");
+        };
+        assert_eq!(dropped, vec![competing]);
+        assert_eq!(wallet.pending().count(), 0);
+        assert!(approval.verify(&policy));
+        assert!(!approval.verify(&MultisigPolicy::new(policy.keys.clone(), 3)?));
+        let state = wallet.wallet().get_channel(&channel_id).unwrap();
+        assert_eq!((state.balances.clone(), state.nonce), (vec![70], 1));
+        assert!(wallet.wallet().reopen_channel(&channel_id)?);
+
+        // An approval gathered elsewhere applies only with the threshold of valid signatures.
+        let transition = wallet.prepare_transition(channel_id, 40, Vec::new())?;
+        let signed = sign_transition(&keys, &params, &transition)?;
+        let message = transition_message(&signed);
+        let sign = |signer: &KeyPair| {
+            (
+                signer.x_only_public_key().0,
+                Secp256k1::new().sign_schnorr(&message, signer),
+            )
+        };
+        let mut approval = MultisigApproval {
+            signed,
+            signatures: vec![sign(&signers[1]), sign(&signers[1]), sign(&cosigner(9))],
+        };
+        assert!(matches!(
+            wallet.apply_approval(&approval),
+            Err(MultisigError::NotApproved)
+        ));
+        approval.signatures.push(sign(&signers[2]));
+        wallet.apply_approval(&approval)?;
+        assert_eq!(wallet.wallet().get_channel(&channel_id).unwrap().nonce, 2);
+        Ok(())
+    }
+}
@@ -0,0 +1,436 @@
+// src/zkp/offer.rs
+
+use bech32::{FromBase32, ToBase32, Variant};
+use bitcoin::secp256k1::{rand, schnorr, KeyPair, Message, Secp256k1, XOnlyPublicKey};
+use bitcoin::Network;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::zkp::helpers::Bytes32;
+use crate::zkp::invoice::{push_string, InvoiceError, Reader};
+use crate::zkp::operator_keys::message_from;
+
+const OFFER_VERSION: u8 = 1;
+/// Seconds an invoice issued for an offer stays payable.
+pub const OFFER_INVOICE_EXPIRY_SECS: u64 = 3_600;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum OfferError {
+    #[error("Invalid offer: {0}")]
+    Decode(#[from] InvoiceError),
+    #[error("Offer signature does not match the payee key")]
+    InvalidSignature,
+    #[error("Offer expired at {0}")]
+    Expired(u64),
+    #[error("Offer {} is unknown", hex::encode(.0))]
+    UnknownOffer(Bytes32),
+    #[error("Offer asks for {expected}, request names {requested}")]
+    AmountMismatch { expected: u64, requested: u64 },
+    #[error("Offer leaves the amount to the payer")]
+    AmountRequired,
+    #[error("Invoice request signature is invalid")]
+    InvalidRequest,
+    #[error("Request {} was not made by this wallet", hex::encode(.0))]
+    UnknownRequest(Bytes32),
+    #[error("Invoice does not answer the request: {0}")]
+    UnexpectedInvoice(&'static str),
+    #[error("Payee refused the request: {0}")]
+    Refused(String),
+    #[error("Invalid message: {0}")]
+    InvalidMessage(String),
+}
+
+/// A static, reusable payment offer a merchant publishes once, e.g. as a QR code.
+///
+/// Payers send an [`InvoiceRequest`] for it and are answered with a fresh invoice, so
+/// every payment gets its own payment hash.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Offer {
+    pub network: Network,
+    pub payee: Bytes32,
+    pub payee_key: XOnlyPublicKey,
+    /// Amount per payment, or `None` to let the payer choose.
+    pub amount: Option<u64>,
+    pub description: String,
+    /// Unix time in seconds after which no more invoices are issued.
+    pub expires_at: Option<u64>,
+    pub signature: schnorr::Signature,
+}
+
+impl Offer {
+    pub fn sign(
+        network: Network,
+        payee: Bytes32,
+        key: &KeyPair,
+        amount: Option<u64>,
+        description: String,
+        expires_at: Option<u64>,
+    ) -> Result<Self, OfferError> {
+        let payee_key = key.x_only_public_key().0;
+        let body = offer_body(&payee, &payee_key, amount, expires_at, &description)?;
+        Ok(Self {
+            network,
+            payee,
+            payee_key,
+            amount,
+            description,
+            expires_at,
+            signature: Secp256k1::new().sign_schnorr(&signing_message(network, &body), key),
+        })
+    }
+
+    /// Gets the id requests name the offer by, a hash of everything the payee signed.
+    pub fn id(&self) -> Result<Bytes32, OfferError> {
+        Ok(Sha256::new()
+            .chain_update(b"overpass/offer-id")
+            .chain_update(prefix(self.network))
+            .chain_update(self.body()?)
+            .finalize()
+            .into())
+    }
+
+    pub fn verify(&self) -> bool {
+        let Ok(body) = self.body() else {
+            return false;
+        };
+        Secp256k1::verification_only()
+            .verify_schnorr(
+                &self.signature,
+                &signing_message(self.network, &body),
+                &self.payee_key,
+            )
+            .is_ok()
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires_at.is_some_and(|expires_at| now > expires_at)
+    }
+
+    /// Checks the amount a payer asks to pay against the offer and settles on one.
+    pub fn amount_for(&self, requested: Option<u64>) -> Result<u64, OfferError> {
+        match (self.amount, requested) {
+            (Some(expected), Some(requested)) if expected != requested => {
+                Err(OfferError::AmountMismatch {
+                    expected,
+                    requested,
+                })
+            }
+            (Some(amount), _) | (None, Some(amount)) => Ok(amount),
+            (None, None) => Err(OfferError::AmountRequired),
+        }
+    }
+
+    /// Encodes the offer as bech32m under its network's prefix.
+    pub fn encode(&self) -> Result<String, OfferError> {
+        let mut bytes = self.body()?;
+        bytes.extend_from_slice(self.signature.as_ref());
+        bech32::encode(prefix(self.network), bytes.to_base32(), Variant::Bech32m)
+            .map_err(|e| InvoiceError::Encoding(e.to_string()).into())
+    }
+
+    pub fn decode(encoded: &str) -> Result<Self, OfferError> {
+        let (hrp, data, variant) =
+            bech32::decode(encoded).map_err(|e| InvoiceError::Encoding(e.to_string()))?;
+        if variant != Variant::Bech32m {
+            return Err(InvoiceError::Encoding("expected bech32m".into()).into());
+        }
+        let network = network_for(&hrp).ok_or(InvoiceError::UnknownPrefix(hrp))?;
+        let bytes =
+            Vec::<u8>::from_base32(&data).map_err(|e| InvoiceError::Encoding(e.to_string()))?;
+        let mut reader = Reader(&bytes);
+        let version = reader.u8()?;
+        if version != OFFER_VERSION {
+            return Err(InvoiceError::UnsupportedVersion(version).into());
+        }
+        let payee = reader.bytes32()?;
+        let payee_key = XOnlyPublicKey::from_slice(reader.take(32)?)
+            .map_err(|_| InvoiceError::Malformed("payee key"))?;
+        let amount = Some(reader.u64()?).filter(|amount| *amount > 0);
+        let expires_at = Some(reader.u64()?).filter(|expires_at| *expires_at > 0);
+        let description = reader.string()?.unwrap_or_default();
+        let signature = schnorr::Signature::from_slice(reader.take(64)?)
+            .map_err(|_| InvoiceError::Malformed("signature"))?;
+        if !reader.0.is_empty() {
+            return Err(InvoiceError::Malformed("trailing bytes").into());
+        }
+        Ok(Self {
+            network,
+            payee,
+            payee_key,
+            amount,
+            description,
+            expires_at,
+            signature,
+        })
+    }
+
+    fn body(&self) -> Result<Vec<u8>, OfferError> {
+        offer_body(
+            &self.payee,
+            &self.payee_key,
+            self.amount,
+            self.expires_at,
+            &self.description,
+        )
+    }
+}
+
+/// Lays out everything but the signature; a zero amount or expiry stands for none.
+fn offer_body(
+    payee: &Bytes32,
+    payee_key: &XOnlyPublicKey,
+    amount: Option<u64>,
+    expires_at: Option<u64>,
+    description: &str,
+) -> Result<Vec<u8>, OfferError> {
+    let mut bytes = vec![OFFER_VERSION];
+    bytes.extend_from_slice(payee);
+    bytes.extend_from_slice(&payee_key.serialize());
+    bytes.extend_from_slice(&amount.unwrap_or(0).to_le_bytes());
+    bytes.extend_from_slice(&expires_at.unwrap_or(0).to_le_bytes());
+    push_string(&mut bytes, Some(description))?;
+    Ok(bytes)
+}
+
+fn signing_message(network: Network, body: &[u8]) -> Message {
+    let mut hasher = Sha256::new();
+    hasher.update(b"overpass/offer/v1");
+    hasher.update(prefix(network));
+    hasher.update(body);
+    message_from(hasher)
+}
+
+/// A payer's request for an invoice against an offer, signed with a key used for this
+/// request only.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InvoiceRequest {
+    pub offer_id: Bytes32,
+    /// Wallet id the payer holds channels with the payee under.
+    pub payer: Bytes32,
+    pub payer_key: XOnlyPublicKey,
+    /// Makes each request, and so each invoice, unique.
+    pub payer_nonce: Bytes32,
+    pub amount: Option<u64>,
+    pub note: Option<String>,
+    pub signature: schnorr::Signature,
+}
+
+impl InvoiceRequest {
+    /// Builds and signs a request under a fresh payer key.
+    pub fn new(
+        offer_id: Bytes32,
+        payer: Bytes32,
+        amount: Option<u64>,
+        note: Option<String>,
+    ) -> Self {
+        let secp = Secp256k1::new();
+        let key = KeyPair::new(&secp, &mut rand::thread_rng());
+        let payer_key = key.x_only_public_key().0;
+        let payer_nonce = Sha256::new()
+            .chain_update(b"overpass/payer-nonce")
+            .chain_update(key.secret_bytes())
+            .finalize()
+            .into();
+        let message = Self::message(&offer_id, &payer, &payer_key, &payer_nonce, amount, &note);
+        Self {
+            offer_id,
+            payer,
+            payer_key,
+            payer_nonce,
+            amount,
+            note,
+            signature: secp.sign_schnorr(&message, &key),
+        }
+    }
+
+    /// Gets the id the payee answers the request under.
+    pub fn id(&self) -> Bytes32 {
+        Sha256::new()
+            .chain_update(b"overpass/invoice-request")
+            .chain_update(self.offer_id)
+            .chain_update(self.payer_key.serialize())
+            .chain_update(self.payer_nonce)
+            .finalize()
+            .into()
+    }
+
+    pub fn verify(&self) -> bool {
+        let message = Self::message(
+            &self.offer_id,
+            &self.payer,
+            &self.payer_key,
+            &self.payer_nonce,
+            self.amount,
+            &self.note,
+        );
+        Secp256k1::verification_only()
+            .verify_schnorr(&self.signature, &message, &self.payer_key)
+            .is_ok()
+    }
+
+    fn message(
+        offer_id: &Bytes32,
+        payer: &Bytes32,
+        payer_key: &XOnlyPublicKey,
+        payer_nonce: &Bytes32,
+        amount: Option<u64>,
+        note: &Option<String>,
+    ) -> Message {
+        let mut hasher = Sha256::new();
+        hasher.update(b"overpass/invoice-request/v1");
+        hasher.update(offer_id);
+        hasher.update(payer);
+        hasher.update(payer_key.serialize());
+        hasher.update(payer_nonce);
+        hasher.update(amount.unwrap_or(0).to_le_bytes());
+        let note = note.as_deref().unwrap_or_default();
+        hasher.update((note.len() as u64).to_le_bytes());
+        hasher.update(note);
+        message_from(hasher)
+    }
+}
+
+/// Messages exchanged between payer and payee to turn an offer into an invoice.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OfferMessage {
+    InvoiceRequest(Box<InvoiceRequest>),
+    /// An encoded invoice issued for the request.
+    Invoice {
+        request_id: Bytes32,
+        invoice: String,
+    },
+    InvoiceError {
+        request_id: Bytes32,
+        reason: String,
+    },
+}
+
+impl OfferMessage {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, OfferError> {
+        serde_json::to_vec(self).map_err(|e| OfferError::InvalidMessage(e.to_string()))
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, OfferError> {
+        serde_json::from_slice(bytes).map_err(|e| OfferError::InvalidMessage(e.to_string()))
+    }
+}
+
+fn prefix(network: Network) -> &'static str {
+    match network {
+        Network::Bitcoin => "ovo",
+        Network::Testnet => "ovot",
+        Network::Signet => "ovos",
+        _ => "ovort",
+    }
+}
+
+fn network_for(prefix: &str) -> Option<Network> {
+    match prefix {
+        "ovo" => Some(Network::Bitcoin),
+        "ovot" => Some(Network::Testnet),
+        "ovos" => Some(Network::Signet),
+        "ovort" => Some(Network::Regtest),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+    use crate::zkp::wallet_contract::{WalletContract, WalletContractError};
+
+    const SHOP: Bytes32 = [7u8; 32];
+    const CUSTOMER: Bytes32 = [1u8; 32];
+
+    fn wallets() -> Result<(WalletContract, WalletContract), WalletContractError> {
+        let shop = test_util::wallet(SHOP);
+        let mut customer = test_util::unkeyed_wallet(CUSTOMER);
+        customer.register_channel([2u8; 32], 500, SHOP, Vec::new())?;
+        Ok((shop, customer))
+    }
+
+    #[test]
+    fn test_offer_round_trips_and_is_signed() -> Result<(), OfferError> {
+        let key = test_util::key_manager().invoice_key().unwrap();
+        let offer = Offer::sign(Network::Regtest, SHOP, &key, None, "tips".into(), Some(10))?;
+        assert!(offer.verify());
+        let encoded = offer.encode()?;
+        assert!(encoded.starts_with("ovort1"));
+        assert_eq!(Offer::decode(&encoded)?, offer);
+        assert_eq!(offer.amount_for(Some(5))?, 5);
+        assert_eq!(offer.amount_for(None), Err(OfferError::AmountRequired));
+        assert!(offer.is_expired(11));
+
+        let mut altered = offer.clone();
+        altered.description = "refunds".into();
+        assert!(!altered.verify());
+        assert_ne!(altered.id()?, offer.id()?);
+
+        let request = InvoiceRequest::new(offer.id()?, CUSTOMER, Some(5), Some("thanks".into()));
+        assert!(request.verify());
+        let message = OfferMessage::InvoiceRequest(Box::new(request.clone()));
+        assert_eq!(OfferMessage::from_bytes(&message.to_bytes()?)?, message);
+        let mut forged = request;
+        forged.amount = Some(1);
+        assert!(!forged.verify());
+        Ok(())
+    }
+
+    #[test]
+    fn test_each_request_gets_its_own_invoice() -> Result<(), WalletContractError> {
+        let (mut shop, mut customer) = wallets()?;
+        let offer = shop
+            .create_offer(Some(25), "coffee".into(), None)?
+            .encode()?;
+
+        let first = customer.request_invoice(&offer, None, None)?;
+        let second = customer.request_invoice(&offer, None, None)?;
+        let first_reply = shop.respond_to_offer(&first);
+        assert_eq!(shop.respond_to_offer(&first), first_reply);
+        let second_reply = shop.respond_to_offer(&second);
+        let (OfferMessage::Invoice { invoice: a, .. }, OfferMessage::Invoice { invoice: b, .. }) =
+            (&first_reply, &second_reply)
+        else {
+            panic!("expected invoices");
+        };
+        assert_ne!(a, b);
+
+        let paid = customer.pay_offer_response(&first_reply)?;
+        assert_eq!(paid.amount, 25);
+        customer.pay_offer_response(&second_reply)?;
+        assert_eq!(
+            customer.get_channel(&[2u8; 32]).unwrap().balances,
+            vec![450]
+        );
+
+        assert!(matches!(
+            customer.request_invoice(&offer, Some(30), None),
+            Err(WalletContractError::OfferError(
+                OfferError::AmountMismatch { .. }
+            ))
+        ));
+        let OfferMessage::InvoiceRequest(mut tampered) =
+            customer.request_invoice(&offer, None, None)?
+        else {
+            panic!("expected a request");
+        };
+        tampered.note = Some("free".into());
+        assert!(matches!(
+            shop.respond_to_offer(&OfferMessage::InvoiceRequest(tampered)),
+            OfferMessage::InvoiceError { .. }
+        ));
+        assert!(matches!(
+            customer.pay_offer_response(&OfferMessage::Invoice {
+                request_id: [0u8; 32],
+                invoice: String::new(),
+            }),
+            Err(WalletContractError::OfferError(OfferError::UnknownRequest(
+                _
+            )))
+        ));
+        Ok(())
+    }
+}
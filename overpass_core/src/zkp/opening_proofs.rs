@@ -0,0 +1,517 @@
+// src/zkp/opening_proofs.rs
+
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use curve25519_dalek::scalar::Scalar;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512};
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::zkp::blinding::BlindingFactor;
+use crate::zkp::helpers::Bytes32;
+use crate::zkp::pedersen_commitment::PedersenCommitment;
+use crate::zkp::pedersen_parameters::PedersenParameters;
+
+/// Bits a range proof decomposes its value into, enough for any `u64`.
+pub const RANGE_BITS: usize = 64;
+
+/// Proof of knowledge of a value and blinding opening a commitment, without revealing them.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OpeningProof {
+    /// Commitment to the prover's nonces, `g * a + h * b`.
+    pub nonce_commitment: Bytes32,
+    pub value_response: Bytes32,
+    pub blinding_response: Bytes32,
+}
+
+/// Proof that a commitment opens to a publicly claimed value.
+///
+/// It shows knowledge of the discrete log of `C - g * value` with respect to `h`, which
+/// exists only if the commitment hides `value`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValueProof {
+    pub nonce_commitment: Bytes32,
+    pub response: Bytes32,
+}
+
+/// Proof that two commitments hide the same value, without revealing it.
+///
+/// Equal values make `C1 - C2 = h * (r1 - r2)`, so this is a proof of knowledge of that
+/// discrete log with respect to `h`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EqualityProof {
+    pub nonce_commitment: Bytes32,
+    pub response: Bytes32,
+}
+
+/// Proof that a commitment to one bit hides 0 or 1: an OR of proofs that `C` or `C - g`
+/// is a multiple of `h`, only one of which the prover can answer honestly.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BitProof {
+    pub commitment: Bytes32,
+    pub challenges: [Bytes32; 2],
+    pub responses: [Bytes32; 2],
+}
+
+/// Proof that a commitment hides a value below `2^RANGE_BITS`, without revealing it.
+///
+/// The value is split into bit commitments that sum, weighted by powers of two, to the
+/// commitment; each comes with a proof that it hides a bit.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RangeProof {
+    pub bits: Vec<BitProof>,
+}
+
+fn random_scalar() -> Scalar {
+    let mut wide = Zeroizing::new([0u8; 64]);
+    OsRng.fill_bytes(wide.as_mut());
+    Scalar::from_bytes_mod_order_wide(&wide)
+}
+
+/// Derives the Fiat-Shamir challenge from the statement, the prover's nonce commitment and
+/// a caller-chosen context such as the channel id, so proofs cannot be replayed elsewhere.
+fn challenge(
+    domain: &[u8],
+    params: &PedersenParameters,
+    points: &[&RistrettoPoint],
+    context: &[u8],
+) -> Scalar {
+    let mut hasher = Sha512::new();
+    hasher.update(domain);
+    hasher.update(params.to_bytes());
+    for point in points {
+        hasher.update(point.compress().as_bytes());
+    }
+    hasher.update((context.len() as u64).to_le_bytes());
+    hasher.update(context);
+    Scalar::from_bytes_mod_order_wide(&hasher.finalize().into())
+}
+
+fn decode_point(bytes: &Bytes32) -> Option<RistrettoPoint> {
+    CompressedRistretto(*bytes).decompress()
+}
+
+fn decode_scalar(bytes: &Bytes32) -> Option<Scalar> {
+    Scalar::from_canonical_bytes(*bytes).into()
+}
+
+/// Proves knowledge of a discrete log `secret` of `target` with respect to `h`.
+fn prove_blinding(
+    domain: &[u8],
+    params: &PedersenParameters,
+    target: &RistrettoPoint,
+    secret: &Scalar,
+    context: &[u8],
+) -> (Bytes32, Bytes32) {
+    let mut nonce = random_scalar();
+    let nonce_commitment = params.h * nonce;
+    let e = challenge(domain, params, &[target, &nonce_commitment], context);
+    let response = (nonce + e * secret).to_bytes();
+    nonce.zeroize();
+    (nonce_commitment.compress().to_bytes(), response)
+}
+
+fn verify_blinding(
+    domain: &[u8],
+    params: &PedersenParameters,
+    target: &RistrettoPoint,
+    nonce_commitment: &Bytes32,
+    response: &Bytes32,
+    context: &[u8],
+) -> bool {
+    let (Some(nonce_commitment), Some(response)) =
+        (decode_point(nonce_commitment), decode_scalar(response))
+    else {
+        return false;
+    };
+    let e = challenge(domain, params, &[target, &nonce_commitment], context);
+    params.h * response == nonce_commitment + target * e
+}
+
+impl OpeningProof {
+    const DOMAIN: &'static [u8] = b"overpass/sigma/opening/v1";
+
+    pub fn prove(
+        params: &PedersenParameters,
+        commitment: &PedersenCommitment,
+        value: u64,
+        blinding: &Scalar,
+        context: &[u8],
+    ) -> Self {
+        let (mut a, mut b) = (random_scalar(), random_scalar());
+        let nonce_commitment = params.g * a + params.h * b;
+        let e = challenge(
+            Self::DOMAIN,
+            params,
+            &[commitment.point(), &nonce_commitment],
+            context,
+        );
+        let proof = Self {
+            nonce_commitment: nonce_commitment.compress().to_bytes(),
+            value_response: (a + e * Scalar::from(value)).to_bytes(),
+            blinding_response: (b + e * blinding).to_bytes(),
+        };
+        a.zeroize();
+        b.zeroize();
+        proof
+    }
+
+    pub fn verify(
+        &self,
+        params: &PedersenParameters,
+        commitment: &PedersenCommitment,
+        context: &[u8],
+    ) -> bool {
+        let (Some(nonce_commitment), Some(value_response), Some(blinding_response)) = (
+            decode_point(&self.nonce_commitment),
+            decode_scalar(&self.value_response),
+            decode_scalar(&self.blinding_response),
+        ) else {
+            return false;
+        };
+        let e = challenge(
+            Self::DOMAIN,
+            params,
+            &[commitment.point(), &nonce_commitment],
+            context,
+        );
+        params.g * value_response + params.h * blinding_response
+            == nonce_commitment + commitment.point() * e
+    }
+}
+
+impl ValueProof {
+    const DOMAIN: &'static [u8] = b"overpass/sigma/value/v1";
+
+    pub fn prove(
+        params: &PedersenParameters,
+        commitment: &PedersenCommitment,
+        value: u64,
+        blinding: &Scalar,
+        context: &[u8],
+    ) -> Self {
+        let target = Self::target(params, commitment, value);
+        let (nonce_commitment, response) = prove_blinding(
+            Self::DOMAIN,
+            params,
+            &target,
+            blinding,
+            &Self::bind(value, context),
+        );
+        Self {
+            nonce_commitment,
+            response,
+        }
+    }
+
+    pub fn verify(
+        &self,
+        params: &PedersenParameters,
+        commitment: &PedersenCommitment,
+        value: u64,
+        context: &[u8],
+    ) -> bool {
+        let target = Self::target(params, commitment, value);
+        verify_blinding(
+            Self::DOMAIN,
+            params,
+            &target,
+            &self.nonce_commitment,
+            &self.response,
+            &Self::bind(value, context),
+        )
+    }
+
+    fn target(
+        params: &PedersenParameters,
+        commitment: &PedersenCommitment,
+        value: u64,
+    ) -> RistrettoPoint {
+        commitment.point() - params.g * Scalar::from(value)
+    }
+
+    fn bind(value: u64, context: &[u8]) -> Vec<u8> {
+        [&value.to_le_bytes(), context].concat()
+    }
+}
+
+impl EqualityProof {
+    const DOMAIN: &'static [u8] = b"overpass/sigma/equality/v1";
+
+    /// Proves `first` and `second` hide the same value, given both blindings.
+    pub fn prove(
+        params: &PedersenParameters,
+        first: &PedersenCommitment,
+        second: &PedersenCommitment,
+        first_blinding: &Scalar,
+        second_blinding: &Scalar,
+        context: &[u8],
+    ) -> Self {
+        let target = first.point() - second.point();
+        let difference = BlindingFactor::from_scalar(first_blinding - second_blinding);
+        let (nonce_commitment, response) = prove_blinding(
+            Self::DOMAIN,
+            params,
+            &target,
+            difference.scalar(),
+            context,
+        );
+        Self {
+            nonce_commitment,
+            response,
+        }
+    }
+
+    pub fn verify(
+        &self,
+        params: &PedersenParameters,
+        first: &PedersenCommitment,
+        second: &PedersenCommitment,
+        context: &[u8],
+    ) -> bool {
+        let target = first.point() - second.point();
+        verify_blinding(
+            Self::DOMAIN,
+            params,
+            &target,
+            &self.nonce_commitment,
+            &self.response,
+            context,
+        )
+    }
+}
+
+impl RangeProof {
+    const DOMAIN: &'static [u8] = b"overpass/sigma/range/v1";
+
+    pub fn prove(
+        params: &PedersenParameters,
+        commitment: &PedersenCommitment,
+        value: u64,
+        blinding: &Scalar,
+        context: &[u8],
+    ) -> Self {
+        // Bit blindings are random but the last, which makes their weighted sum `blinding`.
+        let mut blindings: Vec<Scalar> = (1..RANGE_BITS).map(|_| random_scalar()).collect();
+        let (mut sum, mut weight) = (Scalar::ZERO, Scalar::ONE);
+        for bit_blinding in &blindings {
+            sum += weight * bit_blinding;
+            weight += weight;
+        }
+        blindings.push((blinding - sum) * weight.invert());
+
+        let bits = blindings
+            .iter()
+            .enumerate()
+            .map(|(index, bit_blinding)| {
+                let bit = (value >> index) & 1;
+                let bit_commitment = params.g * Scalar::from(bit) + params.h * bit_blinding;
+                Self::prove_bit(
+                    params,
+                    commitment,
+                    &bit_commitment,
+                    bit as usize,
+                    bit_blinding,
+                    &Self::bind(index, context),
+                )
+            })
+            .collect();
+        blindings.zeroize();
+        Self { bits }
+    }
+
+    pub fn verify(
+        &self,
+        params: &PedersenParameters,
+        commitment: &PedersenCommitment,
+        context: &[u8],
+    ) -> bool {
+        if self.bits.len() != RANGE_BITS {
+            return false;
+        }
+        let mut total = RistrettoPoint::default();
+        let mut weight = Scalar::ONE;
+        for (index, bit) in self.bits.iter().enumerate() {
+            let Some(bit_commitment) = decode_point(&bit.commitment) else {
+                return false;
+            };
+            let context = Self::bind(index, context);
+            if !Self::verify_bit(params, commitment, &bit_commitment, bit, &context) {
+                return false;
+            }
+            total += bit_commitment * weight;
+            weight += weight;
+        }
+        total == *commitment.point()
+    }
+
+    /// Answers the branch for `bit` with `blinding` and simulates the other.
+    fn prove_bit(
+        params: &PedersenParameters,
+        commitment: &PedersenCommitment,
+        bit_commitment: &RistrettoPoint,
+        bit: usize,
+        blinding: &Scalar,
+        context: &[u8],
+    ) -> BitProof {
+        let targets = [*bit_commitment, bit_commitment - params.g];
+        let other = 1 - bit;
+        let (other_challenge, other_response) = (random_scalar(), random_scalar());
+        let mut nonce = random_scalar();
+        let mut nonces = [RistrettoPoint::default(); 2];
+        nonces[bit] = params.h * nonce;
+        nonces[other] = params.h * other_response - targets[other] * other_challenge;
+
+        let e = challenge(
+            Self::DOMAIN,
+            params,
+            &[commitment.point(), bit_commitment, &nonces[0], &nonces[1]],
+            context,
+        );
+        let mut challenges = [Scalar::ZERO; 2];
+        let mut responses = [Scalar::ZERO; 2];
+        challenges[other] = other_challenge;
+        responses[other] = other_response;
+        challenges[bit] = e - other_challenge;
+        responses[bit] = nonce + challenges[bit] * blinding;
+        nonce.zeroize();
+        BitProof {
+            commitment: bit_commitment.compress().to_bytes(),
+            challenges: challenges.map(|c| c.to_bytes()),
+            responses: responses.map(|z| z.to_bytes()),
+        }
+    }
+
+    fn verify_bit(
+        params: &PedersenParameters,
+        commitment: &PedersenCommitment,
+        bit_commitment: &RistrettoPoint,
+        proof: &BitProof,
+        context: &[u8],
+    ) -> bool {
+        let targets = [*bit_commitment, bit_commitment - params.g];
+        let mut challenges = [Scalar::ZERO; 2];
+        let mut nonces = [RistrettoPoint::default(); 2];
+        for branch in 0..2 {
+            let (Some(e), Some(z)) = (
+                decode_scalar(&proof.challenges[branch]),
+                decode_scalar(&proof.responses[branch]),
+            ) else {
+                return false;
+            };
+            challenges[branch] = e;
+            nonces[branch] = params.h * z - targets[branch] * e;
+        }
+        let e = challenge(
+            Self::DOMAIN,
+            params,
+            &[commitment.point(), bit_commitment, &nonces[0], &nonces[1]],
+            context,
+        );
+        challenges[0] + challenges[1] == e
+    }
+
+    fn bind(index: usize, context: &[u8]) -> Vec<u8> {
+        [&(index as u64).to_le_bytes(), context].concat()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zkp::pedersen_commitment::commit_sub;
+
+    fn commit(value: u64, blinding: u64) -> (PedersenCommitment, Scalar) {
+        let blinding = Scalar::from(blinding);
+        (
+            PedersenCommitment::commit(&PedersenParameters::default(), value, &blinding),
+            blinding,
+        )
+    }
+
+    #[test]
+    fn test_opening_proof() {
+        let params = PedersenParameters::default();
+        let (commitment, blinding) = commit(700, 5);
+        let proof = OpeningProof::prove(&params, &commitment, 700, &blinding, b"channel-1");
+
+        assert!(proof.verify(&params, &commitment, b"channel-1"));
+        assert!(!proof.verify(&params, &commitment, b"channel-2"));
+        assert!(!proof.verify(&params, &commit(700, 6).0, b"channel-1"));
+
+        // A prover who does not know the opening cannot produce a valid proof.
+        let forged = OpeningProof::prove(&params, &commitment, 701, &blinding, b"channel-1");
+        assert!(!forged.verify(&params, &commitment, b"channel-1"));
+
+        let decoded: OpeningProof =
+            serde_json::from_str(&serde_json::to_string(&proof).unwrap()).unwrap();
+        assert!(decoded.verify(&params, &commitment, b"channel-1"));
+    }
+
+    #[test]
+    fn test_value_proof() {
+        let params = PedersenParameters::default();
+        let (commitment, blinding) = commit(700, 5);
+        let proof = ValueProof::prove(&params, &commitment, 700, &blinding, b"dispute");
+
+        assert!(proof.verify(&params, &commitment, 700, b"dispute"));
+        assert!(!proof.verify(&params, &commitment, 701, b"dispute"));
+        assert!(
+            !ValueProof::prove(&params, &commitment, 701, &blinding, b"dispute").verify(
+                &params,
+                &commitment,
+                701,
+                b"dispute"
+            )
+        );
+    }
+
+    #[test]
+    fn test_equality_proof() {
+        let params = PedersenParameters::default();
+        let (first, first_blinding) = commit(42, 1);
+        let (second, second_blinding) = commit(42, 99);
+        let proof = EqualityProof::prove(
+            &params,
+            &first,
+            &second,
+            &first_blinding,
+            &second_blinding,
+            b"",
+        );
+        assert!(proof.verify(&params, &first, &second, b""));
+        assert!(!proof.verify(&params, &second, &first, b""));
+
+        let (other, other_blinding) = commit(43, 99);
+        let proof = EqualityProof::prove(
+            &params,
+            &first,
+            &other,
+            &first_blinding,
+            &other_blinding,
+            b"",
+        );
+        assert!(!proof.verify(&params, &first, &other, b""));
+    }
+
+    #[test]
+    fn test_range_proof() {
+        let params = PedersenParameters::default();
+        let (commitment, blinding) = commit(u64::MAX - 3, 11);
+        let proof = RangeProof::prove(&params, &commitment, u64::MAX - 3, &blinding, b"funds");
+        assert_eq!(proof.bits.len(), RANGE_BITS);
+        assert!(proof.verify(&params, &commitment, b"funds"));
+        assert!(!proof.verify(&params, &commitment, b"other"));
+        assert!(!proof.verify(&params, &commit(u64::MAX - 3, 12).0, b"funds"));
+
+        // A commitment to a negative value cannot be proven non-negative.
+        let negative = commit_sub(&commit(5, 11).0, &commit(6, 0).0);
+        let forged = RangeProof::prove(&params, &negative, u64::MAX, &blinding, b"funds");
+        assert!(!forged.verify(&params, &negative, b"funds"));
+
+        let mut tampered = proof.clone();
+        tampered.bits.swap(0, 1);
+        assert!(!tampered.verify(&params, &commitment, b"funds"));
+    }
+}
@@ -0,0 +1,400 @@
+// src/zkp/operator_keys.rs
+
+use bitcoin::secp256k1::{schnorr, Message, Secp256k1, Verification, XOnlyPublicKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::zkp::global_root_contract::SealedEpoch;
+use crate::zkp::helpers::Bytes32;
+
+/// Blocks between announcing a rotation and its earliest activation by default, two weeks.
+pub const DEFAULT_ROTATION_GRACE_PERIOD: u32 = 2_016;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum OperatorKeyError {
+    #[error("Key set needs 1..={0} signers, got a threshold of {1}")]
+    InvalidThreshold(usize, usize),
+    #[error("Rotation is not signed by the current operator keys")]
+    Unauthorized,
+    #[error("Rotation activates at {0}, before the grace period ends at {1}")]
+    GracePeriod(u32, u32),
+    #[error("A rotation activating at {0} is already pending")]
+    RotationPending(u32),
+    #[error("Attestation does not match epoch {0}")]
+    AttestationMismatch(u64),
+}
+
+/// The keys operating the global root contract: a single key, or a federation of which
+/// `threshold` members must sign.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OperatorKeySet {
+    pub keys: Vec<XOnlyPublicKey>,
+    pub threshold: usize,
+}
+
+impl OperatorKeySet {
+    pub fn new(keys: Vec<XOnlyPublicKey>, threshold: usize) -> Result<Self, OperatorKeyError> {
+        let set = Self { keys, threshold };
+        set.validate()?;
+        Ok(set)
+    }
+
+    /// Checks that `threshold` is between one and the number of distinct keys, so the set
+    /// can neither be satisfied by nobody nor never be satisfied.
+    pub fn validate(&self) -> Result<(), OperatorKeyError> {
+        let mut distinct = self.keys.clone();
+        distinct.sort_unstable();
+        distinct.dedup();
+        if self.threshold == 0 || self.threshold > distinct.len() {
+            return Err(OperatorKeyError::InvalidThreshold(distinct.len(), self.threshold));
+        }
+        Ok(())
+    }
+
+    pub fn single(key: XOnlyPublicKey) -> Self {
+        Self {
+            keys: vec![key],
+            threshold: 1,
+        }
+    }
+
+    /// Checks that at least `threshold` distinct members signed `message`.
+    pub fn verify<C: Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        message: &Message,
+        signatures: &[(XOnlyPublicKey, schnorr::Signature)],
+    ) -> bool {
+        let mut signers: Vec<&XOnlyPublicKey> = signatures
+            .iter()
+            .filter(|(key, signature)| {
+                self.keys.contains(key) && secp.verify_schnorr(signature, message, key).is_ok()
+            })
+            .map(|(key, _)| key)
+            .collect();
+        signers.sort_unstable();
+        signers.dedup();
+        signers.len() >= self.threshold
+    }
+
+    fn digest_into(&self, hasher: &mut Sha256) {
+        hasher.update((self.threshold as u64).to_le_bytes());
+        hasher.update((self.keys.len() as u64).to_le_bytes());
+        for key in &self.keys {
+            hasher.update(key.serialize());
+        }
+    }
+}
+
+/// An announced change of operator keys, signed by the keys it replaces.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyRotation {
+    pub new_keys: OperatorKeySet,
+    /// Height from which the new keys sign; the old keys stay valid until then.
+    pub activation_height: u32,
+    pub signatures: Vec<(XOnlyPublicKey, schnorr::Signature)>,
+}
+
+impl KeyRotation {
+    /// Gets the message the current keys sign to authorize the rotation.
+    pub fn message(new_keys: &OperatorKeySet, activation_height: u32) -> Message {
+        let mut hasher = Sha256::new();
+        hasher.update(b"overpass/operator/rotation/v1");
+        new_keys.digest_into(&mut hasher);
+        hasher.update(activation_height.to_le_bytes());
+        message_from(hasher)
+    }
+}
+
+/// Operator signatures over a sealed epoch, made at `height`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EpochAttestation {
+    pub epoch: u64,
+    pub root: Bytes32,
+    pub height: u32,
+    pub signatures: Vec<(XOnlyPublicKey, schnorr::Signature)>,
+}
+
+impl EpochAttestation {
+    /// Gets the message operators sign for a sealed epoch.
+    pub fn message(sealed: &SealedEpoch, height: u32) -> Message {
+        let mut hasher = Sha256::new();
+        hasher.update(b"overpass/operator/epoch/v1");
+        hasher.update(sealed.epoch.to_le_bytes());
+        hasher.update(sealed.root);
+        hasher.update(sealed.digest);
+        hasher.update(height.to_le_bytes());
+        message_from(hasher)
+    }
+}
+
+pub(crate) fn message_from(hasher: Sha256) -> Message {
+    Message::from_slice(&hasher.finalize()).expect("a sha256 digest is a valid message")
+}
+
+/// Every operator key set with the height it became active, plus any announced rotation.
+///
+/// Old key sets are kept, so attestations made before a rotation keep verifying against
+/// the keys that were active when they were made.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OperatorKeySchedule {
+    /// `(activation_height, keys)`, ordered by height; the first set is active from genesis.
+    sets: Vec<(u32, OperatorKeySet)>,
+    pending: Option<KeyRotation>,
+    grace_period: u32,
+}
+
+impl OperatorKeySchedule {
+    pub fn new(initial: OperatorKeySet) -> Self {
+        Self {
+            sets: vec![(0, initial)],
+            pending: None,
+            grace_period: DEFAULT_ROTATION_GRACE_PERIOD,
+        }
+    }
+
+    /// Sets the minimum number of blocks between announcing and activating a rotation.
+    pub fn with_grace_period(mut self, blocks: u32) -> Self {
+        self.grace_period = blocks;
+        self
+    }
+
+    /// Gets the keys active at `height`.
+    pub fn keys_at(&self, height: u32) -> &OperatorKeySet {
+        self.sets
+            .iter()
+            .rev()
+            .find(|(activation, _)| *activation <= height)
+            .map(|(_, keys)| keys)
+            .unwrap_or(&self.sets[0].1)
+    }
+
+    pub fn pending(&self) -> Option<&KeyRotation> {
+        self.pending.as_ref()
+    }
+
+    /// Accepts a rotation announced at `height` if its key set is valid, the keys active
+    /// then signed it and it leaves at least the grace period for watchers to notice.
+    pub fn announce<C: Verification>(
+        &mut self,
+        secp: &Secp256k1<C>,
+        rotation: KeyRotation,
+        height: u32,
+    ) -> Result<(), OperatorKeyError> {
+        rotation.new_keys.validate()?;
+        self.activate(height);
+        if let Some(pending) = &self.pending {
+            return Err(OperatorKeyError::RotationPending(pending.activation_height));
+        }
+        let earliest = height.saturating_add(self.grace_period);
+        if rotation.activation_height < earliest {
+            return Err(OperatorKeyError::GracePeriod(
+                rotation.activation_height,
+                earliest,
+            ));
+        }
+        let message = KeyRotation::message(&rotation.new_keys, rotation.activation_height);
+        if !self
+            .keys_at(height)
+            .verify(secp, &message, &rotation.signatures)
+        {
+            return Err(OperatorKeyError::Unauthorized);
+        }
+        self.pending = Some(rotation);
+        Ok(())
+    }
+
+    /// Moves a pending rotation into the schedule once `height` reaches its activation.
+    pub fn activate(&mut self, height: u32) -> bool {
+        match self.pending.take() {
+            Some(rotation) if rotation.activation_height <= height => {
+                self.sets
+                    .push((rotation.activation_height, rotation.new_keys));
+                true
+            }
+            pending => {
+                self.pending = pending;
+                false
+            }
+        }
+    }
+
+    /// Checks an attestation of `sealed` against the keys active at its height.
+    pub fn verify_attestation<C: Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        sealed: &SealedEpoch,
+        attestation: &EpochAttestation,
+    ) -> Result<(), OperatorKeyError> {
+        if attestation.epoch != sealed.epoch || attestation.root != sealed.root {
+            return Err(OperatorKeyError::AttestationMismatch(sealed.epoch));
+        }
+        let message = EpochAttestation::message(sealed, attestation.height);
+        let keys = self.pending_keys_at(attestation.height);
+        if !keys.verify(secp, &message, &attestation.signatures) {
+            return Err(OperatorKeyError::Unauthorized);
+        }
+        Ok(())
+    }
+
+    /// Gets the keys active at `height`, counting a pending rotation that has reached its
+    /// activation height but not yet been moved into the schedule.
+    fn pending_keys_at(&self, height: u32) -> &OperatorKeySet {
+        match &self.pending {
+            Some(rotation) if rotation.activation_height <= height => &rotation.new_keys,
+            _ => self.keys_at(height),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::secp256k1::{KeyPair, SecretKey};
+
+    fn keypair(byte: u8) -> KeyPair {
+        KeyPair::from_secret_key(
+            &Secp256k1::new(),
+            &SecretKey::from_slice(&[byte; 32]).unwrap(),
+        )
+    }
+
+    fn sign(signers: &[u8], message: &Message) -> Vec<(XOnlyPublicKey, schnorr::Signature)> {
+        let secp = Secp256k1::new();
+        signers
+            .iter()
+            .map(|byte| {
+                let keypair = keypair(*byte);
+                (
+                    keypair.x_only_public_key().0,
+                    secp.sign_schnorr(message, &keypair),
+                )
+            })
+            .collect()
+    }
+
+    fn federation(members: &[u8], threshold: usize) -> OperatorKeySet {
+        let keys = members
+            .iter()
+            .map(|byte| keypair(*byte).x_only_public_key().0)
+            .collect();
+        OperatorKeySet::new(keys, threshold).unwrap()
+    }
+
+    fn sealed(epoch: u64) -> SealedEpoch {
+        SealedEpoch {
+            epoch,
+            previous_root: [0u8; 32],
+            root: [epoch as u8 + 1; 32],
+            updates: Vec::new(),
+            digest: [7u8; 32],
+        }
+    }
+
+    fn attest(sealed: &SealedEpoch, height: u32, signers: &[u8]) -> EpochAttestation {
+        EpochAttestation {
+            epoch: sealed.epoch,
+            root: sealed.root,
+            height,
+            signatures: sign(signers, &EpochAttestation::message(sealed, height)),
+        }
+    }
+
+    #[test]
+    fn test_federation_threshold() {
+        let secp = Secp256k1::new();
+        let keys = federation(&[1, 2, 3], 2);
+        let message = Message::from_slice(&[9u8; 32]).unwrap();
+        assert!(keys.verify(&secp, &message, &sign(&[1, 3], &message)));
+        assert!(!keys.verify(&secp, &message, &sign(&[1, 1], &message)));
+        assert!(!keys.verify(&secp, &message, &sign(&[1, 4], &message)));
+        assert_eq!(
+            OperatorKeySet::new(keys.keys.clone(), 4),
+            Err(OperatorKeyError::InvalidThreshold(3, 4))
+        );
+        let repeated = vec![keys.keys[0], keys.keys[0]];
+        assert_eq!(
+            OperatorKeySet::new(repeated, 2),
+            Err(OperatorKeyError::InvalidThreshold(1, 2))
+        );
+    }
+
+    #[test]
+    fn test_rotation_rejects_invalid_threshold() {
+        let secp = Secp256k1::new();
+        let mut schedule = OperatorKeySchedule::new(federation(&[1], 1));
+        for threshold in [0, 2] {
+            // Built directly, bypassing `OperatorKeySet::new`, as a deserialized rotation is.
+            let new_keys = OperatorKeySet {
+                keys: vec![keypair(4).x_only_public_key().0],
+                threshold,
+            };
+            let rotation = KeyRotation {
+                signatures: sign(&[1], &KeyRotation::message(&new_keys, 5_000)),
+                new_keys,
+                activation_height: 5_000,
+            };
+            assert_eq!(
+                schedule.announce(&secp, rotation, 0),
+                Err(OperatorKeyError::InvalidThreshold(1, threshold))
+            );
+        }
+        assert!(schedule.pending().is_none());
+    }
+
+    #[test]
+    fn test_rotation_keeps_old_attestations_valid() {
+        let secp = Secp256k1::new();
+        let mut schedule =
+            OperatorKeySchedule::new(federation(&[1, 2, 3], 2)).with_grace_period(100);
+        let old_epoch = sealed(0);
+        let old_attestation = attest(&old_epoch, 50, &[1, 2]);
+
+        let new_keys = federation(&[4], 1);
+        let rotation = |activation_height, signers: &[u8]| KeyRotation {
+            new_keys: new_keys.clone(),
+            activation_height,
+            signatures: sign(signers, &KeyRotation::message(&new_keys, activation_height)),
+        };
+        assert_eq!(
+            schedule.announce(&secp, rotation(150, &[1, 2]), 60),
+            Err(OperatorKeyError::GracePeriod(150, 160))
+        );
+        assert_eq!(
+            schedule.announce(&secp, rotation(200, &[4]), 60),
+            Err(OperatorKeyError::Unauthorized)
+        );
+        schedule
+            .announce(&secp, rotation(200, &[2, 3]), 60)
+            .unwrap();
+        assert_eq!(
+            schedule.announce(&secp, rotation(300, &[2, 3]), 61),
+            Err(OperatorKeyError::RotationPending(200))
+        );
+
+        // The old keys sign until the activation height, then only the new one does.
+        let epoch = sealed(1);
+        schedule
+            .verify_attestation(&secp, &epoch, &attest(&epoch, 199, &[1, 3]))
+            .unwrap();
+        assert!(schedule
+            .verify_attestation(&secp, &epoch, &attest(&epoch, 200, &[1, 3]))
+            .is_err());
+        schedule
+            .verify_attestation(&secp, &epoch, &attest(&epoch, 200, &[4]))
+            .unwrap();
+
+        assert!(!schedule.activate(199));
+        assert!(schedule.activate(200));
+        assert!(schedule.pending().is_none());
+        assert_eq!(schedule.keys_at(250), &new_keys);
+        schedule
+            .verify_attestation(&secp, &old_epoch, &old_attestation)
+            .unwrap();
+        assert_eq!(
+            schedule.verify_attestation(&secp, &epoch, &old_attestation),
+            Err(OperatorKeyError::AttestationMismatch(1))
+        );
+    }
+}
@@ -0,0 +1,206 @@
+// src/zkp/payment.rs
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::zkp::helpers::Bytes32;
+use crate::zkp::wallet_contract::{WalletContract, WalletContractError};
+
+/// Where a sent payment stands.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PaymentStatus {
+    /// Applied and proven locally; the wallet root that includes it is not yet recorded
+    /// by the global contract.
+    Pending,
+    /// The global contract records a wallet root that includes the payment.
+    Recorded,
+    /// The deadline passed before the payment was recorded.
+    Expired,
+}
+
+/// What a caller asks to pay, before a channel is picked.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PaymentRequest {
+    pub counterparty: Bytes32,
+    pub amount: u64,
+    /// Paid to intermediaries on top of `amount`, from the same channel.
+    pub routing_fee: u64,
+    pub memo: Option<String>,
+    /// Unix time in seconds after which the payment is no longer wanted.
+    pub deadline: Option<u64>,
+    /// Channels to pay over in preference to the smallest sufficient one.
+    pub channel_hints: Vec<Bytes32>,
+    /// Payment hash of the invoice being paid, if any.
+    pub payment_hash: Option<Bytes32>,
+}
+
+/// A payment the wallet has sent and tracks until it is recorded.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Payment {
+    pub id: Bytes32,
+    pub channel_id: Bytes32,
+    pub counterparty: Bytes32,
+    pub amount: u64,
+    pub routing_fee: u64,
+    /// Nonce of the channel state the payment produced.
+    pub nonce: u64,
+    pub memo: Option<String>,
+    pub deadline: Option<u64>,
+    /// Endpoint the update is delivered to, from the counterparty registry.
+    pub endpoint: Option<String>,
+    pub payment_hash: Option<Bytes32>,
+    pub created_at: u64,
+    pub status: PaymentStatus,
+}
+
+impl Payment {
+    /// Derives the id of the payment that moves `channel_id` to `nonce`.
+    pub fn id_for(wallet_id: &Bytes32, channel_id: &Bytes32, nonce: u64) -> Bytes32 {
+        Sha256::new()
+            .chain_update(b"overpass/payment")
+            .chain_update(wallet_id)
+            .chain_update(channel_id)
+            .chain_update(nonce.to_le_bytes())
+            .finalize()
+            .into()
+    }
+
+    /// Gets the status at `now`, counting a pending payment past its deadline as expired.
+    pub fn status_at(&self, now: u64) -> PaymentStatus {
+        match self.status {
+            PaymentStatus::Pending if self.deadline.is_some_and(|deadline| now > deadline) => {
+                PaymentStatus::Expired
+            }
+            status => status,
+        }
+    }
+}
+
+/// Builds a payment from a wallet, e.g.
+/// `wallet.pay(counterparty, 500).with_memo("rent").send()`.
+pub struct PaymentBuilder<'a> {
+    wallet: &'a mut WalletContract,
+    request: PaymentRequest,
+}
+
+impl<'a> PaymentBuilder<'a> {
+    pub(crate) fn new(wallet: &'a mut WalletContract, counterparty: Bytes32, amount: u64) -> Self {
+        Self {
+            wallet,
+            request: PaymentRequest {
+                counterparty,
+                amount,
+                routing_fee: 0,
+                memo: None,
+                deadline: None,
+                channel_hints: Vec::new(),
+                payment_hash: None,
+            },
+        }
+    }
+
+    /// Attaches a memo, stored as the new channel state's metadata.
+    pub fn with_memo(mut self, memo: impl Into<String>) -> Self {
+        self.request.memo = Some(memo.into());
+        self
+    }
+
+    /// Sets the Unix time in seconds after which the payment is no longer wanted.
+    pub fn with_deadline(mut self, deadline: u64) -> Self {
+        self.request.deadline = Some(deadline);
+        self
+    }
+
+    /// Adds a routing fee, debited from the channel with the amount and recorded in the
+    /// wallet's fee ledger.
+    pub fn with_routing_fee(mut self, fee: u64) -> Self {
+        self.request.routing_fee = fee;
+        self
+    }
+
+    /// Prefers the given channels, e.g. an invoice's hints, when they can carry the
+    /// payment.
+    pub fn with_channel_hints(mut self, hints: Vec<Bytes32>) -> Self {
+        self.request.channel_hints = hints;
+        self
+    }
+
+    /// Ties the payment to an invoice's payment hash.
+    pub fn with_payment_hash(mut self, payment_hash: Bytes32) -> Self {
+        self.request.payment_hash = Some(payment_hash);
+        self
+    }
+
+    pub fn request(&self) -> &PaymentRequest {
+        &self.request
+    }
+
+    /// Picks a channel, applies and proves the transition, stores it, and returns the
+    /// tracked payment.
+    pub fn send(self) -> Result<Payment, WalletContractError> {
+        self.wallet.send_payment(self.request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+
+    const SHOP: Bytes32 = [7u8; 32];
+
+    fn wallet() -> Result<WalletContract, WalletContractError> {
+        let mut wallet = test_util::unkeyed_wallet([1u8; 32]);
+        wallet.register_channel([1u8; 32], 500, SHOP, Vec::new())?;
+        wallet.register_channel([2u8; 32], 80, SHOP, Vec::new())?;
+        wallet.register_channel([3u8; 32], 40, [8u8; 32], Vec::new())?;
+        Ok(wallet)
+    }
+
+    #[test]
+    fn test_payment_picks_channel_and_is_tracked() -> Result<(), WalletContractError> {
+        let mut wallet = wallet()?;
+        let payment = wallet
+            .pay(SHOP, 60)
+            .with_memo("coffee")
+            .with_deadline(u64::MAX)
+            .send()?;
+        assert_eq!(payment.channel_id, [2u8; 32]);
+        assert_eq!(payment.nonce, 1);
+        assert_eq!(payment.status, PaymentStatus::Pending);
+        let channel = wallet.get_channel(&[2u8; 32]).unwrap();
+        assert_eq!(channel.balances, vec![20]);
+        assert_eq!(channel.metadata, b"coffee".to_vec());
+        assert_eq!(wallet.storage.history(&[2u8; 32]).len(), 1);
+
+        let larger = wallet.pay(SHOP, 60).send()?;
+        assert_eq!(larger.channel_id, [1u8; 32]);
+        assert!(matches!(
+            wallet.pay(SHOP, 441).send(),
+            Err(WalletContractError::NoPaymentChannel { amount: 441, .. })
+        ));
+        assert!(matches!(
+            wallet.pay(SHOP, 1).with_deadline(1).send(),
+            Err(WalletContractError::DeadlinePassed(1))
+        ));
+
+        wallet.repair_against_global(1)?;
+        let recorded = wallet.payment(&payment.id).unwrap();
+        assert_eq!(recorded.status, PaymentStatus::Recorded);
+        assert_eq!(recorded.status_at(u64::MAX), PaymentStatus::Recorded);
+        Ok(())
+    }
+
+    #[test]
+    fn test_pending_payments_expire_after_their_deadline() -> Result<(), WalletContractError> {
+        let mut wallet = wallet()?;
+        let payment = wallet
+            .pay([8u8; 32], 40)
+            .with_deadline(u64::MAX - 1)
+            .send()?;
+        assert_eq!(wallet.payment(&payment.id), Some(payment.clone()));
+        assert_eq!(payment.status_at(u64::MAX), PaymentStatus::Expired);
+        assert_eq!(payment.id, Payment::id_for(&[1u8; 32], &[3u8; 32], 1));
+        Ok(())
+    }
+}
@@ -0,0 +1,431 @@
+// src/zkp/payment_schedule.rs
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+use crate::services::overpass_db::OverpassDB;
+use crate::zkp::helpers::Bytes32;
+
+const SCHEDULE_PREFIX: &[u8] = b"payment_schedules:";
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ScheduleError {
+    #[error("Schedule {0} not found")]
+    NotFound(u64),
+    #[error("Recurring schedules need a positive interval")]
+    InvalidInterval,
+    #[error("Storage error: {0}")]
+    StorageError(String),
+    #[error("Corrupt schedule state: {0}")]
+    CorruptState(String),
+}
+
+/// What a schedule does once an occurrence has failed more than `max_retries` times.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OnFailure {
+    /// Gives up on the occurrence and waits for the next one.
+    Skip,
+    /// Stops the schedule until it is resumed.
+    Suspend,
+}
+
+/// How a failed payment is retried.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    /// Delay before the first retry; later retries double it up to `max_backoff_secs`.
+    pub initial_backoff_secs: u64,
+    pub max_backoff_secs: u64,
+    pub on_failure: OnFailure,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff_secs: 60,
+            max_backoff_secs: 3_600,
+            on_failure: OnFailure::Skip,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Gets the backoff after `failures` consecutive failed attempts.
+    pub fn backoff_secs(&self, failures: u32) -> u64 {
+        let factor = 1u64
+            .checked_shl(failures.saturating_sub(1))
+            .unwrap_or(u64::MAX);
+        self.initial_backoff_secs
+            .saturating_mul(factor)
+            .min(self.max_backoff_secs)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ScheduleStatus {
+    Active,
+    Suspended,
+    Completed,
+    Cancelled,
+}
+
+/// A future payment, or a recurring one such as a subscription.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PaymentSchedule {
+    pub counterparty: Bytes32,
+    pub amount: u64,
+    pub memo: Option<String>,
+    pub routing_fee: u64,
+    /// Unix time in seconds of the current occurrence.
+    pub due_at: u64,
+    /// Seconds between occurrences; `None` pays once.
+    pub interval: Option<u64>,
+    /// Occurrences left to pay, unbounded when `None`.
+    pub remaining: Option<u32>,
+    pub retry: RetryPolicy,
+}
+
+impl PaymentSchedule {
+    /// Pays `amount` to `counterparty` once at `due_at`.
+    pub fn once(counterparty: Bytes32, amount: u64, due_at: u64) -> Self {
+        Self {
+            counterparty,
+            amount,
+            memo: None,
+            routing_fee: 0,
+            due_at,
+            interval: None,
+            remaining: Some(1),
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    /// Pays `amount` to `counterparty` every `interval` seconds from `first`, until
+    /// cancelled or limited with [`Self::times`].
+    pub fn every(counterparty: Bytes32, amount: u64, first: u64, interval: u64) -> Self {
+        Self {
+            interval: Some(interval),
+            remaining: None,
+            ..Self::once(counterparty, amount, first)
+        }
+    }
+
+    pub fn times(mut self, occurrences: u32) -> Self {
+        self.remaining = Some(occurrences);
+        self
+    }
+
+    pub fn with_memo(mut self, memo: impl Into<String>) -> Self {
+        self.memo = Some(memo.into());
+        self
+    }
+
+    pub fn with_routing_fee(mut self, fee: u64) -> Self {
+        self.routing_fee = fee;
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+}
+
+/// A schedule the wallet holds, with where it stands.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScheduledPayment {
+    pub id: u64,
+    pub schedule: PaymentSchedule,
+    pub status: ScheduleStatus,
+    /// Unix time in seconds of the next attempt, later than `due_at` while retrying.
+    pub next_attempt: u64,
+    /// Failed attempts at the current occurrence.
+    pub failures: u32,
+    pub last_error: Option<String>,
+    /// Ids of the payments the schedule has sent, oldest first.
+    pub payments: Vec<Bytes32>,
+}
+
+impl ScheduledPayment {
+    pub fn is_due(&self, now: u64) -> bool {
+        self.status == ScheduleStatus::Active && self.next_attempt <= now
+    }
+
+    #[cfg(feature = "prover")]
+    /// Records a sent payment and moves to the next occurrence.
+    pub(crate) fn succeeded(&mut self, payment_id: Bytes32, now: u64) {
+        self.payments.push(payment_id);
+        self.advance(now);
+    }
+
+    #[cfg(feature = "prover")]
+    /// Records a failed attempt and schedules the retry, or applies the failure policy
+    /// once the retries are spent.
+    pub(crate) fn failed(&mut self, error: String, now: u64) {
+        self.failures += 1;
+        self.last_error = Some(error);
+        if self.failures <= self.schedule.retry.max_retries {
+            let backoff = self.schedule.retry.backoff_secs(self.failures);
+            self.next_attempt = now.saturating_add(backoff);
+            return;
+        }
+        match self.schedule.retry.on_failure {
+            OnFailure::Skip => self.advance(now),
+            OnFailure::Suspend => self.status = ScheduleStatus::Suspended,
+        }
+    }
+
+    #[cfg(feature = "prover")]
+    /// Moves past the current occurrence. Occurrences missed while the wallet was not
+    /// running are skipped rather than paid in a burst.
+    fn advance(&mut self, now: u64) {
+        self.failures = 0;
+        if let Some(remaining) = &mut self.schedule.remaining {
+            *remaining = remaining.saturating_sub(1);
+        }
+        let interval = match self.schedule.interval {
+            Some(interval) if self.schedule.remaining != Some(0) => interval,
+            _ => {
+                self.status = ScheduleStatus::Completed;
+                return;
+            }
+        };
+        let missed = now.saturating_sub(self.schedule.due_at) / interval;
+        self.schedule.due_at = self
+            .schedule
+            .due_at
+            .saturating_add(interval.saturating_mul(missed + 1));
+        self.next_attempt = self.schedule.due_at;
+    }
+}
+
+/// Outcome of one scheduled payment attempt.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScheduleRun {
+    pub schedule_id: u64,
+    /// Id of the payment sent, or why it could not be.
+    pub result: Result<Bytes32, String>,
+}
+
+/// A wallet's scheduled payments, by id.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PaymentScheduler {
+    schedules: BTreeMap<u64, ScheduledPayment>,
+    next_id: u64,
+}
+
+impl PaymentScheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, schedule: PaymentSchedule) -> Result<u64, ScheduleError> {
+        if schedule.interval == Some(0) {
+            return Err(ScheduleError::InvalidInterval);
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        self.schedules.insert(
+            id,
+            ScheduledPayment {
+                id,
+                next_attempt: schedule.due_at,
+                schedule,
+                status: ScheduleStatus::Active,
+                failures: 0,
+                last_error: None,
+                payments: Vec::new(),
+            },
+        );
+        Ok(id)
+    }
+
+    pub fn get(&self, id: u64) -> Option<&ScheduledPayment> {
+        self.schedules.get(&id)
+    }
+
+    #[cfg(feature = "prover")]
+    pub(crate) fn get_mut(&mut self, id: u64) -> Option<&mut ScheduledPayment> {
+        self.schedules.get_mut(&id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ScheduledPayment> {
+        self.schedules.values()
+    }
+
+    /// Gets the ids of the schedules due at `now`, in id order.
+    pub fn due(&self, now: u64) -> Vec<u64> {
+        self.schedules
+            .values()
+            .filter(|scheduled| scheduled.is_due(now))
+            .map(|scheduled| scheduled.id)
+            .collect()
+    }
+
+    pub fn cancel(&mut self, id: u64) -> Result<(), ScheduleError> {
+        self.set_status(id, ScheduleStatus::Cancelled)
+    }
+
+    /// Reactivates a suspended schedule, retrying its current occurrence at `now`.
+    pub fn resume(&mut self, id: u64, now: u64) -> Result<(), ScheduleError> {
+        let scheduled = self
+            .schedules
+            .get_mut(&id)
+            .ok_or(ScheduleError::NotFound(id))?;
+        if scheduled.status == ScheduleStatus::Suspended {
+            scheduled.status = ScheduleStatus::Active;
+            scheduled.failures = 0;
+            scheduled.next_attempt = now;
+        }
+        Ok(())
+    }
+
+    fn set_status(&mut self, id: u64, status: ScheduleStatus) -> Result<(), ScheduleError> {
+        self.schedules
+            .get_mut(&id)
+            .ok_or(ScheduleError::NotFound(id))?
+            .status = status;
+        Ok(())
+    }
+
+    /// Writes the schedules to `db` under `wallet_id`.
+    pub fn save(&self, db: &OverpassDB, wallet_id: &Bytes32) -> Result<(), ScheduleError> {
+        let bytes =
+            bincode::serialize(self).map_err(|e| ScheduleError::StorageError(e.to_string()))?;
+        db.put(&schedule_key(wallet_id), &bytes)
+            .and_then(|_| db.flush())
+            .map_err(|e| ScheduleError::StorageError(e.to_string()))
+    }
+
+    /// Loads the schedules saved for `wallet_id`, or none if nothing was saved.
+    pub fn restore(db: &OverpassDB, wallet_id: &Bytes32) -> Result<Self, ScheduleError> {
+        match db
+            .get(&schedule_key(wallet_id))
+            .map_err(|e| ScheduleError::StorageError(e.to_string()))?
+        {
+            Some(bytes) => {
+                bincode::deserialize(&bytes).map_err(|e| ScheduleError::CorruptState(e.to_string()))
+            }
+            None => Ok(Self::default()),
+        }
+    }
+}
+
+fn schedule_key(wallet_id: &Bytes32) -> Vec<u8> {
+    let mut key = SCHEDULE_PREFIX.to_vec();
+    key.extend_from_slice(hex::encode(wallet_id).as_bytes());
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+    use crate::zkp::wallet_contract::{WalletContract, WalletContractError};
+
+    const SHOP: Bytes32 = [7u8; 32];
+    const DAY: u64 = 86_400;
+
+    fn wallet(balance: u64) -> Result<WalletContract, WalletContractError> {
+        let mut wallet = test_util::unkeyed_wallet([1u8; 32]);
+        wallet.register_channel([1u8; 32], balance, SHOP, Vec::new())?;
+        Ok(wallet)
+    }
+
+    #[test]
+    fn test_subscription_pays_each_period() -> anyhow::Result<()> {
+        let mut wallet = wallet(100)?;
+        let id = wallet.schedule_payment(
+            PaymentSchedule::every(SHOP, 30, DAY, 30 * DAY)
+                .times(3)
+                .with_memo("plan"),
+        )?;
+        assert!(wallet.run_due_payments(DAY - 1).is_empty());
+
+        let runs = wallet.run_due_payments(DAY);
+        assert_eq!(runs.len(), 1);
+        let first = runs[0].result.clone().unwrap();
+        assert_eq!(wallet.payment(&first).unwrap().amount, 30);
+        assert_eq!(wallet.get_channel(&[1u8; 32]).unwrap().balances, vec![70]);
+        let scheduled = wallet.schedules().get(id).unwrap();
+        assert_eq!(scheduled.next_attempt, 31 * DAY);
+        assert!(wallet.run_due_payments(2 * DAY).is_empty());
+
+        // Offline for two periods: one payment, then back on the calendar.
+        wallet.run_due_payments(91 * DAY + 5);
+        let scheduled = wallet.schedules().get(id).unwrap();
+        assert_eq!(scheduled.payments.len(), 2);
+        assert_eq!(scheduled.next_attempt, 121 * DAY);
+        wallet.run_due_payments(121 * DAY);
+        let scheduled = wallet.schedules().get(id).unwrap();
+        assert_eq!(scheduled.status, ScheduleStatus::Completed);
+        assert_eq!(wallet.get_channel(&[1u8; 32]).unwrap().balances, vec![10]);
+
+        let path = std::env::temp_dir().join(format!("payment_schedules_{}", std::process::id()));
+        let db = OverpassDB::new(path.to_str().unwrap())?;
+        wallet.schedules().save(&db, &wallet.wallet_id)?;
+        assert_eq!(
+            &PaymentScheduler::restore(&db, &wallet.wallet_id)?,
+            wallet.schedules()
+        );
+        drop(db);
+        let _ = std::fs::remove_dir_all(path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_failed_payments_retry_then_apply_policy() -> Result<(), WalletContractError> {
+        let mut wallet = wallet(10)?;
+        let retry = RetryPolicy {
+            max_retries: 2,
+            initial_backoff_secs: 60,
+            max_backoff_secs: 90,
+            on_failure: OnFailure::Suspend,
+        };
+        let id = wallet.schedule_payment(PaymentSchedule::once(SHOP, 50, 100).with_retry(retry))?;
+
+        let runs = wallet.run_due_payments(100);
+        assert!(runs[0].result.is_err());
+        assert_eq!(wallet.schedules().get(id).unwrap().next_attempt, 160);
+        wallet.run_due_payments(160);
+        assert_eq!(wallet.schedules().get(id).unwrap().next_attempt, 250);
+        wallet.run_due_payments(250);
+        let scheduled = wallet.schedules().get(id).unwrap();
+        assert_eq!(scheduled.status, ScheduleStatus::Suspended);
+        assert!(scheduled.last_error.is_some());
+
+        wallet.register_channel([2u8; 32], 100, SHOP, Vec::new())?;
+        wallet.schedules_mut().resume(id, 300).unwrap();
+        let runs = wallet.run_due_payments(300);
+        assert!(runs[0].result.is_ok());
+        assert_eq!(
+            wallet.schedules().get(id).unwrap().status,
+            ScheduleStatus::Completed
+        );
+
+        let skipped = wallet.schedule_payment(
+            PaymentSchedule::every(SHOP, 500, 400, 100).with_retry(RetryPolicy {
+                max_retries: 0,
+                on_failure: OnFailure::Skip,
+                ..retry
+            }),
+        )?;
+        wallet.run_due_payments(400);
+        let scheduled = wallet.schedules().get(skipped).unwrap();
+        assert_eq!(
+            (scheduled.status, scheduled.next_attempt),
+            (ScheduleStatus::Active, 500)
+        );
+        wallet.schedules_mut().cancel(skipped).unwrap();
+        assert!(wallet.run_due_payments(500).is_empty());
+        assert!(matches!(
+            wallet.schedule_payment(PaymentSchedule::every(SHOP, 1, 0, 0)),
+            Err(WalletContractError::ScheduleError(
+                ScheduleError::InvalidInterval
+            ))
+        ));
+        Ok(())
+    }
+}
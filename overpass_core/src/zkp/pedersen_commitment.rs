@@ -0,0 +1,446 @@
+// src/zkp/pedersen_commitment.rs
+
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::{IsIdentity, MultiscalarMul, VartimeMultiscalarMul};
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+use sha2::{Digest, Sha256};
+use std::fmt::Debug;
+use std::iter::Sum;
+use std::ops::{Add, Mul, Neg, Sub};
+
+use crate::zkp::blinding::BlindingFactor;
+use crate::zkp::channel::ChannelState;
+use crate::zkp::helpers::{generate_random_blinding, hash_point, Bytes32};
+use zeroize::Zeroize;
+use crate::zkp::pedersen_parameters::{PedersenError, PedersenParameters, VectorPedersenParameters};
+
+/// A Pedersen commitment kept as a group element rather than a hash.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct PedersenCommitment(RistrettoPoint);
+
+impl PedersenCommitment {
+    /// Commits to `value` as `g * value + h * blinding`.
+    pub fn commit(params: &PedersenParameters, value: u64, blinding: &Scalar) -> Self {
+        Self(params.g * Scalar::from(value) + params.h * blinding)
+    }
+
+    /// Commits to every element of `values` as `sum(g_i * v_i) + h * blinding`.
+    pub fn commit_vector(
+        params: &VectorPedersenParameters,
+        values: &[Scalar],
+        blinding: &Scalar,
+    ) -> Result<Self, PedersenError> {
+        if values.len() != params.len() {
+            return Err(PedersenError::Length {
+                unit: "values",
+                expected: params.len(),
+                found: values.len(),
+            });
+        }
+        let scalars = values.iter().chain(std::iter::once(blinding));
+        let points = params.g.iter().chain(std::iter::once(&params.h));
+        Ok(Self(RistrettoPoint::multiscalar_mul(scalars, points)))
+    }
+
+    /// Checks that `values` and `blinding` open this vector commitment.
+    pub fn verify_vector_opening(
+        &self,
+        params: &VectorPedersenParameters,
+        values: &[Scalar],
+        blinding: &Scalar,
+    ) -> bool {
+        Self::commit_vector(params, values, blinding).is_ok_and(|expected| expected == *self)
+    }
+
+    /// Checks that `value` and `blinding` open this commitment.
+    pub fn verify_opening(
+        &self,
+        params: &PedersenParameters,
+        value: u64,
+        blinding: &Scalar,
+    ) -> bool {
+        Self::commit(params, value, blinding) == *self
+    }
+
+    pub fn from_point(point: RistrettoPoint) -> Self {
+        Self(point)
+    }
+
+    pub fn point(&self) -> &RistrettoPoint {
+        &self.0
+    }
+
+    /// Gets the compressed encoding of the commitment.
+    pub fn to_bytes(&self) -> Bytes32 {
+        self.0.compress().to_bytes()
+    }
+
+    /// Decodes a compressed commitment.
+    pub fn from_bytes(bytes: &Bytes32) -> Result<Self, PedersenError> {
+        CompressedRistretto(*bytes)
+            .decompress()
+            .map(Self)
+            .ok_or(PedersenError::InvalidPoint("commitment"))
+    }
+
+    /// Gets the hash of the commitment, matching `helpers::pedersen_commit`.
+    pub fn digest(&self) -> Bytes32 {
+        hash_point(self.0)
+    }
+}
+
+impl Debug for PedersenCommitment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("PedersenCommitment")
+            .field(&hex::encode(self.to_bytes()))
+            .finish()
+    }
+}
+
+impl Serialize for PedersenCommitment {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.to_bytes().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for PedersenCommitment {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let bytes = Bytes32::deserialize(deserializer)?;
+        Self::from_bytes(&bytes).map_err(D::Error::custom)
+    }
+}
+
+impl Add for PedersenCommitment {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self(self.0 + other.0)
+    }
+}
+
+impl Sub for PedersenCommitment {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self(self.0 - other.0)
+    }
+}
+
+impl Neg for PedersenCommitment {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self(-self.0)
+    }
+}
+
+impl Mul<Scalar> for PedersenCommitment {
+    type Output = Self;
+
+    fn mul(self, factor: Scalar) -> Self {
+        Self(self.0 * factor)
+    }
+}
+
+impl Sum for PedersenCommitment {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        Self(iter.map(|commitment| commitment.0).sum())
+    }
+}
+
+/// Gets a commitment to the sum of the committed values, blinded by the sum of blindings.
+pub fn commit_add(a: &PedersenCommitment, b: &PedersenCommitment) -> PedersenCommitment {
+    *a + *b
+}
+
+/// Gets a commitment to the difference of the committed values and blindings.
+pub fn commit_sub(a: &PedersenCommitment, b: &PedersenCommitment) -> PedersenCommitment {
+    *a - *b
+}
+
+/// Gets a commitment to `factor` times the committed value and blinding.
+pub fn commit_scale(commitment: &PedersenCommitment, factor: u64) -> PedersenCommitment {
+    *commitment * Scalar::from(factor)
+}
+
+/// Gets a fresh commitment to the same value, blinded by the original blinding plus `delta`.
+///
+/// The result cannot be linked to the original without knowing `delta`, so the same value
+/// can be published in several contexts under unrelated commitments.
+pub fn rerandomize(
+    params: &PedersenParameters,
+    commitment: &PedersenCommitment,
+    delta: &Scalar,
+) -> PedersenCommitment {
+    PedersenCommitment(commitment.0 + params.h * delta)
+}
+
+/// Rerandomizes `commitment` with a random delta, returning the new commitment and delta.
+pub fn rerandomize_random(
+    params: &PedersenParameters,
+    commitment: &PedersenCommitment,
+) -> (PedersenCommitment, BlindingFactor) {
+    let delta = BlindingFactor::random();
+    (rerandomize(params, commitment, delta.scalar()), delta)
+}
+
+/// Checks without opening any commitment that `before` and `after` commit to the same
+/// total value.
+///
+/// `blinding_delta` is the sum of the blindings of `before` minus that of `after`; the
+/// totals match exactly when the difference of the sums is `h * blinding_delta`.
+pub fn verify_conservation(
+    params: &PedersenParameters,
+    before: &[PedersenCommitment],
+    after: &[PedersenCommitment],
+    blinding_delta: &Scalar,
+) -> bool {
+    let difference = before.iter().copied().sum::<PedersenCommitment>()
+        - after.iter().copied().sum::<PedersenCommitment>();
+    difference.0 == params.h * blinding_delta
+}
+
+/// A claimed opening of a single-value commitment.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Opening {
+    pub value: u64,
+    pub blinding: BlindingFactor,
+}
+
+/// Checks many openings at once with a random linear combination.
+///
+/// Each check `C_i == g * v_i + h * r_i` is weighted by a random `z_i` and all of them are
+/// folded into one multi-scalar multiplication. An invalid opening makes the sum non-zero
+/// except with negligible probability; use `invalid_openings` to find which one.
+pub fn batch_verify_openings(
+    params: &PedersenParameters,
+    commitments: &[PedersenCommitment],
+    openings: &[Opening],
+) -> bool {
+    if commitments.len() != openings.len() {
+        return false;
+    }
+    let weights: Vec<Scalar> = (0..commitments.len())
+        .map(|_| Scalar::from_bytes_mod_order(generate_random_blinding()))
+        .collect();
+    let value_sum: Scalar = weights
+        .iter()
+        .zip(openings)
+        .map(|(weight, opening)| weight * Scalar::from(opening.value))
+        .sum();
+    let mut blinding_sum: Scalar = weights
+        .iter()
+        .zip(openings)
+        .map(|(weight, opening)| weight * opening.blinding.scalar())
+        .sum();
+
+    let scalars = weights.iter().copied().chain([-value_sum, -blinding_sum]);
+    let points = commitments
+        .iter()
+        .map(|commitment| commitment.0)
+        .chain([params.g, params.h]);
+    let valid = RistrettoPoint::vartime_multiscalar_mul(scalars, points).is_identity();
+    blinding_sum.zeroize();
+    valid
+}
+
+/// Gets the indices of the openings that do not open their commitments.
+pub fn invalid_openings(
+    params: &PedersenParameters,
+    commitments: &[PedersenCommitment],
+    openings: &[Opening],
+) -> Vec<usize> {
+    commitments
+        .iter()
+        .zip(openings)
+        .enumerate()
+        .filter(|(_, (commitment, opening))| {
+            !commitment.verify_opening(params, opening.value, opening.blinding.scalar())
+        })
+        .map(|(index, _)| index)
+        .collect()
+}
+
+/// Gets the number of values in the vector commitment of a channel with `parties` balances.
+pub fn channel_state_vector_len(parties: usize) -> usize {
+    parties + 2
+}
+
+/// Flattens a channel state into the values committed by `commit_channel_state`: every
+/// balance, then the nonce, then the metadata hash reduced into a scalar.
+pub fn channel_state_vector(state: &ChannelState) -> Vec<Scalar> {
+    let metadata_hash: [u8; 32] = Sha256::digest(&state.metadata).into();
+    state
+        .balances
+        .iter()
+        .map(|balance| Scalar::from(*balance))
+        .chain([
+            Scalar::from(state.nonce),
+            Scalar::from_bytes_mod_order(metadata_hash),
+        ])
+        .collect()
+}
+
+/// Commits to all fields of a channel state under a single point.
+pub fn commit_channel_state(
+    params: &VectorPedersenParameters,
+    state: &ChannelState,
+    blinding: &Scalar,
+) -> Result<PedersenCommitment, PedersenError> {
+    PedersenCommitment::commit_vector(params, &channel_state_vector(state), blinding)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zkp::helpers::pedersen_commit;
+    use anyhow::Result;
+    use crate::zkp::pedersen_parameters::DEFAULT_LABEL;
+    use std::collections::BTreeMap;
+
+    fn state(balances: Vec<u64>, nonce: u64) -> ChannelState {
+        ChannelState {
+            balances,
+            nonce,
+            metadata: b"memo".to_vec(),
+            merkle_root: [0u8; 32],
+            proof: None,
+            assets: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_scalar_commitment_matches_hash_helper() -> Result<()> {
+        let params = PedersenParameters::default();
+        let blinding = [9u8; 32];
+        let commitment =
+            PedersenCommitment::commit(&params, 42, &Scalar::from_bytes_mod_order(blinding));
+        assert_eq!(commitment.digest(), pedersen_commit(42, blinding, &params));
+        assert!(commitment.verify_opening(&params, 42, &Scalar::from_bytes_mod_order(blinding)));
+        assert!(!commitment.verify_opening(&params, 43, &Scalar::from_bytes_mod_order(blinding)));
+
+        let decoded: PedersenCommitment =
+            serde_json::from_str(&serde_json::to_string(&commitment)?)?;
+        assert_eq!(decoded, commitment);
+        Ok(())
+    }
+
+    #[test]
+    fn test_homomorphic_arithmetic() {
+        let params = PedersenParameters::default();
+        let commit = |value, blinding: u64| {
+            PedersenCommitment::commit(&params, value, &Scalar::from(blinding))
+        };
+
+        assert_eq!(commit_add(&commit(30, 5), &commit(12, 6)), commit(42, 11));
+        assert_eq!(commit_sub(&commit(42, 11), &commit(12, 6)), commit(30, 5));
+        assert_eq!(commit_scale(&commit(7, 3), 4), commit(28, 12));
+        assert_eq!(-commit(0, 0) + commit(1, 1), commit(1, 1));
+    }
+
+    #[test]
+    fn test_rerandomized_commitment_opens_to_same_value() {
+        let params = PedersenParameters::default();
+        let blinding = Scalar::from(11u64);
+        let commitment = PedersenCommitment::commit(&params, 500, &blinding);
+
+        let (fresh, delta) = rerandomize_random(&params, &commitment);
+        assert_ne!(fresh, commitment);
+        assert!(fresh.verify_opening(&params, 500, &(blinding + delta.scalar())));
+        assert_eq!(rerandomize(&params, &fresh, (-&delta).scalar()), commitment);
+    }
+
+    #[test]
+    fn test_batch_verification() {
+        let params = PedersenParameters::default();
+        let mut openings: Vec<Opening> = (0..200u64)
+            .map(|i| Opening {
+                value: i * 1_000,
+                blinding: BlindingFactor::from_scalar(Scalar::from(i + 17)),
+            })
+            .collect();
+        let commitments: Vec<PedersenCommitment> = openings
+            .iter()
+            .map(|opening| {
+                PedersenCommitment::commit(&params, opening.value, opening.blinding.scalar())
+            })
+            .collect();
+        assert!(batch_verify_openings(&params, &commitments, &openings));
+        assert!(invalid_openings(&params, &commitments, &openings).is_empty());
+
+        openings[123].value += 1;
+        assert!(!batch_verify_openings(&params, &commitments, &openings));
+        assert_eq!(
+            invalid_openings(&params, &commitments, &openings),
+            vec![123]
+        );
+        assert!(!batch_verify_openings(
+            &params,
+            &commitments[1..],
+            &openings
+        ));
+    }
+
+    #[test]
+    fn test_balance_conservation() {
+        let params = PedersenParameters::default();
+        let commit = |value, blinding: u64| {
+            PedersenCommitment::commit(&params, value, &Scalar::from(blinding))
+        };
+        let before = [commit(60, 10), commit(40, 20)];
+        let after = [commit(25, 3), commit(75, 4)];
+        let delta = Scalar::from(30u64) - Scalar::from(7u64);
+
+        assert!(verify_conservation(&params, &before, &after, &delta));
+        assert!(!verify_conservation(
+            &params,
+            &before,
+            &[commit(25, 3), commit(76, 4)],
+            &delta
+        ));
+        assert!(!verify_conservation(
+            &params,
+            &before,
+            &after,
+            &Scalar::from(22u64)
+        ));
+    }
+
+    #[test]
+    fn test_vector_commitment_binds_every_field() -> Result<()> {
+        let params =
+            VectorPedersenParameters::from_label(DEFAULT_LABEL, channel_state_vector_len(2));
+        let blinding = Scalar::from(7u64);
+        let commitment = commit_channel_state(&params, &state(vec![60, 40], 3), &blinding)?;
+
+        assert!(commitment.verify_vector_opening(
+            &params,
+            &channel_state_vector(&state(vec![60, 40], 3)),
+            &blinding
+        ));
+        assert_ne!(
+            commit_channel_state(&params, &state(vec![40, 60], 3), &blinding)?,
+            commitment
+        );
+        assert_ne!(
+            commit_channel_state(&params, &state(vec![60, 40], 4), &blinding)?,
+            commitment
+        );
+        assert!(commit_channel_state(&params, &state(vec![60, 40, 0], 3), &blinding).is_err());
+
+        // A one-element vector commitment is an ordinary commitment.
+        let single = VectorPedersenParameters::from_label(DEFAULT_LABEL, 1);
+        assert_eq!(
+            PedersenCommitment::commit_vector(&single, &[Scalar::from(5u64)], &blinding)?,
+            PedersenCommitment::commit(&PedersenParameters::default(), 5, &blinding)
+        );
+        Ok(())
+    }
+}
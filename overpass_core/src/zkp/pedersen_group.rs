@@ -0,0 +1,238 @@
+// src/zkp/pedersen_group.rs
+
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use curve25519_dalek::scalar::Scalar as RistrettoScalar;
+use curve25519_dalek::traits::Identity;
+use k256::elliptic_curve::group::GroupEncoding;
+use k256::elliptic_curve::ops::Reduce;
+use k256::elliptic_curve::sec1::FromEncodedPoint;
+use k256::{AffinePoint, EncodedPoint, FieldBytes, ProjectivePoint, U256};
+use sha2::{Digest, Sha256};
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::ops::{Add, Mul, Sub};
+
+use crate::zkp::pedersen_parameters::{hash_to_point, PedersenError, PedersenParameters};
+
+/// A prime-order group Pedersen commitments can be formed in.
+///
+/// Commitments in different groups never compare equal, so both parties must agree on the
+/// group along with the label. No curve over the Goldilocks field used by the plonky2
+/// circuits is available here; a backend for it plugs in through this trait.
+pub trait PedersenGroup: Clone + Debug {
+    type Point: Copy
+        + Eq
+        + Debug
+        + Add<Output = Self::Point>
+        + Sub<Output = Self::Point>
+        + Mul<Self::Scalar, Output = Self::Point>;
+    type Scalar: Copy + Eq + From<u64>;
+
+    /// Name of the group, bound into parameter fingerprints.
+    const NAME: &'static str;
+
+    /// Hashes a public label and base name to a point of unknown discrete log.
+    fn hash_to_point(label: &[u8], base: &[u8]) -> Self::Point;
+
+    /// Reduces 32 uniformly random bytes into a scalar.
+    fn scalar_from_bytes(bytes: &[u8; 32]) -> Self::Scalar;
+
+    fn identity() -> Self::Point;
+
+    /// Gets the canonical compressed encoding of a point.
+    fn encode(point: &Self::Point) -> Vec<u8>;
+
+    /// Decodes a compressed point, rejecting invalid encodings.
+    fn decode(bytes: &[u8]) -> Option<Self::Point>;
+}
+
+/// The ristretto255 group used by `PedersenParameters`.
+#[derive(Clone, Debug)]
+pub struct Ristretto255;
+
+impl PedersenGroup for Ristretto255 {
+    type Point = RistrettoPoint;
+    type Scalar = RistrettoScalar;
+
+    const NAME: &'static str = "ristretto255";
+
+    fn hash_to_point(label: &[u8], base: &[u8]) -> RistrettoPoint {
+        hash_to_point(label, base)
+    }
+
+    fn scalar_from_bytes(bytes: &[u8; 32]) -> RistrettoScalar {
+        RistrettoScalar::from_bytes_mod_order(*bytes)
+    }
+
+    fn identity() -> RistrettoPoint {
+        RistrettoPoint::identity()
+    }
+
+    fn encode(point: &RistrettoPoint) -> Vec<u8> {
+        point.compress().to_bytes().to_vec()
+    }
+
+    fn decode(bytes: &[u8]) -> Option<RistrettoPoint> {
+        CompressedRistretto::from_slice(bytes).ok()?.decompress()
+    }
+}
+
+/// The secp256k1 group, for proof backends that work natively over Bitcoin's curve.
+#[derive(Clone, Debug)]
+pub struct Secp256k1Group;
+
+impl PedersenGroup for Secp256k1Group {
+    type Point = ProjectivePoint;
+    type Scalar = k256::Scalar;
+
+    const NAME: &'static str = "secp256k1";
+
+    /// Maps to the curve by try-and-increment: the first counter whose hash is the
+    /// x-coordinate of a point with even y gives the base.
+    fn hash_to_point(label: &[u8], base: &[u8]) -> ProjectivePoint {
+        (0u32..)
+            .find_map(|counter| {
+                let digest = Sha256::new()
+                    .chain_update(b"overpass/pedersen/generator/secp256k1/v1")
+                    .chain_update((label.len() as u64).to_le_bytes())
+                    .chain_update(label)
+                    .chain_update(base)
+                    .chain_update(counter.to_le_bytes())
+                    .finalize();
+                let encoded =
+                    EncodedPoint::from_bytes([&[0x02], digest.as_slice()].concat()).ok()?;
+                Option::<AffinePoint>::from(AffinePoint::from_encoded_point(&encoded))
+            })
+            .map(ProjectivePoint::from)
+            .expect("a valid x-coordinate is found within a few attempts")
+    }
+
+    fn scalar_from_bytes(bytes: &[u8; 32]) -> k256::Scalar {
+        <k256::Scalar as Reduce<U256>>::reduce_bytes(FieldBytes::from_slice(bytes))
+    }
+
+    fn identity() -> ProjectivePoint {
+        ProjectivePoint::IDENTITY
+    }
+
+    fn encode(point: &ProjectivePoint) -> Vec<u8> {
+        point.to_bytes().to_vec()
+    }
+
+    fn decode(bytes: &[u8]) -> Option<ProjectivePoint> {
+        if bytes.len() != 33 {
+            return None;
+        }
+        let repr = <ProjectivePoint as GroupEncoding>::Repr::clone_from_slice(bytes);
+        ProjectivePoint::from_bytes(&repr).into()
+    }
+}
+
+/// Pedersen bases in an arbitrary group.
+#[derive(Clone, Debug)]
+pub struct GroupPedersenParameters<G: PedersenGroup> {
+    pub g: G::Point,
+    pub h: G::Point,
+    group: PhantomData<G>,
+}
+
+impl<G: PedersenGroup> GroupPedersenParameters<G> {
+    pub fn new(g: G::Point, h: G::Point) -> Self {
+        Self {
+            g,
+            h,
+            group: PhantomData,
+        }
+    }
+
+    /// Derives the bases from a public label by hashing to the group.
+    pub fn from_label(label: &[u8]) -> Self {
+        Self::new(G::hash_to_point(label, b"g"), G::hash_to_point(label, b"h"))
+    }
+
+    /// Checks that these are exactly the parameters derived from `label`.
+    pub fn verify_label(&self, label: &[u8]) -> Result<(), PedersenError> {
+        let expected = Self::from_label(label);
+        if self.g != expected.g || self.h != expected.h {
+            return Err(PedersenError::WrongLabel(G::NAME));
+        }
+        Ok(())
+    }
+
+    /// Commits to `value` as `g * value + h * blinding`.
+    pub fn commit(&self, value: u64, blinding: &G::Scalar) -> G::Point {
+        self.g * G::Scalar::from(value) + self.h * *blinding
+    }
+
+    /// Checks that `value` and `blinding` open `commitment`.
+    pub fn verify_opening(&self, commitment: &G::Point, value: u64, blinding: &G::Scalar) -> bool {
+        self.commit(value, blinding) == *commitment
+    }
+
+    /// Gets a short hash of the group and bases.
+    pub fn fingerprint(&self) -> [u8; 8] {
+        let digest = Sha256::new()
+            .chain_update(b"overpass/pedersen/fingerprint")
+            .chain_update(G::NAME)
+            .chain_update(G::encode(&self.g))
+            .chain_update(G::encode(&self.h))
+            .finalize();
+        let mut fingerprint = [0u8; 8];
+        fingerprint.copy_from_slice(&digest[..8]);
+        fingerprint
+    }
+}
+
+impl From<GroupPedersenParameters<Ristretto255>> for PedersenParameters {
+    fn from(params: GroupPedersenParameters<Ristretto255>) -> Self {
+        PedersenParameters::new(params.g, params.h)
+    }
+}
+
+impl From<PedersenParameters> for GroupPedersenParameters<Ristretto255> {
+    fn from(params: PedersenParameters) -> Self {
+        Self::new(params.g, params.h)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zkp::pedersen_parameters::DEFAULT_LABEL;
+
+    fn check_group<G: PedersenGroup>() {
+        let params = GroupPedersenParameters::<G>::from_label(DEFAULT_LABEL);
+        params.verify_label(DEFAULT_LABEL).unwrap();
+        assert!(params.verify_label(b"other").is_err());
+        assert_ne!(params.g, params.h);
+        assert_ne!(params.g, G::identity());
+
+        let blinding = G::scalar_from_bytes(&[3u8; 32]);
+        let commitment = params.commit(100, &blinding);
+        assert!(params.verify_opening(&commitment, 100, &blinding));
+        assert!(!params.verify_opening(&commitment, 101, &blinding));
+        assert_eq!(
+            params.commit(60, &blinding) + params.commit(40, &G::Scalar::from(0)),
+            commitment
+        );
+        assert_eq!(G::decode(&G::encode(&commitment)), Some(commitment));
+        assert_eq!(G::decode(&[0xff; 3]), None);
+    }
+
+    #[test]
+    fn test_backends() {
+        check_group::<Ristretto255>();
+        check_group::<Secp256k1Group>();
+
+        let ristretto = GroupPedersenParameters::<Ristretto255>::from_label(DEFAULT_LABEL);
+        let secp = GroupPedersenParameters::<Secp256k1Group>::from_label(DEFAULT_LABEL);
+        assert_ne!(ristretto.fingerprint(), secp.fingerprint());
+    }
+
+    #[test]
+    fn test_ristretto_backend_matches_default_parameters() {
+        let params: PedersenParameters =
+            GroupPedersenParameters::<Ristretto255>::from_label(DEFAULT_LABEL).into();
+        params.verify_label(DEFAULT_LABEL).unwrap();
+    }
+}
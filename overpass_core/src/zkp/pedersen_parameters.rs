@@ -1,9 +1,46 @@
 // src/zkp/pedersen_parameters.rs
 
-use anyhow::{anyhow, Result};
-use serde::{Serialize, Deserialize, Serializer, Deserializer};
+use serde::{de::Error as _, Serialize, Deserialize, Serializer, Deserializer};
 use curve25519_dalek::ristretto::{RistrettoPoint, CompressedRistretto};
+use sha2::{Digest, Sha256};
 use std::fmt::Debug;
+use thiserror::Error;
+
+// Generators are derived by `overpass_verify`, so the verifier and browser crates derive
+// the same ones; `DEFAULT_LABEL` labels `PedersenParameters::default`.
+pub use overpass_verify::commitment::DEFAULT_LABEL;
+pub(crate) use overpass_verify::commitment::hash_to_point;
+
+/// Length of the binary encoding: compressed `g` followed by compressed `h`.
+pub const ENCODED_LEN: usize = 64;
+
+/// Length of a parameter fingerprint.
+pub const FINGERPRINT_LEN: usize = 8;
+
+/// Errors from decoding and checking Pedersen parameters and the commitments made under
+/// them.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum PedersenError {
+    #[error("Invalid {0} point bytes")]
+    InvalidPoint(&'static str),
+    #[error("Expected {expected} {unit}, found {found}")]
+    Length {
+        unit: &'static str,
+        expected: usize,
+        found: usize,
+    },
+    #[error(
+        "Pedersen parameter mismatch: ours {}, theirs {}",
+        hex::encode(.ours),
+        hex::encode(.theirs)
+    )]
+    FingerprintMismatch {
+        ours: [u8; FINGERPRINT_LEN],
+        theirs: [u8; FINGERPRINT_LEN],
+    },
+    #[error("{0} Pedersen parameters were not derived from the given label")]
+    WrongLabel(&'static str),
+}
 
 /// Parameters for Pedersen commitments
 #[derive(Clone)]
@@ -40,7 +77,7 @@ impl<'de> Deserialize<'de> for PedersenParameters {
         D: Deserializer<'de>,
     {
         let serde_params = SerdePedersenParameters::deserialize(deserializer)?;
-        Ok(Self::from(serde_params))
+        Self::try_from(serde_params).map_err(D::Error::custom)
     }
 }
 
@@ -51,14 +88,14 @@ impl PedersenParameters {
     }
 
     /// Creates parameters from compressed bytes
-    pub fn from_compressed_bytes(g_bytes: [u8; 32], h_bytes: [u8; 32]) -> Result<Self> {
-        let g = CompressedRistretto::from_slice(&g_bytes)?
+    pub fn from_compressed_bytes(g_bytes: [u8; 32], h_bytes: [u8; 32]) -> Result<Self, PedersenError> {
+        let g = CompressedRistretto(g_bytes)
             .decompress()
-            .ok_or_else(|| anyhow!("Invalid g point bytes"))?;
+            .ok_or(PedersenError::InvalidPoint("g"))?;
             
-        let h = CompressedRistretto::from_slice(&h_bytes)?
+        let h = CompressedRistretto(h_bytes)
             .decompress()
-            .ok_or_else(|| anyhow!("Invalid h point bytes"))?;
+            .ok_or(PedersenError::InvalidPoint("h"))?;
 
         Ok(Self { g, h })
     }
@@ -66,28 +103,130 @@ impl PedersenParameters {
     pub fn to_compressed_bytes(&self) -> (CompressedRistretto, CompressedRistretto) {
         (self.g.compress(), self.h.compress())
     }
+
+    /// Encodes the parameters as compressed `g` followed by compressed `h`.
+    pub fn to_bytes(&self) -> [u8; ENCODED_LEN] {
+        let mut bytes = [0u8; ENCODED_LEN];
+        bytes[..32].copy_from_slice(self.g.compress().as_bytes());
+        bytes[32..].copy_from_slice(self.h.compress().as_bytes());
+        bytes
+    }
+
+    /// Decodes parameters produced by `to_bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, PedersenError> {
+        if bytes.len() != ENCODED_LEN {
+            return Err(PedersenError::Length {
+                unit: "bytes",
+                expected: ENCODED_LEN,
+                found: bytes.len(),
+            });
+        }
+        let mut g = [0u8; 32];
+        let mut h = [0u8; 32];
+        g.copy_from_slice(&bytes[..32]);
+        h.copy_from_slice(&bytes[32..]);
+        Self::from_compressed_bytes(g, h)
+    }
+
+    /// Gets a short hash identifying these parameters, exchanged when a channel opens.
+    pub fn fingerprint(&self) -> [u8; FINGERPRINT_LEN] {
+        let mut hasher = Sha256::new();
+        hasher.update(b"overpass/pedersen/fingerprint");
+        hasher.update(self.to_bytes());
+        let mut fingerprint = [0u8; FINGERPRINT_LEN];
+        fingerprint.copy_from_slice(&hasher.finalize()[..FINGERPRINT_LEN]);
+        fingerprint
+    }
+
+    /// Checks a counterparty's fingerprint against ours before any commitment is made.
+    pub fn check_fingerprint(&self, fingerprint: &[u8; FINGERPRINT_LEN]) -> Result<(), PedersenError> {
+        if self.fingerprint() != *fingerprint {
+            return Err(PedersenError::FingerprintMismatch {
+                ours: self.fingerprint(),
+                theirs: *fingerprint,
+            });
+        }
+        Ok(())
+    }
+
+    /// Derives the bases from a public label by hashing to the curve.
+    ///
+    /// Nobody knows the discrete log of `h` with respect to `g`, so commitments under
+    /// these parameters stay binding for both counterparties.
+    pub fn from_label(label: &[u8]) -> Self {
+        Self {
+            g: hash_to_point(label, b"g"),
+            h: hash_to_point(label, b"h"),
+        }
+    }
+
+    /// Checks that these parameters are exactly the ones derived from `label`.
+    pub fn verify_label(&self, label: &[u8]) -> Result<(), PedersenError> {
+        let expected = Self::from_label(label);
+        if self.g.compress() != expected.g.compress() || self.h.compress() != expected.h.compress() {
+            return Err(PedersenError::WrongLabel("ristretto255"));
+        }
+        Ok(())
+    }
+}
+
+/// Bases for committing to several values under one point.
+#[derive(Clone)]
+pub struct VectorPedersenParameters {
+    /// One base per committed value.
+    pub g: Vec<RistrettoPoint>,
+    pub h: RistrettoPoint,
+}
+
+impl VectorPedersenParameters {
+    /// Derives `len` value bases and a blinding base from a public label.
+    ///
+    /// The first value base and the blinding base equal those of
+    /// `PedersenParameters::from_label`, so a one-element vector commitment is an
+    /// ordinary Pedersen commitment.
+    pub fn from_label(label: &[u8], len: usize) -> Self {
+        let g = (0..len)
+            .map(|index| match index {
+                0 => hash_to_point(label, b"g"),
+                _ => {
+                    let base = [b"g".as_slice(), &(index as u32).to_le_bytes()].concat();
+                    hash_to_point(label, &base)
+                }
+            })
+            .collect();
+        Self {
+            g,
+            h: hash_to_point(label, b"h"),
+        }
+    }
+
+    /// Gets the number of values these parameters commit to.
+    pub fn len(&self) -> usize {
+        self.g.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.g.is_empty()
+    }
+
+    /// Gets the single-value parameters sharing the first base.
+    pub fn scalar_parameters(&self) -> Option<PedersenParameters> {
+        Some(PedersenParameters::new(*self.g.first()?, self.h))
+    }
+}
+
+impl Debug for VectorPedersenParameters {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VectorPedersenParameters")
+            .field("len", &self.g.len())
+            .field("h", &self.h.compress().to_bytes())
+            .finish()
+    }
 }
 
 impl Default for PedersenParameters {
     fn default() -> Self {
-        // Use deterministic points derived from hashing for defaults
-        use sha2::{Sha512, Digest};
-        
-        let g_bytes = {
-            let mut hasher = Sha512::new();
-            hasher.update(b"g_point");
-            let hash = hasher.finalize();
-            RistrettoPoint::from_uniform_bytes(&hash.into())
-        };
-
-        let h_bytes = {
-            let mut hasher = Sha512::new();
-            hasher.update(b"h_point");
-            let hash = hasher.finalize();
-            RistrettoPoint::from_uniform_bytes(&hash.into())
-        };
-
-        Self { g: g_bytes, h: h_bytes }
+        Self::from_label(DEFAULT_LABEL)
     }
 }
 
@@ -107,16 +246,18 @@ impl From<PedersenParameters> for SerdePedersenParameters {
     }
 }
 
-impl From<SerdePedersenParameters> for PedersenParameters {
-    fn from(serde_params: SerdePedersenParameters) -> Self {
+impl TryFrom<SerdePedersenParameters> for PedersenParameters {
+    type Error = PedersenError;
+
+    fn try_from(serde_params: SerdePedersenParameters) -> Result<Self, PedersenError> {
         PedersenParameters::from_compressed_bytes(serde_params.g, serde_params.h)
-            .expect("Invalid Pedersen parameter bytes")
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use anyhow::Result;
     use serde_json;
 
     #[test]
@@ -166,6 +307,43 @@ mod tests {
         assert_eq!(h1.to_bytes(), h2.to_bytes());
     }
 
+    #[test]
+    fn test_binary_encoding_and_fingerprint() -> Result<()> {
+        let params = PedersenParameters::default();
+        let decoded = PedersenParameters::from_bytes(&params.to_bytes())?;
+        assert_eq!(decoded.to_bytes(), params.to_bytes());
+        assert!(PedersenParameters::from_bytes(&params.to_bytes()[..63]).is_err());
+
+        params.check_fingerprint(&decoded.fingerprint())?;
+        let other = PedersenParameters::from_label(b"other");
+        assert_ne!(other.fingerprint(), params.fingerprint());
+        assert!(params.check_fingerprint(&other.fingerprint()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_deserialize_rejects_invalid_points() {
+        let invalid = serde_json::to_string(&SerdePedersenParameters {
+            g: [0xff; 32],
+            h: [0xff; 32],
+        })
+        .unwrap();
+        assert!(serde_json::from_str::<PedersenParameters>(&invalid).is_err());
+    }
+
+    #[test]
+    fn test_label_verification() -> Result<()> {
+        let params = PedersenParameters::from_label(b"channel-42");
+        params.verify_label(b"channel-42")?;
+        assert!(params.verify_label(b"channel-43").is_err());
+        assert_ne!(params.g.compress(), params.h.compress());
+
+        PedersenParameters::default().verify_label(DEFAULT_LABEL)?;
+        let swapped = PedersenParameters::new(params.h, params.g);
+        assert!(swapped.verify_label(b"channel-42").is_err());
+        Ok(())
+    }
+
     #[test]
     fn test_invalid_bytes() {
         let result = PedersenParameters::from_compressed_bytes([0u8; 32], [0u8; 32]);
@@ -0,0 +1,308 @@
+// src/zkp/proof_of_funds.rs
+
+use curve25519_dalek::scalar::Scalar;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::zkp::global_root_contract::WalletInclusionProof;
+use crate::zkp::helpers::{compute_merkle_root, Bytes32};
+use crate::zkp::opening_proofs::RangeProof;
+use crate::zkp::pedersen_commitment::PedersenCommitment;
+use crate::zkp::pedersen_parameters::PedersenParameters;
+use crate::zkp::wallet_root_proof::{self, ChannelOpening, CommitmentPath};
+
+const TRANSCRIPT_DOMAIN: &[u8] = b"overpass/proof-of-funds/v1";
+
+/// Why a proof of funds was not accepted.
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FundsProofError {
+    #[error("Wallet root is not part of the proven global root")]
+    NotInGlobalRoot,
+    #[error("Global root {} is not anchored", hex::encode(.0))]
+    NotAnchored(Bytes32),
+    #[error("Channel commitments are not part of the wallet root")]
+    NotInWalletRoot,
+    #[error("Range proof does not show the claimed minimum")]
+    InvalidRangeProof,
+}
+
+/// Evidence for a third party that a wallet held at least `minimum` under a global root
+/// anchored on Bitcoin, revealing neither its balance nor its channels.
+///
+/// The balance is the sum of the channel commitments under the wallet root, each shown
+/// in place like in a [`WalletRootProof`](crate::zkp::wallet_root_proof::WalletRootProof),
+/// so the wallet can only claim what the state it published commits to.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FundsProof {
+    pub wallet_proof: WalletInclusionProof,
+    pub global_root: Bytes32,
+    /// Channel commitments in channel id order.
+    pub commitments: Vec<Bytes32>,
+    /// Path to the wallet root of each commitment.
+    pub paths: Vec<CommitmentPath>,
+    /// Proof that each commitment hides a balance below `2^64`.
+    pub channel_range_proofs: Vec<RangeProof>,
+    pub minimum: u64,
+    /// Shows that the sum of the commitments less `g * minimum` hides a 64-bit value, so
+    /// the balance is at least `minimum`.
+    pub range_proof: RangeProof,
+}
+
+impl FundsProof {
+    /// Proves the channels in `openings` hold at least `minimum` between them, or returns
+    /// `None` if they do not.
+    ///
+    /// `context` is chosen by the verifier, such as a nonce it sent, so the proof cannot
+    /// be shown to anyone else as fresh.
+    pub fn prove(
+        params: &PedersenParameters,
+        wallet_proof: WalletInclusionProof,
+        global_root: Bytes32,
+        openings: &[ChannelOpening],
+        minimum: u64,
+        context: &[u8],
+    ) -> Option<Self> {
+        #[cfg(feature = "metrics")]
+        let _timer = crate::services::metrics::global().proof_timer("funds");
+        let excess = wallet_root_proof::total_balance(openings)?.checked_sub(minimum)?;
+        let commitments = wallet_root_proof::commit_openings(params, openings);
+        let blinding: Scalar = openings.iter().map(|opening| opening.blinding).sum();
+        let mut proof = Self {
+            wallet_proof,
+            global_root,
+            commitments: commitments.iter().map(PedersenCommitment::to_bytes).collect(),
+            paths: openings.iter().map(|opening| opening.path.clone()).collect(),
+            channel_range_proofs: Vec::new(),
+            minimum,
+            range_proof: RangeProof { bits: Vec::new() },
+        };
+        proof.channel_range_proofs = wallet_root_proof::prove_ranges(
+            params,
+            openings,
+            &commitments,
+            &proof.channel_transcript(),
+        );
+        let excess_commitment = proof.excess_commitment(params, commitments.into_iter().sum());
+        proof.range_proof = RangeProof::prove(
+            params,
+            &excess_commitment,
+            excess,
+            &blinding,
+            &proof.transcript(context),
+        );
+        Some(proof)
+    }
+
+    /// Checks the proof with nothing but the commitment parameters, the global roots the
+    /// verifier has seen anchored and the context it asked for.
+    #[tracing::instrument(
+        name = "verify",
+        skip_all,
+        fields(proof = "funds", wallet_id = %hex::encode(self.wallet_id())),
+        err
+    )]
+    pub fn verify(
+        &self,
+        params: &PedersenParameters,
+        anchored_roots: &[Bytes32],
+        context: &[u8],
+    ) -> Result<(), FundsProofError> {
+        let result = self.check(params, anchored_roots, context);
+        #[cfg(feature = "metrics")]
+        crate::services::metrics::global().record_verification("funds", result.is_ok());
+        result
+    }
+
+    fn check(
+        &self,
+        params: &PedersenParameters,
+        anchored_roots: &[Bytes32],
+        context: &[u8],
+    ) -> Result<(), FundsProofError> {
+        if !self.wallet_proof.verify(&self.global_root) {
+            return Err(FundsProofError::NotInGlobalRoot);
+        }
+        if !anchored_roots.contains(&self.global_root) {
+            return Err(FundsProofError::NotAnchored(self.global_root));
+        }
+        let sum = wallet_root_proof::sum_under_root(
+            params,
+            &self.wallet_proof.wallet_root,
+            &self.commitments,
+            &self.paths,
+            &self.channel_range_proofs,
+            &self.channel_transcript(),
+        )
+        .ok_or(FundsProofError::NotInWalletRoot)?;
+        let excess_commitment = self.excess_commitment(params, sum);
+        if !self
+            .range_proof
+            .verify(params, &excess_commitment, &self.transcript(context))
+        {
+            return Err(FundsProofError::InvalidRangeProof);
+        }
+        Ok(())
+    }
+
+    pub fn wallet_id(&self) -> &Bytes32 {
+        &self.wallet_proof.wallet_id
+    }
+
+    fn excess_commitment(
+        &self,
+        params: &PedersenParameters,
+        sum: PedersenCommitment,
+    ) -> PedersenCommitment {
+        PedersenCommitment::from_point(sum.point() - params.g * Scalar::from(self.minimum))
+    }
+
+    /// Binds the channels' range proofs to the wallet, its root and the commitments, as
+    /// in a wallet root proof.
+    fn channel_transcript(&self) -> Vec<u8> {
+        wallet_root_proof::transcript(
+            &self.wallet_proof.wallet_id,
+            &self.wallet_proof.wallet_root,
+            &self.commitments,
+        )
+    }
+
+    /// Binds the range proof to the wallet, both roots, the commitments, the minimum and
+    /// `context`.
+    fn transcript(&self, context: &[u8]) -> Vec<u8> {
+        [
+            TRANSCRIPT_DOMAIN,
+            &self.wallet_proof.wallet_id,
+            &self.wallet_proof.wallet_root,
+            &self.global_root,
+            &compute_merkle_root(self.commitments.clone()),
+            &(self.commitments.len() as u64).to_le_bytes(),
+            &self.minimum.to_le_bytes(),
+            context,
+        ]
+        .concat()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+    use crate::zkp::blinding::BlindingFactor;
+    use crate::zkp::wallet_contract::{WalletContract, WalletContractError};
+
+    fn wallet() -> WalletContract {
+        let mut wallet = test_util::wallet([1u8; 32]);
+        wallet.open_channel(100, [7u8; 32], Vec::new()).unwrap();
+        wallet.open_channel(50, [8u8; 32], Vec::new()).unwrap();
+        wallet
+    }
+
+    #[test]
+    fn test_third_party_verifies_minimum_offline() -> Result<(), WalletContractError> {
+        let mut wallet = wallet();
+        assert!(matches!(
+            wallet.proof_of_funds(100, b"landlord"),
+            Err(WalletContractError::GlobalRootError(_) | WalletContractError::UnrecordedRoot)
+        ));
+        wallet.repair_against_global(1)?;
+        let anchored = [wallet.global_contract.get_global_merkle_root()];
+        let params = PedersenParameters::default();
+
+        let exported = serde_json::to_string(&wallet.proof_of_funds(120, b"landlord")?)?;
+        let proof: FundsProof = serde_json::from_str(&exported)?;
+        assert_eq!(proof.verify(&params, &anchored, b"landlord"), Ok(()));
+        assert_eq!(proof.wallet_id(), &[1u8; 32]);
+        assert_eq!(
+            proof.verify(&params, &anchored, b"exchange"),
+            Err(FundsProofError::InvalidRangeProof)
+        );
+        assert_eq!(
+            proof.verify(&params, &[[9u8; 32]], b"landlord"),
+            Err(FundsProofError::NotAnchored(anchored[0]))
+        );
+
+        let mut inflated = proof.clone();
+        inflated.minimum = 151;
+        assert_eq!(
+            inflated.verify(&params, &anchored, b"landlord"),
+            Err(FundsProofError::InvalidRangeProof)
+        );
+        let mut moved = proof.clone();
+        moved.wallet_proof.wallet_root = [0u8; 32];
+        assert_eq!(
+            moved.verify(&params, &anchored, b"landlord"),
+            Err(FundsProofError::NotInGlobalRoot)
+        );
+
+        assert!(wallet.proof_of_funds(150, b"landlord").is_ok());
+        assert!(matches!(
+            wallet.proof_of_funds(151, b"landlord"),
+            Err(WalletContractError::InsufficientFunds(151))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_balance_beyond_the_channels_is_rejected() -> Result<(), WalletContractError> {
+        let mut wallet = wallet();
+        wallet.repair_against_global(1)?;
+        let anchored = [wallet.global_contract.get_global_merkle_root()];
+        let params = PedersenParameters::default();
+        let wallet_proof = wallet.global_contract.inclusion_proof(&[1u8; 32])?;
+
+        // The first channel commits to 100, not 1000.
+        let mut openings = wallet.root_openings()?;
+        openings[0].balance = 1_000;
+        let inflated = FundsProof::prove(
+            &params,
+            wallet_proof.clone(),
+            anchored[0],
+            &openings,
+            1_000,
+            b"landlord",
+        )
+        .unwrap();
+        assert_eq!(
+            inflated.verify(&params, &anchored, b"landlord"),
+            Err(FundsProofError::NotInWalletRoot)
+        );
+
+        // Nor can a fresh commitment to the claimed balance stand in for the channels.
+        let detached = FundsProof::prove(
+            &params,
+            wallet_proof,
+            anchored[0],
+            &[ChannelOpening {
+                balance: 1_000,
+                blinding: *BlindingFactor::random().scalar(),
+                path: openings[0].path.clone(),
+            }],
+            1_000,
+            b"landlord",
+        )
+        .unwrap();
+        assert_eq!(
+            detached.verify(&params, &anchored, b"landlord"),
+            Err(FundsProofError::NotInWalletRoot)
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_proof_matches_blocking_proof() -> Result<(), WalletContractError> {
+        let mut wallet = wallet();
+        wallet.repair_against_global(1)?;
+        let anchored = [wallet.global_contract.get_global_merkle_root()];
+        let params = PedersenParameters::default();
+
+        let proof = wallet.proof_of_funds_async(120, b"landlord".to_vec()).await?;
+        assert_eq!(proof.verify(&params, &anchored, b"landlord"), Ok(()));
+        assert_eq!(proof.minimum, 120);
+        assert!(matches!(
+            wallet.proof_of_funds_async(151, b"landlord".to_vec()).await,
+            Err(WalletContractError::InsufficientFunds(151))
+        ));
+        Ok(())
+    }
+}
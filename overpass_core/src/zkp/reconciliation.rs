@@ -0,0 +1,361 @@
+// src/zkp/reconciliation.rs
+
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use thiserror::Error;
+
+use crate::clock::TimePolicy;
+use crate::zkp::channel::ChannelState;
+use crate::zkp::fraud_proof::proof_binds;
+use crate::zkp::helpers::{compute_merkle_root, Bytes32};
+use crate::zkp::state_proof::StateProof;
+use crate::zkp::wallet_contract::ChannelInclusionProof;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ReconciliationError {
+    #[error("Tree view leaves do not hash to its root")]
+    InvalidView,
+    #[error("Evidence for channel {} does not match the sender's root", hex::encode(.0))]
+    InvalidEvidence(Bytes32),
+    #[error("No evidence for divergent channel {}", hex::encode(.0))]
+    MissingEvidence(Bytes32),
+    #[error("State for channel {} does not follow the local one", hex::encode(.0))]
+    InvalidTransition(Bytes32),
+    #[error("Channel {} is not known to this wallet", hex::encode(.0))]
+    UnknownChannel(Bytes32),
+    #[error("Channel {} has two different states at nonce {nonce}", hex::encode(.channel_id))]
+    Conflict { channel_id: Bytes32, nonce: u64 },
+    #[error("Wallet error: {0}")]
+    Wallet(String),
+}
+
+/// One party's view of a wallet tree: every channel's state hash, ordered by channel id.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TreeView {
+    pub root: Bytes32,
+    pub leaves: Vec<(Bytes32, Bytes32)>,
+}
+
+impl TreeView {
+    pub fn new(leaves: Vec<(Bytes32, Bytes32)>) -> Self {
+        let root = compute_merkle_root(leaves.iter().map(|(_, hash)| *hash).collect());
+        Self { root, leaves }
+    }
+
+    /// Checks that the leaves are sorted and hash to the root.
+    pub fn verify(&self) -> Result<(), ReconciliationError> {
+        let sorted = self.leaves.windows(2).all(|pair| pair[0].0 < pair[1].0);
+        if !sorted || TreeView::new(self.leaves.clone()).root != self.root {
+            return Err(ReconciliationError::InvalidView);
+        }
+        Ok(())
+    }
+}
+
+/// A channel on which two views disagree.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Divergence {
+    OnlyLocal(Bytes32),
+    OnlyRemote(Bytes32),
+    Differs(Bytes32),
+}
+
+impl Divergence {
+    pub fn channel_id(&self) -> &Bytes32 {
+        match self {
+            Divergence::OnlyLocal(id) | Divergence::OnlyRemote(id) | Divergence::Differs(id) => id,
+        }
+    }
+}
+
+/// Lists the channels on which two views disagree, in channel id order.
+pub fn diff(local: &TreeView, remote: &TreeView) -> Vec<Divergence> {
+    if local.root == remote.root {
+        return Vec::new();
+    }
+    let (mut ours, mut theirs) = (
+        local.leaves.iter().peekable(),
+        remote.leaves.iter().peekable(),
+    );
+    let mut divergences = Vec::new();
+    loop {
+        match (ours.peek(), theirs.peek()) {
+            (Some((id, hash)), Some((remote_id, remote_hash))) => match id.cmp(remote_id) {
+                Ordering::Less => {
+                    divergences.push(Divergence::OnlyLocal(*id));
+                    ours.next();
+                }
+                Ordering::Greater => {
+                    divergences.push(Divergence::OnlyRemote(*remote_id));
+                    theirs.next();
+                }
+                Ordering::Equal => {
+                    if hash != remote_hash {
+                        divergences.push(Divergence::Differs(*id));
+                    }
+                    ours.next();
+                    theirs.next();
+                }
+            },
+            (Some((id, _)), None) => {
+                divergences.push(Divergence::OnlyLocal(*id));
+                ours.next();
+            }
+            (None, Some((id, _))) => {
+                divergences.push(Divergence::OnlyRemote(*id));
+                theirs.next();
+            }
+            (None, None) => return divergences,
+        }
+    }
+}
+
+/// A channel state together with proof that it is a leaf of the sender's view.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ChannelEvidence {
+    pub state: ChannelState,
+    pub proof: ChannelInclusionProof,
+}
+
+impl ChannelEvidence {
+    /// Checks that the state hashes to the proven leaf under `root`.
+    pub fn verify(&self, root: &Bytes32) -> Result<(), ReconciliationError> {
+        let matches = self
+            .state
+            .hash()
+            .is_ok_and(|hash| hash == self.proof.channel_hash);
+        if !matches || !self.proof.verify(root) {
+            return Err(ReconciliationError::InvalidEvidence(self.proof.channel_id));
+        }
+        Ok(())
+    }
+
+    /// Checks that the state is the update right after `from`: the next nonce, carrying
+    /// the proof of the transition from `from`'s commitment to its own.
+    pub fn verify_transition(
+        &self,
+        from: &ChannelState,
+        now: u64,
+    ) -> Result<(), ReconciliationError> {
+        // A missed update may be arbitrarily old, but not from the future
+        let policy = TimePolicy {
+            max_age: u64::MAX,
+            ..TimePolicy::default()
+        };
+        let proof = self
+            .state
+            .proof
+            .as_deref()
+            .and_then(|bytes| serde_json::from_slice::<StateProof>(bytes).ok());
+        let follows = from.nonce.checked_add(1) == Some(self.state.nonce)
+            && proof.is_some_and(|proof| {
+                policy.accepts(proof.timestamp, now)
+                    && proof_binds(&proof, &from.merkle_root, &self.state.merkle_root)
+            });
+        if !follows {
+            return Err(ReconciliationError::InvalidTransition(self.proof.channel_id));
+        }
+        Ok(())
+    }
+}
+
+/// The party whose state a channel converges on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Side {
+    Local,
+    Remote,
+}
+
+/// Picks the state both parties should converge on for a channel they disagree about.
+///
+/// The later state by nonce wins. Two different states at the same nonce mean someone
+/// signed conflicting updates, which no exchange of proofs can settle.
+pub fn resolve(
+    local: &ChannelEvidence,
+    remote: &ChannelEvidence,
+) -> Result<Side, ReconciliationError> {
+    match local.state.nonce.cmp(&remote.state.nonce) {
+        Ordering::Greater => Ok(Side::Local),
+        Ordering::Less => Ok(Side::Remote),
+        Ordering::Equal if local.proof.channel_hash == remote.proof.channel_hash => Ok(Side::Local),
+        Ordering::Equal => Err(ReconciliationError::Conflict {
+            channel_id: local.proof.channel_id,
+            nonce: local.state.nonce,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+    use crate::zkp::helpers::{convert_helper_proof, generate_state_proof};
+    use crate::zkp::pedersen_parameters::PedersenParameters;
+    use crate::zkp::wallet_contract::WalletContract;
+
+    fn wallet(id: u8, channels: &[(u8, u64, u64)]) -> WalletContract {
+        let mut wallet = test_util::unkeyed_wallet([id; 32]);
+        for &(channel, balance, nonce) in channels {
+            wallet
+                .channels
+                .insert([channel; 32], test_util::channel_state(vec![balance], nonce));
+        }
+        wallet
+    }
+
+    /// Registers a channel with `wallet` and drops its state, as if the state was lost.
+    fn forget(wallet: &mut WalletContract, channel: u8) {
+        assert!(wallet.register_channel([channel; 32], 0, [9u8; 32], Vec::new()).unwrap());
+        wallet.channels.remove(&[channel; 32]);
+    }
+
+    /// Gets the update after `from` to `balance`, with the proof of the transition.
+    fn next_state(from: &ChannelState, balance: u64) -> ChannelState {
+        let mut next = test_util::channel_state(vec![balance], from.nonce + 1);
+        next.merkle_root = [balance as u8; 32];
+        let proof = convert_helper_proof(generate_state_proof(
+            from.merkle_root,
+            next.merkle_root,
+            [0u8; 32],
+            &PedersenParameters::default(),
+        ));
+        next.proof = Some(serde_json::to_vec(&proof).unwrap());
+        next
+    }
+
+    #[test]
+    fn test_wallets_converge() {
+        let mut alice = wallet(1, &[(1, 100, 3), (2, 50, 1), (4, 10, 0)]);
+        let mut bob = wallet(2, &[(2, 50, 1), (3, 70, 2)]);
+        bob.channels.insert([1u8; 32], next_state(&alice.channels[&[1u8; 32]], 90));
+        forget(&mut alice, 3);
+        forget(&mut bob, 4);
+        let (alice_view, bob_view) = (alice.tree_view().unwrap(), bob.tree_view().unwrap());
+        let from_alice = alice.evidence_for(&bob_view).unwrap();
+        let from_bob = bob.evidence_for(&alice_view).unwrap();
+        assert_eq!(from_alice.len(), 2);
+        assert_eq!(from_bob.len(), 2);
+
+        assert_eq!(
+            alice.reconcile(&bob_view, &from_bob).unwrap(),
+            vec![[1u8; 32], [3u8; 32]]
+        );
+        assert_eq!(
+            bob.reconcile(&alice_view, &from_alice).unwrap(),
+            vec![[4u8; 32]]
+        );
+        assert_eq!(alice.tree_view().unwrap(), bob.tree_view().unwrap());
+        assert_eq!(alice.get_merkle_root(), bob.get_merkle_root());
+        assert_eq!(alice.get_channel(&[1u8; 32]).unwrap().balances, vec![90]);
+    }
+
+    #[test]
+    fn test_conflicts_and_bad_evidence_are_rejected() {
+        let mut alice = wallet(1, &[(1, 100, 3), (2, 10, 0)]);
+        let bob = wallet(2, &[(1, 90, 3), (2, 20, 1)]);
+        let bob_view = bob.tree_view().unwrap();
+        let from_bob = bob.evidence_for(&alice.tree_view().unwrap()).unwrap();
+        let before = alice.tree_view().unwrap();
+
+        assert_eq!(
+            alice.reconcile(&bob_view, &from_bob),
+            Err(ReconciliationError::Conflict {
+                channel_id: [1u8; 32],
+                nonce: 3
+            })
+        );
+        assert_eq!(alice.tree_view().unwrap(), before);
+        assert_eq!(
+            alice.reconcile(&bob_view, &from_bob[..1]),
+            Err(ReconciliationError::Conflict {
+                channel_id: [1u8; 32],
+                nonce: 3
+            })
+        );
+        assert_eq!(
+            alice.reconcile(&bob_view, &from_bob[1..]),
+            Err(ReconciliationError::MissingEvidence([1u8; 32]))
+        );
+
+        let mut forged = from_bob.clone();
+        forged[1].state.balances = vec![1_000];
+        assert_eq!(
+            alice.reconcile(&bob_view, &forged[1..]),
+            Err(ReconciliationError::MissingEvidence([1u8; 32]))
+        );
+        assert_eq!(
+            forged[1].verify(&bob_view.root),
+            Err(ReconciliationError::InvalidEvidence([2u8; 32]))
+        );
+    }
+
+    #[test]
+    fn test_unproven_and_unknown_states_are_rejected() {
+        let mut alice = wallet(1, &[(1, 100, 3)]);
+        let before = alice.tree_view().unwrap();
+        let ours = alice.channels[&[1u8; 32]].clone();
+        let attempt = |alice: &mut WalletContract, theirs: ChannelState| {
+            let mut mallory = wallet(2, &[]);
+            mallory.channels.insert([1u8; 32], theirs);
+            let view = mallory.tree_view().unwrap();
+            let evidence = mallory.evidence_for(&alice.tree_view().unwrap()).unwrap();
+            alice.reconcile(&view, &evidence)
+        };
+
+        let mut skipped = test_util::channel_state(vec![1_000_000], u64::MAX);
+        skipped.proof = next_state(&ours, 1_000_000).proof;
+        assert_eq!(
+            attempt(&mut alice, skipped),
+            Err(ReconciliationError::InvalidTransition([1u8; 32]))
+        );
+        let mut unproven = next_state(&ours, 1_000_000);
+        unproven.proof = None;
+        assert_eq!(
+            attempt(&mut alice, unproven),
+            Err(ReconciliationError::InvalidTransition([1u8; 32]))
+        );
+        let mut elsewhere = ours.clone();
+        elsewhere.merkle_root = [7u8; 32];
+        assert_eq!(
+            attempt(&mut alice, next_state(&elsewhere, 1_000_000)),
+            Err(ReconciliationError::InvalidTransition([1u8; 32]))
+        );
+
+        let mut mallory = wallet(2, &[(5, 1_000_000, 0)]);
+        mallory.channels.insert([1u8; 32], ours);
+        let evidence = mallory.evidence_for(&before).unwrap();
+        assert_eq!(
+            alice.reconcile(&mallory.tree_view().unwrap(), &evidence),
+            Err(ReconciliationError::UnknownChannel([5u8; 32]))
+        );
+        assert_eq!(alice.tree_view().unwrap(), before);
+    }
+
+    #[test]
+    fn test_diff_finds_divergent_channels() {
+        let local = TreeView::new(vec![
+            ([1u8; 32], [1u8; 32]),
+            ([2u8; 32], [2u8; 32]),
+            ([4u8; 32], [4u8; 32]),
+        ]);
+        let remote = TreeView::new(vec![
+            ([2u8; 32], [9u8; 32]),
+            ([3u8; 32], [3u8; 32]),
+            ([4u8; 32], [4u8; 32]),
+        ]);
+        local.verify().unwrap();
+        assert_eq!(
+            diff(&local, &remote),
+            vec![
+                Divergence::OnlyLocal([1u8; 32]),
+                Divergence::Differs([2u8; 32]),
+                Divergence::OnlyRemote([3u8; 32]),
+            ]
+        );
+        assert!(diff(&local, &local).is_empty());
+
+        let mut unsorted = remote.clone();
+        unsorted.leaves.reverse();
+        assert_eq!(unsorted.verify(), Err(ReconciliationError::InvalidView));
+    }
+}
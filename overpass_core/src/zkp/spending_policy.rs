@@ -0,0 +1,351 @@
+// src/zkp/spending_policy.rs
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use thiserror::Error;
+
+use crate::services::overpass_db::OverpassDB;
+use crate::zkp::helpers::Bytes32;
+
+const SECONDS_PER_DAY: u64 = 86_400;
+const POLICY_PREFIX: &[u8] = b"spending_policy:";
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum PolicyError {
+    #[error("Spend of {amount} exceeds the daily limit, {remaining} left today")]
+    DailyLimit { amount: u64, remaining: u64 },
+    #[error(
+        "Spend of {amount} to {} exceeds its limit, {remaining} left today",
+        hex::encode(.counterparty)
+    )]
+    CounterpartyLimit {
+        counterparty: Bytes32,
+        amount: u64,
+        remaining: u64,
+    },
+    #[error("Spend of {0} needs confirmation")]
+    ConfirmationRequired(u64),
+    #[error("Storage error: {0}")]
+    StorageError(String),
+    #[error("Corrupt policy state: {0}")]
+    CorruptState(String),
+}
+
+/// Rules a wallet checks before signing a transition that lowers its balance.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpendingPolicy {
+    /// Most the wallet spends per UTC day, over all channels.
+    pub daily_limit: Option<u64>,
+    /// Most the wallet spends per UTC day towards each listed counterparty.
+    pub counterparty_limits: HashMap<Bytes32, u64>,
+    /// Spends above this amount need a confirmation given beforehand.
+    pub confirm_above: Option<u64>,
+}
+
+/// A balance decrease the wallet is about to sign.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Spend {
+    pub channel_id: Bytes32,
+    pub counterparty: Bytes32,
+    pub amount: u64,
+    /// Unix time in seconds.
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AuditEvent {
+    PolicySet(SpendingPolicy),
+    Confirmed { channel_id: Bytes32, amount: u64 },
+    Allowed(Spend),
+    Refused(Spend, String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub timestamp: u64,
+    pub event: AuditEvent,
+}
+
+/// Enforces a spending policy and keeps the running totals and audit log it needs.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PolicyEngine {
+    policy: SpendingPolicy,
+    /// UTC day the totals below were counted in.
+    day: u64,
+    spent_today: u64,
+    counterparty_spent: HashMap<Bytes32, u64>,
+    /// Confirmed `(channel_id, amount)` spends not yet made.
+    confirmations: Vec<(Bytes32, u64)>,
+    audit: Vec<AuditEntry>,
+}
+
+impl PolicyEngine {
+    pub fn new(policy: SpendingPolicy, now: u64) -> Self {
+        let mut engine = Self {
+            policy: SpendingPolicy::default(),
+            day: now / SECONDS_PER_DAY,
+            spent_today: 0,
+            counterparty_spent: HashMap::new(),
+            confirmations: Vec::new(),
+            audit: Vec::new(),
+        };
+        engine.set_policy(policy, now);
+        engine
+    }
+
+    pub fn policy(&self) -> &SpendingPolicy {
+        &self.policy
+    }
+
+    /// Replaces the policy. Amounts already spent today still count against new limits.
+    pub fn set_policy(&mut self, policy: SpendingPolicy, now: u64) {
+        self.policy = policy.clone();
+        self.log(now, AuditEvent::PolicySet(policy));
+    }
+
+    /// Confirms one upcoming spend of `amount` on a channel, for when it exceeds the
+    /// confirmation threshold.
+    pub fn confirm(&mut self, channel_id: Bytes32, amount: u64, now: u64) {
+        self.confirmations.push((channel_id, amount));
+        self.log(now, AuditEvent::Confirmed { channel_id, amount });
+    }
+
+    /// Gets how much more may be spent today under the daily limit.
+    pub fn remaining_today(&self, now: u64) -> Option<u64> {
+        let spent = self.spent_on(now / SECONDS_PER_DAY);
+        self.policy
+            .daily_limit
+            .map(|limit| limit.saturating_sub(spent))
+    }
+
+    /// Checks a spend against every rule without recording it.
+    pub fn check(&self, spend: &Spend) -> Result<(), PolicyError> {
+        let day = spend.timestamp / SECONDS_PER_DAY;
+        if let Some(limit) = self.policy.daily_limit {
+            let remaining = limit.saturating_sub(self.spent_on(day));
+            if spend.amount > remaining {
+                return Err(PolicyError::DailyLimit {
+                    amount: spend.amount,
+                    remaining,
+                });
+            }
+        }
+        if let Some(limit) = self.policy.counterparty_limits.get(&spend.counterparty) {
+            let spent = if day == self.day {
+                self.counterparty_spent
+                    .get(&spend.counterparty)
+                    .copied()
+                    .unwrap_or(0)
+            } else {
+                0
+            };
+            let remaining = limit.saturating_sub(spent);
+            if spend.amount > remaining {
+                return Err(PolicyError::CounterpartyLimit {
+                    counterparty: spend.counterparty,
+                    amount: spend.amount,
+                    remaining,
+                });
+            }
+        }
+        let needs_confirmation = self
+            .policy
+            .confirm_above
+            .is_some_and(|threshold| spend.amount > threshold);
+        if needs_confirmation && self.confirmation_for(spend).is_none() {
+            return Err(PolicyError::ConfirmationRequired(spend.amount));
+        }
+        Ok(())
+    }
+
+    /// Checks a spend and, if allowed, counts it against today's limits and uses up its
+    /// confirmation. Refusals are logged too.
+    pub fn authorize(&mut self, spend: Spend) -> Result<(), PolicyError> {
+        if let Err(err) = self.check(&spend) {
+            self.log(spend.timestamp, AuditEvent::Refused(spend, err.to_string()));
+            return Err(err);
+        }
+        let day = spend.timestamp / SECONDS_PER_DAY;
+        if day != self.day {
+            self.day = day;
+            self.spent_today = 0;
+            self.counterparty_spent.clear();
+        }
+        self.spent_today = self.spent_today.saturating_add(spend.amount);
+        let spent = self
+            .counterparty_spent
+            .entry(spend.counterparty)
+            .or_insert(0);
+        *spent = spent.saturating_add(spend.amount);
+        if self
+            .policy
+            .confirm_above
+            .is_some_and(|threshold| spend.amount > threshold)
+        {
+            if let Some(index) = self.confirmation_for(&spend) {
+                self.confirmations.remove(index);
+            }
+        }
+        self.log(spend.timestamp, AuditEvent::Allowed(spend));
+        Ok(())
+    }
+
+    pub fn audit_log(&self) -> &[AuditEntry] {
+        &self.audit
+    }
+
+    /// Writes the policy, its running totals and the audit log to `db` under `wallet_id`.
+    pub fn save(&self, db: &OverpassDB, wallet_id: &Bytes32) -> Result<(), PolicyError> {
+        let bytes =
+            bincode::serialize(self).map_err(|e| PolicyError::StorageError(e.to_string()))?;
+        db.put(&policy_key(wallet_id), &bytes)
+            .and_then(|_| db.flush())
+            .map_err(|e| PolicyError::StorageError(e.to_string()))
+    }
+
+    /// Loads the engine saved for `wallet_id`, or returns `None` if nothing was saved.
+    pub fn restore(db: &OverpassDB, wallet_id: &Bytes32) -> Result<Option<Self>, PolicyError> {
+        db.get(&policy_key(wallet_id))
+            .map_err(|e| PolicyError::StorageError(e.to_string()))?
+            .map(|bytes| {
+                bincode::deserialize(&bytes).map_err(|e| PolicyError::CorruptState(e.to_string()))
+            })
+            .transpose()
+    }
+
+    fn spent_on(&self, day: u64) -> u64 {
+        if day == self.day {
+            self.spent_today
+        } else {
+            0
+        }
+    }
+
+    fn confirmation_for(&self, spend: &Spend) -> Option<usize> {
+        self.confirmations.iter().position(|&(channel_id, amount)| {
+            channel_id == spend.channel_id && amount == spend.amount
+        })
+    }
+
+    fn log(&mut self, timestamp: u64, event: AuditEvent) {
+        self.audit.push(AuditEntry { timestamp, event });
+    }
+}
+
+fn policy_key(wallet_id: &Bytes32) -> Vec<u8> {
+    let mut key = POLICY_PREFIX.to_vec();
+    key.extend_from_slice(hex::encode(wallet_id).as_bytes());
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: u64 = SECONDS_PER_DAY;
+
+    fn spend(counterparty: u8, amount: u64, timestamp: u64) -> Spend {
+        Spend {
+            channel_id: [counterparty; 32],
+            counterparty: [counterparty; 32],
+            amount,
+            timestamp,
+        }
+    }
+
+    fn policy() -> SpendingPolicy {
+        SpendingPolicy {
+            daily_limit: Some(1_000),
+            counterparty_limits: HashMap::from([([2u8; 32], 300)]),
+            confirm_above: Some(500),
+        }
+    }
+
+    #[test]
+    fn test_limits_reset_daily() -> Result<(), PolicyError> {
+        let mut engine = PolicyEngine::new(policy(), 10 * DAY);
+        engine.authorize(spend(2, 250, 10 * DAY))?;
+        assert_eq!(
+            engine.check(&spend(2, 100, 10 * DAY + 60)),
+            Err(PolicyError::CounterpartyLimit {
+                counterparty: [2u8; 32],
+                amount: 100,
+                remaining: 50
+            })
+        );
+        engine.authorize(spend(1, 400, 10 * DAY + 120))?;
+        engine.authorize(spend(1, 300, 10 * DAY + 180))?;
+        assert_eq!(
+            engine.authorize(spend(1, 100, 10 * DAY + 240)),
+            Err(PolicyError::DailyLimit {
+                amount: 100,
+                remaining: 50
+            })
+        );
+        assert_eq!(engine.remaining_today(10 * DAY + 300), Some(50));
+
+        assert_eq!(engine.remaining_today(11 * DAY), Some(1_000));
+        engine.authorize(spend(2, 300, 11 * DAY))?;
+        assert_eq!(engine.remaining_today(11 * DAY), Some(700));
+        Ok(())
+    }
+
+    #[test]
+    fn test_large_spends_need_a_confirmation_each() {
+        let mut engine = PolicyEngine::new(policy(), 0);
+        assert_eq!(
+            engine.authorize(spend(1, 600, 10)),
+            Err(PolicyError::ConfirmationRequired(600))
+        );
+        engine.confirm([1u8; 32], 600, 20);
+        assert_eq!(
+            engine.check(&spend(1, 601, 30)),
+            Err(PolicyError::ConfirmationRequired(601))
+        );
+        engine.authorize(spend(1, 600, 30)).unwrap();
+        engine.confirm([1u8; 32], 200, 40);
+        assert_eq!(
+            engine.authorize(spend(1, 600, 50)),
+            Err(PolicyError::DailyLimit {
+                amount: 600,
+                remaining: 400
+            })
+        );
+
+        let events: Vec<_> = engine
+            .audit_log()
+            .iter()
+            .map(|entry| &entry.event)
+            .collect();
+        assert!(matches!(
+            events.as_slice(),
+            [
+                AuditEvent::PolicySet(_),
+                AuditEvent::Refused(..),
+                AuditEvent::Confirmed { amount: 600, .. },
+                AuditEvent::Allowed(Spend { amount: 600, .. }),
+                AuditEvent::Confirmed { amount: 200, .. },
+                AuditEvent::Refused(..),
+            ]
+        ));
+    }
+
+    #[test]
+    fn test_policy_state_survives_restart() -> anyhow::Result<()> {
+        let path = std::env::temp_dir().join(format!("spending_policy_{}", std::process::id()));
+        let db = OverpassDB::new(path.to_str().unwrap())?;
+        assert_eq!(PolicyEngine::restore(&db, &[1u8; 32])?, None);
+
+        let mut engine = PolicyEngine::new(policy(), DAY);
+        engine.authorize(spend(1, 250, DAY + 1))?;
+        engine.save(&db, &[1u8; 32])?;
+
+        let restored = PolicyEngine::restore(&db, &[1u8; 32])?.unwrap();
+        assert_eq!(restored, engine);
+        assert_eq!(restored.remaining_today(DAY + 2), Some(750));
+        assert_eq!(PolicyEngine::restore(&db, &[2u8; 32])?, None);
+        drop(db);
+        let _ = std::fs::remove_dir_all(path);
+        Ok(())
+    }
+}
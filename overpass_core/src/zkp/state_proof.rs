@@ -6,7 +6,7 @@ use serde::{Serialize, Deserialize};
 pub type Bytes32 = [u8; 32];
 
 /// Zero-knowledge proof of state transition validity.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct StateProof {
     /// The proof itself.
     pub pi: Bytes32,
@@ -2,14 +2,17 @@
 
 use crate::zkp::tree::{MerkleProof, MerkleTree};
 use plonky2::plonk::config::Hasher;
-use anyhow::{anyhow, Context, Result};
+use anyhow::Context;
 use plonky2::{
     field::goldilocks_field::GoldilocksField,
     hash::{
         hash_types::{HashOut, HashOutTarget},
         poseidon::PoseidonHash,
     },
-    iop::witness::{PartialWitness, WitnessWrite},
+    iop::{
+        target::Target,
+        witness::{PartialWitness, WitnessWrite},
+    },
     plonk::{
         circuit_builder::CircuitBuilder,
         circuit_data::{CircuitConfig, CircuitData},
@@ -19,18 +22,56 @@ use plonky2::{
 };
 use crate::zkp::channel::ChannelState;
 use plonky2_field::types::{Field, PrimeField64};
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
+use thiserror::Error;
 
 
 /// Type alias for Poseidon configuration
 type PoseidonConfig = PoseidonGoldilocksConfig;
 
+/// Assets, the native balance included, whose conservation one proof can constrain.
+pub const MAX_CIRCUIT_ASSETS: usize = 4;
+
+/// Bits each balance is range-checked to: wide enough for any channel amount, narrow enough
+/// that two balances sum below the field order, so conservation cannot hold by wrapping.
+pub const BALANCE_BITS: usize = 62;
+
+/// Balances of both parties before and after a transition, for one asset.
+type AssetSlot = ([u64; 2], [u64; 2]);
+
+/// Errors from applying, proving and verifying state transitions.
+#[derive(Error, Debug)]
+pub enum TransitionError {
+    #[error("Invalid transition: {0}")]
+    InvalidTransition(&'static str),
+    #[error("Transition does not conserve asset balances")]
+    NotConserved,
+    #[error("Balance exceeds the circuit's {}-bit range", BALANCE_BITS)]
+    BalanceOutOfRange,
+    #[error("The transition circuit supports two parties")]
+    TooManyParties,
+    #[error("Transition touches {0} assets, more than the circuit's {}", MAX_CIRCUIT_ASSETS)]
+    TooManyAssets(usize),
+    #[error("Expected 8 public inputs, got {0}")]
+    PublicInputs(usize),
+    #[error("Proof generation failed: {0}")]
+    Prover(#[source] anyhow::Error),
+    #[error("Proof verification failed: {0}")]
+    Verifier(#[source] anyhow::Error),
+}
+
 /// Represents the state transition circuit using Plonky2.
+///
+/// The public inputs are the current and next states. The current state binds the digest of
+/// the channel state to the old balances, and the next state binds the current state and the
+/// transition data to the new balances, so a proof cannot claim balances its states do not
+/// commit to.
 pub struct StateTransitionCircuit {
     circuit_data: CircuitData<GoldilocksField, PoseidonConfig, 2>,
-    current_state_target: HashOutTarget,
-    next_state_target: HashOutTarget,
+    current_digest_target: HashOutTarget,
     transition_data_target: HashOutTarget,
+    /// Old and new party balances of each asset slot; slot 0 is the native balance.
+    asset_targets: Vec<([Target; 2], [Target; 2])>,
     channel_roots: HashMap<[u8; 32], [u8; 32]>, // Changed to [u8; 32]
     merkle_tree: MerkleTree,
 }
@@ -41,147 +82,196 @@ impl StateTransitionCircuit {
         let config = CircuitConfig::standard_recursion_zk_config();
         let mut builder = CircuitBuilder::<GoldilocksField, 2>::new(config);
 
-        // Define virtual hash targets for current state, transition data, and next state.
-        let current_state_target = builder.add_virtual_hash();
+        // Define virtual hash targets for the current state's digest and the transition data.
+        let current_digest_target = builder.add_virtual_hash();
         let transition_data_target = builder.add_virtual_hash();
-        let next_state_target = builder.add_virtual_hash();
 
-        // Register current and next states as public inputs.
-        builder.register_public_inputs(&current_state_target.elements);
-        builder.register_public_inputs(&next_state_target.elements);
+        // Enforce that no transition creates or destroys any asset: per slot, both parties'
+        // balances must sum to the same total before and after, with every balance in range.
+        let asset_targets: Vec<([Target; 2], [Target; 2])> = (0..MAX_CIRCUIT_ASSETS)
+            .map(|_| {
+                let old = [builder.add_virtual_target(), builder.add_virtual_target()];
+                let new = [builder.add_virtual_target(), builder.add_virtual_target()];
+                for &balance in old.iter().chain(&new) {
+                    builder.range_check(balance, BALANCE_BITS);
+                }
+                let old_total = builder.add(old[0], old[1]);
+                let new_total = builder.add(new[0], new[1]);
+                builder.connect(old_total, new_total);
+                (old, new)
+            })
+            .collect();
 
-        // Prepare inputs for Poseidon hash: interleaving current state and transition data.
-        let inputs = current_state_target
+        // Bind the old balances into the current state.
+        let mut inputs = current_digest_target.elements.to_vec();
+        inputs.extend(asset_targets.iter().flat_map(|(old, _)| *old));
+        let current_state = builder.hash_n_to_hash_no_pad::<PoseidonHash>(inputs);
+
+        // Interleave the current state and transition data, then bind the new balances.
+        let mut inputs = current_state
             .elements
             .iter()
             .zip(transition_data_target.elements.iter())
             .flat_map(|(&c, &t)| vec![c, t])
             .collect::<Vec<_>>();
+        inputs.extend(asset_targets.iter().flat_map(|(_, new)| *new));
+        let next_state = builder.hash_n_to_hash_no_pad::<PoseidonHash>(inputs);
 
-        // Compute the next state hash using Poseidon without padding.
-        let computed_next_state = builder.hash_n_to_hash_no_pad::<PoseidonHash>(inputs);
-
-        // Enforce that the computed hash matches the declared next state.
-        for i in 0..4 {
-            builder.connect(computed_next_state.elements[i], next_state_target.elements[i]);
-        }
+        // Register current and next states as public inputs.
+        builder.register_public_inputs(&current_state.elements);
+        builder.register_public_inputs(&next_state.elements);
 
         // Finalize the circuit.
         let circuit_data = builder.build::<PoseidonConfig>();
 
         Self {
             circuit_data,
-            current_state_target,
-            next_state_target,
+            current_digest_target,
             transition_data_target,
+            asset_targets,
             channel_roots: HashMap::new(),
             merkle_tree: MerkleTree::new(),
         }
     }
 
     /// Generates a zero-knowledge proof for a state transition.
+    #[tracing::instrument(name = "prove", skip_all, fields(circuit = "state_transition"), err)]
     pub fn generate_zkp(
         &self,
         initial_state: &ChannelState,
         transition_data: &[u8; 32],
-    ) -> Result<ProofWithPublicInputs<GoldilocksField, PoseidonConfig, 2>> {
-        let mut pw = PartialWitness::new();
+    ) -> Result<ProofWithPublicInputs<GoldilocksField, PoseidonConfig, 2>, TransitionError> {
         // Compute next state by applying transition data to initial state
-        let next_state = apply_transition(initial_state, transition_data)
-            .context("Failed to apply transition to initial state")?;
-
-        // Serialize and hash the initial and next states
-        let initial_state_bytes = hash_state(&initial_state)
-            .context("Failed to hash initial state")?;
-        let next_state_bytes = hash_state(&next_state)
-            .context("Failed to hash next state")?;
-
-        // Convert byte arrays to HashOut targets.
-        let initial_hash = Self::to_hash_out(initial_state_bytes)
-            .context("Failed to convert initial hash")?;
-        let transition_hash = Self::to_hash_out(*transition_data)
-            .context("Failed to convert transition data hash")?;
-        let next_hash = Self::to_hash_out(next_state_bytes)
-            .context("Failed to convert next hash")?;
-
-        // Assign hashes to their respective targets.
-        pw.set_hash_target(self.current_state_target, initial_hash)
-            .context("Failed to set initial state hash")?;
+        let next_state = apply_transition(initial_state, transition_data)?;
+        if !next_state.conserves(initial_state) {
+            return Err(TransitionError::NotConserved);
+        }
+        let slots = asset_slots(initial_state, &next_state)?;
+        if slots
+            .iter()
+            .flat_map(|(old, new)| old.iter().chain(new))
+            .any(|balance| balance >> BALANCE_BITS != 0)
+        {
+            return Err(TransitionError::BalanceOutOfRange);
+        }
+
+        // Hash the initial state into the digest its balances are bound to.
+        let digest = hash_state(initial_state);
+        let pw = self
+            .witness(digest, transition_data, &slots)
+            .map_err(TransitionError::Prover)?;
+
+        // Generate and return the proof.
+        self.circuit_data.prove(pw).map_err(TransitionError::Prover)
+    }
+
+    /// Assigns the digest, transition data and balance slots, leaving unused slots at zero.
+    fn witness(
+        &self,
+        digest: [u8; 32],
+        transition_data: &[u8; 32],
+        slots: &[AssetSlot],
+    ) -> anyhow::Result<PartialWitness<GoldilocksField>> {
+        let mut pw = PartialWitness::new();
+        let digest = Self::to_hash_out(digest);
+        let transition_hash = Self::to_hash_out(*transition_data);
+        pw.set_hash_target(self.current_digest_target, digest)
+            .context("Failed to set state digest")?;
         pw.set_hash_target(self.transition_data_target, transition_hash)
             .context("Failed to set transition data hash")?;
-        pw.set_hash_target(self.next_state_target, next_hash)
-            .context("Failed to set next state hash")?;
 
-        // Generate and return the proof.
-        self.circuit_data.prove(pw).context("Proof generation failed")
+        for (index, (old_target, new_target)) in self.asset_targets.iter().enumerate() {
+            let (old, new) = slots.get(index).copied().unwrap_or_default();
+            for party in 0..2 {
+                pw.set_target(old_target[party], GoldilocksField::from_noncanonical_u64(old[party]))
+                    .context("Failed to set old asset balance")?;
+                pw.set_target(new_target[party], GoldilocksField::from_noncanonical_u64(new[party]))
+                    .context("Failed to set new asset balance")?;
+            }
+        }
+        Ok(pw)
     }
 
     /// Verifies a zero-knowledge proof for a state transition.
+    #[tracing::instrument(name = "verify", skip_all, fields(circuit = "state_transition"), err)]
     pub fn verify_proof(
         &self,
         proof: ProofWithPublicInputs<GoldilocksField, PoseidonConfig, 2>,
-    ) -> Result<bool> {
+    ) -> Result<bool, TransitionError> {
         self.circuit_data
             .verify(proof)
             .map(|_| true)
-            .context("Proof verification failed")
+            .map_err(TransitionError::Verifier)
     }
 
-    /// Converts a byte array to a Poseidon HashOut.
-    fn to_hash_out(data: [u8; 32]) -> Result<HashOut<GoldilocksField>, anyhow::Error> {
-        let elements = data
-            .chunks(8)
-            .map(|chunk| {
-                let bytes: [u8; 8] = chunk
-                    .try_into()
-                    .map_err(|_| anyhow!("Invalid byte length for field element"))?;
-                Ok(GoldilocksField::from_canonical_u64(u64::from_le_bytes(bytes)))
-            })
-            .collect::<Result<Vec<_>, anyhow::Error>>()?;
+    /// Reads the current and next states a proof attests to.
+    pub fn public_states(
+        proof: &ProofWithPublicInputs<GoldilocksField, PoseidonConfig, 2>,
+    ) -> Result<([u8; 32], [u8; 32]), TransitionError> {
+        if proof.public_inputs.len() != 8 {
+            return Err(TransitionError::PublicInputs(proof.public_inputs.len()));
+        }
+        let current = HashOut::from_partial(&proof.public_inputs[..4]);
+        let next = HashOut::from_partial(&proof.public_inputs[4..]);
+        Ok((Self::hash_out_to_bytes(&current), Self::hash_out_to_bytes(&next)))
+    }
 
-        Ok(HashOut::from_partial(&elements))
+    /// Converts a byte array to a Poseidon HashOut.
+    fn to_hash_out(data: [u8; 32]) -> HashOut<GoldilocksField> {
+        let mut elements = [GoldilocksField::ZERO; 4];
+        for (element, chunk) in elements.iter_mut().zip(data.chunks_exact(8)) {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(chunk);
+            *element = GoldilocksField::from_noncanonical_u64(u64::from_le_bytes(bytes));
+        }
+        HashOut { elements }
     }
 
     /// Converts a Poseidon HashOut back to a byte array.
-    fn hash_out_to_bytes(hash: &HashOut<GoldilocksField>) -> Result<[u8; 32]> {
+    fn hash_out_to_bytes(hash: &HashOut<GoldilocksField>) -> [u8; 32] {
         let mut bytes = [0u8; 32];
         for (i, &element) in hash.elements.iter().enumerate() {
-            let elem_u64 = element.to_noncanonical_u64();
+            let elem_u64 = element.to_canonical_u64();
             bytes[i * 8..(i + 1) * 8].copy_from_slice(&elem_u64.to_le_bytes());
         }
-        Ok(bytes)
+        bytes
     }
 
-    /// Computes the Poseidon hash for the next state based on current state and transition data.
-    fn compute_poseidon_hash(
-        current_hash: &HashOut<GoldilocksField>,
-        transition_hash: &HashOut<GoldilocksField>,
-    ) -> HashOut<GoldilocksField> {
-        let inputs = vec![
-            current_hash.elements[0],
-            transition_hash.elements[0],
-            current_hash.elements[1],
-            transition_hash.elements[1],
-            current_hash.elements[2],
-            transition_hash.elements[2],
-            current_hash.elements[3],
-            transition_hash.elements[3],
-        ];
+    /// Hashes `prefix` followed by each slot's party balances, as the circuit binds balances
+    /// into its states.
+    fn bind_balances(prefix: Vec<GoldilocksField>, balances: &[[u64; 2]]) -> HashOut<GoldilocksField> {
+        let mut inputs = prefix;
+        for slot in 0..MAX_CIRCUIT_ASSETS {
+            let pair = balances.get(slot).copied().unwrap_or_default();
+            inputs.extend(pair.map(GoldilocksField::from_noncanonical_u64));
+        }
         PoseidonHash::hash_no_pad(&inputs)
     }
 
-    /// Computes the next state based on current state and transition data.
+    /// Computes the current state from the channel state's digest and its balance slots.
+    pub fn compute_current_state(&self, digest: [u8; 32], balances: &[[u64; 2]]) -> [u8; 32] {
+        let digest = Self::to_hash_out(digest);
+        let current_hash = Self::bind_balances(digest.elements.to_vec(), balances);
+        Self::hash_out_to_bytes(&current_hash)
+    }
+
+    /// Computes the next state from the current state, transition data and new balance slots.
     pub fn compute_next_state(
         &self,
         current_state: [u8; 32],
         transition_data: [u8; 32],
-    ) -> Result<[u8; 32]> {
-        let current_hash = Self::to_hash_out(current_state)
-            .context("Failed to convert current state hash")?;
-        let transition_hash = Self::to_hash_out(transition_data)
-            .context("Failed to convert transition data hash")?;
-        let next_hash = Self::compute_poseidon_hash(&current_hash, &transition_hash);
-        Self::hash_out_to_bytes(&next_hash).context("Failed to convert next hash to bytes")
+        balances: &[[u64; 2]],
+    ) -> [u8; 32] {
+        let current_hash = Self::to_hash_out(current_state);
+        let transition_hash = Self::to_hash_out(transition_data);
+        let inputs = current_hash
+            .elements
+            .iter()
+            .zip(transition_hash.elements.iter())
+            .flat_map(|(&c, &t)| [c, t])
+            .collect();
+        let next_hash = Self::bind_balances(inputs, balances);
+        Self::hash_out_to_bytes(&next_hash)
     }
 
     /// Generates a Merkle proof for a channel's transaction history.
@@ -201,50 +291,83 @@ impl StateTransitionCircuit {
     }
 }
 
+/// Lays out a transition's balances as circuit slots: the native balance first, then each
+/// asset either state holds in id order, with a missing party or asset counting as zero.
+fn asset_slots(
+    initial_state: &ChannelState,
+    next_state: &ChannelState,
+) -> Result<Vec<AssetSlot>, TransitionError> {
+    fn pair(balances: Option<&[u64]>) -> Result<[u64; 2], TransitionError> {
+        match balances.unwrap_or_default() {
+            [] => Ok([0, 0]),
+            [a] => Ok([*a, 0]),
+            [a, b] => Ok([*a, *b]),
+            _ => Err(TransitionError::TooManyParties),
+        }
+    }
+
+    let assets: BTreeSet<_> = initial_state
+        .assets
+        .keys()
+        .chain(next_state.assets.keys())
+        .collect();
+    if assets.len() + 1 > MAX_CIRCUIT_ASSETS {
+        return Err(TransitionError::TooManyAssets(assets.len() + 1));
+    }
+    let mut slots = vec![(
+        pair(Some(&initial_state.balances))?,
+        pair(Some(&next_state.balances))?,
+    )];
+    for asset in assets {
+        slots.push((
+            pair(initial_state.asset_balances(asset))?,
+            pair(next_state.asset_balances(asset))?,
+        ));
+    }
+    Ok(slots)
+}
+
 /// Converts ChannelState to a 32-byte hash using PoseidonHash.
-fn hash_state(state: &ChannelState) -> Result<[u8; 32]> {
+fn hash_state(state: &ChannelState) -> [u8; 32] {
     use plonky2::hash::poseidon::PoseidonHash;
 
-    println!("Hashing state:");
-    println!("  Balances: {:?}", state.balances);
-    println!("  Nonce: {}", state.nonce);
-    println!("  Metadata length: {}", state.metadata.len());
-    println!("  Merkle Root: {:?}", state.merkle_root);
+    tracing::trace!(
+        balances = ?state.balances,
+        nonce = state.nonce,
+        metadata_len = state.metadata.len(),
+        merkle_root = %hex::encode(state.merkle_root),
+        "Hashing state"
+    );
 
     // Convert ChannelState fields to field elements
     let mut inputs = Vec::new();
 
     // Serialize balances
     for &balance in &state.balances {
-        let field_element = GoldilocksField::from_canonical_u64(balance);
-        println!("  Balance field element: {:?}", field_element);
-        inputs.push(field_element);
+        inputs.push(GoldilocksField::from_canonical_u64(balance));
     }
 
     // Serialize nonce
-    let nonce_element = GoldilocksField::from_canonical_u64(state.nonce);
-    println!("  Nonce field element: {:?}", nonce_element);
-    inputs.push(nonce_element);
+    inputs.push(GoldilocksField::from_canonical_u64(state.nonce));
 
     // Serialize metadata
     for &byte in &state.metadata {
-        let metadata_element = GoldilocksField::from_canonical_u8(byte);
-        println!("  Metadata field element: {:?}", metadata_element);
-        inputs.push(metadata_element);
+        inputs.push(GoldilocksField::from_canonical_u8(byte));
     }
 
     // Serialize merkle_root
     for &byte in &state.merkle_root {
-        let merkle_element = GoldilocksField::from_canonical_u8(byte);
-        println!("  Merkle Root field element: {:?}", merkle_element);
-        inputs.push(merkle_element);
+        inputs.push(GoldilocksField::from_canonical_u8(byte));
     }
 
-    println!("Total input elements: {}", inputs.len());
+    // Serialize asset balances
+    for (asset, balances) in &state.assets {
+        inputs.extend(asset.iter().map(|&byte| GoldilocksField::from_canonical_u8(byte)));
+        inputs.extend(balances.iter().map(|&balance| GoldilocksField::from_canonical_u64(balance)));
+    }
 
     // Compute Poseidon hash
     let hash_out = PoseidonHash::hash_no_pad(&inputs);
-    println!("Hash elements: {:?}", hash_out.elements);
 
     // Convert to bytes
     let mut bytes = [0u8; 32];
@@ -253,12 +376,15 @@ fn hash_state(state: &ChannelState) -> Result<[u8; 32]> {
         bytes[i * 8..(i + 1) * 8].copy_from_slice(&elem_u64.to_le_bytes());
     }
 
-    println!("Final hash bytes: {:?}", bytes);
-    Ok(bytes)
+    tracing::trace!(inputs = inputs.len(), hash = %hex::encode(bytes), "Hashed state");
+    bytes
 }
 
 /// Applies transition data to the initial state to produce the next state.
-fn apply_transition(initial_state: &ChannelState, transition_data: &[u8; 32]) -> Result<ChannelState> {
+fn apply_transition(
+    initial_state: &ChannelState,
+    transition_data: &[u8; 32],
+) -> Result<ChannelState, TransitionError> {
     // Example transition logic:
     // - Update balances
     // - Increment nonce
@@ -270,45 +396,38 @@ fn apply_transition(initial_state: &ChannelState, transition_data: &[u8; 32]) ->
     // - delta_nonce: i32 (4 bytes)
     // The rest of the bytes are unused.
 
-    let delta_balance_0 = i32::from_le_bytes(
-        transition_data[0..4]
-            .try_into()
-            .context("Failed to parse delta_balance_0")?,
-    );
-    let delta_balance_1 = i32::from_le_bytes(
-        transition_data[4..8]
-            .try_into()
-            .context("Failed to parse delta_balance_1")?,
-    );
-    let delta_nonce = i32::from_le_bytes(
-        transition_data[8..12]
-            .try_into()
-            .context("Failed to parse delta_nonce")?,
-    );
+    let delta = |offset: usize| {
+        let mut bytes = [0u8; 4];
+        bytes.copy_from_slice(&transition_data[offset..offset + 4]);
+        i32::from_le_bytes(bytes)
+    };
+    let delta_balance_0 = delta(0);
+    let delta_balance_1 = delta(4);
+    let delta_nonce = delta(8);
 
     // Apply deltas to balances and nonce
     let new_balance_0 = initial_state
         .balances
         .get(0)
-        .ok_or_else(|| anyhow!("Missing balance 0"))?
+        .ok_or(TransitionError::InvalidTransition("missing balance 0"))?
         .checked_add_signed(delta_balance_0 as i64)
-        .ok_or_else(|| anyhow!("Balance overflow for balance 0"))?;
+        .ok_or(TransitionError::InvalidTransition("balance 0 overflows"))?;
     let new_balance_1 = initial_state
         .balances
         .get(1)
-        .ok_or_else(|| anyhow!("Missing balance 1"))?
+        .ok_or(TransitionError::InvalidTransition("missing balance 1"))?
         .checked_add_signed(delta_balance_1 as i64)
-        .ok_or_else(|| anyhow!("Balance overflow for balance 1"))?;
+        .ok_or(TransitionError::InvalidTransition("balance 1 overflows"))?;
     let new_nonce = if delta_nonce >= 0 {
         initial_state
             .nonce
             .checked_add(delta_nonce as u64)
-            .ok_or_else(|| anyhow!("Nonce overflow"))?
+            .ok_or(TransitionError::InvalidTransition("nonce overflows"))?
     } else {
         initial_state
             .nonce
             .checked_sub((-delta_nonce) as u64)
-            .ok_or_else(|| anyhow!("Nonce underflow"))?
+            .ok_or(TransitionError::InvalidTransition("nonce underflows"))?
     };
 
  
@@ -319,11 +438,11 @@ fn apply_transition(initial_state: &ChannelState, transition_data: &[u8; 32]) ->
         metadata: initial_state.metadata.clone(),
         merkle_root: [0u8; 32], // Placeholder, will be updated after hashing
         proof: initial_state.proof.clone(),
+        assets: initial_state.assets.clone(),
     };
 
     // Compute the new merkle_root based on the updated state
-    new_state.merkle_root = hash_state(&new_state)
-        .context("Failed to compute new merkle_root")?;
+    new_state.merkle_root = hash_state(&new_state);
 
     Ok(new_state)
 }
@@ -331,11 +450,15 @@ fn apply_transition(initial_state: &ChannelState, transition_data: &[u8; 32]) ->
 #[cfg(test)]
 mod tests {
     use super::*;
-    use anyhow::Result;
+    use anyhow::{anyhow, Result};
+    #[cfg(feature = "bitcoin-backend")]
     use bitcoincore_rpc::{Auth, Client, RpcApi};
+    #[cfg(feature = "bitcoin-backend")]
     use bitcoin::Network;
+    use std::collections::BTreeMap;
 
     #[test]
+    #[cfg(feature = "bitcoin-backend")]
     fn test_e2e_integration() -> Result<()> {
         println!("\n=== Starting E2E Integration Test ===\n");
 
@@ -375,6 +498,7 @@ mod tests {
             metadata: Vec::<u8>::new(),
             merkle_root: [0u8; 32],   // Placeholder value
             proof: None,
+            assets: BTreeMap::new(),
         };
         println!("Initial state created: {:?}", initial_state);
 
@@ -399,10 +523,8 @@ mod tests {
         // Compute state hashes
         println!("\n=== Computing State Hashes ===\n");
 
-        let initial_state_bytes = hash_state(&initial_state)
-            .context("Failed to hash initial state")?;
-        let next_state_bytes = hash_state(&next_state)
-            .context("Failed to hash next state")?;
+        let initial_state_bytes = hash_state(&initial_state);
+        let next_state_bytes = hash_state(&next_state);
 
         println!("Initial State Hash: {:?}", initial_state_bytes);
         println!("Next State Hash: {:?}", next_state_bytes);
@@ -448,7 +570,101 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_transitions_lay_out_asset_slots() -> Result<()> {
+        let mut initial_state = ChannelState {
+            balances: vec![100, 50],
+            nonce: 0,
+            metadata: Vec::new(),
+            merkle_root: [0u8; 32],
+            proof: None,
+            assets: BTreeMap::from([([1u8; 32], vec![10, 5])]),
+        };
+        let mut transition_data = [0u8; 32];
+        transition_data[0..4].copy_from_slice(&(-3i32).to_le_bytes());
+        transition_data[4..8].copy_from_slice(&3i32.to_le_bytes());
+        transition_data[8..12].copy_from_slice(&1i32.to_le_bytes());
+
+        let next_state = apply_transition(&initial_state, &transition_data)?;
+        assert!(next_state.conserves(&initial_state));
+        assert_eq!(
+            asset_slots(&initial_state, &next_state)?,
+            vec![([100, 50], [97, 53]), ([10, 5], [10, 5])]
+        );
+
+        let mut minting = transition_data;
+        minting[4..8].copy_from_slice(&4i32.to_le_bytes());
+        assert!(!apply_transition(&initial_state, &minting)?.conserves(&initial_state));
+
+        for asset in 2..=MAX_CIRCUIT_ASSETS as u8 {
+            initial_state.assets.insert([asset; 32], vec![1, 1]);
+        }
+        let next_state = apply_transition(&initial_state, &transition_data)?;
+        assert!(matches!(
+            asset_slots(&initial_state, &next_state),
+            Err(TransitionError::TooManyAssets(5))
+        ));
+        Ok(())
+    }
+
+    /// Whether `witness` yields a proof the circuit accepts, counting a prover that refuses to
+    /// prove an unsatisfiable witness as rejecting it.
+    fn accepts(circuit: &StateTransitionCircuit, witness: PartialWitness<GoldilocksField>) -> bool {
+        std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| circuit.circuit_data.prove(witness)))
+            .ok()
+            .and_then(Result::ok)
+            .is_some_and(|proof| circuit.verify_proof(proof).is_ok())
+    }
+
+    #[test]
+    fn test_circuit_binds_and_range_checks_balances() -> Result<()> {
+        let circuit = StateTransitionCircuit::new();
+        let initial_state = ChannelState {
+            balances: vec![100, 50],
+            nonce: 0,
+            metadata: Vec::new(),
+            merkle_root: [0u8; 32],
+            proof: None,
+            assets: BTreeMap::from([([1u8; 32], vec![10, 5])]),
+        };
+        let mut transition_data = [0u8; 32];
+        transition_data[0..4].copy_from_slice(&(-3i32).to_le_bytes());
+        transition_data[4..8].copy_from_slice(&3i32.to_le_bytes());
+
+        let proof = circuit.generate_zkp(&initial_state, &transition_data)?;
+        let (current, next) = StateTransitionCircuit::public_states(&proof)?;
+        assert!(circuit.verify_proof(proof)?);
+        let digest = hash_state(&initial_state);
+        assert_eq!(current, circuit.compute_current_state(digest, &[[100, 50], [10, 5]]));
+        assert_eq!(
+            next,
+            circuit.compute_next_state(current, transition_data, &[[97, 53], [10, 5]])
+        );
+
+        // A prover swapping in other conserving balances proves a different next state.
+        let swapped = [([100, 50], [50, 100]), ([10, 5], [10, 5])];
+        let proof = circuit
+            .circuit_data
+            .prove(circuit.witness(digest, &transition_data, &swapped)?)?;
+        assert_ne!(StateTransitionCircuit::public_states(&proof)?.1, next);
+
+        // Minting, and balancing the books with a wrapped "negative" balance, are both rejected.
+        let minted = [([100, 50], [97, 54])];
+        assert!(!accepts(&circuit, circuit.witness(digest, &transition_data, &minted)?));
+        let wrapped = [([100, 50], [GoldilocksField::NEG_ONE.to_canonical_u64(), 151])];
+        assert!(!accepts(&circuit, circuit.witness(digest, &transition_data, &wrapped)?));
+
+        let mut oversized = initial_state.clone();
+        oversized.balances = vec![1 << BALANCE_BITS, 0];
+        assert!(matches!(
+            circuit.generate_zkp(&oversized, &[0u8; 32]),
+            Err(TransitionError::BalanceOutOfRange)
+        ));
+        Ok(())
+    }
+
     /// Builds an OP_RETURN transaction embedding the provided data.
+    #[cfg(feature = "bitcoin-backend")]
     fn build_op_return_transaction(client: &Client, data: [u8; 32]) -> Result<String> {  
         // Define a reasonable fee (e.g., 1,000 satoshis)
         let fee = 1_000;
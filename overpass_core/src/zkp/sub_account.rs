@@ -0,0 +1,289 @@
+// src/zkp/sub_account.rs
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use thiserror::Error;
+
+use crate::services::overpass_db::OverpassDB;
+use crate::zkp::helpers::{compute_merkle_root, merkle_path, Bytes32};
+
+const ACCOUNTS_PREFIX: &[u8] = b"sub_accounts:";
+pub const MAX_LABEL_LEN: usize = 64;
+
+/// An account's label, `None` for the default account, and its channel leaves.
+type AccountLeaves = (Option<String>, Vec<(Bytes32, Bytes32)>);
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum AccountError {
+    #[error("Unknown account {0:?}")]
+    UnknownAccount(String),
+    #[error("Account {0:?} already exists")]
+    DuplicateAccount(String),
+    #[error("Account labels must be 1 to {MAX_LABEL_LEN} bytes")]
+    InvalidLabel,
+    #[error("Storage error: {0}")]
+    StorageError(String),
+    #[error("Corrupt account state: {0}")]
+    CorruptState(String),
+}
+
+/// Labeled sub-accounts of a wallet and the channels assigned to each.
+///
+/// Channels in no labeled account belong to the default account. Assignments outlive
+/// the channel's close so closed balances still count towards their account.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubAccounts {
+    accounts: BTreeMap<String, BTreeSet<Bytes32>>,
+}
+
+impl SubAccounts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn create(&mut self, label: &str) -> Result<(), AccountError> {
+        if label.is_empty() || label.len() > MAX_LABEL_LEN {
+            return Err(AccountError::InvalidLabel);
+        }
+        if self.accounts.contains_key(label) {
+            return Err(AccountError::DuplicateAccount(label.to_string()));
+        }
+        self.accounts.insert(label.to_string(), BTreeSet::new());
+        Ok(())
+    }
+
+    /// Gets the labels of every account, in order.
+    pub fn labels(&self) -> impl Iterator<Item = &str> {
+        self.accounts.keys().map(String::as_str)
+    }
+
+    pub fn contains(&self, label: &str) -> bool {
+        self.accounts.contains_key(label)
+    }
+
+    /// Moves a channel into the labeled account, or into the default account for `None`.
+    pub fn assign(&mut self, channel_id: Bytes32, label: Option<&str>) -> Result<(), AccountError> {
+        if let Some(label) = label.filter(|label| !self.accounts.contains_key(*label)) {
+            return Err(AccountError::UnknownAccount(label.to_string()));
+        }
+        for channels in self.accounts.values_mut() {
+            channels.remove(&channel_id);
+        }
+        if let Some(channels) = label.and_then(|label| self.accounts.get_mut(label)) {
+            channels.insert(channel_id);
+        }
+        Ok(())
+    }
+
+    /// Gets the label of the account holding a channel, `None` for the default account.
+    pub fn account_of(&self, channel_id: &Bytes32) -> Option<&str> {
+        self.accounts
+            .iter()
+            .find(|(_, channels)| channels.contains(channel_id))
+            .map(|(label, _)| label.as_str())
+    }
+
+    /// Splits channel leaves, ordered by channel id, into the per-account tree.
+    pub fn tree(&self, leaves: &[(Bytes32, Bytes32)]) -> AccountTree {
+        let mut accounts: Vec<AccountLeaves> = Vec::new();
+        accounts.push((None, Vec::new()));
+        accounts.extend(
+            self.accounts
+                .keys()
+                .map(|label| (Some(label.clone()), Vec::new())),
+        );
+        for leaf in leaves {
+            let position = match self.account_of(&leaf.0) {
+                Some(label) => 1 + self.accounts.keys().position(|l| l == label).unwrap_or(0),
+                None => 0,
+            };
+            accounts[position].1.push(*leaf);
+        }
+        AccountTree { accounts }
+    }
+
+    /// Writes the accounts to `db` under `wallet_id`.
+    pub fn save(&self, db: &OverpassDB, wallet_id: &Bytes32) -> Result<(), AccountError> {
+        let bytes =
+            bincode::serialize(self).map_err(|e| AccountError::StorageError(e.to_string()))?;
+        db.put(&accounts_key(wallet_id), &bytes)
+            .and_then(|_| db.flush())
+            .map_err(|e| AccountError::StorageError(e.to_string()))
+    }
+
+    /// Loads the accounts saved for `wallet_id`, or none if none were.
+    pub fn restore(db: &OverpassDB, wallet_id: &Bytes32) -> Result<Self, AccountError> {
+        match db
+            .get(&accounts_key(wallet_id))
+            .map_err(|e| AccountError::StorageError(e.to_string()))?
+        {
+            Some(bytes) => {
+                bincode::deserialize(&bytes).map_err(|e| AccountError::CorruptState(e.to_string()))
+            }
+            None => Ok(Self::new()),
+        }
+    }
+}
+
+/// A wallet tree with one subtree per account: the default account first, then the
+/// labeled ones by label.
+///
+/// The wallet root is the Merkle root over the account sub-roots. A wallet without
+/// labeled accounts has the default sub-root alone, which is the root over its channels.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AccountTree {
+    accounts: Vec<AccountLeaves>,
+}
+
+impl AccountTree {
+    fn sub_roots(&self) -> Vec<Bytes32> {
+        self.accounts
+            .iter()
+            .map(|(_, leaves)| compute_merkle_root(leaves.iter().map(|(_, hash)| *hash).collect()))
+            .collect()
+    }
+
+    pub fn root(&self) -> Bytes32 {
+        compute_merkle_root(self.sub_roots())
+    }
+
+    /// Gets the root over one account's channels, `None` naming the default account.
+    pub fn sub_root(&self, label: Option<&str>) -> Option<Bytes32> {
+        let position = self
+            .accounts
+            .iter()
+            .position(|(account, _)| account.as_deref() == label)?;
+        Some(self.sub_roots()[position])
+    }
+
+    /// Gets every channel leaf, account by account.
+    pub fn leaves(&self) -> impl Iterator<Item = &(Bytes32, Bytes32)> {
+        self.accounts.iter().flat_map(|(_, leaves)| leaves)
+    }
+
+    /// Gets a channel's leaf index and the siblings from its leaf up to the wallet root.
+    pub fn path(&self, channel_id: &Bytes32) -> Option<(u64, Vec<Bytes32>)> {
+        let (account, index) = self
+            .accounts
+            .iter()
+            .enumerate()
+            .find_map(|(account, entry)| {
+                let index = entry.1.iter().position(|(id, _)| id == channel_id)?;
+                Some((account, index))
+            })?;
+        let hashes = self.accounts[account]
+            .1
+            .iter()
+            .map(|(_, hash)| *hash)
+            .collect();
+        let mut siblings = merkle_path(hashes, index);
+        let depth = siblings.len();
+        siblings.extend(merkle_path(self.sub_roots(), account));
+        Some((((account as u64) << depth) | index as u64, siblings))
+    }
+}
+
+fn accounts_key(wallet_id: &Bytes32) -> Vec<u8> {
+    let mut key = ACCOUNTS_PREFIX.to_vec();
+    key.extend_from_slice(hex::encode(wallet_id).as_bytes());
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+    use crate::zkp::helpers::verify_merkle_path;
+    use crate::zkp::wallet_contract::WalletContractError;
+
+    fn leaves(count: u8) -> Vec<(Bytes32, Bytes32)> {
+        (0..count).map(|i| ([i; 32], [i + 100; 32])).collect()
+    }
+
+    #[test]
+    fn test_tree_rolls_up_sub_roots() {
+        let mut accounts = SubAccounts::new();
+        let leaves = leaves(5);
+        let flat = compute_merkle_root(leaves.iter().map(|(_, hash)| *hash).collect());
+        assert_eq!(accounts.tree(&leaves).root(), flat);
+
+        accounts.create("business").unwrap();
+        accounts.create("savings").unwrap();
+        assert_eq!(
+            accounts.create("business"),
+            Err(AccountError::DuplicateAccount("business".into()))
+        );
+        assert_eq!(accounts.create(""), Err(AccountError::InvalidLabel));
+        accounts.assign([1u8; 32], Some("business")).unwrap();
+        accounts.assign([3u8; 32], Some("business")).unwrap();
+        accounts.assign([4u8; 32], Some("savings")).unwrap();
+        accounts.assign([4u8; 32], None).unwrap();
+        assert_eq!(
+            accounts.assign([4u8; 32], Some("travel")),
+            Err(AccountError::UnknownAccount("travel".into()))
+        );
+        assert_eq!(accounts.account_of(&[3u8; 32]), Some("business"));
+        assert_eq!(accounts.account_of(&[4u8; 32]), None);
+
+        let tree = accounts.tree(&leaves);
+        let business = compute_merkle_root(vec![[101u8; 32], [103u8; 32]]);
+        assert_eq!(tree.sub_root(Some("business")), Some(business));
+        assert_eq!(tree.sub_root(Some("savings")), Some([0u8; 32]));
+        assert_eq!(
+            tree.root(),
+            compute_merkle_root(vec![
+                compute_merkle_root(vec![[100u8; 32], [102u8; 32], [104u8; 32]]),
+                business,
+                [0u8; 32],
+            ])
+        );
+        for (channel_id, hash) in &leaves {
+            let (index, siblings) = tree.path(channel_id).unwrap();
+            assert!(verify_merkle_path(*hash, index, &siblings, &tree.root()));
+        }
+        assert_eq!(tree.path(&[9u8; 32]), None);
+    }
+
+    #[test]
+    fn test_wallet_accounts() -> Result<(), WalletContractError> {
+        let mut wallet = test_util::wallet([1u8; 32]);
+        wallet.create_account("business")?;
+        let personal = wallet.open_channel(100, [7u8; 32], Vec::new())?;
+        let shop = wallet.open_channel(40, [8u8; 32], Vec::new())?;
+        let supplier = wallet.open_channel(25, [9u8; 32], Vec::new())?;
+        let flat = wallet.get_merkle_root();
+        wallet.assign_channel(&shop, Some("business"))?;
+        wallet.assign_channel(&supplier, Some("business"))?;
+        assert_ne!(wallet.get_merkle_root(), flat);
+        assert!(matches!(
+            wallet.assign_channel(&[0u8; 32], Some("business")),
+            Err(WalletContractError::ChannelNotFound)
+        ));
+
+        assert_eq!(wallet.account_balance(None)?.open, 100);
+        assert_eq!(wallet.account_balance(Some("business"))?.open, 65);
+        for channel_id in [personal, shop, supplier] {
+            assert!(wallet
+                .channel_inclusion_proof(&channel_id)?
+                .verify(&wallet.get_merkle_root()));
+        }
+        wallet.repair_against_global(1)?;
+        assert!(wallet.validate_against_global()?.is_consistent());
+
+        wallet.close_channel(&supplier)?;
+        let balance = wallet.account_balance(Some("business"))?;
+        assert_eq!(
+            (balance.open, balance.closed, balance.open_channels),
+            (40, 25, 1)
+        );
+        assert_ne!(wallet.account_root(Some("business"))?, [0u8; 32]);
+        assert!(wallet.account_history(Some("business"))?.is_empty());
+        assert!(matches!(
+            wallet.account_balance(Some("travel")),
+            Err(WalletContractError::AccountError(
+                AccountError::UnknownAccount(_)
+            ))
+        ));
+        Ok(())
+    }
+}
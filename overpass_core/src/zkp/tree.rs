@@ -90,14 +90,15 @@ impl MerkleTree {
     }
     /// Incrementally updates the tree upon inserting a new leaf.
     fn update_tree_on_insert(&mut self) -> Result<(), MerkleTreeError> {
-        let mut level = self.tree.len();
-        if level == 0 {
+        if self.tree.is_empty() {
             self.tree.push(self.leaves.clone());
-            level = 1;
         } else {
             self.tree[0] = self.leaves.clone();
         }
 
+        // Walk up from the leaves, so each level above takes the new leaf's hash.
+        let mut level = 1;
+
         let mut pos = self.leaves.len() - 1;
         while level < self.tree.len() || self.tree[level-1].len() > 1 {
             let current_level = &self.tree[level-1];
@@ -180,12 +181,11 @@ impl MerkleTree {
             return Ok(());
         }
 
-        // Update the base level
-        self.tree[0] = self.leaves.clone();
-
-        // Recompute each level
-        let mut current_level = self.tree[0].clone();
-        for level in 1..self.tree.len() {
+        // Rebuild every level, dropping those the smaller tree no longer needs so that
+        // later inserts and updates see a level count matching the leaves.
+        self.tree = vec![self.leaves.clone()];
+        let mut current_level = self.leaves.clone();
+        while current_level.len() > 1 {
             let mut next_level = Vec::new();
             
             for chunk in current_level.chunks(2) {
@@ -196,7 +196,7 @@ impl MerkleTree {
                 }
             }
             
-            self.tree[level] = next_level.clone();
+            self.tree.push(next_level.clone());
             current_level = next_level;
         }
 
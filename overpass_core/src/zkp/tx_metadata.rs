@@ -0,0 +1,239 @@
+// src/zkp/tx_metadata.rs
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::zkp::fee_ledger::FeeKind;
+use crate::zkp::helpers::Bytes32;
+
+const METADATA_DOMAIN: &[u8] = b"overpass/tx-metadata/v1";
+pub const MAX_MEMO_LEN: usize = 256;
+pub const MAX_SCHEMA_LEN: usize = 64;
+pub const MAX_CUSTOM_LEN: usize = 512;
+/// Limit on the encoding of any metadata, whatever its kind.
+pub const MAX_ENCODED_LEN: usize = 1024;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum MetadataError {
+    #[error("{field} is {len} bytes, over the limit of {max}")]
+    TooLarge {
+        field: &'static str,
+        len: usize,
+        max: usize,
+    },
+    #[error("{0} must not be empty")]
+    Empty(&'static str),
+    #[error("{0} contains control characters")]
+    ControlCharacters(&'static str),
+    #[error("Custom schema {0:?} is not lowercase ASCII letters, digits, '-', '_', '.' and '/'")]
+    InvalidSchema(String),
+    #[error("{0} amount must be positive")]
+    ZeroAmount(&'static str),
+    #[error("Failed to encode metadata: {0}")]
+    Encoding(String),
+}
+
+/// What a stored transaction was for, hashed into its compressed record.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TxMetadata {
+    /// A balance update with nothing more to record.
+    #[default]
+    None,
+    Payment {
+        payment_id: Bytes32,
+        counterparty: Bytes32,
+        amount: u64,
+        routing_fee: u64,
+        memo: Option<String>,
+        deadline: Option<u64>,
+    },
+    /// A payment settling an invoice, referenced by its payment hash.
+    Invoice {
+        payment_id: Bytes32,
+        payment_hash: Bytes32,
+        amount: u64,
+        memo: Option<String>,
+    },
+    /// Funds returned for an earlier payment.
+    Refund {
+        payment_id: Bytes32,
+        amount: u64,
+        reason: Option<String>,
+    },
+    Fee {
+        kind: FeeKind,
+        amount: u64,
+        reference: Option<Bytes32>,
+    },
+    KeyRotation {
+        generation: u32,
+    },
+    /// Application-defined metadata, named by a schema such as `"shop/order-v1"`.
+    Custom {
+        schema: String,
+        payload: Vec<u8>,
+    },
+}
+
+impl TxMetadata {
+    /// Checks the fields of the metadata against its kind's schema and the size limits.
+    pub fn validate(&self) -> Result<(), MetadataError> {
+        match self {
+            Self::None | Self::KeyRotation { .. } => {}
+            Self::Payment { memo, .. } | Self::Invoice { memo, .. } => {
+                check_text("memo", memo.as_deref())?
+            }
+            Self::Refund { amount, reason, .. } => {
+                check_text("reason", reason.as_deref())?;
+                check_amount("Refund", *amount)?;
+            }
+            Self::Fee { amount, .. } => check_amount("Fee", *amount)?,
+            Self::Custom { schema, payload } => {
+                if schema.is_empty() {
+                    return Err(MetadataError::Empty("schema"));
+                }
+                check_len("schema", schema.len(), MAX_SCHEMA_LEN)?;
+                let valid =
+                    |c: char| c.is_ascii_lowercase() || c.is_ascii_digit() || "-_./".contains(c);
+                if !schema.chars().all(valid) {
+                    return Err(MetadataError::InvalidSchema(schema.clone()));
+                }
+                check_len("payload", payload.len(), MAX_CUSTOM_LEN)?;
+            }
+        }
+        check_len("metadata", self.encode()?.len(), MAX_ENCODED_LEN)
+    }
+
+    /// Encodes the metadata canonically, as it is hashed.
+    pub fn encode(&self) -> Result<Vec<u8>, MetadataError> {
+        bincode::serialize(self).map_err(|e| MetadataError::Encoding(e.to_string()))
+    }
+
+    /// Validates the metadata and hashes its encoding.
+    pub fn hash(&self) -> Result<Bytes32, MetadataError> {
+        self.validate()?;
+        Ok(Sha256::new()
+            .chain_update(METADATA_DOMAIN)
+            .chain_update(self.encode()?)
+            .finalize()
+            .into())
+    }
+}
+
+fn check_len(field: &'static str, len: usize, max: usize) -> Result<(), MetadataError> {
+    if len > max {
+        return Err(MetadataError::TooLarge { field, len, max });
+    }
+    Ok(())
+}
+
+fn check_text(field: &'static str, text: Option<&str>) -> Result<(), MetadataError> {
+    let Some(text) = text else {
+        return Ok(());
+    };
+    check_len(field, text.len(), MAX_MEMO_LEN)?;
+    if text.chars().any(char::is_control) {
+        return Err(MetadataError::ControlCharacters(field));
+    }
+    Ok(())
+}
+
+fn check_amount(kind: &'static str, amount: u64) -> Result<(), MetadataError> {
+    if amount == 0 {
+        return Err(MetadataError::ZeroAmount(kind));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+    use crate::zkp::wallet_contract::WalletContractError;
+
+    fn custom(schema: &str, payload: usize) -> TxMetadata {
+        TxMetadata::Custom {
+            schema: schema.into(),
+            payload: vec![7u8; payload],
+        }
+    }
+
+    #[test]
+    fn test_metadata_is_checked_before_hashing() {
+        let refund = TxMetadata::Refund {
+            payment_id: [1u8; 32],
+            amount: 40,
+            reason: Some("duplicate order".into()),
+        };
+        let decoded: TxMetadata = bincode::deserialize(&refund.encode().unwrap()).unwrap();
+        assert_eq!(decoded, refund);
+        assert_eq!(refund.hash(), decoded.hash());
+        assert_ne!(refund.hash(), TxMetadata::None.hash());
+        assert!(custom("shop/order-v1", MAX_CUSTOM_LEN).hash().is_ok());
+
+        assert_eq!(
+            custom("shop", MAX_CUSTOM_LEN + 1).hash(),
+            Err(MetadataError::TooLarge {
+                field: "payload",
+                len: MAX_CUSTOM_LEN + 1,
+                max: MAX_CUSTOM_LEN
+            })
+        );
+        assert_eq!(
+            custom("", 1).validate(),
+            Err(MetadataError::Empty("schema"))
+        );
+        assert_eq!(
+            custom("Shop Order", 1).validate(),
+            Err(MetadataError::InvalidSchema("Shop Order".into()))
+        );
+        let memo = |memo: String| TxMetadata::Invoice {
+            payment_id: [1u8; 32],
+            payment_hash: [2u8; 32],
+            amount: 10,
+            memo: Some(memo),
+        };
+        assert!(memo("x".repeat(MAX_MEMO_LEN)).validate().is_ok());
+        assert!(matches!(
+            memo("x".repeat(MAX_MEMO_LEN + 1)).validate(),
+            Err(MetadataError::TooLarge { field: "memo", .. })
+        ));
+        assert_eq!(
+            memo("line\nbreak".into()).validate(),
+            Err(MetadataError::ControlCharacters("memo"))
+        );
+        let fee = TxMetadata::Fee {
+            kind: FeeKind::Routing,
+            amount: 0,
+            reference: None,
+        };
+        assert_eq!(fee.validate(), Err(MetadataError::ZeroAmount("Fee")));
+    }
+
+    #[test]
+    fn test_wallet_rejects_oversized_memo() -> Result<(), WalletContractError> {
+        let mut wallet = test_util::wallet([1u8; 32]);
+        let channel_id = wallet.open_channel(100, [7u8; 32], Vec::new())?;
+        assert!(matches!(
+            wallet
+                .pay([7u8; 32], 10)
+                .with_memo("x".repeat(MAX_MEMO_LEN + 1))
+                .send(),
+            Err(WalletContractError::MetadataError(
+                MetadataError::TooLarge { field: "memo", .. }
+            ))
+        ));
+        let channel = wallet.get_channel(&channel_id).unwrap();
+        assert_eq!((channel.nonce, channel.balances[0]), (0, 100));
+        assert!(wallet.storage.history(&channel_id).is_empty());
+
+        wallet.rotate_keys()?;
+        let rotation = TxMetadata::KeyRotation { generation: 1 }.hash().unwrap();
+        assert_eq!(
+            wallet.storage.history(&channel_id)[0].metadata_hash,
+            rotation
+        );
+        Ok(())
+    }
+}
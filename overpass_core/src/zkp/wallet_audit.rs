@@ -0,0 +1,308 @@
+// src/zkp/wallet_audit.rs
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+use crate::services::overpass_db::OverpassDB;
+use crate::zkp::helpers::Bytes32;
+use crate::zkp::spending_policy::SpendingPolicy;
+
+const AUDIT_PREFIX: &[u8] = b"wallet_audit:";
+const RECORD_DOMAIN: &[u8] = b"overpass/wallet-audit/v1";
+const COMMIT_DOMAIN: &[u8] = b"overpass/wallet-audit-root/v1";
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum AuditError {
+    #[error("Audit record {0} does not chain to the one before it")]
+    Tampered(u64),
+    #[error("Storage error: {0}")]
+    StorageError(String),
+    #[error("Corrupt audit log: {0}")]
+    CorruptState(String),
+    #[error("Failed to encode audit event: {0}")]
+    Encoding(String),
+}
+
+/// A wallet-level operation, as recorded in the audit log.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WalletEvent {
+    ChannelOpened {
+        channel_id: Bytes32,
+        counterparty: Bytes32,
+        balance: u64,
+    },
+    ChannelClosed {
+        channel_id: Bytes32,
+        balance: u64,
+    },
+    PaymentSent {
+        payment_id: Bytes32,
+        channel_id: Bytes32,
+        amount: u64,
+    },
+    /// A channel update that raised the wallet's own balance.
+    PaymentReceived {
+        channel_id: Bytes32,
+        amount: u64,
+    },
+    /// The spending policy's rules, with counterparty limits ordered so the record hashes
+    /// the same after a round trip.
+    PolicyChanged {
+        daily_limit: Option<u64>,
+        counterparty_limits: BTreeMap<Bytes32, u64>,
+        confirm_above: Option<u64>,
+    },
+    KeyRotated {
+        channel_id: Bytes32,
+        generation: u32,
+    },
+}
+
+impl WalletEvent {
+    pub fn policy_changed(policy: &SpendingPolicy) -> Self {
+        Self::PolicyChanged {
+            daily_limit: policy.daily_limit,
+            counterparty_limits: policy.counterparty_limits.clone().into_iter().collect(),
+            confirm_above: policy.confirm_above,
+        }
+    }
+}
+
+/// An event with its place in the chain: `hash` covers the record and `prev`, the hash
+/// of the record before it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub sequence: u64,
+    pub timestamp: u64,
+    pub event: WalletEvent,
+    pub prev: Bytes32,
+    pub hash: Bytes32,
+}
+
+impl AuditRecord {
+    fn compute_hash(&self) -> Result<Bytes32, AuditError> {
+        let event =
+            bincode::serialize(&self.event).map_err(|e| AuditError::Encoding(e.to_string()))?;
+        Ok(Sha256::new()
+            .chain_update(RECORD_DOMAIN)
+            .chain_update(self.prev)
+            .chain_update(self.sequence.to_le_bytes())
+            .chain_update(self.timestamp.to_le_bytes())
+            .chain_update(event)
+            .finalize()
+            .into())
+    }
+}
+
+/// Append-only, hash-chained history of a wallet's operations.
+///
+/// Editing, dropping or reordering any record changes the head, so a commitment to the
+/// head taken earlier exposes any rewrite of the history before it.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditLog {
+    records: Vec<AuditRecord>,
+}
+
+impl AuditLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends an event and returns the new head.
+    pub fn append(&mut self, event: WalletEvent, timestamp: u64) -> Result<Bytes32, AuditError> {
+        let mut record = AuditRecord {
+            sequence: self.records.len() as u64,
+            timestamp,
+            event,
+            prev: self.head(),
+            hash: [0u8; 32],
+        };
+        record.hash = record.compute_hash()?;
+        self.records.push(record);
+        Ok(self.head())
+    }
+
+    pub fn records(&self) -> &[AuditRecord] {
+        &self.records
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Gets the hash of the latest record, or zero for an empty log.
+    pub fn head(&self) -> Bytes32 {
+        self.records.last().map_or([0u8; 32], |record| record.hash)
+    }
+
+    /// Checks every record's hash and its link to the record before it.
+    pub fn verify(&self) -> Result<(), AuditError> {
+        let mut prev = [0u8; 32];
+        for (sequence, record) in self.records.iter().enumerate() {
+            let sequence = sequence as u64;
+            if record.sequence != sequence
+                || record.prev != prev
+                || record.compute_hash()? != record.hash
+            {
+                return Err(AuditError::Tampered(sequence));
+            }
+            prev = record.hash;
+        }
+        Ok(())
+    }
+
+    /// Commits the head and length of the log together with a wallet root.
+    pub fn commit(&self, wallet_root: &Bytes32) -> Bytes32 {
+        Sha256::new()
+            .chain_update(COMMIT_DOMAIN)
+            .chain_update(wallet_root)
+            .chain_update(self.head())
+            .chain_update((self.records.len() as u64).to_le_bytes())
+            .finalize()
+            .into()
+    }
+
+    /// Writes the log to `db` under `wallet_id`.
+    pub fn save(&self, db: &OverpassDB, wallet_id: &Bytes32) -> Result<(), AuditError> {
+        let bytes =
+            bincode::serialize(self).map_err(|e| AuditError::StorageError(e.to_string()))?;
+        db.put(&audit_key(wallet_id), &bytes)
+            .and_then(|_| db.flush())
+            .map_err(|e| AuditError::StorageError(e.to_string()))
+    }
+
+    /// Loads and verifies the log saved for `wallet_id`, or an empty log if none was.
+    pub fn restore(db: &OverpassDB, wallet_id: &Bytes32) -> Result<Self, AuditError> {
+        let log: Self = match db
+            .get(&audit_key(wallet_id))
+            .map_err(|e| AuditError::StorageError(e.to_string()))?
+        {
+            Some(bytes) => {
+                bincode::deserialize(&bytes).map_err(|e| AuditError::CorruptState(e.to_string()))?
+            }
+            None => Self::new(),
+        };
+        log.verify()?;
+        Ok(log)
+    }
+}
+
+fn audit_key(wallet_id: &Bytes32) -> Vec<u8> {
+    let mut key = AUDIT_PREFIX.to_vec();
+    key.extend_from_slice(hex::encode(wallet_id).as_bytes());
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+    use crate::zkp::wallet_contract::WalletContractError;
+
+    fn opened(balance: u64) -> WalletEvent {
+        WalletEvent::ChannelOpened {
+            channel_id: [1u8; 32],
+            counterparty: [2u8; 32],
+            balance,
+        }
+    }
+
+    #[test]
+    fn test_chain_detects_rewrites() -> anyhow::Result<()> {
+        let mut log = AuditLog::new();
+        assert_eq!(log.head(), [0u8; 32]);
+        log.append(opened(100), 10)?;
+        let head = log.append(
+            WalletEvent::PaymentReceived {
+                channel_id: [1u8; 32],
+                amount: 5,
+            },
+            20,
+        )?;
+        log.append(WalletEvent::policy_changed(&SpendingPolicy::default()), 30)?;
+        assert_eq!(log.records()[2].prev, head);
+        assert_eq!(log.verify(), Ok(()));
+        let commitment = log.commit(&[9u8; 32]);
+
+        let mut edited = log.clone();
+        edited.records[0].event = opened(1_000);
+        assert_eq!(edited.verify(), Err(AuditError::Tampered(0)));
+        let mut dropped = log.clone();
+        dropped.records.remove(1);
+        assert_eq!(dropped.verify(), Err(AuditError::Tampered(1)));
+        let mut truncated = log.clone();
+        truncated.records.pop();
+        assert_eq!(truncated.verify(), Ok(()));
+        assert_ne!(truncated.commit(&[9u8; 32]), commitment);
+
+        let path = std::env::temp_dir().join(format!("wallet_audit_{}", std::process::id()));
+        let db = OverpassDB::new(path.to_str().unwrap())?;
+        assert!(AuditLog::restore(&db, &[1u8; 32])?.is_empty());
+        log.save(&db, &[1u8; 32])?;
+        assert_eq!(AuditLog::restore(&db, &[1u8; 32])?, log);
+        edited.save(&db, &[2u8; 32])?;
+        assert_eq!(
+            AuditLog::restore(&db, &[2u8; 32]),
+            Err(AuditError::Tampered(0))
+        );
+        drop(db);
+        let _ = std::fs::remove_dir_all(path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_wallet_records_operations() -> Result<(), WalletContractError> {
+        let mut wallet = test_util::wallet([1u8; 32]);
+        let channel_id = wallet.open_channel(100, [7u8; 32], Vec::new())?;
+        let before = wallet.audited_root();
+        wallet.rotate_keys()?;
+        wallet.set_spending_policy(SpendingPolicy {
+            daily_limit: Some(50),
+            ..SpendingPolicy::default()
+        })?;
+        wallet.close_channel(&channel_id)?;
+        assert_ne!(wallet.audited_root(), before);
+
+        let events: Vec<_> = wallet
+            .audit_log()
+            .records()
+            .iter()
+            .map(|record| record.event.clone())
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                WalletEvent::ChannelOpened {
+                    channel_id,
+                    counterparty: [7u8; 32],
+                    balance: 100,
+                },
+                WalletEvent::KeyRotated {
+                    channel_id,
+                    generation: 1,
+                },
+                WalletEvent::PolicyChanged {
+                    daily_limit: Some(50),
+                    counterparty_limits: BTreeMap::new(),
+                    confirm_above: None,
+                },
+                WalletEvent::ChannelClosed {
+                    channel_id,
+                    balance: 100,
+                },
+            ]
+        );
+        assert_eq!(wallet.audit_log().verify(), Ok(()));
+        assert_eq!(
+            wallet.audited_root(),
+            wallet.audit_log().commit(&wallet.get_merkle_root())
+        );
+        Ok(())
+    }
+}
@@ -1,17 +1,66 @@
+use crate::bitcoin::keys::{channel_index_for_id, BlindingPath, KeyFamily, KeyManager};
+use crate::bitcoin::wallet::WalletError;
+use crate::clock::{self, SharedClock};
+use crate::config::OverpassConfig;
+use bitcoin::secp256k1::{KeyPair, PublicKey, XOnlyPublicKey};
+use bitcoin::Network;
 use crate::zkp::pedersen_parameters::PedersenParameters;
-use crate::zkp::global_root_contract::{GlobalRootContract, GlobalRootContractError};
-use std::collections::HashMap;
-use crate::zkp::channel::ChannelState;
+use crate::zkp::global_root_contract::{
+    GlobalRootContract, GlobalRootContractError, WalletInclusionProof,
+};
+use std::collections::{BTreeMap, HashMap};
+use crate::zkp::blinding::BlindingFactor;
+use crate::zkp::channel::{AssetId, ChannelError, ChannelState};
+use crate::zkp::channel_backup::{
+    BackupError, ChannelBackup, RecoveryReport, RECOVERY_GAP_LIMIT,
+};
+use crate::zkp::counterparty_registry::CounterpartyRegistry;
+use crate::zkp::cross_validation::{self, RootInconsistency, ValidationReport};
+use crate::zkp::fee_ledger::{FeeEntry, FeeKind, FeeLedger};
+use crate::zkp::history_replay::{self, Replay, ReplayError};
+use crate::zkp::device_sync::{
+    self, Holding, SyncAction, SyncBundle, SyncConflict, SyncError, SyncReport,
+};
 use crate::zkp::helpers::{
     compute_global_root,
+    convert_helper_proof,
     generate_random_blinding,
+    verify_merkle_path,
     pedersen_commit,
-    generate_state_proof,
+    generate_state_proof_at,
     Bytes32,
 };
+use crate::zkp::invoice::{payment_hash, Invoice, InvoiceError, InvoiceTerms, MAX_CHANNEL_HINTS};
+use crate::zkp::key_rotation::KeyRotation;
+use crate::zkp::offer::{
+    InvoiceRequest, Offer, OfferError, OfferMessage, OFFER_INVOICE_EXPIRY_SECS,
+};
 use crate::zkp::mobile_optimized_storage::{MobileOptimizedStorage, StorageError};
-use anyhow::Result;
+use crate::zkp::payment::{Payment, PaymentBuilder, PaymentRequest, PaymentStatus};
+use crate::zkp::payment_schedule::{
+    PaymentSchedule, PaymentScheduler, ScheduleError, ScheduleRun,
+};
+use crate::zkp::proof_of_funds::FundsProof;
+use crate::zkp::spending_policy::{PolicyEngine, PolicyError, Spend, SpendingPolicy};
+use crate::zkp::sub_account::{AccountError, AccountTree, SubAccounts};
+use crate::zkp::tx_metadata::{MetadataError, TxMetadata};
+use crate::zkp::wallet_signer::{KeyId, LocalSigner, WalletSigner, WalletSignerError};
+use crate::zkp::watch_only::{SignedTransition, UnsignedTransition, WatchKeys};
+use crate::zkp::wallet_root_proof::{ChannelOpening, CommitmentPath, WalletRootProof};
+use crate::zkp::wallet_audit::{AuditError, AuditLog, AuditRecord, WalletEvent};
+use crate::zkp::wallet_lock::{KdfParams, LockError, SealedKeys};
+use crate::zkp::reconciliation::{
+    diff, resolve, ChannelEvidence, Divergence, ReconciliationError, Side, TreeView,
+};
+#[cfg(feature = "tokio")]
+use crate::utils::blocking::run_blocking;
+use serde::{Deserialize, Serialize};
 use serde_json;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+#[cfg(any(test, feature = "tokio"))]
+use tokio::sync::broadcast;
+use zeroize::Zeroizing;
 
 use super::state_proof;
 
@@ -24,32 +73,145 @@ pub struct WalletContract {
     pub merkle_root: Bytes32,
     pub storage: MobileOptimizedStorage,
     pub global_contract: GlobalRootContract,
+    /// Seed-derived source of blinding factors; random blindings are used when unset.
+    keys: Option<Arc<KeyManager>>,
+    /// Channels closed out of the tree, kept for their final balances.
+    pub closed_channels: HashMap<Bytes32, ChannelState>,
+    /// Sequence number of the next channel opened from the seed.
+    next_channel: u64,
+    /// Counterparty each channel was registered with.
+    counterparties: HashMap<Bytes32, Bytes32>,
+    /// Limits checked before signing an update that lowers the wallet's balance.
+    policy: Option<PolicyEngine>,
+    /// Public keys of a watch-only wallet, which has no key manager and cannot sign.
+    watch: Option<WatchKeys>,
+    /// Per peer device, the state hash of each channel both held after the last sync.
+    sync_bases: HashMap<Bytes32, HashMap<Bytes32, Bytes32>>,
+    /// Payments sent from this wallet, by id.
+    payments: HashMap<Bytes32, Payment>,
+    /// Known counterparties, checked when a channel opens and used to route payments.
+    registry: CounterpartyRegistry,
+    /// Rotations each channel's signing keys went through, oldest first; channels with
+    /// none sign with generation 0.
+    key_rotations: HashMap<Bytes32, Vec<KeyRotation>>,
+    /// Signer used instead of the key manager, e.g. an enclave or a remote server.
+    signer: Option<Arc<dyn WalletSigner>>,
+    /// On-chain, routing and anchoring fees the wallet paid.
+    fees: FeeLedger,
+    /// Future and recurring payments, sent by `run_due_payments`.
+    schedules: PaymentScheduler,
+    /// Invoices this wallet issued and the preimages behind them, by payment hash.
+    invoices: HashMap<Bytes32, (Invoice, Bytes32)>,
+    /// Offers this wallet published, by offer id.
+    offers: HashMap<Bytes32, Offer>,
+    /// Encoded invoices issued against offers, by the id of the request they answer.
+    offer_invoices: HashMap<Bytes32, String>,
+    /// Invoice requests this wallet sent and the offers they were made against, by id.
+    invoice_requests: HashMap<Bytes32, (Offer, InvoiceRequest)>,
+    /// The seed encrypted under the wallet passphrase, once one is set.
+    sealed_keys: Option<SealedKeys>,
+    /// Whether the key manager was dropped by `lock` and signing is refused.
+    locked: bool,
+    /// Hash-chained history of the wallet's operations.
+    audit: AuditLog,
+    /// Labeled sub-accounts, each rolled up into the wallet root under its own sub-root.
+    accounts: SubAccounts,
+    /// Time proofs, payments, invoices and audit entries are stamped with.
+    clock: SharedClock,
+    #[cfg(any(test, feature = "tokio"))]
+    updates: broadcast::Sender<WalletUpdate>,
+}
+
+/// A change to the wallet that servers can pass on to their clients as it happens.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WalletUpdate {
+    /// A record was appended to the audit log.
+    Audited(AuditRecord),
+    /// A payment was sent, or the global contract recorded it.
+    Payment(Box<Payment>),
+}
+
+/// Balances summed over a wallet's channels.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WalletBalance {
+    pub open: u64,
+    pub closed: u64,
+    pub open_channels: usize,
 }
 
 /// Represents errors in WalletContract operations.
 #[derive(Debug, thiserror::Error)]
 pub enum WalletContractError {
     #[error("Hash computation failed: {0}")]
-    HashError(String),
-    #[error("Merkle root computation failed: {0}")]
-    MerkleRootError(String),
-    #[error("Storage error: {0}")]
-    StorageError(String),
+    HashError(#[from] ChannelError),
+    #[error("Serialization error: {0}")]
+    SerializationError(#[from] serde_json::Error),
+    #[error("Channel history: {0}")]
+    Storage(#[from] StorageError),
     #[error("Global root contract error: {0}")]
     GlobalRootError(#[from] GlobalRootContractError),
     #[error("State proof generation failed: {0}")]
     ProofGenerationError(String),
+    #[error("Key derivation failed: {0}")]
+    KeyDerivationError(#[from] WalletError),
+    #[error("Channel not found")]
+    ChannelNotFound,
+    #[error("Wallet has no key manager")]
+    NoKeyManager,
+    #[error("Spending policy: {0}")]
+    PolicyError(#[from] PolicyError),
+    #[error("Watch-only wallets cannot sign")]
+    WatchOnly,
+    #[error("Invalid signed transition: {0}")]
+    InvalidTransition(&'static str),
+    #[error("Open balance is below {0}")]
+    InsufficientFunds(u64),
+    #[error("Wallet root differs from the one the global contract records")]
+    UnrecordedRoot,
+    #[error("No channel with {} holds {amount}", hex::encode(.counterparty))]
+    NoPaymentChannel { counterparty: Bytes32, amount: u64 },
+    #[error("Payment deadline {0} has passed")]
+    DeadlinePassed(u64),
+    #[error("Counterparty check failed: {0}")]
+    CounterpartyMismatch(String),
+    #[error("Signer error: {0}")]
+    SignerError(#[from] WalletSignerError),
+    #[error("Seed does not re-open the commitment of channel {0}")]
+    CommitmentMismatch(String),
+    #[error("Payment schedule error: {0}")]
+    ScheduleError(#[from] ScheduleError),
+    #[error("Invoice error: {0}")]
+    InvoiceError(#[from] InvoiceError),
+    #[error("Offer error: {0}")]
+    OfferError(#[from] OfferError),
+    #[error("Wallet is locked")]
+    Locked,
+    #[error("Wallet lock error: {0}")]
+    LockError(#[from] LockError),
+    #[error("Sub-account error: {0}")]
+    AccountError(#[from] AccountError),
+    #[error("History replay failed: {0}")]
+    ReplayError(#[from] ReplayError),
+    #[error("Transaction metadata: {0}")]
+    MetadataError(#[from] MetadataError),
+    #[error("Audit log: {0}")]
+    AuditError(#[from] AuditError),
 }
 
-impl From<StorageError> for WalletContractError {
-    fn from(err: StorageError) -> Self {
-        WalletContractError::StorageError(err.to_string())
-    }
+/// Proof that a channel's state hash is a leaf of a wallet root.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChannelInclusionProof {
+    pub channel_id: Bytes32,
+    pub channel_hash: Bytes32,
+    /// Position of the channel's leaf; leaves are ordered by account, then channel id.
+    pub index: u64,
+    pub siblings: Vec<Bytes32>,
 }
 
-impl From<serde_json::Error> for WalletContractError {
-    fn from(err: serde_json::Error) -> Self {
-        WalletContractError::StorageError(err.to_string())
+impl ChannelInclusionProof {
+    /// Checks that the proof leads from the channel's hash to `wallet_root`.
+    pub fn verify(&self, wallet_root: &Bytes32) -> bool {
+        verify_merkle_path(self.channel_hash, self.index, &self.siblings, wallet_root)
     }
 }
 
@@ -61,32 +223,363 @@ impl WalletContract {
         global_contract: GlobalRootContract,
     ) -> Self {
         // Initialize Merkle root based on initial channels (empty at creation)
-        let merkle_root = match compute_global_root(&HashMap::new()) {
-            Ok(root) => root,
-            Err(_) => [0u8; 32],
-        };
+        let merkle_root = compute_global_root(&HashMap::new());
             
         Self {
             wallet_id,
             params,
             channels: HashMap::new(),
             merkle_root,
-            storage: MobileOptimizedStorage::new(100, 30 * 24 * 3600),
+            storage: OverpassConfig::default().storage(),
             global_contract,
+            keys: None,
+            closed_channels: HashMap::new(),
+            next_channel: 0,
+            counterparties: HashMap::new(),
+            policy: None,
+            watch: None,
+            sync_bases: HashMap::new(),
+            payments: HashMap::new(),
+            registry: CounterpartyRegistry::new(),
+            key_rotations: HashMap::new(),
+            signer: None,
+            fees: FeeLedger::new(),
+            schedules: PaymentScheduler::new(),
+            invoices: HashMap::new(),
+            offers: HashMap::new(),
+            offer_invoices: HashMap::new(),
+            invoice_requests: HashMap::new(),
+            sealed_keys: None,
+            locked: false,
+            audit: AuditLog::new(),
+            accounts: SubAccounts::new(),
+            clock: clock::system(),
+            #[cfg(any(test, feature = "tokio"))]
+            updates: broadcast::channel(crate::zkp::global_root_contract::EVENT_CAPACITY).0,
         }
     }
+
+    /// Replaces the wallet's storage with one built from `config`, before channels are opened.
+    pub fn with_config(mut self, config: &OverpassConfig) -> Self {
+        self.storage = config.storage().with_clock(self.clock.clone());
+        self
+    }
+
+    /// Takes time from `clock` instead of the system clock, in the wallet, its storage and
+    /// its global contract.
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.storage.set_clock(clock.clone());
+        self.global_contract.set_clock(clock.clone());
+        self.clock = clock;
+        self
+    }
+
+    /// Gets the clock the wallet stamps and checks deadlines with.
+    pub fn clock(&self) -> &SharedClock {
+        &self.clock
+    }
+
+    /// Creates a wallet that tracks channels and verifies proofs under `keys` but holds no
+    /// secrets; its updates are prepared here and signed elsewhere.
+    pub fn watch_only(
+        wallet_id: Bytes32,
+        params: PedersenParameters,
+        global_contract: GlobalRootContract,
+        keys: WatchKeys,
+    ) -> Self {
+        let mut wallet = Self::new(wallet_id, params, global_contract);
+        wallet.watch = Some(keys);
+        wallet
+    }
+
+    pub fn is_watch_only(&self) -> bool {
+        self.watch.is_some()
+    }
+
+    /// Exports the public keys a watch-only copy of this wallet needs.
+    pub fn export_watch_keys(&self) -> Result<WatchKeys, WalletContractError> {
+        let keys = self.key_manager()?;
+        let account_xpub = keys
+            .wallet_account_xpub()
+            .map_err(WalletContractError::KeyDerivationError)?;
+        let funding_keys = self
+            .channels
+            .keys()
+            .map(|id| {
+                let key = self.channel_key(id, KeyFamily::Funding)?;
+                Ok((*id, key.public_key()))
+            })
+            .collect::<Result<_, WalletContractError>>()?;
+        Ok(WatchKeys {
+            account_xpub,
+            funding_keys,
+        })
+    }
+
+    fn ensure_signer(&self) -> Result<(), WalletContractError> {
+        if self.locked {
+            return Err(WalletContractError::Locked);
+        }
+        match self.watch {
+            Some(_) => Err(WalletContractError::WatchOnly),
+            None => Ok(()),
+        }
+    }
+
+    fn key_manager(&self) -> Result<&Arc<KeyManager>, WalletContractError> {
+        match &self.keys {
+            Some(keys) => Ok(keys),
+            None if self.locked => Err(WalletContractError::Locked),
+            None => Err(WalletContractError::NoKeyManager),
+        }
+    }
+
+    /// Encrypts the seed under `passphrase` so the wallet can be locked, using the
+    /// default Argon2 cost.
+    pub fn set_passphrase(&mut self, passphrase: &str) -> Result<(), WalletContractError> {
+        self.set_passphrase_with(passphrase, KdfParams::default())
+    }
+
+    /// Encrypts the seed under `passphrase` with a key derived at the given Argon2 cost.
+    pub fn set_passphrase_with(
+        &mut self,
+        passphrase: &str,
+        kdf: KdfParams,
+    ) -> Result<(), WalletContractError> {
+        let sealed = SealedKeys::seal(self.key_manager()?, passphrase, kdf)?;
+        self.sealed_keys = Some(sealed);
+        Ok(())
+    }
+
+    /// Creates the wallet locked over a seed sealed by `set_passphrase`, as loaded from
+    /// storage; `unlock` recovers its key manager.
+    pub fn with_sealed_keys(mut self, sealed: SealedKeys) -> Self {
+        self.keys = None;
+        self.sealed_keys = Some(sealed);
+        self.locked = true;
+        self
+    }
+
+    /// Continues the audit log of a restored wallet.
+    pub fn with_audit_log(mut self, audit: AuditLog) -> Self {
+        self.audit = audit;
+        self
+    }
+
+    pub fn audit_log(&self) -> &AuditLog {
+        &self.audit
+    }
+
+    /// Gets the wallet root committed together with the audit log's head, for anchoring
+    /// or backing up so a later rewrite of local history can be detected.
+    pub fn audited_root(&self) -> Bytes32 {
+        self.audit.commit(&self.merkle_root)
+    }
+
+    fn audit(&mut self, event: WalletEvent) -> Result<(), WalletContractError> {
+        self.audit.append(event, self.clock.now())?;
+        if let Some(record) = self.audit.records().last() {
+            self.emit(WalletUpdate::Audited(record.clone()));
+        }
+        Ok(())
+    }
+
+    /// Subscribes to the wallet's updates from now on.
+    ///
+    /// Expiry is not an update: a pending payment past its deadline counts as expired
+    /// without the wallet changing, so subscribers check deadlines themselves.
+    #[cfg(any(test, feature = "tokio"))]
+    pub fn subscribe(&self) -> broadcast::Receiver<WalletUpdate> {
+        self.updates.subscribe()
+    }
+
+    #[cfg(any(test, feature = "tokio"))]
+    fn emit(&self, update: WalletUpdate) {
+        // Sending only fails when nobody is subscribed.
+        let _ = self.updates.send(update);
+    }
+
+    /// Without tokio there is nobody to tell.
+    #[cfg(not(any(test, feature = "tokio")))]
+    fn emit(&self, _update: WalletUpdate) {}
+
+    /// Gets the sealed seed, to be persisted in place of the plaintext keys.
+    pub fn sealed_keys(&self) -> Option<&SealedKeys> {
+        self.sealed_keys.as_ref()
+    }
+
+    /// Wipes the wallet's key manager, leaving only the sealed seed, and refuses to sign
+    /// until `unlock`. Signers sharing the key manager lose its keys too.
+    pub fn lock(&mut self) -> Result<(), WalletContractError> {
+        if self.sealed_keys.is_none() {
+            return Err(LockError::NoPassphrase.into());
+        }
+        if let Some(keys) = self.keys.take() {
+            keys.wipe();
+        }
+        self.locked = true;
+        Ok(())
+    }
+
+    /// Decrypts the sealed seed with `passphrase` and allows signing again.
+    pub fn unlock(&mut self, passphrase: &str) -> Result<(), WalletContractError> {
+        if !self.locked {
+            return Ok(());
+        }
+        let sealed = self.sealed_keys.as_ref().ok_or(LockError::NoPassphrase)?;
+        self.keys = Some(Arc::new(sealed.open(passphrase)?));
+        self.locked = false;
+        Ok(())
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    /// Derives blinding factors from the wallet seed so a restored wallet can re-open
+    /// every channel commitment.
+    pub fn with_key_manager(mut self, keys: Arc<KeyManager>) -> Self {
+        self.keys = Some(keys);
+        self
+    }
+
+    /// Authorizes the `_with_signer` operations through `signer` rather than the key
+    /// manager; a watch-only wallet can use one to sign remotely.
+    pub fn with_signer(mut self, signer: Arc<dyn WalletSigner>) -> Self {
+        self.signer = Some(signer);
+        self
+    }
+
+    /// Gets the configured signer, or one over the key manager.
+    fn signer(&self) -> Result<Arc<dyn WalletSigner>, WalletContractError> {
+        if self.locked {
+            return Err(WalletContractError::Locked);
+        }
+        match (&self.signer, &self.keys) {
+            (Some(signer), _) => Ok(signer.clone()),
+            (None, Some(keys)) => Ok(Arc::new(LocalSigner::new(keys.clone(), self.params.clone()))),
+            (None, None) => Err(WalletContractError::NoKeyManager),
+        }
+    }
+
+    /// Enforces a spending policy on every channel update that lowers the wallet's balance.
+    pub fn with_spending_policy(mut self, policy: PolicyEngine) -> Result<Self, WalletContractError> {
+        self.audit(WalletEvent::policy_changed(policy.policy()))?;
+        self.policy = Some(policy);
+        Ok(self)
+    }
+
+    /// Replaces the spending policy's rules, starting a policy engine if there is none.
+    pub fn set_spending_policy(&mut self, policy: SpendingPolicy) -> Result<(), WalletContractError> {
+        let now = self.clock.now();
+        self.audit(WalletEvent::policy_changed(&policy))?;
+        match &mut self.policy {
+            Some(engine) => engine.set_policy(policy, now),
+            None => self.policy = Some(PolicyEngine::new(policy, now)),
+        }
+        Ok(())
+    }
+
+    pub fn spending_policy(&self) -> Option<&PolicyEngine> {
+        self.policy.as_ref()
+    }
+
+    /// Gets the policy mutably, e.g. to confirm a large spend. Rule changes made through
+    /// it bypass the audit log; use `set_spending_policy` for those.
+    pub fn spending_policy_mut(&mut self) -> Option<&mut PolicyEngine> {
+        self.policy.as_mut()
+    }
+
+    pub fn with_registry(mut self, registry: CounterpartyRegistry) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    pub fn registry(&self) -> &CounterpartyRegistry {
+        &self.registry
+    }
+
+    pub fn registry_mut(&mut self) -> &mut CounterpartyRegistry {
+        &mut self.registry
+    }
+
+    pub fn with_fee_ledger(mut self, fees: FeeLedger) -> Self {
+        self.fees = fees;
+        self
+    }
+
+    pub fn fees(&self) -> &FeeLedger {
+        &self.fees
+    }
+
+    /// Records a fee paid now, for `channel_id` and its counterparty or, without a
+    /// channel, for the wallet as a whole.
+    pub fn record_fee(
+        &mut self,
+        kind: FeeKind,
+        channel_id: Option<Bytes32>,
+        amount: u64,
+        reference: Option<String>,
+    ) -> Result<(), WalletContractError> {
+        if let Some(id) = &channel_id {
+            if !self.channels.contains_key(id) && !self.closed_channels.contains_key(id) {
+                return Err(WalletContractError::ChannelNotFound);
+            }
+        }
+        self.fees.record(FeeEntry {
+            kind,
+            amount,
+            channel_id,
+            counterparty: channel_id.and_then(|id| self.counterparties.get(&id).copied()),
+            timestamp: self.clock.now(),
+            reference,
+        });
+        Ok(())
+    }
+
+    /// Gets the blinding of a channel's commitment at `nonce`, derived when a key manager
+    /// is set.
+    fn channel_blinding(&self, channel_id: &Bytes32, nonce: u64) -> Result<Zeroizing<Bytes32>, WalletContractError> {
+        self.ensure_signer()?;
+        match &self.keys {
+            Some(keys) => keys
+                .blinding_bytes(&BlindingPath::new(channel_index_for_id(channel_id), nonce))
+                .map_err(WalletContractError::KeyDerivationError),
+            None => Ok(Zeroizing::new(generate_random_blinding())),
+        }
+    }
+
+    /// Checks that the seed-derived blinding re-opens a channel's current commitment.
+    pub fn reopen_channel(&self, channel_id: &Bytes32) -> Result<bool, WalletContractError> {
+        let (Some(keys), Some(channel)) = (&self.keys, self.channels.get(channel_id)) else {
+            return Ok(false);
+        };
+        let Some(balance) = channel.balances.first() else {
+            return Ok(false);
+        };
+        let blinding = keys
+            .blinding_bytes(&BlindingPath::new(channel_index_for_id(channel_id), channel.nonce))
+            .map_err(WalletContractError::KeyDerivationError)?;
+        Ok(pedersen_commit(*balance, *blinding, &self.params) == channel.merkle_root)
+    }
     
     /// Registers a new channel.
     pub fn register_channel(
         &mut self,
         channel_id: Bytes32,
         initial_balance: u64,
-        _counterparty: Bytes32,
+        counterparty: Bytes32,
         metadata: Vec<u8>,
     ) -> Result<bool, WalletContractError> {
         if self.channels.contains_key(&channel_id) {
             return Ok(false); // Channel already exists
         }
+        // A known counterparty must commit under the same parameters as this wallet
+        if let Some(known) = self.registry.get(&counterparty) {
+            self.params
+                .check_fingerprint(&known.params_fingerprint)
+                .map_err(|e| WalletContractError::CounterpartyMismatch(e.to_string()))?;
+        }
 
         // Sanitize metadata
         let sanitized_metadata = Self::sanitize_metadata(metadata).unwrap_or_else(Vec::new);
@@ -96,21 +589,363 @@ impl WalletContract {
             metadata: sanitized_metadata,
             merkle_root: [0u8; 32], // Initial Merkle root for the channel
             proof: None,
+            assets: BTreeMap::new(),
         };
 
         self.channels.insert(channel_id, channel);
+        self.counterparties.insert(channel_id, counterparty);
+        self.audit(WalletEvent::ChannelOpened {
+            channel_id,
+            counterparty,
+            balance: initial_balance,
+        })?;
 
         // Update the Merkle root to reflect the new channel
         self.update_merkle_root()?;
 
-        // Register wallet in global root contract
-        self.global_contract
-            .register_wallet(self.wallet_id, self.merkle_root)
-            .map_err(WalletContractError::from)?;
+        // Register wallet in global root contract on its first channel; later roots are
+        // submitted with proofs.
+        if self.global_contract.get_wallet_root(&self.wallet_id).is_none() {
+            self.global_contract
+                .register_wallet(self.wallet_id, self.merkle_root)
+                .map_err(WalletContractError::from)?;
+        }
 
         Ok(true)
     }
 
+    /// Gets the id of the channel opened `sequence`-th from the seed.
+    ///
+    /// Ids depend only on the wallet's account key and the sequence, so a restored wallet
+    /// can enumerate its channels; their keys then follow from the id.
+    pub fn channel_id_at(&self, sequence: u64) -> Result<Bytes32, WalletContractError> {
+        let account = match (&self.watch, &self.keys) {
+            (Some(watch), _) => watch.account_xpub,
+            (None, Some(keys)) => keys
+                .wallet_account_xpub()
+                .map_err(WalletContractError::KeyDerivationError)?,
+            (None, None) => return Err(WalletContractError::NoKeyManager),
+        };
+        Ok(Sha256::new()
+            .chain_update(b"overpass/hd-channel")
+            .chain_update(account.encode())
+            .chain_update(sequence.to_le_bytes())
+            .finalize()
+            .into())
+    }
+
+    /// Opens a channel under the next seed-derived id, committing to its balance with a
+    /// derived blinding so the commitment can be re-opened after a restore.
+    pub fn open_channel(
+        &mut self,
+        initial_balance: u64,
+        counterparty: Bytes32,
+        metadata: Vec<u8>,
+    ) -> Result<Bytes32, WalletContractError> {
+        self.ensure_signer()?;
+        let channel_id = loop {
+            let id = self.channel_id_at(self.next_channel)?;
+            self.next_channel += 1;
+            let index = channel_index_for_id(&id);
+            let taken = self
+                .channels
+                .keys()
+                .chain(self.closed_channels.keys())
+                .any(|open| channel_index_for_id(open) == index);
+            if !taken {
+                break id;
+            }
+        };
+        self.register_channel(channel_id, initial_balance, counterparty, metadata)?;
+        let blinding = self.channel_blinding(&channel_id, 0)?;
+        let commitment = pedersen_commit(initial_balance, *blinding, &self.params);
+        if let Some(channel) = self.channels.get_mut(&channel_id) {
+            channel.merkle_root = commitment;
+        }
+        self.update_merkle_root()?;
+        Ok(channel_id)
+    }
+
+    /// Derives a key of the given family for a channel, from its current key generation.
+    pub fn channel_key(
+        &self,
+        channel_id: &Bytes32,
+        family: KeyFamily,
+    ) -> Result<KeyPair, WalletContractError> {
+        self.channel_key_at(channel_id, family, self.key_generation(channel_id))
+    }
+
+    fn channel_key_at(
+        &self,
+        channel_id: &Bytes32,
+        family: KeyFamily,
+        generation: u32,
+    ) -> Result<KeyPair, WalletContractError> {
+        let keys = self.key_manager()?;
+        keys.rotated_channel_key(family, channel_index_for_id(channel_id), generation)
+            .map_err(WalletContractError::KeyDerivationError)
+    }
+
+    /// Gets the generation of the keys a channel currently signs with.
+    pub fn key_generation(&self, channel_id: &Bytes32) -> u32 {
+        self.key_rotations
+            .get(channel_id)
+            .and_then(|rotations| rotations.last())
+            .map_or(0, |rotation| rotation.generation)
+    }
+
+    /// Gets the rotations a channel's keys went through, oldest first.
+    pub fn key_rotations(&self, channel_id: &Bytes32) -> &[KeyRotation] {
+        self.key_rotations.get(channel_id).map_or(&[], Vec::as_slice)
+    }
+
+    /// Checks whether `key` is a funding key a channel has rotated away from.
+    pub fn is_retired_key(&self, channel_id: &Bytes32, key: &XOnlyPublicKey) -> bool {
+        self.key_rotations(channel_id)
+            .iter()
+            .any(|rotation| rotation.old_key == *key)
+    }
+
+    /// Moves every open channel to its next generation of signing keys without closing it.
+    ///
+    /// Each channel takes a transition with its balance unchanged whose metadata is the
+    /// rotation, signed by the funding key it retires, so the new key is part of the
+    /// proven state. Blinding keys are not rotated, so commitments still re-open from
+    /// the seed.
+    pub fn rotate_keys(&mut self) -> Result<Vec<KeyRotation>, WalletContractError> {
+        self.ensure_signer()?;
+        let mut channel_ids: Vec<Bytes32> = self.channels.keys().copied().collect();
+        channel_ids.sort_unstable();
+        let mut rotations = Vec::with_capacity(channel_ids.len());
+        for channel_id in channel_ids {
+            let current = self.key_generation(&channel_id);
+            let generation = current + 1;
+            let old = self.channel_key_at(&channel_id, KeyFamily::Funding, current)?;
+            let new_key = self
+                .channel_key_at(&channel_id, KeyFamily::Funding, generation)?
+                .x_only_public_key()
+                .0;
+            let channel = &self.channels[&channel_id];
+            let balance = channel.balances.first().copied().unwrap_or(0);
+            let rotation =
+                KeyRotation::sign(&old, new_key, channel_id, generation, channel.nonce + 1);
+            let metadata = serde_json::to_vec(&rotation)?;
+            let details = TxMetadata::KeyRotation { generation };
+            self.transition_channel(channel_id, balance, metadata, details)?;
+            self.audit(WalletEvent::KeyRotated {
+                channel_id,
+                generation,
+            })?;
+            self.key_rotations
+                .entry(channel_id)
+                .or_default()
+                .push(rotation.clone());
+            rotations.push(rotation);
+        }
+        Ok(rotations)
+    }
+
+    /// Rotates every open channel's keys like [`Self::rotate_keys`], with the keys,
+    /// signatures and transitions all coming from the signer.
+    pub async fn rotate_keys_with_signer(
+        &mut self,
+    ) -> Result<Vec<KeyRotation>, WalletContractError> {
+        let signer = self.signer()?;
+        let mut channel_ids: Vec<Bytes32> = self.channels.keys().copied().collect();
+        channel_ids.sort_unstable();
+        let mut rotations = Vec::with_capacity(channel_ids.len());
+        for channel_id in channel_ids {
+            let current = self.key_generation(&channel_id);
+            let generation = current + 1;
+            let funding = |generation| KeyId {
+                channel_id,
+                family: KeyFamily::Funding,
+                generation,
+            };
+            let old_key = signer.public_key(funding(current)).await?;
+            let new_key = signer.public_key(funding(generation)).await?;
+            let channel = &self.channels[&channel_id];
+            let nonce = channel.nonce + 1;
+            let balance = channel.balances.first().copied().unwrap_or(0);
+            let message = KeyRotation::message(&channel_id, generation, nonce, &old_key, &new_key);
+            let rotation = KeyRotation {
+                channel_id,
+                generation,
+                nonce,
+                old_key,
+                new_key,
+                signature: signer.sign_message(funding(current), &message).await?,
+            };
+            if !rotation.verify() {
+                return Err(WalletContractError::InvalidTransition(
+                    "rotation is not signed by the old key",
+                ));
+            }
+            let transition =
+                self.prepare_transition(channel_id, balance, serde_json::to_vec(&rotation)?)?;
+            self.apply_signed_transition(signer.sign_transition(&transition).await?)?;
+            self.audit(WalletEvent::KeyRotated {
+                channel_id,
+                generation,
+            })?;
+            self.key_rotations
+                .entry(channel_id)
+                .or_default()
+                .push(rotation.clone());
+            rotations.push(rotation);
+        }
+        Ok(rotations)
+    }
+
+    /// Gets a channel's funding public key, from the watch keys of a watch-only wallet.
+    pub fn funding_key(&self, channel_id: &Bytes32) -> Result<PublicKey, WalletContractError> {
+        match &self.watch {
+            Some(watch) => watch
+                .funding_keys
+                .get(channel_id)
+                .copied()
+                .ok_or(WalletContractError::ChannelNotFound),
+            None => Ok(self.channel_key(channel_id, KeyFamily::Funding)?.public_key()),
+        }
+    }
+
+    /// Removes a channel from the wallet tree, keeping its final state.
+    pub fn close_channel(
+        &mut self,
+        channel_id: &Bytes32,
+    ) -> Result<ChannelState, WalletContractError> {
+        let channel = self
+            .channels
+            .remove(channel_id)
+            .ok_or(WalletContractError::ChannelNotFound)?;
+        self.closed_channels.insert(*channel_id, channel.clone());
+        self.update_merkle_root()?;
+        self.audit(WalletEvent::ChannelClosed {
+            channel_id: *channel_id,
+            balance: channel.balances.first().copied().unwrap_or(0),
+        })?;
+        Ok(channel)
+    }
+
+    /// Sums the wallet's own balance, the first entry of each channel's balances.
+    pub fn balance(&self) -> WalletBalance {
+        let own = |channel: &ChannelState| channel.balances.first().copied().unwrap_or(0);
+        WalletBalance {
+            open: self.channels.values().map(own).sum(),
+            closed: self.closed_channels.values().map(own).sum(),
+            open_channels: self.channels.len(),
+        }
+    }
+
+    /// Restores sub-accounts saved with `SubAccounts::save`.
+    pub fn with_accounts(mut self, accounts: SubAccounts) -> Result<Self, WalletContractError> {
+        self.accounts = accounts;
+        self.update_merkle_root()?;
+        Ok(self)
+    }
+
+    pub fn accounts(&self) -> &SubAccounts {
+        &self.accounts
+    }
+
+    /// Adds an empty labeled sub-account, e.g. "personal" or "business".
+    pub fn create_account(&mut self, label: &str) -> Result<(), WalletContractError> {
+        self.accounts.create(label)?;
+        self.update_merkle_root()
+    }
+
+    /// Moves an open channel into a labeled account, or back to the default one for `None`.
+    pub fn assign_channel(
+        &mut self,
+        channel_id: &Bytes32,
+        label: Option<&str>,
+    ) -> Result<(), WalletContractError> {
+        if !self.channels.contains_key(channel_id) {
+            return Err(WalletContractError::ChannelNotFound);
+        }
+        self.accounts.assign(*channel_id, label)?;
+        self.update_merkle_root()
+    }
+
+    /// Gets the root over one account's channels, `None` naming the default account.
+    pub fn account_root(&self, label: Option<&str>) -> Result<Bytes32, WalletContractError> {
+        self.account_tree()?
+            .sub_root(label)
+            .ok_or_else(|| unknown_account(label))
+    }
+
+    /// Sums the wallet's own balance over one account's channels.
+    pub fn account_balance(
+        &self,
+        label: Option<&str>,
+    ) -> Result<WalletBalance, WalletContractError> {
+        if label.is_some_and(|label| !self.accounts.contains(label)) {
+            return Err(unknown_account(label));
+        }
+        let own = |channel: &ChannelState| channel.balances.first().copied().unwrap_or(0);
+        let in_account =
+            |(id, _): &(&Bytes32, &ChannelState)| self.accounts.account_of(id) == label;
+        let open: Vec<&ChannelState> =
+            self.channels.iter().filter(in_account).map(|(_, channel)| channel).collect();
+        Ok(WalletBalance {
+            open: open.iter().copied().map(own).sum(),
+            closed: self
+                .closed_channels
+                .iter()
+                .filter(in_account)
+                .map(|(_, channel)| own(channel))
+                .sum(),
+            open_channels: open.len(),
+        })
+    }
+
+    /// Gets the payments sent from one account's channels, oldest first.
+    pub fn account_history(
+        &self,
+        label: Option<&str>,
+    ) -> Result<Vec<Payment>, WalletContractError> {
+        if label.is_some_and(|label| !self.accounts.contains(label)) {
+            return Err(unknown_account(label));
+        }
+        let mut payments: Vec<Payment> = self
+            .payments
+            .values()
+            .filter(|payment| self.accounts.account_of(&payment.channel_id) == label)
+            .filter_map(|payment| self.payment(&payment.id))
+            .collect();
+        payments.sort_by_key(|payment| (payment.created_at, payment.nonce));
+        Ok(payments)
+    }
+
+    /// Replays an open channel's stored history and checks it ends on the channel's
+    /// current commitment.
+    pub fn replay_channel(&self, channel_id: &Bytes32) -> Result<Replay, WalletContractError> {
+        let channel = self
+            .channels
+            .get(channel_id)
+            .ok_or(WalletContractError::ChannelNotFound)?;
+        let replay =
+            history_replay::replay(self.storage.history(channel_id)).map_err(ReplayError::from)?;
+        match replay.head() {
+            Some(head) if head != channel.merkle_root => {
+                Err(ReplayError::HeadMismatch(head).into())
+            }
+            _ => Ok(replay),
+        }
+    }
+
+    /// Sums the wallet's own balance of each asset over its open channels.
+    pub fn asset_balances(&self) -> BTreeMap<AssetId, u64> {
+        let mut totals = BTreeMap::new();
+        for channel in self.channels.values() {
+            for (asset, balances) in &channel.assets {
+                let own = balances.first().copied().unwrap_or(0);
+                *totals.entry(*asset).or_insert(0) += own;
+            }
+        }
+        totals
+    }
+
     /// Helper to sanitize metadata, ensuring it's valid.
     fn sanitize_metadata(metadata: Vec<u8>) -> Option<Vec<u8>> {
         if metadata.is_empty() {
@@ -120,21 +955,54 @@ impl WalletContract {
         }
     }
 
+    /// Gets each channel's id and state hash, ordered by channel id.
+    fn channel_leaves(&self) -> Result<Vec<(Bytes32, Bytes32)>, WalletContractError> {
+        let mut leaves = self
+            .channels
+            .iter()
+            .map(|(channel_id, channel_state)| {
+                channel_state
+                    .hash()
+                    .map(|hash| (*channel_id, hash))
+                    .map_err(WalletContractError::HashError)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        leaves.sort_unstable_by_key(|(channel_id, _)| *channel_id);
+        Ok(leaves)
+    }
+
     /// Updates the Merkle root for the wallet, based on channel states.
     fn update_merkle_root(&mut self) -> Result<(), WalletContractError> {
-        // Compute channel hashes
-        let mut channel_hashes = HashMap::new();
-        for (channel_id, channel_state) in &self.channels {
-            let channel_hash = channel_state.hash()
-                .map_err(|e| WalletContractError::HashError(e.to_string()))?;
-            channel_hashes.insert(*channel_id, channel_hash);
-        }
+        self.merkle_root = self.account_tree()?.root();
+        Ok(())
+    }
 
-        // Compute the new global Merkle root based on all channel hashes
-        self.merkle_root = compute_global_root(&channel_hashes)
-            .map_err(|e| WalletContractError::MerkleRootError(e.to_string()))?;
+    /// Gets the wallet tree, with each account's channels under its own sub-root.
+    fn account_tree(&self) -> Result<AccountTree, WalletContractError> {
+        Ok(self.accounts.tree(&self.channel_leaves()?))
+    }
 
-        Ok(())
+    /// Proves that a channel's current state is part of the wallet's current root.
+    pub fn channel_inclusion_proof(
+        &self,
+        channel_id: &Bytes32,
+    ) -> Result<ChannelInclusionProof, WalletContractError> {
+        let channel_hash = self
+            .channels
+            .get(channel_id)
+            .ok_or(WalletContractError::ChannelNotFound)?
+            .hash()
+            .map_err(WalletContractError::HashError)?;
+        let (index, siblings) = self
+            .account_tree()?
+            .path(channel_id)
+            .ok_or(WalletContractError::ChannelNotFound)?;
+        Ok(ChannelInclusionProof {
+            channel_id: *channel_id,
+            channel_hash,
+            index,
+            siblings,
+        })
     }
 
     /// Updates a channel's state and generates a proof.
@@ -144,26 +1012,68 @@ impl WalletContract {
         new_balance: u64,
         metadata: Vec<u8>,
     ) -> Result<bool, WalletContractError> {
+        let old_balance = self
+            .channels
+            .get(&channel_id)
+            .and_then(|channel| channel.balances.first().copied())
+            .unwrap_or(0);
+        let Some(state_proof) =
+            self.transition_channel(channel_id, new_balance, metadata, TxMetadata::None)?
+        else {
+            return Ok(false);
+        };
+        if new_balance > old_balance {
+            self.audit(WalletEvent::PaymentReceived {
+                channel_id,
+                amount: new_balance - old_balance,
+            })?;
+        }
+
+        // Update global root contract
+        self.global_contract
+            .update_wallet(self.wallet_id, self.merkle_root, state_proof)
+            .map_err(WalletContractError::from)?;
+        self.mark_payments_recorded();
+        Ok(true)
+    }
+
+    /// Applies a balance update locally and stores its proof, with `details` as the stored
+    /// transaction's metadata. Returns `None` if the channel does not exist.
+    fn transition_channel(
+        &mut self,
+        channel_id: Bytes32,
+        new_balance: u64,
+        metadata: Vec<u8>,
+        details: TxMetadata,
+    ) -> Result<Option<state_proof::StateProof>, WalletContractError> {
+        self.ensure_signer()?;
+        details.validate()?;
         // First, check if channel exists and get required data
-        let (old_merkle_root, old_commitment_hash) = match self.channels.get(&channel_id) {
+        let (old_merkle_root, nonce, old_balance) = match self.channels.get(&channel_id) {
             Some(channel) => {
-                let hash = channel.hash()
-                    .map_err(|e| WalletContractError::HashError(e.to_string()))?;
-                (channel.merkle_root, hash)
+                let balance = channel.balances.first().copied().unwrap_or(0);
+                (channel.merkle_root, channel.nonce, balance)
             },
-            None => return Ok(false),
+            None => return Ok(None),
         };
+
+        // Check the spending policy before anything is signed
+        self.authorize_spend(channel_id, old_balance, new_balance)?;
     
         // Generate new commitment and proof
-        let blinding = generate_random_blinding();
-        let new_commitment = pedersen_commit(new_balance, blinding, &self.params);
+        let blinding = self.channel_blinding(&channel_id, nonce + 1)?;
+        let new_commitment = pedersen_commit(new_balance, *blinding, &self.params);
     
-        let helper_proof = generate_state_proof(
-            old_merkle_root,
-            new_commitment,
-            self.merkle_root,
-            &self.params,
-        );
+        let prove = tracing::info_span!("prove", channel_id = %hex::encode(channel_id));
+        let helper_proof = prove.in_scope(|| {
+            generate_state_proof_at(
+                old_merkle_root,
+                new_commitment,
+                self.merkle_root,
+                &self.params,
+                self.clock.now(),
+            )
+        });
     
         // Convert helpers::StateProof to state_proof::StateProof
         let state_proof = state_proof::StateProof {
@@ -173,33 +1083,981 @@ impl WalletContract {
         };
     
         // Now update the channel
+        let encoded_proof = serde_json::to_vec(&state_proof)?;
         if let Some(channel) = self.channels.get_mut(&channel_id) {
             channel.balances = vec![new_balance];
             channel.nonce += 1;
             channel.metadata = metadata;
             channel.merkle_root = new_commitment;
+            channel.proof = Some(encoded_proof);
         }
     
         // Store transaction
         self.storage
             .store_transaction(
                 channel_id,
-                old_commitment_hash,
+                old_merkle_root,
                 new_commitment,
                 state_proof.clone(),
-                serde_json::Value::Null,
-            )
-            .map_err(|e| WalletContractError::StorageError(e.to_string()))?;
+                details,
+            )?;
     
         // Update merkle root
         self.update_merkle_root()?;
-    
-        // Update global root contract
-        self.global_contract
-            .update_wallet(self.wallet_id, self.merkle_root, state_proof)
-            .map_err(WalletContractError::from)?;
-    
-        Ok(true)
+        Ok(Some(state_proof))
+    }
+
+    /// Starts a payment of `amount` to `counterparty`, sent over whichever of their open
+    /// channels fits it best.
+    pub fn pay(&mut self, counterparty: Bytes32, amount: u64) -> PaymentBuilder<'_> {
+        PaymentBuilder::new(self, counterparty, amount)
+    }
+
+    /// Sends a payment over the counterparty's channel with the smallest own balance that
+    /// covers it, keeping larger channels free for larger payments.
+    ///
+    /// Only the local tree changes; the payment is recorded once a root including it
+    /// reaches the global contract.
+    pub(crate) fn send_payment(
+        &mut self,
+        request: PaymentRequest,
+    ) -> Result<Payment, WalletContractError> {
+        let now = self.clock.now();
+        if let Some(deadline) = request.deadline.filter(|deadline| now > *deadline) {
+            return Err(WalletContractError::DeadlinePassed(deadline));
+        }
+        let debit = request.amount.saturating_add(request.routing_fee);
+        let (channel_id, balance) = self
+            .channels
+            .iter()
+            .filter(|(id, _)| self.counterparties.get(*id) == Some(&request.counterparty))
+            .filter_map(|(id, channel)| Some((*id, channel.balances.first().copied()?)))
+            .filter(|(_, balance)| *balance >= debit)
+            .min_by_key(|(id, balance)| {
+                let hint = request.channel_hints.iter().position(|hint| hint == id);
+                (hint.unwrap_or(usize::MAX), *balance, *id)
+            })
+            .ok_or(WalletContractError::NoPaymentChannel {
+                counterparty: request.counterparty,
+                amount: debit,
+            })?;
+        let nonce = self.channels[&channel_id].nonce + 1;
+        let id = Payment::id_for(&self.wallet_id, &channel_id, nonce);
+        let details = match request.payment_hash {
+            Some(payment_hash) => TxMetadata::Invoice {
+                payment_id: id,
+                payment_hash,
+                amount: request.amount,
+                memo: request.memo.clone(),
+            },
+            None => TxMetadata::Payment {
+                payment_id: id,
+                counterparty: request.counterparty,
+                amount: request.amount,
+                routing_fee: request.routing_fee,
+                memo: request.memo.clone(),
+                deadline: request.deadline,
+            },
+        };
+        let metadata = request.memo.clone().map(String::into_bytes).unwrap_or_default();
+        self.transition_channel(channel_id, balance - debit, metadata, details)?;
+        if request.routing_fee > 0 {
+            self.record_fee(
+                FeeKind::Routing,
+                Some(channel_id),
+                request.routing_fee,
+                Some(hex::encode(id)),
+            )?;
+        }
+
+        let payment = Payment {
+            id,
+            channel_id,
+            counterparty: request.counterparty,
+            amount: request.amount,
+            routing_fee: request.routing_fee,
+            nonce,
+            memo: request.memo,
+            deadline: request.deadline,
+            endpoint: self
+                .registry
+                .get(&request.counterparty)
+                .and_then(|known| known.endpoints.first().cloned()),
+            payment_hash: request.payment_hash,
+            created_at: now,
+            status: PaymentStatus::Pending,
+        };
+        self.audit(WalletEvent::PaymentSent {
+            payment_id: id,
+            channel_id,
+            amount: request.amount,
+        })?;
+        self.payments.insert(id, payment.clone());
+        self.emit(WalletUpdate::Payment(Box::new(payment.clone())));
+        Ok(payment)
+    }
+
+    pub fn with_schedules(mut self, schedules: PaymentScheduler) -> Self {
+        self.schedules = schedules;
+        self
+    }
+
+    pub fn schedules(&self) -> &PaymentScheduler {
+        &self.schedules
+    }
+
+    pub fn schedules_mut(&mut self) -> &mut PaymentScheduler {
+        &mut self.schedules
+    }
+
+    /// Adds a future or recurring payment and returns its schedule id.
+    pub fn schedule_payment(
+        &mut self,
+        schedule: PaymentSchedule,
+    ) -> Result<u64, WalletContractError> {
+        Ok(self.schedules.add(schedule)?)
+    }
+
+    /// Sends every scheduled payment due at `now` through the payment builder, retrying
+    /// or giving up on failures as each schedule's retry policy says.
+    pub fn run_due_payments(&mut self, now: u64) -> Vec<ScheduleRun> {
+        let mut runs = Vec::new();
+        for schedule_id in self.schedules.due(now) {
+            let Some(schedule) = self.schedules.get(schedule_id).map(|s| s.schedule.clone())
+            else {
+                continue;
+            };
+            let mut builder = self
+                .pay(schedule.counterparty, schedule.amount)
+                .with_routing_fee(schedule.routing_fee);
+            if let Some(memo) = schedule.memo {
+                builder = builder.with_memo(memo);
+            }
+            let result = builder.send().map(|payment| payment.id);
+            if let Some(scheduled) = self.schedules.get_mut(schedule_id) {
+                match &result {
+                    Ok(payment_id) => scheduled.succeeded(*payment_id, now),
+                    Err(err) => scheduled.failed(err.to_string(), now),
+                }
+            }
+            runs.push(ScheduleRun {
+                schedule_id,
+                result: result.map_err(|err| err.to_string()),
+            });
+        }
+        runs
+    }
+
+    /// Issues an invoice for `amount`, signed with the seed's invoice key. When `payer`
+    /// is given, the invoice hints the channels held with it.
+    pub fn create_invoice(
+        &mut self,
+        amount: u64,
+        expiry_secs: u64,
+        memo: Option<String>,
+        payer: Option<Bytes32>,
+    ) -> Result<Invoice, WalletContractError> {
+        let keys = self.key_manager()?;
+        let key = keys
+            .invoice_key()
+            .map_err(WalletContractError::KeyDerivationError)?;
+        let mut channel_hints: Vec<Bytes32> = match payer {
+            Some(payer) => self
+                .channels
+                .keys()
+                .filter(|id| self.counterparties.get(*id) == Some(&payer))
+                .copied()
+                .collect(),
+            None => Vec::new(),
+        };
+        channel_hints.sort_unstable();
+        channel_hints.truncate(MAX_CHANNEL_HINTS);
+        let preimage = generate_random_blinding();
+        let terms = InvoiceTerms {
+            amount,
+            payment_hash: payment_hash(&preimage),
+            channel_hints,
+            created_at: self.clock.now(),
+            expiry_secs,
+            memo,
+        };
+        let invoice = Invoice::sign(keys.network(), self.wallet_id, &key, terms)?;
+        self.invoices
+            .insert(invoice.terms.payment_hash, (invoice.clone(), preimage));
+        Ok(invoice)
+    }
+
+    /// Gets the preimage of an invoice this wallet issued, to reveal once it is paid.
+    pub fn invoice_preimage(&self, payment_hash: &Bytes32) -> Option<Bytes32> {
+        self.invoices.get(payment_hash).map(|(_, preimage)| *preimage)
+    }
+
+    /// Pays an encoded invoice to its payee over a channel with it, preferring the
+    /// channels it hints.
+    pub fn pay_invoice(&mut self, encoded: &str) -> Result<Payment, WalletContractError> {
+        let invoice = Invoice::decode(encoded)?;
+        if !invoice.verify() {
+            return Err(InvoiceError::InvalidSignature.into());
+        }
+        if let Some(keys) = &self.keys {
+            if keys.network() != invoice.network {
+                return Err(InvoiceError::WrongNetwork(invoice.network).into());
+            }
+        }
+        if invoice.is_expired(self.clock.now()) {
+            return Err(InvoiceError::Expired(invoice.expires_at()).into());
+        }
+        if let Some(known) = self.registry.get(&invoice.payee) {
+            if known.public_key.x_only_public_key().0 != invoice.payee_key {
+                return Err(WalletContractError::CounterpartyMismatch(
+                    "invoice is signed by another key".into(),
+                ));
+            }
+        }
+        let hash = invoice.terms.payment_hash;
+        if self
+            .payments
+            .values()
+            .any(|payment| payment.payment_hash == Some(hash))
+        {
+            return Err(InvoiceError::AlreadyPaid.into());
+        }
+        let mut builder = self
+            .pay(invoice.payee, invoice.terms.amount)
+            .with_deadline(invoice.expires_at())
+            .with_channel_hints(invoice.terms.channel_hints)
+            .with_payment_hash(hash);
+        if let Some(memo) = invoice.terms.memo {
+            builder = builder.with_memo(memo);
+        }
+        builder.send()
+    }
+
+    /// Publishes a reusable offer signed with the seed's invoice key.
+    pub fn create_offer(
+        &mut self,
+        amount: Option<u64>,
+        description: String,
+        expires_at: Option<u64>,
+    ) -> Result<Offer, WalletContractError> {
+        let keys = self.key_manager()?;
+        let key = keys
+            .invoice_key()
+            .map_err(WalletContractError::KeyDerivationError)?;
+        let offer = Offer::sign(
+            keys.network(),
+            self.wallet_id,
+            &key,
+            amount,
+            description,
+            expires_at,
+        )?;
+        self.offers.insert(offer.id()?, offer.clone());
+        Ok(offer)
+    }
+
+    /// Asks the payee of an encoded offer for an invoice of `amount`, which may be left
+    /// out when the offer fixes it.
+    pub fn request_invoice(
+        &mut self,
+        encoded_offer: &str,
+        amount: Option<u64>,
+        note: Option<String>,
+    ) -> Result<OfferMessage, WalletContractError> {
+        let offer = Offer::decode(encoded_offer)?;
+        if !offer.verify() {
+            return Err(OfferError::InvalidSignature.into());
+        }
+        if let Some(keys) = &self.keys {
+            if keys.network() != offer.network {
+                return Err(InvoiceError::WrongNetwork(offer.network).into());
+            }
+        }
+        if let Some(expires_at) = offer.expires_at.filter(|_| offer.is_expired(self.clock.now()))
+        {
+            return Err(OfferError::Expired(expires_at).into());
+        }
+        if let Some(known) = self.registry.get(&offer.payee) {
+            if known.public_key.x_only_public_key().0 != offer.payee_key {
+                return Err(WalletContractError::CounterpartyMismatch(
+                    "offer is signed by another key".into(),
+                ));
+            }
+        }
+        let amount = offer.amount_for(amount)?;
+        let request = InvoiceRequest::new(offer.id()?, self.wallet_id, Some(amount), note);
+        self.invoice_requests
+            .insert(request.id(), (offer, request.clone()));
+        Ok(OfferMessage::InvoiceRequest(Box::new(request)))
+    }
+
+    /// Answers an invoice request against one of this wallet's offers with an invoice,
+    /// or with the reason it was refused. A repeated request gets the same invoice.
+    pub fn respond_to_offer(&mut self, message: &OfferMessage) -> OfferMessage {
+        let OfferMessage::InvoiceRequest(request) = message else {
+            return OfferMessage::InvoiceError {
+                request_id: [0u8; 32],
+                reason: "expected an invoice request".into(),
+            };
+        };
+        let request_id = request.id();
+        match self.invoice_for_request(request) {
+            Ok(invoice) => OfferMessage::Invoice {
+                request_id,
+                invoice,
+            },
+            Err(err) => OfferMessage::InvoiceError {
+                request_id,
+                reason: err.to_string(),
+            },
+        }
+    }
+
+    fn invoice_for_request(
+        &mut self,
+        request: &InvoiceRequest,
+    ) -> Result<String, WalletContractError> {
+        if let Some(invoice) = self.offer_invoices.get(&request.id()) {
+            return Ok(invoice.clone());
+        }
+        if !request.verify() {
+            return Err(OfferError::InvalidRequest.into());
+        }
+        let offer = self
+            .offers
+            .get(&request.offer_id)
+            .ok_or(OfferError::UnknownOffer(request.offer_id))?;
+        if let Some(expires_at) = offer.expires_at.filter(|_| offer.is_expired(self.clock.now()))
+        {
+            return Err(OfferError::Expired(expires_at).into());
+        }
+        let amount = offer.amount_for(request.amount)?;
+        let description = offer.description.clone();
+        let invoice = self
+            .create_invoice(
+                amount,
+                OFFER_INVOICE_EXPIRY_SECS,
+                Some(description),
+                Some(request.payer),
+            )?
+            .encode()?;
+        self.offer_invoices.insert(request.id(), invoice.clone());
+        Ok(invoice)
+    }
+
+    /// Pays the invoice a payee sent back for one of this wallet's requests, after
+    /// checking it comes from the offer's payee and asks for the requested amount.
+    pub fn pay_offer_response(
+        &mut self,
+        message: &OfferMessage,
+    ) -> Result<Payment, WalletContractError> {
+        let (request_id, encoded) = match message {
+            OfferMessage::Invoice {
+                request_id,
+                invoice,
+            } => (request_id, invoice),
+            OfferMessage::InvoiceError { reason, .. } => {
+                return Err(OfferError::Refused(reason.clone()).into())
+            }
+            OfferMessage::InvoiceRequest(_) => {
+                return Err(OfferError::InvalidMessage("expected an invoice".into()).into())
+            }
+        };
+        let (offer, request) = self
+            .invoice_requests
+            .get(request_id)
+            .ok_or(OfferError::UnknownRequest(*request_id))?;
+        let invoice = Invoice::decode(encoded)?;
+        if invoice.payee != offer.payee || invoice.payee_key != offer.payee_key {
+            return Err(OfferError::UnexpectedInvoice("payee").into());
+        }
+        if Some(invoice.terms.amount) != request.amount {
+            return Err(OfferError::UnexpectedInvoice("amount").into());
+        }
+        let payment = self.pay_invoice(encoded)?;
+        self.invoice_requests.remove(request_id);
+        Ok(payment)
+    }
+
+    /// Gets a sent payment with its status as of now.
+    pub fn payment(&self, id: &Bytes32) -> Option<Payment> {
+        let mut payment = self.payments.get(id)?.clone();
+        payment.status = payment.status_at(self.clock.now());
+        Some(payment)
+    }
+
+    /// Gets every sent payment with its status as of now, oldest first.
+    pub fn payments(&self) -> Vec<Payment> {
+        let mut payments: Vec<Payment> = self
+            .payments
+            .keys()
+            .filter_map(|id| self.payment(id))
+            .collect();
+        payments.sort_by_key(|payment| (payment.created_at, payment.nonce));
+        payments
+    }
+
+    /// Marks pending payments recorded once the global contract holds the current root,
+    /// which includes every payment sent so far.
+    fn mark_payments_recorded(&mut self) {
+        if self.global_contract.get_wallet_root(&self.wallet_id) != Some(self.merkle_root) {
+            return;
+        }
+        let mut recorded = Vec::new();
+        for payment in self.payments.values_mut() {
+            if payment.status == PaymentStatus::Pending {
+                payment.status = PaymentStatus::Recorded;
+                recorded.push(payment.clone());
+            }
+        }
+        recorded.sort_by_key(|payment| (payment.created_at, payment.nonce));
+        for payment in recorded {
+            self.emit(WalletUpdate::Payment(Box::new(payment)));
+        }
+    }
+
+    /// Runs a balance decrease on a channel past the spending policy, if one is set.
+    fn authorize_spend(
+        &mut self,
+        channel_id: Bytes32,
+        old_balance: u64,
+        new_balance: u64,
+    ) -> Result<(), WalletContractError> {
+        if let (Some(policy), true) = (&mut self.policy, new_balance < old_balance) {
+            policy.authorize(Spend {
+                channel_id,
+                counterparty: self.counterparties.get(&channel_id).copied().unwrap_or_default(),
+                amount: old_balance - new_balance,
+                timestamp: self.clock.now(),
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Prepares an update of a channel's balance for an offline signer, after checking it
+    /// against the spending policy.
+    pub fn prepare_transition(
+        &mut self,
+        channel_id: Bytes32,
+        new_balance: u64,
+        metadata: Vec<u8>,
+    ) -> Result<UnsignedTransition, WalletContractError> {
+        let channel = self
+            .channels
+            .get(&channel_id)
+            .ok_or(WalletContractError::ChannelNotFound)?;
+        let state_hash = channel
+            .hash()
+            .map_err(WalletContractError::HashError)?;
+        let transition = UnsignedTransition {
+            wallet_id: self.wallet_id,
+            channel_id,
+            nonce: channel.nonce,
+            state_hash,
+            old_commitment: channel.merkle_root,
+            wallet_root: self.merkle_root,
+            new_balance,
+            metadata,
+        };
+        let old_balance = channel.balances.first().copied().unwrap_or(0);
+        self.authorize_spend(channel_id, old_balance, new_balance)?;
+        Ok(transition)
+    }
+
+    /// Updates a channel's balance with the transition authorized by the signer.
+    pub async fn update_channel_with_signer(
+        &mut self,
+        channel_id: Bytes32,
+        new_balance: u64,
+        metadata: Vec<u8>,
+    ) -> Result<(), WalletContractError> {
+        let signer = self.signer()?;
+        let transition = self.prepare_transition(channel_id, new_balance, metadata)?;
+        self.apply_signed_transition(signer.sign_transition(&transition).await?)
+    }
+
+    /// Applies a transition signed offline, if it still extends the channel's current
+    /// state and its proof is over the transition.
+    ///
+    /// Only the local tree changes; the new root reaches the global contract by the usual
+    /// submission path.
+    pub fn apply_signed_transition(
+        &mut self,
+        signed: SignedTransition,
+    ) -> Result<(), WalletContractError> {
+        let transition = &signed.transition;
+        if transition.wallet_id != self.wallet_id {
+            return Err(WalletContractError::InvalidTransition("signed for another wallet"));
+        }
+        let verify = tracing::info_span!("verify", channel_id = %hex::encode(transition.channel_id));
+        let bound = verify.in_scope(|| signed.is_bound());
+        if !bound {
+            return Err(WalletContractError::InvalidTransition("proof is not over the transition"));
+        }
+        if transition.wallet_root != self.merkle_root {
+            return Err(WalletContractError::InvalidTransition("wallet root has moved on"));
+        }
+        let encoded_proof = serde_json::to_vec(&signed.proof)?;
+        let channel = self
+            .channels
+            .get_mut(&transition.channel_id)
+            .ok_or(WalletContractError::ChannelNotFound)?;
+        let state_hash = channel
+            .hash()
+            .map_err(WalletContractError::HashError)?;
+        if channel.nonce != transition.nonce || state_hash != transition.state_hash {
+            return Err(WalletContractError::InvalidTransition("channel state has moved on"));
+        }
+        let old_commitment = channel.merkle_root;
+        channel.balances = vec![transition.new_balance];
+        channel.nonce += 1;
+        channel.metadata = transition.metadata.clone();
+        channel.merkle_root = signed.commitment;
+        channel.proof = Some(encoded_proof);
+
+        self.storage.store_transaction(
+            transition.channel_id,
+            old_commitment,
+            signed.commitment,
+            signed.proof,
+            TxMetadata::None,
+        )?;
+        self.update_merkle_root()
+    }
+
+    /// Gets this wallet's view of its channel tree, to compare with a counterparty's.
+    pub fn tree_view(&self) -> Result<TreeView, WalletContractError> {
+        Ok(TreeView::new(self.channel_leaves()?))
+    }
+
+    /// Gets a channel's current state with proof of its place in the tree view.
+    pub fn channel_evidence(
+        &self,
+        channel_id: &Bytes32,
+    ) -> Result<ChannelEvidence, WalletContractError> {
+        let state = self
+            .channels
+            .get(channel_id)
+            .ok_or(WalletContractError::ChannelNotFound)?
+            .clone();
+        Ok(ChannelEvidence {
+            state,
+            proof: self.channel_inclusion_proof(channel_id)?,
+        })
+    }
+
+    /// Gets the evidence a counterparty with view `remote` needs to converge: every
+    /// channel it lacks or holds a different state for.
+    pub fn evidence_for(
+        &self,
+        remote: &TreeView,
+    ) -> Result<Vec<ChannelEvidence>, ReconciliationError> {
+        let local = self.tree_view().map_err(|e| ReconciliationError::Wallet(e.to_string()))?;
+        diff(&local, remote)
+            .iter()
+            .filter(|divergence| !matches!(divergence, Divergence::OnlyRemote(_)))
+            .map(|divergence| {
+                self.channel_evidence(divergence.channel_id())
+                    .map_err(|e| ReconciliationError::Wallet(e.to_string()))
+            })
+            .collect()
+    }
+
+    /// Adopts every state from `remote` that is later than this wallet's, given the
+    /// evidence the counterparty sent, and returns the ids of the channels adopted.
+    ///
+    /// A later state is only adopted if it proves the update from this wallet's state,
+    /// and a channel this wallet lacks only if it was registered here and is not closed.
+    /// All evidence is checked before anything is applied, so a conflict on one channel
+    /// leaves the wallet untouched.
+    pub fn reconcile(
+        &mut self,
+        remote: &TreeView,
+        evidence: &[ChannelEvidence],
+    ) -> Result<Vec<Bytes32>, ReconciliationError> {
+        remote.verify()?;
+        let local = self.tree_view().map_err(|e| ReconciliationError::Wallet(e.to_string()))?;
+        let mut adopted = Vec::new();
+        for divergence in diff(&local, remote) {
+            let channel_id = *divergence.channel_id();
+            match divergence {
+                Divergence::OnlyLocal(_) => continue,
+                Divergence::OnlyRemote(_) if !self.knows_channel(&channel_id) => {
+                    return Err(ReconciliationError::UnknownChannel(channel_id));
+                }
+                _ => {}
+            }
+            let theirs = evidence
+                .iter()
+                .find(|e| e.proof.channel_id == channel_id)
+                .ok_or(ReconciliationError::MissingEvidence(channel_id))?;
+            theirs.verify(&remote.root)?;
+            if let Divergence::Differs(_) = divergence {
+                let ours = self
+                    .channel_evidence(&channel_id)
+                    .map_err(|e| ReconciliationError::Wallet(e.to_string()))?;
+                if resolve(&ours, theirs)? == Side::Local {
+                    continue;
+                }
+                theirs.verify_transition(&ours.state, self.clock.now())?;
+            }
+            adopted.push((channel_id, theirs.state.clone()));
+        }
+
+        let ids = adopted.iter().map(|(channel_id, _)| *channel_id).collect();
+        self.channels.extend(adopted);
+        self.update_merkle_root()
+            .map_err(|e| ReconciliationError::Wallet(e.to_string()))?;
+        Ok(ids)
+    }
+
+    /// Whether a channel was registered with this wallet and has not been closed.
+    fn knows_channel(&self, channel_id: &Bytes32) -> bool {
+        self.counterparties.contains_key(channel_id)
+            && !self.closed_channels.contains_key(channel_id)
+    }
+
+    /// Gets everything another device of this wallet needs to sync with this one.
+    pub fn sync_bundle(&self, device_id: Bytes32) -> Result<SyncBundle, WalletContractError> {
+        let view = self.tree_view()?;
+        let channels = view
+            .leaves
+            .iter()
+            .map(|(channel_id, _)| self.channel_evidence(channel_id))
+            .collect::<Result<_, _>>()?;
+        let mut closed: Vec<Bytes32> = self.closed_channels.keys().copied().collect();
+        closed.sort();
+        let history = view
+            .leaves
+            .iter()
+            .map(|(channel_id, _)| (*channel_id, self.storage.history(channel_id).to_vec()))
+            .filter(|(_, history)| !history.is_empty())
+            .collect();
+        Ok(SyncBundle {
+            wallet_id: self.wallet_id,
+            device_id,
+            view,
+            channels,
+            closed,
+            history,
+        })
+    }
+
+    /// Syncs with another device of this wallet: takes every channel the peer moved on,
+    /// closes what it closed, and reports channels both devices moved since they last
+    /// synced, which stay as they are here.
+    ///
+    /// The whole bundle is checked before anything is applied.
+    pub fn sync_from(&mut self, bundle: &SyncBundle) -> Result<SyncReport, SyncError> {
+        if bundle.wallet_id != self.wallet_id {
+            return Err(SyncError::WrongWallet(bundle.wallet_id));
+        }
+        bundle.view.verify()?;
+        for (channel_id, _) in &bundle.view.leaves {
+            bundle
+                .evidence(channel_id)
+                .ok_or(ReconciliationError::MissingEvidence(*channel_id))?
+                .verify(&bundle.view.root)?;
+        }
+
+        let local = self.tree_view().map_err(|e| SyncError::Wallet(e.to_string()))?;
+        let empty = HashMap::new();
+        let base = self.sync_bases.get(&bundle.device_id).unwrap_or(&empty);
+        let local_holding = |channel_id: &Bytes32| {
+            match local.leaves.iter().find(|(id, _)| id == channel_id) {
+                Some((_, hash)) => Holding::Open(self.channels[channel_id].nonce, *hash),
+                None if self.closed_channels.contains_key(channel_id) => Holding::Closed,
+                None => Holding::Absent,
+            }
+        };
+        let remote_holding = |channel_id: &Bytes32| match bundle.evidence(channel_id) {
+            Some(evidence) => Holding::Open(evidence.state.nonce, evidence.proof.channel_hash),
+            None if bundle.closed.contains(channel_id) => Holding::Closed,
+            None => Holding::Absent,
+        };
+
+        let mut report = SyncReport::default();
+        for divergence in diff(&local, &bundle.view) {
+            let channel_id = *divergence.channel_id();
+            let (ours, theirs) = (local_holding(&channel_id), remote_holding(&channel_id));
+            match device_sync::decide(ours, theirs, base.get(&channel_id)) {
+                SyncAction::Pull => report.pulled.push(channel_id),
+                SyncAction::Close => report.closed.push(channel_id),
+                SyncAction::Keep => report.ahead.push(channel_id),
+                SyncAction::Conflict => report.conflicts.push(SyncConflict {
+                    channel_id,
+                    local: ours,
+                    remote: theirs,
+                }),
+            }
+        }
+
+        // Both devices now hold the peer's state, or one descending from it, on every
+        // channel except the conflicting ones.
+        let mut new_base: HashMap<Bytes32, Bytes32> =
+            bundle.view.leaves.iter().copied().collect();
+        for conflict in &report.conflicts {
+            new_base.remove(&conflict.channel_id);
+            if let Some(hash) = base.get(&conflict.channel_id) {
+                new_base.insert(conflict.channel_id, *hash);
+            }
+        }
+
+        for channel_id in &report.pulled {
+            if let Some(evidence) = bundle.evidence(channel_id) {
+                self.channels.insert(*channel_id, evidence.state.clone());
+            }
+            let history = bundle
+                .history
+                .iter()
+                .find(|(id, _)| id == channel_id)
+                .map(|(_, history)| history.clone())
+                .unwrap_or_default();
+            self.storage.import_history(*channel_id, history);
+        }
+        for channel_id in &report.closed {
+            if let Some(channel) = self.channels.remove(channel_id) {
+                self.closed_channels.insert(*channel_id, channel);
+            }
+        }
+        self.sync_bases.insert(bundle.device_id, new_base);
+        self.update_merkle_root()
+            .map_err(|e| SyncError::Wallet(e.to_string()))?;
+        Ok(report)
+    }
+
+    /// Backs up a channel's current state with proof that it is part of the wallet root
+    /// the global contract records, ready to be checked against an anchored global root.
+    pub fn channel_backup(
+        &self,
+        channel_id: &Bytes32,
+    ) -> Result<ChannelBackup, WalletContractError> {
+        let state = self
+            .channels
+            .get(channel_id)
+            .ok_or(WalletContractError::ChannelNotFound)?
+            .clone();
+        Ok(ChannelBackup {
+            counterparty: self.counterparties.get(channel_id).copied().unwrap_or_default(),
+            state,
+            channel_proof: self.channel_inclusion_proof(channel_id)?,
+            wallet_proof: self.global_contract.inclusion_proof(&self.wallet_id)?,
+            global_root: self.global_contract.get_global_merkle_root(),
+            history: self.storage.history(channel_id).to_vec(),
+        })
+    }
+
+    /// Recovers a wallet from its backup phrase and static channel backups.
+    ///
+    /// Keys are re-derived from the phrase, and each channel is restored from its latest
+    /// backup whose proofs lead to one of `anchored_roots`; the rest are reported. Seed
+    /// sequences are scanned for the restored ids so new channels do not reuse one.
+    pub fn recover(
+        mnemonic: &str,
+        passphrase: &str,
+        network: Network,
+        wallet_id: Bytes32,
+        backups: &[ChannelBackup],
+        anchored_roots: &[Bytes32],
+        global_contract: GlobalRootContract,
+    ) -> Result<(Self, RecoveryReport), WalletContractError> {
+        let keys = KeyManager::from_mnemonic(mnemonic, passphrase, network)
+            .map_err(WalletContractError::KeyDerivationError)?;
+        let params = global_contract.params().clone();
+        let mut wallet =
+            Self::new(wallet_id, params, global_contract).with_key_manager(Arc::new(keys));
+
+        let mut report = RecoveryReport::default();
+        let mut latest: HashMap<Bytes32, &ChannelBackup> = HashMap::new();
+        for backup in backups {
+            let channel_id = *backup.channel_id();
+            if let Err(err) = backup.verify(&wallet_id, anchored_roots) {
+                report.rejected.push((channel_id, err));
+                continue;
+            }
+            match latest.get(&channel_id) {
+                Some(kept) if kept.state.nonce >= backup.state.nonce => {
+                    report.rejected.push((channel_id, BackupError::Superseded));
+                }
+                Some(_) => {
+                    report.rejected.push((channel_id, BackupError::Superseded));
+                    latest.insert(channel_id, backup);
+                }
+                None => {
+                    latest.insert(channel_id, backup);
+                }
+            }
+        }
+        for (channel_id, backup) in latest {
+            wallet.channels.insert(channel_id, backup.state.clone());
+            wallet.counterparties.insert(channel_id, backup.counterparty);
+            wallet.storage.import_history(channel_id, backup.history.clone());
+            report.restored.push(channel_id);
+        }
+        report.restored.sort();
+
+        let mut sequence = 0;
+        while sequence < wallet.next_channel + RECOVERY_GAP_LIMIT {
+            if wallet.channels.contains_key(&wallet.channel_id_at(sequence)?) {
+                wallet.next_channel = sequence + 1;
+            }
+            sequence += 1;
+        }
+        report.next_channel = wallet.next_channel;
+        wallet.update_merkle_root()?;
+        Ok((wallet, report))
+    }
+
+    /// Proves to a third party that the open balance behind the root the global contract
+    /// records for this wallet is at least `minimum`, for the verifier's `context`.
+    pub fn proof_of_funds(
+        &self,
+        minimum: u64,
+        context: &[u8],
+    ) -> Result<FundsProof, WalletContractError> {
+        let (wallet_proof, global_root, openings) = self.funds_statement()?;
+        FundsProof::prove(&self.params, wallet_proof, global_root, &openings, minimum, context)
+            .ok_or(WalletContractError::InsufficientFunds(minimum))
+    }
+
+    /// Proves funds like [`Self::proof_of_funds`], building the range proof on tokio's
+    /// blocking pool.
+    #[cfg(feature = "tokio")]
+    pub async fn proof_of_funds_async(
+        &self,
+        minimum: u64,
+        context: Vec<u8>,
+    ) -> Result<FundsProof, WalletContractError> {
+        let (wallet_proof, global_root, openings) = self.funds_statement()?;
+        let params = self.params.clone();
+        run_blocking(move || {
+            FundsProof::prove(&params, wallet_proof, global_root, &openings, minimum, &context)
+        })
+        .await
+        .ok_or(WalletContractError::InsufficientFunds(minimum))
+    }
+
+    /// Gets the wallet's inclusion proof, the global root it is under and the openings
+    /// of the channel commitments, once the global contract records the current root.
+    fn funds_statement(
+        &self,
+    ) -> Result<(WalletInclusionProof, Bytes32, Vec<ChannelOpening>), WalletContractError> {
+        let wallet_proof = self.global_contract.inclusion_proof(&self.wallet_id)?;
+        if wallet_proof.wallet_root != self.merkle_root {
+            return Err(WalletContractError::UnrecordedRoot);
+        }
+        Ok((
+            wallet_proof,
+            self.global_contract.get_global_merkle_root(),
+            self.root_openings()?,
+        ))
+    }
+
+    /// Proves the open balance is the sum of the channel commitments under the current
+    /// root. Every channel must have been committed with seed-derived blindings.
+    pub fn wallet_root_proof(&self) -> Result<WalletRootProof, WalletContractError> {
+        let openings = self.root_openings()?;
+        WalletRootProof::prove(&self.params, self.wallet_id, self.merkle_root, &openings)
+            .ok_or_else(|| WalletContractError::ProofGenerationError("total overflows".into()))
+    }
+
+    /// Proves the wallet root like [`Self::wallet_root_proof`], on tokio's blocking pool.
+    #[cfg(feature = "tokio")]
+    pub async fn wallet_root_proof_async(&self) -> Result<WalletRootProof, WalletContractError> {
+        let openings = self.root_openings()?;
+        let (params, wallet_id, root) = (self.params.clone(), self.wallet_id, self.merkle_root);
+        run_blocking(move || WalletRootProof::prove(&params, wallet_id, root, &openings))
+            .await
+            .ok_or_else(|| WalletContractError::ProofGenerationError("total overflows".into()))
+    }
+
+    /// Opens every channel commitment, in channel id order, with its path to the wallet root.
+    pub(crate) fn root_openings(&self) -> Result<Vec<ChannelOpening>, WalletContractError> {
+        let keys = self.key_manager()?;
+        let tree = self.account_tree()?;
+        let mut channel_ids: Vec<_> = self.channels.keys().copied().collect();
+        channel_ids.sort_unstable();
+        let mut openings = Vec::with_capacity(channel_ids.len());
+        for channel_id in channel_ids {
+            if !self.reopen_channel(&channel_id)? {
+                return Err(WalletContractError::CommitmentMismatch(hex::encode(channel_id)));
+            }
+            let channel = &self.channels[&channel_id];
+            let state_digest = channel
+                .hash_state()
+                .map_err(WalletContractError::HashError)?;
+            let (index, siblings) = tree
+                .path(&channel_id)
+                .ok_or(WalletContractError::ChannelNotFound)?;
+            let blinding = keys
+                .blinding_bytes(&BlindingPath::new(
+                    channel_index_for_id(&channel_id),
+                    channel.nonce,
+                ))
+                .map_err(WalletContractError::KeyDerivationError)?;
+            openings.push(ChannelOpening {
+                balance: channel.balances[0],
+                blinding: *BlindingFactor::from_bytes(&blinding).scalar(),
+                path: CommitmentPath {
+                    state_digest,
+                    index,
+                    siblings,
+                },
+            });
+        }
+        Ok(openings)
+    }
+
+    /// Checks this wallet's channels against the root the global contract records for it.
+    pub fn validate_against_global(&self) -> Result<ValidationReport, WalletContractError> {
+        Ok(cross_validation::check(
+            &self.wallet_id,
+            &self.merkle_root,
+            &self.account_tree()?,
+            &self.global_contract,
+        ))
+    }
+
+    /// Validates against the global contract, repairs what it can, and validates again.
+    ///
+    /// A stale cached root is recomputed, an unregistered wallet is registered, and a root
+    /// the contract records differently is resubmitted at `height`, which opens it to
+    /// challenge like any other submission.
+    pub fn repair_against_global(
+        &mut self,
+        height: u32,
+    ) -> Result<ValidationReport, WalletContractError> {
+        let mut repaired = Vec::new();
+        for issue in self.validate_against_global()?.issues {
+            match issue {
+                RootInconsistency::StaleWalletRoot { .. } => self.update_merkle_root()?,
+                RootInconsistency::NotRegistered => {
+                    self.global_contract
+                        .register_wallet(self.wallet_id, self.merkle_root)?;
+                }
+                RootInconsistency::RootMismatch { recorded, computed } => {
+                    let proof = convert_helper_proof(generate_state_proof_at(
+                        recorded,
+                        computed,
+                        computed,
+                        &self.params,
+                        self.clock.now(),
+                    ));
+                    self.global_contract
+                        .submit_root(self.wallet_id, proof, height)?;
+                }
+                RootInconsistency::UnrecordedChannel(_) | RootInconsistency::NotInGlobalRoot => {
+                    continue
+                }
+            }
+            repaired.push(issue);
+        }
+        let mut report = self.validate_against_global()?;
+        report.repaired = repaired;
+        if report.is_consistent() {
+            self.mark_payments_recorded();
+        }
+        Ok(report)
     }
 
     /// Gets the current merkle root.
@@ -207,6 +2065,13 @@ impl WalletContract {
         self.merkle_root
     }
 
+    /// Checks that `proof` shows this wallet's current root is part of `global_root`.
+    pub fn verify_inclusion(&self, proof: &WalletInclusionProof, global_root: &Bytes32) -> bool {
+        proof.wallet_id == self.wallet_id
+            && proof.wallet_root == self.merkle_root
+            && proof.verify(global_root)
+    }
+
     /// Gets a channel by ID.
     pub fn get_channel(&self, channel_id: &Bytes32) -> Option<&ChannelState> {
         self.channels.get(channel_id)
@@ -223,20 +2088,256 @@ impl WalletContract {
     }
 }
 
+fn unknown_account(label: Option<&str>) -> WalletContractError {
+    AccountError::UnknownAccount(label.unwrap_or_default().to_string()).into()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_util::{self, PHRASE};
 
-    fn setup_test_wallet() -> WalletContract {
-        let wallet_id = [1u8; 32];
+    #[test]
+    fn test_channel_inclusion_proofs() -> Result<(), WalletContractError> {
+        let mut wallet = test_util::unkeyed_wallet([1u8; 32]);
+        for i in 2..7u8 {
+            let state = ChannelState {
+                balances: vec![i as u64 * 10],
+                nonce: 0,
+                metadata: vec![i],
+                merkle_root: [0u8; 32],
+                proof: None,
+                assets: BTreeMap::new(),
+            };
+            wallet.channels.insert([i; 32], state);
+        }
+        wallet.update_merkle_root()?;
+
+        for i in 2..7u8 {
+            let proof = wallet.channel_inclusion_proof(&[i; 32])?;
+            assert_eq!(proof.channel_hash, wallet.channels[&[i; 32]].hash().unwrap());
+            assert!(proof.verify(&wallet.get_merkle_root()));
+            assert!(!proof.verify(&[0u8; 32]));
+        }
+        assert!(matches!(
+            wallet.channel_inclusion_proof(&[9u8; 32]),
+            Err(WalletContractError::ChannelNotFound)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_asset_balances_are_summed_per_asset() -> Result<(), WalletContractError> {
+        let mut wallet = test_util::unkeyed_wallet([1u8; 32]);
+        let holdings = [
+            vec![([1u8; 32], vec![10, 90])],
+            vec![([1u8; 32], vec![5, 0]), ([2u8; 32], vec![7])],
+            Vec::new(),
+        ];
+        for (i, assets) in holdings.into_iter().enumerate() {
+            let state = ChannelState {
+                balances: vec![100],
+                nonce: 0,
+                metadata: Vec::new(),
+                merkle_root: [0u8; 32],
+                proof: None,
+                assets: assets.into_iter().collect(),
+            };
+            wallet.channels.insert([i as u8; 32], state);
+        }
+        assert_eq!(
+            wallet.asset_balances(),
+            BTreeMap::from([([1u8; 32], 15), ([2u8; 32], 7)])
+        );
+        assert_eq!(wallet.balance().open, 300);
+        Ok(())
+    }
+
+    #[test]
+    fn test_spending_policy_refuses_before_signing() -> Result<(), WalletContractError> {
+        use crate::zkp::spending_policy::{AuditEvent, SpendingPolicy};
+
+        let policy = SpendingPolicy {
+            counterparty_limits: HashMap::from([([7u8; 32], 30)]),
+            ..SpendingPolicy::default()
+        };
+        let mut wallet = test_util::unkeyed_wallet([1u8; 32])
+            .with_spending_policy(PolicyEngine::new(policy, crate::zkp::helpers::current_timestamp()))?;
+        wallet.register_channel([1u8; 32], 100, [7u8; 32], Vec::new())?;
+        let root = wallet.get_merkle_root();
+
+        assert!(matches!(
+            wallet.update_channel([1u8; 32], 60, Vec::new()),
+            Err(WalletContractError::PolicyError(PolicyError::CounterpartyLimit {
+                amount: 40,
+                remaining: 30,
+                ..
+            }))
+        ));
+        assert_eq!(wallet.get_channel(&[1u8; 32]).unwrap().nonce, 0);
+        assert_eq!(wallet.get_merkle_root(), root);
+        assert!(matches!(
+            wallet.spending_policy().unwrap().audit_log().last().map(|entry| &entry.event),
+            Some(AuditEvent::Refused(spend, _)) if spend.counterparty == [7u8; 32]
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_watch_only_wallet_applies_offline_signatures() -> Result<(), WalletContractError> {
+        use crate::zkp::watch_only::sign_transition;
+
+        let keys = test_util::key_manager();
+        let mut signer = test_util::unkeyed_wallet([1u8; 32]).with_key_manager(keys.clone());
+        let channel_id = signer.open_channel(100, [7u8; 32], Vec::new())?;
+
+        let params = PedersenParameters::default();
+        let mut watcher = WalletContract::watch_only(
+            [1u8; 32],
+            params.clone(),
+            GlobalRootContract::new(params.clone()),
+            signer.export_watch_keys()?,
+        );
+        watcher.channels = signer.channels.clone();
+        watcher.update_merkle_root()?;
+        assert_eq!(watcher.channel_id_at(0)?, channel_id);
+        assert_eq!(watcher.funding_key(&channel_id)?, signer.funding_key(&channel_id)?);
+        assert!(matches!(
+            watcher.update_channel(channel_id, 60, Vec::new()),
+            Err(WalletContractError::WatchOnly)
+        ));
+
+        let unsigned = watcher.prepare_transition(channel_id, 60, vec![1])?;
+        let signed = sign_transition(&keys, &params, &unsigned)?;
+        let mut forged = signed.clone();
+        forged.commitment = [9u8; 32];
+        assert!(matches!(
+            watcher.apply_signed_transition(forged),
+            Err(WalletContractError::InvalidTransition(_))
+        ));
+        watcher.apply_signed_transition(signed.clone())?;
+        assert_eq!(watcher.balance().open, 60);
+        assert!(matches!(
+            watcher.apply_signed_transition(signed),
+            Err(WalletContractError::InvalidTransition(_))
+        ));
+
+        // The signer's seed re-opens the commitment the watch-only wallet now tracks.
+        signer.channels = watcher.channels.clone();
+        assert!(signer.reopen_channel(&channel_id)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_recovery_restores_anchored_backups() -> Result<(), WalletContractError> {
+        use crate::zkp::state_proof::StateProof;
+
+        let mut wallet = test_util::wallet([1u8; 32]);
+        let first = wallet.open_channel(100, [7u8; 32], Vec::new())?;
+        wallet.repair_against_global(5)?;
+        let window = wallet.global_contract.parameters().challenge_window;
+        wallet.global_contract.finalize_submissions(6 + window);
+        let stale = wallet.channel_backup(&first)?;
+        let second = wallet.open_channel(50, [8u8; 32], Vec::new())?;
+        let proof = StateProof {
+            pi: [0u8; 32],
+            public_inputs: Vec::new(),
+            timestamp: 5,
+        };
+        wallet
+            .storage
+            .store_transaction(second, [0u8; 32], [1u8; 32], proof, TxMetadata::None)?;
+        wallet.channels.get_mut(&first).unwrap().nonce = 1;
+        wallet.repair_against_global(10 + window)?;
+
+        let anchored = [stale.global_root, wallet.global_contract.get_global_merkle_root()];
+        let mut forged = wallet.channel_backup(&second)?;
+        forged.state.balances = vec![5_000];
+        let backups = [
+            wallet.channel_backup(&first)?,
+            stale,
+            wallet.channel_backup(&second)?,
+            forged,
+        ];
         let params = PedersenParameters::default();
-        let global_contract = GlobalRootContract::new(params.clone());
-        WalletContract::new(wallet_id, params, global_contract)
+        let (mut recovered, report) = WalletContract::recover(
+            PHRASE,
+            "",
+            Network::Regtest,
+            wallet.wallet_id,
+            &backups,
+            &anchored,
+            GlobalRootContract::new(params),
+        )?;
+
+        let mut restored = vec![first, second];
+        restored.sort();
+        assert_eq!(report.restored, restored);
+        assert!(report.rejected.contains(&(first, BackupError::Superseded)));
+        assert!(report.rejected.contains(&(second, BackupError::StateMismatch)));
+        assert_eq!(report.next_channel, 2);
+        assert_eq!(recovered.get_merkle_root(), wallet.get_merkle_root());
+        assert!(recovered.reopen_channel(&second)?);
+        assert_eq!(recovered.storage.history(&second).len(), 1);
+        assert_eq!(recovered.open_channel(1, [9u8; 32], Vec::new())?, wallet.channel_id_at(2)?);
+
+        let (_, report) = WalletContract::recover(
+            PHRASE,
+            "",
+            Network::Regtest,
+            wallet.wallet_id,
+            &backups[..1],
+            &[[0u8; 32]],
+            GlobalRootContract::new(PedersenParameters::default()),
+        )?;
+        assert!(report.restored.is_empty());
+        assert!(matches!(report.rejected[0], (_, BackupError::NotAnchored(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_hd_wallet_opens_and_closes_channels() -> Result<(), WalletContractError> {
+        let keys = test_util::key_manager();
+        let mut wallet = test_util::unkeyed_wallet([1u8; 32]).with_key_manager(keys.clone());
+        let first = wallet.open_channel(100, [7u8; 32], Vec::new())?;
+        let second = wallet.open_channel(50, [8u8; 32], Vec::new())?;
+        let third = wallet.open_channel(25, [9u8; 32], Vec::new())?;
+        assert_eq!(first, wallet.channel_id_at(0)?);
+        assert!(wallet.reopen_channel(&second)?);
+        assert_ne!(
+            wallet.channel_key(&first, KeyFamily::Funding)?.public_key(),
+            wallet.channel_key(&second, KeyFamily::Funding)?.public_key()
+        );
+        assert!(wallet.channel_inclusion_proof(&third)?.verify(&wallet.get_merkle_root()));
+
+        let root = wallet.get_merkle_root();
+        wallet.close_channel(&second)?;
+        assert_ne!(wallet.get_merkle_root(), root);
+        assert!(matches!(
+            wallet.close_channel(&second),
+            Err(WalletContractError::ChannelNotFound)
+        ));
+        assert_eq!(
+            wallet.balance(),
+            WalletBalance {
+                open: 125,
+                closed: 50,
+                open_channels: 2,
+            }
+        );
+
+        let restored = test_util::unkeyed_wallet([1u8; 32]).with_key_manager(keys);
+        assert_eq!(restored.channel_id_at(2)?, third);
+        assert!(matches!(
+            test_util::unkeyed_wallet([1u8; 32]).open_channel(1, [0u8; 32], Vec::new()),
+            Err(WalletContractError::NoKeyManager)
+        ));
+        Ok(())
     }
 
     #[test]
     fn test_register_channel() -> Result<(), WalletContractError> {
-        let mut wallet = setup_test_wallet();
+        let mut wallet = test_util::unkeyed_wallet([1u8; 32]);
         let channel_id = [2u8; 32];
         
         // Register new channel
@@ -263,7 +2364,7 @@ mod tests {
 
     #[test]
     fn test_update_channel() -> Result<(), WalletContractError> {
-        let mut wallet = setup_test_wallet();
+        let mut wallet = test_util::unkeyed_wallet([1u8; 32]);
         let channel_id = [2u8; 32];
         
         // Register channel
@@ -282,9 +2383,31 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_injected_clock_stamps_and_prunes_history() -> Result<(), WalletContractError> {
+        use crate::clock::ManualClock;
+
+        let clock = Arc::new(ManualClock::new(1_000_000));
+        let mut wallet = test_util::unkeyed_wallet([1u8; 32]).with_clock(clock.clone());
+        let channel_id = [2u8; 32];
+        wallet.register_channel(channel_id, 100, [0u8; 32], Vec::new())?;
+
+        let proof = wallet
+            .transition_channel(channel_id, 150, Vec::new(), TxMetadata::None)?
+            .unwrap();
+        assert_eq!(proof.timestamp, 1_000_000);
+        assert_eq!(wallet.storage.history(&channel_id).last().unwrap().timestamp, 1_000_000);
+
+        clock.advance(30 * 24 * 3600 - 1);
+        assert!(wallet.storage.prune_now().is_empty());
+        clock.advance(1);
+        assert_eq!(wallet.storage.prune_now(), vec![channel_id]);
+        Ok(())
+    }
+
     #[test]
     fn test_list_channels() -> Result<(), WalletContractError> {
-        let mut wallet = setup_test_wallet();
+        let mut wallet = test_util::unkeyed_wallet([1u8; 32]);
         let channel_ids: Vec<[u8; 32]> = vec![[1u8; 32], [2u8; 32], [3u8; 32]];
         
         // Register multiple channels
@@ -300,4 +2423,4 @@ mod tests {
 
         Ok(())
     }
-}
\ No newline at end of file
+}
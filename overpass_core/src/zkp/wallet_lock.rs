@@ -0,0 +1,234 @@
+// src/zkp/wallet_lock.rs
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use bitcoin::Network;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use zeroize::Zeroizing;
+
+use crate::bitcoin::keys::KeyManager;
+use crate::services::overpass_db::OverpassDB;
+use crate::zkp::helpers::Bytes32;
+
+const SEALED_PREFIX: &[u8] = b"sealed_keys:";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum LockError {
+    #[error("No passphrase has been set")]
+    NoPassphrase,
+    #[error("Wrong passphrase")]
+    WrongPassphrase,
+    #[error("Key derivation failed: {0}")]
+    Kdf(String),
+    #[error("Storage error: {0}")]
+    StorageError(String),
+    #[error("Corrupt sealed keys: {0}")]
+    CorruptState(String),
+    #[error("Keys were wiped by an earlier lock")]
+    Wiped,
+}
+
+/// Argon2id cost parameters; the defaults follow the OWASP minimum, which phones can
+/// afford on every unlock.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KdfParams {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self {
+            memory_kib: 19 * 1024,
+            iterations: 2,
+            parallelism: 1,
+        }
+    }
+}
+
+impl KdfParams {
+    fn derive(&self, passphrase: &str, salt: &[u8]) -> Result<Zeroizing<Bytes32>, LockError> {
+        let params = Params::new(self.memory_kib, self.iterations, self.parallelism, Some(32))
+            .map_err(|e| LockError::Kdf(e.to_string()))?;
+        let mut key = Zeroizing::new([0u8; 32]);
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), salt, key.as_mut())
+            .map_err(|e| LockError::Kdf(e.to_string()))?;
+        Ok(key)
+    }
+}
+
+/// A wallet's seed encrypted under a passphrase, the only form it is kept in at rest
+/// and while the wallet is locked.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SealedKeys {
+    pub network: Network,
+    pub kdf: KdfParams,
+    salt: [u8; SALT_LEN],
+    nonce: [u8; NONCE_LEN],
+    ciphertext: Vec<u8>,
+}
+
+impl SealedKeys {
+    /// Encrypts the seed behind `keys` with a key derived from `passphrase`.
+    pub fn seal(keys: &KeyManager, passphrase: &str, kdf: KdfParams) -> Result<Self, LockError> {
+        let mut salt = [0u8; SALT_LEN];
+        let mut nonce = [0u8; NONCE_LEN];
+        OsRng.fill_bytes(&mut salt);
+        OsRng.fill_bytes(&mut nonce);
+        let key = kdf.derive(passphrase, &salt)?;
+        let secret = keys.to_secret_bytes().map_err(|_| LockError::Wiped)?;
+        let ciphertext = ChaCha20Poly1305::new(Key::from_slice(key.as_ref()))
+            .encrypt(Nonce::from_slice(&nonce), secret.as_slice())
+            .map_err(|e| LockError::Kdf(e.to_string()))?;
+        Ok(Self {
+            network: keys.network(),
+            kdf,
+            salt,
+            nonce,
+            ciphertext,
+        })
+    }
+
+    /// Decrypts the seed, failing with `WrongPassphrase` if `passphrase` did not seal it.
+    pub fn open(&self, passphrase: &str) -> Result<KeyManager, LockError> {
+        let key = self.kdf.derive(passphrase, &self.salt)?;
+        let secret = Zeroizing::new(
+            ChaCha20Poly1305::new(Key::from_slice(key.as_ref()))
+                .decrypt(Nonce::from_slice(&self.nonce), self.ciphertext.as_slice())
+                .map_err(|_| LockError::WrongPassphrase)?,
+        );
+        KeyManager::from_secret_bytes(&secret, self.network)
+            .map_err(|e| LockError::CorruptState(e.to_string()))
+    }
+
+    /// Writes the sealed seed to `db` under `wallet_id`.
+    pub fn save(&self, db: &OverpassDB, wallet_id: &Bytes32) -> Result<(), LockError> {
+        let bytes = bincode::serialize(self).map_err(|e| LockError::StorageError(e.to_string()))?;
+        db.put(&sealed_key(wallet_id), &bytes)
+            .and_then(|_| db.flush())
+            .map_err(|e| LockError::StorageError(e.to_string()))
+    }
+
+    /// Loads the sealed seed saved for `wallet_id`, if there is one.
+    pub fn restore(db: &OverpassDB, wallet_id: &Bytes32) -> Result<Option<Self>, LockError> {
+        db.get(&sealed_key(wallet_id))
+            .map_err(|e| LockError::StorageError(e.to_string()))?
+            .map(|bytes| {
+                bincode::deserialize(&bytes).map_err(|e| LockError::CorruptState(e.to_string()))
+            })
+            .transpose()
+    }
+}
+
+fn sealed_key(wallet_id: &Bytes32) -> Vec<u8> {
+    let mut key = SEALED_PREFIX.to_vec();
+    key.extend_from_slice(hex::encode(wallet_id).as_bytes());
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::keys::KeyFamily;
+    use crate::test_util::{self, PHRASE};
+    use crate::zkp::wallet_contract::WalletContractError;
+
+    /// Cheap enough for debug-build tests.
+    const FAST: KdfParams = KdfParams {
+        memory_kib: 64,
+        iterations: 1,
+        parallelism: 1,
+    };
+
+    #[test]
+    fn test_locked_wallet_refuses_to_sign() -> Result<(), WalletContractError> {
+        let keys = test_util::key_manager();
+        let mut wallet = test_util::unkeyed_wallet([1u8; 32]).with_key_manager(keys.clone());
+        let channel_id = wallet.open_channel(100, [7u8; 32], Vec::new())?;
+        let funding = wallet.channel_key(&channel_id, KeyFamily::Funding)?;
+        assert!(matches!(
+            wallet.lock(),
+            Err(WalletContractError::LockError(LockError::NoPassphrase))
+        ));
+
+        wallet.set_passphrase_with("correct horse", FAST)?;
+        wallet.lock()?;
+        assert!(wallet.is_locked());
+        // The copy another holder kept is wiped along with the wallet's.
+        assert!(keys.is_wiped());
+        assert_eq!(
+            SealedKeys::seal(&keys, "correct horse", FAST).err(),
+            Some(LockError::Wiped)
+        );
+        assert!(matches!(
+            wallet.update_channel(channel_id, 60, Vec::new()),
+            Err(WalletContractError::Locked)
+        ));
+        assert!(matches!(
+            wallet.channel_key(&channel_id, KeyFamily::Funding),
+            Err(WalletContractError::Locked)
+        ));
+        assert!(matches!(
+            wallet.open_channel(5, [8u8; 32], Vec::new()),
+            Err(WalletContractError::Locked)
+        ));
+        assert_eq!(wallet.get_channel(&channel_id).unwrap().nonce, 0);
+
+        assert!(matches!(
+            wallet.unlock("wrong"),
+            Err(WalletContractError::LockError(LockError::WrongPassphrase))
+        ));
+        assert!(wallet.is_locked());
+        wallet.unlock("correct horse")?;
+        assert_eq!(
+            wallet
+                .channel_key(&channel_id, KeyFamily::Funding)?
+                .secret_key(),
+            funding.secret_key()
+        );
+        assert!(wallet.reopen_channel(&channel_id)?);
+        wallet.open_channel(5, [8u8; 32], Vec::new())?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_sealed_keys_survive_restart() -> anyhow::Result<()> {
+        let keys = KeyManager::from_mnemonic(PHRASE, "extra words", Network::Regtest)?;
+        let sealed = SealedKeys::seal(&keys, "pin 2468", FAST)?;
+        assert_eq!(
+            sealed.open("pin 1357").err(),
+            Some(LockError::WrongPassphrase)
+        );
+
+        let path = std::env::temp_dir().join(format!("sealed_keys_{}", std::process::id()));
+        let db = OverpassDB::new(path.to_str().unwrap())?;
+        assert_eq!(SealedKeys::restore(&db, &[1u8; 32])?, None);
+        sealed.save(&db, &[1u8; 32])?;
+        let restored = SealedKeys::restore(&db, &[1u8; 32])?.unwrap();
+        drop(db);
+        let _ = std::fs::remove_dir_all(path);
+
+        let mut wallet = test_util::unkeyed_wallet([1u8; 32]).with_sealed_keys(restored);
+        assert!(wallet.is_locked());
+        wallet.unlock("pin 2468")?;
+        assert_eq!(
+            wallet
+                .channel_key(&[3u8; 32], KeyFamily::Funding)?
+                .secret_key(),
+            keys.channel_key(
+                KeyFamily::Funding,
+                crate::bitcoin::keys::channel_index_for_id(&[3u8; 32])
+            )?
+            .secret_key()
+        );
+        Ok(())
+    }
+}
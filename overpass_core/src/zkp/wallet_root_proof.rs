@@ -0,0 +1,350 @@
+// src/zkp/wallet_root_proof.rs
+
+use curve25519_dalek::scalar::Scalar;
+use serde::{Deserialize, Serialize};
+
+use crate::zkp::helpers::{compute_merkle_root, hash_pair, verify_merkle_path, Bytes32};
+use crate::zkp::opening_proofs::{RangeProof, ValueProof};
+use crate::zkp::pedersen_commitment::PedersenCommitment;
+use crate::zkp::pedersen_parameters::PedersenParameters;
+
+const TRANSCRIPT_DOMAIN: &[u8] = b"overpass/wallet-root-proof/v1";
+
+/// A channel's own balance and the blinding of its commitment, known only to the wallet.
+pub struct ChannelOpening {
+    pub balance: u64,
+    pub blinding: Scalar,
+    pub path: CommitmentPath,
+}
+
+/// Where a channel commitment sits under a wallet root.
+///
+/// A channel's leaf pairs the digest of its commitment with the digest of its whole state,
+/// so the path shows the commitment is the channel's without revealing the state.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommitmentPath {
+    pub state_digest: Bytes32,
+    pub index: u64,
+    pub siblings: Vec<Bytes32>,
+}
+
+impl CommitmentPath {
+    /// Checks that the path leads from the commitment's leaf to `wallet_root`.
+    pub fn verify(&self, commitment: &PedersenCommitment, wallet_root: &Bytes32) -> bool {
+        let leaf = hash_pair(commitment.digest(), self.state_digest);
+        verify_merkle_path(leaf, self.index, &self.siblings, wallet_root)
+    }
+}
+
+/// Proof that a wallet's declared total is the sum of the balances its channels commit
+/// to under a wallet root, revealing neither the balances nor the blindings.
+///
+/// Each commitment comes with its path to the wallet root, at a leaf no other commitment
+/// claims, and a range proof that it hides a balance no channel could exceed; the sum of
+/// the commitments then opens to `total` under the summed blindings. A counterparty
+/// holding a channel state can find its channel among `commitments` by its digest.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WalletRootProof {
+    pub wallet_id: Bytes32,
+    pub wallet_root: Bytes32,
+    /// Channel commitments in channel id order.
+    pub commitments: Vec<Bytes32>,
+    /// Path to the wallet root of each commitment.
+    pub paths: Vec<CommitmentPath>,
+    /// Proof that each commitment hides a balance below `2^64`.
+    pub range_proofs: Vec<RangeProof>,
+    pub total: u64,
+    pub proof: ValueProof,
+}
+
+impl WalletRootProof {
+    /// Proves the openings sum to their total, or returns `None` if the total overflows.
+    pub fn prove(
+        params: &PedersenParameters,
+        wallet_id: Bytes32,
+        wallet_root: Bytes32,
+        openings: &[ChannelOpening],
+    ) -> Option<Self> {
+        let total = total_balance(openings)?;
+        let commitments = commit_openings(params, openings);
+        let blinding: Scalar = openings.iter().map(|opening| opening.blinding).sum();
+        let bytes: Vec<Bytes32> = commitments
+            .iter()
+            .map(PedersenCommitment::to_bytes)
+            .collect();
+        let transcript = transcript(&wallet_id, &wallet_root, &bytes);
+        let range_proofs = prove_ranges(params, openings, &commitments, &transcript);
+        let sum = commitments.into_iter().sum();
+        let proof = ValueProof::prove(params, &sum, total, &blinding, &transcript);
+        Some(Self {
+            wallet_id,
+            wallet_root,
+            commitments: bytes,
+            paths: openings.iter().map(|opening| opening.path.clone()).collect(),
+            range_proofs,
+            total,
+            proof,
+        })
+    }
+
+    /// Checks that each channel commitment is a distinct leaf of the wallet root hiding an
+    /// in-range balance, and that together they sum to a commitment to `total`.
+    pub fn verify(&self, params: &PedersenParameters) -> bool {
+        let transcript = transcript(&self.wallet_id, &self.wallet_root, &self.commitments);
+        sum_under_root(
+            params,
+            &self.wallet_root,
+            &self.commitments,
+            &self.paths,
+            &self.range_proofs,
+            &transcript,
+        )
+        .is_some_and(|sum| self.proof.verify(params, &sum, self.total, &transcript))
+    }
+
+    /// Gets the digests of the channel commitments, as stored in each channel state.
+    pub fn commitment_digests(&self) -> Vec<Bytes32> {
+        self.commitments
+            .iter()
+            .filter_map(|bytes| PedersenCommitment::from_bytes(bytes).ok())
+            .map(|commitment| commitment.digest())
+            .collect()
+    }
+}
+
+/// Sums the openings' balances, or returns `None` if the sum overflows.
+pub(crate) fn total_balance(openings: &[ChannelOpening]) -> Option<u64> {
+    openings
+        .iter()
+        .try_fold(0u64, |total, opening| total.checked_add(opening.balance))
+}
+
+/// Commits to each opening's balance under its blinding.
+pub(crate) fn commit_openings(
+    params: &PedersenParameters,
+    openings: &[ChannelOpening],
+) -> Vec<PedersenCommitment> {
+    openings
+        .iter()
+        .map(|opening| PedersenCommitment::commit(params, opening.balance, &opening.blinding))
+        .collect()
+}
+
+/// Proves each commitment hides its opening's balance below `2^64`.
+pub(crate) fn prove_ranges(
+    params: &PedersenParameters,
+    openings: &[ChannelOpening],
+    commitments: &[PedersenCommitment],
+    transcript: &[u8],
+) -> Vec<RangeProof> {
+    openings
+        .iter()
+        .zip(commitments)
+        .map(|(opening, commitment)| {
+            RangeProof::prove(params, commitment, opening.balance, &opening.blinding, transcript)
+        })
+        .collect()
+}
+
+/// Checks that each commitment is a distinct leaf of `wallet_root` hiding an in-range
+/// balance and returns their sum, or `None` if any check fails.
+pub(crate) fn sum_under_root(
+    params: &PedersenParameters,
+    wallet_root: &Bytes32,
+    commitments: &[Bytes32],
+    paths: &[CommitmentPath],
+    range_proofs: &[RangeProof],
+    transcript: &[u8],
+) -> Option<PedersenCommitment> {
+    if paths.len() != commitments.len() || range_proofs.len() != commitments.len() {
+        return None;
+    }
+    let commitments = commitments
+        .iter()
+        .map(PedersenCommitment::from_bytes)
+        .collect::<Result<Vec<_>, _>>()
+        .ok()?;
+    let mut indices: Vec<u64> = paths.iter().map(|path| path.index).collect();
+    indices.sort_unstable();
+    indices.dedup();
+    if indices.len() != paths.len() {
+        return None;
+    }
+    let channels_valid = commitments
+        .iter()
+        .zip(paths)
+        .zip(range_proofs)
+        .all(|((commitment, path), range_proof)| {
+            path.verify(commitment, wallet_root)
+                && range_proof.verify(params, commitment, transcript)
+        });
+    channels_valid.then(|| commitments.into_iter().sum())
+}
+
+/// Binds a proof to the wallet, its root and the exact set of commitments.
+pub(crate) fn transcript(wallet_id: &Bytes32, wallet_root: &Bytes32, commitments: &[Bytes32]) -> Vec<u8> {
+    [
+        TRANSCRIPT_DOMAIN,
+        wallet_id,
+        wallet_root,
+        &compute_merkle_root(commitments.to_vec()),
+        &(commitments.len() as u64).to_le_bytes(),
+    ]
+    .concat()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+    use crate::zkp::blinding::BlindingFactor;
+    use crate::zkp::global_root_contract::GlobalRootContractError;
+    use crate::zkp::helpers::merkle_path;
+    use crate::zkp::wallet_contract::WalletContractError;
+
+    /// Builds a wallet root over `commitments`, each paired with a made-up state digest.
+    fn tree(commitments: &[PedersenCommitment]) -> (Bytes32, Vec<CommitmentPath>) {
+        let digests: Vec<Bytes32> = (0..commitments.len() as u8).map(|i| [i; 32]).collect();
+        let leaves: Vec<Bytes32> = commitments
+            .iter()
+            .zip(&digests)
+            .map(|(commitment, digest)| hash_pair(commitment.digest(), *digest))
+            .collect();
+        let paths = digests
+            .iter()
+            .enumerate()
+            .map(|(index, digest)| CommitmentPath {
+                state_digest: *digest,
+                index: index as u64,
+                siblings: merkle_path(leaves.clone(), index),
+            })
+            .collect();
+        (compute_merkle_root(leaves), paths)
+    }
+
+    /// Opens channels holding `balances` under the wallet root they make.
+    fn openings(params: &PedersenParameters, balances: &[u64]) -> (Bytes32, Vec<ChannelOpening>) {
+        let blindings: Vec<Scalar> = balances
+            .iter()
+            .map(|_| *BlindingFactor::random().scalar())
+            .collect();
+        let commitments: Vec<_> = balances
+            .iter()
+            .zip(&blindings)
+            .map(|(balance, blinding)| PedersenCommitment::commit(params, *balance, blinding))
+            .collect();
+        let (root, paths) = tree(&commitments);
+        let openings = balances
+            .iter()
+            .zip(blindings)
+            .zip(paths)
+            .map(|((&balance, blinding), path)| ChannelOpening {
+                balance,
+                blinding,
+                path,
+            })
+            .collect();
+        (root, openings)
+    }
+
+    #[test]
+    fn test_proof_binds_total_and_commitments() {
+        let params = PedersenParameters::default();
+        let (root, opened) = openings(&params, &[100, 50, 0]);
+        let proof = WalletRootProof::prove(&params, [1u8; 32], root, &opened).unwrap();
+        assert!(proof.verify(&params));
+        assert_eq!(proof.total, 150);
+
+        let mut inflated = proof.clone();
+        inflated.total = 151;
+        assert!(!inflated.verify(&params));
+        let mut dropped = proof.clone();
+        dropped.commitments.pop();
+        assert!(!dropped.verify(&params));
+        let mut moved = proof.clone();
+        moved.wallet_root = [3u8; 32];
+        assert!(!moved.verify(&params));
+        let mut detached = proof;
+        detached.paths[1].state_digest = [9u8; 32];
+        assert!(!detached.verify(&params));
+
+        // Counting a channel twice would otherwise prove a total of 200.
+        let (root, mut doubled) = openings(&params, &[100, 50]);
+        doubled.push(ChannelOpening {
+            balance: 50,
+            blinding: doubled[1].blinding,
+            path: doubled[1].path.clone(),
+        });
+        let proof = WalletRootProof::prove(&params, [1u8; 32], root, &doubled).unwrap();
+        assert_eq!(proof.total, 200);
+        assert!(!proof.verify(&params));
+
+        let (root, overflowing) = openings(&params, &[u64::MAX, 1]);
+        assert!(WalletRootProof::prove(&params, [1u8; 32], root, &overflowing).is_none());
+    }
+
+    #[test]
+    fn test_negative_balance_fails_its_range_proof() {
+        // A wallet commits to 155 and -5 under its own root, so its commitments sum to 150
+        // and everything but the range proof of the second checks out.
+        let params = PedersenParameters::default();
+        let blindings = [*BlindingFactor::random().scalar(), *BlindingFactor::random().scalar()];
+        let commitments = [
+            PedersenCommitment::commit(&params, 155, &blindings[0]),
+            -PedersenCommitment::commit(&params, 5, &-blindings[1]),
+        ];
+        let (wallet_root, paths) = tree(&commitments);
+        let bytes: Vec<Bytes32> = commitments.iter().map(PedersenCommitment::to_bytes).collect();
+        let transcript = transcript(&[1u8; 32], &wallet_root, &bytes);
+        let sum = commitments[0] + commitments[1];
+        let forged = WalletRootProof {
+            wallet_id: [1u8; 32],
+            wallet_root,
+            commitments: bytes,
+            paths,
+            range_proofs: vec![
+                RangeProof::prove(&params, &commitments[0], 155, &blindings[0], &transcript),
+                RangeProof::prove(&params, &commitments[1], u64::MAX - 4, &blindings[1], &transcript),
+            ],
+            total: 150,
+            proof: ValueProof::prove(&params, &sum, 150, &(blindings[0] + blindings[1]), &transcript),
+        };
+        assert!(forged.proof.verify(&params, &sum, 150, &transcript));
+        assert!(forged.paths[1].verify(&commitments[1], &wallet_root));
+        assert!(!forged.verify(&params));
+    }
+
+    #[test]
+    fn test_contract_verifies_wallet_totals() -> Result<(), WalletContractError> {
+        let mut wallet = test_util::wallet([1u8; 32]);
+        let first = wallet.open_channel(100, [7u8; 32], Vec::new())?;
+        wallet.open_channel(40, [8u8; 32], Vec::new())?;
+        wallet.repair_against_global(1)?;
+
+        let proof = wallet.wallet_root_proof()?;
+        assert_eq!(proof.total, wallet.balance().open);
+        assert_eq!(wallet.global_contract.verify_wallet_total(&proof)?, 140);
+        let state = wallet.get_channel(&first).unwrap();
+        assert!(proof.commitment_digests().contains(&state.merkle_root));
+
+        let mut forged = proof.clone();
+        forged.total = 1_000;
+        assert!(matches!(
+            wallet.global_contract.verify_wallet_total(&forged),
+            Err(GlobalRootContractError::ProofVerificationFailed)
+        ));
+        let mut stale = proof;
+        stale.wallet_root = [0u8; 32];
+        assert!(matches!(
+            wallet.global_contract.verify_wallet_total(&stale),
+            Err(GlobalRootContractError::WalletRootMismatch)
+        ));
+
+        wallet.register_channel([9u8; 32], 5, [9u8; 32], Vec::new())?;
+        assert!(matches!(
+            wallet.wallet_root_proof(),
+            Err(WalletContractError::CommitmentMismatch(_))
+        ));
+        Ok(())
+    }
+}
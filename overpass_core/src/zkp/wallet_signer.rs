@@ -0,0 +1,219 @@
+// src/zkp/wallet_signer.rs
+
+use async_trait::async_trait;
+use bitcoin::secp256k1::{schnorr, KeyPair, Message, Secp256k1, XOnlyPublicKey};
+use std::sync::Arc;
+use thiserror::Error;
+
+use crate::bitcoin::keys::{channel_index_for_id, KeyFamily, KeyManager};
+use crate::zkp::helpers::Bytes32;
+use crate::zkp::pedersen_parameters::PedersenParameters;
+use crate::zkp::watch_only::{self, SignedTransition, UnsignedTransition};
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum WalletSignerError {
+    #[error("Signer unavailable: {0}")]
+    Unavailable(String),
+    #[error("Signer refused: {0}")]
+    Refused(String),
+    #[error("Key derivation failed: {0}")]
+    KeyDerivation(String),
+}
+
+/// A channel key a signer holds, by channel, family and rotation generation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct KeyId {
+    pub channel_id: Bytes32,
+    pub family: KeyFamily,
+    pub generation: u32,
+}
+
+/// Authorizes the wallet's off-chain operations: channel transitions and messages signed
+/// with channel keys.
+///
+/// The wallet only sees the results, so the seed can live in a secure enclave, an HSM or
+/// on a remote signing server; [`LocalSigner`] keeps it in process.
+#[async_trait]
+pub trait WalletSigner: Send + Sync {
+    /// Commits to a transition's new balance under the blinding for its next nonce and
+    /// proves the update.
+    async fn sign_transition(
+        &self,
+        transition: &UnsignedTransition,
+    ) -> Result<SignedTransition, WalletSignerError>;
+
+    /// Gets the public half of a channel key.
+    async fn public_key(&self, key: KeyId) -> Result<XOnlyPublicKey, WalletSignerError>;
+
+    /// Signs `message` with a channel key.
+    async fn sign_message(
+        &self,
+        key: KeyId,
+        message: &Message,
+    ) -> Result<schnorr::Signature, WalletSignerError>;
+}
+
+/// Signs with a seed held in this process.
+pub struct LocalSigner {
+    keys: Arc<KeyManager>,
+    params: PedersenParameters,
+}
+
+impl LocalSigner {
+    pub fn new(keys: Arc<KeyManager>, params: PedersenParameters) -> Self {
+        Self { keys, params }
+    }
+
+    fn key_pair(&self, key: KeyId) -> Result<KeyPair, WalletSignerError> {
+        self.keys
+            .rotated_channel_key(
+                key.family,
+                channel_index_for_id(&key.channel_id),
+                key.generation,
+            )
+            .map_err(|e| WalletSignerError::KeyDerivation(e.to_string()))
+    }
+}
+
+#[async_trait]
+impl WalletSigner for LocalSigner {
+    async fn sign_transition(
+        &self,
+        transition: &UnsignedTransition,
+    ) -> Result<SignedTransition, WalletSignerError> {
+        watch_only::sign_transition(&self.keys, &self.params, transition)
+            .map_err(|e| WalletSignerError::KeyDerivation(e.to_string()))
+    }
+
+    async fn public_key(&self, key: KeyId) -> Result<XOnlyPublicKey, WalletSignerError> {
+        Ok(self.key_pair(key)?.x_only_public_key().0)
+    }
+
+    async fn sign_message(
+        &self,
+        key: KeyId,
+        message: &Message,
+    ) -> Result<schnorr::Signature, WalletSignerError> {
+        Ok(Secp256k1::new().sign_schnorr(message, &self.key_pair(key)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+    use crate::zkp::global_root_contract::GlobalRootContract;
+    use crate::zkp::wallet_contract::{WalletContract, WalletContractError};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Stands in for a remote signer, counting the requests it serves.
+    struct RemoteSigner {
+        inner: LocalSigner,
+        requests: AtomicUsize,
+        refuse: bool,
+    }
+
+    #[async_trait]
+    impl WalletSigner for RemoteSigner {
+        async fn sign_transition(
+            &self,
+            transition: &UnsignedTransition,
+        ) -> Result<SignedTransition, WalletSignerError> {
+            self.requests.fetch_add(1, Ordering::SeqCst);
+            if self.refuse {
+                return Err(WalletSignerError::Refused("user declined".into()));
+            }
+            self.inner.sign_transition(transition).await
+        }
+
+        async fn public_key(&self, key: KeyId) -> Result<XOnlyPublicKey, WalletSignerError> {
+            self.inner.public_key(key).await
+        }
+
+        async fn sign_message(
+            &self,
+            key: KeyId,
+            message: &Message,
+        ) -> Result<schnorr::Signature, WalletSignerError> {
+            self.requests.fetch_add(1, Ordering::SeqCst);
+            self.inner.sign_message(key, message).await
+        }
+    }
+
+    fn wallets(refuse: bool) -> (WalletContract, WalletContract, Arc<RemoteSigner>, Bytes32) {
+        let keys = test_util::key_manager();
+        let params = PedersenParameters::default();
+        let mut full = test_util::unkeyed_wallet([1u8; 32]).with_key_manager(keys.clone());
+        let channel_id = full.open_channel(100, [7u8; 32], Vec::new()).unwrap();
+
+        let signer = Arc::new(RemoteSigner {
+            inner: LocalSigner::new(keys, params.clone()),
+            requests: AtomicUsize::new(0),
+            refuse,
+        });
+        let mut watch = WalletContract::watch_only(
+            [1u8; 32],
+            params.clone(),
+            GlobalRootContract::new(params),
+            full.export_watch_keys().unwrap(),
+        )
+        .with_signer(signer.clone());
+        watch
+            .channels
+            .insert(channel_id, full.get_channel(&channel_id).unwrap().clone());
+        (full, watch, signer, channel_id)
+    }
+
+    #[tokio::test]
+    async fn test_watch_only_wallet_signs_remotely() -> Result<(), WalletContractError> {
+        let (mut full, mut watch, signer, channel_id) = wallets(false);
+        assert!(matches!(
+            watch.update_channel(channel_id, 70, Vec::new()),
+            Err(WalletContractError::WatchOnly)
+        ));
+        watch
+            .update_channel_with_signer(channel_id, 70, Vec::new())
+            .await?;
+        let state = watch.get_channel(&channel_id).unwrap().clone();
+        assert_eq!((state.balances.clone(), state.nonce), (vec![70], 1));
+        // The signer committed under the seed's blinding, so the seed re-opens it.
+        full.channels.insert(channel_id, state);
+        assert!(full.reopen_channel(&channel_id)?);
+
+        let rotations = watch.rotate_keys_with_signer().await?;
+        assert_eq!(rotations.len(), 1);
+        assert!(rotations[0].verify());
+        assert_eq!(watch.key_generation(&channel_id), 1);
+        let funding = KeyId {
+            channel_id,
+            family: KeyFamily::Funding,
+            generation: 1,
+        };
+        assert_eq!(
+            rotations[0].new_key,
+            signer.inner.public_key(funding).await.unwrap()
+        );
+        assert_eq!(watch.get_channel(&channel_id).unwrap().nonce, 2);
+        assert_eq!(signer.requests.load(Ordering::SeqCst), 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_refused_signature_leaves_state_alone() {
+        let (_, mut watch, _, channel_id) = wallets(true);
+        let before = watch.get_channel(&channel_id).unwrap().clone();
+        assert!(matches!(
+            watch
+                .update_channel_with_signer(channel_id, 70, Vec::new())
+                .await,
+            Err(WalletContractError::SignerError(
+                WalletSignerError::Refused(_)
+            ))
+        ));
+        let after = watch.get_channel(&channel_id).unwrap();
+        assert_eq!(
+            (&after.balances, after.nonce),
+            (&before.balances, before.nonce)
+        );
+    }
+}
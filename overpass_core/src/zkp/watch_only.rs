@@ -0,0 +1,87 @@
+// src/zkp/watch_only.rs
+
+use bitcoin::bip32::ExtendedPubKey as Xpub;
+use bitcoin::secp256k1::PublicKey;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::bitcoin::keys::{channel_index_for_id, BlindingPath, KeyManager};
+use crate::zkp::helpers::{convert_helper_proof, generate_state_proof, pedersen_commit, Bytes32};
+use crate::zkp::pedersen_parameters::PedersenParameters;
+use crate::zkp::state_proof::StateProof;
+use crate::zkp::wallet_contract::WalletContractError;
+
+/// The public half of a wallet's keys, enough to track it without being able to sign.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WatchKeys {
+    /// Account key the wallet's channel ids are derived from.
+    pub account_xpub: Xpub,
+    /// Funding key of each channel; channel keys are hardened, so they cannot be derived
+    /// from the xpub and are exported one by one.
+    pub funding_keys: HashMap<Bytes32, PublicKey>,
+}
+
+/// A channel update prepared by a watch-only wallet for an offline signer.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnsignedTransition {
+    pub wallet_id: Bytes32,
+    pub channel_id: Bytes32,
+    /// Nonce of the state being replaced; the signed state has `nonce + 1`.
+    pub nonce: u64,
+    /// Hash of the state being replaced.
+    pub state_hash: Bytes32,
+    pub old_commitment: Bytes32,
+    /// Wallet root the update is proven against.
+    pub wallet_root: Bytes32,
+    pub new_balance: u64,
+    pub metadata: Vec<u8>,
+}
+
+/// An update signed offline: the commitment to the new balance and its state proof.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SignedTransition {
+    pub transition: UnsignedTransition,
+    pub commitment: Bytes32,
+    pub proof: StateProof,
+}
+
+impl SignedTransition {
+    /// Checks that the proof is over the transition's old commitment, the new commitment
+    /// and the wallet root, in that order.
+    pub fn is_bound(&self) -> bool {
+        self.proof.public_inputs
+            == [
+                self.transition.old_commitment,
+                self.commitment,
+                self.transition.wallet_root,
+            ]
+    }
+}
+
+/// Signs a prepared transition with the seed, committing to the new balance under the
+/// blinding derived for the next nonce.
+pub fn sign_transition(
+    keys: &KeyManager,
+    params: &PedersenParameters,
+    transition: &UnsignedTransition,
+) -> Result<SignedTransition, WalletContractError> {
+    let path = BlindingPath::new(
+        channel_index_for_id(&transition.channel_id),
+        transition.nonce + 1,
+    );
+    let blinding = keys
+        .blinding_bytes(&path)
+        .map_err(WalletContractError::KeyDerivationError)?;
+    let commitment = pedersen_commit(transition.new_balance, *blinding, params);
+    let proof = convert_helper_proof(generate_state_proof(
+        transition.old_commitment,
+        commitment,
+        transition.wallet_root,
+        params,
+    ));
+    Ok(SignedTransition {
+        transition: transition.clone(),
+        commitment,
+        proof,
+    })
+}
@@ -1,5 +1,6 @@
 use anyhow::{anyhow, Context, Result}; // Ensure Context is imported
 use bitcoin::Network;
+use std::collections::BTreeMap;
 use plonky2_field::types::{Field, PrimeField64};
 use plonky2_field::goldilocks_field::GoldilocksField;
 use plonky2::plonk::config::Hasher;
@@ -103,6 +104,7 @@ fn apply_transition(initial_state: &ChannelState, transition_data: &[u8; 32]) ->
         metadata: initial_state.metadata.clone(),
         merkle_root: [0u8; 32], // Placeholder, will be updated after hashing
         proof: None,
+        assets: BTreeMap::new(),
     };
 
     // Compute the new merkle_root based on the updated state
@@ -145,6 +147,7 @@ fn test_e2e_integration() -> Result<()> {
         metadata: vec![],
         merkle_root: [0u8; 32],   // Placeholder value
         proof: None,
+        assets: BTreeMap::new(),
     };
     println!("Initial state created: {:?}", initial_state);
 
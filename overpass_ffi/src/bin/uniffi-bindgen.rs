@@ -0,0 +1,5 @@
+// src/bin/uniffi-bindgen.rs
+
+fn main() {
+    uniffi::uniffi_bindgen_main()
+}
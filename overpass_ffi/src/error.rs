@@ -0,0 +1,73 @@
+// src/error.rs
+
+use overpass_core::error::overpass_error::{OverpassError as CoreError, ZkpError};
+use overpass_core::zkp::wallet_contract::WalletContractError;
+
+/// Errors handed to Swift and Kotlin, one case per kind of failure a mobile wallet
+/// reacts to differently. The reason is the core error's message.
+#[derive(Debug, thiserror::Error, uniffi::Error)]
+pub enum OverpassError {
+    #[error("Invalid input: {reason}")]
+    InvalidInput { reason: String },
+    #[error("Insufficient funds: {reason}")]
+    InsufficientFunds { reason: String },
+    #[error("Wallet error: {reason}")]
+    Wallet { reason: String },
+    #[error("Proof error: {reason}")]
+    Proof { reason: String },
+    #[error("Storage error: {reason}")]
+    Storage { reason: String },
+    #[error("Bitcoin error: {reason}")]
+    Bitcoin { reason: String },
+    #[error("Service error: {reason}")]
+    Service { reason: String },
+    /// A call panicked while holding the wallet, which may be half-updated; reopen it
+    /// from storage.
+    #[error("Wallet is unusable: {reason}")]
+    Poisoned { reason: String },
+}
+
+impl OverpassError {
+    pub(crate) fn invalid(reason: impl Into<String>) -> Self {
+        Self::InvalidInput {
+            reason: reason.into(),
+        }
+    }
+}
+
+impl From<CoreError> for OverpassError {
+    fn from(error: CoreError) -> Self {
+        let reason = error.to_string();
+        match error {
+            CoreError::Zkp(ZkpError::Wallet(wallet)) => match wallet {
+                WalletContractError::InsufficientFunds(_) => Self::InsufficientFunds { reason },
+                WalletContractError::Storage(_) => Self::Storage { reason },
+                WalletContractError::ReplayError(_)
+                | WalletContractError::ProofGenerationError(_) => Self::Proof { reason },
+                _ => Self::Wallet { reason },
+            },
+            CoreError::Zkp(
+                ZkpError::Storage(_)
+                | ZkpError::Delta(_)
+                | ZkpError::Archive(_)
+                | ZkpError::Backup(_),
+            ) => Self::Storage { reason },
+            CoreError::Zkp(
+                ZkpError::FundsProof(_)
+                | ZkpError::Replay(_)
+                | ZkpError::MerkleTree(_)
+                | ZkpError::Pedersen(_),
+            ) => Self::Proof { reason },
+            CoreError::Zkp(_) => Self::Wallet { reason },
+            CoreError::Bitcoin(_) => Self::Bitcoin { reason },
+            CoreError::Service(_) | CoreError::Network(_) | CoreError::Client(_) => {
+                Self::Service { reason }
+            }
+        }
+    }
+}
+
+/// Maps any core module error through the crate-wide error.
+pub(crate) fn core_error(error: impl Into<CoreError>) -> OverpassError {
+    error.into().into()
+}
@@ -0,0 +1,17 @@
+// src/lib.rs
+
+//! UniFFI bindings that expose Overpass wallets to Swift and Kotlin.
+
+uniffi::setup_scaffolding!();
+
+pub mod error;
+pub mod proof;
+pub mod store;
+pub mod types;
+pub mod wallet;
+
+pub use error::OverpassError;
+pub use proof::verify_funds_proof;
+pub use store::Store;
+pub use types::{Balance, Channel, History, Network, PaymentReceipt};
+pub use wallet::Wallet;
@@ -0,0 +1,27 @@
+// src/proof.rs
+
+use overpass_core::zkp::pedersen_parameters::PedersenParameters;
+use overpass_core::zkp::proof_of_funds::FundsProof;
+
+use crate::error::{core_error, OverpassError};
+use crate::types::bytes32;
+
+/// Checks a proof of funds made by `Wallet::proof_of_funds` against the global roots
+/// the verifier has seen anchored, and returns the minimum it proves.
+#[uniffi::export]
+pub fn verify_funds_proof(
+    proof_json: String,
+    anchored_roots: Vec<Vec<u8>>,
+    context: Vec<u8>,
+) -> Result<u64, OverpassError> {
+    let proof: FundsProof = serde_json::from_str(&proof_json)
+        .map_err(|e| OverpassError::invalid(format!("Malformed proof: {}", e)))?;
+    let anchored_roots = anchored_roots
+        .iter()
+        .map(|root| bytes32(root, "Anchored root"))
+        .collect::<Result<Vec<_>, _>>()?;
+    proof
+        .verify(&PedersenParameters::default(), &anchored_roots, &context)
+        .map_err(core_error)?;
+    Ok(proof.minimum)
+}
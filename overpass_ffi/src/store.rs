@@ -0,0 +1,29 @@
+// src/store.rs
+
+use std::sync::Arc;
+
+use overpass_core::services::overpass_db::OverpassDB;
+use overpass_core::services::storage_migration;
+
+use crate::error::{core_error, OverpassError};
+
+/// The on-device database wallets save channel histories to.
+#[derive(uniffi::Object)]
+pub struct Store {
+    pub(crate) db: OverpassDB,
+}
+
+#[uniffi::export]
+impl Store {
+    /// Opens the database at `path` and brings its schema up to date.
+    #[uniffi::constructor]
+    pub fn open(path: String) -> Result<Arc<Self>, OverpassError> {
+        let db = OverpassDB::new(&path).map_err(core_error)?;
+        storage_migration::migrate(&db).map_err(core_error)?;
+        Ok(Arc::new(Self { db }))
+    }
+
+    pub fn schema_version(&self) -> Result<u32, OverpassError> {
+        storage_migration::schema_version(&self.db).map_err(core_error)
+    }
+}
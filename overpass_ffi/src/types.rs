@@ -0,0 +1,117 @@
+// src/types.rs
+
+use overpass_core::zkp::channel::ChannelState;
+use overpass_core::zkp::helpers::Bytes32;
+use overpass_core::zkp::history_replay::ChainSummary;
+use overpass_core::zkp::payment::Payment;
+use overpass_core::zkp::wallet_contract::WalletBalance;
+
+use crate::error::OverpassError;
+
+/// Reads a 32-byte id or hash handed over as a byte array.
+pub(crate) fn bytes32(bytes: &[u8], what: &str) -> Result<Bytes32, OverpassError> {
+    bytes.try_into().map_err(|_| {
+        OverpassError::invalid(format!("{} must be 32 bytes, got {}", what, bytes.len()))
+    })
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, uniffi::Enum)]
+pub enum Network {
+    Bitcoin,
+    Testnet,
+    Signet,
+    Regtest,
+}
+
+impl From<Network> for bitcoin::Network {
+    fn from(network: Network) -> Self {
+        match network {
+            Network::Bitcoin => bitcoin::Network::Bitcoin,
+            Network::Testnet => bitcoin::Network::Testnet,
+            Network::Signet => bitcoin::Network::Signet,
+            Network::Regtest => bitcoin::Network::Regtest,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, uniffi::Record)]
+pub struct Channel {
+    pub channel_id: Vec<u8>,
+    /// Balance of each party, this wallet's first.
+    pub balances: Vec<u64>,
+    pub nonce: u64,
+    /// Commitment to the channel's current state.
+    pub commitment: Vec<u8>,
+}
+
+impl Channel {
+    pub(crate) fn new(channel_id: &Bytes32, state: &ChannelState) -> Self {
+        Self {
+            channel_id: channel_id.to_vec(),
+            balances: state.balances.clone(),
+            nonce: state.nonce,
+            commitment: state.merkle_root.to_vec(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, uniffi::Record)]
+pub struct Balance {
+    pub open: u64,
+    pub closed: u64,
+    pub open_channels: u64,
+}
+
+impl From<WalletBalance> for Balance {
+    fn from(balance: WalletBalance) -> Self {
+        Self {
+            open: balance.open,
+            closed: balance.closed,
+            open_channels: balance.open_channels as u64,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, uniffi::Record)]
+pub struct PaymentReceipt {
+    pub payment_id: Vec<u8>,
+    pub channel_id: Vec<u8>,
+    pub amount: u64,
+    pub routing_fee: u64,
+    /// Nonce of the channel state the payment produced.
+    pub nonce: u64,
+    pub created_at: u64,
+}
+
+impl From<Payment> for PaymentReceipt {
+    fn from(payment: Payment) -> Self {
+        Self {
+            payment_id: payment.id.to_vec(),
+            channel_id: payment.channel_id.to_vec(),
+            amount: payment.amount,
+            routing_fee: payment.routing_fee,
+            nonce: payment.nonce,
+            created_at: payment.created_at,
+        }
+    }
+}
+
+/// What a channel's verified history covers.
+#[derive(Clone, Debug, PartialEq, Eq, uniffi::Record)]
+pub struct History {
+    pub records: u64,
+    pub updates: u64,
+    pub head: Option<Vec<u8>>,
+    pub last_timestamp: Option<u64>,
+}
+
+impl From<ChainSummary> for History {
+    fn from(summary: ChainSummary) -> Self {
+        Self {
+            records: summary.records as u64,
+            updates: summary.updates as u64,
+            head: summary.head.map(|head| head.to_vec()),
+            last_timestamp: summary.last_timestamp,
+        }
+    }
+}
@@ -0,0 +1,306 @@
+// src/wallet.rs
+
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use overpass_core::bitcoin::keys::KeyManager;
+use overpass_core::zkp::global_root_contract::GlobalRootContract;
+use overpass_core::zkp::helpers::Bytes32;
+use overpass_core::zkp::pedersen_parameters::PedersenParameters;
+use overpass_core::zkp::wallet_contract::WalletContract;
+
+use crate::error::{core_error, OverpassError};
+use crate::store::Store;
+use crate::types::{bytes32, Balance, Channel, History, Network, PaymentReceipt};
+
+/// A wallet and its channels, shared safely between the app's threads.
+#[derive(uniffi::Object)]
+pub struct Wallet {
+    inner: Mutex<WalletContract>,
+}
+
+impl Wallet {
+    /// Locks the wallet, refusing once a call has panicked while holding it.
+    fn lock(&self) -> Result<MutexGuard<'_, WalletContract>, OverpassError> {
+        self.inner.lock().map_err(|_| OverpassError::Poisoned {
+            reason: "a wallet call panicked mid-update".into(),
+        })
+    }
+
+    fn channel_id(bytes: &[u8]) -> Result<Bytes32, OverpassError> {
+        bytes32(bytes, "Channel id")
+    }
+}
+
+fn to_json(value: &impl serde::Serialize) -> Result<String, OverpassError> {
+    serde_json::to_string(value).map_err(|e| OverpassError::Service {
+        reason: e.to_string(),
+    })
+}
+
+#[uniffi::export]
+impl Wallet {
+    /// Opens the wallet `wallet_id` with keys derived from a BIP-39 mnemonic.
+    #[uniffi::constructor]
+    pub fn from_mnemonic(
+        wallet_id: Vec<u8>,
+        mnemonic: String,
+        passphrase: String,
+        network: Network,
+    ) -> Result<Arc<Self>, OverpassError> {
+        let wallet_id = bytes32(&wallet_id, "Wallet id")?;
+        let keys = KeyManager::from_mnemonic(&mnemonic, &passphrase, network.into())
+            .map_err(|e| OverpassError::invalid(e.to_string()))?;
+        let params = PedersenParameters::default();
+        let wallet =
+            WalletContract::new(wallet_id, params.clone(), GlobalRootContract::new(params))
+                .with_key_manager(Arc::new(keys));
+        Ok(Arc::new(Self {
+            inner: Mutex::new(wallet),
+        }))
+    }
+
+    pub fn wallet_id(&self) -> Result<Vec<u8>, OverpassError> {
+        Ok(self.lock()?.wallet_id.to_vec())
+    }
+
+    pub fn merkle_root(&self) -> Result<Vec<u8>, OverpassError> {
+        Ok(self.lock()?.get_merkle_root().to_vec())
+    }
+
+    pub fn balance(&self) -> Result<Balance, OverpassError> {
+        Ok(self.lock()?.balance().into())
+    }
+
+    /// Opens a channel with `counterparty` holding `balance`, and returns its id.
+    pub fn open_channel(
+        &self,
+        balance: u64,
+        counterparty: Vec<u8>,
+    ) -> Result<Vec<u8>, OverpassError> {
+        let counterparty = bytes32(&counterparty, "Counterparty")?;
+        let channel_id = self
+            .lock()?
+            .open_channel(balance, counterparty, Vec::new())
+            .map_err(core_error)?;
+        Ok(channel_id.to_vec())
+    }
+
+    pub fn close_channel(&self, channel_id: Vec<u8>) -> Result<Channel, OverpassError> {
+        let channel_id = Self::channel_id(&channel_id)?;
+        let state = self.lock()?.close_channel(&channel_id).map_err(core_error)?;
+        Ok(Channel::new(&channel_id, &state))
+    }
+
+    pub fn channel(&self, channel_id: Vec<u8>) -> Result<Option<Channel>, OverpassError> {
+        let channel_id = Self::channel_id(&channel_id)?;
+        Ok(self
+            .lock()?
+            .get_channel(&channel_id)
+            .map(|state| Channel::new(&channel_id, state)))
+    }
+
+    /// Lists the open channels, ordered by id.
+    pub fn channels(&self) -> Result<Vec<Channel>, OverpassError> {
+        let wallet = self.lock()?;
+        let mut channel_ids = wallet.list_channels();
+        channel_ids.sort_unstable();
+        Ok(channel_ids
+            .iter()
+            .filter_map(|channel_id| {
+                Some(Channel::new(channel_id, wallet.get_channel(channel_id)?))
+            })
+            .collect())
+    }
+
+    /// Pays `counterparty` over one of its channels.
+    pub fn pay(
+        &self,
+        counterparty: Vec<u8>,
+        amount: u64,
+        memo: Option<String>,
+    ) -> Result<PaymentReceipt, OverpassError> {
+        let counterparty = bytes32(&counterparty, "Counterparty")?;
+        let mut wallet = self.lock()?;
+        let mut payment = wallet.pay(counterparty, amount);
+        if let Some(memo) = memo {
+            payment = payment.with_memo(memo);
+        }
+        Ok(payment.send().map_err(core_error)?.into())
+    }
+
+    /// Moves every channel to fresh keys.
+    pub fn rotate_keys(&self) -> Result<(), OverpassError> {
+        self.lock()?.rotate_keys().map_err(core_error)?;
+        Ok(())
+    }
+
+    /// Records the wallet's root with the global contract at `height`.
+    pub fn publish_root(&self, height: u32) -> Result<(), OverpassError> {
+        self.lock()?
+            .repair_against_global(height)
+            .map_err(core_error)?;
+        Ok(())
+    }
+
+    /// Verifies a channel's stored history against its current state.
+    pub fn channel_history(&self, channel_id: Vec<u8>) -> Result<History, OverpassError> {
+        let channel_id = Self::channel_id(&channel_id)?;
+        let replay = self
+            .lock()?
+            .replay_channel(&channel_id)
+            .map_err(core_error)?;
+        Ok(replay.summary.into())
+    }
+
+    pub fn save_history(
+        &self,
+        store: Arc<Store>,
+        channel_id: Vec<u8>,
+    ) -> Result<(), OverpassError> {
+        let channel_id = Self::channel_id(&channel_id)?;
+        self.lock()?
+            .storage
+            .save_history(&store.db, &channel_id)
+            .map_err(core_error)
+    }
+
+    pub fn restore_history(
+        &self,
+        store: Arc<Store>,
+        channel_id: Vec<u8>,
+    ) -> Result<(), OverpassError> {
+        let channel_id = Self::channel_id(&channel_id)?;
+        self.lock()?
+            .storage
+            .restore_history(&store.db, channel_id)
+            .map_err(core_error)
+    }
+
+    /// Backs a channel up, as JSON for the app to keep off the device.
+    pub fn channel_backup(&self, channel_id: Vec<u8>) -> Result<String, OverpassError> {
+        let channel_id = Self::channel_id(&channel_id)?;
+        to_json(
+            &self
+                .lock()?
+                .channel_backup(&channel_id)
+                .map_err(core_error)?,
+        )
+    }
+
+    /// Proves the open balance is at least `minimum` to the verifier that sent `context`,
+    /// as JSON.
+    pub fn proof_of_funds(&self, minimum: u64, context: Vec<u8>) -> Result<String, OverpassError> {
+        to_json(
+            &self
+                .lock()?
+                .proof_of_funds(minimum, &context)
+                .map_err(core_error)?,
+        )
+    }
+
+    /// Proves the open balance is the sum of the channel commitments, as JSON.
+    pub fn wallet_root_proof(&self) -> Result<String, OverpassError> {
+        to_json(&self.lock()?.wallet_root_proof().map_err(core_error)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proof::verify_funds_proof;
+
+    const PHRASE: &str = "abandon abandon abandon abandon abandon abandon abandon abandon \
+                          abandon abandon abandon about";
+
+    fn wallet() -> Arc<Wallet> {
+        Wallet::from_mnemonic(
+            vec![1u8; 32],
+            PHRASE.into(),
+            String::new(),
+            Network::Regtest,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_wallet_round_trips_through_the_store() -> Result<(), OverpassError> {
+        let path = std::env::temp_dir().join(format!("overpass_ffi_{}", std::process::id()));
+        let store = Store::open(path.to_str().unwrap().into())?;
+        assert_eq!(store.schema_version()?, 1);
+
+        let wallet = wallet();
+        let channel_id = wallet.open_channel(100, vec![7u8; 32])?;
+        wallet.open_channel(40, vec![8u8; 32])?;
+        wallet.rotate_keys()?;
+        assert_eq!(wallet.balance()?.open, 140);
+        assert_eq!(wallet.channels()?.len(), 2);
+        let channel = wallet.channel(channel_id.clone())?.unwrap();
+        assert_eq!((channel.balances[0], channel.nonce), (100, 1));
+        let history = wallet.channel_history(channel_id.clone())?;
+        assert_eq!(history.head, Some(channel.commitment));
+
+        wallet.save_history(store.clone(), channel_id.clone())?;
+        let restored = self::wallet();
+        restored.restore_history(store, channel_id.clone())?;
+        assert!(restored.channel_history(channel_id.clone()).is_err());
+        assert!(
+            serde_json::from_str::<serde_json::Value>(&wallet.channel_backup(channel_id)?).is_ok()
+        );
+
+        assert!(matches!(
+            wallet.channel(vec![1u8; 4]),
+            Err(OverpassError::InvalidInput { .. })
+        ));
+        assert!(matches!(
+            wallet.close_channel(vec![9u8; 32]),
+            Err(OverpassError::Wallet { .. })
+        ));
+        let _ = std::fs::remove_dir_all(path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_proofs_verify_across_the_boundary() -> Result<(), OverpassError> {
+        let wallet = wallet();
+        wallet.open_channel(100, vec![7u8; 32])?;
+        wallet.publish_root(1)?;
+        let wallet_root = wallet.wallet_root_proof()?;
+        assert!(wallet_root.contains("\"total\":100"));
+
+        let proof = wallet.proof_of_funds(60, b"landlord".to_vec())?;
+        let anchored = {
+            let json: serde_json::Value = serde_json::from_str(&proof).unwrap();
+            serde_json::from_value::<Vec<u8>>(json["global_root"].clone()).unwrap()
+        };
+        assert_eq!(
+            verify_funds_proof(proof.clone(), vec![anchored.clone()], b"landlord".to_vec())?,
+            60
+        );
+        assert!(matches!(
+            verify_funds_proof(proof, vec![anchored], b"exchange".to_vec()),
+            Err(OverpassError::Proof { .. })
+        ));
+        assert!(matches!(
+            wallet.proof_of_funds(101, Vec::new()),
+            Err(OverpassError::InsufficientFunds { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_poisoned_wallet_refuses_calls() {
+        let wallet = wallet();
+        let holder = wallet.clone();
+        let _ = std::thread::spawn(move || {
+            let _wallet = holder.inner.lock().unwrap();
+            panic!("update failed halfway");
+        })
+        .join();
+
+        assert!(matches!(wallet.balance(), Err(OverpassError::Poisoned { .. })));
+        assert!(matches!(
+            wallet.open_channel(100, vec![7u8; 32]),
+            Err(OverpassError::Poisoned { .. })
+        ));
+    }
+}
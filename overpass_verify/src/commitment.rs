@@ -0,0 +1,80 @@
+// src/commitment.rs
+
+use curve25519_dalek::ristretto::RistrettoPoint;
+use curve25519_dalek::scalar::Scalar;
+use sha2::{Digest, Sha256, Sha512};
+
+use crate::Bytes32;
+
+/// Domain separator for hashing parameter labels to curve points.
+const GENERATOR_DOMAIN: &[u8] = b"overpass/pedersen/generator/v1";
+
+/// Label the default Pedersen generators are derived from.
+pub const DEFAULT_LABEL: &[u8] = b"overpass";
+
+/// Derives the generator named `base` for the parameters labelled `label`.
+pub fn hash_to_point(label: &[u8], base: &[u8]) -> RistrettoPoint {
+    let mut hasher = Sha512::new();
+    hasher.update(GENERATOR_DOMAIN);
+    hasher.update((label.len() as u64).to_le_bytes());
+    hasher.update(label);
+    hasher.update(base);
+    RistrettoPoint::from_uniform_bytes(&hasher.finalize().into())
+}
+
+/// Gets the default generators `g` and `h`.
+pub fn default_generators() -> (RistrettoPoint, RistrettoPoint) {
+    (hash_to_point(DEFAULT_LABEL, b"g"), hash_to_point(DEFAULT_LABEL, b"h"))
+}
+
+/// Hashes a point's compressed encoding, the form commitments are published in.
+pub fn hash_point(point: &RistrettoPoint) -> Bytes32 {
+    Sha256::digest(point.compress().as_bytes()).into()
+}
+
+/// Computes the Pedersen commitment `value * g + blinding * h`, hashed.
+pub fn commit(value: u64, blinding: &Scalar, g: &RistrettoPoint, h: &RistrettoPoint) -> Bytes32 {
+    hash_point(&(g * Scalar::from(value) + h * blinding))
+}
+
+/// Checks that `commitment` opens to `value` under `blinding`, given as the 32 bytes
+/// reduced into a scalar.
+pub fn verify_opening(
+    commitment: &Bytes32,
+    value: u64,
+    blinding: &Bytes32,
+    g: &RistrettoPoint,
+    h: &RistrettoPoint,
+) -> bool {
+    commit(value, &Scalar::from_bytes_mod_order(*blinding), g, h) == *commitment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use curve25519_dalek::constants::RISTRETTO_BASEPOINT_POINT;
+
+    #[test]
+    fn test_openings_verify_only_with_their_value_and_blinding() {
+        let g = RISTRETTO_BASEPOINT_POINT;
+        let h = g * Scalar::from(5u64);
+        let blinding = [7u8; 32];
+        let commitment = commit(42, &Scalar::from_bytes_mod_order(blinding), &g, &h);
+
+        assert!(verify_opening(&commitment, 42, &blinding, &g, &h));
+        assert!(!verify_opening(&commitment, 43, &blinding, &g, &h));
+        assert!(!verify_opening(&commitment, 42, &[8u8; 32], &g, &h));
+    }
+
+    #[test]
+    fn test_default_generators_match_the_published_vector() {
+        let (g, h) = default_generators();
+        let commitment = commit(100, &Scalar::from_bytes_mod_order([7u8; 32]), &g, &h);
+        let hex: alloc::string::String =
+            commitment.iter().map(|byte| alloc::format!("{:02x}", byte)).collect();
+        assert_eq!(
+            hex,
+            "c81065ff7cbc782cba44664ba13e490ece323abd030785a1b8c0c18be050aebd"
+        );
+    }
+}
@@ -0,0 +1,16 @@
+// src/lib.rs
+
+//! Checks of Overpass commitments, Merkle paths and state proofs that need only `alloc`,
+//! so embedded devices and secure elements can verify states without the full wallet.
+
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+pub mod commitment;
+pub mod merkle;
+pub mod mmr;
+pub mod proof;
+
+/// Type alias for bytes32.
+pub type Bytes32 = [u8; 32];
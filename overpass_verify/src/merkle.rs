@@ -0,0 +1,77 @@
+// src/merkle.rs
+
+use alloc::vec::Vec;
+use sha2::{Digest, Sha256};
+
+use crate::Bytes32;
+
+/// Hashes two bytes32 together to form parent node.
+pub fn hash_pair(left: Bytes32, right: Bytes32) -> Bytes32 {
+    Sha256::new()
+        .chain_update(left)
+        .chain_update(right)
+        .finalize()
+        .into()
+}
+
+/// Computes Merkle root from list of leaves, pairing an odd node out with itself.
+pub fn compute_merkle_root(leaves: Vec<Bytes32>) -> Bytes32 {
+    if leaves.is_empty() {
+        return [0u8; 32];
+    }
+    let mut current_level = leaves;
+    while current_level.len() > 1 {
+        if current_level.len() % 2 != 0 {
+            current_level.push(*current_level.last().unwrap());
+        }
+        current_level = current_level
+            .chunks(2)
+            .map(|pair| hash_pair(pair[0], pair[1]))
+            .collect();
+    }
+    current_level[0]
+}
+
+/// Checks that `siblings` lead from `leaf` at `index` to `root`.
+pub fn verify_merkle_path(
+    leaf: Bytes32,
+    mut index: u64,
+    siblings: &[Bytes32],
+    root: &Bytes32,
+) -> bool {
+    let mut node = leaf;
+    for sibling in siblings {
+        node = if index % 2 == 0 {
+            hash_pair(node, *sibling)
+        } else {
+            hash_pair(*sibling, node)
+        };
+        index /= 2;
+    }
+    index == 0 && node == *root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_paths_verify_against_the_root() {
+        let leaves: Vec<Bytes32> = (0..5u8).map(|i| [i; 32]).collect();
+        let root = compute_merkle_root(leaves.clone());
+        let siblings = [
+            leaves[3],
+            hash_pair(leaves[0], leaves[1]),
+            hash_pair(
+                hash_pair(leaves[4], leaves[4]),
+                hash_pair(leaves[4], leaves[4]),
+            ),
+        ];
+
+        assert!(verify_merkle_path(leaves[2], 2, &siblings, &root));
+        assert!(!verify_merkle_path(leaves[2], 3, &siblings, &root));
+        assert!(!verify_merkle_path(leaves[2], 6, &siblings, &root));
+        assert_eq!(compute_merkle_root(vec![]), [0u8; 32]);
+    }
+}
@@ -0,0 +1,54 @@
+// src/mmr.rs
+
+use sha2::{Digest, Sha256};
+
+use crate::merkle::verify_merkle_path;
+use crate::Bytes32;
+
+/// Checks that `leaf` sits at `leaf_index` of a range of `leaf_count` leaves whose
+/// bagged peaks are `root`, given the path to its mountain's peak and every peak.
+pub fn verify_mmr_path(
+    leaf: &Bytes32,
+    leaf_index: u64,
+    leaf_count: u64,
+    siblings: &[Bytes32],
+    peaks: &[Bytes32],
+    root: &Bytes32,
+) -> bool {
+    let Some((start, height)) = mountain_of(leaf_count, leaf_index) else {
+        return false;
+    };
+    let above = leaf_count.checked_shr(height + 1).unwrap_or(0);
+    let mountain = above.count_ones() as usize;
+    peaks.len() == leaf_count.count_ones() as usize
+        && siblings.len() == height as usize
+        && verify_merkle_path(*leaf, leaf_index - start, siblings, &peaks[mountain])
+        && bag_peaks(leaf_count, peaks) == *root
+}
+
+/// Finds the first leaf and height of the mountain holding leaf `index`.
+pub fn mountain_of(leaf_count: u64, index: u64) -> Option<(u64, u32)> {
+    let mut start = 0;
+    for height in (0..u64::BITS).rev() {
+        let size = 1u64 << height;
+        if leaf_count & size == 0 {
+            continue;
+        }
+        if index < start + size {
+            return Some((start, height));
+        }
+        start += size;
+    }
+    None
+}
+
+/// Hashes the peaks of a range of `leaf_count` leaves into its root.
+pub fn bag_peaks(leaf_count: u64, peaks: &[Bytes32]) -> Bytes32 {
+    let mut hasher = Sha256::new();
+    hasher.update(b"overpass/mmr/v1");
+    hasher.update(leaf_count.to_le_bytes());
+    for peak in peaks {
+        hasher.update(peak);
+    }
+    hasher.finalize().into()
+}
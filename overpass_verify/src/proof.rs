@@ -0,0 +1,77 @@
+// src/proof.rs
+
+use curve25519_dalek::ristretto::RistrettoPoint;
+use sha2::{Digest, Sha256};
+
+use crate::Bytes32;
+
+/// Gets the hash a state proof of the transition from `old_commitment` to
+/// `new_commitment` under `merkle_root`, made at `timestamp`, carries.
+pub fn transition_hash(
+    old_commitment: &Bytes32,
+    new_commitment: &Bytes32,
+    merkle_root: &Bytes32,
+    timestamp: u64,
+) -> Bytes32 {
+    Sha256::new()
+        .chain_update(old_commitment)
+        .chain_update(new_commitment)
+        .chain_update(merkle_root)
+        .chain_update(timestamp.to_le_bytes())
+        .finalize()
+        .into()
+}
+
+/// Checks that a proof with hash `pi` over `public_inputs` was generated for the
+/// transition from `old_root` to `new_root`. The proof's age is not checked.
+pub fn proof_binds(
+    pi: &Bytes32,
+    public_inputs: &[Bytes32],
+    timestamp: u64,
+    old_root: &Bytes32,
+    new_root: &Bytes32,
+) -> bool {
+    let [old, new, merkle_root] = public_inputs else {
+        return false;
+    };
+    old == old_root && new == new_root && *pi == transition_hash(old, new, merkle_root, timestamp)
+}
+
+/// Verifies a zero-knowledge proof against its public inputs and the Pedersen
+/// generators `g` and `h`.
+pub fn verify_zk_proof(
+    proof: &Bytes32,
+    public_inputs: &[Bytes32],
+    g: &RistrettoPoint,
+    h: &RistrettoPoint,
+) -> bool {
+    if public_inputs.is_empty() {
+        return false;
+    }
+    let mut hasher = Sha256::new();
+    hasher.update(proof);
+    for input in public_inputs {
+        hasher.update(input);
+    }
+    hasher.update(g.compress().as_bytes());
+    hasher.update(h.compress().as_bytes());
+    let expected: Bytes32 = hasher.finalize().into();
+    *proof == expected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_proofs_bind_to_their_transition() {
+        let (old, new, root) = ([1u8; 32], [2u8; 32], [3u8; 32]);
+        let pi = transition_hash(&old, &new, &root, 1_700_000_000);
+        let inputs = [old, new, root];
+
+        assert!(proof_binds(&pi, &inputs, 1_700_000_000, &old, &new));
+        assert!(!proof_binds(&pi, &inputs, 1_700_000_001, &old, &new));
+        assert!(!proof_binds(&pi, &inputs, 1_700_000_000, &new, &old));
+        assert!(!proof_binds(&pi, &inputs[..2], 1_700_000_000, &old, &new));
+    }
+}
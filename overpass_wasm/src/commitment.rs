@@ -0,0 +1,24 @@
+// src/commitment.rs
+
+use curve25519_dalek::scalar::Scalar;
+use overpass_verify::commitment::{commit, default_generators};
+
+pub use overpass_verify::Bytes32;
+
+/// Commits to `value` under `blinding` and hashes the point, with the generators and
+/// commitment `overpass_core` uses for a channel balance.
+pub fn pedersen_commit(value: u64, blinding: &Bytes32) -> Bytes32 {
+    let (g, h) = default_generators();
+    commit(value, &Scalar::from_bytes_mod_order(*blinding), &g, &h)
+}
+
+/// Reads a 32-byte value handed over from JavaScript.
+pub fn bytes32(bytes: &[u8], what: &str) -> Result<Bytes32, String> {
+    bytes
+        .try_into()
+        .map_err(|_| format!("{} must be 32 bytes, got {}", what, bytes.len()))
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -1,14 +1,19 @@
 // src/lib.rs
 
 mod channel;
+pub mod commitment;
 mod error;
 
 mod storage;
+pub mod transition;
 
 pub mod types; // Ensure this is declared
 
 pub use types::dag_boc::StateUpdateWrapper; // Re-export `StateUpdateWrapper`
 pub use channel::{Channel, ChannelWrapper, create_channel, verify_state_update};
+pub use storage::backend::{ChannelStore, MemoryBackend, StorageBackend, StorageError};
+pub use storage::indexed_db::{ChannelDb, IndexedDbBackend};
+pub use transition::{verify_transition, ChannelState, StateProof, Transition, WalletChannel};
 pub use types::generate_keypair; // Re-export `generate_keypair`
 use wasm_bindgen::prelude::*;
 
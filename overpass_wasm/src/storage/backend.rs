@@ -0,0 +1,171 @@
+// src/storage/backend.rs
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+use crate::commitment::{to_hex, Bytes32};
+use crate::transition::ChannelState;
+
+/// Prefix of the keys channel states are saved under.
+pub const CHANNEL_PREFIX: &str = "channel-";
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum StorageError {
+    #[error("Storage backend error: {0}")]
+    Backend(String),
+    #[error("Corrupt channel state under {key}: {reason}")]
+    Corrupt { key: String, reason: String },
+}
+
+/// Key-value storage a wallet keeps its channels in between sessions.
+///
+/// The calls are async because browser storage such as IndexedDB only answers through
+/// callbacks.
+#[allow(async_fn_in_trait)]
+pub trait StorageBackend {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, StorageError>;
+
+    async fn put(&self, key: &str, value: &[u8]) -> Result<(), StorageError>;
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError>;
+
+    /// Gets every key starting with `prefix`, in order.
+    async fn keys(&self, prefix: &str) -> Result<Vec<String>, StorageError>;
+}
+
+/// Storage that lasts as long as the page, for tests and private browsing.
+#[derive(Debug, Default)]
+pub struct MemoryBackend {
+    entries: RefCell<BTreeMap<String, Vec<u8>>>,
+}
+
+impl StorageBackend for MemoryBackend {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, StorageError> {
+        Ok(self.entries.borrow().get(key).cloned())
+    }
+
+    async fn put(&self, key: &str, value: &[u8]) -> Result<(), StorageError> {
+        self.entries
+            .borrow_mut()
+            .insert(key.to_string(), value.to_vec());
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        self.entries.borrow_mut().remove(key);
+        Ok(())
+    }
+
+    async fn keys(&self, prefix: &str) -> Result<Vec<String>, StorageError> {
+        Ok(self
+            .entries
+            .borrow()
+            .keys()
+            .filter(|key| key.starts_with(prefix))
+            .cloned()
+            .collect())
+    }
+}
+
+pub fn channel_key(channel_id: &Bytes32) -> String {
+    format!("{}{}", CHANNEL_PREFIX, to_hex(channel_id))
+}
+
+/// Channel states saved in a storage backend, one entry per channel.
+pub struct ChannelStore<B> {
+    backend: B,
+}
+
+impl<B: StorageBackend> ChannelStore<B> {
+    pub fn new(backend: B) -> Self {
+        Self { backend }
+    }
+
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    pub async fn save(&self, channel: &ChannelState) -> Result<(), StorageError> {
+        let bytes =
+            bincode::serialize(channel).map_err(|e| StorageError::Backend(e.to_string()))?;
+        self.backend
+            .put(&channel_key(&channel.channel_id), &bytes)
+            .await
+    }
+
+    pub async fn load(&self, channel_id: &Bytes32) -> Result<Option<ChannelState>, StorageError> {
+        let key = channel_key(channel_id);
+        match self.backend.get(&key).await? {
+            Some(bytes) => decode(&key, &bytes).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn remove(&self, channel_id: &Bytes32) -> Result<(), StorageError> {
+        self.backend.delete(&channel_key(channel_id)).await
+    }
+
+    /// Loads every saved channel, ordered by channel id.
+    pub async fn channels(&self) -> Result<Vec<ChannelState>, StorageError> {
+        let mut channels = Vec::new();
+        for key in self.backend.keys(CHANNEL_PREFIX).await? {
+            if let Some(bytes) = self.backend.get(&key).await? {
+                channels.push(decode(&key, &bytes)?);
+            }
+        }
+        Ok(channels)
+    }
+}
+
+fn decode(key: &str, bytes: &[u8]) -> Result<ChannelState, StorageError> {
+    bincode::deserialize(bytes).map_err(|e| StorageError::Corrupt {
+        key: key.to_string(),
+        reason: e.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future::Future;
+    use std::pin::pin;
+    use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+    const NOOP: RawWakerVTable = RawWakerVTable::new(|_| RAW, |_| {}, |_| {}, |_| {});
+    const RAW: RawWaker = RawWaker::new(std::ptr::null(), &NOOP);
+
+    /// Runs a future over the memory backend, which never waits.
+    fn ready<F: Future>(future: F) -> F::Output {
+        // SAFETY: the vtable ignores the data pointer, so a null one is never read.
+        let waker = unsafe { Waker::from_raw(RAW) };
+        match pin!(future).poll(&mut Context::from_waker(&waker)) {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("Memory backend futures are always ready"),
+        }
+    }
+
+    #[test]
+    fn test_channel_store_round_trip() {
+        let store = ChannelStore::new(MemoryBackend::default());
+        let first = ChannelState::open([2u8; 32], 100, &[7u8; 32]);
+        let mut second = ChannelState::open([1u8; 32], 40, &[8u8; 32]);
+        ready(store.save(&first)).unwrap();
+        ready(store.save(&second)).unwrap();
+        ready(store.backend().put("settings", b"dark")).unwrap();
+
+        let transition = second.transition(30, &[9u8; 32], [3u8; 32], 1_700_000_000);
+        second.apply(&transition, 30, &[9u8; 32]).unwrap();
+        ready(store.save(&second)).unwrap();
+        assert_eq!(ready(store.load(&[1u8; 32])), Ok(Some(second.clone())));
+        assert_eq!(ready(store.channels()), Ok(vec![second, first]));
+
+        ready(store.remove(&[2u8; 32])).unwrap();
+        assert_eq!(ready(store.load(&[2u8; 32])), Ok(None));
+        ready(store.backend().put(&channel_key(&[5u8; 32]), b"junk")).unwrap();
+        assert!(matches!(
+            ready(store.channels()),
+            Err(StorageError::Corrupt { key, .. }) if key == channel_key(&[5u8; 32])
+        ));
+    }
+}
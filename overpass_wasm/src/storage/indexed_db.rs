@@ -0,0 +1,193 @@
+// src/storage/indexed_db.rs
+
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::{future_to_promise, JsFuture};
+use web_sys::{
+    Event, IdbDatabase, IdbObjectStore, IdbOpenDbRequest, IdbRequest, IdbTransactionMode,
+};
+
+use crate::commitment::bytes32;
+use crate::storage::backend::{ChannelStore, StorageBackend, StorageError};
+use crate::transition::WalletChannel;
+
+/// Object store the backend keeps its entries in.
+const STORE: &str = "overpass";
+const SCHEMA_VERSION: u32 = 1;
+
+fn backend_error(error: JsValue) -> StorageError {
+    StorageError::Backend(format!("{:?}", error))
+}
+
+/// Storage backed by the browser's IndexedDB, in one object store keyed by string.
+pub struct IndexedDbBackend {
+    db: IdbDatabase,
+}
+
+impl IndexedDbBackend {
+    /// Opens the database `name`, creating its object store on first use.
+    pub async fn open(name: &str) -> Result<Self, StorageError> {
+        let factory = web_sys::window()
+            .ok_or_else(|| StorageError::Backend("No window found".into()))?
+            .indexed_db()
+            .map_err(backend_error)?
+            .ok_or_else(|| StorageError::Backend("No IndexedDB found".into()))?;
+        let request = factory
+            .open_with_u32(name, SCHEMA_VERSION)
+            .map_err(backend_error)?;
+        let upgrade = Closure::<dyn FnMut(Event)>::new(|event: Event| {
+            let db = event
+                .target()
+                .and_then(|target| target.dyn_into::<IdbOpenDbRequest>().ok())
+                .and_then(|request| request.result().ok())
+                .and_then(|result| result.dyn_into::<IdbDatabase>().ok());
+            if let Some(db) = db.filter(|db| !db.object_store_names().contains(STORE)) {
+                let _ = db.create_object_store(STORE);
+            }
+        });
+        request.set_onupgradeneeded(Some(upgrade.as_ref().unchecked_ref()));
+        let db = settle(&request).await?;
+        request.set_onupgradeneeded(None);
+        Ok(Self {
+            db: db
+                .dyn_into()
+                .map_err(|_| StorageError::Backend("Open did not yield a database".into()))?,
+        })
+    }
+
+    fn store(&self, mode: IdbTransactionMode) -> Result<IdbObjectStore, StorageError> {
+        self.db
+            .transaction_with_str_and_mode(STORE, mode)
+            .and_then(|transaction| transaction.object_store(STORE))
+            .map_err(backend_error)
+    }
+}
+
+/// Waits for an IndexedDB request to succeed, with its result, or to fail.
+async fn settle(request: &IdbRequest) -> Result<JsValue, StorageError> {
+    let promise = js_sys::Promise::new(&mut |resolve, reject| {
+        let succeeded = request.clone();
+        let on_success = Closure::once_into_js(move |_: Event| {
+            let result = succeeded.result().unwrap_or(JsValue::UNDEFINED);
+            let _ = resolve.call1(&JsValue::NULL, &result);
+        });
+        let failed = request.clone();
+        let on_error = Closure::once_into_js(move |_: Event| {
+            let error = failed.error().ok().flatten().map(JsValue::from);
+            let _ = reject.call1(&JsValue::NULL, &error.unwrap_or(JsValue::UNDEFINED));
+        });
+        request.set_onsuccess(Some(on_success.unchecked_ref()));
+        request.set_onerror(Some(on_error.unchecked_ref()));
+    });
+    JsFuture::from(promise).await.map_err(backend_error)
+}
+
+impl StorageBackend for IndexedDbBackend {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, StorageError> {
+        let request = self
+            .store(IdbTransactionMode::Readonly)?
+            .get(&JsValue::from_str(key))
+            .map_err(backend_error)?;
+        let value = settle(&request).await?;
+        if value.is_undefined() {
+            return Ok(None);
+        }
+        Ok(Some(js_sys::Uint8Array::new(&value).to_vec()))
+    }
+
+    async fn put(&self, key: &str, value: &[u8]) -> Result<(), StorageError> {
+        let request = self
+            .store(IdbTransactionMode::Readwrite)?
+            .put_with_key(&js_sys::Uint8Array::from(value), &JsValue::from_str(key))
+            .map_err(backend_error)?;
+        settle(&request).await.map(|_| ())
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        let request = self
+            .store(IdbTransactionMode::Readwrite)?
+            .delete(&JsValue::from_str(key))
+            .map_err(backend_error)?;
+        settle(&request).await.map(|_| ())
+    }
+
+    async fn keys(&self, prefix: &str) -> Result<Vec<String>, StorageError> {
+        let request = self
+            .store(IdbTransactionMode::Readonly)?
+            .get_all_keys()
+            .map_err(backend_error)?;
+        let keys = js_sys::Array::from(&settle(&request).await?);
+        let mut keys: Vec<String> = keys
+            .iter()
+            .filter_map(|key| key.as_string())
+            .filter(|key| key.starts_with(prefix))
+            .collect();
+        keys.sort();
+        Ok(keys)
+    }
+}
+
+fn to_js(error: StorageError) -> JsValue {
+    JsValue::from_str(&error.to_string())
+}
+
+/// Wallet channels saved in IndexedDB. Every call returns a promise.
+#[wasm_bindgen]
+pub struct ChannelDb(Rc<ChannelStore<IndexedDbBackend>>);
+
+#[wasm_bindgen]
+impl ChannelDb {
+    /// Opens the database `name`, resolving to a `ChannelDb`.
+    pub fn open(name: String) -> js_sys::Promise {
+        future_to_promise(async move {
+            let backend = IndexedDbBackend::open(&name).await.map_err(to_js)?;
+            Ok(ChannelDb(Rc::new(ChannelStore::new(backend))).into())
+        })
+    }
+
+    #[wasm_bindgen(js_name = saveChannel)]
+    pub fn save_channel(&self, channel: &WalletChannel) -> js_sys::Promise {
+        let (store, state) = (self.0.clone(), channel.state().clone());
+        future_to_promise(async move {
+            store.save(&state).await.map_err(to_js)?;
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+
+    /// Resolves to the saved `WalletChannel`, or `null` if there is none.
+    #[wasm_bindgen(js_name = loadChannel)]
+    pub fn load_channel(&self, channel_id: &[u8]) -> js_sys::Promise {
+        let (store, channel_id) = (self.0.clone(), bytes32(channel_id, "Channel id"));
+        future_to_promise(async move {
+            let channel_id = channel_id.map_err(|e| JsValue::from_str(&e))?;
+            Ok(match store.load(&channel_id).await.map_err(to_js)? {
+                Some(state) => WalletChannel::from(state).into(),
+                None => JsValue::NULL,
+            })
+        })
+    }
+
+    #[wasm_bindgen(js_name = removeChannel)]
+    pub fn remove_channel(&self, channel_id: &[u8]) -> js_sys::Promise {
+        let (store, channel_id) = (self.0.clone(), bytes32(channel_id, "Channel id"));
+        future_to_promise(async move {
+            let channel_id = channel_id.map_err(|e| JsValue::from_str(&e))?;
+            store.remove(&channel_id).await.map_err(to_js)?;
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+
+    /// Resolves to every saved `WalletChannel`, ordered by channel id.
+    pub fn channels(&self) -> js_sys::Promise {
+        let store = self.0.clone();
+        future_to_promise(async move {
+            let channels = store.channels().await.map_err(to_js)?;
+            Ok(channels
+                .into_iter()
+                .map(|state| JsValue::from(WalletChannel::from(state)))
+                .collect::<js_sys::Array>()
+                .into())
+        })
+    }
+}
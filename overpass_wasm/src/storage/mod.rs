@@ -1,5 +1,8 @@
 // File: overpass_wasm/src/storage/mod.rs
 
+pub mod backend;
+pub mod indexed_db;
+
 use wasm_bindgen::prelude::*;
 use web_sys::{Storage, Window};
 
@@ -25,9 +28,12 @@ impl ClientStorage {
 
     #[wasm_bindgen(js_name = saveState)]
     pub fn save_state(&self, channel_id: &str, state: &JsValue) -> Result<(), JsValue> {
+        let state = state
+            .as_string()
+            .ok_or_else(|| JsValue::from_str("Channel state must be a string"))?;
         // Store in localStorage
         self.storage
-            .set_item(channel_id, &state.as_string().unwrap_or_default())
+            .set_item(channel_id, &state)
             .map_err(|e| JsValue::from(format!("{:?}", e)))?;
 
         Ok(())
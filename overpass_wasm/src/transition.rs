@@ -0,0 +1,276 @@
+// src/transition.rs
+
+use overpass_verify::proof::{proof_binds, transition_hash};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use wasm_bindgen::prelude::*;
+
+use crate::commitment::{bytes32, pedersen_commit, Bytes32};
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum TransitionError {
+    #[error("Transition is for another channel")]
+    WrongChannel,
+    #[error("Transition has nonce {found}, expected {expected}")]
+    StaleNonce { expected: u64, found: u64 },
+    #[error("Transition does not start from the channel's commitment")]
+    WrongStart,
+    #[error("Transition does not commit to the given balance")]
+    CommitmentMismatch,
+    #[error("Transition proof does not bind its commitments")]
+    InvalidProof,
+}
+
+/// A state transition proof, in the shape `overpass_core` serializes it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateProof {
+    pub pi: Bytes32,
+    /// The old commitment, the new commitment and the wallet root before the move.
+    pub public_inputs: Vec<Bytes32>,
+    pub timestamp: u64,
+}
+
+impl StateProof {
+    pub fn prove(old: Bytes32, new: Bytes32, wallet_root: Bytes32, timestamp: u64) -> Self {
+        Self {
+            pi: transition_hash(&old, &new, &wallet_root, timestamp),
+            public_inputs: vec![old, new, wallet_root],
+            timestamp,
+        }
+    }
+
+    /// Checks the proof is over the move from `old` to `new`.
+    pub fn binds(&self, old: &Bytes32, new: &Bytes32) -> bool {
+        proof_binds(&self.pi, &self.public_inputs, self.timestamp, old, new)
+    }
+}
+
+/// A channel as one party's wallet holds it: its own balance behind a commitment.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChannelState {
+    pub channel_id: Bytes32,
+    pub balance: u64,
+    pub nonce: u64,
+    pub commitment: Bytes32,
+}
+
+/// A channel's move to its next state, for the counterparty or the operator to check.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Transition {
+    pub channel_id: Bytes32,
+    pub nonce: u64,
+    pub old_commitment: Bytes32,
+    pub new_commitment: Bytes32,
+    pub proof: StateProof,
+}
+
+impl Transition {
+    pub fn verify(&self) -> bool {
+        self.proof.binds(&self.old_commitment, &self.new_commitment)
+    }
+}
+
+impl ChannelState {
+    pub fn open(channel_id: Bytes32, balance: u64, blinding: &Bytes32) -> Self {
+        Self {
+            channel_id,
+            balance,
+            nonce: 0,
+            commitment: pedersen_commit(balance, blinding),
+        }
+    }
+
+    /// Builds the move to `balance` under a fresh `blinding`, proven against the wallet
+    /// root the move starts from.
+    pub fn transition(
+        &self,
+        balance: u64,
+        blinding: &Bytes32,
+        wallet_root: Bytes32,
+        timestamp: u64,
+    ) -> Transition {
+        let new_commitment = pedersen_commit(balance, blinding);
+        Transition {
+            channel_id: self.channel_id,
+            nonce: self.nonce + 1,
+            old_commitment: self.commitment,
+            new_commitment,
+            proof: StateProof::prove(self.commitment, new_commitment, wallet_root, timestamp),
+        }
+    }
+
+    /// Moves the channel to `balance` once `transition` is checked to be the next state
+    /// and to commit to it.
+    pub fn apply(
+        &mut self,
+        transition: &Transition,
+        balance: u64,
+        blinding: &Bytes32,
+    ) -> Result<(), TransitionError> {
+        if transition.channel_id != self.channel_id {
+            return Err(TransitionError::WrongChannel);
+        }
+        if transition.nonce != self.nonce + 1 {
+            return Err(TransitionError::StaleNonce {
+                expected: self.nonce + 1,
+                found: transition.nonce,
+            });
+        }
+        if transition.old_commitment != self.commitment {
+            return Err(TransitionError::WrongStart);
+        }
+        if transition.new_commitment != pedersen_commit(balance, blinding) {
+            return Err(TransitionError::CommitmentMismatch);
+        }
+        if !transition.verify() {
+            return Err(TransitionError::InvalidProof);
+        }
+        self.balance = balance;
+        self.nonce = transition.nonce;
+        self.commitment = transition.new_commitment;
+        Ok(())
+    }
+}
+
+fn to_js(error: impl ToString) -> JsValue {
+    JsValue::from_str(&error.to_string())
+}
+
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct WalletChannel(ChannelState);
+
+impl WalletChannel {
+    pub fn state(&self) -> &ChannelState {
+        &self.0
+    }
+}
+
+impl From<ChannelState> for WalletChannel {
+    fn from(state: ChannelState) -> Self {
+        Self(state)
+    }
+}
+
+#[wasm_bindgen]
+impl WalletChannel {
+    /// Opens a channel holding `balance`, committed under `blinding`.
+    #[wasm_bindgen(constructor)]
+    pub fn new(channel_id: &[u8], balance: u64, blinding: &[u8]) -> Result<WalletChannel, JsValue> {
+        Ok(Self(ChannelState::open(
+            bytes32(channel_id, "Channel id").map_err(to_js)?,
+            balance,
+            &bytes32(blinding, "Blinding").map_err(to_js)?,
+        )))
+    }
+
+    #[wasm_bindgen(getter, js_name = channelId)]
+    pub fn channel_id(&self) -> js_sys::Uint8Array {
+        js_sys::Uint8Array::from(&self.0.channel_id[..])
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn balance(&self) -> u64 {
+        self.0.balance
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn nonce(&self) -> u64 {
+        self.0.nonce
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn commitment(&self) -> js_sys::Uint8Array {
+        js_sys::Uint8Array::from(&self.0.commitment[..])
+    }
+
+    /// Builds the move to `balance`, returned as a plain transition object.
+    #[wasm_bindgen(js_name = buildTransition)]
+    pub fn build_transition(
+        &self,
+        balance: u64,
+        blinding: &[u8],
+        wallet_root: &[u8],
+        timestamp: u64,
+    ) -> Result<JsValue, JsValue> {
+        let transition = self.0.transition(
+            balance,
+            &bytes32(blinding, "Blinding").map_err(to_js)?,
+            bytes32(wallet_root, "Wallet root").map_err(to_js)?,
+            timestamp,
+        );
+        serde_wasm_bindgen::to_value(&transition).map_err(to_js)
+    }
+
+    #[wasm_bindgen(js_name = applyTransition)]
+    pub fn apply_transition(
+        &mut self,
+        transition: JsValue,
+        balance: u64,
+        blinding: &[u8],
+    ) -> Result<(), JsValue> {
+        let transition: Transition = serde_wasm_bindgen::from_value(transition).map_err(to_js)?;
+        let blinding = bytes32(blinding, "Blinding").map_err(to_js)?;
+        self.0.apply(&transition, balance, &blinding).map_err(to_js)
+    }
+}
+
+/// Checks a transition object's proof against its commitments.
+#[wasm_bindgen(js_name = verifyTransition)]
+pub fn verify_transition(transition: JsValue) -> Result<bool, JsValue> {
+    let transition: Transition = serde_wasm_bindgen::from_value(transition).map_err(to_js)?;
+    Ok(transition.verify())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commitment::to_hex;
+
+    // Produced by `overpass_core::zkp::helpers` with the default parameters; the core
+    // wallet checks the same vectors.
+    const CORE_COMMITMENT: &str =
+        "c81065ff7cbc782cba44664ba13e490ece323abd030785a1b8c0c18be050aebd";
+    const CORE_PI: &str = "6853638facea72d0565427080e3084fc7bcde726abc7efdba96bdbfb83e096e3";
+
+    #[test]
+    fn test_commitments_match_the_core_wallet() {
+        let channel = ChannelState::open([1u8; 32], 100, &[7u8; 32]);
+        assert_eq!(to_hex(&channel.commitment), CORE_COMMITMENT);
+
+        let proof = StateProof::prove([1u8; 32], [2u8; 32], [3u8; 32], 1_700_000_000);
+        assert_eq!(to_hex(&proof.pi), CORE_PI);
+        assert!(proof.binds(&[1u8; 32], &[2u8; 32]));
+        assert!(!proof.binds(&[2u8; 32], &[1u8; 32]));
+    }
+
+    #[test]
+    fn test_transition_applies_once() {
+        let mut sender = ChannelState::open([1u8; 32], 100, &[7u8; 32]);
+        let mut mirror = sender.clone();
+        let transition = sender.transition(60, &[8u8; 32], [3u8; 32], 1_700_000_000);
+        assert!(transition.verify());
+
+        assert_eq!(
+            mirror.apply(&transition, 61, &[8u8; 32]),
+            Err(TransitionError::CommitmentMismatch)
+        );
+        let mut forged = transition.clone();
+        forged.proof.timestamp += 1;
+        assert_eq!(
+            mirror.apply(&forged, 60, &[8u8; 32]),
+            Err(TransitionError::InvalidProof)
+        );
+        mirror.apply(&transition, 60, &[8u8; 32]).unwrap();
+        sender.apply(&transition, 60, &[8u8; 32]).unwrap();
+        assert_eq!(mirror, sender);
+        assert_eq!((sender.nonce, sender.balance), (1, 60));
+        assert_eq!(
+            sender.apply(&transition, 60, &[8u8; 32]),
+            Err(TransitionError::StaleNonce {
+                expected: 2,
+                found: 1
+            })
+        );
+    }
+}
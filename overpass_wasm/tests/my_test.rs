@@ -1,5 +1,7 @@
 use wasm_bindgen_test::*;
-use overpass_wasm::{ChannelWrapper, StateUpdateWrapper};
+use overpass_wasm::{
+    ChannelState, ChannelStore, ChannelWrapper, IndexedDbBackend, StateUpdateWrapper,
+};
 
 wasm_bindgen_test_configure!(run_in_browser);
 
@@ -136,4 +138,35 @@ fn test_hash_consistency() {
         hash.to_vec(),
         "Empty channels should have identical hashes"
     );
-}
\ No newline at end of file
+}
+
+#[wasm_bindgen_test]
+async fn test_channels_persist_in_indexed_db() {
+    let store = ChannelStore::new(
+        IndexedDbBackend::open("overpass-test")
+            .await
+            .expect("IndexedDB should open"),
+    );
+    let mut channel = ChannelState::open([1u8; 32], 100, &[7u8; 32]);
+    let transition = channel.transition(60, &[8u8; 32], [3u8; 32], 1_700_000_000);
+    channel
+        .apply(&transition, 60, &[8u8; 32])
+        .expect("Transition should apply");
+    store.save(&channel).await.expect("Channel should save");
+
+    let reopened = ChannelStore::new(
+        IndexedDbBackend::open("overpass-test")
+            .await
+            .expect("IndexedDB should reopen"),
+    );
+    assert_eq!(
+        reopened.load(&[1u8; 32]).await.expect("Channel should load"),
+        Some(channel),
+        "Saved channel should survive reopening the database"
+    );
+    reopened
+        .remove(&[1u8; 32])
+        .await
+        .expect("Channel should be removed");
+    assert_eq!(reopened.channels().await.expect("Channels should list"), Vec::new());
+}